@@ -0,0 +1,68 @@
+// Throughput of the comparisons in every mode, on outputs equal to the
+// answer, differing only at the end and differing right away, of 1MB and
+// 100MB. The 100MB ones are left out with BENCH_QUICK=1.
+//
+//     cargo bench --bench compare [FILTER...]
+
+mod harness;
+
+use std::fs::File;
+
+use harness::Divergence;
+use secure_judger::compare::Comparison;
+
+const MB: u64 = 1_000_000;
+
+const MODES: [Comparison; 3] = [Comparison::Exact, Comparison::Tokens, Comparison::Float { tolerance: 1e-6 }];
+
+// Baselines on the reference machine, per size, mode and divergence
+const BASELINES: [(u64, &str, &str, &str); 18] = [
+    (MB, "exact", "equal", "2.42ms"),
+    (MB, "exact", "almost-equal", "5.96ms"),
+    (MB, "exact", "early-diverging", "4.7us"),
+    (MB, "tokens", "equal", "4.77ms"),
+    (MB, "tokens", "almost-equal", "4.70ms"),
+    (MB, "tokens", "early-diverging", "4.1us"),
+    (MB, "float", "equal", "3.73ms"),
+    (MB, "float", "almost-equal", "4.72ms"),
+    (MB, "float", "early-diverging", "5.4us"),
+    (100 * MB, "exact", "equal", "258.75ms"),
+    (100 * MB, "exact", "almost-equal", "660.72ms"),
+    (100 * MB, "exact", "early-diverging", "5.6us"),
+    (100 * MB, "tokens", "equal", "442.98ms"),
+    (100 * MB, "tokens", "almost-equal", "532.78ms"),
+    (100 * MB, "tokens", "early-diverging", "3.8us"),
+    (100 * MB, "float", "equal", "467.55ms"),
+    (100 * MB, "float", "almost-equal", "409.75ms"),
+    (100 * MB, "float", "early-diverging", "4.8us")
+];
+
+fn main() {
+    let quick = std::env::var_os("BENCH_QUICK").is_some_and(|value| value != "0");
+    let sizes: &[u64] = match quick {
+        true => &[MB],
+        false => &[MB, 100 * MB]
+    };
+    for &size in sizes {
+        for divergence in [Divergence::Equal, Divergence::AtEnd, Divergence::AtStart] {
+            let (answer, output) = harness::data_pair(size, divergence);
+            for mode in MODES {
+                // The float mode's tolerance has no place in the name
+                let mode_name = mode.to_string().split(':').next().unwrap_or_default().to_string();
+                let name = format!("compare/{}MB/{mode_name}/{}", size / MB, divergence.name());
+                let baseline = BASELINES.iter()
+                    .find(|(s, m, d, _)| *s == size && *m == mode_name && *d == divergence.name())
+                    .map_or("none", |(_, _, _, baseline)| baseline);
+                // Throughput says nothing of a comparison stopping at the first byte
+                let bytes = match divergence {
+                    Divergence::AtStart => None,
+                    _ => Some(size)
+                };
+                harness::bench(&name, bytes, baseline, || {
+                    let status = mode.compare(File::open(&answer).unwrap(), File::open(&output).unwrap()).unwrap();
+                    std::hint::black_box(status);
+                });
+            }
+        }
+    }
+}
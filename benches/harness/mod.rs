@@ -0,0 +1,142 @@
+// A small timing harness for the benchmarks, and the files they work on,
+// generated instead of checked in. Each benchmark is run for a time budget
+// and reported as its mean, with the baseline of the reference machine
+// (a single vCPU of a Xeon VM, Linux 6.18, bench profile) beside it.
+// Each benchmark uses only part of it.
+#![allow(dead_code)]
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// How long each benchmark runs for at most, past its first iteration
+const DEFAULT_BUDGET: Duration = Duration::from_secs(1);
+
+/// How the output differs from the answer
+#[derive(Clone, Copy, Debug)]
+pub enum Divergence {
+    /// Not at all
+    Equal,
+    /// In the last number only
+    AtEnd,
+    /// Right in the first byte
+    AtStart
+}
+
+impl Divergence {
+    // Names the benchmark and the file of the output
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Equal => "equal",
+            Self::AtEnd => "almost-equal",
+            Self::AtStart => "early-diverging"
+        }
+    }
+}
+
+/*
+ *  The time budget of every benchmark, BENCH_SECONDS if set
+ */
+pub fn budget() -> Duration {
+    std::env::var("BENCH_SECONDS")
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|s| *s > 0.0)
+        .map_or(DEFAULT_BUDGET, Duration::from_secs_f64)
+}
+
+/*
+ *  Whether the benchmarks named by the arguments include `name`: all of
+ *  them without arguments, else the ones containing an argument. Flags
+ *  such as cargo's --bench are skipped.
+ */
+pub fn selected(name: &str) -> bool {
+    let filters: Vec<String> = std::env::args().skip(1).filter(|arg| !arg.starts_with("--")).collect();
+    filters.is_empty() || filters.iter().any(|filter| name.contains(filter.as_str()))
+}
+
+/*
+ *  Run `f` until the budget is used up and print its mean time, with the
+ *  throughput over `bytes` if given and the baseline to compare with
+ */
+pub fn bench(name: &str, bytes: Option<u64>, baseline: &str, mut f: impl FnMut()) {
+    if !selected(name) {
+        return;
+    }
+    let budget = budget();
+    let begin = Instant::now();
+    let mut iterations: u32 = 0;
+    while iterations == 0 || begin.elapsed() < budget {
+        f();
+        iterations += 1;
+    }
+    let mean = begin.elapsed() / iterations;
+    let throughput = match bytes {
+        Some(bytes) => format!("{:.1} MB/s", bytes as f64 / mean.as_secs_f64() / 1e6),
+        None => String::new()
+    };
+    println!("{name:<40} {:>12} {throughput:>12}  x{iterations:<6} baseline {baseline}", format_time(mean));
+}
+
+fn format_time(time: Duration) -> String {
+    match time.as_micros() {
+        0..=999 => format!("{:.1}us", time.as_secs_f64() * 1e6),
+        1000..=999_999 => format!("{:.2}ms", time.as_secs_f64() * 1e3),
+        _ => format!("{:.2}s", time.as_secs_f64())
+    }
+}
+
+/*
+ *  Where the generated files go
+ */
+pub fn data_dir() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("bench-data");
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/*
+ *  An answer of about `size` bytes of numbers, two to a line, and an
+ *  output diverging from it as `divergence` says. Generated once, later
+ *  runs find them in place.
+ */
+pub fn data_pair(size: u64, divergence: Divergence) -> (PathBuf, PathBuf) {
+    let dir = data_dir();
+    let answer = dir.join(format!("{size}.ans"));
+    let output = dir.join(format!("{size}.{}.out", divergence.name()));
+    if !answer.exists() {
+        write_numbers(&answer, size, None);
+    }
+    if !output.exists() {
+        write_numbers(&output, size, Some(divergence));
+    }
+    (answer, output)
+}
+
+fn write_numbers(path: &Path, size: u64, divergence: Option<Divergence>) {
+    // Written under another name first, so that an interrupted run doesn't
+    // leave a short file behind to be taken as complete
+    let temp = path.with_extension("tmp");
+    let mut file = BufWriter::new(File::create(&temp).unwrap());
+    // Deterministic, so that the answer and the outputs agree
+    let mut x: u64 = 0x2545_f491;
+    let mut written = 0;
+    let mut first = true;
+    while written < size {
+        x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        let mut line = format!("{} {}.{:03}\n", x >> 44, (x >> 20) & 0xffff, x & 0x3ff);
+        written += line.len() as u64;
+        let last = written >= size;
+        match divergence {
+            Some(Divergence::AtStart) if first => line.replace_range(0..1, "x"),
+            Some(Divergence::AtEnd) if last => line.insert(line.len() - 1, '7'),
+            _ => {}
+        }
+        first = false;
+        file.write_all(line.as_bytes()).unwrap();
+    }
+    file.flush().unwrap();
+    drop(file);
+    fs::rename(&temp, path).unwrap();
+}
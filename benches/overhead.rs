@@ -0,0 +1,134 @@
+// What judging costs beyond the program itself: a run of /bin/true judged
+// a thousand times, a run of an executable evicted from the page cache with
+// and without prewarming it, and compiling the seccomp filters of the
+// policies.
+//
+//     cargo bench --bench overhead [FILTER...]
+
+mod harness;
+
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use secure_judger::judger::{JudgeSession, JudgeStatus};
+use secure_judger::sandbox::{self, SandboxPolicy, SandboxStrength};
+
+// How many runs the per-run overhead is the mean of
+const RUNS: u32 = 1000;
+
+// How many times the executable is evicted and run again
+const COLD_RUNS: u32 = 20;
+
+// Makes the benchmark exit as soon as it starts, for it to be judged
+const EXIT_AT_ONCE: &str = "--exit-at-once";
+
+fn main() {
+    if std::env::args().any(|arg| arg == EXIT_AT_ONCE) {
+        return;
+    }
+    judge_overhead();
+    cold_start();
+    if !cfg!(feature = "seccomp") {
+        return println!("filters/*                                skipped, built without the seccomp feature");
+    }
+    harness::bench("filters/default", None, "25.0us", || {
+        std::hint::black_box(sandbox::compile_filters(&SandboxPolicy::default()).unwrap());
+    });
+    harness::bench("filters/threads-allowed", None, "20.7us", || {
+        std::hint::black_box(sandbox::compile_filters(&SandboxPolicy::threads_allowed()).unwrap());
+    });
+}
+
+/*
+ *  Judge /bin/true RUNS times in one session, as a judge of many cases
+ *  would, and print the mean time of a run from start to verdict
+ */
+fn judge_overhead() {
+    const NAME: &str = "judge/true";
+    if !harness::selected(NAME) {
+        return;
+    }
+    let answer = harness::data_dir().join("empty.ans");
+    std::fs::write(&answer, "").unwrap();
+    let strength = match cfg!(feature = "seccomp") {
+        true => SandboxStrength::Require,
+        false => SandboxStrength::Disabled
+    };
+    let session = JudgeSession::builder(PathBuf::from("/bin/true"))
+        .answer(answer)
+        .time_limit(Duration::from_secs(1))
+        .sandbox_strength(strength)
+        .copy_exec(false)
+        .build()
+        .unwrap();
+    let begin = Instant::now();
+    for _ in 0..RUNS {
+        match session.run_judge(&[]) {
+            Ok(result) if matches!(result.status, JudgeStatus::Accepted) => {},
+            Ok(result) => return println!("{NAME:<40} skipped, /bin/true was judged {}", result.status),
+            Err(e) => return println!("{NAME:<40} skipped, the sandbox is unavailable: {e}")
+        }
+    }
+    let mean = begin.elapsed() / RUNS;
+    println!("{NAME:<40} {:>12} {:>12}  x{RUNS:<6} baseline 1.59ms", format!("{:.2}ms", mean.as_secs_f64() * 1e3), "");
+}
+
+/*
+ *  Drop the pages of `path` from the page cache, as if it were on a disk
+ *  nothing read it from in a while
+ */
+fn evict(path: &Path) {
+    let file = std::fs::File::open(path).unwrap();
+    assert_eq!(unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) }, 0);
+}
+
+/*
+ *  The time the benchmark's own executable, far larger than /bin/true,
+ *  takes to run once evicted: without prewarming, with it, and run again
+ *  while still cached. Prewarmed, the first run should take about as long
+ *  as the ones after it.
+ */
+fn cold_start() {
+    let exec = std::env::current_exe().unwrap();
+    let answer = harness::data_dir().join("empty.ans");
+    std::fs::write(&answer, "").unwrap();
+    let strength = match cfg!(feature = "seccomp") {
+        true => SandboxStrength::Require,
+        false => SandboxStrength::Disabled
+    };
+    let session = |prewarm: bool| {
+        JudgeSession::builder(exec.clone())
+            .answer(answer.clone())
+            .time_limit(Duration::from_secs(1))
+            .sandbox_strength(strength)
+            .copy_exec(false)
+            .prewarm(prewarm)
+            .build()
+            .unwrap()
+    };
+    let cases = [
+        ("judge/cold", false, true, "1.34ms"),
+        ("judge/cold-prewarmed", true, true, "0.90ms"),
+        ("judge/warm", false, false, "0.89ms")
+    ];
+    for (name, prewarm, cold, baseline) in cases {
+        if !harness::selected(name) {
+            continue;
+        }
+        let session = session(prewarm);
+        let mut total = Duration::ZERO;
+        for _ in 0..COLD_RUNS {
+            if cold {
+                evict(&exec);
+            }
+            match session.run_judge(&[EXIT_AT_ONCE]) {
+                Ok(result) if matches!(result.status, JudgeStatus::Accepted) => total += result.time_used,
+                Ok(result) => return println!("{name:<40} skipped, the benchmark was judged {}", result.status),
+                Err(e) => return println!("{name:<40} skipped, the sandbox is unavailable: {e}")
+            }
+        }
+        let mean = total / COLD_RUNS;
+        println!("{name:<40} {:>12} {:>12}  x{COLD_RUNS:<6} baseline {baseline}", format!("{:.2}ms", mean.as_secs_f64() * 1e3), "");
+    }
+}
@@ -0,0 +1,110 @@
+// What waiting for a run costs the judger: a thousand runs of /bin/true
+// started in the sandbox and waited for with SandboxChildHandle::wait, as
+// the judger once did by trying again and again without blocking,
+// sleeping 100us between tries, and as it does now, sleeping until the
+// pidfd of the child turns readable. Each prints the CPU time the waits
+// took the waiting thread for all of the runs, then the mean time from a
+// start to the reaping of its child.
+//
+//     cargo bench --bench wait [FILTER...]
+
+mod harness;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use secure_judger::sandbox::{
+    InputSource, LinuxSandbox, RunSpec, Sandbox, SandboxChildHandle, SandboxIo, SandboxPolicy, WaitOutcome
+};
+
+// How many runs are waited for with each way
+const RUNS: u32 = 1000;
+
+// How long the busy wait slept between its tries
+const BUSY_SLEEP: Duration = Duration::from_micros(100);
+
+fn main() {
+    wait("wait/busy", busy_wait);
+    wait("wait/pidfd-poll", pidfd_wait);
+}
+
+/*
+ *  Run /bin/true RUNS times, waiting for each with `wait`, and print what
+ *  the waits cost
+ */
+fn wait(name: &str, wait: fn(&mut dyn SandboxChildHandle) -> io::Result<WaitOutcome>) {
+    if !harness::selected(name) {
+        return;
+    }
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let out = dir.join("wait.out");
+    let err = dir.join("wait.err");
+    let policy = SandboxPolicy::default();
+    let sandbox = LinuxSandbox::default();
+    let mut cpu = Duration::ZERO;
+    let mut total = Duration::ZERO;
+    for _ in 0..RUNS {
+        // The sandbox makes its capture files anew for every run
+        let _ = fs::remove_file(&out);
+        let _ = fs::remove_file(&err);
+        let io = SandboxIo {
+            stdin: InputSource::File(PathBuf::from("/dev/null")),
+            stdout: &out,
+            stderr: &err,
+            scratch_dir: &dir,
+            named: None,
+            provided: &[]
+        };
+        let mut child = match sandbox.spawn(RunSpec::new(Path::new("/bin/true"), &["true"], io, &policy)) {
+            Ok(child) => child,
+            Err(e) => return println!("{name:<40} skipped, cannot run /bin/true: {e}")
+        };
+        let before = thread_cpu_time();
+        let waited = wait(child.as_mut());
+        cpu += thread_cpu_time() - before;
+        if let Err(e) = waited {
+            return println!("{name:<40} skipped, cannot wait for /bin/true: {e}");
+        }
+        total += child.start_instant().elapsed();
+    }
+    let _ = fs::remove_file(&out);
+    let _ = fs::remove_file(&err);
+    let mean = total / RUNS;
+    println!(
+        "{name:<40} {:>12} {:>12}  x{RUNS}",
+        format!("{:.2}ms", cpu.as_secs_f64() * 1e3),
+        format!("{:.2}ms", mean.as_secs_f64() * 1e3)
+    );
+}
+
+/*
+ *  The CPU time the calling thread used so far
+ */
+fn thread_cpu_time() -> Duration {
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    assert_eq!(unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) }, 0);
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/*
+ *  The wait before the pidfd: look whether the child exited without
+ *  blocking, sleeping a little between tries until it has
+ */
+fn busy_wait(child: &mut dyn SandboxChildHandle) -> io::Result<WaitOutcome> {
+    loop {
+        match child.wait(Some(Duration::ZERO))? {
+            WaitOutcome::Timeout => thread::sleep(BUSY_SLEEP),
+            outcome => return Ok(outcome)
+        }
+    }
+}
+
+/*
+ *  The wait of the judger: sleep until the child exits
+ */
+fn pidfd_wait(child: &mut dyn SandboxChildHandle) -> io::Result<WaitOutcome> {
+    child.wait(None)
+}
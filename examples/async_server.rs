@@ -0,0 +1,137 @@
+// A judging server whose runs all wait on one thread, as futures of
+// JudgeSession::run_judge_async polled by the small executor below. A
+// request is a line of "EXEC INPUT ANSWER", paths on the server, and the
+// reply the result's JSON on a line of its own, in the order the runs end.
+//
+//     cargo run --example async_server --features async -- 127.0.0.1:7070 1000
+//     printf '%s\n' './a.out 1.in 1.ans' './a.out 2.in 2.ans' | nc 127.0.0.1 7070
+//
+// The last argument is the time limit in milliseconds, 1000 if left out.
+// Any executor can poll the futures, tokio's as well as this one.
+
+use std::collections::VecDeque;
+use std::env;
+use std::error::Error;
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+use secure_judger::judger::JudgeSession;
+use secure_judger::sandbox::InputSource;
+use secure_judger::utils;
+
+type Task = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Tasks waiting to be polled for the first time, from the threads
+/// reading the connections
+struct Queue {
+    tasks: Mutex<VecDeque<Task>>,
+    executor: Thread
+}
+
+impl Queue {
+    fn push(&self, task: Task) {
+        self.tasks.lock().unwrap().push_back(task);
+        self.executor.unpark();
+    }
+}
+
+/// Wakes the executor, which then polls every task again
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(addr) = args.first() else {
+        eprintln!("usage: async_server ADDR [TIME_LIMIT_MS]");
+        return ExitCode::from(2);
+    };
+    let time_limit = match args.get(1).map(|ms| ms.parse()) {
+        None => Duration::from_secs(1),
+        Some(Ok(ms)) => Duration::from_millis(ms),
+        Some(Err(e)) => {
+            eprintln!("time limit {}: {e}", args[1]);
+            return ExitCode::from(2);
+        }
+    };
+    let listener = match TcpListener::bind(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("{addr}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let queue = Arc::new(Queue { tasks: Mutex::new(VecDeque::new()), executor: thread::current() });
+    let accepting = Arc::clone(&queue);
+    thread::spawn(move || {
+        for stream in listener.incoming().map_while(Result::ok) {
+            let queue = Arc::clone(&accepting);
+            thread::spawn(move || read_requests(stream, &queue, time_limit));
+        }
+    });
+    run(&queue)
+}
+
+/*
+ *  Poll the tasks until the process is killed, parking the thread while
+ *  none of them can go on
+ */
+fn run(queue: &Queue) -> ExitCode {
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut tasks: Vec<Task> = Vec::new();
+    loop {
+        tasks.extend(queue.tasks.lock().unwrap().drain(..));
+        tasks.retain_mut(|task| task.as_mut().poll(&mut cx).is_pending());
+        thread::park();
+    }
+}
+
+/*
+ *  Queue a task for every request on the connection, each writing its
+ *  reply once the run is judged
+ */
+fn read_requests(stream: TcpStream, queue: &Queue, time_limit: Duration) {
+    let Ok(writer) = stream.try_clone() else {
+        return;
+    };
+    let writer = Arc::new(Mutex::new(writer));
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        let request: Vec<String> = line.split_whitespace().map(String::from).collect();
+        let writer = Arc::clone(&writer);
+        queue.push(Box::pin(async move {
+            let reply = match judge(&request, time_limit).await {
+                Ok(json) => json,
+                Err(e) => utils::json_object(&[("error", utils::json_string(&e.to_string()))])
+            };
+            let _ = writeln!(writer.lock().unwrap(), "{reply}");
+        }));
+    }
+}
+
+/*
+ *  Judge the run a request asks for, the result as JSON
+ */
+async fn judge(request: &[String], time_limit: Duration) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let [exec, input, answer] = request else {
+        return Err("a request is EXEC INPUT ANSWER".into());
+    };
+    let session = JudgeSession::builder(PathBuf::from(exec))
+        .input(InputSource::File(PathBuf::from(input)))
+        .answer(PathBuf::from(answer))
+        .time_limit(time_limit)
+        .build()?;
+    Ok(session.run_judge_async(&[]).await?.to_json())
+}
@@ -0,0 +1,52 @@
+// A client of `secure-judger daemon`: sends its requests, a line of JSON
+// each, and prints the replies as they come until the daemon has answered
+// all of them.
+//
+//     cargo run --example daemon_client -- /run/judger.sock \
+//         '{"id": 1, "exec": "/sub/a.out", "input": "/t/1.in", "answer": "/t/1.ans"}'
+//
+// Without requests as arguments they are read from stdin, a line each.
+// '{"cmd": "shutdown"}' asks the daemon to exit once it judged what it has.
+
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let Some(socket) = args.next() else {
+        eprintln!("usage: daemon_client SOCKET [REQUEST...]");
+        return ExitCode::from(2);
+    };
+    let mut requests: Vec<String> = args.collect();
+    if requests.is_empty() {
+        requests = io::stdin().lock().lines().map_while(Result::ok).filter(|line| !line.trim().is_empty()).collect();
+    }
+    match talk(&socket, &requests) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{socket}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/*
+ *  Send every request, then read replies until the daemon closes the
+ *  connection, which it does once the last result is written
+ */
+fn talk(socket: &str, requests: &[String]) -> io::Result<()> {
+    let mut stream = UnixStream::connect(socket)?;
+    for request in requests {
+        stream.write_all(request.trim().as_bytes())?;
+        stream.write_all(b"\n")?;
+    }
+    // Nothing more is coming, which lets the daemon close its side when done
+    stream.shutdown(Shutdown::Write)?;
+    for line in BufReader::new(stream).lines() {
+        println!("{}", line?);
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+// A client of `secure-judger serve-grpc`: sends the program, input and
+// answer inline and prints the events of the judging as they come.
+//
+//     cargo run --example grpc_client --features grpc -- 127.0.0.1:50051 \
+//         ./a.out 1.in 1.ans
+//
+// With --paths the files are named instead of sent, for a server given a
+// --root they are under.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use secure_judger::grpc::{Client, JudgeEvent, JudgeRequest, Source};
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let paths = args.iter().position(|arg| arg == "--paths").map(|i| args.remove(i)).is_some();
+    let [address, exec, input, answer] = &args[..] else {
+        eprintln!("usage: grpc_client [--paths] ADDRESS EXEC INPUT ANSWER");
+        return ExitCode::from(2);
+    };
+    let source = |file: &str| match paths {
+        true => Ok(Source::Path(PathBuf::from(file))),
+        false => fs::read(file).map(Source::Inline).map_err(|e| format!("{file}: {e}"))
+    };
+    let request = match (source(exec), source(input), source(answer)) {
+        (Ok(exec), Ok(input), Ok(answer)) => JudgeRequest {
+            exec: Some(exec),
+            input: Some(input),
+            answer: Some(answer),
+            ..Default::default()
+        },
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut client = match Client::connect(address.as_str()) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{address}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let events = match client.judge_events(&request) {
+        Ok(events) => events,
+        Err(status) => {
+            eprintln!("{status}");
+            return ExitCode::FAILURE;
+        }
+    };
+    for event in events {
+        match event {
+            Ok(JudgeEvent::Started) => println!("started"),
+            Ok(JudgeEvent::Tick { elapsed_ms, memory_bytes }) => println!("running {elapsed_ms}ms, {memory_bytes} bytes"),
+            Ok(JudgeEvent::Finished(response)) => println!("{} in {}ms", response.detail, response.time_ms),
+            Err(status) => {
+                eprintln!("{status}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
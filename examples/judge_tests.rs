@@ -0,0 +1,61 @@
+// Judges a program on the tests of a directory with the library instead
+// of the secure-judger binary, printing the verdict of every test and the
+// submission's.
+//
+//     cargo run --example judge_tests -- ./a.out tests/ 1000
+//
+// The tests are NAME.in with NAME.ans or NAME.out, the last argument is
+// the time limit in milliseconds, 1000 if left out.
+
+use std::env;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use secure_judger::judger::JudgeSession;
+use secure_judger::problem::{self, ProblemJudge, TestLayout};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (exec, tests) = match args.as_slice() {
+        [exec, tests] | [exec, tests, _] => (exec, tests),
+        _ => {
+            eprintln!("usage: judge_tests EXECUTABLE TESTS_DIR [TIME_LIMIT_MS]");
+            return ExitCode::from(2);
+        }
+    };
+    let time_limit = match args.get(2).map(|ms| ms.parse()) {
+        None => Duration::from_secs(1),
+        Some(Ok(ms)) => Duration::from_millis(ms),
+        Some(Err(e)) => {
+            eprintln!("time limit: {e}");
+            return ExitCode::from(2);
+        }
+    };
+    match judge(exec, tests, time_limit) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/*
+ *  Judge `exec` on every test in `tests`, whether all were accepted
+ */
+fn judge(exec: &str, tests: &str, time_limit: Duration) -> Result<bool, Box<dyn Error>> {
+    let cases = problem::test_cases_in(tests.as_ref(), &TestLayout::default())?;
+    let session = JudgeSession::builder(PathBuf::from(exec))
+        .time_limit(time_limit)
+        .build()?;
+    let judge = ProblemJudge::new(session, cases);
+    let submission = judge.run(&[exec])?;
+    for (case, result) in judge.cases().iter().zip(&submission.results) {
+        println!("{}: {} in {}ms", case.name(), result.status.abbr(), result.time_used.as_millis());
+    }
+    println!("{}", submission.status);
+    Ok(submission.accepted())
+}
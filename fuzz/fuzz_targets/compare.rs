@@ -0,0 +1,10 @@
+// An answer and an output, split at the first 0xff byte, compared in
+// every mode
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let (answer, output) = secure_judger::fuzz::split(data);
+    secure_judger::fuzz::comparisons(answer, output);
+});
@@ -0,0 +1,8 @@
+// Arbitrary text read as a problem file and as JSON
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    secure_judger::fuzz::config(data);
+});
@@ -0,0 +1,149 @@
+/* The C API of secure-judger, built with the ffi feature. Regenerate with cbindgen from src/ffi.rs, see cbindgen.toml. */
+
+#ifndef SECURE_JUDGER_H
+#define SECURE_JUDGER_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Success
+#define SJ_OK 0
+
+// A pointer that must not be null was
+#define SJ_ERR_NULL -1
+
+// A string wasn't UTF-8
+#define SJ_ERR_UTF8 -2
+
+// A value out of range, a name not known, or a session that doesn't
+// check out
+#define SJ_ERR_INVALID -3
+
+// Judging failed, see sj_last_error
+#define SJ_ERR_RUN -4
+
+// The judger panicked, see sj_last_error
+#define SJ_ERR_PANIC -5
+
+// Accepted
+#define SJ_VERDICT_AC 0
+
+// Wrong Answer
+#define SJ_VERDICT_WA 1
+
+// Time Limit Exceeded
+#define SJ_VERDICT_TLE 2
+
+// Memory Limit Exceeded
+#define SJ_VERDICT_MLE 3
+
+// Output Limit Exceeded
+#define SJ_VERDICT_OLE 4
+
+// Runtime Error, killed by a signal
+#define SJ_VERDICT_RE 5
+
+// Idleness Limit Exceeded
+#define SJ_VERDICT_ILE 6
+
+// Security Violation
+#define SJ_VERDICT_SV 7
+
+// Presentation Error
+#define SJ_VERDICT_PE 8
+
+// Output Missing
+#define SJ_VERDICT_OM 9
+
+// Return Value Not Zero
+#define SJ_VERDICT_RNZ 10
+
+// System Error
+#define SJ_VERDICT_SE 11
+
+// Cancelled
+#define SJ_VERDICT_CAN 12
+
+// Skipped
+#define SJ_VERDICT_SKIP 13
+
+// The outcome of sj_run
+typedef struct sj_result sj_result;
+
+// What a session is made of, built into a JudgeSession by every sj_run
+typedef struct sj_session sj_session;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Make a session judging the executable at `exec`, into `*session_out`.
+// Without an answer set, every run is a System Error.
+int32_t sj_session_new(const char *exec, size_t exec_len, sj_session **session_out);
+
+// Pass `arg` to the program after those added before
+int32_t sj_session_add_arg(sj_session *session, const char *arg, size_t arg_len);
+
+// Feed the program the file at `path` on stdin
+int32_t sj_session_set_input(sj_session *session, const char *path, size_t path_len);
+
+// Compare the program's output with the file at `path`
+int32_t sj_session_set_answer(sj_session *session, const char *path, size_t path_len);
+
+// Limit the program to `ms` milliseconds of CPU time
+int32_t sj_session_set_time_limit_ms(sj_session *session, uint64_t ms);
+
+// Limit the program to `bytes` of memory
+int32_t sj_session_set_memory_limit(sj_session *session, uint64_t bytes);
+
+// Limit what the program writes to `bytes`
+int32_t sj_session_set_output_limit(sj_session *session, uint64_t bytes);
+
+// Compare in `mode`: exact, tokens, float or float:TOLERANCE
+int32_t sj_session_set_mode(sj_session *session, const char *mode, size_t mode_len);
+
+// Sandbox the program as `strength` says: require, best-effort or
+// disabled
+int32_t sj_session_set_sandbox(sj_session *session, const char *strength, size_t strength_len);
+
+// Judge the program once, into `*result_out`. Every verdict is SJ_OK,
+// the program's failures included; SJ_ERR_INVALID if the session doesn't
+// check out and SJ_ERR_RUN if the judger couldn't run it.
+int32_t sj_run(sj_session *session, sj_result **result_out);
+
+// The verdict, one of the SJ_VERDICT_* codes, or SJ_ERR_NULL
+int32_t sj_result_verdict(const sj_result *result);
+
+// Wall time the program ran for in milliseconds, 0 for a null result
+uint64_t sj_result_time_ms(const sj_result *result);
+
+// CPU time the program used in milliseconds, 0 for a null result
+uint64_t sj_result_cpu_time_ms(const sj_result *result);
+
+// Peak resident memory of the program in bytes, 0 for a null result
+uint64_t sj_result_memory_bytes(const sj_result *result);
+
+// The verdict in words, such as "[RE] Runtime Error (SIGSEGV)", with its
+// length in bytes in `*len` if that isn't null. NUL-terminated, and
+// valid until the result is freed.
+const char *sj_result_detail(const sj_result *result, size_t *len);
+
+// Why the last call on this thread failed, with its length in `*len` if
+// that isn't null. NUL-terminated, and valid until the next failure on
+// the thread.
+const char *sj_last_error(size_t *len);
+
+// Free a session from sj_session_new, doing nothing for null
+void sj_session_free(sj_session *session);
+
+// Free a result from sj_run, doing nothing for null
+void sj_result_free(sj_result *result);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SECURE_JUDGER_H */
@@ -0,0 +1,209 @@
+"""Judging programs from Python, through the C API of the secure-judger
+library (include/secure_judger.h).
+
+    from secure_judger import JudgeSession
+
+    session = JudgeSession("./a.out", input="1.in", answer="1.ans", time_limit_ms=1000)
+    result = session.run()
+    print(result["abbr"], result["time_ms"])
+
+The library is built with the python feature and found next to this file,
+or wherever SECURE_JUDGER_LIB points:
+
+    cargo build --release --features python
+    cp target/release/libsecure_judger.so python/secure_judger/
+    pip install ./python
+
+Every call into the library releases the GIL, so several Python threads
+judge at once; judge_many does that with a thread pool.
+"""
+
+import ctypes
+import os
+from concurrent.futures import ThreadPoolExecutor
+
+__all__ = ["JudgeError", "JudgeSession", "judge", "judge_many", "VERDICTS"]
+
+# Codes of the C API's SJ_* constants
+_OK = 0
+_ERRORS = {
+    -1: "null pointer",
+    -2: "not UTF-8",
+    -3: "invalid",
+    -4: "judging failed",
+    -5: "panic"
+}
+
+# SJ_VERDICT_* codes, as the abbreviation and the name of the verdict
+VERDICTS = {
+    0: ("AC", "Accepted"),
+    1: ("WA", "Wrong Answer"),
+    2: ("TLE", "Time Limit Exceeded"),
+    3: ("MLE", "Memory Limit Exceeded"),
+    4: ("OLE", "Output Limit Exceeded"),
+    5: ("RE", "Runtime Error"),
+    6: ("ILE", "Idleness Limit Exceeded"),
+    7: ("SV", "Security Violation"),
+    8: ("PE", "Presentation Error"),
+    9: ("OM", "Output Missing"),
+    10: ("RNZ", "Return Value Not Zero"),
+    11: ("SE", "System Error"),
+    12: ("CAN", "Cancelled"),
+    13: ("SKIP", "Skipped")
+}
+
+
+class JudgeError(Exception):
+    """A call into the library failed; code is its SJ_ERR_* code"""
+
+    def __init__(self, code, message):
+        super().__init__(f"{message} ({_ERRORS.get(code, code)})")
+        self.code = code
+
+
+def _load():
+    path = os.environ.get("SECURE_JUDGER_LIB") or os.path.join(os.path.dirname(__file__), "libsecure_judger.so")
+    lib = ctypes.CDLL(path)
+    session_p = ctypes.c_void_p
+    result_p = ctypes.c_void_p
+    string = [ctypes.c_char_p, ctypes.c_size_t]
+    signatures = {
+        "sj_session_new": (ctypes.c_int32, string + [ctypes.POINTER(session_p)]),
+        "sj_session_add_arg": (ctypes.c_int32, [session_p] + string),
+        "sj_session_set_input": (ctypes.c_int32, [session_p] + string),
+        "sj_session_set_answer": (ctypes.c_int32, [session_p] + string),
+        "sj_session_set_time_limit_ms": (ctypes.c_int32, [session_p, ctypes.c_uint64]),
+        "sj_session_set_memory_limit": (ctypes.c_int32, [session_p, ctypes.c_uint64]),
+        "sj_session_set_output_limit": (ctypes.c_int32, [session_p, ctypes.c_uint64]),
+        "sj_session_set_mode": (ctypes.c_int32, [session_p] + string),
+        "sj_session_set_sandbox": (ctypes.c_int32, [session_p] + string),
+        "sj_run": (ctypes.c_int32, [session_p, ctypes.POINTER(result_p)]),
+        "sj_result_verdict": (ctypes.c_int32, [result_p]),
+        "sj_result_time_ms": (ctypes.c_uint64, [result_p]),
+        "sj_result_cpu_time_ms": (ctypes.c_uint64, [result_p]),
+        "sj_result_memory_bytes": (ctypes.c_uint64, [result_p]),
+        "sj_result_detail": (ctypes.c_void_p, [result_p, ctypes.POINTER(ctypes.c_size_t)]),
+        "sj_last_error": (ctypes.c_void_p, [ctypes.POINTER(ctypes.c_size_t)]),
+        "sj_session_free": (None, [session_p]),
+        "sj_result_free": (None, [result_p])
+    }
+    for name, (restype, argtypes) in signatures.items():
+        function = getattr(lib, name)
+        function.restype = restype
+        function.argtypes = argtypes
+    return lib
+
+
+_lib = None
+
+
+def _library():
+    # Loaded on first use, so that importing works without the library
+    global _lib
+    if _lib is None:
+        _lib = _load()
+    return _lib
+
+
+def _text(pointer, length):
+    return ctypes.string_at(pointer, length).decode("utf-8")
+
+
+def _check(code):
+    if code != _OK:
+        length = ctypes.c_size_t()
+        pointer = _library().sj_last_error(ctypes.byref(length))
+        raise JudgeError(code, _text(pointer, length.value))
+
+
+def _string(value):
+    data = os.fsencode(value) if isinstance(value, os.PathLike) else str(value).encode("utf-8")
+    return data, len(data)
+
+
+class JudgeSession:
+    """A program judged under limits against an answer, as the library's
+    JudgeSessionBuilder sets it up. Limits left out keep the library's
+    defaults."""
+
+    def __init__(self, exec, *, input=None, answer=None, time_limit_ms=None, memory_limit=None,
+                 output_limit=None, comparison=None, sandbox=None, args=()):
+        lib = _library()
+        self._session = ctypes.c_void_p()
+        _check(lib.sj_session_new(*_string(exec), ctypes.byref(self._session)))
+        try:
+            for arg in args:
+                _check(lib.sj_session_add_arg(self._session, *_string(arg)))
+            if input is not None:
+                _check(lib.sj_session_set_input(self._session, *_string(input)))
+            if answer is not None:
+                _check(lib.sj_session_set_answer(self._session, *_string(answer)))
+            if time_limit_ms is not None:
+                _check(lib.sj_session_set_time_limit_ms(self._session, time_limit_ms))
+            if memory_limit is not None:
+                _check(lib.sj_session_set_memory_limit(self._session, memory_limit))
+            if output_limit is not None:
+                _check(lib.sj_session_set_output_limit(self._session, output_limit))
+            if comparison is not None:
+                _check(lib.sj_session_set_mode(self._session, *_string(comparison)))
+            if sandbox is not None:
+                _check(lib.sj_session_set_sandbox(self._session, *_string(sandbox)))
+        except BaseException:
+            self.close()
+            raise
+
+    def run(self):
+        """Judge the program once. Returns a dict of status, abbr, time_ms,
+        cpu_ms, memory_bytes and detail; raises JudgeError if the judger
+        couldn't run it."""
+        lib = _library()
+        result = ctypes.c_void_p()
+        _check(lib.sj_run(self._session, ctypes.byref(result)))
+        try:
+            abbr, status = VERDICTS[lib.sj_result_verdict(result)]
+            length = ctypes.c_size_t()
+            detail = _text(lib.sj_result_detail(result, ctypes.byref(length)), length.value)
+            return {
+                "status": status,
+                "abbr": abbr,
+                "time_ms": lib.sj_result_time_ms(result),
+                "cpu_ms": lib.sj_result_cpu_time_ms(result),
+                "memory_bytes": lib.sj_result_memory_bytes(result),
+                "detail": detail
+            }
+        finally:
+            lib.sj_result_free(result)
+
+    def close(self):
+        """Free the session; it can't be run afterwards"""
+        if self._session:
+            _library().sj_session_free(self._session)
+            self._session = ctypes.c_void_p()
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def __del__(self):
+        if _lib is not None:
+            self.close()
+
+
+def judge(exec, **options):
+    """Judge `exec` once with the options of JudgeSession"""
+    with JudgeSession(exec, **options) as session:
+        return session.run()
+
+
+def judge_many(jobs, threads=None):
+    """Judge every job, a dict of JudgeSession's arguments with exec among
+    them, on `threads` threads. The results come back in the order of the
+    jobs."""
+    def run(job):
+        options = dict(job)
+        return judge(options.pop("exec"), **options)
+
+    with ThreadPoolExecutor(max_workers=threads) as pool:
+        return list(pool.map(run, jobs))
@@ -0,0 +1,124 @@
+"""The bindings end to end: fixtures of tests/fixtures judged through the
+library built with the python feature.
+
+    cargo build --features python
+    SECURE_JUDGER_LIB=target/debug/libsecure_judger.so pytest python/tests
+
+Without SECURE_JUDGER_LIB the debug build of the checkout is used.
+Skipped without the library or a C compiler.
+"""
+
+import os
+import shutil
+import subprocess
+import sys
+import threading
+import time
+
+import pytest
+
+ROOT = os.path.dirname(os.path.dirname(os.path.dirname(os.path.abspath(__file__))))
+sys.path.insert(0, os.path.join(ROOT, "python"))
+os.environ.setdefault("SECURE_JUDGER_LIB", os.path.join(ROOT, "target", "debug", "libsecure_judger.so"))
+
+import secure_judger  # noqa: E402
+
+if not os.path.exists(os.environ["SECURE_JUDGER_LIB"]):
+    pytest.skip("the library isn't built, run cargo build --features python", allow_module_level=True)
+
+
+@pytest.fixture(scope="module")
+def fixtures(tmp_path_factory):
+    """Compile a fixture by name, once per module"""
+    cc = os.environ.get("CC", "cc")
+    if shutil.which(cc) is None:
+        pytest.skip(f"no C compiler ({cc}), set CC to use another")
+    out = tmp_path_factory.mktemp("fixtures")
+    built = {}
+
+    def build(name):
+        if name not in built:
+            exec = str(out / name)
+            subprocess.run([cc, "-O2", "-o", exec, os.path.join(ROOT, "tests", "fixtures", f"{name}.c")], check=True)
+            built[name] = exec
+        return built[name]
+
+    return build
+
+
+@pytest.fixture
+def answer(tmp_path):
+    """Write an answer file"""
+    def write(text):
+        path = tmp_path / f"{len(list(tmp_path.iterdir()))}.ans"
+        path.write_text(text)
+        return str(path)
+
+    return write
+
+
+def test_hello_is_accepted(fixtures, answer):
+    result = secure_judger.judge(fixtures("hello"), answer=answer("hello\n"), time_limit_ms=1000,
+                                 sandbox="best-effort")
+    assert result["abbr"] == "AC"
+    assert result["status"] == "Accepted"
+    assert result["detail"] == "[AC] Accepted"
+    assert result["time_ms"] < 1000
+    assert result["memory_bytes"] > 0
+
+
+def test_wrong_answer(fixtures, answer):
+    result = secure_judger.judge(fixtures("hello"), answer=answer("goodbye\n"), sandbox="best-effort")
+    assert result["abbr"] == "WA"
+
+
+def test_infinite_loop_exceeds_time(fixtures, answer):
+    result = secure_judger.judge(fixtures("infinite_loop"), answer=answer(""), time_limit_ms=200,
+                                 sandbox="best-effort")
+    assert result["abbr"] == "TLE"
+    assert result["cpu_ms"] >= 200
+
+
+def test_bad_options_raise(fixtures, answer):
+    with pytest.raises(secure_judger.JudgeError) as error:
+        secure_judger.JudgeSession(fixtures("hello"), comparison="fuzzy")
+    assert "fuzzy" in str(error.value)
+    with pytest.raises(secure_judger.JudgeError):
+        secure_judger.judge(fixtures("hello"), answer=answer(""), time_limit_ms=0)
+
+
+def test_session_runs_repeatedly(fixtures, answer):
+    with secure_judger.JudgeSession(fixtures("hello"), answer=answer("hello\n"), sandbox="best-effort") as session:
+        assert [session.run()["abbr"] for _ in range(3)] == ["AC"] * 3
+
+
+def test_judge_many_keeps_order(fixtures, answer):
+    jobs = [
+        {"exec": fixtures("hello"), "answer": answer("hello\n"), "sandbox": "best-effort"},
+        {"exec": fixtures("hello"), "answer": answer("bye\n"), "sandbox": "best-effort"},
+        {"exec": fixtures("infinite_loop"), "answer": answer(""), "time_limit_ms": 100, "sandbox": "best-effort"}
+    ]
+    assert [r["abbr"] for r in secure_judger.judge_many(jobs, threads=3)] == ["AC", "WA", "TLE"]
+
+
+def test_gil_is_released_while_judging(fixtures, answer):
+    # A Python thread keeps counting while another waits on a program
+    # spinning for its whole time limit
+    ticks = []
+    done = threading.Event()
+
+    def count():
+        while not done.is_set():
+            ticks.append(1)
+            time.sleep(0.005)
+
+    counter = threading.Thread(target=count)
+    counter.start()
+    try:
+        result = secure_judger.judge(fixtures("infinite_loop"), answer=answer(""), time_limit_ms=300,
+                                     sandbox="best-effort")
+    finally:
+        done.set()
+        counter.join()
+    assert result["abbr"] == "TLE"
+    assert len(ticks) > 10
@@ -0,0 +1,146 @@
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use crate::metrics::METRICS;
+use crate::utils;
+
+/// What a job reserves for as long as it runs: its memory limit, the
+/// most it may use, and a core of its own if it asked for one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Demand {
+    /// Memory limit of the job's runs, scaled for their language
+    pub memory_bytes: u64,
+    /// Whether the job runs on a core no other job is given
+    pub dedicated_core: bool
+}
+
+/// Admits jobs only while the host has room for them, so that jobs run
+/// at once never need more memory than the host has nor share a core
+/// they asked to have alone. A job starts once its memory limit fits in
+/// the budget besides those of the jobs running, and a core of the pool
+/// is free if it wants one; its reservation is given back when it ends.
+/// The memory reserved and the cores are kept in METRICS for the
+/// metrics and GET /health.
+pub struct Admission {
+    // None to admit jobs whatever their memory limits come to
+    memory_budget: Option<u64>,
+    reserved: Mutex<Reserved>,
+    // Signalled when a reservation is given back
+    released: Condvar
+}
+
+#[derive(Default)]
+struct Reserved {
+    memory_bytes: u64,
+    // Cores of the pool no job holds
+    free_cores: Vec<usize>,
+    dedicated: usize
+}
+
+impl Admission {
+    /**
+     *  Admit jobs whose memory limits sum to `memory_budget` at most, if
+     *  given, giving the cores of `cores` to those asking for one
+     */
+    pub fn new(memory_budget: Option<u64>, cores: Vec<usize>) -> Self {
+        METRICS.set_memory_budget(memory_budget);
+        METRICS.set_reserved(0, 0);
+        Admission {
+            memory_budget,
+            reserved: Mutex::new(Reserved { free_cores: cores, ..Reserved::default() }),
+            released: Condvar::new()
+        }
+    }
+
+    /**
+     *  Whether a job of `demand` can ever be admitted, with why not if it
+     *  can't: it would wait forever for more memory than the budget or a
+     *  core the pool doesn't have
+     */
+    pub fn check(&self, demand: Demand) -> Result<(), String> {
+        if let Some(budget) = self.memory_budget.filter(|&budget| demand.memory_bytes > budget) {
+            return Err(format!(
+                "memory limit {} is over the memory budget of {}",
+                utils::format_memory(demand.memory_bytes),
+                utils::format_memory(budget)
+            ));
+        }
+        let reserved = self.lock();
+        if demand.dedicated_core && reserved.free_cores.len() + reserved.dedicated == 0 {
+            return Err(String::from("no core can be dedicated to the job"));
+        }
+        Ok(())
+    }
+
+    /**
+     *  Reserve what `demand` needs if the host has room for it now, None
+     *  if it hasn't
+     */
+    pub fn try_reserve(&self, demand: Demand) -> Option<Reservation<'_>> {
+        let mut reserved = self.lock();
+        self.take(&mut reserved, demand)
+    }
+
+    /**
+     *  Reserve what `demand` needs, waiting for the jobs running to give
+     *  back enough of theirs. Check the demand first, it waits forever if
+     *  it can never be admitted.
+     */
+    pub fn reserve(&self, demand: Demand) -> Reservation<'_> {
+        let mut reserved = self.lock();
+        loop {
+            if let Some(reservation) = self.take(&mut reserved, demand) {
+                return reservation;
+            }
+            reserved = self.released.wait(reserved).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Reserved> {
+        self.reserved.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /*
+     *  Reserve `demand` out of `reserved` if it fits there
+     */
+    fn take(&self, reserved: &mut Reserved, demand: Demand) -> Option<Reservation<'_>> {
+        let memory_bytes = reserved.memory_bytes.checked_add(demand.memory_bytes)?;
+        if self.memory_budget.is_some_and(|budget| memory_bytes > budget) {
+            return None;
+        }
+        let core = match demand.dedicated_core {
+            true => Some(reserved.free_cores.pop()?),
+            false => None
+        };
+        reserved.memory_bytes = memory_bytes;
+        reserved.dedicated += core.is_some() as usize;
+        METRICS.set_reserved(reserved.memory_bytes, reserved.dedicated);
+        Some(Reservation { admission: self, memory_bytes: demand.memory_bytes, core })
+    }
+}
+
+/// What an admitted job reserved, given back when dropped
+pub struct Reservation<'a> {
+    admission: &'a Admission,
+    memory_bytes: u64,
+    core: Option<usize>
+}
+
+impl Reservation<'_> {
+    /// The core dedicated to the job, if it asked for one
+    pub fn core(&self) -> Option<usize> {
+        self.core
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut reserved = self.admission.lock();
+        reserved.memory_bytes -= self.memory_bytes;
+        if let Some(core) = self.core {
+            reserved.free_cores.push(core);
+            reserved.dedicated -= 1;
+        }
+        METRICS.set_reserved(reserved.memory_bytes, reserved.dedicated);
+        self.admission.released.notify_all();
+    }
+}
@@ -0,0 +1,51 @@
+use std::env;
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use secure_judger::calibration::{self, Calibration};
+use secure_judger::judger::{JudgeSession, JudgeStatus};
+use secure_judger::sandbox::ScratchDir;
+
+// Hidden argument making the judger binary run the reference workload
+pub const WORKLOAD_ARG: &str = "__calibrate";
+
+// Limits of the workload, far over what it needs on a slow host
+const WORKLOAD_TIME: Duration = Duration::from_secs(60);
+const WORKLOAD_MEMORY: u64 = 64 * 1024 * 1024;
+
+/*
+ *  Run the workload as the sandboxed program, printing what it comes to
+ */
+pub fn workload_main() -> ! {
+    println!("{}", calibration::workload());
+    io::stdout().flush().unwrap_or_default();
+    std::process::exit(0);
+}
+
+/*
+ *  Run the workload in the sandbox, as programs are, and calibrate by the
+ *  CPU time it took. A workload that came to the wrong checksum is an
+ *  error, it didn't run what the baseline did.
+ */
+pub fn measure(cgroup_root: Option<PathBuf>, tmp_dir: Option<PathBuf>) -> Result<Calibration, Box<dyn Error>> {
+    let exe = env::current_exe()?;
+    // Private and unpredictably named, removed with the answer on return
+    let scratch = ScratchDir::create_named(&tmp_dir.unwrap_or_else(env::temp_dir), "secure-judger-calibrate")?;
+    let mut answer = scratch.create_file("workload.ans", 0o644)?;
+    writeln!(answer, "{}", calibration::WORKLOAD_CHECKSUM)?;
+    let mut builder = JudgeSession::builder(exe.clone())
+        .answer(scratch.file("workload.ans"))
+        .time_limit(WORKLOAD_TIME)
+        .memory_limit(WORKLOAD_MEMORY)
+        .scratch_dir(scratch.path().to_path_buf());
+    if let Some(root) = cgroup_root {
+        builder = builder.cgroup_root(root);
+    }
+    let result = builder.build()?.run_judge(&[&exe.to_string_lossy(), WORKLOAD_ARG])?;
+    match result.status {
+        JudgeStatus::Accepted => Ok(Calibration::of(Duration::from_millis(result.cpu_time_ms))),
+        status => Err(format!("the workload got {status}").into())
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt::Display;
+
+use secure_judger::utils;
+
+// Help lines put the description in this column, or on a line of its own
+// when the option doesn't fit before it
+const HELP_COLUMN: usize = 36;
+
+/// What an option takes after its name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptValue {
+    None,
+    // The next argument, or attached with =
+    Required(&'static str),
+    // Only attached with =, as the option means something without it too
+    Attached(&'static str)
+}
+
+/// An option of the judger, as it is parsed and listed in the help
+pub struct OptSpec {
+    // The name it is listed under first, then its aliases
+    pub names: &'static [&'static str],
+    pub value: OptValue,
+    pub help: &'static str
+}
+
+impl OptSpec {
+    pub fn name(&self) -> &'static str {
+        self.names[0]
+    }
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    UnknownOption { option: String, suggestion: Option<&'static str> },
+    MissingValue(&'static str),
+    UnexpectedValue(&'static str),
+    InvalidValue { option: &'static str, value: String, expected: &'static str },
+    // A value its parser said what is wrong with, naming the value
+    UnreadableValue { option: &'static str, reason: String },
+    Usage(String)
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownOption { option, suggestion: Some(name) } => {
+                f.write_fmt(format_args!("unknown option {option}, did you mean {name}?"))
+            },
+            Self::UnknownOption { option, suggestion: None } => f.write_fmt(format_args!("unknown option {option}")),
+            Self::MissingValue(option) => f.write_fmt(format_args!("option {option} requires a value")),
+            Self::UnexpectedValue(option) => f.write_fmt(format_args!("option {option} takes no value")),
+            Self::InvalidValue { option, value, expected } => {
+                f.write_fmt(format_args!("invalid value '{value}' for {option}: expected {expected}"))
+            },
+            Self::UnreadableValue { option, reason } => f.write_fmt(format_args!("invalid value for {option}: {reason}")),
+            Self::Usage(message) => f.write_str(message)
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// One of the judger's own arguments
+pub enum Arg<'a> {
+    Opt(&'a OptSpec, Option<String>),
+    Positional(String)
+}
+
+/// Tells the judger's options from its positional arguments. The caller
+/// stops at the executable and takes what follows it as the program's
+/// arguments, so those never get mistaken for options.
+pub struct ArgParser<'a> {
+    args: std::vec::IntoIter<String>,
+    specs: Vec<&'a OptSpec>,
+    // After a --, everything is positional
+    options_done: bool
+}
+
+impl<'a> ArgParser<'a> {
+    /// `args` without the judger's own name, with the options of all `groups`
+    pub fn new(args: Vec<String>, groups: &[&'a [OptSpec]]) -> Self {
+        let specs = groups.iter().flat_map(|group| group.iter()).collect();
+        ArgParser { args: args.into_iter(), specs, options_done: false }
+    }
+
+    pub fn next_arg(&mut self) -> Result<Option<Arg<'a>>, CliError> {
+        let Some(arg) = self.args.next() else {
+            return Ok(None);
+        };
+        // A lone - is the judger's stdin
+        if self.options_done || arg == "-" || !arg.starts_with('-') {
+            return Ok(Some(Arg::Positional(arg)));
+        }
+        if arg == "--" {
+            self.options_done = true;
+            return self.next_arg();
+        }
+        let (name, attached) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None)
+        };
+        let Some(spec) = self.specs.iter().find(|spec| spec.names.contains(&name)) else {
+            return Err(CliError::UnknownOption { option: name.to_string(), suggestion: suggest(&self.specs, name) });
+        };
+        let value = match (spec.value, attached) {
+            (OptValue::None, Some(_)) => return Err(CliError::UnexpectedValue(spec.name())),
+            (OptValue::Required(_), None) => Some(self.args.next().ok_or(CliError::MissingValue(spec.name()))?),
+            (_, value) => value
+        };
+        Ok(Some(Arg::Opt(spec, value)))
+    }
+
+    /*
+     *  Everything after the executable. A -- right after it is dropped,
+     *  unless one already ended the options and this one is the program's.
+     */
+    pub fn rest(self) -> Vec<String> {
+        let mut rest: Vec<String> = self.args.collect();
+        if !self.options_done && rest.first().is_some_and(|arg| arg == "--") {
+            rest.remove(0);
+        }
+        rest
+    }
+}
+
+/*
+ *  Parse the value of `option` with `parse`, saying what was expected
+ *  if it fails
+ */
+pub fn parse_value<T>(
+    option: &'static str,
+    value: &str,
+    parse: impl FnOnce(&str) -> Option<T>,
+    expected: &'static str
+) -> Result<T, CliError> {
+    parse(value).ok_or_else(|| CliError::InvalidValue { option, value: value.to_string(), expected })
+}
+
+/*
+ *  Parse the value of `option` with `parse`, which says what is wrong
+ *  with it if it fails
+ */
+pub fn parse_checked<T, E: Display>(
+    option: &'static str,
+    value: &str,
+    parse: impl FnOnce(&str) -> Result<T, E>
+) -> Result<T, CliError> {
+    parse(value).map_err(|e| CliError::UnreadableValue { option, reason: e.to_string() })
+}
+
+/*
+ *  The option closest to a misspelt one, if any is close enough
+ */
+fn suggest(specs: &[&OptSpec], name: &str) -> Option<&'static str> {
+    utils::closest(name, specs.iter().flat_map(|spec| spec.names.iter().copied()))
+}
+
+/*
+ *  List the options with their help, one or two lines each
+ */
+pub fn options_help(specs: &[OptSpec]) -> String {
+    let mut help = String::new();
+    for spec in specs {
+        let mut usage = format!("  {}", spec.names.join(", "));
+        match spec.value {
+            OptValue::None => {},
+            OptValue::Required(placeholder) => usage += &format!(" {placeholder}"),
+            OptValue::Attached(placeholder) => usage += &format!("[={placeholder}]")
+        }
+        match usage.len() < HELP_COLUMN {
+            true => help += &format!("{usage:<HELP_COLUMN$}{}\n", spec.help),
+            false => help += &format!("{usage}\n{:HELP_COLUMN$}{}\n", "", spec.help)
+        }
+    }
+    help
+}
@@ -0,0 +1,695 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use secure_judger::admission::{Admission, Demand};
+use secure_judger::compare::Comparison;
+use crate::interrupt;
+use secure_judger::json::{self, Value};
+use secure_judger::judger::{JudgeHandle, JudgeResult, JudgeSession, JudgeSessionBuilder};
+use secure_judger::language::{self, LimitMultipliers};
+use secure_judger::log;
+use secure_judger::metrics::METRICS;
+use secure_judger::sandbox::InputSource;
+use secure_judger::utils::host;
+use crate::store::{JobStatus, JobStore, StoredJob};
+
+// How often the waiting threads check whether the daemon is draining
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// Longest request line, longer ones end the connection
+const MAX_REQUEST: u64 = 1 << 20;
+
+/// A test to judge, as a client asked for it. The limits left out are
+/// those the daemon was started with.
+pub struct Request {
+    exec: PathBuf,
+    // The program's arguments after its name
+    args: Vec<String>,
+    input: PathBuf,
+    answer: PathBuf,
+    time_limit: Option<Duration>,
+    wall_limit: Option<Duration>,
+    memory_limit: Option<u64>,
+    output_limit: Option<u64>,
+    comparison: Option<Comparison>,
+    multipliers: Option<LimitMultipliers>,
+    // Whether the run gets a core no other such run is given
+    dedicated_core: bool
+}
+
+/// A line a client sent, with the id given back in the reply for the
+/// client to tell its requests apart
+pub enum Message {
+    // With the members of the request's object, for storing it
+    Judge { id: Option<Value>, request: Box<Request>, members: Vec<(String, Value)> },
+    // Stop taking requests and exit once those taken are judged
+    Shutdown { id: Option<Value> },
+    // The stored jobs, those with `status` if given
+    Jobs { id: Option<Value>, status: Option<JobStatus> }
+}
+
+/// Why a line isn't a message, with the id it had if it had any
+pub struct RequestError {
+    pub id: Option<Value>,
+    pub message: String
+}
+
+// The keys a request may have, with what kind of value they take
+const REQUEST_KEYS: [(&str, &str); 11] = [
+    ("exec", "a string"),
+    ("args", "an array of strings"),
+    ("input", "a string"),
+    ("answer", "a string"),
+    ("time_limit_ms", "a positive integer"),
+    ("wall_time_limit_ms", "a positive integer"),
+    ("memory_limit_bytes", "a positive integer"),
+    ("output_limit_bytes", "a positive integer"),
+    ("compare", "a string"),
+    ("lang", "a string"),
+    ("dedicated_core", "a boolean")
+];
+
+impl Message {
+    /*
+     *  Read a line of the protocol: a JSON object that is either a
+     *  request, or a command such as {"cmd":"shutdown"} or
+     *  {"cmd":"jobs","status":"queued"}. Either may have an id.
+     */
+    pub fn parse(line: &str) -> Result<Message, RequestError> {
+        let value = json::parse(line).map_err(|e| RequestError { id: None, message: format!("not JSON, {e}") })?;
+        let Value::Object(mut members) = value else {
+            return Err(RequestError { id: None, message: format!("a request is an object, not {}", value.kind()) });
+        };
+        let id = take_member(&mut members, "id");
+        let error = |message: String| RequestError { id: id.clone(), message };
+        if let Some(id) = &id {
+            if !matches!(id, Value::String(_) | Value::Number(_)) {
+                return Err(error(format!("id must be a string or a number, not {}", id.kind())));
+            }
+        }
+        match take_member(&mut members, "cmd") {
+            Some(Value::String(cmd)) if cmd == "shutdown" && members.is_empty() => Ok(Message::Shutdown { id }),
+            Some(Value::String(cmd)) if cmd == "shutdown" => Err(error(String::from("shutdown takes no other keys than id"))),
+            Some(Value::String(cmd)) if cmd == "jobs" => {
+                let status = match take_member(&mut members, "status") {
+                    None => None,
+                    Some(Value::String(name)) => match JobStatus::from_name(&name) {
+                        Some(status) => Some(status),
+                        None => return Err(error(format!("unknown status {name}, expected queued, running, done or failed")))
+                    },
+                    Some(value) => return Err(error(format!("status must be a string, not {}", value.kind())))
+                };
+                match members.is_empty() {
+                    true => Ok(Message::Jobs { id, status }),
+                    false => Err(error(String::from("jobs takes no other keys than id and status")))
+                }
+            },
+            Some(Value::String(cmd)) => Err(error(format!("unknown command {cmd}, expected shutdown or jobs"))),
+            Some(value) => Err(error(format!("cmd must be a string, not {}", value.kind()))),
+            None => match Request::from_object(&members) {
+                Ok(request) => Ok(Message::Judge { id, request: Box::new(request), members }),
+                Err(message) => Err(error(message))
+            }
+        }
+    }
+}
+
+/*
+ *  Remove the member `key` from an object, giving its value
+ */
+pub fn take_member(members: &mut Vec<(String, Value)>, key: &str) -> Option<Value> {
+    let index = members.iter().position(|(member, _)| member == key)?;
+    Some(members.remove(index).1)
+}
+
+impl Request {
+    /*
+     *  Read a request from the members of its JSON object, which has at
+     *  least exec, input and answer. Keys that aren't known are refused,
+     *  so that a misspelt limit doesn't go unnoticed.
+     */
+    pub fn from_object(members: &[(String, Value)]) -> Result<Request, String> {
+        for (key, _) in members {
+            if !REQUEST_KEYS.iter().any(|(known, _)| known == key) {
+                return Err(format!("unknown key {key}"));
+            }
+        }
+        let get = |key: &str| members.iter().find(|(member, _)| member == key).map(|(_, value)| value);
+        let mistyped = |key: &str, value: &Value| {
+            let expected = REQUEST_KEYS.iter().find(|(known, _)| *known == key).map_or("", |(_, kind)| *kind);
+            format!("{key} must be {expected}, not {}", value.kind())
+        };
+        let string = |key: &str| match get(key) {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s.clone())),
+            Some(value) => Err(mistyped(key, value))
+        };
+        let count = |key: &str| match get(key) {
+            None => Ok(None),
+            Some(Value::Number(x)) if x.fract() == 0.0 && *x >= 1.0 && *x < 9e15 => Ok(Some(*x as u64)),
+            Some(value) => Err(mistyped(key, value))
+        };
+        let required = |key: &str| match string(key)? {
+            Some(s) if !s.is_empty() => Ok(s),
+            _ => Err(format!("missing {key}"))
+        };
+        let exec = PathBuf::from(required("exec")?);
+        let input = PathBuf::from(required("input")?);
+        let answer = PathBuf::from(required("answer")?);
+        let args = match get("args") {
+            None => Vec::new(),
+            Some(Value::Array(items)) => {
+                let strings: Option<Vec<String>> = items.iter().map(|item| match item {
+                    Value::String(s) => Some(s.clone()),
+                    _ => None
+                }).collect();
+                strings.ok_or_else(|| String::from("args must be an array of strings"))?
+            },
+            Some(value) => return Err(mistyped("args", value))
+        };
+        let comparison = match string("compare")? {
+            Some(mode) => match Comparison::from_name(&mode) {
+                Some(comparison) => Some(comparison),
+                None => return Err(format!("unknown compare {mode}, expected exact, tokens, float or float:TOLERANCE"))
+            },
+            None => None
+        };
+        let multipliers = match string("lang")? {
+            Some(name) => match language::find(&name) {
+                Some(lang) => Some(lang.limit_multipliers),
+                None => return Err(format!("unknown lang {name}"))
+            },
+            None => None
+        };
+        let dedicated_core = match get("dedicated_core") {
+            None => false,
+            Some(Value::Boolean(dedicated)) => *dedicated,
+            Some(value) => return Err(mistyped("dedicated_core", value))
+        };
+        Ok(Request {
+            exec,
+            args,
+            input,
+            answer,
+            time_limit: count("time_limit_ms")?.map(Duration::from_millis),
+            wall_limit: count("wall_time_limit_ms")?.map(Duration::from_millis),
+            memory_limit: count("memory_limit_bytes")?,
+            output_limit: count("output_limit_bytes")?,
+            comparison,
+            multipliers,
+            dedicated_core
+        })
+    }
+
+    pub fn exec(&self) -> &Path {
+        &self.exec
+    }
+
+    /*
+     *  What judging the request in a session of `builder` reserves: the
+     *  memory limit of the session, which may be the builder's and is
+     *  scaled for the language, and a core if the request asked for one
+     */
+    pub fn demand(&self, builder: JudgeSessionBuilder) -> Result<Demand, String> {
+        let session = self.session(builder)?;
+        Ok(Demand { memory_bytes: session.limits().effective.memory_bytes, dedicated_core: self.dedicated_core })
+    }
+
+    /*
+     *  Judge the request in a session of `builder`, which has the limits
+     *  the request leaves out. The error says why it couldn't be judged.
+     */
+    pub fn judge(&self, builder: JudgeSessionBuilder) -> Result<JudgeResult, String> {
+        let _span = log::span("request");
+        let session = self.session(builder)?;
+        let exec = self.exec.to_string_lossy();
+        let argv: Vec<&str> = [exec.as_ref()].into_iter().chain(self.args.iter().map(String::as_str)).collect();
+        session.run_judge(&argv).map_err(|e| format!("cannot judge: {e}"))
+    }
+
+    /*
+     *  Judge the request as judge does, on `core` if given: the calling
+     *  thread is pinned to it for the run, and to `cpus` again after
+     */
+    pub fn judge_on(&self, builder: JudgeSessionBuilder, core: Option<usize>, cpus: &[usize]) -> Result<JudgeResult, String> {
+        let pinned = core.filter(|&core| match host::pin_thread(&[core]) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("cannot pin the job to CPU {core}: {e}");
+                false
+            }
+        });
+        let judged = self.judge(builder);
+        if pinned.is_some() {
+            if let Err(e) = host::pin_thread(cpus) {
+                warn!("cannot unpin the worker: {e}");
+            }
+        }
+        judged
+    }
+
+    fn session(&self, builder: JudgeSessionBuilder) -> Result<JudgeSession, String> {
+        let mut builder = builder
+            .input(InputSource::File(self.input.clone()))
+            .answer(self.answer.clone());
+        if let Some(limit) = self.time_limit {
+            builder = builder.time_limit(limit);
+        }
+        if let Some(limit) = self.wall_limit {
+            builder = builder.wall_time_limit(limit);
+        }
+        if let Some(bytes) = self.memory_limit {
+            builder = builder.memory_limit(bytes);
+        }
+        if let Some(bytes) = self.output_limit {
+            builder = builder.output_limit(bytes);
+        }
+        if let Some(comparison) = self.comparison {
+            builder = builder.comparison(comparison);
+        }
+        if let Some(multipliers) = self.multipliers {
+            builder = builder.limit_multipliers(multipliers);
+        }
+        builder.build().map_err(|e| format!("invalid judging setup: {e}"))
+    }
+}
+
+/*
+ *  Admission of jobs within `memory_budget`, a core of each physical
+ *  one the judger may run on given to those asking for one, with the
+ *  CPUs the judger may run on for workers running the others
+ */
+pub fn admission(memory_budget: Option<u64>) -> (Admission, Vec<usize>) {
+    let cpus = host::allowed_cpus().unwrap_or_else(|e| {
+        warn!("cannot tell the CPUs the judger may run on ({e}), no core can be dedicated to a job");
+        Vec::new()
+    });
+    (Admission::new(memory_budget, host::physical_cores(&cpus)), cpus)
+}
+
+/*
+ *  A reply line: the id of the request, if it had one, then `fields`
+ */
+fn reply(id: &Option<Value>, fields: &[(&str, String)]) -> String {
+    let id = id.as_ref().map_or(String::from("null"), Value::to_json);
+    let mut members = vec![format!("\"id\":{id}")];
+    members.extend(fields.iter().map(|(key, value)| format!("{}:{value}", secure_judger::utils::json_string(key))));
+    format!("{{{}}}\n", members.join(","))
+}
+
+fn error_reply(id: &Option<Value>, message: &str) -> String {
+    reply(id, &[("error", secure_judger::utils::json_string(message))])
+}
+
+/// A request waiting for a worker, with where its result goes
+struct Job {
+    id: Option<Value>,
+    request: Request,
+    // None for a job taken before a restart, whose client is gone
+    client: Option<Arc<Mutex<UnixStream>>>,
+    // Its record, when the daemon keeps its jobs
+    stored: Option<StoredJob>,
+    // What it reserves while it runs
+    demand: Demand
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<Job>,
+    // Jobs a worker took and is judging
+    running: usize
+}
+
+/// What the threads of a daemon share
+struct Shared<'a> {
+    state: Mutex<State>,
+    store: Option<&'a JobStore>,
+    admission: Admission,
+    // CPUs the workers may run on when not on a core dedicated to a job
+    cpus: Vec<usize>,
+    // Signalled when a job is queued or finishes, or the daemon starts
+    // draining
+    ready: Condvar,
+    draining: AtomicBool,
+    judged: AtomicU64
+}
+
+impl Shared<'_> {
+    fn draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    // Whether nothing is queued or being judged
+    fn idle(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.queue.is_empty() && state.running == 0
+    }
+
+    fn drain(&self) {
+        if !self.draining.swap(true, Ordering::SeqCst) {
+            debug!("draining");
+        }
+        self.ready.notify_all();
+    }
+
+    /*
+     *  Read the requests of a client, a line each, and queue them. The
+     *  results are written back as they come, which may not be in the
+     *  order of the requests. Requests the host can never have room for,
+     *  their memory limit over the budget, are refused. Once the daemon
+     *  drains, the requests still coming are refused until the last job
+     *  is judged.
+     */
+    fn serve_client(&self, stream: UnixStream, builder: &(impl Fn(PathBuf) -> JudgeSessionBuilder + Sync)) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        let client = Arc::new(Mutex::new(stream.try_clone()?));
+        let send = |line: &str| client.lock().unwrap().write_all(line.as_bytes());
+        let mut reader = BufReader::new(stream);
+        let mut line: Vec<u8> = Vec::new();
+        while !(self.draining() && self.idle()) {
+            let read = (&mut reader).take(MAX_REQUEST + 1 - line.len() as u64).read_until(b'\n', &mut line);
+            match read {
+                Ok(0) if line.is_empty() => return Ok(()),
+                Ok(_) if line.len() as u64 > MAX_REQUEST => {
+                    return send(&error_reply(&None, "request longer than 1MiB"));
+                },
+                // The last line may go without its line break
+                Ok(n) if n > 0 && line.last() != Some(&b'\n') => continue,
+                Ok(_) => {},
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => continue,
+                Err(e) => return Err(e)
+            }
+            let text = String::from_utf8_lossy(&line).into_owned();
+            line.clear();
+            if text.trim().is_empty() {
+                continue;
+            }
+            match Message::parse(text.trim()) {
+                Ok(Message::Judge { id, request, members }) => {
+                    let demand = request.demand(builder(request.exec().to_path_buf()));
+                    let demand = match demand.and_then(|demand| self.admission.check(demand).map(|_| demand)) {
+                        Ok(demand) => demand,
+                        Err(message) => {
+                            send(&error_reply(&id, &message))?;
+                            continue;
+                        }
+                    };
+                    let mut state = self.state.lock().unwrap();
+                    if self.draining() {
+                        drop(state);
+                        send(&error_reply(&id, "the daemon is shutting down"))?;
+                        continue;
+                    }
+                    // Stored before it is queued, so that a job is never
+                    // judged without a record of it
+                    let stored = match self.store {
+                        Some(store) => {
+                            let job = StoredJob { number: store.next_number(), id: id.clone(), status: JobStatus::Queued, request: members, outcome: None };
+                            if let Err(e) = store.put(&job) {
+                                drop(state);
+                                send(&error_reply(&id, &format!("cannot store the job: {e}")))?;
+                                continue;
+                            }
+                            Some(job)
+                        },
+                        None => None
+                    };
+                    debug!("queued {}, {} waiting", request.exec().display(), state.queue.len() + 1);
+                    state.queue.push_back(Job { id, request: *request, client: Some(client.clone()), stored, demand });
+                    METRICS.set_queued(state.queue.len());
+                    self.ready.notify_one();
+                },
+                Ok(Message::Shutdown { id }) => {
+                    self.drain();
+                    let pending = {
+                        let state = self.state.lock().unwrap();
+                        state.queue.len() + state.running
+                    };
+                    send(&reply(&id, &[("shutdown", String::from("true")), ("pending", pending.to_string())]))?;
+                },
+                Ok(Message::Jobs { id, status }) => send(&self.jobs_reply(&id, status))?,
+                Err(e) => send(&error_reply(&e.id, &e.message))?
+            }
+        }
+        Ok(())
+    }
+
+    /*
+     *  The reply to the jobs command: the stored jobs with `status`, or
+     *  all of them
+     */
+    fn jobs_reply(&self, id: &Option<Value>, status: Option<JobStatus>) -> String {
+        let Some(store) = self.store else {
+            return error_reply(id, "this daemon keeps no jobs, start it with --state-dir DIR");
+        };
+        match store.load() {
+            Ok(jobs) => {
+                let listed: Vec<String> = jobs.iter().filter(|job| status.is_none_or(|status| job.status == status)).map(StoredJob::summary_json).collect();
+                reply(id, &[("jobs", format!("[{}]", listed.join(",")))])
+            },
+            Err(e) => error_reply(id, &format!("cannot read the jobs: {e}"))
+        }
+    }
+
+    /*
+     *  Record where a stored job is at, which a daemon that cannot write
+     *  its state only logs: the job goes on, and is judged again after a
+     *  restart at worst
+     */
+    fn record(&self, job: &Option<StoredJob>) {
+        if let (Some(store), Some(job)) = (self.store, job) {
+            if let Err(e) = store.put(job) {
+                warn!("cannot store job {} as {}: {e}", job.number, job.status.name());
+            }
+        }
+    }
+
+    /*
+     *  Judge queued jobs one after another until the daemon drains and
+     *  the queue is empty. The job first in the queue is taken once the
+     *  host has room for it, those after it waiting their turn even if
+     *  they'd fit, so that small jobs can't hold a large one back for
+     *  ever.
+     */
+    fn work(&self, builder: &(impl Fn(PathBuf) -> JudgeSessionBuilder + Sync)) {
+        loop {
+            let (mut job, reservation) = {
+                let mut state = self.state.lock().unwrap();
+                loop {
+                    let admitted = state.queue.front().and_then(|job| self.admission.try_reserve(job.demand));
+                    if let Some(reservation) = admitted {
+                        let job = state.queue.pop_front().expect("the job admitted");
+                        state.running += 1;
+                        METRICS.set_queued(state.queue.len());
+                        METRICS.job_started();
+                        break (job, reservation);
+                    }
+                    if self.draining() && state.queue.is_empty() {
+                        return;
+                    }
+                    state = self.ready.wait_timeout(state, POLL_INTERVAL).unwrap().0;
+                }
+            };
+            if let Some(stored) = &mut job.stored {
+                stored.status = JobStatus::Running;
+            }
+            self.record(&job.stored);
+            // A handle of its own, so that SIGTERM lets the run finish
+            // rather than cancelling it
+            let session = builder(job.request.exec().to_path_buf()).cancel_handle(JudgeHandle::new());
+            let judged = job.request.judge_on(session, reservation.core(), &self.cpus);
+            // Given back under the lock, so that no worker checks for room
+            // between it and the signal
+            {
+                let _state = self.state.lock().unwrap();
+                drop(reservation);
+            }
+            self.ready.notify_all();
+            let mut fields = Vec::new();
+            if let Some(stored) = &mut job.stored {
+                fields.push(("job", stored.number.to_string()));
+                (stored.status, stored.outcome) = match &judged {
+                    Ok(result) => (JobStatus::Done, Some(result.to_json())),
+                    Err(message) => (JobStatus::Failed, Some(message.clone()))
+                };
+            }
+            // Recorded before the reply, so that a job whose result a
+            // client got is never judged again
+            self.record(&job.stored);
+            match judged {
+                Ok(result) => fields.push(("result", result.to_json())),
+                Err(message) => fields.push(("error", secure_judger::utils::json_string(&message)))
+            }
+            let line = reply(&job.id, &fields);
+            self.judged.fetch_add(1, Ordering::SeqCst);
+            METRICS.job_finished();
+            // A client that went away doesn't get its result
+            let sent = job.client.as_ref().is_some_and(|client| client.lock().unwrap().write_all(line.as_bytes()).is_ok());
+            if !sent {
+                debug!("client of {} went away", job.request.exec().display());
+            }
+            self.state.lock().unwrap().running -= 1;
+        }
+    }
+}
+
+/// Judges the requests clients send over a Unix socket, a number of them
+/// at once and the rest queued. Each request is a line of JSON and so is
+/// each reply. The socket is removed when the daemon is dropped.
+pub struct Daemon {
+    listener: UnixListener,
+    socket: PathBuf,
+    jobs: usize,
+    // Most the memory limits of the jobs running may sum to
+    memory_budget: Option<u64>,
+    store: Option<JobStore>,
+    // Stored jobs left queued or running by the daemon before
+    recovered: Vec<StoredJob>
+}
+
+impl Daemon {
+    /*
+     *  Listen on `socket`, replacing a socket file no daemon listens on
+     *  any more but nothing else
+     */
+    pub fn bind(socket: &Path, jobs: usize) -> io::Result<Self> {
+        if let Ok(metadata) = fs::symlink_metadata(socket) {
+            if !metadata.file_type().is_socket() {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} exists and is not a socket", socket.display())));
+            }
+            match UnixStream::connect(socket) {
+                Ok(_) => {
+                    return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("a daemon already listens on {}", socket.display())));
+                },
+                Err(_) => fs::remove_file(socket)?
+            }
+        }
+        let listener = UnixListener::bind(socket)?;
+        listener.set_nonblocking(true)?;
+        Ok(Daemon { listener, socket: socket.to_path_buf(), jobs: jobs.max(1), memory_budget: None, store: None, recovered: Vec::new() })
+    }
+
+    /*
+     *  Judge at once only as many jobs as their memory limits fit in
+     *  `bytes`, the others waiting for them to finish
+     */
+    pub fn memory_budget(mut self, bytes: u64) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    /*
+     *  Keep the jobs in `dir`, taking up those a daemon before left queued
+     *  or running there
+     */
+    pub fn persist(mut self, dir: &Path) -> io::Result<Self> {
+        let (store, jobs) = JobStore::open(dir)?;
+        self.recovered = jobs.into_iter().filter(|job| matches!(job.status, JobStatus::Queued | JobStatus::Running)).collect();
+        self.store = Some(store);
+        Ok(self)
+    }
+
+    /*
+     *  The recovered jobs as the queue starts with them, those whose
+     *  request became invalid, such as for a language no longer known or
+     *  a memory limit over the budget, recorded as failed instead
+     */
+    fn recover(&mut self, admission: &Admission, builder: &impl Fn(PathBuf) -> JudgeSessionBuilder) -> VecDeque<Job> {
+        let mut queue = VecDeque::new();
+        for mut stored in self.recovered.drain(..) {
+            let admitted = Request::from_object(&stored.request).and_then(|request| {
+                let demand = request.demand(builder(request.exec().to_path_buf()))?;
+                admission.check(demand)?;
+                Ok((request, demand))
+            });
+            match admitted {
+                Ok((request, demand)) => {
+                    debug!("taking up job {}, {}", stored.number, stored.status.name());
+                    queue.push_back(Job { id: stored.id.clone(), request, client: None, stored: Some(stored), demand });
+                },
+                Err(message) => {
+                    (stored.status, stored.outcome) = (JobStatus::Failed, Some(message));
+                    if let Some(Err(e)) = self.store.as_ref().map(|store| store.put(&stored)) {
+                        warn!("cannot store job {} as failed: {e}", stored.number);
+                    }
+                }
+            }
+        }
+        queue
+    }
+
+    /*
+     *  Take requests until a client asks to shut down or SIGINT or SIGTERM
+     *  comes, then judge those taken and return how many were judged in
+     *  all. `builder` configures the session of an executable, with the
+     *  limits requests don't give.
+     */
+    pub fn serve(&mut self, builder: impl Fn(PathBuf) -> JudgeSessionBuilder + Sync) -> io::Result<u64> {
+        interrupt::handle()?;
+        let (admission, cpus) = admission(self.memory_budget);
+        let queue = self.recover(&admission, &builder);
+        if !queue.is_empty() {
+            eprintln!("Taking up {} jobs left by the daemon before", queue.len());
+        }
+        METRICS.set_queued(queue.len());
+        let shared = Shared {
+            state: Mutex::new(State { queue, running: 0 }),
+            store: self.store.as_ref(),
+            admission,
+            cpus,
+            ready: Condvar::new(),
+            draining: AtomicBool::new(false),
+            judged: AtomicU64::new(0)
+        };
+        let shared = &shared;
+        let builder = &builder;
+        thread::scope(|scope| {
+            for _ in 0..self.jobs {
+                scope.spawn(move || shared.work(builder));
+            }
+            while !shared.draining() {
+                if interrupt::interrupted().is_some() {
+                    shared.drain();
+                    break;
+                }
+                match self.listener.accept() {
+                    Ok((stream, _)) => {
+                        debug!("client connected");
+                        scope.spawn(move || {
+                            if let Err(e) = shared.serve_client(stream, builder) {
+                                debug!("client dropped: {e}");
+                            }
+                        });
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => self.wait_for_client(),
+                    // Such as running out of file descriptors, which may pass
+                    Err(e) => {
+                        debug!("cannot accept a client: {e}");
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                }
+            }
+        });
+        Ok(shared.judged.load(Ordering::SeqCst))
+    }
+
+    fn wait_for_client(&self) {
+        let mut pfd = libc::pollfd { fd: self.listener.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        unsafe { libc::poll(&mut pfd, 1, POLL_INTERVAL.as_millis() as i32) };
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.socket);
+    }
+}
@@ -0,0 +1,649 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::daemon::{self, Request};
+use crate::interrupt;
+use secure_judger::admission::{Admission, Demand};
+use secure_judger::json::{self, Value};
+use secure_judger::judger::{JudgeHandle, JudgeSessionBuilder};
+use secure_judger::metrics::METRICS;
+use secure_judger::sandbox::ScratchDir;
+use secure_judger::utils::{self, json_string};
+
+// Largest inline input or answer, once decoded
+const INLINE_LIMIT: usize = 16 << 20;
+// Room for both inline files in base64 and the rest of the job
+const MAX_BODY: u64 = 2 * (INLINE_LIMIT as u64 / 3 * 4 + 4) + (64 << 10);
+// Longest request line and headers together
+const MAX_HEAD: u64 = 16 << 10;
+// Connections being read or answered at once, more are refused
+const MAX_CONNECTIONS: usize = 64;
+// A client has this long to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// Jobs done that are kept to be asked about, the oldest forgotten first
+const KEPT_JOBS: usize = 1024;
+// How often the waiting threads check whether the server stops
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const JSON: &str = "application/json";
+// Version 0.0.4 of the text format, as Prometheus asks for it
+const METRICS_TEXT: &str = "text/plain; version=0.0.4";
+
+/// Why a request can't be answered as asked, as the status it gets
+struct HttpError {
+    status: u16,
+    message: String
+}
+
+impl HttpError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        HttpError { status, message: message.into() }
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    // Without the query, which nothing takes
+    path: String,
+    body: Vec<u8>
+}
+
+/*
+ *  Read a request of HTTP/1.x, with its body if it has a length. Chunked
+ *  bodies are refused, no client of this API needs them.
+ */
+fn read_request(reader: &mut BufReader<TcpStream>) -> Result<HttpRequest, HttpError> {
+    let mut head: Vec<String> = Vec::new();
+    let mut head_bytes: u64 = 0;
+    loop {
+        let mut line = Vec::new();
+        let read = reader.by_ref().take(MAX_HEAD + 1 - head_bytes).read_until(b'\n', &mut line);
+        match read {
+            Ok(0) => return Err(HttpError::new(400, "the request ended before its headers did")),
+            Ok(n) => head_bytes += n as u64,
+            Err(e) => return Err(HttpError::new(408, format!("cannot read the request: {e}")))
+        }
+        if head_bytes > MAX_HEAD {
+            return Err(HttpError::new(431, "request headers too large"));
+        }
+        let line = String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string();
+        if line.is_empty() && !head.is_empty() {
+            break;
+        }
+        if !line.is_empty() {
+            head.push(line);
+        }
+    }
+    let mut request_line = head[0].split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (request_line.next(), request_line.next(), request_line.next(), request_line.next())
+    else {
+        return Err(HttpError::new(400, "malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(HttpError::new(505, format!("{version} is not supported, use HTTP/1.1")));
+    }
+    let header = |name: &str| {
+        head[1..].iter()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    };
+    if header("transfer-encoding").is_some() {
+        return Err(HttpError::new(501, "chunked bodies are not supported, give Content-Length"));
+    }
+    let length = match header("content-length").map(str::parse::<u64>) {
+        None => 0,
+        Some(Ok(length)) if length <= MAX_BODY => length,
+        Some(Ok(_)) => return Err(HttpError::new(413, format!("body larger than {}", utils::format_memory(MAX_BODY)))),
+        Some(Err(_)) => return Err(HttpError::new(400, "invalid Content-Length"))
+    };
+    // curl waits for this before sending a large body
+    if length > 0 && header("expect").is_some_and(|value| value.eq_ignore_ascii_case("100-continue")) {
+        let _ = reader.get_mut().write_all(b"HTTP/1.1 100 Continue\r\n\r\n");
+    }
+    let mut body = Vec::new();
+    if let Err(e) = reader.by_ref().take(length).read_to_end(&mut body) {
+        return Err(HttpError::new(408, format!("cannot read the body: {e}")));
+    }
+    if (body.len() as u64) < length {
+        return Err(HttpError::new(400, "the body is shorter than its Content-Length"));
+    }
+    let path = target.split('?').next().unwrap_or_default().to_string();
+    Ok(HttpRequest { method: method.to_string(), path, body })
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Content Too Large",
+        431 => "Request Header Fields Too Large",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        505 => "HTTP Version Not Supported",
+        _ => "Unknown"
+    }
+}
+
+/*
+ *  Answer with a JSON body and close the connection
+ */
+fn respond(stream: &mut TcpStream, status: u16, body: &str, headers: &[(&str, String)]) -> io::Result<()> {
+    respond_with(stream, status, JSON, &format!("{body}\n"), headers)
+}
+
+fn respond_with(stream: &mut TcpStream, status: u16, content_type: &str, body: &str, headers: &[(&str, String)]) -> io::Result<()> {
+    let mut response = format!("HTTP/1.1 {status} {}\r\n", reason(status));
+    response.push_str(&format!("Content-Type: {content_type}\r\n"));
+    response.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    response.push_str("Connection: close\r\n");
+    for (name, value) in headers {
+        response.push_str(&format!("{name}: {value}\r\n"));
+    }
+    response.push_str(&format!("\r\n{body}"));
+    stream.write_all(response.as_bytes())
+}
+
+/*
+ *  Answer GET /metrics with the metrics, which only reads counters and
+ *  so never waits for the jobs
+ */
+fn respond_metrics(stream: &mut TcpStream, request: &HttpRequest, scratch_base: &Path) -> io::Result<()> {
+    match request.method.as_str() {
+        "GET" => respond_with(stream, 200, METRICS_TEXT, &METRICS.render(scratch_base), &[]),
+        _ => respond(stream, 405, &error_body(&format!("{} is not allowed here", request.method)), &[("Allow", String::from("GET"))])
+    }
+}
+
+/*
+ *  Answer GET /health: 200 while runs can be set up, 503 with the reason
+ *  once the scratch space can't take them any more, such as a full disk.
+ *  Either gives what the jobs running reserved.
+ */
+fn respond_health(stream: &mut TcpStream, request: &HttpRequest, scratch: &ScratchCheck) -> io::Result<()> {
+    if request.method != "GET" {
+        return respond(stream, 405, &error_body(&format!("{} is not allowed here", request.method)), &[("Allow", String::from("GET"))]);
+    }
+    let reserved = METRICS.reservations_json();
+    match utils::check_scratch(&scratch.base, scratch.needed, scratch.exec) {
+        Ok(()) => respond(stream, 200, &utils::json_object(&[("status", json_string("ok")), ("reserved", reserved)]), &[]),
+        Err(e) => {
+            let body = utils::json_object(&[
+                ("status", json_string("unavailable")),
+                ("error", json_string(&e.to_string())),
+                ("reserved", reserved)
+            ]);
+            respond(stream, 503, &body, &[])
+        }
+    }
+}
+
+fn error_body(message: &str) -> String {
+    utils::json_object(&[("error", json_string(message))])
+}
+
+/// Where a job is at
+enum JobState {
+    Queued,
+    Running,
+    // With the result as JSON
+    Finished(String),
+    // Why it couldn't be judged
+    Failed(String),
+    // Deleted before it finished, with the result of the run it cut short
+    Cancelled(Option<String>)
+}
+
+struct Job {
+    state: JobState,
+    // Taken by the worker that judges it
+    request: Option<Request>,
+    cancel: JudgeHandle,
+    // Holds the inline input and answer until the job is judged
+    files: Option<ScratchDir>,
+    // What it reserves while it runs
+    demand: Demand
+}
+
+impl Job {
+    fn done(&self) -> bool {
+        !matches!(self.state, JobState::Queued | JobState::Running)
+    }
+
+    fn to_json(&self, id: u64) -> String {
+        let (status, detail) = match &self.state {
+            JobState::Queued => ("queued", None),
+            JobState::Running => ("running", None),
+            JobState::Finished(result) => ("finished", Some(("result", result.clone()))),
+            JobState::Failed(message) => ("failed", Some(("error", json_string(message)))),
+            JobState::Cancelled(result) => ("cancelled", result.clone().map(|result| ("result", result)))
+        };
+        let mut fields = vec![("id", id.to_string()), ("status", json_string(status))];
+        fields.extend(detail);
+        utils::json_object(&fields)
+    }
+}
+
+#[derive(Default)]
+struct Jobs {
+    // By id, which count up, so the oldest come first
+    table: BTreeMap<u64, Job>,
+    queue: VecDeque<u64>,
+    // The id of the latest job, the first being 1
+    last_id: u64
+}
+
+impl Jobs {
+    /*
+     *  Forget the oldest jobs that are done beyond KEPT_JOBS of them
+     */
+    fn trim(&mut self) {
+        let done: Vec<u64> = self.table.iter().filter(|(_, job)| job.done()).map(|(&id, _)| id).collect();
+        for id in done.iter().take(done.len().saturating_sub(KEPT_JOBS)) {
+            self.table.remove(id);
+        }
+    }
+}
+
+/// What the threads of a server share
+struct Shared<'a> {
+    jobs: Mutex<Jobs>,
+    // Signalled when a job is queued or finishes, or the server stops
+    ready: Condvar,
+    stopping: AtomicBool,
+    connections: AtomicUsize,
+    root: &'a Path,
+    scratch: &'a ScratchCheck,
+    admission: Admission,
+    // CPUs the workers may run on when not on a core dedicated to a job
+    cpus: Vec<usize>
+}
+
+impl Shared<'_> {
+    fn stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+
+    /*
+     *  Stop judging: the jobs queued are cancelled and so are the runs in
+     *  progress, their results being of no use once the server is gone
+     */
+    fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        let mut jobs = self.jobs.lock().unwrap();
+        for id in std::mem::take(&mut jobs.queue) {
+            if let Some(job) = jobs.table.get_mut(&id) {
+                job.state = JobState::Cancelled(None);
+            }
+        }
+        METRICS.set_queued(0);
+        for job in jobs.table.values() {
+            job.cancel.cancel();
+        }
+        self.ready.notify_all();
+    }
+
+    fn serve_client(&self, stream: TcpStream, builder: &(impl Fn(PathBuf) -> JudgeSessionBuilder + Sync)) {
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+        let Ok(mut writer) = stream.try_clone() else {
+            return;
+        };
+        let mut reader = BufReader::new(stream);
+        let responded = match read_request(&mut reader) {
+            Ok(request) if request.path == "/metrics" => respond_metrics(&mut writer, &request, &self.scratch.base),
+            Ok(request) if request.path == "/health" => respond_health(&mut writer, &request, self.scratch),
+            Ok(request) => {
+                debug!("{} {}", request.method, request.path);
+                let (status, body, headers) = self.route(&request, builder);
+                respond(&mut writer, status, &body, &headers)
+            },
+            Err(e) => respond(&mut writer, e.status, &error_body(&e.message), &[])
+        };
+        if let Err(e) = responded {
+            debug!("cannot answer a client: {e}");
+        }
+    }
+
+    fn route(&self, request: &HttpRequest, builder: &(impl Fn(PathBuf) -> JudgeSessionBuilder + Sync)) -> (u16, String, Vec<(&'static str, String)>) {
+        let failed = |e: HttpError| (e.status, error_body(&e.message), Vec::new());
+        let not_allowed = |allow: &str| (405, error_body(&format!("{} is not allowed here", request.method)), vec![("Allow", allow.to_string())]);
+        if request.path == "/judge" {
+            return match request.method.as_str() {
+                "POST" => match self.submit(&request.body, builder) {
+                    Ok(id) => {
+                        let body = utils::json_object(&[("id", id.to_string()), ("status", json_string("queued"))]);
+                        (202, body, vec![("Location", format!("/jobs/{id}"))])
+                    },
+                    Err(e) => failed(e)
+                },
+                _ => not_allowed("POST")
+            };
+        }
+        let Some(id) = request.path.strip_prefix("/jobs/").and_then(|id| id.parse::<u64>().ok()) else {
+            return failed(HttpError::new(404, format!("no such resource {}", request.path)));
+        };
+        let mut jobs = self.jobs.lock().unwrap();
+        let Some(job) = jobs.table.get_mut(&id) else {
+            return failed(HttpError::new(404, format!("no job {id}")));
+        };
+        match request.method.as_str() {
+            "GET" => (200, job.to_json(id), Vec::new()),
+            // Cancel a job that isn't done, and forget one that is
+            "DELETE" => match job.state {
+                JobState::Queued => {
+                    job.state = JobState::Cancelled(None);
+                    job.files = None;
+                    let body = job.to_json(id);
+                    jobs.queue.retain(|&queued| queued != id);
+                    METRICS.set_queued(jobs.queue.len());
+                    (200, body, Vec::new())
+                },
+                JobState::Running => {
+                    job.cancel.cancel();
+                    (200, job.to_json(id), Vec::new())
+                },
+                _ => {
+                    let body = job.to_json(id);
+                    jobs.table.remove(&id);
+                    (200, body, Vec::new())
+                }
+            },
+            _ => not_allowed("GET, DELETE")
+        }
+    }
+
+    /*
+     *  Queue the job a POST /judge describes, giving its id. The body is
+     *  a request as the daemon takes, except that the input and answer
+     *  may come inline in base64 as input_base64 and answer_base64. A job
+     *  the host can never have room for, its memory limit over the
+     *  budget, is refused.
+     */
+    fn submit(&self, body: &[u8], builder: &(impl Fn(PathBuf) -> JudgeSessionBuilder + Sync)) -> Result<u64, HttpError> {
+        if self.stopping() {
+            return Err(HttpError::new(503, "the server is shutting down"));
+        }
+        let text = std::str::from_utf8(body).map_err(|_| HttpError::new(400, "the body is not UTF-8"))?;
+        let value = json::parse(text).map_err(|e| HttpError::new(400, format!("not JSON, {e}")))?;
+        let Value::Object(mut members) = value else {
+            return Err(HttpError::new(400, format!("a job is an object, not {}", value.kind())));
+        };
+        let mut files: Option<ScratchDir> = None;
+        for key in ["input", "answer"] {
+            let inline_key = format!("{key}_base64");
+            let inline = match daemon::take_member(&mut members, &inline_key) {
+                Some(Value::String(text)) => text,
+                Some(value) => return Err(HttpError::new(400, format!("{inline_key} must be a string, not {}", value.kind()))),
+                None => {
+                    self.confine(&mut members, key)?;
+                    continue;
+                }
+            };
+            if members.iter().any(|(member, _)| member == key) {
+                return Err(HttpError::new(400, format!("give {key} or {inline_key}, not both")));
+            }
+            let bytes = utils::base64_decode(&inline).ok_or_else(|| HttpError::new(400, format!("{inline_key} is not base64")))?;
+            if bytes.len() > INLINE_LIMIT {
+                return Err(HttpError::new(413, format!("{inline_key} is over {}", utils::format_memory(INLINE_LIMIT as u64))));
+            }
+            let scratch = match files.take() {
+                Some(scratch) => scratch,
+                None => ScratchDir::create(&self.scratch.base).map_err(|e| HttpError::new(503, format!("cannot store {inline_key}: {e}")))?
+            };
+            scratch
+                .create_file(key, 0o600)
+                .and_then(|mut file| file.write_all(&bytes))
+                .map_err(|e| HttpError::new(503, format!("cannot store {inline_key}: {e}")))?;
+            let path = scratch.file(key);
+            members.push((key.to_string(), Value::String(path.to_string_lossy().into_owned())));
+            files = Some(scratch);
+        }
+        self.confine(&mut members, "exec")?;
+        let request = Request::from_object(&members).map_err(|message| HttpError::new(400, message))?;
+        let demand = request.demand(builder(request.exec().to_path_buf())).map_err(|message| HttpError::new(400, message))?;
+        self.admission.check(demand).map_err(|message| HttpError::new(400, message))?;
+
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.last_id += 1;
+        let id = jobs.last_id;
+        debug!("job {id}: {}", request.exec().display());
+        jobs.table.insert(id, Job { state: JobState::Queued, request: Some(request), cancel: JudgeHandle::new(), files, demand });
+        jobs.queue.push_back(id);
+        METRICS.set_queued(jobs.queue.len());
+        jobs.trim();
+        self.ready.notify_one();
+        Ok(id)
+    }
+
+    /*
+     *  Resolve the path under `key` in the root, relative ones from it,
+     *  and refuse it if it leads anywhere else, through .. or symlinks
+     *  too. A path outside and one that doesn't exist get the same
+     *  answer, so that files outside can't be told apart by it.
+     */
+    fn confine(&self, members: &mut [(String, Value)], key: &str) -> Result<(), HttpError> {
+        let Some((_, Value::String(path))) = members.iter_mut().find(|(member, _)| member == key) else {
+            return Ok(());
+        };
+        let resolved = fs::canonicalize(self.root.join(&*path))
+            .ok()
+            .filter(|resolved| resolved.starts_with(self.root))
+            .ok_or_else(|| HttpError::new(403, format!("{key} {path} is not a file under the root")))?;
+        *path = resolved.to_string_lossy().into_owned();
+        Ok(())
+    }
+
+    /*
+     *  Judge queued jobs one after another until the server stops, the
+     *  job first in the queue taken once the host has room for it
+     */
+    fn work(&self, builder: &(impl Fn(PathBuf) -> JudgeSessionBuilder + Sync)) {
+        loop {
+            let (id, request, cancel, reservation) = {
+                let mut jobs = self.jobs.lock().unwrap();
+                loop {
+                    if self.stopping() {
+                        return;
+                    }
+                    let admitted = jobs.queue.front().and_then(|id| jobs.table.get(id)).and_then(|job| self.admission.try_reserve(job.demand));
+                    let taken = admitted.and_then(|reservation| {
+                        let id = jobs.queue.pop_front()?;
+                        let job = jobs.table.get_mut(&id)?;
+                        job.state = JobState::Running;
+                        Some((id, job.request.take()?, job.cancel.clone(), reservation))
+                    });
+                    METRICS.set_queued(jobs.queue.len());
+                    if let Some(taken) = taken {
+                        break taken;
+                    }
+                    jobs = self.ready.wait_timeout(jobs, POLL_INTERVAL).unwrap().0;
+                }
+            };
+            METRICS.job_started();
+            let judged = request.judge_on(builder(request.exec().to_path_buf()).cancel_handle(cancel.clone()), reservation.core(), &self.cpus);
+            METRICS.job_finished();
+            let mut jobs = self.jobs.lock().unwrap();
+            // Given back under the lock, so that no worker checks for room
+            // between it and the signal
+            drop(reservation);
+            self.ready.notify_all();
+            // Deleted while it ran, which may have been too late to matter
+            if let Some(job) = jobs.table.get_mut(&id) {
+                job.state = match (judged, cancel.is_cancelled()) {
+                    (Ok(result), true) => JobState::Cancelled(Some(result.to_json())),
+                    (Ok(result), false) => JobState::Finished(result.to_json()),
+                    (Err(_), true) => JobState::Cancelled(None),
+                    (Err(message), false) => JobState::Failed(message)
+                };
+                job.files = None;
+            }
+            jobs.trim();
+        }
+    }
+}
+
+/// Takes judging jobs over HTTP and judges them with a pool of workers.
+/// Clients submit a job with POST /judge, ask about it with GET
+/// /jobs/ID and cancel it with DELETE /jobs/ID. The files jobs name have
+/// to be under the root. GET /metrics gives the metrics for Prometheus,
+/// GET /health whether runs can still be set up.
+pub struct Server {
+    listener: TcpListener,
+    root: PathBuf,
+    jobs: usize,
+    // Most the memory limits of the jobs running may sum to
+    memory_budget: Option<u64>,
+    // Its base is where the inline inputs and answers go too
+    scratch: ScratchCheck
+}
+
+/// What GET /health checks: that the scratch directories of runs still
+/// fit under their base, see utils::check_scratch
+pub struct ScratchCheck {
+    pub base: PathBuf,
+    // Free space a run needs there
+    pub needed: u64,
+    // Whether copies of executables are run from there
+    pub exec: bool
+}
+
+impl Server {
+    pub fn bind(address: &str, root: &Path, jobs: usize, scratch: ScratchCheck) -> io::Result<Self> {
+        let root = fs::canonicalize(root).map_err(|e| io::Error::new(e.kind(), format!("root {}: {e}", root.display())))?;
+        if !root.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("root {} is not a directory", root.display())));
+        }
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Server { listener, root, jobs: jobs.max(1), memory_budget: None, scratch })
+    }
+
+    /*
+     *  Judge at once only as many jobs as their memory limits fit in
+     *  `bytes`, the others waiting for them to finish
+     */
+    pub fn memory_budget(mut self, bytes: u64) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /*
+     *  Serve until SIGINT or SIGTERM, which cancel the jobs that are left.
+     *  `builder` configures the session of an executable, with the limits
+     *  jobs don't give.
+     */
+    pub fn serve(&self, builder: impl Fn(PathBuf) -> JudgeSessionBuilder + Sync) -> io::Result<()> {
+        interrupt::handle()?;
+        let (admission, cpus) = daemon::admission(self.memory_budget);
+        let shared = Shared {
+            jobs: Mutex::new(Jobs::default()),
+            ready: Condvar::new(),
+            stopping: AtomicBool::new(false),
+            connections: AtomicUsize::new(0),
+            root: &self.root,
+            scratch: &self.scratch,
+            admission,
+            cpus
+        };
+        let shared = &shared;
+        let builder = &builder;
+        thread::scope(|scope| {
+            for _ in 0..self.jobs {
+                scope.spawn(move || shared.work(builder));
+            }
+            while interrupt::interrupted().is_none() {
+                match self.listener.accept() {
+                    Ok((mut stream, _)) if shared.connections.load(Ordering::SeqCst) >= MAX_CONNECTIONS => {
+                        let _ = stream.set_nonblocking(false);
+                        let _ = respond(&mut stream, 503, &error_body("too many connections"), &[("Retry-After", String::from("1"))]);
+                    },
+                    Ok((stream, _)) => {
+                        shared.connections.fetch_add(1, Ordering::SeqCst);
+                        scope.spawn(move || {
+                            shared.serve_client(stream, builder);
+                            shared.connections.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => self.wait_for_client(),
+                    Err(e) => {
+                        debug!("cannot accept a client: {e}");
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                }
+            }
+            shared.stop();
+        });
+        Ok(())
+    }
+
+    fn wait_for_client(&self) {
+        let mut pfd = libc::pollfd { fd: self.listener.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        unsafe { libc::poll(&mut pfd, 1, POLL_INTERVAL.as_millis() as i32) };
+    }
+}
+
+/// Answers GET /metrics and GET /health and nothing else, for the
+/// daemon, whose own socket doesn't speak HTTP
+pub struct MetricsListener {
+    listener: TcpListener,
+    // Its base is the one whose free space the metrics give
+    scratch: ScratchCheck
+}
+
+impl MetricsListener {
+    pub fn bind(address: &str, scratch: ScratchCheck) -> io::Result<Self> {
+        Ok(MetricsListener { listener: TcpListener::bind(address)?, scratch })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /*
+     *  Answer scrapes one at a time, for as long as the process lives.
+     *  A scrape only reads counters, so one at a time keeps up.
+     */
+    pub fn serve(&self) {
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(x) => x,
+                Err(e) => {
+                    debug!("cannot accept a scrape: {e}");
+                    thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+            };
+            let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+            let Ok(mut writer) = stream.try_clone() else {
+                continue;
+            };
+            let responded = match read_request(&mut BufReader::new(stream)) {
+                Ok(request) if request.path == "/metrics" => respond_metrics(&mut writer, &request, &self.scratch.base),
+                Ok(request) if request.path == "/health" => respond_health(&mut writer, &request, &self.scratch),
+                Ok(request) => respond(&mut writer, 404, &error_body(&format!("no such resource {}", request.path)), &[]),
+                Err(e) => respond(&mut writer, e.status, &error_body(&e.message), &[])
+            };
+            if let Err(e) = responded {
+                debug!("cannot answer a scrape: {e}");
+            }
+        }
+    }
+}
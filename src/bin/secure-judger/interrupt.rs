@@ -0,0 +1,68 @@
+use std::io;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::OnceLock;
+use secure_judger::judger::JudgeHandle;
+
+// The signal that interrupted the judger, 0 until one did
+static INTERRUPTED: AtomicI32 = AtomicI32::new(0);
+static HANDLE: OnceLock<JudgeHandle> = OnceLock::new();
+
+const SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+/*
+ *  The handle SIGINT and SIGTERM cancel the runs through, installing the
+ *  handlers for them the first time. Sessions built with it clean up
+ *  after their program when one of the signals comes, instead of the
+ *  judger dying with the program still running. SIGINT kills the program
+ *  at once. A first SIGTERM stops the runs softly, forwarding SIGTERM to
+ *  the program and giving it the grace period to exit, and a second one
+ *  kills it. Any signal after that exits right away.
+ */
+pub fn handle() -> io::Result<JudgeHandle> {
+    if let Some(handle) = HANDLE.get() {
+        return Ok(handle.clone());
+    }
+    let handle = HANDLE.get_or_init(JudgeHandle::new).clone();
+    for signal in SIGNALS {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    Ok(handle)
+}
+
+/// The signal that cancelled the runs, if one did
+pub fn interrupted() -> Option<i32> {
+    match INTERRUPTED.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal)
+    }
+}
+
+pub fn signal_name(signal: i32) -> &'static str {
+    match signal {
+        libc::SIGINT => "SIGINT",
+        libc::SIGTERM => "SIGTERM",
+        _ => "a signal"
+    }
+}
+
+/*
+ *  Only stores and writes to the handle's pipe, as anything else isn't
+ *  safe in a signal handler. The wait loop polls the pipe with the child.
+ */
+extern "C" fn on_signal(signal: libc::c_int) {
+    let first = INTERRUPTED.compare_exchange(0, signal, Ordering::SeqCst, Ordering::SeqCst).is_ok();
+    match HANDLE.get() {
+        Some(handle) if first && signal == libc::SIGTERM => handle.stop(),
+        Some(handle) if !handle.is_cancelled() => handle.cancel(),
+        _ if first => {},
+        _ => unsafe { libc::_exit(128 + signal) }
+    }
+}
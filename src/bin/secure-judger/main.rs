@@ -0,0 +1,2777 @@
+#[macro_use]
+extern crate secure_judger;
+
+mod cli;
+mod selftest;
+mod calibrate;
+mod interrupt;
+mod watch;
+mod daemon;
+mod store;
+mod table;
+mod progress;
+#[cfg(feature = "http")]
+mod http;
+
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use secure_judger::compare::Comparison;
+use secure_judger::config::ProblemConfig;
+use secure_judger::judger::{IoMode, JudgeEnvironment, JudgeError, JudgeResult, JudgeSession, JudgeSessionBuilder, JudgeStatus, KeepPolicy, TlePolicy};
+use secure_judger::output::{DiffTolerance, JsonComparisonV1, JsonResultsDiffV1, JsonSubmissionV1, ResultDiff, Schema};
+use secure_judger::plan::PlanFormat;
+use secure_judger::problem::{FailurePolicy, ProblemJudge, SubmissionResult, TestCase, TestLayout};
+use secure_judger::language::LimitMultipliers;
+use secure_judger::sandbox::{ExecArch, InputSource, JoinNamespace, Namespace, SandboxPolicy, SandboxStrength};
+use secure_judger::stress::{StressOutcome, StressTest};
+use secure_judger::transcript::Transcript;
+use secure_judger::utils::{host, StrictPaths};
+use secure_judger::cache::{AnswerCache, BatchStamps};
+use secure_judger::calibration::{self, Calibration};
+use secure_judger::{cache, language, log, problem, utils};
+#[cfg(feature = "grpc")]
+use secure_judger::grpc;
+use cli::{Arg, ArgParser, CliError, OptSpec, OptValue};
+use daemon::Daemon;
+use progress::Progress;
+use table::{Cell, Color, Table};
+use watch::Watcher;
+
+const ARGUMENTS_HELP: &str = "\
+Options may come anywhere before <executable>, everything after it is passed
+to the program, which gets <executable> as given for its argv[0]. A -- ends
+the judger's options, before the positional arguments or right after
+<executable>.";
+
+const DAEMON_NOTES: &str = "\
+Each request is a line of JSON such as
+  {\"id\": 1, \"exec\": \"/sub/a.out\", \"input\": \"/t/1.in\", \"answer\": \"/t/1.ans\"}
+which may also give args, time_limit_ms, wall_time_limit_ms,
+memory_limit_bytes, output_limit_bytes, compare, lang and dedicated_core,
+the options given here applying otherwise. Each reply is a line with the request's id and its
+result, or an error. {\"cmd\": \"shutdown\"}, SIGINT or SIGTERM stop taking
+requests, those taken are judged before the daemon exits.
+With --state-dir, every request is kept in DIR until judged and its result
+after, a daemon started again on DIR judging those it finds queued or
+running. Replies then give each request's job number, and
+{\"cmd\": \"jobs\", \"status\": \"queued\"} lists the jobs, with their results
+once done.
+With --memory-budget, a request starts only once its memory limit, scaled
+for its language, fits in SIZE besides those of the requests running,
+those after it waiting their turn; one over SIZE is refused. A request
+with \"dedicated_core\": true waits for a physical core no other such
+request runs on.
+With --metrics-listen, GET /metrics on ADDR gives the daemon's metrics in
+the Prometheus text format, and GET /health answers 503 once the scratch
+directories of runs no longer fit under --tmp-dir. Both give the memory
+and the cores the requests running reserved.";
+
+const CALIBRATE_NOTES: &str = "\
+The workload is fixed rounds of integer and floating-point arithmetic,
+about 2s of CPU time on the baseline's host in a release build. The score
+is how much faster this host ran it, the multiplier its inverse: the factor
+time limits set on the baseline's host need here. With --auto-calibrate it
+is stored in the --calibration file, and later commands scale the time
+limits of their sessions by it on top of their language's multipliers,
+unless given --no-calibration. Their results record the calibration in
+their environment.";
+
+const SERVE_NOTES: &str = "\
+POST /judge takes a job as the daemon's requests are, where input_base64 and
+answer_base64 may give the input and answer inline, up to 16MiB each. It is
+answered with the job's id, GET /jobs/ID gives where the job is at and its
+result, DELETE /jobs/ID cancels it or forgets it once done. Paths are taken
+from the root and may not lead out of it. SIGINT or SIGTERM cancel the jobs
+left and stop the server. GET /metrics gives the server's metrics in the
+Prometheus text format, and GET /health answers 503 once the scratch
+directories of runs no longer fit under --tmp-dir, both with the memory and
+the cores the jobs running reserved. --memory-budget and dedicated_core
+hold jobs back as they do the daemon's requests.";
+
+const SERVE_GRPC_NOTES: &str = "\
+Judger/Judge and Judger/JudgeEvents of proto/judge.proto take a request
+with the executable, input and answer inline, up to 16MiB each, or as paths
+under --root, which without one are refused. Limits the request leaves at 0
+are the ones given here. Cancelling a call kills its program. Requests past
+64 waiting for a worker get RESOURCE_EXHAUSTED. --memory-budget holds
+requests back as it does the daemon's, one over it gets INVALID_ARGUMENT.
+SIGINT or SIGTERM cancel the requests left and stop the server.";
+
+// What values have to look like, for error messages
+const TIME: &str = "a time such as 2s, 1.5s or 500ms";
+const SIZE: &str = "a size such as 256m, 64MiB or 1.5g";
+const COUNT: &str = "a positive whole number";
+const PERCENT: &str = "a percentage such as 5% or 2.5";
+// The input that is no input, see --no-stdin
+const NULL_INPUT: &str = "/dev/null";
+
+// Compilers get more than programs do, unless told otherwise
+const COMPILE_TIME_LIMIT: Duration = Duration::from_secs(10);
+const COMPILE_MEMORY_LIMIT: u64 = 1 << 30;
+// Unless told otherwise, stress tests run this many iterations and report
+// progress this often
+const STRESS_ITERATIONS: u64 = 100;
+const STRESS_PROGRESS: u64 = 100;
+// How long the watched files have to stay unchanged before judging again
+const WATCH_QUIET: Duration = Duration::from_millis(300);
+
+const HELP_OPTIONS: [OptSpec; 1] = [
+    OptSpec { names: &["--help", "-h"], value: OptValue::None, help: "Print the help of the command" }
+];
+
+const LOG_OPTIONS: [OptSpec; 2] = [
+    OptSpec {
+        names: &["--verbose", "-v"],
+        value: OptValue::None,
+        help: "Log what the judger does to stderr, as RUST_LOG=debug does"
+    },
+    OptSpec { names: &["-vv"], value: OptValue::None, help: "Log in full detail, as -v -v or RUST_LOG=trace do" }
+];
+
+const LIMIT_OPTIONS: [OptSpec; 8] = [
+    OptSpec {
+        names: &["--time-limit", "--cpu-time-limit"],
+        value: OptValue::Required("TIME"),
+        help: "CPU time limit, counting all threads and children [default: 1s]"
+    },
+    OptSpec {
+        names: &["--real-time-limit", "--wall-time-limit"],
+        value: OptValue::Required("TIME"),
+        help: "Wall time limit [default: 3 times the CPU time limit]"
+    },
+    OptSpec { names: &["--memory-limit"], value: OptValue::Required("SIZE"), help: "Memory limit [default: 100m]" },
+    OptSpec {
+        names: &["--output-limit"],
+        value: OptValue::Required("SIZE"),
+        help: "Largest output, or any other file, the program may write [default: unlimited]"
+    },
+    OptSpec {
+        names: &["--io-limit"],
+        value: OptValue::Required("SIZE"),
+        help: "Most the program may read from and write to disk together [default: unlimited]"
+    },
+    OptSpec {
+        names: &["--stderr-limit"],
+        value: OptValue::Required("SIZE"),
+        help: "Most of the program's stderr to keep, the rest is dropped [default: 1m]"
+    },
+    OptSpec { names: &["--lang"], value: OptValue::Required("LANG"), help: "Scale the limits for the program's language, e.g. java" },
+    OptSpec {
+        names: &["--time-multiplier"],
+        value: OptValue::Required("X"),
+        help: "Scale the time limits by X, whatever the language's factor"
+    }
+];
+
+// Where runs happen, which the self test needs to know too
+const HOST_OPTIONS: [OptSpec; 4] = [
+    OptSpec { names: &["--tmp-dir"], value: OptValue::Required("DIR"), help: "Put the runs' scratch directories under DIR [default: $TMPDIR or /tmp]" },
+    OptSpec {
+        names: &["--cgroup-root"],
+        value: OptValue::Required("DIR"),
+        help: "Enforce memory, task and CPU limits in a cgroup v2 under DIR"
+    },
+    OptSpec {
+        names: &["--calibration"],
+        value: OptValue::Required("FILE"),
+        help: "Scale time limits by the calibration stored in FILE [default: $XDG_STATE_HOME/secure-judger/calibration.json]"
+    },
+    OptSpec { names: &["--no-calibration"], value: OptValue::None, help: "Leave time limits unscaled by a stored calibration" }
+];
+
+const CALIBRATE_OPTIONS: [OptSpec; 1] = [
+    OptSpec {
+        names: &["--auto-calibrate"],
+        value: OptValue::None,
+        help: "Store the calibration for later sessions to scale their time limits by"
+    }
+];
+
+const SANDBOX_OPTIONS: [OptSpec; 6] = [
+    OptSpec { names: &["--max-tasks"], value: OptValue::Required("N"), help: "Tasks the program may have at once in a cgroup [default: 32]" },
+    OptSpec { names: &["--cpu-quota"], value: OptValue::Required("CORES"), help: "Throttle the program to CORES cores in a cgroup" },
+    OptSpec {
+        names: &["--exec-arch"],
+        value: OptValue::Required("ARCH"),
+        help: "Sandbox the program as x86_64, i386 or aarch64 instead of by its ELF header"
+    },
+    OptSpec {
+        names: &["--sandbox"],
+        value: OptValue::Required("MODE"),
+        help: "Seccomp filter: require, best-effort (skipped if unavailable) or disabled [default: require]"
+    },
+    OptSpec {
+        names: &["--join-ns"],
+        value: OptValue::Required("DIR|KIND=PATH"),
+        help: "Run in the namespaces of DIR such as /proc/PID/ns, or the KIND namespace at PATH; repeatable"
+    },
+    OptSpec {
+        names: &["--dry-run"],
+        value: OptValue::Attached("json"),
+        help: "Print what a run would set up instead of running, as text or JSON"
+    }
+];
+
+// How the program, but not a compiler, is set up
+const PROGRAM_OPTIONS: [OptSpec; 8] = [
+    OptSpec { names: &["--scratch-limit"], value: OptValue::Required("SIZE"), help: "Size of the program's private /tmp [default: 64m]" },
+    OptSpec {
+        names: &["--file-io"],
+        value: OptValue::Required("IN:OUT"),
+        help: "The program reads file IN and writes file OUT instead of stdin and stdout"
+    },
+    OptSpec {
+        names: &["--provide"],
+        value: OptValue::Required("SRC:NAME"),
+        help: "Copy file SRC into the program's working directory as NAME, read-only; repeatable"
+    },
+    OptSpec { names: &["--no-copy-exec"], value: OptValue::None, help: "Run the executable in place instead of a private copy" },
+    OptSpec { names: &["--prewarm"], value: OptValue::None, help: "Read the executable into the page cache before each run" },
+    OptSpec {
+        names: &["--env"],
+        value: OptValue::Required("NAME=VALUE"),
+        help: "Set NAME in the program's environment, over TZ=UTC, LANG=C and LC_ALL=C; repeatable"
+    },
+    OptSpec {
+        names: &["--interpreter"],
+        value: OptValue::Required("PATH"),
+        help: "Run scripts with the interpreter at PATH instead of the one their #! line names"
+    },
+    OptSpec {
+        names: &["--strict-paths"],
+        value: OptValue::Attached("ROOT"),
+        help: "Refuse programs in places other users can write to or own, or outside ROOT if given"
+    }
+];
+
+const COMPARE_OPTIONS: [OptSpec; 1] = [
+    OptSpec {
+        names: &["--compare"],
+        value: OptValue::Required("MODE"),
+        help: "Compare exact, by tokens, or as float[:TOLERANCE] [default: exact]"
+    }
+];
+
+const KEEP_OPTIONS: [OptSpec; 1] = [
+    OptSpec { names: &["--keep-output"], value: OptValue::Attached("DIR"), help: "Keep the output of failed runs, in DIR if given" }
+];
+
+const TRANSCRIPT_OPTIONS: [OptSpec; 2] = [
+    OptSpec {
+        names: &["--transcript"],
+        value: OptValue::Required("PATH"),
+        help: "Write every step of the runs to PATH as JSON lines, for appeals"
+    },
+    OptSpec {
+        names: &["--transcript-include-answers"],
+        value: OptValue::None,
+        help: "Put the contents of answers and outputs in the transcript"
+    }
+];
+
+const JUDGE_OPTIONS: [OptSpec; 2] = [
+    OptSpec { names: &["--runs"], value: OptValue::Required("N"), help: "Run the test N times and report the spread" },
+    OptSpec {
+        names: &["--tle-policy"],
+        value: OptValue::Required("any|median"),
+        help: "Whether any run or the median decides on TLE with --runs [default: any]"
+    }
+];
+
+const BATCH_OPTIONS: [OptSpec; 14] = [
+    OptSpec { names: &["--tests"], value: OptValue::Required("DIR"), help: "Judge every INPUT.in in DIR against its answer" },
+    OptSpec {
+        names: &["--manifest"],
+        value: OptValue::Required("FILE"),
+        help: "Judge the tests FILE lists, relative to --tests if given"
+    },
+    OptSpec { names: &["--input-ext"], value: OptValue::Required("EXT"), help: "Extension of inputs in DIR [default: in]" },
+    OptSpec {
+        names: &["--answer-ext"],
+        value: OptValue::Required("EXT[,EXT...]"),
+        help: "Extensions of answers in DIR, the first found counts [default: ans,out]"
+    },
+    OptSpec { names: &["--json"], value: OptValue::None, help: "Report the batch as JSON of the latest schema" },
+    OptSpec {
+        names: &["--format"],
+        value: OptValue::Required("FORMAT"),
+        help: "Report the batch as a table, json or json-v1, pinning the schema [default: table]"
+    },
+    OptSpec { names: &["--quiet", "-q"], value: OptValue::None, help: "Show no progress on stderr while judging" },
+    OptSpec { names: &["--jobs"], value: OptValue::Required("N"), help: "Judge N tests at once [default: 1]" },
+    OptSpec { names: &["--memory-budget"], value: OptValue::Required("SIZE"), help: "Judge at once only tests whose memory limits fit in SIZE" },
+    OptSpec { names: &["--pin-cpus"], value: OptValue::None, help: "Pin every running test to a physical core of its own" },
+    OptSpec {
+        names: &["--cpus"],
+        value: OptValue::Required("LIST"),
+        help: "Pin the running tests to the CPUs of LIST, such as 0-3,8, rather than to the physical cores"
+    },
+    OptSpec {
+        names: &["--overall-timeout"],
+        value: OptValue::Required("TIME"),
+        help: "Time limit of the whole batch, the tests left over are skipped"
+    },
+    OptSpec {
+        names: &["--resume"],
+        value: OptValue::Required("DIR"),
+        help: "Stamp the judged tests in DIR, taking the results of those stamped there already"
+    },
+    OptSpec { names: &["--no-resume"], value: OptValue::None, help: "Judge every test again, stamping it anew with --resume" }
+];
+
+const COMPARE_RUNS_OPTIONS: [OptSpec; 8] = [
+    OptSpec { names: &["--tests"], value: OptValue::Required("DIR"), help: "Judge on every INPUT.in in DIR against its answer" },
+    OptSpec {
+        names: &["--manifest"],
+        value: OptValue::Required("FILE"),
+        help: "Judge on the tests FILE lists, relative to --tests if given"
+    },
+    OptSpec { names: &["--input-ext"], value: OptValue::Required("EXT"), help: "Extension of inputs in DIR [default: in]" },
+    OptSpec {
+        names: &["--answer-ext"],
+        value: OptValue::Required("EXT[,EXT...]"),
+        help: "Extensions of answers in DIR, the first found counts [default: ans,out]"
+    },
+    OptSpec { names: &["--json"], value: OptValue::None, help: "Report the verdicts as JSON of the latest schema" },
+    OptSpec {
+        names: &["--format"],
+        value: OptValue::Required("FORMAT"),
+        help: "Report the verdicts as a table, json or json-v1, pinning the schema [default: table]"
+    },
+    OptSpec { names: &["--quiet", "-q"], value: OptValue::None, help: "Show no progress on stderr while judging" },
+    OptSpec { names: &["--jobs"], value: OptValue::Required("N"), help: "Judge N tests of an executable at once [default: 1]" }
+];
+
+const FAILURE_OPTIONS: [OptSpec; 3] = [
+    OptSpec {
+        names: &["--stop-on-fail", "--stop-on-failure"],
+        value: OptValue::None,
+        help: "Skip the remaining tests once one fails [default of judge --problem]"
+    },
+    OptSpec { names: &["--run-all"], value: OptValue::None, help: "Judge every test, for partial scores [default of batch]" },
+    OptSpec { names: &["--stop-after"], value: OptValue::Required("N"), help: "Skip the remaining tests once N failed" }
+];
+
+const PROBLEM_OPTIONS: [OptSpec; 2] = [
+    OptSpec {
+        names: &["--problem"],
+        value: OptValue::Required("FILE"),
+        help: "Take the limits, comparison and tests from the problem's TOML file"
+    },
+    OptSpec {
+        names: &["--validator"],
+        value: OptValue::Required("PATH"),
+        help: "Check every input with PATH first, which exits with 0 if it is valid"
+    }
+];
+
+const DAEMON_OPTIONS: [OptSpec; 5] = [
+    OptSpec { names: &["--socket"], value: OptValue::Required("PATH"), help: "Listen on the Unix socket PATH" },
+    OptSpec { names: &["--state-dir"], value: OptValue::Required("DIR"), help: "Keep the jobs in DIR, taking up those left after a restart" },
+    OptSpec { names: &["--metrics-listen"], value: OptValue::Required("ADDR"), help: "Serve the metrics over HTTP on ADDR, e.g. 127.0.0.1:9100" },
+    OptSpec { names: &["--jobs"], value: OptValue::Required("N"), help: "Judge N requests at once, queueing the rest [default: 1]" },
+    OptSpec { names: &["--memory-budget"], value: OptValue::Required("SIZE"), help: "Judge at once only requests whose memory limits fit in SIZE" }
+];
+
+const SERVE_OPTIONS: [OptSpec; 4] = [
+    OptSpec { names: &["--listen"], value: OptValue::Required("ADDR"), help: "Address and port to listen on [default: 127.0.0.1:8080]" },
+    OptSpec { names: &["--root"], value: OptValue::Required("DIR"), help: "Only judge with executables, inputs and answers under DIR" },
+    OptSpec { names: &["--jobs"], value: OptValue::Required("N"), help: "Judge N jobs at once, queueing the rest [default: 1]" },
+    OptSpec { names: &["--memory-budget"], value: OptValue::Required("SIZE"), help: "Judge at once only jobs whose memory limits fit in SIZE" }
+];
+
+const GRPC_OPTIONS: [OptSpec; 4] = [
+    OptSpec { names: &["--listen"], value: OptValue::Required("ADDR"), help: "Address and port to listen on [default: 127.0.0.1:50051]" },
+    OptSpec { names: &["--root"], value: OptValue::Required("DIR"), help: "Also take executables, inputs and answers by path under DIR" },
+    OptSpec { names: &["--jobs"], value: OptValue::Required("N"), help: "Judge N requests at once, queueing the rest [default: 1]" },
+    OptSpec { names: &["--memory-budget"], value: OptValue::Required("SIZE"), help: "Judge at once only requests whose memory limits fit in SIZE" }
+];
+
+const CACHE_OPTIONS: [OptSpec; 2] = [
+    OptSpec {
+        names: &["--cache-dir"],
+        value: OptValue::Required("DIR"),
+        help: "Keep results in DIR and reuse them for the same executable, test and limits"
+    },
+    OptSpec { names: &["--no-cache"], value: OptValue::None, help: "Run every test, even with --cache-dir given" }
+];
+
+const CLEAR_CACHE_OPTIONS: [OptSpec; 1] = [
+    OptSpec { names: &["--cache-dir"], value: OptValue::Required("DIR"), help: "The cache directory to empty" }
+];
+
+const WATCH_OPTIONS: [OptSpec; 1] = [
+    OptSpec { names: &["--watch"], value: OptValue::None, help: "Judge again whenever the executable, or the tests directory, changes" }
+];
+
+const RUN_OPTIONS: [OptSpec; 1] = [
+    OptSpec { names: &["--input"], value: OptValue::Required("FILE|-"), help: "Give the program FILE, or the judger's stdin, as input" }
+];
+
+const STDIN_OPTIONS: [OptSpec; 1] = [
+    OptSpec { names: &["--no-stdin"], value: OptValue::None, help: "Give the program /dev/null as input, for tests without one" }
+];
+
+const COMPILE_OPTIONS: [OptSpec; 2] = [
+    OptSpec { names: &["--output"], value: OptValue::Required("FILE"), help: "Where the compiled program goes" },
+    OptSpec {
+        names: &["--artifact"],
+        value: OptValue::Required("NAME"),
+        help: "What the compiler names it in its working directory [default: the name of FILE]"
+    }
+];
+
+const STRESS_OPTIONS: [OptSpec; 7] = [
+    OptSpec {
+        names: &["--gen"],
+        value: OptValue::Required("GEN"),
+        help: "Generator writing an input to stdout, given the seed as its argument"
+    },
+    OptSpec { names: &["--brute"], value: OptValue::Required("BRUTE"), help: "Reference solution whose output counts as the answer" },
+    OptSpec { names: &["--candidate"], value: OptValue::Required("CAND"), help: "Solution looked for an input it gets wrong" },
+    OptSpec { names: &["--iterations"], value: OptValue::Required("N"), help: "Give up after N inputs [default: 100]" },
+    OptSpec { names: &["--seed"], value: OptValue::Required("S"), help: "Seed of the first input, the next get S+1, S+2... [default: 1]" },
+    OptSpec {
+        names: &["--save-dir"],
+        value: OptValue::Required("DIR"),
+        help: "Where the failing input, both outputs and the seed go [default: stress-failure]"
+    },
+    OptSpec { names: &["--progress"], value: OptValue::Required("N"), help: "Report progress every N iterations [default: 100]" }
+];
+
+const DIFF_OPTIONS: [OptSpec; 5] = [
+    OptSpec { names: &["--json"], value: OptValue::None, help: "Report the differences as JSON of the latest schema" },
+    OptSpec {
+        names: &["--format"],
+        value: OptValue::Required("FORMAT"),
+        help: "Report the differences as a table, json or json-v1, pinning the schema [default: table]"
+    },
+    OptSpec {
+        names: &["--time-tolerance"],
+        value: OptValue::Required("PERCENT"),
+        help: "Count times that moved by PERCENT at most as unchanged [default: 5%]"
+    },
+    OptSpec {
+        names: &["--min-time-change"],
+        value: OptValue::Required("TIME"),
+        help: "Count times that moved by TIME at most as unchanged too [default: 10ms]"
+    },
+    OptSpec {
+        names: &["--memory-tolerance"],
+        value: OptValue::Required("PERCENT"),
+        help: "Count memory that moved by PERCENT at most as unchanged [default: 5%]"
+    }
+];
+
+const ANSWER_OPTIONS: [OptSpec; 5] = [
+    OptSpec { names: &["--tests"], value: OptValue::Required("DIR"), help: "Make the answers of the inputs in DIR" },
+    OptSpec { names: &["--solution"], value: OptValue::Required("PATH"), help: "Trusted solution whose output becomes the answer" },
+    OptSpec { names: &["--input-ext"], value: OptValue::Required("EXT"), help: "Extension of inputs in DIR [default: in]" },
+    OptSpec {
+        names: &["--answer-ext"],
+        value: OptValue::Required("EXT[,EXT...]"),
+        help: "Extensions of answers in DIR, the first is written [default: ans,out]"
+    },
+    OptSpec { names: &["--force"], value: OptValue::None, help: "Replace the answers that exist too" }
+];
+
+/// A subcommand with its arguments and options
+struct Subcommand {
+    name: &'static str,
+    about: &'static str,
+    // Positional arguments. With `program` set, the last is the executable
+    // and everything after it are its arguments.
+    positionals: &'static [&'static str],
+    program: bool,
+    options: &'static [&'static [OptSpec]],
+    // Said in the help, after the options
+    notes: &'static str
+}
+
+static SUBCOMMANDS: [Subcommand; 15] = [
+    Subcommand {
+        name: "judge",
+        about: "Judge a program on a single test",
+        positionals: &["<stdin file|->", "<standard answer file>", "<executable>"],
+        program: true,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &STDIN_OPTIONS, &LIMIT_OPTIONS, &HOST_OPTIONS, &SANDBOX_OPTIONS, &PROGRAM_OPTIONS, &COMPARE_OPTIONS, &KEEP_OPTIONS, &TRANSCRIPT_OPTIONS, &PROBLEM_OPTIONS, &FAILURE_OPTIONS, &JUDGE_OPTIONS, &CACHE_OPTIONS, &WATCH_OPTIONS],
+        notes: "\
+With --problem, the tests of the problem are judged as by batch and only
+<executable> is given. Options given override the problem file's. The
+tests after the first failed one are skipped unless --run-all is given.
+With --no-stdin, <stdin file> is left out and the program reads /dev/null,
+as it does with /dev/null given.
+With --watch, the test is judged again each time the executable is written,
+a line per run, until Ctrl-C.
+With --cache-dir, a test judged before with the same executable, input,
+answer and limits gets its stored result without running. --runs judges
+without the cache, as do --keep-output and --transcript."
+    },
+    Subcommand {
+        name: "batch",
+        about: "Judge a program on a directory or a manifest of tests",
+        positionals: &["<executable>"],
+        program: true,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &LIMIT_OPTIONS, &HOST_OPTIONS, &SANDBOX_OPTIONS, &PROGRAM_OPTIONS, &COMPARE_OPTIONS, &KEEP_OPTIONS, &TRANSCRIPT_OPTIONS, &PROBLEM_OPTIONS, &BATCH_OPTIONS, &FAILURE_OPTIONS, &CACHE_OPTIONS, &WATCH_OPTIONS],
+        notes: "\
+Either --tests, --manifest or the tests of --problem say what is judged.
+Options given override the problem file's.
+Every test is judged unless --stop-on-fail or --stop-after N is given, or
+failures in the [judging] table of the problem file: the tests after the
+failure that stops the batch are then skipped, tests run in parallel with it
+cut short.
+With --watch, the tests are judged again each time the executable or a file
+of the --tests directory is written, a line per test, until Ctrl-C.
+With --cache-dir, the tests judged before with the same executable, input,
+answer and limits get their stored results without running.
+With --resume, a batch run again keeps the results of the tests it judged
+before with the same executable and setup, as after being interrupted, and
+judges the rest. Changing either makes stamps stale, as does --no-resume.
+While judging, the progress goes to stderr: a line redrawn in place on a
+terminal, otherwise a line every 10s and one at the end. --quiet drops it."
+    },
+    Subcommand {
+        name: "compare-runs",
+        about: "Judge several executables on the same tests and show their verdicts side by side",
+        positionals: &["<executable>", "<executable>..."],
+        program: false,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &LIMIT_OPTIONS, &HOST_OPTIONS, &SANDBOX_OPTIONS, &PROGRAM_OPTIONS, &COMPARE_OPTIONS, &PROBLEM_OPTIONS, &COMPARE_RUNS_OPTIONS],
+        notes: "\
+The executables are judged one after another on every test, alike and
+without arguments, with the answers read once for all of them. The table
+has a column per executable and marks the tests whose verdicts differ.
+The exit code is 0 when the executables agree on every test and 1 when
+they don't."
+    },
+    Subcommand {
+        name: "diff-results",
+        about: "Show which tests changed between two batch reports, and by how much",
+        positionals: &["<old report>", "<new report>"],
+        program: false,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &DIFF_OPTIONS],
+        notes: "\
+The reports are what batch --json writes, their tests paired by name. A test
+changed if its verdict did, or what the verdict says, such as the exit code
+of RNZ or where the output went wrong, or if its real or CPU time or memory
+moved by more than the tolerance. A time that moved by --min-time-change at
+most is unchanged whatever its percent. Tests only one report has are listed
+apart. The exit code is 0 when nothing changed and 1 when something did."
+    },
+    Subcommand {
+        name: "run",
+        about: "Run a program in the sandbox without judging it",
+        positionals: &["<executable>"],
+        program: true,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &RUN_OPTIONS, &STDIN_OPTIONS, &LIMIT_OPTIONS, &HOST_OPTIONS, &SANDBOX_OPTIONS, &PROGRAM_OPTIONS],
+        notes: "The program's output goes to stdout and its stderr to stderr, the result\nto stderr after them. Without --input it reads /dev/null."
+    },
+    Subcommand {
+        name: "compile",
+        about: "Run a compiler in the sandbox and keep what it makes",
+        positionals: &["<compiler>"],
+        program: true,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &COMPILE_OPTIONS, &LIMIT_OPTIONS, &HOST_OPTIONS, &SANDBOX_OPTIONS],
+        notes: "\
+The compiler runs in place, allowed to start programs and write files, in a
+scratch directory it has instead of a private /tmp. Give it its sources as
+absolute paths. Its limits are 10s and 1g unless given."
+    },
+    Subcommand {
+        name: "stress",
+        about: "Look for an input a solution gets wrong, against a brute force one",
+        positionals: &[],
+        program: false,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &STRESS_OPTIONS, &LIMIT_OPTIONS, &HOST_OPTIONS, &COMPARE_OPTIONS],
+        notes: "\
+Each iteration runs the generator with its seed, then the brute force and the
+candidate on what it wrote, which have to agree by the comparison. The first
+iteration they don't ends the test. All three are sandboxed alike, under the
+limits given. The generator or the brute force failing stops the test."
+    },
+    Subcommand {
+        name: "gen-answers",
+        about: "Make the missing answers of a tests directory with a trusted solution",
+        positionals: &[],
+        program: false,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &ANSWER_OPTIONS, &LIMIT_OPTIONS, &HOST_OPTIONS, &PROGRAM_OPTIONS],
+        notes: "\
+Inputs that have an answer by any of the extensions are left alone unless
+--force is given. A test the solution fails on gets no answer, those tests
+are listed at the end."
+    },
+    Subcommand {
+        name: "daemon",
+        about: "Judge the requests of clients on a Unix socket until told to stop",
+        positionals: &[],
+        program: false,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &DAEMON_OPTIONS, &LIMIT_OPTIONS, &HOST_OPTIONS, &PROGRAM_OPTIONS, &COMPARE_OPTIONS],
+        notes: DAEMON_NOTES
+    },
+    Subcommand {
+        name: "serve",
+        about: "Judge jobs submitted over HTTP until SIGINT or SIGTERM",
+        positionals: &[],
+        program: false,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &SERVE_OPTIONS, &LIMIT_OPTIONS, &HOST_OPTIONS, &PROGRAM_OPTIONS, &COMPARE_OPTIONS],
+        notes: SERVE_NOTES
+    },
+    Subcommand {
+        name: "serve-grpc",
+        about: "Judge requests of the gRPC service until SIGINT or SIGTERM",
+        positionals: &[],
+        program: false,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &GRPC_OPTIONS, &LIMIT_OPTIONS, &HOST_OPTIONS, &PROGRAM_OPTIONS, &COMPARE_OPTIONS],
+        notes: SERVE_GRPC_NOTES
+    },
+    Subcommand {
+        name: "clear-cache",
+        about: "Remove the stored results of a cache directory",
+        positionals: &[],
+        program: false,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &CLEAR_CACHE_OPTIONS],
+        notes: "Only the cache's entries are removed, other files in the directory stay."
+    },
+    Subcommand {
+        name: "check",
+        about: "Compare an output file with an answer, running nothing",
+        positionals: &["<output file>", "<standard answer file>"],
+        program: false,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &COMPARE_OPTIONS],
+        notes: ""
+    },
+    Subcommand {
+        name: "selftest",
+        about: "Check that the sandbox holds up on this host",
+        positionals: &[],
+        program: false,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &HOST_OPTIONS],
+        notes: ""
+    },
+    Subcommand {
+        name: "calibrate",
+        about: "Time a reference workload in the sandbox to work out the factor of time limits here",
+        positionals: &[],
+        program: false,
+        options: &[&HELP_OPTIONS, &LOG_OPTIONS, &HOST_OPTIONS, &CALIBRATE_OPTIONS],
+        notes: CALIBRATE_NOTES
+    }
+];
+
+// Exit codes of a batch
+const EXIT_ACCEPTED: i32 = 0;
+// The program failed a test
+const EXIT_REJECTED: i32 = 1;
+// Nothing was run, the options, tests or executable are wrong
+const EXIT_SETUP: i32 = 2;
+// The judger itself failed or was cut short
+const EXIT_JUDGE_FAILED: i32 = 3;
+
+/// Where a batch finds its tests and how it reports them
+#[derive(Default)]
+struct BatchOptions {
+    dir: Option<PathBuf>,
+    // Lists the tests instead of finding them in dir, whose paths are
+    // relative to dir if given
+    manifest: Option<PathBuf>,
+    // The layout's defaults where None
+    input_ext: Option<String>,
+    answer_exts: Option<Vec<String>>,
+    // The problem file, whose tests are judged unless dir or manifest is
+    // given, and which sets what the options leave out
+    problem: Option<PathBuf>,
+    // Listed by the problem file
+    tests: Vec<TestCase>,
+    // The schema of the JSON report, a table where None
+    json: Option<Schema>,
+    // Leaves out the progress on stderr
+    quiet: bool,
+    // As the command line gives it, over the problem file's
+    failure_policy: Option<FailurePolicy>,
+    // When neither says, which depends on the subcommand
+    default_failure_policy: FailurePolicy
+}
+
+impl BatchOptions {
+    fn load_tests(&self) -> io::Result<Vec<TestCase>> {
+        match (&self.manifest, &self.dir) {
+            (Some(manifest), dir) => problem::test_cases_from_manifest(manifest, dir.as_deref()),
+            (None, Some(dir)) => problem::test_cases_in(dir, &self.layout()),
+            (None, None) if !self.tests.is_empty() => {
+                problem::check_readable(&self.tests)?;
+                Ok(self.tests.clone())
+            },
+            (None, None) => Err(io::Error::new(io::ErrorKind::InvalidInput, "no tests to judge, give --tests, --manifest or a problem file with tests"))
+        }
+    }
+
+    fn layout(&self) -> TestLayout {
+        let mut layout = TestLayout::default();
+        if let Some(ext) = &self.input_ext {
+            layout.input_ext = ext.clone();
+        }
+        if let Some(exts) = &self.answer_exts {
+            layout.answer_exts = exts.clone();
+        }
+        layout
+    }
+
+    fn check(&self, command: &str) -> Result<(), CliError> {
+        let layout = self.layout();
+        let dot_free = |ext: &String| !ext.is_empty() && !ext.contains(['.', '/']);
+        if !dot_free(&layout.input_ext) || !layout.answer_exts.iter().all(dot_free) {
+            return Err(CliError::Usage(String::from("extensions are given without their dot, e.g. --answer-ext ans,out")));
+        }
+        if layout.answer_exts.contains(&layout.input_ext) {
+            return Err(CliError::Usage(String::from("inputs and answers need different extensions")));
+        }
+        if self.dir.is_none() && self.manifest.is_none() && self.problem.is_none() {
+            return Err(CliError::Usage(format!("{command} needs --tests DIR, --manifest FILE or --problem FILE")));
+        }
+        Ok(())
+    }
+
+    /*
+     *  Take the tests of the problem file, unless the command line gave
+     *  some, and the layout the command line left out
+     */
+    fn apply_problem(&mut self, config: &mut ProblemConfig) {
+        if self.dir.is_none() && self.manifest.is_none() {
+            self.dir = config.tests_dir.take();
+            self.manifest = config.manifest.take();
+            self.tests = std::mem::take(&mut config.tests);
+        }
+        self.input_ext = self.input_ext.take().or(config.input_ext.take());
+        self.answer_exts = self.answer_exts.take().or(config.answer_exts.take());
+        self.failure_policy = self.failure_policy.or(config.failure_policy);
+    }
+
+    fn failure_policy(&self) -> FailurePolicy {
+        self.failure_policy.unwrap_or(self.default_failure_policy)
+    }
+}
+
+/// What the command line asks for
+enum Command {
+    // The overall help, or that of a subcommand
+    Help(Option<&'static Subcommand>),
+    Version,
+    SelfTest,
+    // Store the calibration with --auto-calibrate
+    Calibrate { store: bool },
+    // A single test, its input "-" for the judger's own stdin
+    Judge { input: String, answer: PathBuf },
+    Batch(BatchOptions),
+    // The executables as given
+    CompareRuns { batch: BatchOptions, execs: Vec<String> },
+    // A table where json is None
+    DiffResults { old: PathBuf, new: PathBuf, tolerance: DiffTolerance, json: Option<Schema> },
+    // The program's input, if it gets any
+    Run { input: Option<String> },
+    Compile { output: PathBuf, artifact: String },
+    Check { output: PathBuf, answer: PathBuf },
+    Stress(StressOptions),
+    GenAnswers { dir: PathBuf, layout: TestLayout, solution: String, force: bool },
+    ClearCache { dir: PathBuf },
+    Daemon { socket: PathBuf, metrics: Option<String>, state: Option<PathBuf> },
+    Serve { listen: String, root: PathBuf },
+    ServeGrpc { listen: String, root: Option<PathBuf> }
+}
+
+/// The programs of a stress test and how long it goes
+struct StressOptions {
+    generator: String,
+    brute: String,
+    candidate: String,
+    iterations: u64,
+    seed: u64,
+    save_dir: PathBuf,
+    progress: u64
+}
+
+/// Options of the judger, None where the session's default applies
+#[derive(Default)]
+struct JudgeOptions {
+    time_limit: Option<Duration>,
+    wall_limit: Option<Duration>,
+    memory_limit: Option<u64>,
+    output_limit: Option<u64>,
+    io_limit: Option<u64>,
+    stderr_limit: Option<u64>,
+    multipliers: Option<LimitMultipliers>,
+    // Set explicitly, so that --lang doesn't override it whatever the order
+    time_multiplier: Option<f64>,
+    tmp_dir: Option<PathBuf>,
+    scratch_limit: Option<u64>,
+    cgroup_root: Option<PathBuf>,
+    // Where the calibration is stored, the default place if None
+    calibration: Option<PathBuf>,
+    no_calibration: bool,
+    max_tasks: Option<u64>,
+    cpu_quota: Option<f64>,
+    exec_arch: Option<ExecArch>,
+    sandbox: Option<SandboxStrength>,
+    // In the order given, later ones overriding earlier ones of a kind
+    join_ns: Vec<JoinNamespace>,
+    io_mode: Option<IoMode>,
+    // Copied into the working directory, as (source, name there)
+    provide: Vec<(PathBuf, String)>,
+    // Set in the program's environment, later ones winning
+    env: Vec<(String, String)>,
+    comparison: Option<Comparison>,
+    validator: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    // Overrides cache_dir
+    no_cache: bool,
+    no_copy_exec: bool,
+    prewarm: bool,
+    // Runs scripts, whatever their #! line says
+    interpreter: Option<PathBuf>,
+    strict_paths: Option<StrictPaths>,
+    keep_output: bool,
+    output_dir: Option<PathBuf>,
+    // Where every step of the runs is recorded, answers included if
+    // transcript_answers
+    transcript: Option<PathBuf>,
+    transcript_answers: bool,
+    // Where the batch stamps the tests it judged
+    resume: Option<PathBuf>,
+    // Overrides resume for reading the stamps, which are still written
+    no_resume: bool,
+    jobs: Option<usize>,
+    pin_cpus: bool,
+    // The CPUs to pin to instead of the physical cores, which implies pin_cpus
+    cpus: Option<Vec<usize>>,
+    // Most the memory limits of the daemon's or the server's jobs running
+    // may sum to
+    memory_budget: Option<u64>,
+    runs: Option<usize>,
+    tle_policy: TlePolicy,
+    overall_timeout: Option<Duration>,
+    // Judge again whenever the executable changes
+    watch: bool,
+    // Print what a run would set up instead of running
+    dry_run: Option<PlanFormat>,
+    // How many times --verbose was given
+    verbosity: u8
+}
+
+impl JudgeOptions {
+    /*
+     *  Take the limits and the setup of the problem file where the command
+     *  line left them out
+     */
+    fn apply_problem(&mut self, config: &mut ProblemConfig) {
+        self.time_limit = self.time_limit.or(config.time_limit);
+        self.wall_limit = self.wall_limit.or(config.wall_limit);
+        self.memory_limit = self.memory_limit.or(config.memory_limit);
+        self.output_limit = self.output_limit.or(config.output_limit);
+        self.multipliers = self.multipliers.or(config.multipliers);
+        self.comparison = self.comparison.or(config.comparison);
+        self.io_mode = self.io_mode.take().or(config.io_mode.take());
+        self.validator = self.validator.take().or(config.validator.take());
+    }
+}
+
+struct CommandLine {
+    command: Command,
+    options: JudgeOptions,
+    // The executable as given, which is the program's argv[0], and the
+    // program's arguments
+    exec_args: Vec<String>
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().collect();
+
+    // The self test runs this binary inside the sandbox as its probes
+    if args.len() == 3 && args[1] == selftest::PROBE_ARG {
+        selftest::probe_main(&args[2]);
+    }
+    // And calibrate as its workload
+    if args.len() == 2 && args[1] == calibrate::WORKLOAD_ARG {
+        calibrate::workload_main();
+    }
+
+    let program = match args.is_empty() {
+        true => String::from(env!("CARGO_PKG_NAME")),
+        false => args.remove(0)
+    };
+    // Usage errors point to the help of the subcommand they are about
+    let help = match args.first().filter(|first| SUBCOMMANDS.iter().any(|subcommand| subcommand.name == *first)) {
+        Some(name) => format!("{program} {name} --help"),
+        None => format!("{program} --help")
+    };
+    let CommandLine { command, mut options, exec_args } = match parse_args(&program, args) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("error: {e}");
+            eprintln!("Run '{help}' for usage");
+            std::process::exit(EXIT_SETUP);
+        }
+    };
+    // RUST_LOG only counts without --verbose
+    let level = match options.verbosity {
+        0 => match env::var("RUST_LOG") {
+            Ok(value) => log::env_level(&value),
+            // So that running without the seccomp filter is never silent
+            Err(_) => Some(log::Level::Warn)
+        },
+        1 => Some(log::Level::Debug),
+        _ => Some(log::Level::Trace)
+    };
+    if let Some(level) = level {
+        log::set_logger(Box::new(log::StderrLogger::new()), level);
+    }
+    // Looked up before anything is set up, the problem file's validator is
+    // a path already
+    let setting_up = !matches!(command, Command::Help(_) | Command::Version);
+    if let Some(validator) = options.validator.as_ref().filter(|_| setting_up) {
+        let Some(path) = find_program(&validator.to_string_lossy()) else {
+            std::process::exit(EXIT_SETUP);
+        };
+        options.validator = Some(path);
+    }
+    if let Some(interpreter) = options.interpreter.as_ref().filter(|_| setting_up) {
+        let Some(path) = find_program(&interpreter.to_string_lossy()) else {
+            std::process::exit(EXIT_SETUP);
+        };
+        options.interpreter = Some(path);
+    }
+    // Before any session is built, nor the environment of results collected
+    if setting_up && !matches!(command, Command::SelfTest | Command::Calibrate { .. }) {
+        install_calibration(&options);
+    }
+    let (single, mut batch) = match command {
+        Command::Help(None) => {
+            print_help(&program);
+            return;
+        },
+        Command::Help(Some(subcommand)) => {
+            print_subcommand_help(&program, subcommand);
+            return;
+        },
+        Command::Version => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            return;
+        },
+        Command::SelfTest => {
+            match selftest::run(options.cgroup_root, options.tmp_dir) {
+                Ok(true) => return,
+                Ok(false) => println!("Self test failed"),
+                Err(e) => println!("Self test could not run: {e}")
+            }
+            std::process::exit(1);
+        },
+        Command::Calibrate { store } => std::process::exit(calibrate(&options, store)),
+        Command::Check { output, answer } => std::process::exit(check_output(&output, &answer, options.comparison.unwrap_or_default())),
+        Command::DiffResults { old, new, tolerance, json } => std::process::exit(diff_results(&old, &new, &tolerance, json)),
+        Command::Run { input } => finish(run_program(&options, input, &exec_args)),
+        Command::Compile { output, artifact } => finish(compile(&options, &output, &artifact, &exec_args)),
+        Command::Stress(stress) => finish(stress_test(&options, &stress)),
+        Command::GenAnswers { dir, layout, solution, force } => {
+            finish(gen_answers(&options, &dir, &layout, &solution, force));
+        },
+        Command::ClearCache { dir } => std::process::exit(clear_cache(&dir)),
+        Command::Daemon { socket, metrics, state } => std::process::exit(daemon(&options, &socket, metrics.as_deref(), state.as_deref())),
+        Command::Serve { listen, root } => std::process::exit(serve(&options, &listen, &root)),
+        Command::ServeGrpc { listen, root } => std::process::exit(serve_grpc(&options, &listen, root)),
+        Command::CompareRuns { mut batch, execs } => {
+            apply_problem(&mut options, &mut batch);
+            finish(compare_runs(&options, &batch, &execs));
+        },
+        Command::Judge { input, answer } => (Some((input, answer)), None),
+        Command::Batch(batch) => (None, Some(batch))
+    };
+    if let Some(batch) = batch.as_mut() {
+        apply_problem(&mut options, batch);
+    }
+
+    if options.watch {
+        std::process::exit(watch(&options, single, batch, &exec_args));
+    }
+
+    let Some(exec_path) = find_program(&exec_args[0]) else {
+        std::process::exit(EXIT_SETUP);
+    };
+    let exec_args: Vec<&str> = exec_args.iter().map(String::as_str).collect();
+    let mut builder = session_builder(&options, exec_path, SandboxPolicy::default());
+    if let Some((input, answer)) = single {
+        builder = builder.input(input_source(&input)).answer(answer);
+    }
+    let progress = batch.as_ref().filter(|batch| !batch.quiet).map(|_| Arc::new(Progress::new()));
+    if let Some(progress) = &progress {
+        builder = builder.observer(progress.clone());
+    }
+    if let Some(path) = &options.transcript {
+        match Transcript::create(path, options.transcript_answers) {
+            Ok(transcript) => builder = builder.observer(Arc::new(transcript)),
+            Err(e) => {
+                println!("Cannot write the transcript {}: {e}", path.display());
+                std::process::exit(EXIT_SETUP);
+            }
+        }
+    }
+    let session = match builder.build() {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Invalid judging setup: {e}");
+            if batch.is_some() {
+                std::process::exit(EXIT_SETUP);
+            }
+            return;
+        }
+    };
+    // The tests are checked before anything runs, in a dry run too
+    let cases = match &batch {
+        Some(options) => match options.load_tests() {
+            Ok(x) => Some(x),
+            Err(e) => {
+                println!("Cannot load tests: {e}");
+                std::process::exit(EXIT_SETUP);
+            }
+        },
+        None => None
+    };
+    if let Some(format) = options.dry_run {
+        print_plan(&session, format, &exec_args);
+        return;
+    }
+    if let (Some(batch), Some(cases)) = (batch, cases) {
+        let mut judge = ProblemJudge::new(session, cases)
+            .with_failure_policy(batch.failure_policy())
+            .with_pin_cpus(options.pin_cpus);
+        if let Some(cpus) = &options.cpus {
+            judge = judge.with_cpus(cpus.clone());
+        }
+        if let Some(limit) = options.overall_timeout {
+            judge = judge.with_overall_deadline(limit);
+        }
+        if let Some(bytes) = options.memory_budget {
+            judge = judge.with_memory_budget(bytes);
+        }
+        match stamps(&options) {
+            Ok(Some(stamps)) => judge = judge.with_stamps(stamps),
+            Ok(None) => {},
+            Err(e) => {
+                println!("Cannot resume: {e}");
+                std::process::exit(EXIT_SETUP);
+            }
+        }
+        if let Some(progress) = &progress {
+            progress.begin(judge.cases().len());
+        }
+        let verbose = options.verbosity > 0;
+        finish(judge_tests(&judge, options.jobs.unwrap_or(1), &exec_args, batch.json, verbose, progress.as_deref()));
+    }
+    if let Some(runs) = options.runs.filter(|&runs| runs > 1) {
+        judge_repeated(&session, runs, options.tle_policy, &exec_args);
+        finish(EXIT_ACCEPTED);
+    }
+    let result = match session.run_judge(&exec_args) {
+        Ok(x) => x,
+        Err(e) => {
+            print_run_error(e.as_ref());
+            return;
+        }
+    };
+
+    io::stderr().write_all(&result.stderr).unwrap_or_default();
+    if result.accepted() {
+        println!("Congratulations, accepted!");
+    }
+    println!("RESULT BEGIN>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>");
+    println!("{result}");
+    // Where it was judged, as in the JSON of a result
+    if let (true, Some(environment)) = (options.verbosity > 0, result.environment) {
+        println!("{environment}");
+    }
+    println!("RESULT END>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>");
+    if let Some(dir) = &result.kept_dir {
+        println!("Run files kept in {}", dir.display());
+    } else if let Some(path) = &result.output_path {
+        println!("Output kept as {}", path.display());
+    }
+    finish(EXIT_ACCEPTED);
+}
+
+/*
+ *  Take what the command line left out from the batch's problem file, if
+ *  it has one, exiting if the file can't be read
+ */
+fn apply_problem(options: &mut JudgeOptions, batch: &mut BatchOptions) {
+    let Some(path) = batch.problem.clone() else {
+        return;
+    };
+    let mut config = match ProblemConfig::load(&path) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Invalid problem file: {e}");
+            std::process::exit(EXIT_SETUP);
+        }
+    };
+    options.apply_problem(&mut config);
+    batch.apply_problem(&mut config);
+}
+
+/*
+ *  The stamps of --resume, read unless --no-resume says otherwise
+ */
+fn stamps(options: &JudgeOptions) -> io::Result<Option<BatchStamps>> {
+    options.resume.clone().map(|dir| BatchStamps::open(dir, !options.no_resume)).transpose()
+}
+
+/*
+ *  Exit with `code`, unless a signal cut the runs short. Then the exit
+ *  code is 128 plus the signal, as shells make it.
+ */
+fn finish(code: i32) -> ! {
+    if let Some(signal) = interrupt::interrupted() {
+        eprintln!("Judging interrupted by {}", interrupt::signal_name(signal));
+        std::process::exit(128 + signal);
+    }
+    std::process::exit(code);
+}
+
+/*
+ *  Make sense of the judger's arguments, without the judger's own name.
+ *  The first of them picks the subcommand. Without one they are those of
+ *  judge, or of batch for "tests DIR", as they were before subcommands.
+ */
+fn parse_args(program: &str, mut args: Vec<String>) -> Result<CommandLine, CliError> {
+    let only = |command: Command| CommandLine { command, options: JudgeOptions::default(), exec_args: Vec::new() };
+    let Some(first) = args.first() else {
+        return Err(CliError::Usage(String::from("missing a command, such as judge")));
+    };
+    match first.as_str() {
+        "--help" | "-h" => return Ok(only(Command::Help(None))),
+        "help" => {
+            let subcommand = match args.get(1) {
+                Some(name) => Some(find_subcommand(name)?),
+                None => None
+            };
+            return Ok(only(Command::Help(subcommand)));
+        },
+        "--version" | "-V" => return Ok(only(Command::Version)),
+        _ => {}
+    }
+    if let Some(subcommand) = SUBCOMMANDS.iter().find(|subcommand| subcommand.name == first) {
+        args.remove(0);
+        return parse_command_line(subcommand, args);
+    }
+    match first.as_str() {
+        "tests" => {
+            eprintln!("warning: 'tests DIR' is deprecated, use '{program} batch --tests DIR ...'");
+            args[0] = String::from("--tests");
+            parse_command_line(find_subcommand("batch")?, args)
+        },
+        _ => {
+            eprintln!("warning: judging without a subcommand is deprecated, use '{program} judge ...'");
+            parse_command_line(find_subcommand("judge")?, args)
+        }
+    }
+}
+
+fn find_subcommand(name: &str) -> Result<&'static Subcommand, CliError> {
+    SUBCOMMANDS.iter()
+        .find(|subcommand| subcommand.name == name)
+        .ok_or_else(|| CliError::Usage(format!("unknown command {name}")))
+}
+
+/*
+ *  Parse the arguments of `subcommand`, which only knows its own options.
+ *  With a program, the last positional argument is its executable and
+ *  the rest of the arguments are the program's.
+ */
+fn parse_command_line(subcommand: &'static Subcommand, args: Vec<String>) -> Result<CommandLine, CliError> {
+    let mut parser = ArgParser::new(args, subcommand.options);
+    let mut options = JudgeOptions::default();
+    let mut batch = BatchOptions::default();
+    let mut input: Option<String> = None;
+    let mut no_stdin = false;
+    let mut output: Option<PathBuf> = None;
+    let mut artifact: Option<String> = None;
+    // The generator, brute force and candidate of a stress test
+    let mut stress_programs: [Option<String>; 3] = [None, None, None];
+    let mut iterations = STRESS_ITERATIONS;
+    let mut seed: u64 = 1;
+    let mut save_dir = PathBuf::from("stress-failure");
+    let mut progress = STRESS_PROGRESS;
+    let mut solution: Option<String> = None;
+    let mut force = false;
+    let mut auto_calibrate = false;
+    let mut tolerance = DiffTolerance::default();
+    let mut socket: Option<PathBuf> = None;
+    let mut state: Option<PathBuf> = None;
+    let mut metrics: Option<String> = None;
+    let mut listen: Option<String> = None;
+    let mut root: Option<PathBuf> = None;
+    let mut positionals: Vec<String> = Vec::new();
+    let mut exec_args: Vec<String> = Vec::new();
+    while let Some(arg) = parser.next_arg()? {
+        let (spec, value) = match arg {
+            Arg::Opt(spec, value) => (spec, value),
+            Arg::Positional(arg) => {
+                positionals.push(arg);
+                if subcommand.program && positionals.len() == expected_positionals(subcommand, &batch, no_stdin).len() {
+                    exec_args = positionals.split_off(positionals.len() - 1);
+                    exec_args.extend(parser.rest());
+                    break;
+                }
+                continue;
+            }
+        };
+        let name = spec.name();
+        let text = value.as_deref().unwrap_or_default();
+        match name {
+            "--help" => return Ok(CommandLine { command: Command::Help(Some(subcommand)), options, exec_args }),
+            "--verbose" => options.verbosity = options.verbosity.saturating_add(1),
+            "-vv" => options.verbosity = options.verbosity.saturating_add(2),
+            "--time-limit" => options.time_limit = Some(cli::parse_checked(name, text, positive_duration)?),
+            "--real-time-limit" => options.wall_limit = Some(cli::parse_checked(name, text, positive_duration)?),
+            "--memory-limit" => options.memory_limit = Some(cli::parse_checked(name, text, positive_size)?),
+            "--output-limit" => options.output_limit = Some(cli::parse_checked(name, text, positive_size)?),
+            "--io-limit" => options.io_limit = Some(cli::parse_checked(name, text, positive_size)?),
+            "--stderr-limit" => options.stderr_limit = Some(cli::parse_checked(name, text, utils::parse_size)?),
+            "--lang" => match language::find(text) {
+                Some(lang) => options.multipliers = Some(lang.limit_multipliers),
+                None => {
+                    let names: Vec<&str> = language::LANGUAGES.iter().map(|lang| lang.name).collect();
+                    return Err(CliError::Usage(format!("unknown language {text}, expected one of {}", names.join(", "))));
+                }
+            },
+            "--time-multiplier" => {
+                options.time_multiplier = Some(cli::parse_value(name, text, positive_number, "a positive number")?);
+            },
+            "--tmp-dir" => options.tmp_dir = Some(PathBuf::from(text)),
+            "--scratch-limit" => options.scratch_limit = Some(cli::parse_checked(name, text, positive_size)?),
+            "--cgroup-root" => options.cgroup_root = Some(PathBuf::from(text)),
+            "--calibration" => options.calibration = Some(PathBuf::from(text)),
+            "--no-calibration" => options.no_calibration = true,
+            "--auto-calibrate" => auto_calibrate = true,
+            "--max-tasks" => options.max_tasks = Some(cli::parse_value(name, text, positive_count, COUNT)?),
+            "--cpu-quota" => {
+                options.cpu_quota = Some(cli::parse_value(name, text, positive_number, "a positive number of cores")?);
+            },
+            // For scripts, whose interpreter decides the architecture
+            "--exec-arch" => {
+                options.exec_arch = Some(cli::parse_value(name, text, ExecArch::from_name, "x86_64, i386 or aarch64")?);
+            },
+            "--sandbox" => {
+                options.sandbox = Some(cli::parse_value(name, text, SandboxStrength::from_name, "require, best-effort or disabled")?);
+            },
+            "--join-ns" => options.join_ns.extend(cli::parse_value(name, text, parse_join_ns, "a directory such as /proc/PID/ns or KIND=PATH")?),
+            "--provide" => options.provide.push(cli::parse_value(name, text, parse_provide, "a file and a name such as words.txt:dict.txt")?),
+            "--env" => options.env.push(cli::parse_value(name, text, parse_env, "a variable and its value such as TZ=Asia/Tokyo")?),
+            "--file-io" => {
+                options.io_mode = Some(cli::parse_value(name, text, parse_file_io, "file names such as problem.in:problem.out")?);
+            },
+            "--compare" => {
+                options.comparison = Some(cli::parse_value(name, text, Comparison::from_name, "exact, tokens, float or float:TOLERANCE")?);
+            },
+            "--no-copy-exec" => options.no_copy_exec = true,
+            "--prewarm" => options.prewarm = true,
+            "--interpreter" => options.interpreter = Some(PathBuf::from(text)),
+            "--strict-paths" => options.strict_paths = Some(StrictPaths { root: value.map(PathBuf::from), ..StrictPaths::default() }),
+            "--keep-output" => {
+                options.keep_output = true;
+                options.output_dir = value.map(PathBuf::from);
+            },
+            "--transcript" => options.transcript = Some(PathBuf::from(text)),
+            "--transcript-include-answers" => options.transcript_answers = true,
+            "--stop-on-fail" => batch.failure_policy = Some(FailurePolicy::StopOnFirst),
+            "--run-all" => batch.failure_policy = Some(FailurePolicy::RunAll),
+            "--stop-after" => batch.failure_policy = Some(FailurePolicy::StopAfter(cli::parse_value(name, text, positive_count, COUNT)?)),
+            "--resume" => options.resume = Some(PathBuf::from(text)),
+            "--no-resume" => options.no_resume = true,
+            "--jobs" => options.jobs = Some(cli::parse_value(name, text, positive_count, COUNT)?),
+            "--pin-cpus" => options.pin_cpus = true,
+            "--cpus" => options.cpus = Some(cli::parse_value(name, text, host::parse_cpu_list, "a list of CPUs such as 0-3,8")?),
+            "--memory-budget" => options.memory_budget = Some(cli::parse_checked(name, text, positive_size)?),
+            "--runs" => options.runs = Some(cli::parse_value(name, text, positive_count, COUNT)?),
+            "--tle-policy" => options.tle_policy = cli::parse_value(name, text, parse_tle_policy, "any or median")?,
+            "--overall-timeout" => options.overall_timeout = Some(cli::parse_checked(name, text, positive_duration)?),
+            "--dry-run" => {
+                options.dry_run = Some(match value.as_deref() {
+                    None | Some("text") => PlanFormat::Text,
+                    Some("json") => PlanFormat::Json,
+                    Some(other) => {
+                        return Err(CliError::InvalidValue { option: name, value: other.to_string(), expected: "text or json" });
+                    }
+                });
+            },
+            "--tests" => batch.dir = Some(PathBuf::from(text)),
+            "--manifest" => batch.manifest = Some(PathBuf::from(text)),
+            "--input-ext" => batch.input_ext = Some(text.to_string()),
+            "--answer-ext" => batch.answer_exts = Some(text.split(',').map(String::from).collect()),
+            "--json" => batch.json = Some(Schema::LATEST),
+            "--format" => {
+                batch.json = match text {
+                    "table" => None,
+                    format => Some(cli::parse_value(name, format, Schema::from_format, "table, json or json-v1")?)
+                };
+            },
+            "--quiet" => batch.quiet = true,
+            "--time-tolerance" => tolerance.time_percent = cli::parse_value(name, text, parse_percent, PERCENT)?,
+            "--min-time-change" => tolerance.time_ms = cli::parse_checked(name, text, utils::parse_duration)?.as_millis() as u64,
+            "--memory-tolerance" => tolerance.memory_percent = cli::parse_value(name, text, parse_percent, PERCENT)?,
+            "--problem" => batch.problem = Some(PathBuf::from(text)),
+            "--validator" => options.validator = Some(PathBuf::from(text)),
+            "--input" => input = Some(text.to_string()),
+            "--no-stdin" => no_stdin = true,
+            "--output" => output = Some(PathBuf::from(text)),
+            "--artifact" => artifact = Some(text.to_string()),
+            "--gen" => stress_programs[0] = Some(text.to_string()),
+            "--brute" => stress_programs[1] = Some(text.to_string()),
+            "--candidate" => stress_programs[2] = Some(text.to_string()),
+            "--iterations" => iterations = cli::parse_value(name, text, positive_count, COUNT)?,
+            "--seed" => seed = cli::parse_value(name, text, |s| s.parse().ok(), "a whole number")?,
+            "--save-dir" => save_dir = PathBuf::from(text),
+            "--progress" => progress = cli::parse_value(name, text, positive_count, COUNT)?,
+            "--solution" => solution = Some(text.to_string()),
+            "--force" => force = true,
+            "--watch" => options.watch = true,
+            "--cache-dir" => options.cache_dir = Some(PathBuf::from(text)),
+            "--no-cache" => options.no_cache = true,
+            "--socket" => socket = Some(PathBuf::from(text)),
+            "--state-dir" => state = Some(PathBuf::from(text)),
+            "--metrics-listen" => metrics = Some(text.to_string()),
+            "--listen" => listen = Some(text.to_string()),
+            "--root" => root = Some(PathBuf::from(text)),
+            _ => unreachable!("option {name} has no handling")
+        }
+    }
+
+    let expected = expected_positionals(subcommand, &batch, no_stdin);
+    let given = positionals.len() + usize::from(!exec_args.is_empty());
+    if given < expected.len() {
+        return Err(CliError::Usage(format!("missing {}", expected[given..].join(" "))));
+    }
+    // As many as given of the last, if it ends in ...
+    let variadic = expected.last().is_some_and(|name| name.ends_with("..."));
+    if let Some(extra) = positionals.get(expected.len()).filter(|_| !variadic) {
+        return Err(CliError::Usage(format!("unexpected argument {extra} after {}", subcommand.name)));
+    }
+    if options.watch && (options.runs.is_some() || options.dry_run.is_some()) {
+        return Err(CliError::Usage(String::from("option --watch judges once per change, without --runs or --dry-run")));
+    }
+    if options.watch && options.transcript.is_some() {
+        return Err(CliError::Usage(String::from("option --transcript records a single judging, not --watch")));
+    }
+    if options.transcript_answers && options.transcript.is_none() {
+        return Err(CliError::Usage(String::from("option --transcript-include-answers needs --transcript")));
+    }
+    let mut positionals = positionals.into_iter();
+    let command = match subcommand.name {
+        // The tests of the problem, judged as a batch
+        "judge" if batch.problem.is_some() => {
+            if options.runs.is_some() {
+                return Err(CliError::Usage(String::from("option --runs needs a single test, not --problem")));
+            }
+            if no_stdin {
+                return Err(CliError::Usage(String::from("option --no-stdin needs a single test, not --problem")));
+            }
+            batch.default_failure_policy = FailurePolicy::StopOnFirst;
+            batch.check("batch")?;
+            Command::Batch(batch)
+        },
+        "judge" if batch.failure_policy.is_some() => {
+            return Err(CliError::Usage(String::from("options --stop-on-fail, --run-all and --stop-after need --problem")));
+        },
+        "judge" => {
+            let input = match no_stdin {
+                true => String::from(NULL_INPUT),
+                false => positionals.next().unwrap_or_default()
+            };
+            if options.watch && input == "-" {
+                return Err(CliError::Usage(String::from("option --watch needs an input file, stdin can only be read once")));
+            }
+            Command::Judge { input, answer: PathBuf::from(positionals.next().unwrap_or_default()) }
+        },
+        "batch" => {
+            batch.check("batch")?;
+            Command::Batch(batch)
+        },
+        "compare-runs" => {
+            batch.check("compare-runs")?;
+            Command::CompareRuns { batch, execs: positionals.collect() }
+        },
+        "run" if no_stdin && input.is_some() => {
+            return Err(CliError::Usage(String::from("option --no-stdin and --input give the program two inputs")));
+        },
+        "run" => Command::Run { input },
+        "compile" => {
+            let Some(output) = output else {
+                return Err(CliError::Usage(String::from("compile needs --output FILE")));
+            };
+            let artifact = match (artifact, output.file_name()) {
+                (Some(artifact), _) => artifact,
+                (None, Some(name)) => name.to_string_lossy().into_owned(),
+                (None, None) => return Err(CliError::Usage(format!("{} is not a file, give --artifact", output.display())))
+            };
+            Command::Compile { output, artifact }
+        },
+        "check" => {
+            let output = PathBuf::from(positionals.next().unwrap_or_default());
+            Command::Check { output, answer: PathBuf::from(positionals.next().unwrap_or_default()) }
+        },
+        "diff-results" => {
+            let old = PathBuf::from(positionals.next().unwrap_or_default());
+            Command::DiffResults { old, new: PathBuf::from(positionals.next().unwrap_or_default()), tolerance, json: batch.json }
+        },
+        "stress" => match stress_programs {
+            [Some(generator), Some(brute), Some(candidate)] => Command::Stress(StressOptions {
+                generator,
+                brute,
+                candidate,
+                iterations,
+                seed,
+                save_dir,
+                progress
+            }),
+            _ => return Err(CliError::Usage(String::from("stress needs --gen, --brute and --candidate")))
+        },
+        "gen-answers" => match (batch.dir.take(), solution) {
+            (Some(dir), Some(solution)) => Command::GenAnswers { dir, layout: batch.layout(), solution, force },
+            _ => return Err(CliError::Usage(String::from("gen-answers needs --tests DIR and --solution PATH")))
+        },
+        "clear-cache" => match options.cache_dir.take() {
+            Some(dir) => Command::ClearCache { dir },
+            None => return Err(CliError::Usage(String::from("clear-cache needs --cache-dir DIR")))
+        },
+        "daemon" => match socket {
+            Some(socket) => Command::Daemon { socket, metrics, state },
+            None => return Err(CliError::Usage(String::from("daemon needs --socket PATH")))
+        },
+        "serve" => match root {
+            Some(root) => Command::Serve { listen: listen.unwrap_or_else(|| String::from("127.0.0.1:8080")), root },
+            None => return Err(CliError::Usage(String::from("serve needs --root DIR")))
+        },
+        "serve-grpc" => Command::ServeGrpc { listen: listen.unwrap_or_else(|| String::from("127.0.0.1:50051")), root },
+        "selftest" => Command::SelfTest,
+        "calibrate" => Command::Calibrate { store: auto_calibrate },
+        name => unreachable!("command {name} has no handling")
+    };
+    Ok(CommandLine { command, options, exec_args })
+}
+
+/*
+ *  The positional arguments `subcommand` takes, which for judge with a
+ *  problem file is only the executable, and leaves out the input file
+ *  with --no-stdin
+ */
+fn expected_positionals(subcommand: &Subcommand, batch: &BatchOptions, no_stdin: bool) -> &'static [&'static str] {
+    match (subcommand.name, &batch.problem) {
+        ("judge", Some(_)) => &["<executable>"],
+        ("judge", None) if no_stdin => &["<standard answer file>", "<executable>"],
+        _ => subcommand.positionals
+    }
+}
+
+/*
+ *  The program `name` as utils::find_path finds it, or None after saying
+ *  why not
+ */
+fn find_program(name: &str) -> Option<PathBuf> {
+    utils::find_path(name).map_err(|e| println!("{e}")).ok()
+}
+
+/*
+ *  Configure a session for the executable as the options say, under
+ *  `policy`, leaving the input and answer to the caller
+ */
+fn session_builder(options: &JudgeOptions, exec: PathBuf, policy: SandboxPolicy) -> JudgeSessionBuilder {
+    let mut builder = JudgeSession::builder(exec).policy(policy);
+    if let Some(limit) = options.time_limit {
+        builder = builder.time_limit(limit);
+    }
+    if let Some(limit) = options.wall_limit {
+        builder = builder.wall_time_limit(limit);
+    }
+    if let Some(bytes) = options.memory_limit {
+        builder = builder.memory_limit(bytes);
+    }
+    if let Some(bytes) = options.output_limit {
+        builder = builder.output_limit(bytes);
+    }
+    if let Some(bytes) = options.io_limit {
+        builder = builder.io_limit(bytes);
+    }
+    if let Some(bytes) = options.stderr_limit {
+        builder = builder.stderr_limit(bytes);
+    }
+    if let Some(dir) = &options.tmp_dir {
+        builder = builder.scratch_dir(dir.clone());
+    }
+    if let Some(root) = &options.cgroup_root {
+        builder = builder.cgroup_root(root.clone());
+    }
+    if let Some(n) = options.max_tasks {
+        builder = builder.max_tasks(n);
+    }
+    if let Some(cores) = options.cpu_quota {
+        builder = builder.cpu_quota(cores);
+    }
+    if let Some(arch) = options.exec_arch {
+        builder = builder.exec_arch(arch);
+    }
+    if let Some(strength) = options.sandbox {
+        builder = builder.sandbox_strength(strength);
+    }
+    if let Some(bytes) = options.scratch_limit {
+        builder = builder.scratch_limit(bytes);
+    }
+    for namespace in &options.join_ns {
+        builder = builder.join_namespace(namespace.clone());
+    }
+    for (source, name) in &options.provide {
+        builder = builder.provide_file(source.clone(), name.clone());
+    }
+    for (name, value) in &options.env {
+        builder = builder.env(name.clone(), value.clone());
+    }
+    if options.keep_output {
+        // Accepted runs have nothing to debug
+        builder = builder.keep_output(KeepPolicy::OnFailure);
+    }
+    if let Some(dir) = &options.output_dir {
+        builder = builder.output_dir(dir.clone());
+    }
+    if let Some(path) = &options.validator {
+        builder = builder.validator(path.clone());
+    }
+    // A transcript records the runs, which a cached result never had
+    if let Some(dir) = options.cache_dir.as_ref().filter(|_| !options.no_cache && options.transcript.is_none()) {
+        builder = builder.cache_dir(dir.clone());
+    }
+    if let Some(path) = &options.interpreter {
+        builder = builder.interpreter(path.clone());
+    }
+    if let Some(strict) = &options.strict_paths {
+        builder = builder.strict_paths(strict.clone());
+    }
+    // Ctrl-C kills the program and cleans up after it, and so does SIGTERM
+    match interrupt::handle() {
+        Ok(handle) => builder = builder.cancel_handle(handle),
+        Err(e) => eprintln!("note: cannot handle SIGINT and SIGTERM ({e}), interrupting leaves the program running")
+    }
+    // Left to the session otherwise, which scales scripts for their language
+    if options.multipliers.is_some() || options.time_multiplier.is_some() {
+        let mut multipliers = options.multipliers.unwrap_or_default();
+        if let Some(m) = options.time_multiplier {
+            multipliers.time = m;
+        }
+        builder = builder.limit_multipliers(multipliers);
+    }
+    builder
+        .io_mode(options.io_mode.clone().unwrap_or_default())
+        .copy_exec(!options.no_copy_exec)
+        .prewarm(options.prewarm)
+        .comparison(options.comparison.unwrap_or_default())
+}
+
+/*
+ *  Run the program on `input`, its output going to stdout, and report the
+ *  run on stderr after what the program wrote there. Returns the exit
+ *  code, which is that of a rejected test unless the run was fine.
+ */
+fn run_program(options: &JudgeOptions, input: Option<String>, exec_args: &[String]) -> i32 {
+    let Some(exec) = find_program(&exec_args[0]) else {
+        return EXIT_SETUP;
+    };
+    let exec_args: Vec<&str> = exec_args.iter().map(String::as_str).collect();
+    let mut builder = session_builder(options, exec, SandboxPolicy::default());
+    if let Some(input) = input {
+        builder = builder.input(input_source(&input));
+    }
+    let session = match builder.build() {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Invalid run setup: {e}");
+            return EXIT_SETUP;
+        }
+    };
+    if let Some(format) = options.dry_run {
+        print_plan(&session, format, &exec_args);
+        return EXIT_ACCEPTED;
+    }
+    let result = session.run_program(&exec_args, &mut io::stdout().lock());
+    io::stdout().flush().unwrap_or_default();
+    let result = match result {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Cannot run: {e}");
+            return failure_code(e.as_ref());
+        }
+    };
+    io::stderr().write_all(&result.stderr).unwrap_or_default();
+    eprintln!("{result}");
+    match result.accepted() {
+        true => EXIT_ACCEPTED,
+        false => EXIT_REJECTED
+    }
+}
+
+/*
+ *  Run the compiler in place under the compiler policy and move the
+ *  `artifact` it makes to `output`, reporting on stderr. Returns the exit
+ *  code, which is that of a rejected test if compiling failed.
+ */
+fn compile(options: &JudgeOptions, output: &Path, artifact: &str, exec_args: &[String]) -> i32 {
+    let Some(exec) = find_program(&exec_args[0]) else {
+        return EXIT_SETUP;
+    };
+    let exec_args: Vec<&str> = exec_args.iter().map(String::as_str).collect();
+    let mut builder = session_builder(options, exec, SandboxPolicy::compiler())
+        .copy_exec(false);
+    if options.time_limit.is_none() {
+        builder = builder.time_limit(COMPILE_TIME_LIMIT);
+    }
+    if options.memory_limit.is_none() {
+        builder = builder.memory_limit(COMPILE_MEMORY_LIMIT);
+    }
+    let session = match builder.build() {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Invalid compiling setup: {e}");
+            return EXIT_SETUP;
+        }
+    };
+    if let Some(format) = options.dry_run {
+        print_plan(&session, format, &exec_args);
+        return EXIT_ACCEPTED;
+    }
+    let result = match session.compile(&exec_args, artifact, output) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Cannot compile: {e}");
+            return failure_code(e.as_ref());
+        }
+    };
+    io::stderr().write_all(&result.stderr).unwrap_or_default();
+    match result.status {
+        JudgeStatus::Accepted => {
+            eprintln!("Compiled {} in {:.2}s", output.display(), result.time_used.as_secs_f64());
+            EXIT_ACCEPTED
+        },
+        JudgeStatus::OutputMissing => {
+            eprintln!("Compiling succeeded without making {artifact}");
+            EXIT_REJECTED
+        },
+        _ => {
+            eprintln!("Compiling failed");
+            eprintln!("{result}");
+            EXIT_REJECTED
+        }
+    }
+}
+
+/*
+ *  Write the answers the tests in `dir` lack with the trusted solution,
+ *  reporting what each run used, for setting the limits. Returns the
+ *  exit code, which is that of a rejected test if the solution failed on
+ *  any of them.
+ */
+fn gen_answers(options: &JudgeOptions, dir: &Path, layout: &TestLayout, solution: &str, force: bool) -> i32 {
+    let Some(exec) = find_program(solution) else {
+        return EXIT_SETUP;
+    };
+    let session = match session_builder(options, exec, SandboxPolicy::default()).build() {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Invalid solution setup: {e}");
+            return EXIT_SETUP;
+        }
+    };
+    let cases = match problem::inputs_in(dir, layout) {
+        Ok(cases) if cases.is_empty() => {
+            println!("Cannot load tests: no inputs (*.{}) in {}", layout.input_ext, dir.display());
+            return EXIT_SETUP;
+        },
+        Ok(x) => x,
+        Err(e) => {
+            println!("Cannot load tests: {e}");
+            return EXIT_SETUP;
+        }
+    };
+
+    let name_width = cases.iter().map(|case| case.name().len()).max().unwrap_or(0).max(4) + 2;
+    println!("{:<name_width$}{:<8}{:<10}{:<12}ANSWER", "TEST", "VERDICT", "CPU", "MEMORY");
+    let mut written: Vec<JudgeResult> = Vec::new();
+    let mut failed: Vec<String> = Vec::new();
+    let mut kept = 0;
+    for case in &cases {
+        if case.answer.is_file() && !force {
+            println!("{:<name_width$}{:<8}{:<10}{:<12}kept {}", case.name(), "-", "-", "-", case.answer.display());
+            kept += 1;
+            continue;
+        }
+        let result = match problem::write_answer(&session, case, &[solution]) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("Failed on test {}", case.name());
+                print_run_error(e.as_ref());
+                return failure_code(e.as_ref());
+            }
+        };
+        let answer = match result.accepted() {
+            true => format!("wrote {}", case.answer.display()),
+            false => String::from("none")
+        };
+        println!(
+            "{:<name_width$}{:<8}{:<10}{:<12}{answer}",
+            case.name(),
+            result.status.abbr(),
+            format!("{}ms", result.cpu_time_ms),
+            utils::format_memory(result.memory_used_bytes)
+        );
+        if matches!(result.status, JudgeStatus::Cancelled) {
+            break;
+        }
+        match result.accepted() {
+            true => written.push(result),
+            false => {
+                if matches!(result.status, JudgeStatus::RuntimeError(_) | JudgeStatus::ReturnNonZero(_) | JudgeStatus::SystemError(_)) {
+                    println!("\t{}", result.status);
+                }
+                failed.push(case.name());
+            }
+        }
+    }
+
+    println!("{} answers written, {kept} kept", written.len());
+    if let (Some(time), Some(memory)) = (
+        written.iter().map(|r| r.cpu_time_ms).max(),
+        written.iter().map(|r| r.memory_used_bytes).max()
+    ) {
+        println!("The solution took up to {time}ms of CPU time and {}", utils::format_memory(memory));
+    }
+    match failed.is_empty() {
+        true => EXIT_ACCEPTED,
+        false => {
+            println!("No answers for the tests the solution failed: {}", failed.join(", "));
+            EXIT_REJECTED
+        }
+    }
+}
+
+/*
+ *  Run the stress test, its progress going to stderr and its outcome to
+ *  stdout. Returns the exit code, which is that of a rejected test if the
+ *  candidate got an input wrong.
+ */
+fn stress_test(options: &JudgeOptions, stress: &StressOptions) -> i32 {
+    let program = |name: &String| find_program(name).map(|exec| (session_builder(options, exec, SandboxPolicy::default()), name.clone()));
+    let (Some(generator), Some(brute), Some(candidate)) = (program(&stress.generator), program(&stress.brute), program(&stress.candidate)) else {
+        return EXIT_SETUP;
+    };
+    let base = options.tmp_dir.clone().unwrap_or_else(env::temp_dir);
+    let test = match StressTest::new(generator, brute, candidate, &base) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Invalid stress test setup: {e}");
+            return EXIT_SETUP;
+        }
+    };
+    let test = test
+        .with_comparison(options.comparison.unwrap_or_default())
+        .with_save_dir(stress.save_dir.clone())
+        .with_progress(stress.progress);
+    let iterations = stress.iterations;
+    let outcome = test.run(stress.seed, iterations, |done| {
+        eprintln!("{done}/{iterations} iterations, no difference yet");
+    });
+    let outcome = match outcome {
+        Ok(x) => x,
+        Err(e) => {
+            print_run_error(e.as_ref());
+            return failure_code(e.as_ref());
+        }
+    };
+    match outcome {
+        StressOutcome::Agreed { iterations } => {
+            println!("No difference in {iterations} iterations");
+            EXIT_ACCEPTED
+        },
+        StressOutcome::Failed { iteration, seed, status } => {
+            println!("Iteration {} with seed {seed}: the candidate got {status}", iteration + 1);
+            println!("Input, outputs and seed saved to {}", test.save_dir().display());
+            EXIT_REJECTED
+        },
+        StressOutcome::Aborted { program, iteration, seed, result } => {
+            io::stderr().write_all(&result.stderr).unwrap_or_default();
+            println!("The {program} failed on iteration {} with seed {seed}, stopping", iteration + 1);
+            println!("{result}");
+            EXIT_SETUP
+        },
+        StressOutcome::Cancelled { iterations } => {
+            println!("Stopped after {iterations} iterations without a difference");
+            EXIT_JUDGE_FAILED
+        }
+    }
+}
+
+/*
+ *  Judge the program as the options say, then again every time the
+ *  executable or the tests directory changes, until SIGINT or SIGTERM.
+ *  Returns the exit code, which is that of success once stopped so.
+ */
+fn watch(options: &JudgeOptions, single: Option<(String, PathBuf)>, batch: Option<BatchOptions>, exec_args: &[String]) -> i32 {
+    let Some(exec_path) = find_program(&exec_args[0]) else {
+        return EXIT_SETUP;
+    };
+    if let Err(e) = interrupt::handle() {
+        println!("Cannot watch: SIGINT would not stop it ({e})");
+        return EXIT_SETUP;
+    }
+    let watched = Watcher::new().and_then(|mut watcher| {
+        watcher.watch_file(&exec_path)?;
+        if let Some(dir) = batch.as_ref().and_then(|batch| batch.dir.as_ref()) {
+            watcher.watch_dir(dir)?;
+        }
+        Ok(watcher)
+    });
+    let watcher = match watched {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Cannot watch: {e}");
+            return EXIT_SETUP;
+        }
+    };
+    println!("Watching {}, Ctrl-C to stop", exec_path.display());
+    loop {
+        judge_once(options, &exec_path, single.as_ref(), batch.as_ref(), exec_args);
+        match watcher.wait(WATCH_QUIET, || interrupt::interrupted().is_some()) {
+            Ok(Some(_)) => continue,
+            Ok(None) => break,
+            Err(e) => {
+                println!("Cannot watch: {e}");
+                return EXIT_JUDGE_FAILED;
+            }
+        }
+    }
+    println!("Stopped watching");
+    EXIT_ACCEPTED
+}
+
+/*
+ *  One round of a watch: judge the program and sum it up in a line per
+ *  test, each with the time of day. What goes wrong is a line too, the
+ *  next change may well fix it.
+ */
+fn judge_once(options: &JudgeOptions, exec_path: &Path, single: Option<&(String, PathBuf)>, batch: Option<&BatchOptions>, exec_args: &[String]) {
+    let exec_args: Vec<&str> = exec_args.iter().map(String::as_str).collect();
+    let mut builder = session_builder(options, exec_path.to_path_buf(), SandboxPolicy::default());
+    if let Some((input, answer)) = single {
+        builder = builder.input(input_source(input)).answer(answer.clone());
+    }
+    let summary = |status: &JudgeStatus, time: Duration, memory: u64| {
+        format!("{:<4}{:>8}  {}", status.abbr(), format!("{}ms", time.as_millis()), utils::format_memory(memory))
+    };
+    let result = builder.build().map_err(Box::<dyn Error>::from).and_then(|session| match batch {
+        Some(batch) => {
+            let judge = ProblemJudge::new(session, batch.load_tests()?)
+                .with_failure_policy(batch.failure_policy())
+                .with_pin_cpus(options.pin_cpus);
+            let judge = match &options.cpus {
+                Some(cpus) => judge.with_cpus(cpus.clone()),
+                None => judge
+            };
+            let judge = match options.overall_timeout {
+                Some(limit) => judge.with_overall_deadline(limit),
+                None => judge
+            };
+            let judge = match options.memory_budget {
+                Some(bytes) => judge.with_memory_budget(bytes),
+                None => judge
+            };
+            let judge = match stamps(options)? {
+                Some(stamps) => judge.with_stamps(stamps),
+                None => judge
+            };
+            let submission = judge.run_parallel(options.jobs.unwrap_or(1), &exec_args)?;
+            let name_width = judge.cases().iter().map(|case| case.name().len()).max().unwrap_or(0) + 2;
+            for (case, result) in judge.cases().iter().zip(&submission.results) {
+                let line = summary(&result.status, result.time_used, result.memory_used_bytes);
+                println!("[{}] {:<name_width$}{line}", watch::timestamp(), case.name());
+            }
+            Ok(format!("{}, score {}/{}", submission.status, submission.score, submission.max_score))
+        },
+        None => {
+            let result = session.run_judge(&exec_args)?;
+            Ok(summary(&result.status, result.time_used, result.memory_used_bytes))
+        }
+    });
+    match result {
+        Ok(line) => println!("[{}] {line}", watch::timestamp()),
+        Err(e) => println!("[{}] Cannot judge: {e}", watch::timestamp())
+    }
+}
+
+/*
+ *  Time the workload and report the calibration it comes to, storing it
+ *  for later sessions if `store`
+ */
+fn calibrate(options: &JudgeOptions, store: bool) -> i32 {
+    let path = options.calibration.clone().or_else(calibration::default_path);
+    let path = match (store, path) {
+        (true, None) => {
+            eprintln!("error: nowhere to store the calibration without $XDG_STATE_HOME or $HOME, give --calibration FILE");
+            return EXIT_SETUP;
+        },
+        (_, path) => path
+    };
+    eprintln!("Running the workload, {}s on the baseline's host...", calibration::BASELINE.as_secs());
+    let calibration = match calibrate::measure(options.cgroup_root.clone(), options.tmp_dir.clone()) {
+        Ok(calibration) => calibration,
+        Err(e) => {
+            eprintln!("error: cannot calibrate: {e}");
+            return EXIT_JUDGE_FAILED;
+        }
+    };
+    println!("Workload:  \t{}ms of CPU time, {}ms on the baseline's host", calibration.cpu_time.as_millis(), calibration::BASELINE.as_millis());
+    println!("Score:     \t{}", calibration.score);
+    println!("Multiplier:\t{} for time limits", calibration.multiplier);
+    match (store, path) {
+        (true, Some(path)) => match calibration.store(&path) {
+            Ok(()) => println!("Stored in {}, later sessions scale their time limits by it", path.display()),
+            Err(e) => {
+                eprintln!("error: cannot store the calibration in {}: {e}", path.display());
+                return EXIT_JUDGE_FAILED;
+            }
+        },
+        _ => println!("Run with --auto-calibrate to have later sessions scale their time limits by it")
+    }
+    EXIT_ACCEPTED
+}
+
+/*
+ *  Scale the time limits of every session by the stored calibration,
+ *  unless told not to. A calibration that can't be read is warned of and
+ *  left out, as is one missing from a --calibration file.
+ */
+fn install_calibration(options: &JudgeOptions) {
+    if options.no_calibration {
+        return;
+    }
+    let Some(path) = options.calibration.clone().or_else(calibration::default_path) else {
+        return;
+    };
+    match Calibration::load(&path) {
+        Ok(Some(stored)) => {
+            debug!("time limits scaled by {} as calibrated in {}", stored.multiplier, path.display());
+            calibration::install(stored);
+        },
+        Ok(None) if options.calibration.is_some() => warn!("no calibration in {}, time limits are left unscaled", path.display()),
+        Ok(None) => {},
+        Err(e) => warn!("cannot read the calibration, time limits are left unscaled: {e}")
+    }
+}
+
+/*
+ *  Remove the entries of the result cache in `dir`
+ */
+fn clear_cache(dir: &Path) -> i32 {
+    match cache::clear(dir) {
+        Ok(removed) => {
+            println!("Removed {removed} cached results from {}", dir.display());
+            EXIT_ACCEPTED
+        },
+        Err(e) => {
+            println!("Cannot clear the cache in {}: {e}", dir.display());
+            EXIT_SETUP
+        }
+    }
+}
+
+/*
+ *  Serve judging requests on `socket` until told to stop, the options
+ *  setting what the requests leave out, keeping the jobs in `state` if
+ *  given
+ */
+fn daemon(options: &JudgeOptions, socket: &Path, metrics: Option<&str>, state: Option<&Path>) -> i32 {
+    if let Some(address) = metrics {
+        if let Err(code) = serve_metrics(options, address) {
+            return code;
+        }
+    }
+    let mut daemon = match Daemon::bind(socket, options.jobs.unwrap_or(1)) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Cannot listen on {}: {e}", socket.display());
+            return EXIT_SETUP;
+        }
+    };
+    if let Some(bytes) = options.memory_budget {
+        daemon = daemon.memory_budget(bytes);
+    }
+    if let Some(dir) = state {
+        daemon = match daemon.persist(dir) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("Cannot keep the jobs in {}: {e}", dir.display());
+                return EXIT_SETUP;
+            }
+        };
+    }
+    eprintln!("Listening on {}", socket.display());
+    match daemon.serve(|exec| session_builder(options, exec, SandboxPolicy::default())) {
+        Ok(judged) => {
+            eprintln!("Shut down after judging {judged} requests");
+            EXIT_ACCEPTED
+        },
+        Err(e) => {
+            eprintln!("Daemon failed: {e}");
+            EXIT_JUDGE_FAILED
+        }
+    }
+}
+
+/*
+ *  Answer metrics scrapes on `address` in a thread of their own, which
+ *  ends with the process
+ */
+#[cfg(feature = "http")]
+fn serve_metrics(options: &JudgeOptions, address: &str) -> Result<(), i32> {
+    let listener = match http::MetricsListener::bind(address, scratch_check(options)) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Cannot serve metrics on {address}: {e}");
+            return Err(EXIT_SETUP);
+        }
+    };
+    match listener.local_addr() {
+        Ok(address) => eprintln!("Metrics on http://{address}/metrics"),
+        Err(_) => eprintln!("Metrics on {address}")
+    }
+    std::thread::spawn(move || listener.serve());
+    Ok(())
+}
+
+/*
+ *  What GET /health checks the scratch space for: room for a run within
+ *  the limits of the options, which a request may still raise
+ */
+#[cfg(feature = "http")]
+fn scratch_check(options: &JudgeOptions) -> http::ScratchCheck {
+    // Files are capped at the I/O limit too
+    let output_limit = options.output_limit.into_iter().chain(options.io_limit).min();
+    let mut policy = SandboxPolicy { output_limit, ..SandboxPolicy::default() };
+    if let Some(bytes) = options.scratch_limit {
+        policy.scratch_limit = bytes;
+    }
+    if let Some(bytes) = options.stderr_limit {
+        policy.stderr_limit = bytes;
+    }
+    http::ScratchCheck {
+        base: options.tmp_dir.clone().unwrap_or_else(env::temp_dir),
+        needed: policy.scratch_needed(),
+        exec: !options.no_copy_exec
+    }
+}
+
+#[cfg(not(feature = "http"))]
+fn serve_metrics(_options: &JudgeOptions, _address: &str) -> Result<(), i32> {
+    eprintln!("This judger was built without the http feature, rebuild it with --features http to serve metrics");
+    Err(EXIT_SETUP)
+}
+
+/*
+ *  Serve judging jobs over HTTP on `listen`, confined to files under
+ *  `root`, until SIGINT or SIGTERM
+ */
+#[cfg(feature = "http")]
+fn serve(options: &JudgeOptions, listen: &str, root: &Path) -> i32 {
+    let mut server = match http::Server::bind(listen, root, options.jobs.unwrap_or(1), scratch_check(options)) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Cannot serve on {listen}: {e}");
+            return EXIT_SETUP;
+        }
+    };
+    if let Some(bytes) = options.memory_budget {
+        server = server.memory_budget(bytes);
+    }
+    match server.local_addr() {
+        Ok(address) => eprintln!("Serving on http://{address}"),
+        Err(_) => eprintln!("Serving on {listen}")
+    }
+    match server.serve(|exec| session_builder(options, exec, SandboxPolicy::default())) {
+        Ok(()) => {
+            eprintln!("Stopped serving");
+            EXIT_ACCEPTED
+        },
+        Err(e) => {
+            eprintln!("Server failed: {e}");
+            EXIT_JUDGE_FAILED
+        }
+    }
+}
+
+#[cfg(not(feature = "http"))]
+fn serve(_options: &JudgeOptions, _listen: &str, _root: &Path) -> i32 {
+    eprintln!("This judger was built without the http feature, rebuild it with --features http to serve");
+    EXIT_SETUP
+}
+
+/*
+ *  Serve the gRPC service on `listen`, taking paths under `root` if
+ *  given, until SIGINT or SIGTERM
+ */
+#[cfg(feature = "grpc")]
+fn serve_grpc(options: &JudgeOptions, listen: &str, root: Option<PathBuf>) -> i32 {
+    let config = grpc::ServerConfig {
+        workers: options.jobs.unwrap_or(1),
+        root,
+        scratch_base: options.tmp_dir.clone().unwrap_or_else(env::temp_dir),
+        memory_budget: options.memory_budget,
+        ..Default::default()
+    };
+    if let Err(e) = interrupt::handle() {
+        eprintln!("Cannot handle SIGINT and SIGTERM: {e}");
+        return EXIT_SETUP;
+    }
+    let server = match grpc::Server::bind(listen, config) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Cannot serve on {listen}: {e}");
+            return EXIT_SETUP;
+        }
+    };
+    match server.local_addr() {
+        Ok(address) => eprintln!("Serving gRPC on {address}"),
+        Err(_) => eprintln!("Serving gRPC on {listen}")
+    }
+    // Lets the thread stopping the server on a signal end once it stopped
+    // otherwise
+    let done = std::sync::atomic::AtomicBool::new(false);
+    let served = std::thread::scope(|scope| {
+        let (server, done) = (&server, &done);
+        scope.spawn(move || {
+            while interrupt::interrupted().is_none() && !done.load(std::sync::atomic::Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            server.stop();
+        });
+        let served = server.serve(|exec, policy| session_builder(options, exec, policy));
+        done.store(true, std::sync::atomic::Ordering::SeqCst);
+        served
+    });
+    match served {
+        Ok(()) => {
+            eprintln!("Stopped serving");
+            EXIT_ACCEPTED
+        },
+        Err(e) => {
+            eprintln!("Server failed: {e}");
+            EXIT_JUDGE_FAILED
+        }
+    }
+}
+
+#[cfg(not(feature = "grpc"))]
+fn serve_grpc(_options: &JudgeOptions, _listen: &str, _root: Option<PathBuf>) -> i32 {
+    eprintln!("This judger was built without the grpc feature, rebuild it with --features grpc to serve gRPC");
+    EXIT_SETUP
+}
+
+/*
+ *  Compare `output` with `answer` and print the verdict. Returns the exit
+ *  code, which is that of a rejected test unless they match.
+ */
+fn check_output(output: &Path, answer: &Path, comparison: Comparison) -> i32 {
+    let open = |path: &Path| File::open(path).map_err(|e| format!("{}: {e}", path.display()));
+    let (answer, output) = match (open(answer), open(output)) {
+        (Ok(answer), Ok(output)) => (answer, output),
+        (Err(e), _) | (_, Err(e)) => {
+            println!("Cannot compare: {e}");
+            return EXIT_SETUP;
+        }
+    };
+    match comparison.compare(answer, output) {
+        Ok(status) => {
+            println!("{status}");
+            match status {
+                JudgeStatus::Accepted => EXIT_ACCEPTED,
+                _ => EXIT_REJECTED
+            }
+        },
+        Err(e) => {
+            println!("Cannot compare: {e}");
+            EXIT_SETUP
+        }
+    }
+}
+
+/*
+ *  Compare the batch reports `old` and `new` test by test, showing those
+ *  that changed beyond `tolerance` as a table or as JSON of `json`
+ */
+fn diff_results(old: &Path, new: &Path, tolerance: &DiffTolerance, json: Option<Schema>) -> i32 {
+    let read = |path: &Path| {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        JsonSubmissionV1::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    };
+    let (old_batch, new_batch) = match (read(old), read(new)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(e), _) | (_, Err(e)) => {
+            println!("Cannot read the report {e}");
+            return EXIT_SETUP;
+        }
+    };
+    let diff = JsonResultsDiffV1::between(&old_batch, &new_batch, *tolerance);
+    let exit_code = match diff.is_empty() {
+        true => EXIT_ACCEPTED,
+        false => EXIT_REJECTED
+    };
+    if let Some(schema) = json {
+        match schema {
+            Schema::V1 => println!("{}", diff.to_json())
+        }
+        return exit_code;
+    }
+
+    if !diff.changed.is_empty() {
+        print!("{}", diff_table(&diff.changed, tolerance).render(use_color()));
+    }
+    for (names, path) in [(&diff.removed, old), (&diff.added, new)] {
+        if !names.is_empty() {
+            println!("Only in {}: {}", path.display(), names.join(", "));
+        }
+    }
+    let compared = diff.changed.len() + diff.unchanged;
+    match diff.changed.is_empty() {
+        true => println!(
+            "None of the {compared} tests changed by more than {}% or {}ms of time and {}% of memory",
+            tolerance.time_percent,
+            tolerance.time_ms,
+            tolerance.memory_percent
+        ),
+        false => {
+            let verdicts = diff.changed.iter().filter(|(_, diff)| diff.verdict_changed()).count();
+            println!("{} of {compared} tests changed, {verdicts} of them in verdict", diff.changed.len());
+        }
+    }
+    exit_code
+}
+
+/*
+ *  A row for every test that changed, with its verdict, times and memory
+ *  before and after, and what the verdict says under it where only that
+ *  changed
+ */
+fn diff_table(changed: &[(String, ResultDiff)], tolerance: &DiffTolerance) -> Table {
+    let mut table = Table::new(&["TEST", "VERDICT", "TIME", "CPU", "MEMORY"]);
+    for (name, diff) in changed {
+        let (old, new) = &diff.status;
+        let verdict = match (diff.verdict_changed(), new.as_str()) {
+            (false, _) => Cell::from(new.as_str()),
+            (true, "AC") => Cell::colored(format!("{old} -> {new}"), Color::Green),
+            (true, _) => Cell::colored(format!("{old} -> {new}"), Color::Red)
+        };
+        let time = |(old, new): (u64, u64), percent: Option<f64>| {
+            let text = match percent {
+                Some(percent) => format!("{old}ms -> {new}ms ({percent:+.1}%)"),
+                None => format!("{old}ms -> {new}ms")
+            };
+            match diff.time_changed(tolerance) {
+                true => Cell::colored(text, Color::Yellow),
+                false => Cell::from(text)
+            }
+        };
+        let memory = format!("{} -> {}", utils::format_memory(diff.memory_bytes.0), utils::format_memory(diff.memory_bytes.1));
+        table.row(vec![
+            Cell::from(name.as_str()),
+            verdict,
+            time(diff.time_ms, diff.time_delta_percent()),
+            time(diff.cpu_time_ms, diff.cpu_time_delta_percent()),
+            match diff.memory_changed(tolerance) {
+                true => Cell::colored(memory, Color::Yellow),
+                false => Cell::from(memory)
+            }
+        ]);
+        if diff.detail_changed() {
+            table.note(format!("{} -> {}", diff.message.0, diff.message.1));
+            if diff.difference.0 != diff.difference.1 {
+                let difference = |difference: &Option<String>| difference.clone().unwrap_or_else(|| String::from("none"));
+                table.note(format!("differed at {} -> {}", difference(&diff.difference.0), difference(&diff.difference.1)));
+            }
+        }
+    }
+    table
+}
+
+fn parse_percent(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let percent: f64 = value.strip_suffix('%').unwrap_or(value).trim_end().parse().ok()?;
+    (percent.is_finite() && percent >= 0.0).then_some(percent)
+}
+
+/*
+ *  The input a test or a run is given, "-" streaming the judger's own
+ *  stdin to the program and /dev/null giving it none
+ */
+fn input_source(input: &str) -> InputSource {
+    match input {
+        "-" => InputSource::Reader(Box::new(io::stdin())),
+        NULL_INPUT => InputSource::Null,
+        path => InputSource::File(PathBuf::from(path))
+    }
+}
+
+/*
+ *  Exit code of a run that failed: the setup's fault when it was the files
+ *  given, the judger's otherwise
+ */
+fn failure_code(e: &(dyn Error + 'static)) -> i32 {
+    match e.is::<JudgeError>() {
+        true => EXIT_SETUP,
+        false => EXIT_JUDGE_FAILED
+    }
+}
+
+/*
+ *  Print the usage with the subcommands, for --help
+ */
+fn print_help(program: &str) {
+    println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    println!("Judges a program in a sandbox, on a single test or a batch of them");
+    println!();
+    println!("Usage: {program} <COMMAND> [OPTIONS] [ARGS...]");
+    println!();
+    println!("Commands:");
+    let width = SUBCOMMANDS.iter().map(|subcommand| subcommand.name.len()).max().unwrap_or_default();
+    for subcommand in &SUBCOMMANDS {
+        println!("  {:width$}  {}", subcommand.name, subcommand.about);
+    }
+    println!();
+    println!("Run '{program} <COMMAND> --help' for the arguments and options of a command.");
+    println!("Judging without a command, as '{program} judge' does, is deprecated.");
+}
+
+/*
+ *  Print the usage of a subcommand with its options, for its --help
+ */
+fn print_subcommand_help(program: &str, subcommand: &Subcommand) {
+    let mut usage = format!("Usage: {program} {} [OPTIONS]", subcommand.name);
+    for positional in subcommand.positionals {
+        usage += &format!(" {positional}");
+    }
+    if subcommand.program {
+        usage += " [--] [args...]";
+    }
+    println!("{usage}");
+    println!("{}", subcommand.about);
+    if let (true, Some(executable)) = (subcommand.program, subcommand.positionals.last()) {
+        println!();
+        println!("{}", ARGUMENTS_HELP.replace("<executable>", executable));
+    }
+    println!();
+    println!("Options:");
+    for group in subcommand.options {
+        print!("{}", cli::options_help(group));
+    }
+    if !subcommand.notes.is_empty() {
+        println!();
+        println!("{}", subcommand.notes);
+    }
+}
+
+fn positive_size(value: &str) -> Result<u64, String> {
+    match utils::parse_size(value) {
+        Ok(0) => Err(format!("'{}' is zero, expected {SIZE}", value.trim())),
+        Ok(bytes) => Ok(bytes),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn positive_duration(value: &str) -> Result<Duration, String> {
+    match utils::parse_duration(value) {
+        Ok(time) if time.is_zero() => Err(format!("'{}' is zero, expected {TIME}", value.trim())),
+        Ok(time) => Ok(time),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+fn positive_count<T: std::str::FromStr + Default + PartialOrd>(value: &str) -> Option<T> {
+    value.parse().ok().filter(|n| *n > T::default())
+}
+
+fn positive_number(value: &str) -> Option<f64> {
+    value.parse().ok().filter(|x: &f64| *x > 0.0 && x.is_finite())
+}
+
+fn parse_tle_policy(value: &str) -> Option<TlePolicy> {
+    match value {
+        "any" => Some(TlePolicy::AnyRun),
+        "median" => Some(TlePolicy::Median),
+        _ => None
+    }
+}
+/*
+ *  Judge the program on all test cases, `jobs` at once, and report each
+ *  of them and the submission as a whole, as a table or as JSON, with the
+ *  host it was judged on if `verbose`. Returns the exit code for the
+ *  submission's verdict.
+ */
+fn judge_tests(judge: &ProblemJudge, jobs: usize, exec_args: &[&str], json: Option<Schema>, verbose: bool, progress: Option<&Progress>) -> i32 {
+    let submission = judge.run_parallel(jobs, exec_args);
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    let submission = match submission {
+        Ok(x) => x,
+        Err(e) => {
+            print_run_error(e.as_ref());
+            return match e.downcast_ref::<JudgeError>() {
+                Some(_) => EXIT_SETUP,
+                None => EXIT_JUDGE_FAILED
+            };
+        }
+    };
+    let exit_code = match submission.status {
+        JudgeStatus::Accepted => EXIT_ACCEPTED,
+        JudgeStatus::SystemError(_) | JudgeStatus::Cancelled => EXIT_JUDGE_FAILED,
+        _ => EXIT_REJECTED
+    };
+    if let Some(schema) = json {
+        match schema {
+            Schema::V1 => println!("{}", JsonSubmissionV1::of(&submission, judge.cases()).to_json())
+        }
+        return exit_code;
+    }
+
+    print!("{}", summary_table(judge.cases(), &submission).render(use_color()));
+    // Where the output went wrong, when a single test failed
+    let failed: Vec<(&TestCase, &JudgeResult)> = judge.cases().iter()
+        .zip(&submission.results)
+        .filter(|(_, result)| !result.accepted() && !matches!(result.status, JudgeStatus::Skipped))
+        .collect();
+    if let [(case, result)] = failed[..] {
+        if let Some(difference) = &result.difference {
+            println!("Test {} differs at {difference}", case.name());
+        }
+    }
+    if submission.accepted() {
+        println!("Congratulations, accepted!");
+    }
+    println!("RESULT BEGIN>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>");
+    println!("{submission}");
+    if verbose {
+        println!("{}", JudgeEnvironment::current());
+    }
+    println!("RESULT END>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>");
+    exit_code
+}
+
+/*
+ *  Judge every executable on all the tests of the batch in turn, sharing
+ *  the answers between their sessions, and report their verdicts side by
+ *  side as a table or as JSON. Returns the exit code, that of a rejected
+ *  test if the executables disagree on one.
+ */
+fn compare_runs(options: &JudgeOptions, batch: &BatchOptions, execs: &[String]) -> i32 {
+    let mut paths = Vec::with_capacity(execs.len());
+    for exec in execs {
+        let Some(path) = find_program(exec) else {
+            return EXIT_SETUP;
+        };
+        paths.push(path);
+    }
+    let cases = match batch.load_tests() {
+        Ok(x) => x,
+        Err(e) => {
+            println!("Cannot load tests: {e}");
+            return EXIT_SETUP;
+        }
+    };
+    let answers = Arc::new(AnswerCache::new());
+    let mut submissions = Vec::with_capacity(execs.len());
+    for (exec, path) in execs.iter().zip(paths) {
+        let progress = (!batch.quiet).then(|| Arc::new(Progress::new()));
+        let mut builder = session_builder(options, path, SandboxPolicy::default()).answer_cache(answers.clone());
+        if let Some(progress) = &progress {
+            builder = builder.observer(progress.clone());
+        }
+        let session = match builder.build() {
+            Ok(x) => x,
+            Err(e) => {
+                println!("Invalid judging setup for {exec}: {e}");
+                return EXIT_SETUP;
+            }
+        };
+        let judge = ProblemJudge::new(session, cases.clone());
+        if let Some(progress) = &progress {
+            eprintln!("Judging {exec}");
+            progress.begin(cases.len());
+        }
+        let submission = judge.run_parallel(options.jobs.unwrap_or(1), &[]);
+        if let Some(progress) = &progress {
+            progress.finish();
+        }
+        match submission {
+            Ok(x) => submissions.push(x),
+            Err(e) => {
+                println!("Judging {exec} failed");
+                print_run_error(e.as_ref());
+                return match e.downcast_ref::<JudgeError>() {
+                    Some(_) => EXIT_SETUP,
+                    None => EXIT_JUDGE_FAILED
+                };
+            }
+        }
+    }
+    debug!("read {} answers for {} tests", answers.reads(), cases.len());
+
+    let differing: Vec<&TestCase> = cases.iter()
+        .enumerate()
+        .filter(|&(index, _)| verdicts_differ(&submissions, index))
+        .map(|(_, case)| case)
+        .collect();
+    let failed = submissions.iter().any(|submission| matches!(submission.status, JudgeStatus::SystemError(_) | JudgeStatus::Cancelled));
+    let exit_code = match (failed, differing.is_empty()) {
+        (true, _) => EXIT_JUDGE_FAILED,
+        (false, true) => EXIT_ACCEPTED,
+        (false, false) => EXIT_REJECTED
+    };
+    if let Some(schema) = batch.json {
+        match schema {
+            Schema::V1 => {
+                let comparison = JsonComparisonV1 {
+                    solutions: execs.iter().zip(&submissions).map(|(exec, submission)| (exec.clone(), JsonSubmissionV1::of(submission, &cases))).collect(),
+                    disagreements: differing.iter().map(|case| case.name()).collect()
+                };
+                println!("{}", comparison.to_json());
+            }
+        }
+        return exit_code;
+    }
+
+    print!("{}", comparison_table(&cases, execs, &submissions).render(use_color()));
+    match differing.is_empty() {
+        true => println!("The {} executables agree on every test", execs.len()),
+        false => {
+            let names: Vec<String> = differing.iter().map(|case| case.name()).collect();
+            println!("They disagree on {} of {} tests: {}", differing.len(), cases.len(), names.join(", "));
+        }
+    }
+    exit_code
+}
+
+/*
+ *  Whether the executables got verdicts of different kinds on test
+ *  `index`, as told by their abbreviations
+ */
+fn verdicts_differ(submissions: &[SubmissionResult], index: usize) -> bool {
+    let mut verdicts = submissions.iter().map(|submission| submission.results[index].status.abbr());
+    let first = verdicts.next();
+    verdicts.any(|verdict| Some(verdict) != first)
+}
+
+/*
+ *  A row for every test with each executable's verdict and time in its
+ *  column, marked where the verdicts differ, and their totals under a
+ *  rule
+ */
+fn comparison_table(cases: &[TestCase], execs: &[String], submissions: &[SubmissionResult]) -> Table {
+    let mut header = vec!["TEST"];
+    header.extend(execs.iter().map(String::as_str));
+    header.push("");
+    let mut table = Table::new(&header);
+    for (index, case) in cases.iter().enumerate() {
+        let mut cells = vec![Cell::from(case.name())];
+        for submission in submissions {
+            let result = &submission.results[index];
+            let text = match result.status {
+                JudgeStatus::Skipped => result.status.abbr().to_string(),
+                _ => format!("{} {}ms", result.status.abbr(), result.time_used.as_millis())
+            };
+            cells.push(Cell::colored(text, verdict_color(&result.status)));
+        }
+        cells.push(match verdicts_differ(submissions, index) {
+            true => Cell::colored("differs", Color::Red),
+            false => Cell::from("")
+        });
+        table.row(cells);
+    }
+    table.rule();
+    let mut totals = vec![Cell::from("total")];
+    for submission in submissions {
+        let text = format!("{} {}/{}", submission.status.abbr(), submission.score, submission.max_score);
+        totals.push(Cell::colored(text, verdict_color(&submission.status)));
+    }
+    table.row(totals);
+    table
+}
+
+/*
+ *  A row for every test and a line summing them up under a rule
+ */
+fn summary_table(cases: &[TestCase], submission: &SubmissionResult) -> Table {
+    let mut table = Table::new(&["TEST", "VERDICT", "TIME", "CPU", "MEMORY", "SCORE", ""]);
+    for (case, result) in cases.iter().zip(&submission.results) {
+        let earned = match result.accepted() {
+            true => case.weight,
+            false => 0.0
+        };
+        let verdict = Cell::colored(result.status.abbr(), verdict_color(&result.status));
+        if matches!(result.status, JudgeStatus::Skipped) {
+            table.row(vec![case.name().into(), verdict, "-".into(), "-".into(), "-".into(), format!("{earned}/{}", case.weight).into()]);
+            continue;
+        }
+        let marker = match (result.cached, result.resumed) {
+            (_, true) => "(resumed)",
+            (true, false) => "(cached)",
+            (false, false) => ""
+        };
+        table.row(vec![
+            case.name().into(),
+            verdict,
+            format!("{}ms", result.time_used.as_millis()).into(),
+            format!("{}ms", result.cpu_time_ms).into(),
+            utils::format_memory(result.memory_used_bytes).into(),
+            format!("{earned}/{}", case.weight).into(),
+            marker.into()
+        ]);
+        // Verdicts with more to them than their abbreviation
+        if matches!(result.status, JudgeStatus::RuntimeError(_) | JudgeStatus::ReturnNonZero(_) | JudgeStatus::SystemError(_)) {
+            table.note(result.status.to_string());
+        }
+        if let Some(path) = &result.output_path {
+            table.note(format!("Output kept as {}", path.display()));
+        }
+    }
+    let total_time: Duration = submission.results.iter().map(|result| result.time_used).sum();
+    table.rule();
+    table.summary(vec![
+        "total".into(),
+        Cell::colored(submission.status.abbr(), verdict_color(&submission.status)),
+        format!(
+            "{}ms in all, {}ms at most, {} of memory at most, {}/{} points",
+            total_time.as_millis(),
+            submission.max_time.as_millis(),
+            utils::format_memory(submission.max_memory_bytes),
+            submission.score,
+            submission.max_score
+        ).into()
+    ]);
+    table
+}
+
+fn verdict_color(status: &JudgeStatus) -> Color {
+    match status {
+        JudgeStatus::Accepted => Color::Green,
+        JudgeStatus::TimeLimitExceeded
+        | JudgeStatus::MemoryLimitExceeded
+        | JudgeStatus::OutputLimitExceeded
+        | JudgeStatus::IdlenessLimitExceeded => Color::Yellow,
+        JudgeStatus::SecurityViolation | JudgeStatus::SystemError(_) | JudgeStatus::Cancelled => Color::Magenta,
+        JudgeStatus::Skipped => Color::Dim,
+        _ => Color::Red
+    }
+}
+
+/*
+ *  Whether to colour what goes to stdout: when it is a terminal, unless
+ *  NO_COLOR is set
+ */
+fn use_color() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/*
+ *  Judge the program on the test `runs` times and report the spread
+ */
+fn judge_repeated(session: &JudgeSession, runs: usize, tle_policy: TlePolicy, exec_args: &[&str]) {
+    let repeated = match session.run_repeated(runs, tle_policy, exec_args) {
+        Ok(x) => x,
+        Err(e) => {
+            print_run_error(e.as_ref());
+            return;
+        }
+    };
+
+    if repeated.accepted() {
+        println!("Congratulations, accepted!");
+    }
+    println!("RESULT BEGIN>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>");
+    println!("{repeated}");
+    println!("RESULT END>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>");
+}
+
+/*
+ *  Print what a run of the program would set up, for --dry-run
+ */
+fn print_plan(session: &JudgeSession, format: PlanFormat, exec_args: &[&str]) {
+    match session.describe(exec_args) {
+        Ok(plan) if format == PlanFormat::Json => println!("{}", plan.to_json()),
+        Ok(plan) => println!("{plan}"),
+        Err(e) => print_run_error(e.as_ref())
+    }
+}
+
+/*
+ *  Report why judging failed, in one line when it was the files given
+ */
+fn print_run_error(e: &(dyn Error + 'static)) {
+    match e.downcast_ref::<JudgeError>() {
+        Some(e) => println!("Cannot judge: {e}"),
+        None => {
+            println!("Failed to run program");
+            println!("Error: {e}");
+        }
+    }
+}
+
+/*
+ *  Parse the namespaces of --join-ns: every kind in a directory such as
+ *  /proc/PID/ns, or net=/run/netns/judge for just the one
+ */
+fn parse_join_ns(value: &str) -> Option<Vec<JoinNamespace>> {
+    let explicit = value.split_once('=').and_then(|(kind, path)| Some((Namespace::from_name(kind)?, path)));
+    match explicit {
+        Some((_, "")) => None,
+        Some((kind, path)) => Some(vec![JoinNamespace { kind, path: PathBuf::from(path) }]),
+        None if value.is_empty() => None,
+        None => Some(JoinNamespace::all_of(Path::new(value)))
+    }
+}
+
+/*
+ *  Parse a file of --provide and its name in the working directory, e.g.
+ *  data/words.txt:dict.txt. The name is what comes after the last colon.
+ */
+fn parse_provide(value: &str) -> Option<(PathBuf, String)> {
+    let (source, name) = value.rsplit_once(':')?;
+    match !source.is_empty() && utils::is_file_name(name) {
+        true => Some((PathBuf::from(source), name.to_string())),
+        false => None
+    }
+}
+
+/*
+ *  Parse a variable of --env and its value, e.g. TZ=Asia/Tokyo. The value
+ *  may be empty, the name not.
+ */
+fn parse_env(value: &str) -> Option<(String, String)> {
+    match value.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Some((name.to_string(), value.to_string())),
+        _ => None
+    }
+}
+
+/*
+ *  Parse the input and output file names of --file-io, e.g.
+ *  problem.in:problem.out. Both are plain names in the working directory.
+ */
+fn parse_file_io(value: &str) -> Option<IoMode> {
+    let (input, output) = value.split_once(':')?;
+    if !utils::is_file_name(input) || !utils::is_file_name(output) || input == output {
+        return None;
+    }
+    Some(IoMode::NamedFiles { input_name: input.to_string(), output_name: output.to_string() })
+}
@@ -0,0 +1,142 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use secure_judger::judger::{JudgeObserver, JudgeResult};
+use secure_judger::problem::TestCase;
+
+// Redraws of the line on a terminal are at least this far apart
+const REDRAW_EVERY: Duration = Duration::from_millis(100);
+// So are the lines written where stderr isn't a terminal, as in a CI log
+const LINE_EVERY: Duration = Duration::from_secs(10);
+
+struct State {
+    total: usize,
+    start: Instant,
+    // Tests started so far, the last of them named
+    started: usize,
+    current: String,
+    // How many tests got each verdict, in the order they first came
+    verdicts: Vec<(&'static str, usize)>,
+    last_shown: Option<Instant>,
+    // Whether tests finished since the line was last shown
+    unshown: bool
+}
+
+/// The progress of a batch on stderr, kept as the observer of its
+/// session: a line redrawn in place on a terminal, and otherwise a line
+/// now and then. Tests resumed from their stamps never run, so they
+/// aren't counted.
+pub struct Progress {
+    terminal: bool,
+    state: Mutex<State>
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        let state = State {
+            total: 0,
+            start: Instant::now(),
+            started: 0,
+            current: String::new(),
+            verdicts: Vec::new(),
+            last_shown: None,
+            unshown: false
+        };
+        Progress { terminal: io::stderr().is_terminal(), state: Mutex::new(state) }
+    }
+
+    /*
+     *  The batch of `total` tests starts now, as the session it observes
+     *  was made before the tests were known
+     */
+    pub fn begin(&self, total: usize) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.total = total;
+        state.start = Instant::now();
+    }
+
+    /*
+     *  Done with the batch: the line is cleared off a terminal, ahead of
+     *  the report going to stdout, and written one last time elsewhere
+     */
+    pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match self.terminal {
+            true if state.last_shown.is_some() => {
+                let _ = io::stderr().write_all(b"\r\x1b[K");
+            },
+            true => {},
+            false if state.unshown => self.show(&mut state),
+            false => {}
+        }
+        state.last_shown = None;
+    }
+
+    fn update(&self, state: &mut State, force: bool) {
+        let every = match self.terminal {
+            true => REDRAW_EVERY,
+            false => LINE_EVERY
+        };
+        if force || state.last_shown.is_none_or(|shown| shown.elapsed() >= every) {
+            self.show(state);
+        }
+    }
+
+    fn show(&self, state: &mut State) {
+        let line = progress_line(state.started, state.total, &state.current, state.start.elapsed(), &state.verdicts);
+        let mut stderr = io::stderr().lock();
+        let _ = match self.terminal {
+            true => write!(stderr, "\r{line}\x1b[K"),
+            false => writeln!(stderr, "{line}")
+        };
+        let _ = stderr.flush();
+        state.last_shown = Some(Instant::now());
+        state.unshown = false;
+    }
+}
+
+impl JudgeObserver for Progress {
+    fn on_run_start(&self, test: &TestCase) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.started += 1;
+        state.current = test.name();
+        // A new test is always shown on a terminal
+        self.update(&mut state, self.terminal);
+    }
+
+    fn on_tick(&self, _elapsed: Duration, _rss: Option<u64>) {
+        if self.terminal {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            self.update(&mut state, false);
+        }
+    }
+
+    fn on_run_complete(&self, result: &JudgeResult) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let abbr = result.status.abbr();
+        match state.verdicts.iter_mut().find(|(verdict, _)| *verdict == abbr) {
+            Some((_, count)) => *count += 1,
+            None => state.verdicts.push((abbr, 1))
+        }
+        state.unshown = true;
+        self.update(&mut state, false);
+    }
+}
+
+/*
+ *  Such as `test 12/200 (big-1), 1m05s, 10 AC 1 WA`
+ */
+fn progress_line(started: usize, total: usize, current: &str, elapsed: Duration, verdicts: &[(&str, usize)]) -> String {
+    let secs = elapsed.as_secs();
+    let elapsed = match secs >= 60 {
+        true => format!("{}m{:02}s", secs / 60, secs % 60),
+        false => format!("{secs}s")
+    };
+    let mut line = format!("test {started}/{total} ({current}), {elapsed}");
+    if !verdicts.is_empty() {
+        let counts: Vec<String> = verdicts.iter().map(|(verdict, count)| format!("{count} {verdict}")).collect();
+        line.push_str(&format!(", {}", counts.join(" ")));
+    }
+    line
+}
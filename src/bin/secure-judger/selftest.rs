@@ -0,0 +1,683 @@
+use std::env;
+use std::ffi::CString;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::net::UnixStream;
+use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use secure_judger::config::ProblemConfig;
+use secure_judger::judger::{JudgeHandle, JudgeSession, KeepPolicy, RetryPolicy};
+use secure_judger::language::LimitMultipliers;
+use secure_judger::sandbox::{self, SandboxPolicy, SandboxStrength, ScratchDir};
+use secure_judger::utils;
+
+// Hidden argument making the judger binary act as one of the probes
+pub const PROBE_ARG: &str = "__probe";
+
+// What a probe prints when the operation it tried was refused
+const BLOCKED: &str = "blocked\n";
+
+const PROBE_TIME: Duration = Duration::from_millis(500);
+const PROBE_MEMORY: u64 = 64 * 1024 * 1024;
+
+/// A probe program together with the verdict it should get when the
+/// sandbox works as intended
+struct Probe {
+    name: &'static str,
+    expected: &'static str,
+    policy: fn() -> SandboxPolicy,
+    // Cancel the run this long after it started
+    cancel_after: Option<Duration>,
+    // Forks to fail before the run gets started, all of which must be retried
+    fork_failures: u32,
+    // Output limit of the run, none if None
+    output_limit: Option<u64>,
+    run: fn()
+}
+
+const PROBES: [Probe; 15] = [
+    Probe { name: "sanity", expected: "AC", policy: SandboxPolicy::default, cancel_after: None, fork_failures: 0, output_limit: None, run: probe_sanity },
+    Probe { name: "fork", expected: "AC", policy: SandboxPolicy::default, cancel_after: None, fork_failures: 0, output_limit: None, run: probe_fork },
+    Probe { name: "socket", expected: "AC", policy: SandboxPolicy::default, cancel_after: None, fork_failures: 0, output_limit: None, run: probe_socket },
+    Probe { name: "open-write", expected: "AC", policy: SandboxPolicy::default, cancel_after: None, fork_failures: 0, output_limit: None, run: probe_open_write },
+    Probe { name: "chmod", expected: "AC", policy: SandboxPolicy::default, cancel_after: None, fork_failures: 0, output_limit: None, run: probe_chmod },
+    Probe { name: "exec-shell", expected: "AC", policy: SandboxPolicy::default, cancel_after: None, fork_failures: 0, output_limit: None, run: probe_exec_shell },
+    Probe { name: "huge-malloc", expected: "MLE", policy: SandboxPolicy::default, cancel_after: None, fork_failures: 0, output_limit: None, run: probe_huge_malloc },
+    Probe { name: "infinite-loop", expected: "TLE", policy: SandboxPolicy::default, cancel_after: None, fork_failures: 0, output_limit: None, run: probe_infinite_loop },
+    // Without an output limit the output only has to be captured and
+    // judged without taking the judger down
+    Probe { name: "giant-output", expected: "WA", policy: SandboxPolicy::default, cancel_after: None, fork_failures: 0, output_limit: None, run: probe_giant_output },
+    // The same output, over a limit of 1MiB
+    Probe {
+        name: "output-limit",
+        expected: "OLE",
+        policy: SandboxPolicy::default,
+        cancel_after: None,
+        fork_failures: 0,
+        output_limit: Some(1 << 20),
+        run: probe_giant_output
+    },
+    // Leaves a spinning grandchild behind, which must neither keep the
+    // run going nor survive it
+    Probe { name: "orphan", expected: "AC", policy: SandboxPolicy::threads_allowed, cancel_after: None, fork_failures: 0, output_limit: None, run: probe_orphan },
+    // Uses more CPU time than the limit, in less wall time given enough cores
+    Probe { name: "thread-spin", expected: "TLE", policy: SandboxPolicy::threads_allowed, cancel_after: None, fork_failures: 0, output_limit: None, run: probe_thread_spin },
+    // Would idle until the wall limit with a child, unless cancelled first
+    Probe {
+        name: "cancel",
+        expected: "CAN",
+        policy: SandboxPolicy::threads_allowed,
+        cancel_after: Some(Duration::from_millis(100)),
+        fork_failures: 0,
+        output_limit: None,
+        run: probe_cancel
+    },
+    // Starts only after two forks failed as if the host were out of processes
+    Probe {
+        name: "fork-retry",
+        expected: "AC",
+        policy: SandboxPolicy::default,
+        cancel_after: None,
+        fork_failures: 2,
+        output_limit: None,
+        run: probe_sanity
+    },
+    // The socket probe again with the filter disabled, which must let the
+    // socket through rather than still block it
+    Probe {
+        name: "unfiltered",
+        expected: "WA",
+        policy: unfiltered_policy,
+        cancel_after: None,
+        fork_failures: 0,
+        output_limit: None,
+        run: probe_socket
+    }
+];
+
+/// Something the judger works out without running anything, such as a
+/// limit scaled by a language's multiplier, and what it should come to
+struct Check {
+    name: &'static str,
+    expected: &'static str,
+    value: fn() -> String
+}
+
+const CHECKS: [Check; 14] = [
+    Check { name: "scale-none", expected: "1050ms", value: || scaled_time(1050, 1.0) },
+    Check { name: "scale-exact", expected: "2000ms", value: || scaled_time(1000, 2.0) },
+    // 1000 * 1.1 is a little over 1100 in floating point
+    Check { name: "scale-inexact", expected: "1100ms", value: || scaled_time(1000, 1.1) },
+    Check { name: "scale-round-up", expected: "1000ms", value: || scaled_time(333, 3.0) },
+    Check { name: "scale-tiny", expected: "100ms", value: || scaled_time(1, 1.5) },
+    Check { name: "scale-memory", expected: "144.00MiB", value: || scaled_memory(100_000_000, 1.5) },
+    // Problem files, whose errors have to say where they are
+    Check { name: "config-valid", expected: "ok", value: || config_error("[limits]\ntime = \"1s\"\n\n[[test]]\ninput = \"1.in\"\nanswer = \"1.ans\"\n") },
+    Check { name: "config-key", expected: "line 3", value: || config_error("[limits]\ntime = \"1s\"\nmemroy = \"1g\"\n") },
+    Check { name: "config-table", expected: "line 2", value: || config_error("\n[limit]\ntime = \"1s\"\n") },
+    Check { name: "config-type", expected: "line 2", value: || config_error("[limits]\ntime = 1\n") },
+    Check { name: "config-syntax", expected: "line 2", value: || config_error("[limits]\ntime = 1s\n") },
+    Check { name: "config-twice", expected: "line 3", value: || config_error("[limits]\n\n[limits]\n") },
+    Check { name: "config-tol", expected: "line 3", value: || config_error("[compare]\nmode = \"exact\"\ntolerance = 0.1\n") },
+    Check { name: "config-tests", expected: "line 4", value: || config_error("[tests]\ndir = \"tests\"\n\n[[test]]\ninput = \"1.in\"\nanswer = \"1.ans\"\n") }
+];
+
+/*
+ *  Run every probe through the sandbox and the checks that need none,
+ *  printing a pass/fail table. Returns whether everything passed.
+ */
+pub fn run(cgroup_root: Option<PathBuf>, tmp_dir: Option<PathBuf>) -> io::Result<bool> {
+    let exe = env::current_exe()?;
+    let base = tmp_dir.unwrap_or_else(env::temp_dir);
+    // Private and unpredictably named, removed with everything in it on
+    // return
+    let dir = ScratchDir::create_named(&base, "secure-judger-selftest")?;
+    let scratch = dir.path();
+    dir.create_file("probe.ans", 0o644)?.write_all(BLOCKED.as_bytes())?;
+    let answer = dir.file("probe.ans");
+    // Output of the runs that failed, which is all that may be kept
+    let kept = scratch.join("kept");
+    fs::create_dir(&kept)?;
+    // What the self-test has in it itself, all that may be left after a run
+    let own_files = fs::read_dir(scratch)?.count();
+
+    println!("{:<16}{:<10}{:<10}RESULT", "PROBE", "EXPECTED", "GOT");
+    let mut all_passed = true;
+    for probe in PROBES.iter() {
+        // The probes' scratch directories go next to the answer file and
+        // the kept output, which must be all that is left after each run
+        let mut got = run_probe(probe, &exe, &answer, scratch, &kept, cgroup_root.as_ref());
+        let kept_files = fs::read_dir(&kept)?.count();
+        let should_keep = !matches!(got.as_str(), "AC" | "CAN") && !got.starts_with("error");
+        if kept_files != usize::from(should_keep) {
+            got = format!("{got}+{kept_files} kept");
+        }
+        fs::remove_dir_all(&kept)?;
+        fs::create_dir(&kept)?;
+        let leftovers = leftover_probes();
+        if leftovers > 0 {
+            got = format!("{got}+{leftovers} left");
+        }
+        let stale_files = fs::read_dir(scratch)?.count() - own_files;
+        if stale_files > 0 {
+            got = format!("{got}+{stale_files} stale");
+        }
+        all_passed &= print_row(probe.name, probe.expected, &got);
+    }
+
+    // The judger itself, told to stop as Ctrl-C does while its program spins.
+    // It has to kill the program and remove its scratch directory first.
+    let mut got = interrupt_judger(&exe, scratch);
+    let leftovers = leftover_probes();
+    if leftovers > 0 {
+        got = format!("{got}+{leftovers} left");
+    }
+    let stale_files = fs::read_dir(scratch)?.count() - own_files;
+    if stale_files > 0 {
+        got = format!("{got}+{stale_files} stale");
+    }
+    all_passed &= print_row("interrupt", "exit 130", &got);
+
+    // The daemon, judging a probe and refusing a request it can't read over
+    // its socket, then shutting down with nothing left behind
+    let mut got = daemon_round_trip(&exe, scratch, &answer);
+    let stale_files = fs::read_dir(scratch)?.count() - own_files;
+    if stale_files > 0 {
+        got = format!("{got}+{stale_files} stale");
+    }
+    all_passed &= print_row("daemon", "ok", &got);
+
+    // The daemon's metrics, scraped after it judged two probes
+    #[cfg(feature = "http")]
+    {
+        let mut got = metrics_scrape(&exe, scratch, &answer);
+        let stale_files = fs::read_dir(scratch)?.count() - own_files;
+        if stale_files > 0 {
+            got = format!("{got}+{stale_files} stale");
+        }
+        all_passed &= print_row("metrics", "ok", &got);
+    }
+
+    for check in CHECKS.iter() {
+        all_passed &= print_row(check.name, check.expected, &(check.value)());
+    }
+
+    Ok(all_passed)
+}
+
+fn print_row(name: &str, expected: &str, got: &str) -> bool {
+    let passed = got == expected;
+    println!("{name:<16}{expected:<10}{got:<10}{}", if passed { "PASS" } else { "FAIL" });
+    passed
+}
+
+/*
+ *  Run the judger on the infinite-loop probe and send it SIGINT once the
+ *  probe runs, giving how the judger exited
+ */
+fn interrupt_judger(exe: &Path, scratch: &Path) -> String {
+    let spawned = Command::new(exe)
+        .args(["run", "--time-limit", "10s", "--tmp-dir"])
+        .arg(scratch)
+        .arg(exe)
+        .args([PROBE_ARG, "infinite-loop"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut judger = match spawned {
+        Ok(x) => x,
+        Err(e) => return format!("error: {e}")
+    };
+    let started = Instant::now();
+    while probe_pids().is_empty() && started.elapsed() < Duration::from_secs(5) {
+        thread::sleep(Duration::from_millis(10));
+    }
+    unsafe {
+        libc::kill(judger.id() as i32, libc::SIGINT);
+    }
+    let interrupted = Instant::now();
+    match judger.wait() {
+        // Not at the end of the time limit, which it would get to otherwise
+        Ok(_) if interrupted.elapsed() > Duration::from_secs(2) => String::from("slow"),
+        Ok(status) => match status.code() {
+            Some(code) => format!("exit {code}"),
+            None => String::from("killed")
+        },
+        Err(e) => format!("error: {e}")
+    }
+}
+
+/*
+ *  Start the judger as a daemon and send it a request for the sanity
+ *  probe, one missing its input and answer, and a shutdown. Gives "ok"
+ *  if each got its reply and the daemon exited and removed its socket,
+ *  what went wrong otherwise.
+ */
+fn daemon_round_trip(exe: &Path, scratch: &Path, answer: &Path) -> String {
+    let socket = scratch.join("judger.sock");
+    let input = scratch.join("daemon.in");
+    if let Err(e) = fs::write(&input, "") {
+        return format!("error: {e}");
+    }
+    let spawned = Command::new(exe)
+        .args(["daemon", "--jobs", "2", "--socket"])
+        .arg(&socket)
+        .arg("--tmp-dir")
+        .arg(scratch)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut daemon = match spawned {
+        Ok(x) => x,
+        Err(e) => return format!("error: {e}")
+    };
+    let started = Instant::now();
+    let mut connected = UnixStream::connect(&socket);
+    while connected.is_err() && started.elapsed() < Duration::from_secs(5) {
+        thread::sleep(Duration::from_millis(10));
+        connected = UnixStream::connect(&socket);
+    }
+    let talked = connected.and_then(|mut stream| {
+        let path = |p: &Path| utils::json_string(&p.to_string_lossy());
+        let requests = [
+            format!(
+                "{{\"id\":1,\"exec\":{},\"args\":[\"{PROBE_ARG}\",\"sanity\"],\"input\":{},\"answer\":{}}}",
+                path(exe),
+                path(&input),
+                path(answer)
+            ),
+            format!("{{\"id\":2,\"exec\":{}}}", path(exe)),
+            String::from("{\"cmd\":\"shutdown\"}")
+        ];
+        stream.write_all(format!("{}\n", requests.join("\n")).as_bytes())?;
+        stream.shutdown(std::net::Shutdown::Write)?;
+        let mut replies = String::new();
+        io::Read::read_to_string(&mut stream, &mut replies)?;
+        Ok(replies)
+    });
+    let replies = match talked {
+        Ok(x) => x,
+        Err(e) => {
+            let _ = daemon.kill();
+            let _ = daemon.wait();
+            return format!("error: {e}");
+        }
+    };
+    let got = match replies.lines().collect::<Vec<_>>() {
+        lines if !lines.iter().any(|line| line.starts_with("{\"id\":1,\"result\":{\"status\":\"AC\"")) => "no AC",
+        lines if !lines.iter().any(|line| line.starts_with("{\"id\":2,\"error\":")) => "no error",
+        lines if !lines.iter().any(|line| line.contains("\"shutdown\":true")) => "no shutdown",
+        _ => "ok"
+    };
+    let _ = fs::remove_file(&input);
+    match daemon.wait() {
+        Ok(status) if !status.success() => status.code().map_or(String::from("killed"), |code| format!("exit {code}")),
+        Ok(_) if socket.exists() => String::from("socket left"),
+        Ok(_) => String::from(got),
+        Err(e) => format!("error: {e}")
+    }
+}
+
+/*
+ *  Start the judger as a daemon serving its metrics, have it judge the
+ *  sanity probe twice and scrape the metrics before shutting it down.
+ *  Gives "ok" if they counted both runs and nothing left queued, what
+ *  went wrong otherwise.
+ */
+#[cfg(feature = "http")]
+fn metrics_scrape(exe: &Path, scratch: &Path, answer: &Path) -> String {
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::{TcpListener, TcpStream};
+
+    // A port that was free a moment ago
+    let address = match TcpListener::bind("127.0.0.1:0").and_then(|listener| listener.local_addr()) {
+        Ok(x) => x.to_string(),
+        Err(e) => return format!("error: {e}")
+    };
+    let socket = scratch.join("metrics.sock");
+    let input = scratch.join("metrics.in");
+    if let Err(e) = fs::write(&input, "") {
+        return format!("error: {e}");
+    }
+    let spawned = Command::new(exe)
+        .args(["daemon", "--socket"])
+        .arg(&socket)
+        .args(["--metrics-listen", &address, "--tmp-dir"])
+        .arg(scratch)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut daemon = match spawned {
+        Ok(x) => x,
+        Err(e) => return format!("error: {e}")
+    };
+    let started = Instant::now();
+    let mut connected = UnixStream::connect(&socket);
+    while connected.is_err() && started.elapsed() < Duration::from_secs(5) {
+        thread::sleep(Duration::from_millis(10));
+        connected = UnixStream::connect(&socket);
+    }
+    let scraped = connected.and_then(|mut stream| {
+        let path = |p: &Path| utils::json_string(&p.to_string_lossy());
+        let request = format!(
+            "{{\"exec\":{},\"args\":[\"{PROBE_ARG}\",\"sanity\"],\"input\":{},\"answer\":{}}}\n",
+            path(exe),
+            path(&input),
+            path(answer)
+        );
+        stream.write_all(request.repeat(2).as_bytes())?;
+        let mut replies = BufReader::new(stream.try_clone()?);
+        for _ in 0..2 {
+            replies.read_line(&mut String::new())?;
+        }
+        let mut scrape = TcpStream::connect(address.as_str())?;
+        scrape.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+        let mut metrics = String::new();
+        scrape.read_to_string(&mut metrics)?;
+        stream.write_all(b"{\"cmd\":\"shutdown\"}\n")?;
+        Ok(metrics)
+    });
+    let _ = fs::remove_file(&input);
+    let metrics = match scraped {
+        Ok(x) => x,
+        Err(e) => {
+            let _ = daemon.kill();
+            let _ = daemon.wait();
+            return format!("error: {e}");
+        }
+    };
+    let has = |line: &str| metrics.lines().any(|got| got == line);
+    let got = match () {
+        _ if !metrics.starts_with("HTTP/1.1 200") => "no metrics",
+        _ if !has("judger_runs_total{verdict=\"AC\"} 2") => "no 2 AC",
+        _ if !has("judger_judge_seconds_count 2") => "no latency",
+        _ if !has("judger_jobs_queued 0") || !has("judger_jobs_running 0") => "jobs left",
+        _ => "ok"
+    };
+    match daemon.wait() {
+        Ok(status) if !status.success() => status.code().map_or(String::from("killed"), |code| format!("exit {code}")),
+        Ok(_) => String::from(got),
+        Err(e) => format!("error: {e}")
+    }
+}
+
+/*
+ *  The default policy without the seccomp filter
+ */
+fn unfiltered_policy() -> SandboxPolicy {
+    SandboxPolicy { strength: SandboxStrength::Disabled, ..SandboxPolicy::default() }
+}
+
+/*
+ *  Where reading the problem file `text` fails, "ok" if it doesn't
+ */
+fn config_error(text: &str) -> String {
+    match ProblemConfig::parse(text, Path::new("problem.toml")) {
+        Ok(_) => String::from("ok"),
+        Err(e) => e.line.map_or(String::from("no line"), |line| format!("line {line}"))
+    }
+}
+
+fn scaled_time(base_ms: u64, time: f64) -> String {
+    let multipliers = LimitMultipliers { time, ..LimitMultipliers::default() };
+    format!("{}ms", multipliers.scale_time(Duration::from_millis(base_ms)).as_millis())
+}
+
+fn scaled_memory(base: u64, memory: f64) -> String {
+    let multipliers = LimitMultipliers { memory, ..LimitMultipliers::default() };
+    utils::format_memory(multipliers.scale_memory(base))
+}
+
+fn run_probe(
+    probe: &Probe,
+    exe: &Path,
+    answer: &Path,
+    base: &Path,
+    kept: &Path,
+    cgroup_root: Option<&PathBuf>
+) -> String {
+    let mut builder = JudgeSession::builder(exe.to_path_buf())
+        .answer(answer.to_path_buf())
+        .time_limit(PROBE_TIME)
+        .memory_limit(PROBE_MEMORY)
+        .policy((probe.policy)())
+        .scratch_dir(base.to_path_buf())
+        .keep_output(KeepPolicy::OnFailure)
+        .output_dir(kept.to_path_buf());
+    if let Some(root) = cgroup_root {
+        builder = builder.cgroup_root(root.clone());
+    }
+    if let Some(bytes) = probe.output_limit {
+        builder = builder.output_limit(bytes);
+    }
+    if probe.fork_failures > 0 {
+        builder = builder.retry(RetryPolicy {
+            max_attempts: probe.fork_failures + 1,
+            backoff: Duration::from_millis(10)
+        });
+    }
+    let handle = JudgeHandle::new();
+    if probe.cancel_after.is_some() {
+        builder = builder.cancel_handle(handle.clone());
+    }
+    let session = match builder.build() {
+        Ok(x) => x,
+        Err(e) => return format!("error: {e}")
+    };
+    let exe_str = exe.to_string_lossy();
+    let result = thread::scope(|scope| {
+        if let Some(delay) = probe.cancel_after {
+            let handle = &handle;
+            scope.spawn(move || {
+                thread::sleep(delay);
+                handle.cancel();
+            });
+        }
+    sandbox::inject_fork_failures(probe.fork_failures);
+        session.run_judge(&[&exe_str, PROBE_ARG, probe.name])
+    });
+    sandbox::inject_fork_failures(0);
+    match result {
+        // A cancelled run has to end promptly, not at one of its limits
+        Ok(result) if probe.cancel_after.is_some_and(|delay| result.time_used > delay + PROBE_TIME / 2) => {
+            format!("{}+slow", result.status.abbr())
+        },
+        Ok(result) if result.retries != probe.fork_failures => {
+            format!("{}+{} retries", result.status.abbr(), result.retries)
+        },
+        Ok(result) => result.status.abbr().to_string(),
+        Err(e) => format!("error: {e}")
+    }
+}
+
+/*
+ *  Count the probe processes still alive after a run, killing them
+ */
+fn leftover_probes() -> usize {
+    let pids = probe_pids();
+    for &pid in &pids {
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+        }
+    }
+    pids.len()
+}
+
+/*
+ *  The probe processes alive, which run with PROBE_ARG as their first
+ *  argument. A judger running one has it further along.
+ */
+fn probe_pids() -> Vec<i32> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut pids = Vec::new();
+    for entry in entries.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<i32>().ok()) else {
+            continue;
+        };
+        let Ok(cmdline) = fs::read(entry.path().join("cmdline")) else {
+            continue;
+        };
+        if cmdline.split(|&c| c == 0).nth(1) == Some(PROBE_ARG.as_bytes()) {
+            pids.push(pid);
+        }
+    }
+    pids
+}
+
+/*
+ *  Entry point of the probe mode, runs inside the sandbox
+ */
+pub fn probe_main(name: &str) -> ! {
+    match PROBES.iter().find(|p| p.name == name) {
+        Some(probe) => (probe.run)(),
+        None => {
+            eprintln!("Unknown probe {name}");
+            std::process::exit(2);
+        }
+    }
+    io::stdout().flush().unwrap_or_default();
+    std::process::exit(0);
+}
+
+/*
+ *  Report whether a raw libc call was refused by the sandbox
+ */
+fn report(ret: i64) {
+    let errno = io::Error::last_os_error().raw_os_error().unwrap_or(0);
+    if ret < 0 && (errno == libc::EPERM || errno == libc::ENOSYS) {
+        print!("{BLOCKED}");
+    } else if ret < 0 {
+        println!("failed with errno {errno}");
+    } else {
+        println!("allowed");
+    }
+}
+
+fn probe_sanity() {
+    print!("{BLOCKED}");
+}
+
+fn probe_fork() {
+    let pid = unsafe { libc::fork() };
+    if pid == 0 {
+        unsafe { libc::_exit(0) };
+    }
+    report(pid as i64);
+}
+
+fn probe_socket() {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+    report(fd as i64);
+}
+
+fn probe_open_write() {
+    let path = CString::new("/tmp/secure-judger-probe-write").unwrap();
+    let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CREAT, 0o644) };
+    report(fd as i64);
+}
+
+fn probe_chmod() {
+    // Its own executable, which the judger's user owns. The filter has to
+    // refuse it with EPERM rather than have it fail in any other way.
+    let path = CString::new("/proc/self/exe").unwrap();
+    let ret = unsafe { libc::chmod(path.as_ptr(), 0o755) };
+    let errno = io::Error::last_os_error().raw_os_error().unwrap_or(0);
+    match (ret, errno) {
+        (0, _) => println!("allowed"),
+        (_, libc::EPERM) => print!("{BLOCKED}"),
+        _ => println!("failed with errno {errno}")
+    }
+}
+
+fn probe_exec_shell() {
+    let sh = CString::new("/bin/sh").unwrap();
+    let argv = [sh.as_ptr(), std::ptr::null()];
+    let ret = unsafe { libc::execv(sh.as_ptr(), argv.as_ptr()) };
+    report(ret as i64);
+}
+
+fn probe_huge_malloc() {
+    // Touch every page so the memory is really committed
+    let block = vec![1u8; 1 << 30];
+    std::hint::black_box(&block);
+}
+
+fn probe_infinite_loop() {
+    let mut counter: u64 = 0;
+    loop {
+        counter = std::hint::black_box(counter.wrapping_add(1));
+    }
+}
+
+fn probe_giant_output() {
+    let chunk = [b'x'; 64 * 1024];
+    let mut stdout = io::stdout().lock();
+    for _ in 0..256 {
+        if stdout.write_all(&chunk).is_err() {
+            return;
+        }
+    }
+}
+
+fn probe_orphan() {
+    // Forking from a thread, the middle process exits right away
+    let spawner = std::thread::spawn(|| unsafe {
+        let pid = libc::fork();
+        if pid == 0 {
+            if libc::fork() == 0 {
+                loop {
+                    std::hint::spin_loop();
+                }
+            }
+            libc::_exit(0);
+        }
+        if pid > 0 {
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+        pid
+    });
+    match spawner.join() {
+        Ok(pid) if pid > 0 => print!("{BLOCKED}"),
+        _ => println!("fork failed")
+    }
+}
+
+fn probe_thread_spin() {
+    const THREADS: u32 = 4;
+
+    let spinners: Vec<_> = (0..THREADS).map(|_| std::thread::spawn(|| {
+        // Each thread burns its share of CPU time, however long that takes
+        let share = PROBE_TIME.mul_f64(1.5) / THREADS;
+        while thread_cpu_time() < share {
+            std::hint::spin_loop();
+        }
+    })).collect();
+    for spinner in spinners {
+        let _ = spinner.join();
+    }
+    print!("{BLOCKED}");
+}
+
+fn probe_cancel() {
+    unsafe {
+        libc::fork();
+    }
+    std::thread::sleep(Duration::from_secs(60));
+    print!("{BLOCKED}");
+}
+
+fn thread_cpu_time() -> Duration {
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts);
+    }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
@@ -0,0 +1,155 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use secure_judger::json::{self, Value};
+use secure_judger::utils::{self, json_string};
+
+/// Where a job of the daemon is at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    // A worker took it. After a restart it is judged again, which gives
+    // the same result as judging is idempotent.
+    Running,
+    Done,
+    // It couldn't be judged, such as for a missing input
+    Failed
+}
+
+impl JobStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [JobStatus::Queued, JobStatus::Running, JobStatus::Done, JobStatus::Failed].into_iter().find(|status| status.name() == name)
+    }
+}
+
+/// A job as it is stored
+pub struct StoredJob {
+    // Numbers the daemon's jobs across restarts
+    pub number: u64,
+    // The id the client gave
+    pub id: Option<Value>,
+    pub status: JobStatus,
+    // The members of the request's object, which it is read from again
+    // after a restart
+    pub request: Vec<(String, Value)>,
+    // The JSON of the result once done, the error once failed
+    pub outcome: Option<String>
+}
+
+impl StoredJob {
+    /*
+     *  The job's members in the replies of the jobs command, which are
+     *  those of its file but the request
+     */
+    pub fn summary_json(&self) -> String {
+        let mut members = vec![format!("\"job\":{}", self.number)];
+        if let Some(id) = &self.id {
+            members.push(format!("\"id\":{}", id.to_json()));
+        }
+        members.push(format!("\"status\":{}", json_string(self.status.name())));
+        match (self.status, &self.outcome) {
+            (JobStatus::Done, Some(result)) => members.push(format!("\"result\":{result}")),
+            (JobStatus::Failed, Some(error)) => members.push(format!("\"error\":{}", json_string(error))),
+            _ => {}
+        }
+        format!("{{{}}}", members.join(","))
+    }
+
+    fn file_json(&self) -> String {
+        let summary = self.summary_json();
+        let request = Value::Object(self.request.clone()).to_json();
+        format!("{},\"request\":{request}}}", &summary[..summary.len() - 1])
+    }
+
+    fn parse(text: &str) -> Option<StoredJob> {
+        let Value::Object(mut members) = json::parse(text).ok()? else {
+            return None;
+        };
+        let mut take = |key: &str| crate::daemon::take_member(&mut members, key);
+        let number = match take("job")? {
+            Value::Number(x) if x.fract() == 0.0 && x >= 1.0 => x as u64,
+            _ => return None
+        };
+        let id = take("id");
+        let status = match take("status")? {
+            Value::String(name) => JobStatus::from_name(&name)?,
+            _ => return None
+        };
+        let Value::Object(request) = take("request")? else {
+            return None;
+        };
+        let outcome = match (status, take("result"), take("error")) {
+            (JobStatus::Done, Some(result), _) => Some(result.to_json()),
+            (JobStatus::Failed, _, Some(Value::String(error))) => Some(error),
+            (JobStatus::Done | JobStatus::Failed, _, _) => return None,
+            _ => None
+        };
+        Some(StoredJob { number, id, status, request, outcome })
+    }
+}
+
+/// The daemon's jobs in a directory, a JSON file each named after its
+/// number. Every change is written beside the file and renamed over it,
+/// after syncing, so that a daemon killed at any point leaves each job
+/// either as it was or as it became.
+pub struct JobStore {
+    dir: PathBuf,
+    next: AtomicU64
+}
+
+impl JobStore {
+    /*
+     *  Use the jobs in `dir`, making the directory if it doesn't exist,
+     *  with the jobs already there in the order of their numbers
+     */
+    pub fn open(dir: &Path) -> io::Result<(JobStore, Vec<StoredJob>)> {
+        fs::create_dir_all(dir).map_err(|e| io::Error::new(e.kind(), format!("cannot make state directory {}: {e}", dir.display())))?;
+        let store = JobStore { dir: dir.to_path_buf(), next: AtomicU64::new(1) };
+        let jobs = store.load()?;
+        let next = jobs.last().map_or(1, |job| job.number + 1);
+        store.next.store(next, Ordering::SeqCst);
+        Ok((store, jobs))
+    }
+
+    /*
+     *  Every job, those with files that can't be read left out after
+     *  saying so
+     */
+    pub fn load(&self) -> io::Result<Vec<StoredJob>> {
+        let mut jobs = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+            match fs::read_to_string(&path).ok().and_then(|text| StoredJob::parse(&text)) {
+                Some(job) => jobs.push(job),
+                None => warn!("ignoring unreadable job {}", path.display())
+            }
+        }
+        jobs.sort_by_key(|job| job.number);
+        Ok(jobs)
+    }
+
+    /*
+     *  The number a new job gets
+     */
+    pub fn next_number(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::SeqCst)
+    }
+
+    pub fn put(&self, job: &StoredJob) -> io::Result<()> {
+        utils::replace_file(&self.dir.join(format!("{:010}.json", job.number)), job.file_json().as_bytes())
+    }
+}
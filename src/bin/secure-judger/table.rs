@@ -0,0 +1,164 @@
+// Space between two columns
+const GAP: usize = 2;
+
+// Cells of the first column longer than this are cut short with an
+// ellipsis, so that one long test name doesn't push the others aside
+const MAX_FIRST: usize = 32;
+
+/// Colour of a cell on a terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Green,
+    Red,
+    Yellow,
+    Magenta,
+    Dim
+}
+
+impl Color {
+    fn code(self) -> &'static str {
+        match self {
+            Color::Green => "\x1b[32m",
+            Color::Red => "\x1b[31m",
+            Color::Yellow => "\x1b[33m",
+            Color::Magenta => "\x1b[35m",
+            Color::Dim => "\x1b[2m"
+        }
+    }
+}
+
+/// Text of a table, coloured when the table is
+pub struct Cell {
+    text: String,
+    color: Option<Color>
+}
+
+impl Cell {
+    pub fn colored(text: impl Into<String>, color: Color) -> Self {
+        Cell { text: text.into(), color: Some(color) }
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Cell { text, color: None }
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        Cell::from(text.to_string())
+    }
+}
+
+enum Line {
+    Row(Vec<Cell>),
+    // The last cell runs on past the columns, and isn't counted in
+    // their widths
+    Summary(Vec<Cell>),
+    // Under the row before, indented
+    Note(String),
+    Rule
+}
+
+/// Rows printed in columns as wide as their widest cell, the first
+/// column cut short where it is too wide
+pub struct Table {
+    header: Vec<String>,
+    lines: Vec<Line>
+}
+
+impl Table {
+    pub fn new(header: &[&str]) -> Self {
+        Table { header: header.iter().map(|name| name.to_string()).collect(), lines: Vec::new() }
+    }
+
+    pub fn row(&mut self, cells: Vec<Cell>) {
+        self.lines.push(Line::Row(cells));
+    }
+
+    /*
+     *  A row whose last cell is free text, such as the totals under a
+     *  rule
+     */
+    pub fn summary(&mut self, cells: Vec<Cell>) {
+        self.lines.push(Line::Summary(cells));
+    }
+
+    pub fn note(&mut self, note: String) {
+        self.lines.push(Line::Note(note));
+    }
+
+    /*
+     *  A line of dashes as wide as the columns
+     */
+    pub fn rule(&mut self) {
+        self.lines.push(Line::Rule);
+    }
+
+    /*
+     *  The table as lines of text, with ANSI colours if `color`
+     */
+    pub fn render(&self, color: bool) -> String {
+        let mut widths: Vec<usize> = self.header.iter().map(|name| name.chars().count()).collect();
+        for line in &self.lines {
+            let cells = match line {
+                Line::Row(cells) => &cells[..],
+                Line::Summary(cells) => &cells[..cells.len().saturating_sub(1)],
+                Line::Note(_) | Line::Rule => continue
+            };
+            for (column, cell) in cells.iter().enumerate() {
+                let width = fit(&cell.text, column).chars().count();
+                match widths.get_mut(column) {
+                    Some(max) => *max = (*max).max(width),
+                    None => widths.push(width)
+                }
+            }
+        }
+
+        let mut out = String::new();
+        let header: Vec<Cell> = self.header.iter().map(|name| Cell::from(name.as_str())).collect();
+        render_cells(&mut out, &header, &widths, color);
+        for line in &self.lines {
+            match line {
+                Line::Row(cells) | Line::Summary(cells) => render_cells(&mut out, cells, &widths, color),
+                Line::Note(note) => out.push_str(&format!("\t{note}\n")),
+                Line::Rule => {
+                    // Columns no row has anything in take no room
+                    let shown: Vec<usize> = widths.iter().copied().filter(|&width| width > 0).collect();
+                    let total = shown.iter().sum::<usize>() + GAP * shown.len().saturating_sub(1);
+                    out.push_str(&format!("{}\n", "-".repeat(total)));
+                }
+            }
+        }
+        out
+    }
+}
+
+/*
+ *  The text of a cell in `column` as it is shown
+ */
+fn fit(text: &str, column: usize) -> String {
+    match column == 0 && text.chars().count() > MAX_FIRST {
+        true => text.chars().take(MAX_FIRST - 1).chain(['…']).collect(),
+        false => text.to_string()
+    }
+}
+
+fn render_cells(out: &mut String, cells: &[Cell], widths: &[usize], color: bool) {
+    let mut line = String::new();
+    for (column, cell) in cells.iter().enumerate() {
+        let text = fit(&cell.text, column);
+        let pad = match column + 1 < cells.len() {
+            true => widths.get(column).copied().unwrap_or(0).saturating_sub(text.chars().count()) + GAP,
+            false => 0
+        };
+        match (color, cell.color) {
+            (true, Some(c)) => line.push_str(&format!("{}{text}\x1b[0m", c.code())),
+            _ => line.push_str(&text)
+        }
+        line.push_str(&" ".repeat(pad));
+    }
+    out.push_str(line.trim_end());
+    out.push('\n');
+}
@@ -0,0 +1,228 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+// Changes after which a watched file may be different, deleting it
+// included so that a file replaced by a new one is seen
+const CHANGE_MASK: u32 = libc::IN_CLOSE_WRITE
+    | libc::IN_MOVED_TO
+    | libc::IN_MOVED_FROM
+    | libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_ATTRIB;
+// How often waiting checks whether to stop
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// How long a file has to keep its size and time to count as written
+const STABLE_FOR: Duration = Duration::from_millis(100);
+// Give up on a file that keeps changing for this long, until its next change
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A directory watched through inotify, for all of its files or some
+struct Watch {
+    descriptor: i32,
+    dir: PathBuf,
+    // Only changes to these names count, any does if None
+    names: Option<Vec<OsString>>
+}
+
+/// Waits for files and directories to change, through inotify. Files are
+/// watched through their directory, as compilers and linkers often write
+/// a new file in place of the old one rather than rewriting it.
+pub struct Watcher {
+    inotify: OwnedFd,
+    watches: Vec<Watch>,
+    // The files watched, which are waited for to be written in full
+    files: Vec<PathBuf>
+}
+
+impl Watcher {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Watcher { inotify: unsafe { OwnedFd::from_raw_fd(fd) }, watches: Vec::new(), files: Vec::new() })
+    }
+
+    /// Watch the file at `path`, which may be missing for a while
+    pub fn watch_file(&mut self, path: &Path) -> io::Result<()> {
+        let Some(name) = path.file_name() else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a file", path.display())));
+        };
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new(".")
+        };
+        self.add(dir, Some(name))?;
+        self.files.push(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Watch every file in `dir`, not those of its subdirectories
+    pub fn watch_dir(&mut self, dir: &Path) -> io::Result<()> {
+        self.add(dir, None)
+    }
+
+    fn add(&mut self, dir: &Path, name: Option<&OsStr>) -> io::Result<()> {
+        let path = std::ffi::CString::new(dir.as_os_str().as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))?;
+        let descriptor = unsafe { libc::inotify_add_watch(self.inotify.as_raw_fd(), path.as_ptr(), CHANGE_MASK) };
+        if descriptor < 0 {
+            let e = io::Error::last_os_error();
+            return Err(io::Error::new(e.kind(), format!("cannot watch {}: {e}", dir.display())));
+        }
+        // The same directory gets the same descriptor, watched once for
+        // all the names in it
+        match self.watches.iter_mut().find(|watch| watch.descriptor == descriptor) {
+            Some(watch) => match (&mut watch.names, name) {
+                (Some(names), Some(name)) => names.push(name.to_os_string()),
+                (names, _) => *names = None
+            },
+            None => self.watches.push(Watch {
+                descriptor,
+                dir: dir.to_path_buf(),
+                names: name.map(|name| vec![name.to_os_string()])
+            })
+        }
+        Ok(())
+    }
+
+    /*
+     *  Wait until something watched changes, then until the changes stop
+     *  for `quiet` and the watched files that exist keep their size, so
+     *  that nothing half written is judged. Returns what changed, or None
+     *  once `stop` says to stop.
+     */
+    pub fn wait(&self, quiet: Duration, stop: impl Fn() -> bool) -> io::Result<Option<Vec<PathBuf>>> {
+        let mut changed: Vec<PathBuf> = Vec::new();
+        let mut last_change: Option<Instant> = None;
+        loop {
+            if stop() {
+                return Ok(None);
+            }
+            let timeout = match last_change {
+                Some(at) if at.elapsed() >= quiet => break,
+                Some(at) => (quiet - at.elapsed()).min(POLL_INTERVAL),
+                None => POLL_INTERVAL
+            };
+            if !self.poll(timeout)? {
+                continue;
+            }
+            for path in self.read_events()? {
+                debug!("{} changed", path.display());
+                last_change = Some(Instant::now());
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+        for file in &self.files {
+            if changed.iter().any(|path| path == file) && !settle(file, &stop) {
+                debug!("{} did not settle", file.display());
+                return match stop() {
+                    true => Ok(None),
+                    false => self.wait(quiet, stop)
+                };
+            }
+        }
+        Ok(Some(changed))
+    }
+
+    /*
+     *  Whether events came within `timeout`. A signal ends the wait early,
+     *  as if none came.
+     */
+    fn poll(&self, timeout: Duration) -> io::Result<bool> {
+        let mut pfd = libc::pollfd { fd: self.inotify.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        match unsafe { libc::poll(&mut pfd, 1, timeout.as_millis() as i32) } {
+            n if n > 0 => Ok(pfd.revents & libc::POLLIN != 0),
+            0 => Ok(false),
+            _ => {
+                let e = io::Error::last_os_error();
+                match e.kind() {
+                    io::ErrorKind::Interrupted => Ok(false),
+                    _ => Err(e)
+                }
+            }
+        }
+    }
+
+    /*
+     *  Read the events there are, as the paths they are about that count
+     */
+    fn read_events(&self) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        // Aligned for the events, which start with an i32
+        let mut buf = [0u32; 1024];
+        loop {
+            let n = unsafe { libc::read(self.inotify.as_raw_fd(), buf.as_mut_ptr().cast(), std::mem::size_of_val(&buf)) };
+            if n < 0 {
+                let e = io::Error::last_os_error();
+                return match e.kind() {
+                    io::ErrorKind::WouldBlock => Ok(paths),
+                    io::ErrorKind::Interrupted => continue,
+                    _ => Err(e)
+                };
+            }
+            let bytes = unsafe { std::slice::from_raw_parts(buf.as_ptr().cast::<u8>(), n as usize) };
+            let header = std::mem::size_of::<libc::inotify_event>();
+            let mut offset = 0;
+            while offset + header <= bytes.len() {
+                let event = unsafe { std::ptr::read_unaligned(bytes[offset..].as_ptr().cast::<libc::inotify_event>()) };
+                let name = &bytes[offset + header..offset + header + event.len as usize];
+                let name = OsStr::from_bytes(name.split(|&b| b == 0).next().unwrap_or_default());
+                offset += header + event.len as usize;
+                let Some(watch) = self.watches.iter().find(|watch| watch.descriptor == event.wd) else {
+                    continue;
+                };
+                let counts = match &watch.names {
+                    Some(names) => names.iter().any(|watched| watched == name),
+                    None => !name.is_empty()
+                };
+                if counts {
+                    paths.push(watch.dir.join(name));
+                }
+            }
+        }
+    }
+}
+
+/*
+ *  Wait for the file to keep its size and modification time, or to be
+ *  missing, for STABLE_FOR. False if it didn't within SETTLE_TIMEOUT or
+ *  `stop` said to stop.
+ */
+fn settle(path: &Path, stop: &impl Fn() -> bool) -> bool {
+    let state = || -> Option<(u64, SystemTime)> {
+        let metadata = fs::metadata(path).ok()?;
+        Some((metadata.len(), metadata.modified().ok()?))
+    };
+    let start = Instant::now();
+    let mut last = state();
+    while start.elapsed() < SETTLE_TIMEOUT && !stop() {
+        thread::sleep(STABLE_FOR);
+        let now = state();
+        if now == last {
+            return true;
+        }
+        last = now;
+    }
+    false
+}
+
+/*
+ *  The local time of day as HH:MM:SS, for the lines of a watch
+ */
+pub fn timestamp() -> String {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return String::from("--:--:--");
+    }
+    format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
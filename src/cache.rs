@@ -0,0 +1,434 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::json::{self, Value};
+use crate::judger::{AppliedLimits, AppliedSandbox, JudgeResult, JudgeStatus, Limits, RuntimeErrorKind};
+use crate::secrun::SandboxStrength;
+use crate::sha256;
+use crate::utils;
+
+/// Files larger than this aren't hashed, the runs on them go uncached
+pub const MAX_HASHED_SIZE: u64 = 256 << 20;
+
+/// Results of earlier runs, one JSON file per run in a directory, named
+/// after the key of what was judged
+pub struct ResultCache {
+    dir: PathBuf
+}
+
+impl ResultCache {
+    /**
+     *  Use the cache in `dir`, making the directory if it doesn't exist
+     */
+    pub fn open(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir).map_err(|e| {
+            io::Error::new(e.kind(), format!("cannot make cache directory {}: {e}", dir.display()))
+        })?;
+        Ok(ResultCache { dir })
+    }
+
+    /**
+     *  The stored result of the run `key` names, marked as cached. An entry
+     *  that can't be read counts as missing, it is replaced on the next store.
+     */
+    pub fn get(&self, key: &str) -> Option<JudgeResult> {
+        let path = self.entry(key);
+        let text = fs::read_to_string(&path).ok()?;
+        match parse_entry(&text) {
+            Some(mut result) => {
+                result.cached = true;
+                Some(result)
+            },
+            None => {
+                debug!("ignoring unreadable cache entry {}", path.display());
+                None
+            }
+        }
+    }
+
+    /**
+     *  Store the result of the run `key` names, unless its verdict says
+     *  nothing about the program. The entry is written beside its place
+     *  and renamed there, so that no judger reading it, on another thread
+     *  or in another process, reads half of it, and judgers storing it at
+     *  once each write a file of their own.
+     */
+    pub fn put(&self, key: &str, result: &JudgeResult) -> io::Result<()> {
+        // An interrupted run may have ended early for it, a timing-suspect one late
+        if !is_cacheable(&result.status) || result.interrupted || result.timing_suspect {
+            return Ok(());
+        }
+        write_entry(&self.dir, key, &entry_json(result))
+    }
+
+    fn entry(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+/// Stamps of the tests a batch judged, a JSON file per test in a
+/// directory, so that the batch run again takes their results instead of
+/// judging them anew. A stamp has the cache key of its run and only holds
+/// for the same executable, test and setup.
+pub struct BatchStamps {
+    dir: PathBuf,
+    // Whether stamps are taken, or only written
+    reuse: bool
+}
+
+impl BatchStamps {
+    /**
+     *  Use the stamps in `dir`, making the directory if it doesn't exist.
+     *  Without `reuse` every test is judged again and stamped anew.
+     */
+    pub fn open(dir: PathBuf, reuse: bool) -> io::Result<Self> {
+        fs::create_dir_all(&dir).map_err(|e| {
+            io::Error::new(e.kind(), format!("cannot make stamp directory {}: {e}", dir.display()))
+        })?;
+        Ok(BatchStamps { dir, reuse })
+    }
+
+    /**
+     *  The result the stamp of test `test` has, marked as resumed, if the
+     *  stamp is of the run `key` names
+     */
+    pub fn get(&self, test: &str, key: &str) -> Option<JudgeResult> {
+        if !self.reuse {
+            return None;
+        }
+        let path = self.dir.join(format!("{}.json", stamp_name(test)));
+        let text = fs::read_to_string(&path).ok()?;
+        let Ok(Value::Object(members)) = json::parse(&text) else {
+            debug!("ignoring unreadable stamp {}", path.display());
+            return None;
+        };
+        let get = |member: &str| members.iter().find(|(name, _)| name == member).map(|(_, value)| value);
+        match (get("test"), get("key")) {
+            (Some(Value::String(stamped)), Some(Value::String(stamped_key))) if stamped == test && stamped_key == key => {},
+            _ => {
+                debug!("stamp {} is of another run of test {test}", path.display());
+                return None;
+            }
+        }
+        let mut result = parse_entry(&text)?;
+        result.resumed = true;
+        Some(result)
+    }
+
+    /**
+     *  Stamp test `test` with the result of the run `key` names, or remove
+     *  its stamp if the verdict says nothing about the program, as the
+     *  cache would not store it
+     */
+    pub fn put(&self, test: &str, key: &str, result: &JudgeResult) -> io::Result<()> {
+        let name = stamp_name(test);
+        if !is_cacheable(&result.status) || result.interrupted || result.timing_suspect {
+            return match fs::remove_file(self.dir.join(format!("{name}.json"))) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(())
+            };
+        }
+        let entry = entry_json(result);
+        let stamp = format!("{{\"test\":{},\"key\":{},{}", utils::json_string(test), utils::json_string(key), &entry[1..]);
+        write_entry(&self.dir, &name, &stamp)
+    }
+}
+
+/// Answers larger than this aren't kept in memory, every comparison reads
+/// them from their file
+pub const MAX_KEPT_ANSWER: u64 = 64 << 20;
+
+/// The answers of a set of tests, each read into memory once and shared by
+/// the sessions given the cache, as when several executables are judged on
+/// the same tests. An answer is kept as it was when first read.
+#[derive(Default)]
+pub struct AnswerCache {
+    // None for an answer too large to keep
+    answers: Mutex<HashMap<PathBuf, Option<Arc<[u8]>>>>,
+    reads: AtomicUsize
+}
+
+impl AnswerCache {
+    /// A cache with no answers read yet
+    pub fn new() -> Self {
+        AnswerCache::default()
+    }
+
+    /**
+     *  The answer at `path`, read on first use, or None if it is over
+     *  MAX_KEPT_ANSWER. The answer is read with the cache locked, so that
+     *  sessions asking for it at once read it only once.
+     */
+    pub fn get(&self, path: &Path) -> io::Result<Option<Arc<[u8]>>> {
+        let mut answers = self.answers.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(answer) = answers.get(path) {
+            return Ok(answer.clone());
+        }
+        let answer: Option<Arc<[u8]>> = match fs::metadata(path)?.len() > MAX_KEPT_ANSWER {
+            true => None,
+            false => {
+                self.reads.fetch_add(1, Ordering::Relaxed);
+                Some(fs::read(path)?.into())
+            }
+        };
+        answers.insert(path.to_path_buf(), answer.clone());
+        Ok(answer)
+    }
+
+    /// How many answers were read from their files so far
+    pub fn reads(&self) -> usize {
+        self.reads.load(Ordering::Relaxed)
+    }
+}
+
+// Test names may be paths, stamps are named after their digest instead
+fn stamp_name(test: &str) -> String {
+    sha256::digest(test.as_bytes())
+}
+
+/*
+ *  Write `contents` to the entry `name` of `dir` with utils::replace_file,
+ *  so that no one reads half of it
+ */
+fn write_entry(dir: &Path, name: &str, contents: &str) -> io::Result<()> {
+    utils::replace_file(&dir.join(format!("{name}.json")), contents.as_bytes())
+}
+
+/**
+ *  Whether a run with this verdict may be answered from the cache next
+ *  time. System errors, cancelled and skipped runs didn't judge the program.
+ *  Nor, it may be, did timing-suspect ones, which are left out as well by
+ *  those storing results.
+ */
+pub fn is_cacheable(status: &JudgeStatus) -> bool {
+    !matches!(status, JudgeStatus::SystemError(_) | JudgeStatus::Cancelled | JudgeStatus::Skipped)
+}
+
+/**
+ *  SHA-256 of the file at `path`, or None if it is larger than
+ *  MAX_HASHED_SIZE
+ */
+pub fn file_digest(path: &Path) -> io::Result<Option<String>> {
+    match fs::metadata(path)?.len() > MAX_HASHED_SIZE {
+        true => Ok(None),
+        false => sha256::file_digest(path).map(Some)
+    }
+}
+
+/**
+ *  The key of a run from the lines describing it, such as the digests of
+ *  its files and its limits
+ */
+pub fn key(lines: &[String]) -> String {
+    sha256::digest(lines.join("\n").as_bytes())
+}
+
+/**
+ *  Remove every entry of the cache in `dir`, returning how many there
+ *  were. Other files in the directory are left alone.
+ */
+pub fn clear(dir: &Path) -> io::Result<u64> {
+    let mut removed = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let is_entry = name.strip_suffix(".json").is_some_and(is_key);
+        // Left over by a judger that died while storing
+        let is_temp = name.starts_with('.') && name.ends_with(".tmp");
+        if !is_entry && !is_temp {
+            continue;
+        }
+        fs::remove_file(entry.path())?;
+        if is_entry {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+fn is_key(name: &str) -> bool {
+    name.len() == 64 && name.chars().all(|ch| ch.is_ascii_hexdigit())
+}
+
+/*
+ *  The result as a cache entry: everything to_json has, in units that
+ *  don't lose precision, and stderr as text
+ */
+fn entry_json(result: &JudgeResult) -> String {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| String::from("null"));
+    let limits_json = |limits: &Limits| utils::json_object(&[
+        ("cpu_us", limits.cpu.as_micros().to_string()),
+        ("wall_us", limits.wall.as_micros().to_string()),
+        ("memory_bytes", limits.memory_bytes.to_string())
+    ]);
+    let detail = match &result.status {
+        JudgeStatus::RuntimeError(kind) => Some(utils::json_string(&kind.to_string())),
+        JudgeStatus::ReturnNonZero(value) => Some(value.to_string()),
+        _ => None
+    };
+    utils::json_object(&[
+        ("status", utils::json_string(result.status.abbr())),
+        ("detail", optional(detail)),
+        ("difference", optional(result.difference.as_deref().map(utils::json_string))),
+        ("time_us", result.time_used.as_micros().to_string()),
+        ("judge_overhead_us", result.judge_overhead.as_micros().to_string()),
+        ("cpu_time_ms", result.cpu_time_ms.to_string()),
+        ("memory_bytes", result.memory_used_bytes.to_string()),
+        ("tasks_peak", optional(result.tasks_peak.map(|n| n.to_string()))),
+        ("task_limit_hits", result.task_limit_hits.to_string()),
+        ("cpu_throttled_us", optional(result.cpu_throttled.map(|t| t.as_micros().to_string()))),
+        ("max_threads", optional(result.max_threads.map(|n| n.to_string()))),
+        ("io_read_bytes", result.io_read_bytes.to_string()),
+        ("io_written_bytes", result.io_written_bytes.to_string()),
+        ("io_limit_exceeded", result.io_limit_exceeded.to_string()),
+        ("stderr_bytes", result.stderr_bytes.to_string()),
+        ("stderr_truncated", result.stderr_truncated.to_string()),
+        ("exec_sha256", optional(result.exec_sha256.as_deref().map(utils::json_string))),
+        ("prewarmed", result.prewarmed.to_string()),
+        ("retries", result.retries.to_string()),
+        ("limits", optional(result.limits.map(|l| utils::json_object(&[
+            ("base", limits_json(&l.base)),
+            ("effective", limits_json(&l.effective))
+        ])))),
+        ("sandbox", optional(result.sandbox.map(|sandbox| utils::json_object(&[
+            ("strength", utils::json_string(&sandbox.strength.to_string())),
+            ("seccomp", sandbox.seccomp.to_string())
+        ])))),
+        ("stderr", utils::json_string(&String::from_utf8_lossy(&result.stderr)))
+    ])
+}
+
+/*
+ *  Read back what entry_json wrote, None if anything is off
+ */
+fn parse_entry(text: &str) -> Option<JudgeResult> {
+    let Ok(Value::Object(members)) = json::parse(text) else {
+        return None;
+    };
+    let get = |key: &str| members.iter().find(|(member, _)| member == key).map(|(_, value)| value);
+    let number = |value: Option<&Value>| match value {
+        Some(Value::Number(x)) if x.fract() == 0.0 && *x >= 0.0 => Some(*x as u64),
+        _ => None
+    };
+    // Some(None) for null, None if the value is of neither kind
+    let nullable = |value: Option<&Value>| match value {
+        Some(Value::Null) => Some(None),
+        value => number(value).map(Some)
+    };
+    let micros = |key: &str| number(get(key)).map(Duration::from_micros);
+    let string = |key: &str| match get(key) {
+        Some(Value::String(s)) => Some(s.clone()),
+        _ => None
+    };
+    let limits_of = |value: Option<&Value>| {
+        let Some(Value::Object(members)) = value else {
+            return None;
+        };
+        let get = |key: &str| members.iter().find(|(member, _)| member == key).map(|(_, value)| value);
+        Some(Limits {
+            cpu: Duration::from_micros(number(get("cpu_us"))?),
+            wall: Duration::from_micros(number(get("wall_us"))?),
+            memory_bytes: number(get("memory_bytes"))?
+        })
+    };
+
+    let status = match (string("status")?.as_str(), get("detail")) {
+        ("RE", Some(Value::String(kind))) => JudgeStatus::RuntimeError(match kind.as_str() {
+            "FloatingPointError" => RuntimeErrorKind::FloatingPointError,
+            "SegmentationFault" => RuntimeErrorKind::SegmentationFault,
+            _ => return None
+        }),
+        ("RNZ", Some(Value::Number(value))) if value.fract() == 0.0 => JudgeStatus::ReturnNonZero(*value as i32),
+        (abbr, _) => simple_status(abbr)?
+    };
+    let limits = match get("limits") {
+        Some(Value::Null) => None,
+        Some(Value::Object(members)) => {
+            let get = |key: &str| members.iter().find(|(member, _)| member == key).map(|(_, value)| value);
+            Some(AppliedLimits { base: limits_of(get("base"))?, effective: limits_of(get("effective"))? })
+        },
+        _ => return None
+    };
+    let sandbox = match get("sandbox") {
+        Some(Value::Null) => None,
+        Some(Value::Object(members)) => {
+            let get = |key: &str| members.iter().find(|(member, _)| member == key).map(|(_, value)| value);
+            let strength = match get("strength") {
+                Some(Value::String(name)) => SandboxStrength::from_name(name)?,
+                _ => return None
+            };
+            let Some(&Value::Boolean(seccomp)) = get("seccomp") else {
+                return None;
+            };
+            Some(AppliedSandbox { strength, seccomp })
+        },
+        _ => return None
+    };
+    let Some(&Value::Boolean(io_limit_exceeded)) = get("io_limit_exceeded") else {
+        return None;
+    };
+    let Some(&Value::Boolean(prewarmed)) = get("prewarmed") else {
+        return None;
+    };
+    let Some(&Value::Boolean(stderr_truncated)) = get("stderr_truncated") else {
+        return None;
+    };
+    let difference = match get("difference") {
+        Some(Value::Null) => None,
+        Some(Value::String(difference)) => Some(difference.clone()),
+        _ => return None
+    };
+    let exec_sha256 = match get("exec_sha256") {
+        Some(Value::Null) => None,
+        Some(Value::String(hash)) => Some(hash.clone()),
+        _ => return None
+    };
+    Some(JudgeResult {
+        time_used: micros("time_us")?,
+        judge_overhead: micros("judge_overhead_us")?,
+        cpu_time_ms: number(get("cpu_time_ms"))?,
+        memory_used_bytes: number(get("memory_bytes"))?,
+        tasks_peak: nullable(get("tasks_peak"))?,
+        task_limit_hits: number(get("task_limit_hits"))?,
+        cpu_throttled: nullable(get("cpu_throttled_us"))?.map(Duration::from_micros),
+        max_threads: nullable(get("max_threads"))?.map(|n| n as u32),
+        io_read_bytes: number(get("io_read_bytes"))?,
+        io_written_bytes: number(get("io_written_bytes"))?,
+        io_limit_exceeded,
+        stderr: string("stderr")?.into_bytes(),
+        stderr_bytes: number(get("stderr_bytes"))?,
+        stderr_truncated,
+        difference,
+        exec_sha256,
+        prewarmed,
+        retries: number(get("retries"))? as u32,
+        limits,
+        sandbox,
+        ..JudgeResult::unfinished(status)
+    })
+}
+
+/*
+ *  The verdicts that are only their abbreviation
+ */
+fn simple_status(abbr: &str) -> Option<JudgeStatus> {
+    let status = match abbr {
+        "AC" => JudgeStatus::Accepted,
+        "WA" => JudgeStatus::WrongAnswer,
+        "TLE" => JudgeStatus::TimeLimitExceeded,
+        "MLE" => JudgeStatus::MemoryLimitExceeded,
+        "OLE" => JudgeStatus::OutputLimitExceeded,
+        "ILE" => JudgeStatus::IdlenessLimitExceeded,
+        "SV" => JudgeStatus::SecurityViolation,
+        "PE" => JudgeStatus::PresentationError,
+        "OM" => JudgeStatus::OutputMissing,
+        _ => return None
+    };
+    Some(status)
+}
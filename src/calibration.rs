@@ -0,0 +1,172 @@
+use std::env;
+use std::fs;
+use std::hint::black_box;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::json::{self, Value};
+use crate::utils;
+
+/// CPU time the workload takes on the host the baseline was taken on, an
+/// Intel Xeon server core running a release build of the judger. Debug
+/// builds run it several times slower and score accordingly.
+pub const BASELINE: Duration = Duration::from_secs(2);
+
+/// What the workload comes to, the same on every host
+pub const WORKLOAD_CHECKSUM: u64 = 4_653_510_151_718_946_905;
+
+// Rounds of each loop of the workload, the floating-point one taking a
+// little longer than the integer one
+const INTEGER_ROUNDS: u64 = 300_000_000;
+const FLOAT_ROUNDS: u64 = 300_000_000;
+
+/**
+ *  The reference workload of calibrate: fixed rounds of integer and
+ *  floating-point arithmetic, each round depending on the one before so
+ *  that neither loop can be vectorized or cut short. The floating-point
+ *  operations are all correctly rounded, so it returns WORKLOAD_CHECKSUM
+ *  on every host.
+ */
+pub fn workload() -> u64 {
+    let mut x: u64 = black_box(0x9e37_79b9_7f4a_7c15);
+    let mut sum: u64 = 0;
+    for _ in 0..INTEGER_ROUNDS {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        sum = sum.wrapping_add(x.wrapping_mul(0x2545_f491_4f6c_dd1d) % 1_000_000_007);
+    }
+    let mut y = black_box(0.5f64);
+    let mut acc = 0.0f64;
+    for i in 0..FLOAT_ROUNDS {
+        y = 3.9 * y * (1.0 - y);
+        acc += (y + i as f64).sqrt() / (1.0 + y);
+    }
+    sum ^ acc.to_bits()
+}
+
+/// How fast this host ran the workload against the baseline, and so how
+/// much time programs need here for the limits set on the baseline's host
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calibration {
+    /// BASELINE over the workload's CPU time, above 1 on faster hosts
+    pub score: f64,
+    /// Factor of the time limits, the inverse of the score
+    pub multiplier: f64,
+    /// CPU time the workload took
+    pub cpu_time: Duration,
+    /// When the workload ran, in seconds since the epoch
+    pub measured_at: u64
+}
+
+impl Calibration {
+    /**
+     *  The calibration of a workload that took `cpu_time` just now, the
+     *  score and multiplier to two decimals
+     */
+    pub fn of(cpu_time: Duration) -> Self {
+        let ratio = cpu_time.as_secs_f64().max(0.001) / BASELINE.as_secs_f64();
+        let measured_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Calibration { score: hundredths(1.0 / ratio), multiplier: hundredths(ratio), cpu_time, measured_at }
+    }
+
+    /**
+     *  The calibration stored at `path`, None if there is no file there
+     */
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e)
+        };
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {message}", path.display()));
+        let value = json::parse(&text).map_err(|e| invalid(e.to_string()))?;
+        Self::from_json(&value).map(Some).ok_or_else(|| invalid(String::from("not a calibration")))
+    }
+
+    /**
+     *  Store the calibration at `path`, making its directory if need be.
+     *  It is written beside its place first and renamed there, so that
+     *  judgers starting meanwhile never read half of it.
+     */
+    pub fn store(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        utils::replace_file(path, format!("{}\n", self.to_json()).as_bytes())
+    }
+
+    /**
+     *  The calibration as a JSON object
+     */
+    pub fn to_json(&self) -> String {
+        utils::json_object(&[
+            ("score", self.score.to_string()),
+            ("multiplier", self.multiplier.to_string()),
+            ("cpu_time_ms", self.cpu_time.as_millis().to_string()),
+            ("measured_at", self.measured_at.to_string())
+        ])
+    }
+
+    /**
+     *  A calibration from the JSON object to_json writes, None if it is
+     *  anything else or its multiplier isn't positive
+     */
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let Value::Object(members) = value else {
+            return None;
+        };
+        let number = |name: &str| match members.iter().find(|(member, _)| member == name) {
+            Some((_, Value::Number(x))) if x.is_finite() && *x >= 0.0 => Some(*x),
+            _ => None
+        };
+        let calibration = Calibration {
+            score: number("score")?,
+            multiplier: number("multiplier").filter(|m| *m > 0.0)?,
+            cpu_time: Duration::from_millis(number("cpu_time_ms")? as u64),
+            measured_at: number("measured_at")? as u64
+        };
+        Some(calibration)
+    }
+}
+
+fn hundredths(x: f64) -> f64 {
+    (x * 100.0).round() / 100.0
+}
+
+/**
+ *  Where calibrate stores the calibration unless told otherwise:
+ *  secure-judger/calibration.json under $XDG_STATE_HOME, or under
+ *  ~/.local/state without it. None without either variable.
+ */
+pub fn default_path() -> Option<PathBuf> {
+    let state = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").filter(|dir| !dir.is_empty()).map(|home| Path::new(&home).join(".local/state")))?;
+    Some(state.join("secure-judger/calibration.json"))
+}
+
+static INSTALLED: OnceLock<Calibration> = OnceLock::new();
+
+/**
+ *  Apply `calibration` to every session this process builds from now on,
+ *  their time limits scaled by its multiplier on top of their language's,
+ *  and record it in the environment of their results. Install it before
+ *  the environment is first collected, the environment being collected
+ *  only once. Only the first calibration installed counts, false is
+ *  returned for the others.
+ */
+pub fn install(calibration: Calibration) -> bool {
+    INSTALLED.set(calibration).is_ok()
+}
+
+/**
+ *  The calibration the sessions of this process are scaled by, if one
+ *  was installed
+ */
+pub fn installed() -> Option<&'static Calibration> {
+    INSTALLED.get()
+}
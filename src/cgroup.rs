@@ -0,0 +1,180 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+static RUN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Limits written into the per-run cgroup
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CgroupLimits {
+    pub memory_max: u64,
+    pub pids_max: u64,
+    // CPU bandwidth in cores, unthrottled when None
+    pub cpu_cores: Option<f64>,
+    // Whether to count the disk I/O in io.stat, for an I/O limit
+    pub io: bool
+}
+
+/// A transient cgroup v2 holding a single sandboxed run.
+/// It is created under a configurable root (which needs the wanted
+/// controllers available) and removed again on drop, killing whatever
+/// is left inside it.
+pub(crate) struct RunCgroup {
+    path: PathBuf
+}
+
+impl RunCgroup {
+    /*
+     *  Create a fresh cgroup under `root` with the given limits and swap disabled
+     */
+    pub fn create(root: &Path, limits: &CgroupLimits) -> io::Result<Self> {
+        // Controllers must be enabled on the root for its children to get
+        // the interface files. This fails harmlessly if already enabled or
+        // if the root isn't ours to configure; writing memory.max tells.
+        let controllers = match limits.cpu_cores {
+            Some(_) => "+memory +pids +cpu",
+            None => "+memory +pids"
+        };
+        let _ = fs::write(root.join("cgroup.subtree_control"), controllers);
+        // On its own, as the counts are only used where the controller is
+        // there and the run shouldn't fail without it
+        if limits.io {
+            let _ = fs::write(root.join("cgroup.subtree_control"), "+io");
+        }
+
+        let name = format!(
+            "run-{}-{}",
+            std::process::id(),
+            RUN_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = root.join(name);
+        fs::create_dir(&path)?;
+        let cgroup = RunCgroup { path };
+
+        cgroup.write("memory.max", &limits.memory_max.to_string())?;
+        cgroup.write("pids.max", &limits.pids_max.to_string())?;
+        if let Some(cores) = limits.cpu_cores {
+            const CPU_PERIOD_US: u64 = 100000;
+            let quota = ((cores * CPU_PERIOD_US as f64) as u64).max(1000);
+            cgroup.write("cpu.max", &format!("{quota} {CPU_PERIOD_US}"))?;
+        }
+        // Not every kernel is built with swap accounting
+        let _ = cgroup.write("memory.swap.max", "0");
+        Ok(cgroup)
+    }
+
+    /*
+     *  Open cgroup.procs for writing, so that a process can move itself in
+     *  by writing "0" to it
+     */
+    pub fn open_procs(&self) -> io::Result<OwnedFd> {
+        let f = OpenOptions::new().write(true).open(self.path.join("cgroup.procs"))?;
+        Ok(f.into())
+    }
+
+    /// Current memory usage of the cgroup
+    pub fn memory_current(&self) -> Option<u64> {
+        self.read("memory.current")?.trim().parse().ok()
+    }
+
+    /// Highest memory usage of the cgroup, None on kernels without memory.peak
+    pub fn memory_peak(&self) -> Option<u64> {
+        self.read("memory.peak")?.trim().parse().ok()
+    }
+
+    /// Whether the kernel OOM killer fired inside the cgroup
+    pub fn oom_killed(&self) -> bool {
+        self.read_keyed("memory.events", "oom_kill").is_some_and(|n| n > 0)
+    }
+
+    /// Highest number of tasks alive at once, None on kernels without pids.peak
+    pub fn pids_peak(&self) -> Option<u64> {
+        self.read("pids.peak")?.trim().parse().ok()
+    }
+
+    /// How many times creating a task failed because of pids.max
+    pub fn pids_limit_hits(&self) -> u64 {
+        self.read_keyed("pids.events", "max").unwrap_or(0)
+    }
+
+    /// Total time the cgroup spent throttled by cpu.max
+    pub fn cpu_throttled(&self) -> Option<Duration> {
+        self.read_keyed("cpu.stat", "throttled_usec").map(Duration::from_micros)
+    }
+
+    /// CPU time used by everything in the cgroup so far
+    pub fn cpu_usage(&self) -> Option<Duration> {
+        self.read_keyed("cpu.stat", "usage_usec").map(Duration::from_micros)
+    }
+
+    /// Bytes read from and written to every device so far, as (read,
+    /// written), None without the io controller. Writes are counted as
+    /// they reach the device, so they may lag behind the program.
+    pub fn io_bytes(&self) -> Option<(u64, u64)> {
+        let content = self.read("io.stat")?;
+        let mut totals = (0, 0);
+        // MAJ:MIN rbytes=N wbytes=N rios=N ...
+        for (key, value) in content.split_whitespace().filter_map(|field| field.split_once('=')) {
+            let value: u64 = value.parse().unwrap_or(0);
+            match key {
+                "rbytes" => totals.0 += value,
+                "wbytes" => totals.1 += value,
+                _ => {}
+            }
+        }
+        Some(totals)
+    }
+
+    fn write(&self, file: &str, value: &str) -> io::Result<()> {
+        fs::write(self.path.join(file), value)
+    }
+
+    fn read(&self, file: &str) -> Option<String> {
+        fs::read_to_string(self.path.join(file)).ok()
+    }
+
+    /*
+     *  Read a value from a flat keyed file such as memory.events
+     */
+    fn read_keyed(&self, file: &str, key: &str) -> Option<u64> {
+        let content = self.read(file)?;
+        content.lines()
+            .filter_map(|line| line.split_once(' '))
+            .find(|(k, _)| *k == key)
+            .and_then(|(_, v)| v.trim().parse().ok())
+    }
+
+    fn kill_all(&self) {
+        // cgroup.kill exists since Linux 5.14
+        if self.write("cgroup.kill", "1").is_ok() {
+            return;
+        }
+        if let Some(procs) = self.read("cgroup.procs") {
+            for pid in procs.lines().filter_map(|l| l.trim().parse::<i32>().ok()) {
+                unsafe {
+                    libc::kill(pid, libc::SIGKILL);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for RunCgroup {
+    fn drop(&mut self) {
+        const RMDIR_ATTEMPTS: u32 = 50;
+
+        self.kill_all();
+        // Removal fails with EBUSY until the killed tasks are fully gone
+        for _ in 0..RMDIR_ATTEMPTS {
+            match fs::remove_dir(&self.path) {
+                Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+                    std::thread::sleep(Duration::from_millis(2));
+                },
+                _ => return
+            }
+        }
+    }
+}
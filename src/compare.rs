@@ -0,0 +1,248 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+
+use crate::judger::JudgeStatus;
+
+// Tolerance of float comparison when none is given
+const DEFAULT_TOLERANCE: f64 = 1e-6;
+
+/// How the output of a program is checked against the answer
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Comparison {
+    /// Byte for byte, a Presentation Error if only whitespace or the case
+    /// of letters differs
+    #[default]
+    Exact,
+    /// The whitespace separated tokens have to match, however spaced
+    Tokens,
+    /// Like Tokens, but numbers also match when they are within `tolerance`
+    /// of the answer's, absolutely or relative to it
+    Float {
+        /// Largest difference accepted
+        tolerance: f64
+    }
+}
+
+impl Comparison {
+    /**
+     *  Parse a comparison such as exact, tokens, float or float:1e-9
+     */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.split_once(':') {
+            None if name == "exact" => Some(Self::Exact),
+            None if name == "tokens" => Some(Self::Tokens),
+            None if name == "float" => Some(Self::Float { tolerance: DEFAULT_TOLERANCE }),
+            Some(("float", tolerance)) => match tolerance.parse::<f64>() {
+                Ok(t) if t >= 0.0 && t.is_finite() => Some(Self::Float { tolerance: t }),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    /**
+     *  Check `output` against `answer`, both from their start
+     */
+    pub fn compare(&self, answer: File, output: File) -> io::Result<JudgeStatus> {
+        self.compare_readers(answer, output)
+    }
+
+    /**
+     *  Check `output` against `answer` like compare, from anything that
+     *  can be read twice such as a Cursor over bytes
+     */
+    pub fn compare_readers<A: Read + Seek, O: Read + Seek>(&self, answer: A, output: O) -> io::Result<JudgeStatus> {
+        match self {
+            Self::Exact => compare_content(answer, output),
+            Self::Tokens => compare_tokens(answer, output, |a, b| a == b),
+            Self::Float { tolerance } => compare_tokens(answer, output, |a, b| a == b || close(a, b, *tolerance))
+        }
+    }
+
+    /**
+     *  Where `output` first differs from `answer` the way this comparison
+     *  goes by, such as `line 3: expected "5", got "4"`, or None if it
+     *  doesn't
+     */
+    pub fn difference<A: Read + Seek, O: Read + Seek>(&self, answer: A, output: O) -> io::Result<Option<String>> {
+        match self {
+            Self::Exact => first_different(answer, output, "line", next_line, |a, b| a == b),
+            Self::Tokens => first_different(answer, output, "token", next_token, |a, b| a == b),
+            Self::Float { tolerance } => {
+                first_different(answer, output, "token", next_token, |a, b| a == b || close(a, b, *tolerance))
+            }
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact => f.write_str("exact"),
+            Self::Tokens => f.write_str("tokens"),
+            Self::Float { tolerance } => f.write_fmt(format_args!("float:{tolerance}"))
+        }
+    }
+}
+
+/*
+ *  Judge output files and give a result among AC, PE and WA
+ */
+fn compare_content<A: Read + Seek, O: Read + Seek>(mut answer: A, mut output: O) -> io::Result<JudgeStatus> {
+    if same_bytes(&mut answer, &mut output, Some)? {
+        return Ok(JudgeStatus::Accepted);
+    }
+    let loose = |ch: u8| match ch.is_ascii_whitespace() {
+        true => None,
+        false => Some(ch.to_ascii_uppercase())
+    };
+    match same_bytes(&mut answer, &mut output, loose)? {
+        true => Ok(JudgeStatus::PresentationError),
+        false => Ok(JudgeStatus::WrongAnswer)
+    }
+}
+
+/*
+ *  Whether both read the same from their start once every byte went
+ *  through `normalize`, which drops the bytes it gives None for
+ */
+fn same_bytes<A: Read + Seek, B: Read + Seek>(a: &mut A, b: &mut B, normalize: impl Fn(u8) -> Option<u8>) -> io::Result<bool> {
+    a.seek(SeekFrom::Start(0))?;
+    b.seek(SeekFrom::Start(0))?;
+    let mut a = BufReader::new(a).bytes().filter_map(|ch| ch.map(&normalize).transpose());
+    let mut b = BufReader::new(b).bytes().filter_map(|ch| ch.map(&normalize).transpose());
+    loop {
+        match (a.next().transpose()?, b.next().transpose()?) {
+            (None, None) => return Ok(true),
+            (x, y) if x == y => {},
+            _ => return Ok(false)
+        }
+    }
+}
+
+/*
+ *  Match the tokens of both files pairwise with `matches`, taking the
+ *  answer's first
+ */
+fn compare_tokens<A: Read + Seek, O: Read + Seek>(
+    mut answer: A,
+    mut output: O,
+    matches: impl Fn(&[u8], &[u8]) -> bool
+) -> io::Result<JudgeStatus> {
+    answer.seek(SeekFrom::Start(0))?;
+    output.seek(SeekFrom::Start(0))?;
+    let mut answer = BufReader::new(answer);
+    let mut output = BufReader::new(output);
+    let (mut expected, mut got) = (Vec::new(), Vec::new());
+    loop {
+        let more_expected = next_token(&mut answer, &mut expected)?;
+        let more_got = next_token(&mut output, &mut got)?;
+        match (more_expected, more_got) {
+            (false, false) => return Ok(JudgeStatus::Accepted),
+            (true, true) if matches(&expected, &got) => {},
+            _ => return Ok(JudgeStatus::WrongAnswer)
+        }
+    }
+}
+
+/*
+ *  Read the pieces `next` splits both files into pairwise until a pair
+ *  doesn't `match`, and describe that pair with its number as the `unit`
+ *  it is
+ */
+fn first_different<A: Read + Seek, O: Read + Seek>(
+    mut answer: A,
+    mut output: O,
+    unit: &str,
+    next: fn(&mut dyn BufRead, &mut Vec<u8>) -> io::Result<bool>,
+    matches: impl Fn(&[u8], &[u8]) -> bool
+) -> io::Result<Option<String>> {
+    answer.seek(SeekFrom::Start(0))?;
+    output.seek(SeekFrom::Start(0))?;
+    let mut answer = BufReader::new(answer);
+    let mut output = BufReader::new(output);
+    let (mut expected, mut got) = (Vec::new(), Vec::new());
+    let mut number = 0;
+    loop {
+        number += 1;
+        let more_expected = next(&mut answer, &mut expected)?;
+        let more_got = next(&mut output, &mut got)?;
+        match (more_expected, more_got) {
+            (false, false) => return Ok(None),
+            (true, true) if matches(&expected, &got) => {},
+            _ => {
+                let expected = snippet(more_expected.then_some(&expected[..]));
+                let got = snippet(more_got.then_some(&got[..]));
+                return Ok(Some(format!("{unit} {number}: expected {expected}, got {got}")));
+            }
+        }
+    }
+}
+
+/*
+ *  A piece of a file as shown in a difference, quoted and cut short
+ */
+fn snippet(piece: Option<&[u8]>) -> String {
+    const SHOWN: usize = 40;
+    let Some(piece) = piece else {
+        return String::from("the end of the output");
+    };
+    let text = String::from_utf8_lossy(piece);
+    match text.chars().count() > SHOWN {
+        true => format!("{:?}", text.chars().take(SHOWN - 1).chain(['…']).collect::<String>()),
+        false => format!("{text:?}")
+    }
+}
+
+/*
+ *  Read the next line into `line`, without its newline, false if there
+ *  is none left
+ */
+fn next_line(reader: &mut dyn BufRead, line: &mut Vec<u8>) -> io::Result<bool> {
+    line.clear();
+    if reader.read_until(b'\n', line)? == 0 {
+        return Ok(false);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    Ok(true)
+}
+
+/*
+ *  Read the next whitespace separated token into `token`, false if there
+ *  is none left
+ */
+fn next_token(reader: &mut dyn BufRead, token: &mut Vec<u8>) -> io::Result<bool> {
+    token.clear();
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(!token.is_empty());
+        }
+        let skipped = match token.is_empty() {
+            true => buf.iter().take_while(|ch| ch.is_ascii_whitespace()).count(),
+            false => 0
+        };
+        let taken = buf[skipped..].iter().take_while(|ch| !ch.is_ascii_whitespace()).count();
+        token.extend_from_slice(&buf[skipped..skipped + taken]);
+        let done = skipped + taken < buf.len() && !token.is_empty();
+        reader.consume(skipped + taken);
+        if done {
+            return Ok(true);
+        }
+    }
+}
+
+/*
+ *  Whether both tokens are numbers within `tolerance` of each other,
+ *  absolutely or relative to the answer's
+ */
+fn close(answer: &[u8], output: &[u8], tolerance: f64) -> bool {
+    let number = |token: &[u8]| std::str::from_utf8(token).ok()?.parse::<f64>().ok().filter(|x| x.is_finite());
+    match (number(answer), number(output)) {
+        (Some(a), Some(b)) => (a - b).abs() <= tolerance || (a - b).abs() <= tolerance * a.abs(),
+        _ => false
+    }
+}
@@ -0,0 +1,393 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::compare::Comparison;
+use crate::judger::IoMode;
+use crate::language::{self, LimitMultipliers};
+use crate::problem::{FailurePolicy, TestCase};
+use crate::toml::{self, Entry, Table, Value};
+use crate::utils;
+
+// The tables of a problem file and the keys each of them takes
+const TABLES: [(&str, &[&str]); 8] = [
+    ("limits", &["time", "real_time", "memory", "output"]),
+    ("language", &["name", "time_multiplier", "memory_multiplier"]),
+    ("compare", &["mode", "tolerance"]),
+    ("io", &["input", "output"]),
+    ("tests", &["dir", "manifest", "input_ext", "answer_ext"]),
+    ("validator", &["path"]),
+    ("judging", &["failures"]),
+    // An array of tables, one per test
+    ("test", &["input", "answer", "name", "weight", "time", "memory"])
+];
+
+/// A problem as its TOML file describes it. Everything is optional, the
+/// command line and the judger's defaults fill in what is left out.
+#[derive(Default)]
+pub struct ProblemConfig {
+    /// time of the limits table
+    pub time_limit: Option<Duration>,
+    /// real_time of the limits table
+    pub wall_limit: Option<Duration>,
+    /// memory of the limits table
+    pub memory_limit: Option<u64>,
+    /// output of the limits table
+    pub output_limit: Option<u64>,
+    /// The language table, by its name or its own multipliers
+    pub multipliers: Option<LimitMultipliers>,
+    /// The compare table
+    pub comparison: Option<Comparison>,
+    /// The io table, named files if given
+    pub io_mode: Option<IoMode>,
+    /// Found in a directory as batch --tests does, or listed in a manifest
+    /// relative to it
+    pub tests_dir: Option<PathBuf>,
+    /// manifest of the tests table
+    pub manifest: Option<PathBuf>,
+    /// input_ext of the tests table
+    pub input_ext: Option<String>,
+    /// answer_ext of the tests table
+    pub answer_exts: Option<Vec<String>>,
+    /// Listed one by one as [[test]]
+    pub tests: Vec<TestCase>,
+    /// Checks every input before the program runs on it
+    pub validator: Option<PathBuf>,
+    /// failures of the judging table, once how many failed tests the rest
+    /// are skipped
+    pub failure_policy: Option<FailurePolicy>
+}
+
+/// What is wrong with a problem file, and where
+#[derive(Debug)]
+pub struct ConfigError {
+    /// The problem file
+    pub path: PathBuf,
+    /// Line of the problem file, None if it couldn't be read
+    pub line: Option<usize>,
+    /// What is wrong
+    pub message: String
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => f.write_fmt(format_args!("{}:{line}: {}", self.path.display(), self.message)),
+            None => f.write_fmt(format_args!("{}: {}", self.path.display(), self.message))
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ProblemConfig {
+    /// Read the problem file at `path`, see parse
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| ConfigError { path: path.to_path_buf(), line: None, message: e.to_string() })?;
+        Self::parse(&text, path)
+    }
+
+    /**
+     *  Read the problem file `text`, which is at `path`. Relative paths in
+     *  it are taken from the file's directory, and keys it doesn't know
+     *  are refused so that a misspelt limit doesn't go unnoticed.
+     */
+    pub fn parse(text: &str, path: &Path) -> Result<Self, ConfigError> {
+        let error = |line: usize, message: String| ConfigError { path: path.to_path_buf(), line: Some(line), message };
+        let tables = toml::parse(text).map_err(|e| error(e.line, e.message))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        let mut config = ProblemConfig::default();
+        for table in &tables {
+            check_table(table).map_err(|(line, message)| error(line, message))?;
+            config.read_table(table, base).map_err(|(line, message)| error(line, message))?;
+        }
+        let listed = tables.iter().find(|table| table.name == "test");
+        let found = tables.iter().find(|table| table.name == "tests");
+        if let (Some(listed), Some(_)) = (listed, found) {
+            let message = String::from("tests are either found by [tests] or listed as [[test]], not both");
+            return Err(error(listed.line, message));
+        }
+        Ok(config)
+    }
+
+    fn read_table(&mut self, table: &Table, base: &Path) -> Result<(), (usize, String)> {
+        match table.name.as_str() {
+            // The root table, which check_table keeps empty
+            "" => {},
+            "limits" => {
+                for entry in &table.entries {
+                    match entry.key.as_str() {
+                        "time" => self.time_limit = Some(duration("limits", entry)?),
+                        "real_time" => self.wall_limit = Some(duration("limits", entry)?),
+                        "memory" => self.memory_limit = Some(size("limits", entry)?),
+                        "output" => self.output_limit = Some(size("limits", entry)?),
+                        key => unreachable!("key {key} of [limits] has no handling")
+                    }
+                }
+            },
+            "language" => self.multipliers = Some(read_language(table)?),
+            "compare" => self.comparison = Some(read_comparison(table)?),
+            "io" => self.io_mode = Some(read_io(table)?),
+            "tests" => {
+                let dir = table_string(table, "dir")?.map(|dir| base.join(dir));
+                // Relative to the dir, as with --tests and --manifest
+                let manifest = table_string(table, "manifest")?;
+                self.manifest = manifest.map(|manifest| dir.as_deref().unwrap_or(base).join(manifest));
+                self.tests_dir = dir;
+                if let Some(entry) = find_entry(table, "input_ext") {
+                    self.input_ext = Some(extension("tests", entry, string("tests", entry)?)?);
+                }
+                if let Some(entry) = find_entry(table, "answer_ext") {
+                    self.answer_exts = Some(answer_extensions(entry)?);
+                }
+                if self.tests_dir.is_none() && self.manifest.is_none() {
+                    return Err((table.line, String::from("[tests] needs a dir or a manifest")));
+                }
+            },
+            "validator" => match table_string(table, "path")? {
+                Some(path) => self.validator = Some(base.join(path)),
+                None => return Err((table.line, String::from("[validator] needs a path")))
+            },
+            "judging" => {
+                if let Some(entry) = find_entry(table, "failures") {
+                    let name = string("judging", entry)?;
+                    let Some(policy) = FailurePolicy::from_name(name) else {
+                        let message = format!("unknown failures {name} in [judging], expected run-all, stop-on-first or stop-after:N");
+                        return Err((entry.line, message));
+                    };
+                    self.failure_policy = Some(policy);
+                }
+            },
+            "test" => {
+                let case = read_test(table, base, &self.tests)?;
+                self.tests.push(case);
+            },
+            name => unreachable!("table [{name}] has no handling")
+        }
+        Ok(())
+    }
+}
+
+/*
+ *  The limit multipliers of [language]: those of the language named, with
+ *  the ones given next to it instead
+ */
+fn read_language(table: &Table) -> Result<LimitMultipliers, (usize, String)> {
+    let mut multipliers = LimitMultipliers::default();
+    if let Some(entry) = find_entry(table, "name") {
+        let name = string("language", entry)?;
+        let Some(lang) = language::find(name) else {
+            let names: Vec<&str> = language::LANGUAGES.iter().map(|lang| lang.name).collect();
+            return Err((entry.line, format!("unknown language {name}, expected one of {}", names.join(", "))));
+        };
+        multipliers = lang.limit_multipliers;
+    }
+    if let Some(entry) = find_entry(table, "time_multiplier") {
+        multipliers.time = multiplier("language", entry)?;
+    }
+    if let Some(entry) = find_entry(table, "memory_multiplier") {
+        multipliers.memory = multiplier("language", entry)?;
+    }
+    Ok(multipliers)
+}
+
+/*
+ *  The comparison of [compare], whose tolerance only goes with float
+ */
+fn read_comparison(table: &Table) -> Result<Comparison, (usize, String)> {
+    let Some(entry) = find_entry(table, "mode") else {
+        return Err((table.line, String::from("[compare] needs a mode, one of exact, tokens or float")));
+    };
+    let mode = string("compare", entry)?;
+    let Some(comparison) = Comparison::from_name(mode) else {
+        return Err((entry.line, format!("unknown mode {mode} in [compare], expected exact, tokens or float")));
+    };
+    let Some(entry) = find_entry(table, "tolerance") else {
+        return Ok(comparison);
+    };
+    let tolerance = number("compare", entry)?;
+    match comparison {
+        _ if !tolerance.is_finite() || tolerance < 0.0 => {
+            Err((entry.line, format!("tolerance in [compare] has to be 0 or more, not {tolerance}")))
+        },
+        Comparison::Float { .. } => Ok(Comparison::Float { tolerance }),
+        _ => Err((entry.line, String::from("tolerance in [compare] only goes with mode = \"float\"")))
+    }
+}
+
+/*
+ *  The files of [io] the program reads and writes instead of stdin and
+ *  stdout
+ */
+fn read_io(table: &Table) -> Result<IoMode, (usize, String)> {
+    let (Some(input), Some(output)) = (table_string(table, "input")?, table_string(table, "output")?) else {
+        return Err((table.line, String::from("[io] needs both an input and an output file name")));
+    };
+    if !utils::is_file_name(input) || !utils::is_file_name(output) || input == output {
+        let message = String::from("input and output of [io] have to be two plain file names, such as \"problem.in\"");
+        return Err((table.line, message));
+    }
+    Ok(IoMode::NamedFiles { input_name: input.to_string(), output_name: output.to_string() })
+}
+
+fn answer_extensions(entry: &Entry) -> Result<Vec<String>, (usize, String)> {
+    match &entry.value {
+        Value::String(ext) => Ok(vec![extension("tests", entry, ext)?]),
+        Value::Array(items) if !items.is_empty() => items.iter()
+            .map(|item| match item {
+                Value::String(ext) => extension("tests", entry, ext),
+                other => Err(mistyped("tests", entry, "an array of strings", other))
+            })
+            .collect(),
+        other => Err(mistyped("tests", entry, "a string or an array of them, such as [\"ans\", \"out\"]", other))
+    }
+}
+
+/*
+ *  Refuse tables and keys a problem file doesn't have, suggesting what
+ *  was probably meant
+ */
+fn check_table(table: &Table) -> Result<(), (usize, String)> {
+    if table.name.is_empty() {
+        return match table.entries.first() {
+            Some(entry) => {
+                let tables: Vec<String> = TABLES.iter().map(|(name, _)| header(name)).collect();
+                Err((entry.line, format!("key {} has to be in a table, one of {}", entry.key, tables.join(", "))))
+            },
+            None => Ok(())
+        };
+    }
+    let Some((_, keys)) = TABLES.iter().find(|(name, _)| *name == table.name) else {
+        let suggestion = utils::closest(&table.name, TABLES.iter().map(|(name, _)| *name));
+        let message = match suggestion {
+            Some(name) => format!("unknown table [{}], did you mean [{name}]?", table.name),
+            None => {
+                let tables: Vec<String> = TABLES.iter().map(|(name, _)| header(name)).collect();
+                format!("unknown table [{}], expected one of {}", table.name, tables.join(", "))
+            }
+        };
+        return Err((table.line, message));
+    };
+    let array = table.name == "test";
+    if table.array != array {
+        let message = match array {
+            true => String::from("tests are listed as [[test]], one table each"),
+            false => format!("[{0}] is a single table, not [[{0}]]", table.name)
+        };
+        return Err((table.line, message));
+    }
+    for entry in &table.entries {
+        if keys.contains(&entry.key.as_str()) {
+            continue;
+        }
+        let message = match utils::closest(&entry.key, keys.iter().copied()) {
+            Some(key) => format!("unknown key {} in [{}], did you mean {key}?", entry.key, table.name),
+            None => format!("unknown key {} in [{}], expected one of {}", entry.key, table.name, keys.join(", "))
+        };
+        return Err((entry.line, message));
+    }
+    Ok(())
+}
+
+/*
+ *  A test listed as [[test]], which needs an input and an answer
+ */
+fn read_test(table: &Table, base: &Path, earlier: &[TestCase]) -> Result<TestCase, (usize, String)> {
+    let (Some(input), Some(answer)) = (table_string(table, "input")?, table_string(table, "answer")?) else {
+        return Err((table.line, String::from("[[test]] needs an input and an answer")));
+    };
+    let mut case = TestCase::new(base.join(input), base.join(answer));
+    for entry in &table.entries {
+        match entry.key.as_str() {
+            "name" => case.label = Some(string("test", entry)?.to_string()),
+            "weight" => {
+                let weight = number("test", entry)?;
+                if !weight.is_finite() || weight < 0.0 {
+                    return Err((entry.line, format!("weight of a test has to be 0 or more, not {weight}")));
+                }
+                case.weight = weight;
+            },
+            "time" => case.time_limit = Some(duration("test", entry)?),
+            "memory" => case.memory_limit = Some(size("test", entry)?),
+            _ => {}
+        }
+    }
+    if let Some(twin) = earlier.iter().find(|twin| twin.name() == case.name()) {
+        let message = format!("test {} is listed twice, give one of them a name", twin.name());
+        return Err((table.line, message));
+    }
+    Ok(case)
+}
+
+// How a table is written, [[test]] being an array of them
+fn header(name: &str) -> String {
+    match name {
+        "test" => String::from("[[test]]"),
+        _ => format!("[{name}]")
+    }
+}
+
+fn find_entry<'a>(table: &'a Table, key: &str) -> Option<&'a Entry> {
+    table.entries.iter().find(|entry| entry.key == key)
+}
+
+fn table_string<'a>(table: &'a Table, key: &str) -> Result<Option<&'a str>, (usize, String)> {
+    match find_entry(table, key) {
+        Some(entry) => string(&table.name, entry).map(Some),
+        None => Ok(None)
+    }
+}
+
+fn mistyped(table: &str, entry: &Entry, expected: &str, got: &Value) -> (usize, String) {
+    (entry.line, format!("{} in [{table}] has to be {expected}, not {}", entry.key, got.kind()))
+}
+
+fn string<'a>(table: &str, entry: &'a Entry) -> Result<&'a str, (usize, String)> {
+    match &entry.value {
+        Value::String(text) => Ok(text),
+        other => Err(mistyped(table, entry, "a string", other))
+    }
+}
+
+fn number(table: &str, entry: &Entry) -> Result<f64, (usize, String)> {
+    match entry.value {
+        Value::Integer(n) => Ok(n as f64),
+        Value::Float(x) => Ok(x),
+        ref other => Err(mistyped(table, entry, "a number", other))
+    }
+}
+
+fn duration(table: &str, entry: &Entry) -> Result<Duration, (usize, String)> {
+    match &entry.value {
+        Value::String(text) => utils::parse_duration(text).ok()
+            .filter(|limit| !limit.is_zero())
+            .ok_or((entry.line, format!("invalid {} in [{table}]: {text:?}, expected a time such as \"2s\" or \"500ms\"", entry.key))),
+        other => Err(mistyped(table, entry, "a time in quotes, such as \"2s\"", other))
+    }
+}
+
+fn size(table: &str, entry: &Entry) -> Result<u64, (usize, String)> {
+    match &entry.value {
+        Value::String(text) => utils::parse_size(text).ok()
+            .filter(|&bytes| bytes > 0)
+            .ok_or((entry.line, format!("invalid {} in [{table}]: {text:?}, expected a size such as \"256m\"", entry.key))),
+        other => Err(mistyped(table, entry, "a size in quotes, such as \"256m\"", other))
+    }
+}
+
+fn multiplier(table: &str, entry: &Entry) -> Result<f64, (usize, String)> {
+    let value = number(table, entry)?;
+    match value.is_finite() && value > 0.0 {
+        true => Ok(value),
+        false => Err((entry.line, format!("{} in [{table}] has to be a positive number, not {value}", entry.key)))
+    }
+}
+
+fn extension(table: &str, entry: &Entry, ext: &str) -> Result<String, (usize, String)> {
+    match !ext.is_empty() && !ext.contains(['.', '/']) {
+        true => Ok(ext.to_string()),
+        false => Err((entry.line, format!("{} in [{table}] takes extensions without their dot, such as \"ans\"", entry.key)))
+    }
+}
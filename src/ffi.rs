@@ -0,0 +1,404 @@
+// The C API of the cdylib, declared in include/secure_judger.h. Strings
+// come in as a pointer and a length of UTF-8 and go out the same way,
+// every function returns one of the SJ_* codes, and a panic is caught
+// here and returned as SJ_ERR_PANIC instead of unwinding into C.
+
+use std::cell::RefCell;
+use std::ffi::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{io, ptr, slice};
+
+use crate::compare::Comparison;
+use crate::judger::{JudgeSession, JudgeStatus};
+use crate::sandbox::{InputSource, SandboxStrength};
+
+/// Success
+pub const SJ_OK: i32 = 0;
+/// A pointer that must not be null was
+pub const SJ_ERR_NULL: i32 = -1;
+/// A string wasn't UTF-8
+pub const SJ_ERR_UTF8: i32 = -2;
+/// A value out of range, a name not known, or a session that doesn't
+/// check out
+pub const SJ_ERR_INVALID: i32 = -3;
+/// Judging failed, see sj_last_error
+pub const SJ_ERR_RUN: i32 = -4;
+/// The judger panicked, see sj_last_error
+pub const SJ_ERR_PANIC: i32 = -5;
+
+/// Accepted
+pub const SJ_VERDICT_AC: i32 = 0;
+/// Wrong Answer
+pub const SJ_VERDICT_WA: i32 = 1;
+/// Time Limit Exceeded
+pub const SJ_VERDICT_TLE: i32 = 2;
+/// Memory Limit Exceeded
+pub const SJ_VERDICT_MLE: i32 = 3;
+/// Output Limit Exceeded
+pub const SJ_VERDICT_OLE: i32 = 4;
+/// Runtime Error, killed by a signal
+pub const SJ_VERDICT_RE: i32 = 5;
+/// Idleness Limit Exceeded
+pub const SJ_VERDICT_ILE: i32 = 6;
+/// Security Violation
+pub const SJ_VERDICT_SV: i32 = 7;
+/// Presentation Error
+pub const SJ_VERDICT_PE: i32 = 8;
+/// Output Missing
+pub const SJ_VERDICT_OM: i32 = 9;
+/// Return Value Not Zero
+pub const SJ_VERDICT_RNZ: i32 = 10;
+/// System Error
+pub const SJ_VERDICT_SE: i32 = 11;
+/// Cancelled
+pub const SJ_VERDICT_CAN: i32 = 12;
+/// Skipped
+pub const SJ_VERDICT_SKIP: i32 = 13;
+
+thread_local! {
+    // Message of the last failure on this thread, NUL-terminated
+    static LAST_ERROR: RefCell<String> = RefCell::new(String::from("\0"));
+}
+
+/// What a session is made of, built into a JudgeSession by every sj_run
+pub struct SjSession {
+    exec: PathBuf,
+    args: Vec<String>,
+    input: Option<PathBuf>,
+    answer: Option<PathBuf>,
+    time_limit: Option<Duration>,
+    memory_limit: Option<u64>,
+    output_limit: Option<u64>,
+    comparison: Option<Comparison>,
+    strength: Option<SandboxStrength>
+}
+
+/// The outcome of sj_run
+pub struct SjResult {
+    verdict: i32,
+    time_ms: u64,
+    cpu_time_ms: u64,
+    memory_bytes: u64,
+    // The verdict in words, NUL-terminated for callers that want that
+    detail: String
+}
+
+// A failure, returned as its code with the message kept for sj_last_error
+struct Failure(i32, String);
+
+type Outcome = Result<(), Failure>;
+
+/*
+ *  Run `f` and turn its failure or panic into a code, keeping the message
+ */
+fn guard(f: impl FnOnce() -> Outcome) -> i32 {
+    let (code, message) = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return SJ_OK,
+        Ok(Err(Failure(code, message))) => (code, message),
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("unknown panic"));
+            (SJ_ERR_PANIC, format!("panicked: {message}"))
+        }
+    };
+    LAST_ERROR.with(|last| *last.borrow_mut() = format!("{message}\0"));
+    code
+}
+
+/*
+ *  The `len` bytes at `ptr` as a string
+ */
+unsafe fn string<'a>(ptr: *const c_char, len: usize, what: &str) -> Result<&'a str, Failure> {
+    if ptr.is_null() {
+        return Err(Failure(SJ_ERR_NULL, format!("{what} is null")));
+    }
+    let bytes = slice::from_raw_parts(ptr.cast::<u8>(), len);
+    std::str::from_utf8(bytes).map_err(|e| Failure(SJ_ERR_UTF8, format!("{what} isn't UTF-8: {e}")))
+}
+
+unsafe fn session_mut<'a>(session: *mut SjSession) -> Result<&'a mut SjSession, Failure> {
+    session.as_mut().ok_or_else(|| Failure(SJ_ERR_NULL, String::from("session is null")))
+}
+
+fn verdict_code(status: &JudgeStatus) -> i32 {
+    match status {
+        JudgeStatus::Accepted              => SJ_VERDICT_AC,
+        JudgeStatus::WrongAnswer           => SJ_VERDICT_WA,
+        JudgeStatus::TimeLimitExceeded     => SJ_VERDICT_TLE,
+        JudgeStatus::MemoryLimitExceeded   => SJ_VERDICT_MLE,
+        JudgeStatus::OutputLimitExceeded   => SJ_VERDICT_OLE,
+        JudgeStatus::RuntimeError(_)       => SJ_VERDICT_RE,
+        JudgeStatus::IdlenessLimitExceeded => SJ_VERDICT_ILE,
+        JudgeStatus::SecurityViolation     => SJ_VERDICT_SV,
+        JudgeStatus::PresentationError     => SJ_VERDICT_PE,
+        JudgeStatus::OutputMissing         => SJ_VERDICT_OM,
+        JudgeStatus::ReturnNonZero(_)      => SJ_VERDICT_RNZ,
+        JudgeStatus::SystemError(_)        => SJ_VERDICT_SE,
+        JudgeStatus::Cancelled             => SJ_VERDICT_CAN,
+        JudgeStatus::Skipped               => SJ_VERDICT_SKIP
+    }
+}
+
+/**
+ *  Make a session judging the executable at `exec`, into `*session_out`.
+ *  Without an answer set, every run is a System Error.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_session_new(exec: *const c_char, exec_len: usize, session_out: *mut *mut SjSession) -> i32 {
+    guard(|| {
+        if session_out.is_null() {
+            return Err(Failure(SJ_ERR_NULL, String::from("session_out is null")));
+        }
+        let exec = PathBuf::from(string(exec, exec_len, "exec")?);
+        let session = SjSession {
+            exec,
+            args: Vec::new(),
+            input: None,
+            answer: None,
+            time_limit: None,
+            memory_limit: None,
+            output_limit: None,
+            comparison: None,
+            strength: None
+        };
+        *session_out = Box::into_raw(Box::new(session));
+        Ok(())
+    })
+}
+
+/**
+ *  Pass `arg` to the program after those added before
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_session_add_arg(session: *mut SjSession, arg: *const c_char, arg_len: usize) -> i32 {
+    guard(|| {
+        let arg = string(arg, arg_len, "arg")?.to_string();
+        session_mut(session)?.args.push(arg);
+        Ok(())
+    })
+}
+
+/**
+ *  Feed the program the file at `path` on stdin
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_session_set_input(session: *mut SjSession, path: *const c_char, path_len: usize) -> i32 {
+    guard(|| {
+        let path = PathBuf::from(string(path, path_len, "path")?);
+        session_mut(session)?.input = Some(path);
+        Ok(())
+    })
+}
+
+/**
+ *  Compare the program's output with the file at `path`
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_session_set_answer(session: *mut SjSession, path: *const c_char, path_len: usize) -> i32 {
+    guard(|| {
+        let path = PathBuf::from(string(path, path_len, "path")?);
+        session_mut(session)?.answer = Some(path);
+        Ok(())
+    })
+}
+
+/**
+ *  Limit the program to `ms` milliseconds of CPU time
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_session_set_time_limit_ms(session: *mut SjSession, ms: u64) -> i32 {
+    guard(|| {
+        session_mut(session)?.time_limit = Some(Duration::from_millis(ms));
+        Ok(())
+    })
+}
+
+/**
+ *  Limit the program to `bytes` of memory
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_session_set_memory_limit(session: *mut SjSession, bytes: u64) -> i32 {
+    guard(|| {
+        session_mut(session)?.memory_limit = Some(bytes);
+        Ok(())
+    })
+}
+
+/**
+ *  Limit what the program writes to `bytes`
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_session_set_output_limit(session: *mut SjSession, bytes: u64) -> i32 {
+    guard(|| {
+        session_mut(session)?.output_limit = Some(bytes);
+        Ok(())
+    })
+}
+
+/**
+ *  Compare in `mode`: exact, tokens, float or float:TOLERANCE
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_session_set_mode(session: *mut SjSession, mode: *const c_char, mode_len: usize) -> i32 {
+    guard(|| {
+        let mode = string(mode, mode_len, "mode")?;
+        let comparison = Comparison::from_name(mode)
+            .ok_or_else(|| Failure(SJ_ERR_INVALID, format!("unknown comparison mode {mode}")))?;
+        session_mut(session)?.comparison = Some(comparison);
+        Ok(())
+    })
+}
+
+/**
+ *  Sandbox the program as `strength` says: require, best-effort or
+ *  disabled
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_session_set_sandbox(session: *mut SjSession, strength: *const c_char, strength_len: usize) -> i32 {
+    guard(|| {
+        let name = string(strength, strength_len, "strength")?;
+        let strength = SandboxStrength::from_name(name)
+            .ok_or_else(|| Failure(SJ_ERR_INVALID, format!("unknown sandbox mode {name}")))?;
+        session_mut(session)?.strength = Some(strength);
+        Ok(())
+    })
+}
+
+/**
+ *  Judge the program once, into `*result_out`. Every verdict is SJ_OK,
+ *  the program's failures included; SJ_ERR_INVALID if the session doesn't
+ *  check out and SJ_ERR_RUN if the judger couldn't run it.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_run(session: *mut SjSession, result_out: *mut *mut SjResult) -> i32 {
+    guard(|| {
+        if result_out.is_null() {
+            return Err(Failure(SJ_ERR_NULL, String::from("result_out is null")));
+        }
+        let session = session_mut(session)?;
+        let mut builder = JudgeSession::builder(session.exec.clone());
+        if let Some(input) = &session.input {
+            builder = builder.input(InputSource::File(input.clone()));
+        }
+        if let Some(answer) = &session.answer {
+            builder = builder.answer(answer.clone());
+        }
+        if let Some(limit) = session.time_limit {
+            builder = builder.time_limit(limit);
+        }
+        if let Some(bytes) = session.memory_limit {
+            builder = builder.memory_limit(bytes);
+        }
+        if let Some(bytes) = session.output_limit {
+            builder = builder.output_limit(bytes);
+        }
+        if let Some(comparison) = session.comparison {
+            builder = builder.comparison(comparison);
+        }
+        if let Some(strength) = session.strength {
+            builder = builder.sandbox_strength(strength);
+        }
+        let judge = builder.build().map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidInput => Failure(SJ_ERR_INVALID, e.to_string()),
+            _ => Failure(SJ_ERR_RUN, e.to_string())
+        })?;
+        let args: Vec<&str> = session.args.iter().map(String::as_str).collect();
+        let judged = judge.run_judge(&args).map_err(|e| Failure(SJ_ERR_RUN, e.to_string()))?;
+        let result = SjResult {
+            verdict: verdict_code(&judged.status),
+            time_ms: judged.time_used.as_millis() as u64,
+            cpu_time_ms: judged.cpu_time_ms,
+            memory_bytes: judged.memory_used_bytes,
+            detail: format!("{}\0", judged.status)
+        };
+        *result_out = Box::into_raw(Box::new(result));
+        Ok(())
+    })
+}
+
+/**
+ *  The verdict, one of the SJ_VERDICT_* codes, or SJ_ERR_NULL
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_result_verdict(result: *const SjResult) -> i32 {
+    result.as_ref().map_or(SJ_ERR_NULL, |r| r.verdict)
+}
+
+/**
+ *  Wall time the program ran for in milliseconds, 0 for a null result
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_result_time_ms(result: *const SjResult) -> u64 {
+    result.as_ref().map_or(0, |r| r.time_ms)
+}
+
+/**
+ *  CPU time the program used in milliseconds, 0 for a null result
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_result_cpu_time_ms(result: *const SjResult) -> u64 {
+    result.as_ref().map_or(0, |r| r.cpu_time_ms)
+}
+
+/**
+ *  Peak resident memory of the program in bytes, 0 for a null result
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_result_memory_bytes(result: *const SjResult) -> u64 {
+    result.as_ref().map_or(0, |r| r.memory_bytes)
+}
+
+/**
+ *  The verdict in words, such as "[RE] Runtime Error (SIGSEGV)", with its
+ *  length in bytes in `*len` if that isn't null. NUL-terminated, and
+ *  valid until the result is freed.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_result_detail(result: *const SjResult, len: *mut usize) -> *const c_char {
+    let Some(result) = result.as_ref() else {
+        return ptr::null();
+    };
+    if let Some(len) = len.as_mut() {
+        *len = result.detail.len() - 1;
+    }
+    result.detail.as_ptr().cast()
+}
+
+/**
+ *  Why the last call on this thread failed, with its length in `*len` if
+ *  that isn't null. NUL-terminated, and valid until the next failure on
+ *  the thread.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_last_error(len: *mut usize) -> *const c_char {
+    LAST_ERROR.with(|last| {
+        let last = last.borrow();
+        if let Some(len) = len.as_mut() {
+            *len = last.len() - 1;
+        }
+        last.as_ptr().cast()
+    })
+}
+
+/**
+ *  Free a session from sj_session_new, doing nothing for null
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_session_free(session: *mut SjSession) {
+    if !session.is_null() {
+        drop(Box::from_raw(session));
+    }
+}
+
+/**
+ *  Free a result from sj_run, doing nothing for null
+ */
+#[no_mangle]
+pub unsafe extern "C" fn sj_result_free(result: *mut SjResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
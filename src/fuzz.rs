@@ -0,0 +1,98 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::compare::Comparison;
+use crate::config::ProblemConfig;
+use crate::json;
+use crate::judger::JudgeStatus;
+
+// Every mode, the float one both exactly and with a tolerance
+const MODES: [Comparison; 4] = [
+    Comparison::Exact,
+    Comparison::Tokens,
+    Comparison::Float { tolerance: 0.0 },
+    Comparison::Float { tolerance: 1e-6 }
+];
+
+/**
+ *  Split fuzzer input into an answer and an output at the first 0xff
+ *  byte, all of it being the answer without one
+ */
+pub fn split(data: &[u8]) -> (&[u8], &[u8]) {
+    match data.iter().position(|&byte| byte == 0xff) {
+        Some(at) => (&data[..at], &data[at + 1..]),
+        None => (data, &[])
+    }
+}
+
+/**
+ *  Compare `output` with `answer` in every mode, panicking where the
+ *  comparisons disagree with each other or with themselves: the same
+ *  inputs always get the same verdict, a file always matches itself, an
+ *  exact match is a match in every mode, and what matches token for token
+ *  is at worst a Presentation Error byte for byte.
+ */
+pub fn comparisons(answer: &[u8], output: &[u8]) {
+    let verdicts: Vec<JudgeStatus> = MODES.iter().map(|mode| compare(mode, answer, output)).collect();
+    for (mode, verdict) in MODES.iter().zip(&verdicts) {
+        let again = compare(mode, answer, output);
+        assert!(again.abbr() == verdict.abbr(), "{mode} gave {} and then {}", verdict.abbr(), again.abbr());
+        assert!(
+            matches!(verdict, JudgeStatus::Accepted | JudgeStatus::PresentationError | JudgeStatus::WrongAnswer),
+            "{mode} gave {}",
+            verdict.abbr()
+        );
+        if !matches!(mode, Comparison::Exact) {
+            assert!(!matches!(verdict, JudgeStatus::PresentationError), "{mode} gave PE");
+        }
+        for text in [answer, output] {
+            let itself = compare(mode, text, text);
+            assert!(matches!(itself, JudgeStatus::Accepted), "{mode} gave {} for the same text", itself.abbr());
+        }
+    }
+    let [exact, tokens, float_exact, float] = &verdicts[..] else {
+        unreachable!();
+    };
+    let accepted = |verdict: &JudgeStatus| matches!(verdict, JudgeStatus::Accepted);
+    assert_eq!(accepted(exact), answer == output, "exact gave {} for {answer:?} and {output:?}", exact.abbr());
+    if accepted(exact) {
+        assert!(accepted(tokens) && accepted(float_exact) && accepted(float), "an exact match isn't one in every mode");
+    }
+    if accepted(tokens) {
+        assert!(!matches!(exact, JudgeStatus::WrongAnswer), "equal tokens are a wrong answer byte for byte");
+        assert!(accepted(float_exact) && accepted(float), "equal tokens aren't close numbers");
+    }
+    if accepted(float_exact) {
+        assert!(accepted(float), "an exact float match isn't one with a tolerance");
+    }
+}
+
+/**
+ *  Parse `text` as a problem file and as JSON, panicking where a parser
+ *  blames a line past the end of the text, or where JSON it accepted
+ *  doesn't read back the same once written out again
+ */
+pub fn config(text: &[u8]) {
+    let Ok(text) = std::str::from_utf8(text) else {
+        return;
+    };
+    if let Err(e) = ProblemConfig::parse(text, Path::new("problem.toml")) {
+        let lines = text.lines().count().max(1);
+        let line = e.line.expect("parsing text always says where it failed");
+        assert!((1..=lines + 1).contains(&line), "line {line} of {lines}: {}", e.message);
+    }
+    match json::parse(text) {
+        Ok(value) => {
+            let written = value.to_json();
+            match json::parse(&written) {
+                Ok(again) => assert!(again == value, "{text:?} read back from {written:?} as {again:?}"),
+                Err(e) => panic!("{text:?} written as {written:?}, which doesn't parse: {e}")
+            }
+        },
+        Err(e) => assert!(e.offset <= text.chars().count(), "offset {} past the text", e.offset)
+    }
+}
+
+fn compare(mode: &Comparison, answer: &[u8], output: &[u8]) -> JudgeStatus {
+    mode.compare_readers(Cursor::new(answer), Cursor::new(output)).expect("reading memory never fails")
+}
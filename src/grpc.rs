@@ -0,0 +1,1588 @@
+//! A gRPC service judging requests from other hosts, over HTTP/2 in
+//! cleartext, with a server and a client for it. proto/judge.proto
+//! defines the service, which the messages here encode as.
+//!
+//! Judger/Judge judges a request and answers with its result;
+//! Judger/JudgeEvents streams Started when a worker takes the request,
+//! Ticks while it runs and Finished with the result. Cancelling the call
+//! cancels the judging. Files come either inline or, on a server given a
+//! root, as paths under it.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::{self, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::admission::{Admission, Demand};
+use crate::compare::Comparison;
+use crate::h2::{self, ConnectionError, Frame};
+use crate::hpack::{self, Decoder};
+use crate::judger::{JudgeHandle, JudgeObserver, JudgeResult, JudgeSessionBuilder};
+use crate::language;
+use crate::metrics::METRICS;
+use crate::problem::TestCase;
+use crate::protobuf::{DecodeError, Reader, Writer};
+use crate::sandbox::{InputSource, SandboxPolicy, ScratchDir};
+
+/// The path of the unary method
+pub const JUDGE_PATH: &str = "/secure_judger.Judger/Judge";
+/// The path of the streaming method
+pub const JUDGE_EVENTS_PATH: &str = "/secure_judger.Judger/JudgeEvents";
+
+// How often the waiting threads check whether the server stops
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// A client has this long to send the rest of a frame it started
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// Connections served at once, more are refused
+const MAX_CONNECTIONS: usize = 64;
+// Requests a connection may be sending at once, more are refused
+const MAX_STREAMS: usize = 100;
+// The window the server gives streams and its connections, so that a
+// request with large inline files isn't held up by WINDOW_UPDATEs
+const RECEIVE_WINDOW: i64 = 1 << 20;
+// Room in a request for what isn't inline files
+const REQUEST_OVERHEAD: usize = 64 << 10;
+
+/// The status codes of gRPC
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Code {
+    /// Not an error
+    Ok = 0,
+    /// The call was cancelled
+    Cancelled = 1,
+    /// An error without a better code, such as a status the client
+    /// doesn't know
+    Unknown = 2,
+    /// The request is wrong and won't be judged as it is
+    InvalidArgument = 3,
+    /// A file of the request isn't there
+    NotFound = 5,
+    /// The request names a path it may not
+    PermissionDenied = 7,
+    /// The queue is full, or the request is too large
+    ResourceExhausted = 8,
+    /// The method isn't one of the service
+    Unimplemented = 12,
+    /// The judger failed
+    Internal = 13,
+    /// The server can't be reached or is shutting down
+    Unavailable = 14
+}
+
+impl Code {
+    /// The code of `value`, Unknown for those not listed here
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            0 => Code::Ok,
+            1 => Code::Cancelled,
+            3 => Code::InvalidArgument,
+            5 => Code::NotFound,
+            7 => Code::PermissionDenied,
+            8 => Code::ResourceExhausted,
+            12 => Code::Unimplemented,
+            13 => Code::Internal,
+            14 => Code::Unavailable,
+            _ => Code::Unknown
+        }
+    }
+}
+
+/// Why a call failed, as the grpc-status and grpc-message it ended with
+#[derive(Clone, Debug)]
+pub struct Status {
+    /// What kind of failure
+    pub code: Code,
+    /// What went wrong, for people
+    pub message: String
+}
+
+impl Status {
+    /// A status of `code` saying `message`
+    pub fn new(code: Code, message: impl Into<String>) -> Self {
+        Status { code, message: message.into() }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for Status {}
+
+impl From<DecodeError> for Status {
+    fn from(e: DecodeError) -> Self {
+        Status::new(Code::InvalidArgument, format!("malformed message: {}", e.0))
+    }
+}
+
+/// Where a file of a request comes from
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    /// A path on the server, under the root it was given
+    Path(PathBuf),
+    /// The contents, up to the server's inline limit
+    Inline(Vec<u8>)
+}
+
+/// Limits of a request, 0 leaving the server's
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    /// CPU time in milliseconds
+    pub time_limit_ms: u64,
+    /// Wall time in milliseconds
+    pub wall_time_limit_ms: u64,
+    /// Memory in bytes
+    pub memory_limit_bytes: u64,
+    /// Output in bytes
+    pub output_limit_bytes: u64
+}
+
+/// What the sandbox lets the program do
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Policy {
+    /// Allow threads and child processes, as
+    /// SandboxPolicy::threads_allowed does
+    pub allow_threads: bool
+}
+
+/// A test to judge: the program, its input and the answer, with what the
+/// server's defaults are overridden by
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JudgeRequest {
+    /// The executable
+    pub exec: Option<Source>,
+    /// What the program gets on stdin
+    pub input: Option<Source>,
+    /// What the output is compared with
+    pub answer: Option<Source>,
+    /// The program's arguments after its name
+    pub args: Vec<String>,
+    /// Limits of the run
+    pub limits: Limits,
+    /// A comparison as Comparison::from_name takes, empty for the server's
+    pub compare: String,
+    /// A language whose limit multipliers apply, empty for none
+    pub lang: String,
+    /// What the sandbox allows
+    pub policy: Policy
+}
+
+/// The result of a request, as JudgeResult has it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JudgeResponse {
+    /// The verdict's abbreviation, such as AC or TLE
+    pub status: String,
+    /// The verdict in words, such as `[RE] Runtime Error (SIGSEGV)`
+    pub detail: String,
+    /// Wall time the program ran for
+    pub time_ms: u64,
+    /// CPU time of the program and its descendants
+    pub cpu_time_ms: u64,
+    /// Peak resident memory
+    pub memory_bytes: u64,
+    /// Beginning of what the program wrote to stderr
+    pub stderr: Vec<u8>,
+    /// Times starting the run failed before it went through
+    pub retries: u32,
+    /// Whether the run was under a seccomp filter
+    pub seccomp: bool,
+    /// The whole result as JudgeResult::to_json writes it
+    pub result_json: String
+}
+
+/// What JudgeEvents streams about a request
+#[derive(Clone, Debug, PartialEq)]
+pub enum JudgeEvent {
+    /// A worker took the request and starts the program
+    Started,
+    /// The program is still running
+    Tick {
+        /// Since it started
+        elapsed_ms: u64,
+        /// What it uses now, 0 if that isn't sampled
+        memory_bytes: u64
+    },
+    /// The program was judged, the last event
+    Finished(JudgeResponse)
+}
+
+impl Source {
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        match self {
+            Source::Path(path) => writer.message(1, path.to_string_lossy().as_bytes()),
+            Source::Inline(bytes) => writer.message(2, bytes)
+        }
+        writer.finish()
+    }
+
+    fn decode(data: &[u8]) -> Result<Option<Self>, DecodeError> {
+        let mut reader = Reader::new(data);
+        let mut source = None;
+        while let Some((number, value)) = reader.field()? {
+            match number {
+                1 => source = Some(Source::Path(PathBuf::from(value.as_string("path")?))),
+                2 => source = Some(Source::Inline(value.as_bytes("inline")?.to_vec())),
+                _ => {}
+            }
+        }
+        Ok(source)
+    }
+}
+
+impl Limits {
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.u64(1, self.time_limit_ms);
+        writer.u64(2, self.wall_time_limit_ms);
+        writer.u64(3, self.memory_limit_bytes);
+        writer.u64(4, self.output_limit_bytes);
+        writer.finish()
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader::new(data);
+        let mut limits = Limits::default();
+        while let Some((number, value)) = reader.field()? {
+            match number {
+                1 => limits.time_limit_ms = value.as_u64("time_limit_ms")?,
+                2 => limits.wall_time_limit_ms = value.as_u64("wall_time_limit_ms")?,
+                3 => limits.memory_limit_bytes = value.as_u64("memory_limit_bytes")?,
+                4 => limits.output_limit_bytes = value.as_u64("output_limit_bytes")?,
+                _ => {}
+            }
+        }
+        Ok(limits)
+    }
+}
+
+impl JudgeRequest {
+    /// The request in the wire format
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        for (number, source) in [(1, &self.exec), (2, &self.input), (3, &self.answer)] {
+            if let Some(source) = source {
+                writer.message(number, &source.encode());
+            }
+        }
+        for arg in &self.args {
+            writer.message(4, arg.as_bytes());
+        }
+        if self.limits != Limits::default() {
+            writer.message(5, &self.limits.encode());
+        }
+        writer.string(6, &self.compare);
+        writer.string(7, &self.lang);
+        if self.policy != Policy::default() {
+            let mut policy = Writer::default();
+            policy.bool(1, self.policy.allow_threads);
+            writer.message(8, &policy.finish());
+        }
+        writer.finish()
+    }
+
+    /// Read a request from the wire format
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader::new(data);
+        let mut request = JudgeRequest::default();
+        while let Some((number, value)) = reader.field()? {
+            match number {
+                1 => request.exec = Source::decode(value.as_bytes("exec")?)?,
+                2 => request.input = Source::decode(value.as_bytes("input")?)?,
+                3 => request.answer = Source::decode(value.as_bytes("answer")?)?,
+                4 => request.args.push(value.as_string("args")?),
+                5 => request.limits = Limits::decode(value.as_bytes("limits")?)?,
+                6 => request.compare = value.as_string("compare")?,
+                7 => request.lang = value.as_string("lang")?,
+                8 => {
+                    let mut policy = Reader::new(value.as_bytes("policy")?);
+                    while let Some((number, value)) = policy.field()? {
+                        if number == 1 {
+                            request.policy.allow_threads = value.as_bool("allow_threads")?;
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+        Ok(request)
+    }
+}
+
+impl JudgeResponse {
+    /// The response of a result
+    pub fn from_result(result: &JudgeResult) -> Self {
+        JudgeResponse {
+            status: result.status.abbr().to_string(),
+            detail: result.status.to_string(),
+            time_ms: result.time_used.as_millis() as u64,
+            cpu_time_ms: result.cpu_time_ms,
+            memory_bytes: result.memory_used_bytes,
+            stderr: result.stderr.clone(),
+            retries: result.retries,
+            seccomp: result.sandbox.is_some_and(|sandbox| sandbox.seccomp),
+            result_json: result.to_json()
+        }
+    }
+
+    /// The response in the wire format
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.string(1, &self.status);
+        writer.string(2, &self.detail);
+        writer.u64(3, self.time_ms);
+        writer.u64(4, self.cpu_time_ms);
+        writer.u64(5, self.memory_bytes);
+        writer.bytes(6, &self.stderr);
+        writer.u64(7, u64::from(self.retries));
+        writer.bool(8, self.seccomp);
+        writer.string(9, &self.result_json);
+        writer.finish()
+    }
+
+    /// Read a response from the wire format
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader::new(data);
+        let mut response = JudgeResponse::default();
+        while let Some((number, value)) = reader.field()? {
+            match number {
+                1 => response.status = value.as_string("status")?,
+                2 => response.detail = value.as_string("detail")?,
+                3 => response.time_ms = value.as_u64("time_ms")?,
+                4 => response.cpu_time_ms = value.as_u64("cpu_time_ms")?,
+                5 => response.memory_bytes = value.as_u64("memory_bytes")?,
+                6 => response.stderr = value.as_bytes("stderr")?.to_vec(),
+                7 => response.retries = value.as_u64("retries")? as u32,
+                8 => response.seccomp = value.as_bool("seccomp")?,
+                9 => response.result_json = value.as_string("result_json")?,
+                _ => {}
+            }
+        }
+        Ok(response)
+    }
+}
+
+impl JudgeEvent {
+    /// The event in the wire format
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        match self {
+            JudgeEvent::Started => writer.message(1, &[]),
+            JudgeEvent::Tick { elapsed_ms, memory_bytes } => {
+                let mut tick = Writer::default();
+                tick.u64(1, *elapsed_ms);
+                tick.u64(2, *memory_bytes);
+                writer.message(2, &tick.finish());
+            },
+            JudgeEvent::Finished(response) => writer.message(3, &response.encode())
+        }
+        writer.finish()
+    }
+
+    /// Read an event from the wire format
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader::new(data);
+        let mut event = None;
+        while let Some((number, value)) = reader.field()? {
+            match number {
+                1 => event = Some(JudgeEvent::Started),
+                2 => {
+                    let mut tick = Reader::new(value.as_bytes("tick")?);
+                    let (mut elapsed_ms, mut memory_bytes) = (0, 0);
+                    while let Some((number, value)) = tick.field()? {
+                        match number {
+                            1 => elapsed_ms = value.as_u64("elapsed_ms")?,
+                            2 => memory_bytes = value.as_u64("memory_bytes")?,
+                            _ => {}
+                        }
+                    }
+                    event = Some(JudgeEvent::Tick { elapsed_ms, memory_bytes });
+                },
+                3 => event = Some(JudgeEvent::Finished(JudgeResponse::decode(value.as_bytes("finished")?)?)),
+                _ => {}
+            }
+        }
+        event.ok_or_else(|| DecodeError(String::from("an event of no known kind")))
+    }
+}
+
+/*
+ *  The gRPC messages of a body: each a compressed flag, a 4 byte length
+ *  and the message. None if the body ends within a message or one is
+ *  compressed, which no encoding was offered for.
+ */
+fn grpc_messages(body: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut messages = Vec::new();
+    let mut rest = body;
+    while !rest.is_empty() {
+        let &[compressed, a, b, c, d, ..] = rest else {
+            return None;
+        };
+        let length = u32::from_be_bytes([a, b, c, d]) as usize;
+        if compressed != 0 || rest.len() < 5 + length {
+            return None;
+        }
+        messages.push(&rest[5..5 + length]);
+        rest = &rest[5 + length..];
+    }
+    Some(messages)
+}
+
+/*
+ *  A message framed for a gRPC body, uncompressed
+ */
+fn grpc_frame(message: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(5 + message.len());
+    framed.push(0);
+    framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    framed.extend_from_slice(message);
+    framed
+}
+
+/*
+ *  Whether the headers of a call are those of JudgeEvents rather than
+ *  Judge, or why it can't be served
+ */
+fn call_kind(headers: &[(String, String)]) -> Result<bool, Status> {
+    let header = |name: &str| headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+    let streaming = match header(":path") {
+        Some(JUDGE_PATH) => false,
+        Some(JUDGE_EVENTS_PATH) => true,
+        Some(path) => return Err(Status::new(Code::Unimplemented, format!("no method {path}"))),
+        None => return Err(Status::new(Code::Internal, "no :path"))
+    };
+    if header(":method") != Some("POST") || !header("content-type").is_some_and(|kind| kind.starts_with("application/grpc")) {
+        return Err(Status::new(Code::Internal, "a gRPC call is a POST of application/grpc"));
+    }
+    if header("grpc-encoding").is_some_and(|encoding| encoding != "identity") {
+        return Err(Status::new(Code::Unimplemented, "only the identity encoding is supported"));
+    }
+    Ok(streaming)
+}
+
+/*
+ *  grpc-message percent-encoded, as the spec has it
+ */
+fn percent_encode(message: &str) -> String {
+    message.bytes().map(|byte| match byte {
+        b' '..=b'~' if byte != b'%' => char::from(byte).to_string(),
+        _ => format!("%{byte:02X}")
+    }).collect()
+}
+
+fn percent_decode(message: &str) -> String {
+    let bytes = message.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok()).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn status_headers(status: &Status) -> Vec<(&'static str, String)> {
+    vec![("grpc-status", (status.code as u32).to_string()), ("grpc-message", percent_encode(&status.message))]
+}
+
+fn wait_readable(fd: i32, timeout: Duration) -> bool {
+    let mut pfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+    unsafe { libc::poll(&mut pfd, 1, timeout.as_millis() as i32) > 0 }
+}
+
+/// How a server judges
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Requests judged at once
+    pub workers: usize,
+    /// Requests waiting for a worker before more are refused with
+    /// RESOURCE_EXHAUSTED
+    pub queue_limit: usize,
+    /// Where requests may name files by path, for trusted deployments.
+    /// Without one, files have to come inline.
+    pub root: Option<PathBuf>,
+    /// Largest inline file
+    pub inline_limit: usize,
+    /// Where inline files are stored until they are judged
+    pub scratch_base: PathBuf,
+    /// Memory the requests judged at once may reserve in all, as their
+    /// memory limits scaled for their language. Those that don't fit
+    /// wait their turn and one over it is refused with INVALID_ARGUMENT.
+    pub memory_budget: Option<u64>
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            workers: 1,
+            queue_limit: 64,
+            root: None,
+            inline_limit: 16 << 20,
+            scratch_base: std::env::temp_dir(),
+            memory_budget: None
+        }
+    }
+}
+
+/// A request checked and its files in place
+struct Prepared {
+    exec: PathBuf,
+    input: PathBuf,
+    answer: PathBuf,
+    request: JudgeRequest,
+    comparison: Option<Comparison>,
+    // Holds the inline files until the request is judged
+    _files: Option<ScratchDir>
+}
+
+/// A prepared request waiting for a worker, with where to answer it
+struct Job {
+    connection: Arc<Connection>,
+    stream: u32,
+    streaming: bool,
+    prepared: Prepared,
+    cancel: JudgeHandle,
+    demand: Demand
+}
+
+/// A stream the server still answers on
+struct OutStream {
+    window: i64,
+    cancel: JudgeHandle
+}
+
+/// The sending side of a connection, shared by its reader and the
+/// workers answering on it
+struct Outgoing {
+    writer: TcpStream,
+    window: i64,
+    // The window new streams start with, from the client's SETTINGS
+    initial_window: i64,
+    streams: HashMap<u32, OutStream>,
+    closed: bool
+}
+
+struct Connection {
+    out: Mutex<Outgoing>,
+    // Signalled when a window opens or the connection closes
+    window_opened: Condvar
+}
+
+impl Connection {
+    /*
+     *  Write `bytes` unless the connection is closed, closing it if the
+     *  write fails
+     */
+    fn write(out: &mut Outgoing, bytes: &[u8]) -> bool {
+        if out.closed {
+            return false;
+        }
+        if out.writer.write_all(bytes).is_err() {
+            out.closed = true;
+        }
+        !out.closed
+    }
+
+    fn send_frame(&self, kind: u8, flags: u8, stream: u32, payload: &[u8]) -> bool {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        Connection::write(&mut out, &h2::frame_bytes(kind, flags, stream, payload))
+    }
+
+    fn open(&self, stream: u32, cancel: JudgeHandle) {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        let window = out.initial_window;
+        out.streams.insert(stream, OutStream { window, cancel });
+    }
+
+    /*
+     *  Send headers on `stream` if it is still open, closing it with them
+     *  if `end_stream`
+     */
+    fn send_headers(&self, stream: u32, headers: &[(&str, String)], end_stream: bool) -> bool {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        if !out.streams.contains_key(&stream) {
+            return false;
+        }
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(name, value)| (*name, value.as_str())).collect();
+        let sent = Connection::write(&mut out, &h2::headers_bytes(stream, &hpack::encode(&headers), end_stream));
+        if end_stream {
+            out.streams.remove(&stream);
+        }
+        sent
+    }
+
+    /*
+     *  Send `data` on `stream` as the windows let it through, waiting for
+     *  them to open. False if the stream or the connection went away.
+     */
+    fn send_data(&self, stream: u32, mut data: &[u8]) -> bool {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        while !data.is_empty() {
+            let stream_window = match out.streams.get(&stream) {
+                Some(open) if !out.closed => open.window,
+                _ => return false
+            };
+            let room = out.window.min(stream_window).min(h2::MAX_FRAME as i64);
+            if room <= 0 {
+                out = self.window_opened.wait_timeout(out, POLL_INTERVAL).unwrap_or_else(|e| e.into_inner()).0;
+                continue;
+            }
+            let (chunk, rest) = data.split_at(data.len().min(room as usize));
+            if !Connection::write(&mut out, &h2::frame_bytes(h2::DATA, 0, stream, chunk)) {
+                return false;
+            }
+            out.window -= chunk.len() as i64;
+            if let Some(open) = out.streams.get_mut(&stream) {
+                open.window -= chunk.len() as i64;
+            }
+            data = rest;
+        }
+        true
+    }
+
+    fn send_message(&self, stream: u32, message: &[u8]) -> bool {
+        self.send_data(stream, &grpc_frame(message))
+    }
+
+    fn send_trailers(&self, stream: u32, status: &Status) -> bool {
+        self.send_headers(stream, &status_headers(status), true)
+    }
+
+    /*
+     *  Answer `stream` with `status` alone, in a Trailers-Only response
+     */
+    fn fail(&self, stream: u32, status: &Status) {
+        let mut headers = vec![(":status", String::from("200")), ("content-type", String::from("application/grpc"))];
+        headers.extend(status_headers(status));
+        self.send_headers(stream, &headers, true);
+    }
+
+    /*
+     *  The client reset `stream`: cancel its judging and send no more
+     */
+    fn reset(&self, stream: u32) {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(open) = out.streams.remove(&stream) {
+            open.cancel.cancel();
+        }
+        self.window_opened.notify_all();
+    }
+
+    fn window_update(&self, stream: u32, increment: u32) -> Result<(), ConnectionError> {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        let window = match stream {
+            0 => &mut out.window,
+            _ => match out.streams.get_mut(&stream) {
+                Some(open) => &mut open.window,
+                None => return Ok(())
+            }
+        };
+        *window += i64::from(increment);
+        if *window > h2::MAX_WINDOW {
+            return Err(ConnectionError::new(h2::FLOW_CONTROL_ERROR, "window over 2^31-1"));
+        }
+        self.window_opened.notify_all();
+        Ok(())
+    }
+
+    fn set_initial_window(&self, window: u32) -> Result<(), ConnectionError> {
+        let window = i64::from(window);
+        if window > h2::MAX_WINDOW {
+            return Err(ConnectionError::new(h2::FLOW_CONTROL_ERROR, "initial window over 2^31-1"));
+        }
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        let delta = window - out.initial_window;
+        out.initial_window = window;
+        for open in out.streams.values_mut() {
+            open.window += delta;
+        }
+        self.window_opened.notify_all();
+        Ok(())
+    }
+
+    /*
+     *  Close the connection, cancelling what it still waits on
+     */
+    fn close(&self, goaway: Option<&ConnectionError>, last_stream: u32) {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(error) = goaway {
+            let mut payload = h2::u32_payload(last_stream).to_vec();
+            payload.extend_from_slice(&error.code.to_be_bytes());
+            payload.extend_from_slice(error.message.as_bytes());
+            payload.truncate(h2::MAX_FRAME);
+            Connection::write(&mut out, &h2::frame_bytes(h2::GOAWAY, 0, 0, &payload));
+        }
+        out.closed = true;
+        let _ = out.writer.shutdown(std::net::Shutdown::Both);
+        for (_, open) in out.streams.drain() {
+            open.cancel.cancel();
+        }
+        self.window_opened.notify_all();
+    }
+}
+
+/// A request being received
+struct Incoming {
+    streaming: bool,
+    body: Vec<u8>
+}
+
+/// What the threads of a server share
+struct Shared<'a> {
+    config: &'a ServerConfig,
+    queue: Mutex<VecDeque<Job>>,
+    // Signalled when a job is queued or the server stops
+    ready: Condvar,
+    stopping: &'a AtomicBool,
+    connections: AtomicUsize,
+    // Makes the session of an executable under a policy
+    builder: &'a (dyn Fn(PathBuf, SandboxPolicy) -> JudgeSessionBuilder + Sync),
+    admission: Admission
+}
+
+/// Judges the requests of gRPC clients with a pool of workers, until
+/// stopped
+pub struct Server {
+    listener: TcpListener,
+    config: ServerConfig,
+    stopping: AtomicBool
+}
+
+impl Server {
+    /**
+     *  Listen on `address`, with the root of `config` made absolute
+     */
+    pub fn bind(address: &str, mut config: ServerConfig) -> io::Result<Self> {
+        if let Some(root) = &config.root {
+            let resolved = fs::canonicalize(root).map_err(|e| io::Error::new(e.kind(), format!("root {}: {e}", root.display())))?;
+            if !resolved.is_dir() {
+                return Err(io::Error::new(ErrorKind::InvalidInput, format!("root {} is not a directory", root.display())));
+            }
+            config.root = Some(resolved);
+        }
+        config.workers = config.workers.max(1);
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Server { listener, config, stopping: AtomicBool::new(false) })
+    }
+
+    /// The address the server listens on, with the port it was given
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Make serve return, cancelling the requests left
+    pub fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+    }
+
+    /**
+     *  Serve until stop is called. `builder` makes the session of an
+     *  executable under a policy, with the limits requests don't give.
+     */
+    pub fn serve(&self, builder: impl Fn(PathBuf, SandboxPolicy) -> JudgeSessionBuilder + Sync) -> io::Result<()> {
+        let shared = Shared {
+            config: &self.config,
+            queue: Mutex::new(VecDeque::new()),
+            ready: Condvar::new(),
+            stopping: &self.stopping,
+            connections: AtomicUsize::new(0),
+            builder: &builder,
+            admission: Admission::new(self.config.memory_budget, Vec::new())
+        };
+        let shared = &shared;
+        thread::scope(|scope| {
+            for _ in 0..self.config.workers {
+                scope.spawn(move || shared.work());
+            }
+            while !shared.stopping() {
+                match self.listener.accept() {
+                    Ok((stream, _)) if shared.connections.load(Ordering::SeqCst) >= MAX_CONNECTIONS => drop(stream),
+                    Ok((stream, _)) => {
+                        shared.connections.fetch_add(1, Ordering::SeqCst);
+                        scope.spawn(move || {
+                            if let Err(e) = shared.serve_connection(stream) {
+                                debug!("gRPC connection failed: {e}");
+                            }
+                            shared.connections.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    },
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        wait_readable(self.listener.as_raw_fd(), POLL_INTERVAL);
+                    },
+                    Err(e) => {
+                        debug!("cannot accept a gRPC client: {e}");
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                }
+            }
+            let mut queue = shared.queue.lock().unwrap_or_else(|e| e.into_inner());
+            for job in queue.drain(..) {
+                job.connection.fail(job.stream, &Status::new(Code::Unavailable, "the server is shutting down"));
+            }
+            METRICS.set_queued(0);
+            shared.ready.notify_all();
+        });
+        Ok(())
+    }
+}
+
+impl Shared<'_> {
+    fn stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+
+    /*
+     *  Read the frames of a client until it leaves or the server stops,
+     *  queueing the requests it completes
+     */
+    fn serve_connection(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        let fd = stream.as_raw_fd();
+        let connection = Arc::new(Connection {
+            out: Mutex::new(Outgoing {
+                writer: stream.try_clone()?,
+                window: h2::DEFAULT_WINDOW,
+                initial_window: h2::DEFAULT_WINDOW,
+                streams: HashMap::new(),
+                closed: false
+            }),
+            window_opened: Condvar::new()
+        });
+        let mut reader = BufReader::new(stream);
+        let mut preface = [0u8; 24];
+        reader.read_exact(&mut preface)?;
+        if preface != h2::PREFACE {
+            return Err(io::Error::new(ErrorKind::InvalidData, "not an HTTP/2 client"));
+        }
+        let settings = [
+            (h2::SETTINGS_ENABLE_PUSH, 0),
+            (h2::SETTINGS_MAX_CONCURRENT_STREAMS, MAX_STREAMS as u32),
+            (h2::SETTINGS_INITIAL_WINDOW_SIZE, RECEIVE_WINDOW as u32),
+            (h2::SETTINGS_MAX_FRAME_SIZE, h2::MAX_FRAME as u32)
+        ];
+        connection.send_frame(h2::SETTINGS, 0, 0, &h2::settings_payload(&settings));
+        connection.send_frame(h2::WINDOW_UPDATE, 0, 0, &h2::u32_payload((RECEIVE_WINDOW - h2::DEFAULT_WINDOW) as u32));
+
+        let mut state = ConnectionState {
+            connection: &connection,
+            decoder: Decoder::default(),
+            incoming: HashMap::new(),
+            header_block: None,
+            last_stream: 0
+        };
+        let outcome = loop {
+            if self.stopping() {
+                break Err(ConnectionError::new(h2::NO_ERROR, "the server is shutting down"));
+            }
+            if reader.buffer().is_empty() && !wait_readable(fd, POLL_INTERVAL) {
+                continue;
+            }
+            let frame = match h2::read_frame(&mut reader) {
+                Ok(Ok(frame)) => frame,
+                Ok(Err(e)) => break Err(e),
+                Err(e) => {
+                    debug!("gRPC client left: {e}");
+                    break Ok(());
+                }
+            };
+            match self.handle_frame(&mut state, frame) {
+                Ok(true) => {},
+                Ok(false) => break Ok(()),
+                Err(e) => break Err(e)
+            }
+        };
+        if let Err(e) = &outcome {
+            debug!("closing a gRPC connection: {}", e.message);
+        }
+        connection.close(outcome.as_ref().err(), state.last_stream);
+        Ok(())
+    }
+
+    /*
+     *  Act on a frame, false once the client said it leaves
+     */
+    fn handle_frame(&self, state: &mut ConnectionState, frame: Frame) -> Result<bool, ConnectionError> {
+        let connection = state.connection;
+        if let Some((stream, _, _)) = &state.header_block {
+            if frame.kind != h2::CONTINUATION || frame.stream != *stream {
+                return Err(ConnectionError::new(h2::PROTOCOL_ERROR, "header block interrupted"));
+            }
+        }
+        match frame.kind {
+            h2::SETTINGS if frame.flags & h2::ACK == 0 => {
+                for (id, value) in h2::parse_settings(&frame.payload)? {
+                    if id == h2::SETTINGS_INITIAL_WINDOW_SIZE {
+                        connection.set_initial_window(value)?;
+                    }
+                }
+                connection.send_frame(h2::SETTINGS, h2::ACK, 0, &[]);
+            },
+            h2::PING if frame.flags & h2::ACK == 0 => {
+                connection.send_frame(h2::PING, h2::ACK, 0, &frame.payload);
+            },
+            h2::WINDOW_UPDATE => connection.window_update(frame.stream, h2::parse_u32(&frame.payload)?)?,
+            h2::RST_STREAM => {
+                state.incoming.remove(&frame.stream);
+                connection.reset(frame.stream);
+            },
+            h2::GOAWAY => return Ok(false),
+            h2::HEADERS => {
+                let block = h2::frame_content(&frame)?.to_vec();
+                let end_stream = frame.flags & h2::END_STREAM != 0;
+                state.header_block = Some((frame.stream, block, end_stream));
+                if frame.flags & h2::END_HEADERS != 0 {
+                    self.headers_done(state)?;
+                }
+            },
+            h2::CONTINUATION => {
+                let Some((_, block, _)) = &mut state.header_block else {
+                    return Err(ConnectionError::new(h2::PROTOCOL_ERROR, "CONTINUATION without HEADERS"));
+                };
+                // Its headers couldn't be decoded anyway
+                if block.len() + frame.payload.len() > hpack::MAX_HEADER_LIST {
+                    return Err(ConnectionError::new(h2::ENHANCE_YOUR_CALM, "header block too large"));
+                }
+                block.extend_from_slice(&frame.payload);
+                if frame.flags & h2::END_HEADERS != 0 {
+                    self.headers_done(state)?;
+                }
+            },
+            h2::DATA => {
+                let content = h2::frame_content(&frame)?;
+                // Whatever the stream became, the window of the connection
+                // has to open again
+                if !frame.payload.is_empty() {
+                    let increment = h2::u32_payload(frame.payload.len() as u32);
+                    connection.send_frame(h2::WINDOW_UPDATE, 0, 0, &increment);
+                    connection.send_frame(h2::WINDOW_UPDATE, 0, frame.stream, &increment);
+                }
+                let limit = 3 * self.config.inline_limit + REQUEST_OVERHEAD;
+                let Some(incoming) = state.incoming.get_mut(&frame.stream) else {
+                    return Ok(true);
+                };
+                if incoming.body.len() + content.len() > limit {
+                    state.incoming.remove(&frame.stream);
+                    connection.fail(frame.stream, &Status::new(Code::ResourceExhausted, "request too large"));
+                    connection.send_frame(h2::RST_STREAM, 0, frame.stream, &h2::u32_payload(h2::NO_ERROR));
+                    return Ok(true);
+                }
+                incoming.body.extend_from_slice(content);
+                if frame.flags & h2::END_STREAM != 0 {
+                    self.dispatch(state, frame.stream);
+                }
+            },
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /*
+     *  A header block is complete: start a call, or end the request body
+     *  if it is the client's trailers
+     */
+    fn headers_done(&self, state: &mut ConnectionState) -> Result<(), ConnectionError> {
+        let (stream, block, end_stream) = state.header_block.take().unwrap();
+        let headers = state.decoder.decode(&block).map_err(|e| ConnectionError::new(h2::COMPRESSION_ERROR, e.0))?;
+        if state.incoming.contains_key(&stream) {
+            if end_stream {
+                self.dispatch(state, stream);
+            }
+            return Ok(());
+        }
+        if stream % 2 == 0 || stream <= state.last_stream {
+            return Err(ConnectionError::new(h2::PROTOCOL_ERROR, format!("stream {stream} can't be opened")));
+        }
+        state.last_stream = stream;
+        let connection = state.connection;
+        if state.incoming.len() >= MAX_STREAMS {
+            connection.send_frame(h2::RST_STREAM, 0, stream, &h2::u32_payload(h2::REFUSED_STREAM));
+            return Ok(());
+        }
+        connection.open(stream, JudgeHandle::new());
+        let streaming = match call_kind(&headers) {
+            Ok(streaming) => streaming,
+            Err(status) => {
+                connection.fail(stream, &status);
+                return Ok(());
+            }
+        };
+        state.incoming.insert(stream, Incoming { streaming, body: Vec::new() });
+        if end_stream {
+            self.dispatch(state, stream);
+        }
+        Ok(())
+    }
+
+    /*
+     *  The request of `stream` is complete: check it and queue it
+     */
+    fn dispatch(&self, state: &mut ConnectionState, stream: u32) {
+        let Some(incoming) = state.incoming.remove(&stream) else {
+            return;
+        };
+        let connection = state.connection;
+        let cancel = {
+            let out = connection.out.lock().unwrap_or_else(|e| e.into_inner());
+            match out.streams.get(&stream) {
+                Some(open) => open.cancel.clone(),
+                None => return
+            }
+        };
+        let prepared = match self.prepare(&incoming.body) {
+            Ok(prepared) => prepared,
+            Err(status) => return connection.fail(stream, &status)
+        };
+        let job = match self.demand(&prepared) {
+            Ok(demand) => Job { connection: Arc::clone(connection), stream, streaming: incoming.streaming, prepared, cancel, demand },
+            Err(status) => return connection.fail(stream, &status)
+        };
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        if self.stopping() {
+            return connection.fail(stream, &Status::new(Code::Unavailable, "the server is shutting down"));
+        }
+        if queue.len() >= self.config.queue_limit {
+            let message = format!("{} requests are waiting already", queue.len());
+            return connection.fail(stream, &Status::new(Code::ResourceExhausted, message));
+        }
+        queue.push_back(job);
+        METRICS.set_queued(queue.len());
+        self.ready.notify_one();
+    }
+
+    /*
+     *  Read the request of a body and put its files in place, inline ones
+     *  in a scratch directory
+     */
+    fn prepare(&self, body: &[u8]) -> Result<Prepared, Status> {
+        let messages = grpc_messages(body).ok_or_else(|| Status::new(Code::Internal, "malformed or compressed gRPC message"))?;
+        let [message] = messages[..] else {
+            return Err(Status::new(Code::InvalidArgument, format!("expected one request, got {}", messages.len())));
+        };
+        let request = JudgeRequest::decode(message)?;
+        let comparison = match request.compare.as_str() {
+            "" => None,
+            mode => Some(Comparison::from_name(mode).ok_or_else(|| {
+                Status::new(Code::InvalidArgument, format!("unknown compare {mode}, expected exact, tokens, float or float:TOLERANCE"))
+            })?)
+        };
+        if !request.lang.is_empty() && language::find(&request.lang).is_none() {
+            return Err(Status::new(Code::InvalidArgument, format!("unknown lang {}", request.lang)));
+        }
+        let mut files: Option<ScratchDir> = None;
+        let mut paths = Vec::new();
+        for (key, source) in [("exec", &request.exec), ("input", &request.input), ("answer", &request.answer)] {
+            let path = match source {
+                None => return Err(Status::new(Code::InvalidArgument, format!("missing {key}"))),
+                Some(Source::Path(path)) => self.confine(key, path)?,
+                Some(Source::Inline(bytes)) => {
+                    if bytes.len() > self.config.inline_limit {
+                        return Err(Status::new(Code::ResourceExhausted, format!("inline {key} over {} bytes", self.config.inline_limit)));
+                    }
+                    let stored = |e: io::Error| Status::new(Code::Internal, format!("cannot store the inline {key}: {e}"));
+                    let scratch = match files.take() {
+                        Some(scratch) => scratch,
+                        None => ScratchDir::create(&self.config.scratch_base).map_err(stored)?
+                    };
+                    let mode = match key {
+                        "exec" => 0o700,
+                        _ => 0o600
+                    };
+                    scratch.create_file(key, mode).and_then(|mut file| file.write_all(bytes)).map_err(stored)?;
+                    let path = scratch.file(key);
+                    files = Some(scratch);
+                    path
+                }
+            };
+            paths.push(path);
+        }
+        let [exec, input, answer]: [PathBuf; 3] = paths.try_into().unwrap();
+        Ok(Prepared { exec, input, answer, request, comparison, _files: files })
+    }
+
+    /*
+     *  What judging a prepared request reserves, the memory limit of its
+     *  session scaled for its language, refused if it can never fit
+     */
+    fn demand(&self, prepared: &Prepared) -> Result<Demand, Status> {
+        let session = request_builder(prepared, self.builder)
+            .build()
+            .map_err(|e| Status::new(Code::InvalidArgument, format!("invalid judging setup: {e}")))?;
+        let demand = Demand { memory_bytes: session.limits().effective.memory_bytes, dedicated_core: false };
+        self.admission.check(demand).map_err(|message| Status::new(Code::InvalidArgument, message))?;
+        Ok(demand)
+    }
+
+    /*
+     *  Resolve a path of a request in the root, refusing it without a root
+     *  or if it leads out of it. A path outside and one that doesn't exist
+     *  get the same answer, so that files outside can't be told apart.
+     */
+    fn confine(&self, key: &str, path: &Path) -> Result<PathBuf, Status> {
+        let Some(root) = &self.config.root else {
+            return Err(Status::new(Code::PermissionDenied, format!("{key} has to be inline, this server takes no paths")));
+        };
+        fs::canonicalize(root.join(path))
+            .ok()
+            .filter(|resolved| resolved.starts_with(root))
+            .ok_or_else(|| Status::new(Code::PermissionDenied, format!("{key} {} is not a file under the root", path.display())))
+    }
+
+    /*
+     *  Judge queued jobs one after another until the server stops, the
+     *  job first in the queue taken once the host has room for it
+     */
+    fn work(&self) {
+        loop {
+            let (job, reservation) = {
+                let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+                loop {
+                    if self.stopping() {
+                        return;
+                    }
+                    if let Some(reservation) = queue.front().and_then(|job| self.admission.try_reserve(job.demand)) {
+                        let job = queue.pop_front().unwrap();
+                        METRICS.set_queued(queue.len());
+                        break (job, reservation);
+                    }
+                    queue = self.ready.wait_timeout(queue, POLL_INTERVAL).unwrap_or_else(|e| e.into_inner()).0;
+                }
+            };
+            if !job.cancel.is_cancelled() {
+                METRICS.job_started();
+                judge(&job, self.builder);
+                METRICS.job_finished();
+            }
+            // Given back under the lock, so that no worker checks for room
+            // between it and the signal
+            let queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+            drop(reservation);
+            self.ready.notify_all();
+            drop(queue);
+        }
+    }
+}
+
+/// The state of a connection's reader
+struct ConnectionState<'a> {
+    connection: &'a Arc<Connection>,
+    decoder: Decoder,
+    incoming: HashMap<u32, Incoming>,
+    // A header block waiting for its CONTINUATIONs: the stream, the block
+    // so far and whether it ends the stream
+    header_block: Option<(u32, Vec<u8>, bool)>,
+    last_stream: u32
+}
+
+/// Streams the progress of a JudgeEvents call
+struct EventSender {
+    connection: Arc<Connection>,
+    stream: u32
+}
+
+impl JudgeObserver for EventSender {
+    fn on_run_start(&self, _test: &TestCase) {
+        self.connection.send_message(self.stream, &JudgeEvent::Started.encode());
+    }
+
+    fn on_tick(&self, elapsed: Duration, rss: Option<u64>) {
+        let tick = JudgeEvent::Tick { elapsed_ms: elapsed.as_millis() as u64, memory_bytes: rss.unwrap_or(0) };
+        self.connection.send_message(self.stream, &tick.encode());
+    }
+}
+
+/*
+ *  The session of a prepared request made by `builder`, with the files
+ *  and the limits the request gives
+ */
+fn request_builder(prepared: &Prepared, builder: &dyn Fn(PathBuf, SandboxPolicy) -> JudgeSessionBuilder) -> JudgeSessionBuilder {
+    let request = &prepared.request;
+    let policy = match request.policy.allow_threads {
+        true => SandboxPolicy::threads_allowed(),
+        false => SandboxPolicy::default()
+    };
+    let mut builder = builder(prepared.exec.clone(), policy)
+        .input(InputSource::File(prepared.input.clone()))
+        .answer(prepared.answer.clone());
+    let limits = &request.limits;
+    if limits.time_limit_ms > 0 {
+        builder = builder.time_limit(Duration::from_millis(limits.time_limit_ms));
+    }
+    if limits.wall_time_limit_ms > 0 {
+        builder = builder.wall_time_limit(Duration::from_millis(limits.wall_time_limit_ms));
+    }
+    if limits.memory_limit_bytes > 0 {
+        builder = builder.memory_limit(limits.memory_limit_bytes);
+    }
+    if limits.output_limit_bytes > 0 {
+        builder = builder.output_limit(limits.output_limit_bytes);
+    }
+    if let Some(comparison) = prepared.comparison {
+        builder = builder.comparison(comparison);
+    }
+    if let Some(lang) = language::find(&request.lang) {
+        builder = builder.limit_multipliers(lang.limit_multipliers);
+    }
+    builder
+}
+
+/*
+ *  Judge a job and answer its call with the result
+ */
+fn judge(job: &Job, builder: &dyn Fn(PathBuf, SandboxPolicy) -> JudgeSessionBuilder) {
+    let _span = crate::log::span("grpc");
+    let connection = &job.connection;
+    let prepared = &job.prepared;
+    let request = &prepared.request;
+    let mut builder = request_builder(prepared, builder).cancel_handle(job.cancel.clone());
+    let response_headers = [(":status", String::from("200")), ("content-type", String::from("application/grpc"))];
+    if job.streaming {
+        builder = builder.observer(Arc::new(EventSender { connection: Arc::clone(connection), stream: job.stream }));
+        if !connection.send_headers(job.stream, &response_headers, false) {
+            return;
+        }
+    }
+    let failed = |status: Status| match job.streaming {
+        true => {
+            connection.send_trailers(job.stream, &status);
+        },
+        false => connection.fail(job.stream, &status)
+    };
+    let session = match builder.build() {
+        Ok(session) => session,
+        Err(e) => return failed(Status::new(Code::InvalidArgument, format!("invalid judging setup: {e}")))
+    };
+    let exec = prepared.exec.to_string_lossy();
+    let argv: Vec<&str> = [exec.as_ref()].into_iter().chain(request.args.iter().map(String::as_str)).collect();
+    let result = match session.run_judge(&argv) {
+        Ok(result) => result,
+        Err(_) if job.cancel.is_cancelled() => return failed(Status::new(Code::Cancelled, "cancelled")),
+        Err(e) => return failed(Status::new(Code::Internal, format!("cannot judge: {e}")))
+    };
+    let response = JudgeResponse::from_result(&result);
+    let message = match job.streaming {
+        true => JudgeEvent::Finished(response).encode(),
+        false => response.encode()
+    };
+    let sent = match job.streaming {
+        true => connection.send_message(job.stream, &message),
+        false => connection.send_headers(job.stream, &response_headers, false) && connection.send_message(job.stream, &message)
+    };
+    if sent {
+        connection.send_trailers(job.stream, &Status::new(Code::Ok, ""));
+    }
+}
+
+/// A connection to a server, making one call at a time
+pub struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    decoder: Decoder,
+    next_stream: u32,
+    window: i64,
+    // The window the server gives new streams
+    initial_window: i64,
+    // Of the call in progress
+    stream_window: i64
+}
+
+fn unavailable(e: io::Error) -> Status {
+    Status::new(Code::Unavailable, format!("connection failed: {e}"))
+}
+
+fn protocol(e: ConnectionError) -> Status {
+    Status::new(Code::Internal, format!("protocol error: {}", e.message))
+}
+
+/// What the server sent on the stream of a call
+enum Received {
+    Headers(Vec<(String, String)>, bool),
+    Data(Vec<u8>, bool),
+    Reset(u32)
+}
+
+impl Client {
+    /**
+     *  Connect to the server at `address`
+     */
+    pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let mut writer = stream.try_clone()?;
+        writer.write_all(h2::PREFACE)?;
+        h2::write_frame(&mut writer, h2::SETTINGS, 0, 0, &h2::settings_payload(&[(h2::SETTINGS_ENABLE_PUSH, 0)]))?;
+        Ok(Client {
+            reader: BufReader::new(stream),
+            writer,
+            decoder: Decoder::default(),
+            next_stream: 1,
+            window: h2::DEFAULT_WINDOW,
+            initial_window: h2::DEFAULT_WINDOW,
+            stream_window: 0
+        })
+    }
+
+    /**
+     *  Judge `request` and wait for its result
+     */
+    pub fn judge(&mut self, request: &JudgeRequest) -> Result<JudgeResponse, Status> {
+        let mut call = self.start(JUDGE_PATH, &request.encode())?;
+        let response = match call.next_message(self)? {
+            Some(message) => JudgeResponse::decode(&message)?,
+            None => return Err(Status::new(Code::Internal, "the call ended without a response"))
+        };
+        match call.next_message(self)? {
+            None => Ok(response),
+            Some(_) => Err(Status::new(Code::Internal, "more than one response"))
+        }
+    }
+
+    /**
+     *  Judge `request`, streaming what happens to it. The stream ends
+     *  after Finished, or with the status the call failed with.
+     */
+    pub fn judge_events(&mut self, request: &JudgeRequest) -> Result<Events<'_>, Status> {
+        let call = self.start(JUDGE_EVENTS_PATH, &request.encode())?;
+        Ok(Events { client: self, call })
+    }
+
+    /*
+     *  Open a stream for a call to `path` and send it `message`, waiting
+     *  for the windows to let it through. The server may answer before it
+     *  got all of it, refusing a request too large, which ends the sending.
+     */
+    fn start(&mut self, path: &str, message: &[u8]) -> Result<Call, Status> {
+        let stream = self.next_stream;
+        self.next_stream += 2;
+        let headers = [
+            (":method", "POST"),
+            (":scheme", "http"),
+            (":path", path),
+            (":authority", "secure-judger"),
+            ("content-type", "application/grpc"),
+            ("te", "trailers")
+        ];
+        self.writer.write_all(&h2::headers_bytes(stream, &hpack::encode(&headers), false)).map_err(unavailable)?;
+        self.stream_window = self.initial_window;
+        let framed = grpc_frame(message);
+        let mut data = &framed[..];
+        loop {
+            let room = self.window.min(self.stream_window).min(h2::MAX_FRAME as i64);
+            if room <= 0 && !data.is_empty() {
+                if let Some(received) = self.receive(stream)? {
+                    return Ok(Call { stream, body: Vec::new(), pending: Some(received), finished: false });
+                }
+                continue;
+            }
+            let (chunk, rest) = data.split_at(data.len().min(room.max(0) as usize));
+            let flags = match rest.is_empty() {
+                true => h2::END_STREAM,
+                false => 0
+            };
+            h2::write_frame(&mut self.writer, h2::DATA, flags, stream, chunk).map_err(unavailable)?;
+            self.window -= chunk.len() as i64;
+            self.stream_window -= chunk.len() as i64;
+            data = rest;
+            if data.is_empty() {
+                return Ok(Call { stream, body: Vec::new(), pending: None, finished: false });
+            }
+        }
+    }
+
+    /*
+     *  Read a frame and act on it, giving what it says about `stream`
+     */
+    fn receive(&mut self, stream: u32) -> Result<Option<Received>, Status> {
+        let frame = h2::read_frame(&mut self.reader).map_err(unavailable)?.map_err(protocol)?;
+        let mut header_block = None;
+        match frame.kind {
+            h2::SETTINGS if frame.flags & h2::ACK == 0 => {
+                for (id, value) in h2::parse_settings(&frame.payload).map_err(protocol)? {
+                    if id == h2::SETTINGS_INITIAL_WINDOW_SIZE {
+                        let delta = i64::from(value) - self.initial_window;
+                        self.initial_window = i64::from(value);
+                        self.stream_window += delta;
+                    }
+                }
+                h2::write_frame(&mut self.writer, h2::SETTINGS, h2::ACK, 0, &[]).map_err(unavailable)?;
+            },
+            h2::PING if frame.flags & h2::ACK == 0 => {
+                h2::write_frame(&mut self.writer, h2::PING, h2::ACK, 0, &frame.payload).map_err(unavailable)?;
+            },
+            h2::WINDOW_UPDATE => {
+                let increment = i64::from(h2::parse_u32(&frame.payload).map_err(protocol)?);
+                match frame.stream {
+                    0 => self.window += increment,
+                    id if id == stream => self.stream_window += increment,
+                    _ => {}
+                }
+            },
+            h2::GOAWAY => {
+                let message = String::from_utf8_lossy(frame.payload.get(8..).unwrap_or_default()).into_owned();
+                return Err(Status::new(Code::Unavailable, format!("the server closed the connection: {message}")));
+            },
+            h2::RST_STREAM if frame.stream == stream => {
+                return Ok(Some(Received::Reset(h2::parse_u32(&frame.payload).map_err(protocol)?)));
+            },
+            h2::HEADERS => {
+                let mut block = h2::frame_content(&frame).map_err(protocol)?.to_vec();
+                let mut flags = frame.flags;
+                while flags & h2::END_HEADERS == 0 {
+                    let next = h2::read_frame(&mut self.reader).map_err(unavailable)?.map_err(protocol)?;
+                    if next.kind != h2::CONTINUATION || next.stream != frame.stream {
+                        return Err(Status::new(Code::Internal, "header block interrupted"));
+                    }
+                    block.extend_from_slice(&next.payload);
+                    flags = next.flags;
+                }
+                // Decoded whatever the stream, to keep the table in step
+                let headers = self.decoder.decode(&block).map_err(|e| Status::new(Code::Internal, e.0))?;
+                header_block = Some((headers, frame.flags & h2::END_STREAM != 0));
+            },
+            h2::DATA => {
+                if !frame.payload.is_empty() {
+                    let increment = h2::u32_payload(frame.payload.len() as u32);
+                    h2::write_frame(&mut self.writer, h2::WINDOW_UPDATE, 0, 0, &increment).map_err(unavailable)?;
+                    if frame.stream == stream && frame.flags & h2::END_STREAM == 0 {
+                        h2::write_frame(&mut self.writer, h2::WINDOW_UPDATE, 0, stream, &increment).map_err(unavailable)?;
+                    }
+                }
+                if frame.stream == stream {
+                    let content = h2::frame_content(&frame).map_err(protocol)?.to_vec();
+                    return Ok(Some(Received::Data(content, frame.flags & h2::END_STREAM != 0)));
+                }
+            },
+            _ => {}
+        }
+        Ok(match header_block {
+            Some((headers, end_stream)) if frame.stream == stream => Some(Received::Headers(headers, end_stream)),
+            _ => None
+        })
+    }
+}
+
+/// The receiving side of a call in progress
+struct Call {
+    stream: u32,
+    // Data received and not yet taken as messages
+    body: Vec<u8>,
+    // Received while the request was still being sent
+    pending: Option<Received>,
+    finished: bool
+}
+
+impl Call {
+    /*
+     *  The next message of the call, None once it ended well
+     */
+    fn next_message(&mut self, client: &mut Client) -> Result<Option<Vec<u8>>, Status> {
+        loop {
+            if let [0, a, b, c, d, ..] = self.body[..] {
+                let length = u32::from_be_bytes([a, b, c, d]) as usize;
+                if self.body.len() >= 5 + length {
+                    let message = self.body[5..5 + length].to_vec();
+                    self.body.drain(..5 + length);
+                    return Ok(Some(message));
+                }
+            } else if self.body.first().is_some_and(|&flag| flag != 0) {
+                return Err(Status::new(Code::Internal, "compressed message, which wasn't asked for"));
+            }
+            if self.finished {
+                return Err(Status::new(Code::Internal, "the call ended within a message"));
+            }
+            let received = match self.pending.take() {
+                Some(received) => Some(received),
+                None => client.receive(self.stream)?
+            };
+            match received {
+                None => {},
+                Some(Received::Data(data, end_stream)) => {
+                    self.body.extend(data);
+                    if end_stream {
+                        return Err(Status::new(Code::Internal, "the call ended without trailers"));
+                    }
+                },
+                Some(Received::Headers(headers, end_stream)) => {
+                    let header = |name: &str| headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+                    if let Some(status) = header(":status").filter(|&status| status != "200") {
+                        return Err(Status::new(Code::Unknown, format!("HTTP status {status}")));
+                    }
+                    if !end_stream {
+                        continue;
+                    }
+                    self.finished = true;
+                    let code = Code::from_u32(header("grpc-status").and_then(|code| code.parse().ok()).unwrap_or(2));
+                    if code != Code::Ok {
+                        return Err(Status::new(code, percent_decode(header("grpc-message").unwrap_or_default())));
+                    }
+                    if !self.body.is_empty() {
+                        return Err(Status::new(Code::Internal, "the call ended within a message"));
+                    }
+                    return Ok(None);
+                },
+                Some(Received::Reset(code)) => {
+                    self.finished = true;
+                    return Err(Status::new(Code::Internal, format!("the server reset the call with error {code}")));
+                }
+            }
+        }
+    }
+}
+
+/// The events of a JudgeEvents call. Dropping it before the end cancels
+/// the call, and the judging with it.
+pub struct Events<'a> {
+    client: &'a mut Client,
+    call: Call
+}
+
+impl Events<'_> {
+    /// Cancel the call, which kills the program if it runs
+    pub fn cancel(self) {}
+}
+
+impl Iterator for Events<'_> {
+    type Item = Result<JudgeEvent, Status>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.call.finished {
+            return None;
+        }
+        match self.call.next_message(self.client) {
+            Ok(Some(message)) => Some(JudgeEvent::decode(&message).map_err(Status::from)),
+            Ok(None) => None,
+            Err(status) => {
+                self.call.finished = true;
+                Some(Err(status))
+            }
+        }
+    }
+}
+
+impl Drop for Events<'_> {
+    fn drop(&mut self) {
+        if !self.call.finished {
+            let _ = h2::write_frame(&mut self.client.writer, h2::RST_STREAM, 0, self.call.stream, &h2::u32_payload(h2::CANCEL));
+        }
+    }
+}
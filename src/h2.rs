@@ -0,0 +1,178 @@
+// HTTP/2 frames (RFC 9113), over cleartext TCP with prior knowledge as
+// gRPC uses it, for the server and client of the grpc module. Frames are
+// read and written whole; what they mean to a connection is left to
+// their users.
+
+use std::io::{self, Read, Write};
+
+/// What every connection of a client starts with
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+/// The largest frame either side sends, SETTINGS_MAX_FRAME_SIZE's default,
+/// which both may rely on without asking
+pub const MAX_FRAME: usize = 16384;
+/// The flow control window a stream and a connection start with
+pub const DEFAULT_WINDOW: i64 = 65535;
+/// The largest window flow control allows
+pub const MAX_WINDOW: i64 = (1 << 31) - 1;
+
+pub const DATA: u8 = 0;
+pub const HEADERS: u8 = 1;
+pub const RST_STREAM: u8 = 3;
+pub const SETTINGS: u8 = 4;
+pub const PING: u8 = 6;
+pub const GOAWAY: u8 = 7;
+pub const WINDOW_UPDATE: u8 = 8;
+pub const CONTINUATION: u8 = 9;
+
+pub const END_STREAM: u8 = 0x1;
+pub const ACK: u8 = 0x1;
+pub const END_HEADERS: u8 = 0x4;
+pub const PADDED: u8 = 0x8;
+pub const PRIORITY: u8 = 0x20;
+
+pub const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+pub const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+pub const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+pub const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+
+// Error codes of RST_STREAM and GOAWAY
+pub const NO_ERROR: u32 = 0x0;
+pub const PROTOCOL_ERROR: u32 = 0x1;
+pub const FLOW_CONTROL_ERROR: u32 = 0x3;
+pub const FRAME_SIZE_ERROR: u32 = 0x6;
+pub const REFUSED_STREAM: u32 = 0x7;
+pub const CANCEL: u32 = 0x8;
+pub const COMPRESSION_ERROR: u32 = 0x9;
+pub const ENHANCE_YOUR_CALM: u32 = 0xb;
+
+/// A frame as read off the connection
+pub struct Frame {
+    pub kind: u8,
+    pub flags: u8,
+    pub stream: u32,
+    pub payload: Vec<u8>
+}
+
+/// A violation of the protocol by the peer, which ends the connection
+/// with GOAWAY and this error code
+#[derive(Debug)]
+pub struct ConnectionError {
+    pub code: u32,
+    pub message: String
+}
+
+impl ConnectionError {
+    pub fn new(code: u32, message: impl Into<String>) -> Self {
+        ConnectionError { code, message: message.into() }
+    }
+}
+
+/*
+ *  Read a frame, refusing one longer than MAX_FRAME as SETTINGS never
+ *  allow more
+ */
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Result<Frame, ConnectionError>> {
+    let mut head = [0u8; 9];
+    reader.read_exact(&mut head)?;
+    let length = usize::from(head[0]) << 16 | usize::from(head[1]) << 8 | usize::from(head[2]);
+    if length > MAX_FRAME {
+        return Ok(Err(ConnectionError::new(FRAME_SIZE_ERROR, format!("frame of {length} bytes"))));
+    }
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload)?;
+    let stream = u32::from_be_bytes([head[5], head[6], head[7], head[8]]) & 0x7fff_ffff;
+    Ok(Ok(Frame { kind: head[3], flags: head[4], stream, payload }))
+}
+
+/*
+ *  The frame header and payload in one buffer, for a single write
+ */
+pub fn frame_bytes(kind: u8, flags: u8, stream: u32, payload: &[u8]) -> Vec<u8> {
+    debug_assert!(payload.len() <= MAX_FRAME);
+    let length = payload.len() as u32;
+    let mut bytes = Vec::with_capacity(9 + payload.len());
+    bytes.extend_from_slice(&length.to_be_bytes()[1..]);
+    bytes.push(kind);
+    bytes.push(flags);
+    bytes.extend_from_slice(&stream.to_be_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+pub fn write_frame(writer: &mut impl Write, kind: u8, flags: u8, stream: u32, payload: &[u8]) -> io::Result<()> {
+    writer.write_all(&frame_bytes(kind, flags, stream, payload))
+}
+
+/*
+ *  A header block as HEADERS and as many CONTINUATION frames as it takes,
+ *  for a single write
+ */
+pub fn headers_bytes(stream: u32, block: &[u8], end_stream: bool) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut chunks = block.chunks(MAX_FRAME).peekable();
+    let mut kind = HEADERS;
+    let mut flags = match end_stream {
+        true => END_STREAM,
+        false => 0
+    };
+    loop {
+        let chunk = chunks.next().unwrap_or_default();
+        let last = chunks.peek().is_none();
+        if last {
+            flags |= END_HEADERS;
+        }
+        bytes.extend(frame_bytes(kind, flags, stream, chunk));
+        if last {
+            return bytes;
+        }
+        kind = CONTINUATION;
+        flags = 0;
+    }
+}
+
+/*
+ *  The settings of a SETTINGS frame, as identifiers and values
+ */
+pub fn parse_settings(payload: &[u8]) -> Result<Vec<(u16, u32)>, ConnectionError> {
+    if !payload.len().is_multiple_of(6) {
+        return Err(ConnectionError::new(FRAME_SIZE_ERROR, "SETTINGS not a multiple of 6 bytes"));
+    }
+    Ok(payload
+        .chunks(6)
+        .map(|setting| (u16::from_be_bytes([setting[0], setting[1]]), u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]])))
+        .collect())
+}
+
+pub fn settings_payload(settings: &[(u16, u32)]) -> Vec<u8> {
+    settings.iter().flat_map(|(id, value)| id.to_be_bytes().into_iter().chain(value.to_be_bytes())).collect()
+}
+
+pub fn u32_payload(value: u32) -> [u8; 4] {
+    value.to_be_bytes()
+}
+
+pub fn parse_u32(payload: &[u8]) -> Result<u32, ConnectionError> {
+    let bytes: [u8; 4] = payload.try_into().map_err(|_| ConnectionError::new(FRAME_SIZE_ERROR, "frame must be 4 bytes"))?;
+    Ok(u32::from_be_bytes(bytes) & 0x7fff_ffff)
+}
+
+/*
+ *  The payload of a HEADERS or DATA frame without its padding, and for
+ *  HEADERS without the priority fields
+ */
+pub fn frame_content(frame: &Frame) -> Result<&[u8], ConnectionError> {
+    let mut content = &frame.payload[..];
+    let mut padding = 0;
+    if frame.flags & PADDED != 0 {
+        let (&length, rest) = content.split_first().ok_or_else(|| ConnectionError::new(PROTOCOL_ERROR, "padded frame without a pad length"))?;
+        padding = usize::from(length);
+        content = rest;
+    }
+    if frame.kind == HEADERS && frame.flags & PRIORITY != 0 {
+        content = content.get(5..).ok_or_else(|| ConnectionError::new(FRAME_SIZE_ERROR, "HEADERS too short for its priority"))?;
+    }
+    if padding > content.len() {
+        return Err(ConnectionError::new(PROTOCOL_ERROR, "padding longer than the frame"));
+    }
+    Ok(&content[..content.len() - padding])
+}
@@ -0,0 +1,321 @@
+// HPACK (RFC 7541), the header compression of HTTP/2. Header blocks are
+// decoded in full, Huffman coding and the dynamic table included, since
+// clients use both. The encoder only writes literals that are never
+// indexed and not Huffman coded, which every decoder takes and which
+// leaves no table to keep in step with the peer.
+
+use std::collections::VecDeque;
+
+// The size of the dynamic table, the default of SETTINGS_HEADER_TABLE_SIZE,
+// which is never raised
+pub const TABLE_SIZE: usize = 4096;
+// Longest header name and value together before a block is refused
+pub const MAX_HEADER_LIST: usize = 64 << 10;
+
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", "")
+];
+
+// The Huffman code of Appendix B, which is canonical: the symbols of each
+// code length, in order, take the codes following those of the shorter
+// lengths. 256 is EOS, which never appears in a string.
+const HUFFMAN: [(u32, &[u16]); 21] = [
+    (5, &[48, 49, 50, 97, 99, 101, 105, 111, 115, 116]),
+    (6, &[32, 37, 45, 46, 47, 51, 52, 53, 54, 55, 56, 57, 61, 65, 95, 98, 100, 102, 103, 104, 108, 109, 110, 112, 114, 117]),
+    (7, &[
+        58, 66, 67, 68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 89, 106, 107, 113, 118,
+        119, 120, 121, 122
+    ]),
+    (8, &[38, 42, 44, 59, 88, 90]),
+    (10, &[33, 34, 40, 41, 63]),
+    (11, &[39, 43, 124]),
+    (12, &[35, 62]),
+    (13, &[0, 36, 64, 91, 93, 126]),
+    (14, &[94, 125]),
+    (15, &[60, 96, 123]),
+    (19, &[92, 195, 208]),
+    (20, &[128, 130, 131, 162, 184, 194, 224, 226]),
+    (21, &[153, 161, 167, 172, 176, 177, 179, 209, 216, 217, 227, 229, 230]),
+    (22, &[
+        129, 132, 133, 134, 136, 146, 154, 156, 160, 163, 164, 169, 170, 173, 178, 181, 185, 186, 187, 189, 190, 196, 198,
+        228, 232, 233
+    ]),
+    (23, &[
+        1, 135, 137, 138, 139, 140, 141, 143, 147, 149, 150, 151, 152, 155, 157, 158, 165, 166, 168, 174, 175, 180, 182, 183,
+        188, 191, 197, 231, 239
+    ]),
+    (24, &[9, 142, 144, 145, 148, 159, 171, 206, 215, 225, 236, 237]),
+    (25, &[199, 207, 234, 235]),
+    (26, &[192, 193, 200, 201, 202, 205, 210, 213, 218, 219, 238, 240, 242, 243, 255]),
+    (27, &[203, 204, 211, 212, 214, 221, 222, 223, 241, 244, 245, 246, 247, 248, 250, 251, 252, 253, 254]),
+    (28, &[2, 3, 4, 5, 6, 7, 8, 11, 12, 14, 15, 16, 17, 18, 19, 20, 21, 23, 24, 25, 26, 27, 28, 29, 30, 31, 127, 220, 249]),
+    (30, &[10, 13, 22, 256])
+];
+
+/// Why a header block can't be decoded, a connection error of HTTP/2
+#[derive(Debug)]
+pub struct HpackError(pub String);
+
+fn error(message: &str) -> HpackError {
+    HpackError(message.to_string())
+}
+
+/*
+ *  Decode a Huffman coded string. The code is walked length by length:
+ *  after reading n bits, the code is one of length n if it falls among
+ *  the codes of that length.
+ */
+fn huffman_decode(data: &[u8]) -> Result<Vec<u8>, HpackError> {
+    let mut out = Vec::with_capacity(data.len() * 8 / 5);
+    let mut code: u32 = 0;
+    let mut length: u32 = 0;
+    // The first code of the length being looked at, and which entry of
+    // HUFFMAN that length is
+    let mut first: u32 = 0;
+    let mut entry = 0;
+    let mut padding_ones = true;
+    for bit in data.iter().flat_map(|&byte| (0..8).rev().map(move |shift| u32::from(byte >> shift & 1))) {
+        code = code << 1 | bit;
+        length += 1;
+        padding_ones &= bit == 1;
+        while entry < HUFFMAN.len() && HUFFMAN[entry].0 < length {
+            if let Some(&(next_length, _)) = HUFFMAN.get(entry + 1) {
+                first = (first + HUFFMAN[entry].1.len() as u32) << (next_length - HUFFMAN[entry].0);
+            }
+            entry += 1;
+        }
+        let Some(&(entry_length, symbols)) = HUFFMAN.get(entry) else {
+            return Err(error("invalid Huffman code"));
+        };
+        if entry_length != length || code < first || code - first >= symbols.len() as u32 {
+            continue;
+        }
+        match symbols[(code - first) as usize] {
+            256 => return Err(error("EOS in a Huffman coded string")),
+            symbol => out.push(symbol as u8)
+        }
+        code = 0;
+        length = 0;
+        first = 0;
+        entry = 0;
+        padding_ones = true;
+    }
+    // What is left has to be a prefix of EOS, which is all ones, shorter
+    // than a byte
+    if length >= 8 || !padding_ones {
+        return Err(error("invalid Huffman padding"));
+    }
+    Ok(out)
+}
+
+/// Decodes the header blocks of one connection, keeping its dynamic
+/// table from block to block
+pub struct Decoder {
+    // Newest first, as the indexes count them
+    table: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder { table: VecDeque::new(), size: 0, max_size: TABLE_SIZE }
+    }
+}
+
+/*
+ *  An integer with an `prefix`-bit prefix in the first byte
+ */
+fn integer(data: &mut &[u8], prefix: u32) -> Result<usize, HpackError> {
+    let (&first, rest) = data.split_first().ok_or_else(|| error("truncated integer"))?;
+    *data = rest;
+    let mask = (1usize << prefix) - 1;
+    let mut value = usize::from(first) & mask;
+    if value < mask {
+        return Ok(value);
+    }
+    for shift in (0..28).step_by(7) {
+        let (&byte, rest) = data.split_first().ok_or_else(|| error("truncated integer"))?;
+        *data = rest;
+        value += usize::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(error("integer too large"))
+}
+
+fn string(data: &mut &[u8]) -> Result<String, HpackError> {
+    let huffman = data.first().is_some_and(|byte| byte & 0x80 != 0);
+    let length = integer(data, 7)?;
+    if data.len() < length {
+        return Err(error("truncated string"));
+    }
+    let (raw, rest) = data.split_at(length);
+    *data = rest;
+    let bytes = match huffman {
+        true => huffman_decode(raw)?,
+        false => raw.to_vec()
+    };
+    String::from_utf8(bytes).map_err(|_| error("header is not UTF-8"))
+}
+
+impl Decoder {
+    fn entry(&self, index: usize) -> Result<(String, String), HpackError> {
+        match index {
+            0 => Err(error("header index 0")),
+            1..=61 => {
+                let (name, value) = STATIC_TABLE[index - 1];
+                Ok((name.to_string(), value.to_string()))
+            },
+            _ => self.table.get(index - 62).cloned().ok_or_else(|| error("header index past the table"))
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.max_size {
+            let Some((name, value)) = self.table.pop_back() else { break };
+            self.size -= name.len() + value.len() + 32;
+        }
+    }
+
+    fn insert(&mut self, name: String, value: String) {
+        self.size += name.len() + value.len() + 32;
+        self.table.push_front((name, value));
+        self.evict();
+    }
+
+    /*
+     *  The headers of a complete block, in order
+     */
+    pub fn decode(&mut self, mut data: &[u8]) -> Result<Vec<(String, String)>, HpackError> {
+        let mut headers = Vec::new();
+        let mut list_size = 0;
+        while let Some(&first) = data.first() {
+            let header = match first {
+                0x80..=0xff => self.entry(integer(&mut data, 7)?)?,
+                0x40..=0x7f => {
+                    let name = match integer(&mut data, 6)? {
+                        0 => string(&mut data)?,
+                        index => self.entry(index)?.0
+                    };
+                    let value = string(&mut data)?;
+                    self.insert(name.clone(), value.clone());
+                    (name, value)
+                },
+                0x20..=0x3f => {
+                    let size = integer(&mut data, 5)?;
+                    if size > TABLE_SIZE || !headers.is_empty() {
+                        return Err(error("invalid dynamic table size update"));
+                    }
+                    self.max_size = size;
+                    self.evict();
+                    continue;
+                },
+                _ => {
+                    let name = match integer(&mut data, 4)? {
+                        0 => string(&mut data)?,
+                        index => self.entry(index)?.0
+                    };
+                    (name, string(&mut data)?)
+                }
+            };
+            list_size += header.0.len() + header.1.len() + 32;
+            if list_size > MAX_HEADER_LIST {
+                return Err(error("header list too large"));
+            }
+            headers.push(header);
+        }
+        Ok(headers)
+    }
+}
+
+fn encode_integer(out: &mut Vec<u8>, flags: u8, prefix: u32, mut value: usize) {
+    let mask = (1usize << prefix) - 1;
+    if value < mask {
+        out.push(flags | value as u8);
+        return;
+    }
+    out.push(flags | mask as u8);
+    value -= mask;
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/*
+ *  A header block of `headers` as literals never indexed
+ */
+pub fn encode(headers: &[(&str, &str)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (name, value) in headers {
+        out.push(0x10);
+        encode_integer(&mut out, 0, 7, name.len());
+        out.extend_from_slice(name.as_bytes());
+        encode_integer(&mut out, 0, 7, value.len());
+        out.extend_from_slice(value.as_bytes());
+    }
+    out
+}
@@ -0,0 +1,281 @@
+use std::fmt::Display;
+
+/// A JSON value, objects keeping their members in order
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// null
+    Null,
+    /// true or false
+    Boolean(bool),
+    /// A number, always kept as a float
+    Number(f64),
+    /// A string, with its escapes resolved
+    String(String),
+    /// An array
+    Array(Vec<Value>),
+    /// An object, members in the order of the text
+    Object(Vec<(String, Value)>)
+}
+
+impl Value {
+    /// What kind of value it is, for error messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Boolean(_) => "a boolean",
+            Self::Number(_) => "a number",
+            Self::String(_) => "a string",
+            Self::Array(_) => "an array",
+            Self::Object(_) => "an object"
+        }
+    }
+
+    /**
+     *  The value as JSON text on one line. Numbers that are whole are
+     *  written without a fraction.
+     */
+    pub fn to_json(&self) -> String {
+        match self {
+            Self::Null => String::from("null"),
+            Self::Boolean(b) => b.to_string(),
+            Self::Number(x) if x.fract() == 0.0 && x.abs() < 1e15 => format!("{}", *x as i64),
+            Self::Number(x) if x.is_finite() => x.to_string(),
+            Self::Number(_) => String::from("null"),
+            Self::String(s) => crate::utils::json_string(s),
+            Self::Array(items) => crate::utils::json_list(items.iter().map(Value::to_json)),
+            Self::Object(members) => {
+                let members: Vec<String> = members.iter()
+                    .map(|(key, value)| format!("{}:{}", crate::utils::json_string(key), value.to_json()))
+                    .collect();
+                format!("{{{}}}", members.join(","))
+            }
+        }
+    }
+}
+
+/// Why a text isn't JSON, and where
+#[derive(Debug)]
+pub struct ParseError {
+    /// Counted in characters from the start of the text
+    pub offset: usize,
+    /// What is wrong there
+    pub message: String
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("at character {}: {}", self.offset, self.message))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Deeper than this is refused rather than recursed into
+const MAX_DEPTH: usize = 64;
+
+/**
+ *  Parse a JSON text holding a single value, surrounded by whitespace
+ *  at most
+ */
+pub fn parse(text: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0 };
+    parser.skip_whitespace();
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    match parser.peek() {
+        None => Ok(value),
+        Some(ch) => Err(parser.error(format!("unexpected {ch:?} after the value")))
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += 1;
+        Some(ch)
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError { offset: self.pos, message }
+    }
+
+    fn expect(&mut self, wanted: char, message: impl FnOnce() -> String) -> Result<(), ParseError> {
+        match self.peek() == Some(wanted) {
+            true => {
+                self.bump();
+                Ok(())
+            },
+            false => Err(self.error(message()))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.bump();
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, ParseError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error(format!("nested deeper than {MAX_DEPTH}")));
+        }
+        match self.peek() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => self.string().map(Value::String),
+            Some('-' | '0'..='9') => self.number(),
+            Some(ch) if ch.is_ascii_alphabetic() => self.literal(),
+            Some(ch) => Err(self.error(format!("unexpected {ch:?}, expected a value"))),
+            None => Err(self.error(String::from("expected a value")))
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Value, ParseError> {
+        self.bump();
+        let mut members: Vec<(String, Value)> = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error(String::from("expected a key in quotes")));
+            }
+            let key = self.string()?;
+            if members.iter().any(|(earlier, _)| *earlier == key) {
+                return Err(self.error(format!("key {key} is given twice")));
+            }
+            self.skip_whitespace();
+            self.expect(':', || format!("expected : after key {key}"))?;
+            self.skip_whitespace();
+            let value = self.value(depth + 1)?;
+            members.push((key, value));
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => {},
+                Some('}') => return Ok(Value::Object(members)),
+                _ => return Err(self.error(String::from("expected , or } in the object")))
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Value, ParseError> {
+        self.bump();
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok(Value::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => {},
+                Some(']') => return Ok(Value::Array(items)),
+                _ => return Err(self.error(String::from("expected , or ] in the array")))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.bump();
+        let mut string = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(ParseError { offset: start, message: String::from("unterminated string") }),
+                Some('"') => return Ok(string),
+                Some('\\') => string.push(self.escape()?),
+                Some(ch) if (ch as u32) < 0x20 => return Err(self.error(String::from("control character in a string"))),
+                Some(ch) => string.push(ch)
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let ch = match self.bump() {
+            Some('b') => '\u{8}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{c}',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('u') => {
+                let unit = self.hex4()?;
+                // Characters outside the BMP come as a surrogate pair
+                let code = match unit {
+                    0xd800..=0xdbff if self.chars[self.pos..].starts_with(&['\\', 'u']) => {
+                        self.pos += 2;
+                        let low = self.hex4()?;
+                        match low {
+                            0xdc00..=0xdfff => 0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00),
+                            _ => return Err(self.error(String::from("invalid surrogate pair")))
+                        }
+                    },
+                    code => code
+                };
+                match char::from_u32(code) {
+                    Some(ch) => ch,
+                    None => return Err(self.error(format!("invalid escape \\u{unit:04x}")))
+                }
+            },
+            Some(other) => return Err(self.error(format!("invalid escape \\{other}"))),
+            None => return Err(self.error(String::from("unterminated string")))
+        };
+        Ok(ch)
+    }
+
+    fn hex4(&mut self) -> Result<u32, ParseError> {
+        let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+        match u32::from_str_radix(&hex, 16) {
+            Ok(unit) if hex.len() == 4 => Ok(unit),
+            _ => Err(self.error(format!("invalid escape \\u{hex}")))
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit() || matches!(ch, '-' | '+' | '.' | 'e' | 'E')) {
+            self.bump();
+        }
+        let token: String = self.chars[start..self.pos].iter().collect();
+        // Rust takes some numbers JSON doesn't, such as 01 or 1.
+        let digits = token.strip_prefix('-').unwrap_or(&token);
+        let leading_zero = digits.starts_with('0') && digits[1..].starts_with(|ch: char| ch.is_ascii_digit());
+        let bare_point = digits.ends_with('.') || digits.contains(".e") || digits.contains(".E");
+        let well_formed = digits.starts_with(|ch: char| ch.is_ascii_digit()) && !leading_zero && !bare_point;
+        match token.parse::<f64>() {
+            Ok(x) if well_formed && x.is_finite() => Ok(Value::Number(x)),
+            _ => Err(ParseError { offset: start, message: format!("invalid number {token}") })
+        }
+    }
+
+    fn literal(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|ch| ch.is_ascii_alphabetic()) {
+            self.bump();
+        }
+        let token: String = self.chars[start..self.pos].iter().collect();
+        match token.as_str() {
+            "null" => Ok(Value::Null),
+            "true" => Ok(Value::Boolean(true)),
+            "false" => Ok(Value::Boolean(false)),
+            _ => Err(ParseError { offset: start, message: format!("unexpected {token}, strings have to be quoted") })
+        }
+    }
+}
@@ -1,16 +1,48 @@
+use std::collections::HashMap;
+use std::env;
 use std::error::Error;
-use std::fmt::Display;
-use std::fs::{File, self};
-use std::io::{self, BufReader, Read, Seek, SeekFrom};
-use std::path::PathBuf;
-use std::ffi::OsStr;
-use std::time::{Instant, Duration};
-use core::mem::size_of;
+use std::fmt::{self, Display};
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::{self, Cursor, Read, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::os::unix::fs::PermissionsExt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Instant, Duration, SystemTime};
 
-use crate::secrun;
+use crate::calibration::{self, Calibration};
+use crate::cache::{self, AnswerCache, ResultCache};
+use crate::cgroup::{CgroupLimits, RunCgroup};
+use crate::compare::Comparison;
+use crate::utils::elf::{self, ElfInfo, ElfKind, ExecArch};
+use crate::language::{self, Language, LimitMultipliers};
+use crate::log;
+use crate::metrics::METRICS;
+use crate::output::{JsonEnvironmentV1, JsonResultV1, ResultDiff};
+use crate::problem::TestCase;
+use crate::plan::{PlannedAnswer, PlannedInput, SessionPlan};
+#[cfg(feature = "async")]
+use crate::reactor;
+use crate::scratch::{self, move_file, PlannedScratch, ScratchDir};
+use crate::sha256;
+use crate::utils::{self, host, PathReport, StrictPaths};
+use crate::secrun::{
+    self, InputSource, Interpreter, JoinNamespace, LinuxSandbox, NamedFiles, ResourceUsage, RunSpec, Sandbox,
+    SandboxChildHandle, SandboxIo, SandboxPolicy, SandboxStrength, StopAction, WaitOutcome
+};
 
+/// Why a program crashed, from the signal that killed it
+#[derive(Clone)]
 pub enum RuntimeErrorKind {
+    /// SIGFPE, such as an integer division by zero
     FloatingPointError,
+    /// SIGSEGV or SIGBUS, an access to memory the program doesn't have
     SegmentationFault
 }
 
@@ -25,26 +57,83 @@ impl Display for RuntimeErrorKind {
     }
 }
 
+/// Verdict of a run, or of a test or submission made of several
+#[derive(Clone)]
 pub enum JudgeStatus {
+    /// The output matched the answer
     Accepted,
+    /// The output didn't match the answer
     WrongAnswer,
+    /// Ran out of CPU or wall time
     TimeLimitExceeded,
+    /// Used more memory than allowed
     MemoryLimitExceeded,
+    /// Wrote more than the output limit
+    OutputLimitExceeded,
+    /// Killed by a signal the judger didn't send
     RuntimeError(RuntimeErrorKind),
+    /// Stopped itself, e.g. by raise(SIGSTOP), see StopAction
+    IdlenessLimitExceeded,
+    /// Made a syscall the sandbox policy denies
+    SecurityViolation,
+    /// The output only matched the answer when ignoring whitespace
     PresentationError,
-    ReturnNonZero(i32)
+    /// The program never opened its named output file
+    OutputMissing,
+    /// Exited with this status instead of 0
+    ReturnNonZero(i32),
+    /// The judger failed to run or judge the program
+    SystemError(String),
+    /// Aborted through a JudgeHandle, or by the deadline of a whole batch
+    Cancelled,
+    /// Not run because the batch stopped before the test
+    Skipped
 }
 
 impl JudgeStatus {
-    fn abbr(&self) -> &'static str {
+    /// Short name of the verdict, such as "AC" or "TLE"
+    pub fn abbr(&self) -> &'static str {
         match &self {
             Self::Accepted              => "AC",
             Self::WrongAnswer           => "WA",
             Self::TimeLimitExceeded     => "TLE",
             Self::MemoryLimitExceeded   => "MLE",
+            Self::OutputLimitExceeded   => "OLE",
+            Self::IdlenessLimitExceeded => "ILE",
+            Self::SecurityViolation     => "SV",
             Self::PresentationError     => "PE",
+            Self::OutputMissing         => "OM",
             Self::RuntimeError(_)       => "RE",
-            Self::ReturnNonZero(_)      => "RNZ"
+            Self::ReturnNonZero(_)      => "RNZ",
+            Self::SystemError(_)        => "SE",
+            Self::Cancelled             => "CAN",
+            Self::Skipped               => "SKIP"
+        }
+    }
+
+    /// Whether the verdict comes from comparing the output with the answer
+    pub fn is_comparison(&self) -> bool {
+        matches!(self, Self::Accepted | Self::PresentationError | Self::WrongAnswer)
+    }
+
+    /// How bad the verdict is, for picking the one a submission gets from
+    /// those of its tests. Higher is worse.
+    pub fn severity(&self) -> u8 {
+        match &self {
+            Self::Accepted              => 0,
+            Self::Skipped               => 1,
+            Self::PresentationError     => 2,
+            Self::WrongAnswer           => 3,
+            Self::OutputMissing         => 4,
+            Self::ReturnNonZero(_)      => 5,
+            Self::RuntimeError(_)       => 6,
+            Self::OutputLimitExceeded   => 7,
+            Self::IdlenessLimitExceeded => 8,
+            Self::TimeLimitExceeded     => 9,
+            Self::MemoryLimitExceeded   => 10,
+            Self::SecurityViolation     => 11,
+            Self::SystemError(_)        => 12,
+            Self::Cancelled             => 13
         }
     }
 }
@@ -56,7 +145,13 @@ impl Display for JudgeStatus {
             Self::WrongAnswer           => "Wrong Answer",
             Self::TimeLimitExceeded     => "Time Limit Exceeded",
             Self::MemoryLimitExceeded   => "Memory Limit Exceeded",
+            Self::OutputLimitExceeded   => "Output Limit Exceeded",
+            Self::IdlenessLimitExceeded => "Idleness Limit Exceeded",
+            Self::SecurityViolation     => "Security Violation",
             Self::PresentationError     => "Presentation Error",
+            Self::OutputMissing         => "Output Missing",
+            Self::Cancelled             => "Cancelled",
+            Self::Skipped               => "Skipped",
             Self::RuntimeError(ek) => {
                 f.write_fmt(format_args!("[{}] Runtime Error ({ek})", self.abbr()))?;
                 return Ok(());
@@ -64,6 +159,10 @@ impl Display for JudgeStatus {
             Self::ReturnNonZero(ret_val) => {
                 f.write_fmt(format_args!("[{}] Return Value Not Zero ({ret_val})", self.abbr()))?;
                 return Ok(());
+            },
+            Self::SystemError(msg) => {
+                f.write_fmt(format_args!("[{}] System Error ({msg})", self.abbr()))?;
+                return Ok(());
             }
         };
         let abbr = self.abbr();
@@ -72,123 +171,2095 @@ impl Display for JudgeStatus {
     }
 }
 
+/// What a run of the program came to: its verdict and what it used
 pub struct JudgeResult {
+    /// Verdict of the run
     pub status: JudgeStatus,
+    /// Wall time the program ran for
     pub time_used: Duration,
+    /// Time from the start of judging until the program was started
+    pub judge_overhead: Duration,
+    /// CPU time of the program and its descendants
     pub cpu_time_ms: u64,
-    pub memory_used_bytes: u64
+    /// Peak resident memory
+    pub memory_used_bytes: u64,
+    /// Peak number of tasks, only known when judged in a cgroup
+    pub tasks_peak: Option<u64>,
+    /// Number of task creations refused by the cgroup pids limit
+    pub task_limit_hits: u64,
+    /// Time the program was held back by the cgroup CPU quota, if one was set
+    pub cpu_throttled: Option<Duration>,
+    /// Most threads the program had at once, only known when the policy
+    /// lets it start any: the cgroup's peak of tasks, or else the most
+    /// seen while sampling it
+    pub max_threads: Option<u32>,
+    /// Roughly how much the program read from disk, see
+    /// ResourceUsage::read_bytes
+    pub io_read_bytes: u64,
+    /// Roughly how much the program wrote to disk
+    pub io_written_bytes: u64,
+    /// Whether it went over the I/O limit, which is Output Limit Exceeded
+    pub io_limit_exceeded: bool,
+    /// Whether judging was stopped through JudgeHandle::stop as the
+    /// program ran, the verdict being how it ended after SIGTERM
+    pub interrupted: bool,
+    /// Where the output first differs from the answer, for a Wrong Answer
+    pub difference: Option<String>,
+    /// Beginning of what the program wrote to stderr
+    pub stderr: Vec<u8>,
+    /// How much the program wrote to stderr in all
+    pub stderr_bytes: u64,
+    /// Whether it wrote more to stderr than the stderr limit, the rest
+    /// being dropped. The verdict doesn't depend on it.
+    pub stderr_truncated: bool,
+    /// SHA-256 of the executable that was run, when it was copied
+    pub exec_sha256: Option<String>,
+    /// Whether the executable, and its interpreter if it is a script, was
+    /// read into the page cache before the run, see
+    /// JudgeSessionBuilder::prewarm
+    pub prewarmed: bool,
+    /// Scratch directory of the run, if it was kept
+    pub kept_dir: Option<PathBuf>,
+    /// Where the program's output was kept, if it was
+    pub output_path: Option<PathBuf>,
+    /// Times starting the run failed before it went through
+    pub retries: u32,
+    /// None if the run never started
+    pub limits: Option<AppliedLimits>,
+    /// How the program was sandboxed, None if the run never started
+    pub sandbox: Option<AppliedSandbox>,
+    /// Taken from the result cache instead of running the program
+    pub cached: bool,
+    /// Taken from the stamp an earlier run of the batch left, see
+    /// ProblemJudge::with_stamps
+    pub resumed: bool,
+    /// The host the run was judged on, None if it didn't run in this
+    /// process, as for results taken from the cache
+    pub environment: Option<&'static JudgeEnvironment>,
+    /// The CPU the run was pinned to, by a batch pinning its runs
+    pub cpu: Option<usize>,
+    /// Whether the run may have been out of time through no fault of the
+    /// program: it got TLE for its wall time with under 80% of its CPU
+    /// time used, and wasn't idle, while the judger woke up late by a
+    /// tenth or more of the wall time it didn't spend on a CPU, so the
+    /// host kept it waiting for one. Worth judging again on a quieter
+    /// host.
+    pub timing_suspect: bool,
+    /// Most the judger woke up late from a wait for the program, an
+    /// estimate of how long the host kept the runs waiting for a CPU
+    pub scheduling_latency: Duration
 }
 
 impl JudgeResult {
+    /// Whether the verdict is Accepted
     pub fn accepted(&self) -> bool {
-        match self.status {
-            JudgeStatus::Accepted => true,
-            _ => false
+        matches!(self.status, JudgeStatus::Accepted)
+    }
+
+    fn system_error(msg: String) -> Self {
+        JudgeResult::unfinished(JudgeStatus::SystemError(msg))
+    }
+
+    /**
+     *  Result of a run that ended without anything to report on the program
+     */
+    pub fn unfinished(status: JudgeStatus) -> Self {
+        JudgeResult {
+            status,
+            time_used: Duration::ZERO,
+            judge_overhead: Duration::ZERO,
+            cpu_time_ms: 0,
+            memory_used_bytes: 0,
+            tasks_peak: None,
+            task_limit_hits: 0,
+            cpu_throttled: None,
+            max_threads: None,
+            io_read_bytes: 0,
+            io_written_bytes: 0,
+            io_limit_exceeded: false,
+            interrupted: false,
+            stderr: Vec::new(),
+            stderr_bytes: 0,
+            stderr_truncated: false,
+            difference: None,
+            exec_sha256: None,
+            prewarmed: false,
+            kept_dir: None,
+            output_path: None,
+            retries: 0,
+            limits: None,
+            sandbox: None,
+            cached: false,
+            resumed: false,
+            environment: None,
+            cpu: None,
+            timing_suspect: false,
+            scheduling_latency: Duration::ZERO
         }
     }
+
+    /**
+     *  The result as a JSON object of the latest schema, stderr left out.
+     *  It goes in other documents, so it doesn't name the schema itself.
+     */
+    pub fn to_json(&self) -> String {
+        JsonResultV1::of(self).to_nested_json()
+    }
+
+    /**
+     *  How `other`, judged after this result, differs from it: the
+     *  verdicts, what they say and the time and memory taken, as their
+     *  JSON has them
+     */
+    pub fn diff(&self, other: &JudgeResult) -> ResultDiff {
+        ResultDiff::between(&JsonResultV1::of(self), &JsonResultV1::of(other))
+    }
 }
 
 impl Display for JudgeResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        const MEM_UNITS: [&'static str; 4] = ["B", "KiB", "MiB", "GiB"];
-        let mut mem_display: f64 = self.memory_used_bytes as f64;
-        let mut display_level: usize = 0;
-        while mem_display > 1024.0 && display_level < 4 {
-            mem_display /= 1024.0;
-            display_level += 1;
-        }
         f.write_fmt(format_args!("Status:  \t{}\n", self.status))?;
+        if let Some(difference) = &self.difference {
+            f.write_fmt(format_args!("Difference:\t{difference}\n"))?;
+        }
         f.write_fmt(format_args!("Used Real Time:\t{}ms\n", self.time_used.as_millis()))?;
         f.write_fmt(format_args!("Used CPU Time:\t{}ms\n", self.cpu_time_ms))?;
-        f.write_fmt(format_args!("Used Memory:\t{:.2}{}", mem_display, MEM_UNITS[display_level]))?;
+        f.write_fmt(format_args!("Judge Overhead:\t{:.2}ms\n", self.judge_overhead.as_secs_f64() * 1000.0))?;
+        f.write_fmt(format_args!("Used Memory:\t{}", utils::format_memory(self.memory_used_bytes)))?;
+        if let Some(tasks) = self.tasks_peak {
+            f.write_fmt(format_args!("\nPeak Tasks:\t{tasks}"))?;
+        }
+        if self.task_limit_hits > 0 {
+            f.write_fmt(format_args!(" ({} refused)", self.task_limit_hits))?;
+        }
+        // Judged in a cgroup, they are the peak tasks
+        if let (Some(threads), None) = (self.max_threads, self.tasks_peak) {
+            f.write_fmt(format_args!("\nPeak Threads:\t{threads}"))?;
+        }
+        if let Some(throttled) = self.cpu_throttled {
+            f.write_fmt(format_args!("\nCPU Throttled:\t{}ms", throttled.as_millis()))?;
+        }
+        if self.io_read_bytes > 0 || self.io_written_bytes > 0 || self.io_limit_exceeded {
+            f.write_fmt(format_args!(
+                "\nDisk I/O:\t{} read, {} written",
+                utils::format_memory(self.io_read_bytes),
+                utils::format_memory(self.io_written_bytes)
+            ))?;
+        }
+        if self.io_limit_exceeded {
+            f.write_str(" (over the I/O limit)")?;
+        }
+        if self.stderr_truncated {
+            f.write_fmt(format_args!("\nStderr:  \t{} written, the rest of it past the stderr limit dropped", utils::format_memory(self.stderr_bytes)))?;
+        }
+        if let Some(hash) = &self.exec_sha256 {
+            f.write_fmt(format_args!("\nExec SHA-256:\t{hash}"))?;
+        }
+        if self.prewarmed {
+            f.write_str("\nPrewarmed:\tthe executable was read into the page cache first")?;
+        }
+        if self.retries > 0 {
+            f.write_fmt(format_args!("\nRetries:\t{}", self.retries))?;
+        }
+        if let Some(cpu) = self.cpu {
+            f.write_fmt(format_args!("\nPinned to:\tCPU {cpu}"))?;
+        }
+        if self.cached {
+            f.write_str("\nCached:  \tyes, the program was not run")?;
+        }
+        if self.resumed {
+            f.write_str("\nResumed:\tyes, as judged by an earlier run of the batch")?;
+        }
+        if self.interrupted {
+            f.write_str("\nInterrupted:\tjudging was stopped, the program was sent SIGTERM")?;
+        }
+        if self.timing_suspect {
+            f.write_fmt(format_args!(
+                "\nTiming: \tsuspect, the host may have held the program back (judger woke up to {:.2}ms late)",
+                self.scheduling_latency.as_secs_f64() * 1000.0
+            ))?;
+        }
+        if let Some(sandbox) = self.sandbox.filter(|s| !s.seccomp) {
+            f.write_fmt(format_args!("\nSandbox:\tNO seccomp filter ({})", sandbox.strength))?;
+        }
+        if let Some(limits) = self.limits.filter(|l| l.is_scaled()) {
+            let Limits { cpu, memory_bytes, .. } = limits.effective;
+            f.write_fmt(format_args!(
+                "\nScaled Limits:\t{}ms, {} (from {}ms, {})",
+                cpu.as_millis(),
+                utils::format_memory(memory_bytes),
+                limits.base.cpu.as_millis(),
+                utils::format_memory(limits.base.memory_bytes)
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// The host results are judged on, so that results of different machines
+/// can be told apart where timings are compared. Collected once per
+/// process by current, with what the host doesn't tell left None.
+#[derive(Clone, Debug)]
+pub struct JudgeEnvironment {
+    /// Network name of the host
+    pub hostname: Option<String>,
+    /// Release of the running kernel
+    pub kernel: Option<String>,
+    /// Model of the CPU, as /proc/cpuinfo names it
+    pub cpu_model: Option<String>,
+    /// CPUs the judger may run on
+    pub cores: Option<usize>,
+    /// Version of the judger
+    pub version: &'static str,
+    /// Whether the seccomp filter can be installed here, that is whether
+    /// the runs asking for it get the full sandbox
+    pub seccomp: bool,
+    /// The calibration the time limits were scaled by, if one was
+    /// installed
+    pub calibration: Option<Calibration>
+}
+
+impl JudgeEnvironment {
+    /// The environment of this process, collected on first use
+    pub fn current() -> &'static JudgeEnvironment {
+        static CURRENT: OnceLock<JudgeEnvironment> = OnceLock::new();
+        CURRENT.get_or_init(JudgeEnvironment::collect)
+    }
+
+    fn collect() -> Self {
+        let uname = host::uname();
+        JudgeEnvironment {
+            hostname: uname.as_ref().map(|uname| uname.nodename.clone()),
+            kernel: uname.map(|uname| uname.release),
+            cpu_model: host::cpu_model(),
+            cores: thread::available_parallelism().ok().map(|cores| cores.get()),
+            version: env!("CARGO_PKG_VERSION"),
+            seccomp: secrun::seccomp_support().is_ok(),
+            calibration: calibration::installed().cloned()
+        }
+    }
+
+    /**
+     *  The environment as a JSON object
+     */
+    pub fn to_json(&self) -> String {
+        JsonEnvironmentV1::of(self).to_json()
+    }
+}
+
+impl Display for JudgeEnvironment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| String::from("unknown"));
+        f.write_fmt(format_args!("Host:    \t{}\n", unknown(&self.hostname)))?;
+        f.write_fmt(format_args!("Kernel:  \t{}\n", unknown(&self.kernel)))?;
+        f.write_fmt(format_args!("CPU:     \t{}", unknown(&self.cpu_model)))?;
+        match self.cores {
+            Some(1) => f.write_str(", 1 core")?,
+            Some(cores) => f.write_fmt(format_args!(", {cores} cores"))?,
+            None => {}
+        }
+        let seccomp = match self.seccomp {
+            true => "seccomp available",
+            false => "no seccomp"
+        };
+        f.write_fmt(format_args!("\nJudger:  \t{} {}, {seccomp}", env!("CARGO_PKG_NAME"), self.version))?;
+        if let Some(calibration) = &self.calibration {
+            f.write_fmt(format_args!(
+                "\nCalibrated:\tscore {}, time limits x{}",
+                calibration.score, calibration.multiplier
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// How the runs of run_repeated decide on a time verdict
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TlePolicy {
+    /// Any run out of time gets the test its verdict
+    #[default]
+    AnyRun,
+    /// Only the median times count, so a single slow run doesn't fail
+    /// a test the program usually passes
+    Median
+}
+
+/// Spread of a time over repeated runs
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimeStats {
+    /// Fastest run
+    pub min: Duration,
+    /// Median run
+    pub median: Duration,
+    /// Slowest run
+    pub max: Duration,
+    /// Population standard deviation
+    pub stddev: Duration
+}
+
+impl TimeStats {
+    fn of(mut samples: Vec<Duration>) -> Self {
+        if samples.is_empty() {
+            return TimeStats::default();
+        }
+        samples.sort();
+        let n = samples.len();
+        let median = match n % 2 {
+            0 => (samples[n / 2 - 1] + samples[n / 2]) / 2,
+            _ => samples[n / 2]
+        };
+        let mean = samples.iter().map(|t| t.as_secs_f64()).sum::<f64>() / n as f64;
+        let variance = samples.iter().map(|t| (t.as_secs_f64() - mean).powi(2)).sum::<f64>() / n as f64;
+        TimeStats {
+            min: samples[0],
+            median,
+            max: samples[n - 1],
+            stddev: Duration::from_secs_f64(variance.sqrt())
+        }
+    }
+}
+
+impl Display for TimeStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "min {}ms, median {}ms, max {}ms, stddev {:.2}ms",
+            self.min.as_millis(),
+            self.median.as_millis(),
+            self.max.as_millis(),
+            self.stddev.as_secs_f64() * 1000.0
+        ))
+    }
+}
+
+/// Result of judging the same test several times
+pub struct RepeatedResult {
+    /// Every run, in order
+    pub runs: Vec<JudgeResult>,
+    /// Verdict of the test over all runs, decided by the TlePolicy
+    pub status: JudgeStatus,
+    /// Wall times of the runs
+    pub wall_time: TimeStats,
+    /// CPU times of the runs
+    pub cpu_time: TimeStats,
+    /// Peak memory of the run that used the most
+    pub max_memory_bytes: u64
+}
+
+impl RepeatedResult {
+    fn new(runs: Vec<JudgeResult>, tle_policy: TlePolicy, limits: &RunLimits) -> Self {
+        let wall_time = TimeStats::of(runs.iter().map(|r| r.time_used).collect());
+        let cpu_time = TimeStats::of(runs.iter().map(|r| Duration::from_millis(r.cpu_time_ms)).collect());
+        let worst = |timed: bool| runs.iter()
+            .map(|r| &r.status)
+            .filter(|s| timed || !matches!(s, JudgeStatus::TimeLimitExceeded | JudgeStatus::IdlenessLimitExceeded))
+            .max_by_key(|s| s.severity())
+            .cloned();
+        let status = match tle_policy {
+            TlePolicy::AnyRun => worst(true),
+            TlePolicy::Median if cpu_time.median > limits.cpu => Some(JudgeStatus::TimeLimitExceeded),
+            // Runs stopped for their wall time are billed exactly the limit
+            TlePolicy::Median if wall_time.median >= limits.wall => match cpu_time.median * 2 < wall_time.median {
+                true => Some(JudgeStatus::IdlenessLimitExceeded),
+                false => Some(JudgeStatus::TimeLimitExceeded)
+            },
+            TlePolicy::Median => worst(false).or_else(|| worst(true))
+        };
+        RepeatedResult {
+            status: status.unwrap_or(JudgeStatus::Accepted),
+            wall_time,
+            cpu_time,
+            max_memory_bytes: runs.iter().map(|r| r.memory_used_bytes).max().unwrap_or_default(),
+            runs
+        }
+    }
+
+    /// Whether the verdict over all runs is Accepted
+    pub fn accepted(&self) -> bool {
+        matches!(self.status, JudgeStatus::Accepted)
+    }
+}
+
+impl Display for RepeatedResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("Status:  \t{}\n", self.status))?;
+        f.write_fmt(format_args!("Runs:    \t{}\n", self.runs.len()))?;
+        f.write_fmt(format_args!("Real Time:\t{}\n", self.wall_time))?;
+        f.write_fmt(format_args!("CPU Time:\t{}\n", self.cpu_time))?;
+        f.write_fmt(format_args!("Max Memory:\t{}", utils::format_memory(self.max_memory_bytes)))?;
         Ok(())
     }
 }
 
+/// Why the judger sent the program a signal to end it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KillReason {
+    /// It went over its CPU time limit
+    CpuTime,
+    /// It went over its real time limit
+    WallTime,
+    /// It went over its memory limit
+    Memory,
+    /// It went over its disk I/O limit
+    DiskIo,
+    /// It stopped itself, which is taken as idling
+    Idle,
+    /// It was stopped for a syscall the sandbox denies
+    SecurityViolation,
+    /// The run was cancelled, or the batch's deadline passed
+    Cancelled,
+    /// Judging is stopping, and the program is asked to exit
+    Stopping,
+    /// An observer failed while the program ran
+    ObserverFailed,
+    /// It was still running once its grace period was over
+    GracePeriodOver
+}
+
+impl KillReason {
+    /// The reason in kebab case, as transcripts have it
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::CpuTime => "cpu-time",
+            Self::WallTime => "wall-time",
+            Self::Memory => "memory",
+            Self::DiskIo => "disk-io",
+            Self::Idle => "idle",
+            Self::SecurityViolation => "security-violation",
+            Self::Cancelled => "cancelled",
+            Self::Stopping => "stopping",
+            Self::ObserverFailed => "observer-failed",
+            Self::GracePeriodOver => "grace-period-over"
+        }
+    }
+}
+
+impl Display for KillReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A step of a run, as observers hear of it from on_event while the run
+/// goes on. Times are since the program started.
+pub enum RunEvent<'a> {
+    /// The validator checked the input, finding `problem` with it if any
+    Validated {
+        /// The input checked
+        input: &'a Path,
+        /// What is wrong with it
+        problem: Option<&'a str>
+    },
+    /// Starting the program failed, to be tried again after `retry_in`
+    /// if given
+    LaunchFailed {
+        /// Why it failed
+        error: String,
+        /// When it is tried again
+        retry_in: Option<Duration>
+    },
+    /// The sandbox forked and executed the program
+    Started {
+        /// Its process ID
+        pid: i32,
+        /// What was executed, the private copy unless copying is off
+        exec: &'a Path,
+        /// SHA-256 of that, if it was copied
+        exec_sha256: Option<&'a str>,
+        /// Limits it runs under
+        limits: AppliedLimits,
+        /// Strength of the sandbox asked for
+        strength: SandboxStrength,
+        /// Whether the seccomp filter was installed
+        seccomp: bool
+    },
+    /// The judger sent the program `signal`
+    Signalled {
+        /// Signal sent, such as SIGKILL
+        signal: i32,
+        /// Why
+        reason: KillReason,
+        /// When
+        elapsed: Duration
+    },
+    /// The program ended and was reaped
+    Exited {
+        /// Its wait status
+        status: i32,
+        /// When
+        elapsed: Duration,
+        /// What it used
+        usage: ResourceUsage
+    },
+    /// The output was compared with the answer
+    Compared {
+        /// Path of the answer, None for one given in memory
+        answer: Option<&'a Path>,
+        /// Path of the output, there until the run is over
+        output: &'a Path,
+        /// How they were compared
+        comparison: Comparison,
+        /// What the comparison found
+        status: &'a JudgeStatus,
+        /// Where they first differ, if they do
+        difference: Option<&'a str>
+    }
+}
+
+/// Follows the runs of a session as they happen, e.g. to report progress.
+/// Every method does nothing unless implemented.
+pub trait JudgeObserver: Send + Sync {
+    /// A run on `test` is about to start
+    fn on_run_start(&self, _test: &TestCase) {}
+
+    /// Called every tick interval while the program runs, with the memory
+    /// it uses when that is being sampled
+    fn on_tick(&self, _elapsed: Duration, _rss: Option<u64>) {}
+
+    /// A run finished, with its result
+    fn on_run_complete(&self, _result: &JudgeResult) {}
+
+    /// A step of the run in progress, on the thread doing the run. A
+    /// panic here is only logged, the steps being a record of the run
+    /// rather than part of judging it.
+    fn on_event(&self, _event: &RunEvent) {}
+}
+
+/*
+ *  Several observers of a session, in the order they were given
+ */
+struct Observers(Vec<Arc<dyn JudgeObserver>>);
+
+impl JudgeObserver for Observers {
+    fn on_run_start(&self, test: &TestCase) {
+        self.0.iter().for_each(|observer| observer.on_run_start(test));
+    }
+
+    fn on_tick(&self, elapsed: Duration, rss: Option<u64>) {
+        self.0.iter().for_each(|observer| observer.on_tick(elapsed, rss));
+    }
+
+    fn on_run_complete(&self, result: &JudgeResult) {
+        self.0.iter().for_each(|observer| observer.on_run_complete(result));
+    }
+
+    fn on_event(&self, event: &RunEvent) {
+        self.0.iter().for_each(|observer| observer.on_event(event));
+    }
+}
+
+/*
+ *  Call into an observer, turning a panic into an error message
+ */
+fn call_observer(call: impl FnOnce()) -> Result<(), String> {
+    panic::catch_unwind(AssertUnwindSafe(call)).map_err(|payload| {
+        let msg = payload.downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        format!("observer panicked: {msg}")
+    })
+}
+
+/// Lets another thread cancel the runs of a session. Cancelling kills
+/// the program of the run in progress, which then gets the Cancelled
+/// verdict, and so do all later runs without the program being started.
+/// Both cancel and stop only set a flag and write to a pipe, so they may
+/// be called from a signal handler.
+#[derive(Clone)]
+pub struct JudgeHandle {
+    cancelled: Arc<AtomicBool>,
+    stopping: Arc<AtomicBool>,
+    // Written to as the handle is cancelled or stopped, waking the wait on
+    // the program at once. None if the pipe couldn't be made, the wait
+    // notices within CANCEL_INTERVAL then.
+    wakeup: Arc<Option<(OwnedFd, OwnedFd)>>
+}
+
+impl Default for JudgeHandle {
+    fn default() -> Self {
+        JudgeHandle::new()
+    }
+}
+
+impl JudgeHandle {
+    /// A handle that isn't cancelled yet
+    pub fn new() -> Self {
+        JudgeHandle {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            stopping: Arc::new(AtomicBool::new(false)),
+            wakeup: Arc::new(wakeup_pipe())
+        }
+    }
+
+    /// Cancel the runs of every session given the handle or a clone of it
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.wake();
+    }
+
+    /// Let the run in progress wind down: its program is sent SIGTERM and
+    /// has the grace period of the session's TerminationPolicy to exit,
+    /// then it is judged as it ended and its result marked interrupted.
+    /// Still running after that, it is killed and Cancelled. Later runs
+    /// are Cancelled without being started, as by cancel, which still
+    /// kills the program at once.
+    pub fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        self.wake();
+    }
+
+    /// Whether cancel was called
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Whether stop was called
+    pub fn is_stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+
+    fn wake(&self) {
+        if let Some((_, write)) = self.wakeup.as_ref() {
+            // Once full the pipe is readable anyway, so a failed write is fine
+            unsafe { libc::write(write.as_raw_fd(), [1u8].as_ptr().cast(), 1) };
+        }
+    }
+
+    /*
+     *  The end of the pipe that turns readable once the handle is
+     *  cancelled or stopped, and stays so
+     */
+    fn wakeup_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.wakeup.as_ref().as_ref().map(|(read, _)| read.as_fd())
+    }
+}
+
+/*
+ *  The pipe of a JudgeHandle, its write end not blocking a signal handler
+ */
+fn wakeup_pipe() -> Option<(OwnedFd, OwnedFd)> {
+    let (read, write) = secrun::cloexec_pipe().ok()?;
+    if unsafe { libc::fcntl(write.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK) } < 0 {
+        return None;
+    }
+    Some((read, write))
+}
+
+/// Checks the test data with a validator program before the program
+/// being judged runs on it. The validator reads the input on stdin and
+/// exits with 0 if it is valid.
+struct Validator {
+    session: JudgeSession,
+    // What was found for the input files checked so far, as of when they
+    // were last modified
+    checked: Mutex<HashMap<PathBuf, (SystemTime, Option<String>)>>
+}
+
+impl Validator {
+    /*
+     *  Why `input` is invalid, if it is. A file that didn't change since it
+     *  was last checked isn't checked again.
+     */
+    async fn check<W: Waiter>(&self, waiter: W, input: &Path) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+        let _span = waiter.span("validate");
+        let modified = fs::metadata(input)?.modified()?;
+        if let Some((when, found)) = self.checked.lock().unwrap_or_else(|e| e.into_inner()).get(input) {
+            if *when == modified {
+                return Ok(found.clone());
+            }
+        }
+        let session = &self.session;
+        let name = session.exec.to_string_lossy().into_owned();
+        let limits = session.run_limits(session.cpu_limit, session.wall_limit, session.max_allowed_memory_bytes, None);
+        session.validate_exec(&session.exec)?;
+        check_file("input", input)?;
+        let input_source = InputSource::File(input.to_path_buf());
+        let output = OutputCheck::Forward(&mut io::sink());
+        let result = session.judge_input(waiter, Instant::now(), &session.exec, input_source, &limits, &[&name], output).await?;
+        let found = match &result.status {
+            JudgeStatus::Accepted => None,
+            // Says nothing about the input, so it is checked again next time
+            JudgeStatus::Cancelled => return Ok(Some(String::from("validation cancelled"))),
+            JudgeStatus::ReturnNonZero(status) if libc::WIFEXITED(*status) => {
+                let stderr = String::from_utf8_lossy(&result.stderr);
+                let mut message = format!("validator exited with {}", libc::WEXITSTATUS(*status));
+                if !stderr.trim().is_empty() {
+                    message += &format!(": {}", stderr.trim());
+                }
+                Some(message)
+            },
+            status => Some(format!("validator got {status}"))
+        };
+        debug!("validated {}: {}", input.display(), found.as_deref().unwrap_or("valid"));
+        self.checked.lock().unwrap_or_else(|e| e.into_inner()).insert(input.to_path_buf(), (modified, found.clone()));
+        Ok(found)
+    }
+}
+
+/// What is wrong with the files of a session, found before any program
+/// is started
+#[derive(Debug)]
+pub enum JudgeError {
+    /// The file can't be looked up or opened
+    Inaccessible {
+        /// Which file of the session it is, such as "input"
+        what: &'static str,
+        /// Its path
+        path: PathBuf,
+        /// Why it can't be
+        error: io::Error
+    },
+    /// The file is a directory or a device, not a file
+    NotRegularFile {
+        /// Which file of the session it is
+        what: &'static str,
+        /// Its path
+        path: PathBuf
+    },
+    /// Run in place, so it needs an execute bit of its own
+    NotExecutable(PathBuf),
+    /// Neither an ELF executable nor a script with a shebang line, such
+    /// as an object file or a program for another system
+    NotExecutableFormat(PathBuf),
+    /// An ELF executable for another architecture than the programs here
+    /// run as
+    ArchMismatch {
+        /// The executable
+        exec: PathBuf,
+        /// What the host runs, or the policy's exec_arch if set
+        expected: ExecArch,
+        /// What the executable was built for
+        found: String
+    },
+    /// A script whose interpreter is none of a known language's, so that
+    /// the judger doesn't know what to allow it
+    UnknownInterpreter {
+        /// The script
+        exec: PathBuf,
+        /// The interpreter its `#!` line names
+        interpreter: PathBuf
+    },
+    /// The interpreter of a script is a script itself, such as a wrapper
+    /// of a version manager
+    ScriptInterpreter(PathBuf),
+    /// A program failed the strict path check
+    UnsafePath {
+        /// Which program of the session it is
+        what: &'static str,
+        /// What the check found
+        report: PathReport
+    },
+    /// The directory the scratch directories of runs go in can't take
+    /// them, such as for a full or read-only filesystem
+    Scratch(io::Error),
+    /// The session has no answer of its own to judge its test against
+    NoAnswer,
+    /// Setting up the run or starting the sandboxed program failed
+    Launch(io::Error),
+    /// Giving the program its input, waiting for it or judging its output
+    /// failed
+    Run(io::Error)
+}
+
+impl JudgeError {
+    /*
+     *  Wrap a failure of starting a run, keeping its OS error if it has one
+     */
+    fn launch(e: Box<dyn Error>) -> Self {
+        let e = match e.downcast::<JudgeError>() {
+            Ok(e) => return *e,
+            Err(e) => e
+        };
+        match e.downcast::<io::Error>() {
+            Ok(e) => JudgeError::Launch(*e),
+            Err(e) => JudgeError::Launch(io::Error::other(e.to_string()))
+        }
+    }
+
+    /*
+     *  Wrap a failure of judging a run, keeping it as it is if it is one
+     *  of the session's errors already
+     */
+    #[cfg(feature = "async")]
+    fn run(e: Box<dyn Error + Send + Sync>) -> Self {
+        let e = match e.downcast::<JudgeError>() {
+            Ok(e) => return *e,
+            Err(e) => e
+        };
+        match e.downcast::<io::Error>() {
+            Ok(e) => JudgeError::Run(*e),
+            Err(e) => JudgeError::Run(io::Error::other(e.to_string()))
+        }
+    }
+
+    /// Whether trying again a little later may well succeed, as when the
+    /// host ran out of processes, memory or disk space for the moment.
+    /// Problems with the files given never go away by themselves.
+    pub fn is_transient(&self) -> bool {
+        let Self::Launch(e) = self else {
+            return false;
+        };
+        matches!(e.raw_os_error(), Some(libc::EAGAIN | libc::ENOMEM | libc::ENOSPC | libc::EMFILE | libc::ENFILE))
+            || matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::OutOfMemory | io::ErrorKind::StorageFull)
+    }
+}
+
+impl Display for JudgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Inaccessible { what, path, error } => {
+                f.write_fmt(format_args!("{what} {}: {error}", path.display()))
+            },
+            Self::NotRegularFile { what, path } => {
+                f.write_fmt(format_args!("{what} {}: not a regular file", path.display()))
+            },
+            Self::NotExecutable(path) => {
+                f.write_fmt(format_args!("executable {}: no execute permission", path.display()))
+            },
+            Self::NotExecutableFormat(path) => {
+                f.write_fmt(format_args!("executable {}: neither an ELF executable nor a script", path.display()))
+            },
+            Self::ArchMismatch { exec, expected, found } => {
+                f.write_fmt(format_args!("executable {}: built for {found}, not {expected}", exec.display()))
+            },
+            Self::UnknownInterpreter { exec, interpreter } => f.write_fmt(format_args!(
+                "executable {}: no language is configured for its interpreter {}",
+                exec.display(),
+                interpreter.display()
+            )),
+            Self::ScriptInterpreter(path) => {
+                f.write_fmt(format_args!("interpreter {}: a script itself, not an ELF file", path.display()))
+            },
+            Self::UnsafePath { what, report } => {
+                let problems: Vec<String> = report.problems.iter().map(|problem| problem.to_string()).collect();
+                f.write_fmt(format_args!("{what} {}: unsafe path, {}", report.path.display(), problems.join(", ")))
+            },
+            Self::Scratch(e) => f.write_fmt(format_args!("{e}")),
+            Self::NoAnswer => f.write_str("the session has no answer, its tests have to be given to run_case"),
+            Self::Launch(e) => f.write_fmt(format_args!("cannot start the program: {e}")),
+            Self::Run(e) => f.write_fmt(format_args!("cannot judge the program: {e}"))
+        }
+    }
+}
+
+impl Error for JudgeError {}
+
+/// How a program that ran out of time is stopped
+#[derive(Clone, Copy, Debug)]
+pub struct TerminationPolicy {
+    /// Signal sent first so the program can flush diagnostics, None to skip it
+    pub term_signal: Option<i32>,
+    /// How long to wait after term_signal before killing the program
+    pub grace_period: Duration,
+    /// Signal that finally ends the program, SIGKILL is used if it survives
+    pub kill_signal: i32
+}
+
+impl Default for TerminationPolicy {
+    fn default() -> Self {
+        TerminationPolicy {
+            term_signal: Some(libc::SIGTERM),
+            grace_period: Duration::from_millis(250),
+            kill_signal: libc::SIGKILL
+        }
+    }
+}
+
+/// How the program gets its input and hands in its output
+#[derive(Clone, Debug, Default)]
+pub enum IoMode {
+    /// Through stdin and stdout
+    #[default]
+    Standard,
+    /// Through files with these names in the program's working directory,
+    /// with stdin and stdout both wired to /dev/null
+    NamedFiles {
+        /// Name of the file the program reads
+        input_name: String,
+        /// Name of the file the program writes
+        output_name: String
+    }
+}
+
+/// Which runs keep the program's output after being judged
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeepPolicy {
+    /// Runs never keep it
+    #[default]
+    Never,
+    /// Runs that weren't accepted
+    OnFailure,
+    /// Every run keeps it
+    Always
+}
+
+/// How often a run is tried again when starting it fails for a reason
+/// that may go away, see JudgeError::is_transient
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Tries in all, 1 to never retry
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubling for every further one
+    pub backoff: Duration
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { max_attempts: 3, backoff: Duration::from_millis(50) }
+    }
+}
+
+/// Time and memory limits of a run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// CPU time limit
+    pub cpu: Duration,
+    /// Wall time limit
+    pub wall: Duration,
+    /// Memory limit
+    pub memory_bytes: u64
+}
+
+/// Limits a run was judged with, and the ones of the problem they were
+/// scaled from by the language's multipliers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AppliedLimits {
+    /// Limits set for the problem
+    pub base: Limits,
+    /// Limits the run was judged with
+    pub effective: Limits
+}
+
+impl AppliedLimits {
+    /// Whether the multipliers changed the limits at all
+    pub fn is_scaled(&self) -> bool {
+        self.base != self.effective
+    }
+}
+
+/// How strongly a run was sandboxed: the strength its policy asked for,
+/// and whether that got it the seccomp filter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AppliedSandbox {
+    /// What the policy asked for
+    pub strength: SandboxStrength,
+    /// Whether the program ran under the seccomp filter. Results without it
+    /// say nothing about the program keeping to the syscall policy.
+    pub seccomp: bool
+}
+
+/// What the output of a session's own test is judged against
+#[derive(Clone)]
+pub enum AnswerSource {
+    /// Read from this file, or kept in the session's answer cache if it
+    /// has one
+    File(PathBuf),
+    /// These bytes, compared with the output in memory without being
+    /// written anywhere
+    Bytes(Vec<u8>),
+    /// The file at the path as this cache keeps it, read once for all the
+    /// sessions given the cache
+    Cached(Arc<AnswerCache>, PathBuf)
+}
+
+impl AnswerSource {
+    /// The file the answer is read from, None for one given in memory
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            AnswerSource::File(path) | AnswerSource::Cached(_, path) => Some(path),
+            AnswerSource::Bytes(_) => None
+        }
+    }
+}
+
+impl Display for AnswerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnswerSource::File(path) | AnswerSource::Cached(_, path) => f.write_fmt(format_args!("{}", path.display())),
+            AnswerSource::Bytes(bytes) => f.write_fmt(format_args!("{} bytes in memory", bytes.len()))
+        }
+    }
+}
+
+// Default wall time limit as a multiple of the CPU time limit
+const WALL_LIMIT_FACTOR: u32 = 3;
+// Limits of a session built without setting them
+const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(1);
+const DEFAULT_MEMORY_LIMIT: u64 = 100 << 20;
+// How often observers hear about a running program by default
+const DEFAULT_TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Judges one executable under the same limits and sandbox policy, on the
+/// input and answer it was built with or on the tests of a problem. Sessions
+/// are made with a JudgeSessionBuilder, see JudgeSession::builder.
 pub struct JudgeSession {
     exec: PathBuf,
-    input_file: PathBuf,
-    standard_ans_file: PathBuf,
-    max_allowed_time: Duration,
-    max_allowed_memory_bytes: u64
+    // Locked as a streamed input is handed over to the first run, None
+    // once that happened
+    input: Mutex<Option<InputSource>>,
+    // None for sessions that only judge test cases
+    standard_ans_file: Option<AnswerSource>,
+    // CPU time limit counting all threads and descendants
+    cpu_limit: Duration,
+    // Backstop for programs that wait instead of running
+    wall_limit: Duration,
+    max_allowed_memory_bytes: u64,
+    // Largest output the program may write, unlimited if None
+    output_limit: Option<u64>,
+    // Disk reads and writes the program may do together, unlimited if None
+    io_limit: Option<u64>,
+    policy: SandboxPolicy,
+    comparison: Comparison,
+    cgroup_root: Option<PathBuf>,
+    max_tasks: u64,
+    cpu_quota: Option<f64>,
+    termination: TerminationPolicy,
+    io_mode: IoMode,
+    // Copied into the working directory of every run, as (source, name)
+    provide_files: Vec<(PathBuf, String)>,
+    copy_exec: bool,
+    // Reads the executable into the page cache before each run
+    prewarm: bool,
+    // Runs scripts instead of the interpreter of their #! line
+    interpreter: Option<PathBuf>,
+    // Checks the paths of the programs before they run, if set
+    strict_paths: Option<StrictPaths>,
+    // Where per-run scratch directories go, the system temp dir if None
+    scratch_base: Option<PathBuf>,
+    // What their names start with, scratch::DEFAULT_PREFIX if None
+    scratch_prefix: Option<String>,
+    keep_output: KeepPolicy,
+    // Where kept output goes, the scratch directory is kept instead if None
+    output_dir: Option<PathBuf>,
+    retry: RetryPolicy,
+    // Scale the limits for the program's language
+    multipliers: LimitMultipliers,
+    cancel: Option<JudgeHandle>,
+    observer: Option<Arc<dyn JudgeObserver>>,
+    tick_interval: Duration,
+    // Starts the runs, shared with the validator
+    sandbox: Arc<dyn Sandbox>,
+    validator: Option<Box<Validator>>,
+    cache: Option<ResultCache>,
+    // Has the answers in memory, shared with other sessions
+    answers: Option<Arc<AnswerCache>>
 }
 
 impl JudgeSession {
+    /// A session limiting both CPU and wall time to `max_allowed_time`,
+    /// with everything else at its default. Use `builder` for more.
     pub fn new(
         exec: PathBuf,
-        input_file: PathBuf,
+        input: InputSource,
         standard_ans_file: PathBuf,
         max_allowed_time: Duration,
         max_allowed_memory_bytes: u64
     ) -> Self {
+        let mut session = JudgeSession::defaults(exec);
+        session.input = Mutex::new(Some(input));
+        session.standard_ans_file = Some(AnswerSource::File(standard_ans_file));
+        session.cpu_limit = max_allowed_time;
+        session.wall_limit = max_allowed_time;
+        session.max_allowed_memory_bytes = max_allowed_memory_bytes;
+        session
+    }
+
+    /// Start building a session running `exec`, with a 1s CPU limit, a 3s
+    /// wall limit, 100MiB of memory and an empty input by default
+    pub fn builder(exec: PathBuf) -> JudgeSessionBuilder {
+        JudgeSessionBuilder {
+            session: JudgeSession::defaults(exec),
+            wall_limit: None,
+            multipliers: None,
+            validator: None,
+            cache_dir: None,
+            observers: Vec::new()
+        }
+    }
+
+    fn defaults(exec: PathBuf) -> Self {
         JudgeSession {
             exec,
-            input_file,
-            standard_ans_file,
-            max_allowed_time,
-            max_allowed_memory_bytes
+            input: Mutex::new(Some(InputSource::Null)),
+            standard_ans_file: None,
+            cpu_limit: DEFAULT_TIME_LIMIT,
+            wall_limit: DEFAULT_TIME_LIMIT.saturating_mul(WALL_LIMIT_FACTOR),
+            max_allowed_memory_bytes: DEFAULT_MEMORY_LIMIT,
+            output_limit: None,
+            io_limit: None,
+            policy: SandboxPolicy::default(),
+            comparison: Comparison::default(),
+            cgroup_root: None,
+            max_tasks: 32,
+            cpu_quota: None,
+            termination: TerminationPolicy::default(),
+            io_mode: IoMode::default(),
+            provide_files: Vec::new(),
+            copy_exec: true,
+            prewarm: false,
+            interpreter: None,
+            strict_paths: None,
+            scratch_base: None,
+            scratch_prefix: None,
+            keep_output: KeepPolicy::Never,
+            output_dir: None,
+            retry: RetryPolicy::default(),
+            multipliers: LimitMultipliers::default(),
+            cancel: None,
+            observer: None,
+            tick_interval: DEFAULT_TICK_INTERVAL,
+            sandbox: Arc::new(LinuxSandbox::default()),
+            validator: None,
+            cache: None,
+            answers: None
         }
     }
 
-    pub fn run_judge(self, args: &[&str]) -> Result<JudgeResult, Box<dyn Error>> {
-        const WAIT_DURATION: Duration = Duration::from_micros(100);
+    /// Run the program on the session's input and judge its output against
+    /// the session's answer, passing it `args`
+    pub fn run_judge(&self, args: &[&str]) -> Result<JudgeResult, Box<dyn Error>> {
+        self.run_judge_on(&self.exec, args)
+    }
 
-        let mut tmp_out = PathBuf::from("/tmp/");
-        tmp_out.push(format!(
-            "{}.out", 
-            self.input_file.file_name().unwrap_or(OsStr::new("tmp")).to_string_lossy()
-        ));
+    /**
+     *  Check the executable, input and answer of the session, so that a
+     *  wrong path shows up as such instead of as the verdict of a run.
+     *  The runs on the session's own test check them first anyway.
+     */
+    pub fn validate(&self) -> Result<(), JudgeError> {
+        self.validate_exec(&self.exec)?;
+        self.check_paths(&self.exec)?;
+        let policy = SandboxPolicy { output_limit: self.file_limit(), ..self.policy.clone() };
+        self.check_scratch(&policy, &self.exec)?;
+        self.validate_own_test()
+    }
 
-        if tmp_out.exists() {
-            if tmp_out.is_dir() {
-                fs::remove_dir(&tmp_out)?;
-            } else {
-                fs::remove_file(&tmp_out)?;
-            }
+    /*
+     *  Check the directory the scratch directories go in for a run of
+     *  `exec` under `policy`, which needs room for the program's copy too
+     */
+    fn check_scratch(&self, policy: &SandboxPolicy, exec: &Path) -> Result<(), JudgeError> {
+        let copy = match self.copy_exec {
+            true => fs::metadata(exec).map_or(0, |metadata| metadata.len()),
+            false => 0
+        };
+        let base = self.scratch_base.clone().unwrap_or_else(env::temp_dir);
+        utils::check_scratch(&base, policy.scratch_needed().saturating_add(copy), self.copy_exec).map_err(JudgeError::Scratch)
+    }
+
+    fn validate_own_test(&self) -> Result<(), JudgeError> {
+        if let Some(InputSource::File(path)) = &*self.input.lock().unwrap_or_else(|e| e.into_inner()) {
+            check_file("input", path)?;
+        }
+        if let Some(path) = self.standard_ans_file.as_ref().and_then(AnswerSource::path) {
+            check_file("answer", path)?;
         }
-        drop(File::create(&tmp_out)?);
+        for (source, _) in &self.provide_files {
+            check_file("provided file", source)?;
+        }
+        Ok(())
+    }
 
-        let (pid, begin_instant) = secrun::sandbox_run(
-            &self.exec, 
-            args, 
-            &self.input_file, 
-            &tmp_out
-        )?;
+    /*
+     *  Check that `exec` is something execve can start: a regular file
+     *  that is an ELF executable that runs here or a script, or anything
+     *  for the session's interpreter. It only needs an execute bit when it is run in place,
+     *  the private copy always has one.
+     */
+    fn validate_exec(&self, exec: &Path) -> Result<(), JudgeError> {
+        let inaccessible = |error| JudgeError::Inaccessible { what: "executable", path: exec.to_path_buf(), error };
+        let metadata = fs::metadata(exec).map_err(inaccessible)?;
+        if !metadata.is_file() {
+            return Err(JudgeError::NotRegularFile { what: "executable", path: exec.to_path_buf() });
+        }
+        if !self.copy_exec && metadata.permissions().mode() & 0o111 == 0 {
+            return Err(JudgeError::NotExecutable(exec.to_path_buf()));
+        }
+        let info = match elf::inspect(exec) {
+            Ok(info) => info,
+            // Execute-only files can still be run in place
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied && !self.copy_exec => return Ok(()),
+            Err(e) => return Err(inaccessible(e))
+        };
+        match info {
+            Some(info) => check_elf(exec, &info, self.policy.exec_arch),
+            None if self.interpreter.is_some() => Ok(()),
+            None => match utils::read_shebang(exec).map_err(inaccessible)? {
+                Some(_) => Ok(()),
+                None => Err(JudgeError::NotExecutableFormat(exec.to_path_buf()))
+            }
+        }
+    }
 
-        let mut return_value: i32 = 0;
-        let stop_instant;
-        let res_used;
-        unsafe {
-            // Initialize C-style struct rusage with zeros
-            let mut res_used_buf: libc::rusage = std::mem::transmute([0u8;size_of::<libc::rusage>()]);
-            loop {
-                let p = libc::wait4(pid, &mut return_value, libc::WNOHANG, &mut res_used_buf);
-                
-                if p == pid {
-                    // Record time as soon as the tested program exits
-                    // Making result more percise.
-                    stop_instant = Instant::now();
-                    res_used = res_used_buf;
-                    break;
-                } else {
-                    let duration = Instant::now().saturating_duration_since(begin_instant);
-                    if self.max_allowed_time != Duration::MAX && duration > self.max_allowed_time {
-                        libc::kill(pid, libc::SIGKILL);
-                    } else {
-                        std::thread::sleep(WAIT_DURATION);
-                    }
-                }
+    /*
+     *  The strict path checks of `exec` and of its interpreter if it is a
+     *  script, None without strict paths. A path failing them is an error.
+     */
+    fn check_paths(&self, exec: &Path) -> Result<Option<Vec<PathReport>>, JudgeError> {
+        let Some(strict) = &self.strict_paths else {
+            return Ok(None);
+        };
+        let mut paths = vec![("executable", exec.to_path_buf())];
+        if let Some((interpreter, _)) = self.interpreter_of(exec)? {
+            paths.push(("interpreter", interpreter.path));
+        }
+        let mut reports = Vec::new();
+        for (what, path) in paths {
+            let report = match utils::check_path(&path, strict) {
+                Ok(report) => report,
+                Err(error) => return Err(JudgeError::Inaccessible { what, path, error })
+            };
+            if !report.is_safe() {
+                return Err(JudgeError::UnsafePath { what, report });
             }
+            reports.push(report);
         }
+        Ok(Some(reports))
+    }
 
-        let duration = stop_instant.saturating_duration_since(begin_instant);
-        let memory_used_bytes = res_used.ru_maxrss as u64 * 1024;
-        let cpu_time_ms = (res_used.ru_utime.tv_usec/1000) as u64;
+    /*
+     *  The interpreter `exec` runs under when it is a script, the
+     *  session's or the one its `#!` line names, and the language that is
+     *  the interpreter's. A `#!/usr/bin/env NAME` line names the NAME found
+     *  in PATH. ELF files, and scripts that can only be executed, are left
+     *  to the kernel.
+     */
+    fn interpreter_of(&self, exec: &Path) -> Result<Option<(Interpreter, Option<&'static Language>)>, JudgeError> {
+        let inaccessible = |error| JudgeError::Inaccessible { what: "executable", path: exec.to_path_buf(), error };
+        match elf::detect(exec) {
+            Ok(ElfKind::NotElf) => {},
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => return Ok(None),
+            Ok(_) => return Ok(None),
+            Err(e) => return Err(inaccessible(e))
+        }
+        if let Some(path) = &self.interpreter {
+            check_interpreter(path, self.policy.exec_arch)?;
+            return Ok(Some((Interpreter { path: path.clone(), arg: None }, language::for_interpreter(path))));
+        }
+        let Some(shebang) = utils::read_shebang(exec).map_err(inaccessible)? else {
+            return Ok(None);
+        };
+        let interpreter = match (shebang.interpreter.file_name() == Some("env".as_ref()), shebang.arg) {
+            (true, Some(name)) => {
+                let path = utils::find_path(&name).map_err(|e| JudgeError::Inaccessible {
+                    what: "interpreter",
+                    path: PathBuf::from(&name),
+                    error: io::Error::new(io::ErrorKind::NotFound, e.to_string())
+                })?;
+                Interpreter { path, arg: None }
+            },
+            (_, arg) => Interpreter { path: shebang.interpreter, arg }
+        };
+        let Some(language) = language::for_interpreter(&interpreter.path) else {
+            return Err(JudgeError::UnknownInterpreter { exec: exec.to_path_buf(), interpreter: interpreter.path });
+        };
+        check_interpreter(&interpreter.path, self.policy.exec_arch)?;
+        Ok(Some((interpreter, Some(language))))
+    }
 
-        let status = if memory_used_bytes > self.max_allowed_memory_bytes {
+    /*
+     *  Set the session up for the language of its program, if it is a
+     *  script: its interpreter gets the syscalls it needs. The language,
+     *  for its limits.
+     */
+    fn adopt_language(&mut self) -> Result<Option<&'static Language>, JudgeError> {
+        let Some((interpreter, language)) = self.interpreter_of(&self.exec)? else {
+            return Ok(None);
+        };
+        debug!(
+            "{} runs under {}, {}",
+            self.exec.display(),
+            interpreter.path.display(),
+            language.map_or("of no known language", |lang| lang.name)
+        );
+        if let Some(language) = language {
+            self.policy.syscalls.retain(|rule| !language.allowed_syscalls.contains(&rule.syscall));
+        }
+        Ok(language)
+    }
+
+    /**
+     *  Limits the session's runs get, as set and as scaled for the
+     *  program's language, before the tests of a problem set their own
+     */
+    pub fn limits(&self) -> AppliedLimits {
+        self.run_limits(self.cpu_limit, self.wall_limit, self.max_allowed_memory_bytes, None).applied()
+    }
+
+    /**
+     *  What a run of the session's program with `args` would set up, from
+     *  the same code that sets up real runs, without starting anything
+     */
+    pub fn describe(&self, args: &[&str]) -> Result<SessionPlan, Box<dyn Error>> {
+        self.validate_exec(&self.exec)?;
+        self.validate_own_test()?;
+        let path_checks = self.check_paths(&self.exec)?;
+        let limits = self.run_limits(self.cpu_limit, self.wall_limit, self.max_allowed_memory_bytes, None);
+        let policy = self.run_policy(&limits);
+        let scratch_base = self.scratch_base.clone().unwrap_or_else(env::temp_dir);
+        let scratch = PlannedScratch::under(&scratch_base, self.scratch_prefix());
+        // The copy doesn't exist yet, but is the same file as the original
+        let interpreter = self.interpreter_of(&self.exec)?.map(|(interpreter, _)| interpreter);
+        let launch = secrun::plan_launch(
+            &self.exec,
+            args,
+            interpreter.as_ref(),
+            &policy,
+            matches!(self.io_mode, IoMode::NamedFiles { .. }),
+            !self.provide_files.is_empty(),
+            self.cgroup_root.is_some(),
+            &scratch.work_dir
+        )?;
+        let input = match &*self.input.lock().unwrap_or_else(|e| e.into_inner()) {
+            _ if self.standard_ans_file.is_none() => PlannedInput::PerTest,
+            Some(InputSource::File(path)) => PlannedInput::File(path.clone()),
+            Some(InputSource::Bytes(bytes)) => PlannedInput::Bytes(bytes.len()),
+            Some(InputSource::Reader(_)) => PlannedInput::Stream,
+            Some(InputSource::Null) => PlannedInput::Null,
+            None => PlannedInput::None
+        };
+        Ok(SessionPlan {
+            exec: self.exec.clone(),
+            exec_copy: self.copy_exec.then_some(scratch.exec_copy),
+            prewarm: self.prewarm,
+            input,
+            answer: match &self.standard_ans_file {
+                Some(AnswerSource::Bytes(bytes)) => PlannedAnswer::Bytes(bytes.len()),
+                Some(answer) => PlannedAnswer::File(answer.path().map(Path::to_path_buf).unwrap_or_default()),
+                None => PlannedAnswer::PerTest
+            },
+            limits: limits.applied(),
+            output_limit: self.output_limit,
+            io_limit: self.io_limit,
+            stderr_limit: self.policy.stderr_limit,
+            comparison: self.comparison,
+            cgroup_root: self.cgroup_root.clone(),
+            max_tasks: self.max_tasks,
+            cpu_quota: self.cpu_quota,
+            scratch_dir: scratch.path,
+            io_mode: self.io_mode.clone(),
+            provided: self.provide_files.clone(),
+            termination: self.termination,
+            retry: self.retry,
+            path_checks,
+            launch
+        })
+    }
+
+    /**
+     *  Judge another executable on the session's test, e.g. the next
+     *  submission to the same problem
+     */
+    pub fn run_judge_on(&self, exec: &Path, args: &[&str]) -> Result<JudgeResult, Box<dyn Error>> {
+        let _span = log::span("session");
+        let limits = self.run_limits(self.cpu_limit, self.wall_limit, self.max_allowed_memory_bytes, None);
+        self.validate_exec(exec)?;
+        self.check_paths(exec)?;
+        self.validate_own_test()?;
+        let answer = self.own_answer()?;
+        self.observe(&self.own_test(answer), || {
+            blocking(self.judge_cached(Blocking, exec, self.next_input()?, &limits, args, answer))
+        })
+    }
+
+    /**
+     *  Judge the program as run_judge does, as a future that waits for
+     *  it on its pidfd instead of blocking a thread, so that an executor's
+     *  few threads judge many runs at once. Any executor can poll it, the
+     *  judger has a thread of its own to wake it, waiting on the pidfds
+     *  with epoll. Waking it from tokio's reactor instead would tie every
+     *  user of the library to tokio and its version, and pull in a runtime
+     *  for a single file descriptor per run. The limits hold as they do
+     *  for run_judge, and cancelling the session's JudgeHandle cancels the
+     *  run as well. Dropping the future kills the program.
+     *  Validating the input and comparing the output still happen on the
+     *  thread polling it, as do the waits of runs without a pidfd to wait
+     *  on, such as those with named files, a millisecond at a time.
+     */
+    #[cfg(feature = "async")]
+    pub async fn run_judge_async(&self, args: &[&str]) -> Result<JudgeResult, JudgeError> {
+        let limits = self.run_limits(self.cpu_limit, self.wall_limit, self.max_allowed_memory_bytes, None);
+        self.validate_exec(&self.exec)?;
+        self.check_paths(&self.exec)?;
+        self.validate_own_test()?;
+        let answer = self.own_answer()?;
+        let test = self.own_test(answer);
+        let started = Instant::now();
+        let result = match self.run_starting(&test) {
+            Some(failed) => Ok(failed),
+            None => {
+                let input = self.next_input().map_err(JudgeError::Run)?;
+                match self.judge_cached(Polled, &self.exec, input, &limits, args, answer).await {
+                    Ok(result) => Ok(self.run_finished(result)),
+                    Err(e) => Err(JudgeError::run(e))
+                }
+            }
+        };
+        METRICS.record_run(result.as_ref().ok(), started.elapsed());
+        result
+    }
+
+    /**
+     *  Judge the program on the session's test `runs` times, for timings
+     *  that hold up when they are close to the limits. The output is only
+     *  compared until a run's output was judged, later runs that get that
+     *  far take over its verdict.
+     */
+    pub fn run_repeated(
+        &self,
+        runs: usize,
+        tle_policy: TlePolicy,
+        args: &[&str]
+    ) -> Result<RepeatedResult, Box<dyn Error>> {
+        let _span = log::span("session");
+        let limits = self.run_limits(self.cpu_limit, self.wall_limit, self.max_allowed_memory_bytes, None);
+        self.validate()?;
+        let answer = self.own_answer()?;
+        let test = self.own_test(answer);
+        let mut results: Vec<JudgeResult> = Vec::with_capacity(runs);
+        for _ in 0..runs.max(1) {
+            let check = match results.iter().map(|r| &r.status).find(|s| s.is_comparison()) {
+                Some(verdict) => OutputCheck::Reuse(verdict),
+                None => OutputCheck::Compare(answer)
+            };
+            let result = self.observe(&test, || {
+                self.judge(&self.exec, self.next_input()?, &limits, args, check)
+            })?;
+            let cancelled = matches!(result.status, JudgeStatus::Cancelled);
+            results.push(result);
+            if cancelled {
+                break;
+            }
+        }
+        Ok(RepeatedResult::new(results, tle_policy, &limits))
+    }
+
+    /**
+     *  Judge the program on a test case instead of the session's own input
+     *  and answer. A time limit of the test case replaces the session's
+     *  CPU limit, the wall limit becoming WALL_LIMIT_FACTOR times that.
+     *  Its memory limit replaces the session's as well.
+     *  A run still going at `deadline` is killed and gets Cancelled.
+     */
+    pub fn run_case(
+        &self,
+        case: &TestCase,
+        args: &[&str],
+        deadline: Option<Instant>
+    ) -> Result<JudgeResult, Box<dyn Error>> {
+        self.run_batch_case(case, args, deadline, None)
+    }
+
+    /*
+     *  Like run_case, for a batch that kills the run and has it Cancelled
+     *  through `cancel` once it no longer needs the result, such as after
+     *  a test before it failed
+     */
+    pub(crate) fn run_batch_case(
+        &self,
+        case: &TestCase,
+        args: &[&str],
+        deadline: Option<Instant>,
+        cancel: Option<&JudgeHandle>
+    ) -> Result<JudgeResult, Box<dyn Error>> {
+        let _span = log::span("session");
+        let limits = RunLimits { cancel: cancel.cloned(), ..self.case_limits(case, deadline) };
+        self.validate_exec(&self.exec)?;
+        check_file("input", &case.input)?;
+        check_file("answer", &case.answer)?;
+        let answer = AnswerSource::File(case.answer.clone());
+        self.observe(case, || {
+            let input = InputSource::File(case.input.clone());
+            blocking(self.judge_cached(Blocking, &self.exec, input, &limits, args, &answer))
+        })
+    }
+
+    /*
+     *  The cache key of judging `case` with `args`, which the stamps of a
+     *  batch keep to tell whether they still hold. The limits are taken
+     *  without the batch's deadline, which differs from run to run.
+     */
+    pub(crate) fn case_key(&self, case: &TestCase, args: &[&str]) -> io::Result<Option<String>> {
+        let input = InputSource::File(case.input.clone());
+        let answer = AnswerSource::File(case.answer.clone());
+        self.cache_key(&self.exec, &input, &self.case_limits(case, None), args, &answer)
+    }
+
+    /*
+     *  The memory limit the runs of `case` get, scaled for the language,
+     *  which a batch reserves out of its memory budget while it runs
+     */
+    pub(crate) fn case_memory_limit(&self, case: &TestCase) -> u64 {
+        self.case_limits(case, None).memory
+    }
+
+    fn case_limits(&self, case: &TestCase, deadline: Option<Instant>) -> RunLimits {
+        let memory = case.memory_limit.unwrap_or(self.max_allowed_memory_bytes);
+        match case.time_limit {
+            Some(limit) => self.run_limits(limit, limit.saturating_mul(WALL_LIMIT_FACTOR), memory, deadline),
+            None => self.run_limits(self.cpu_limit, self.wall_limit, memory, deadline)
+        }
+    }
+
+    /**
+     *  Run the program on the session's input without judging its output,
+     *  which is copied to `output` whatever the verdict. Accepted means it
+     *  ran within its limits and exited with 0.
+     */
+    pub fn run_program(&self, args: &[&str], output: &mut dyn Write) -> Result<JudgeResult, Box<dyn Error>> {
+        let _span = log::span("session");
+        let limits = self.run_limits(self.cpu_limit, self.wall_limit, self.max_allowed_memory_bytes, None);
+        self.validate()?;
+        self.judge(&self.exec, self.next_input()?, &limits, args, OutputCheck::Forward(output))
+    }
+
+    /**
+     *  Like run_program, but on the file `input` instead of the session's
+     *  input, e.g. to make the answers of a problem's tests
+     */
+    pub fn run_program_on(&self, input: &Path, args: &[&str], output: &mut dyn Write) -> Result<JudgeResult, Box<dyn Error>> {
+        let _span = log::span("session");
+        let limits = self.run_limits(self.cpu_limit, self.wall_limit, self.max_allowed_memory_bytes, None);
+        self.validate_exec(&self.exec)?;
+        check_file("input", input)?;
+        self.judge(&self.exec, InputSource::File(input.to_path_buf()), &limits, args, OutputCheck::Forward(output))
+    }
+
+    /**
+     *  Run the program, a compiler, and move the file `artifact` it made in
+     *  its working directory to `dest`. The policy has to leave the working
+     *  directory where the judger sees it, as SandboxPolicy::compiler does.
+     *  Output Missing means the compiler succeeded without making it.
+     */
+    pub fn compile(&self, args: &[&str], artifact: &str, dest: &Path) -> Result<JudgeResult, Box<dyn Error>> {
+        if !utils::is_file_name(artifact) {
+            return Err(format!("{artifact:?} is not a file name in the working directory").into());
+        }
+        if self.policy.private_tmp {
+            return Err("compiling needs a policy without a private /tmp, such as SandboxPolicy::compiler".into());
+        }
+        let limits = self.run_limits(self.cpu_limit, self.wall_limit, self.max_allowed_memory_bytes, None);
+        self.validate()?;
+        self.judge(&self.exec, self.next_input()?, &limits, args, OutputCheck::Collect { name: artifact, dest })
+    }
+
+    /*
+     *  The session's own test as observers get to see it, with an empty
+     *  input or answer path where they aren't files
+     */
+    fn own_test(&self, answer: &AnswerSource) -> TestCase {
+        let input = match &*self.input.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(InputSource::File(path)) => path.clone(),
+            _ => PathBuf::new()
+        };
+        TestCase::new(input, answer.path().map(Path::to_path_buf).unwrap_or_default())
+    }
+
+    /*
+     *  Do a run, telling the observer about its start and its result, and
+     *  count it in the metrics. A panicking observer makes the result a
+     *  SystemError.
+     */
+    fn observe(
+        &self,
+        test: &TestCase,
+        run: impl FnOnce() -> Result<JudgeResult, Box<dyn Error>>
+    ) -> Result<JudgeResult, Box<dyn Error>> {
+        let started = Instant::now();
+        let result = match self.run_starting(test) {
+            Some(failed) => Ok(failed),
+            None => run().map(|result| self.run_finished(result))
+        };
+        METRICS.record_run(result.as_ref().ok(), started.elapsed());
+        result
+    }
+
+    /*
+     *  Tell the observer a run on `test` starts, the run's result instead
+     *  if the observer failed
+     */
+    fn run_starting(&self, test: &TestCase) -> Option<JudgeResult> {
+        let observer = self.observer.as_ref()?;
+        call_observer(|| observer.on_run_start(test)).err().map(JudgeResult::system_error)
+    }
+
+    fn run_finished(&self, mut result: JudgeResult) -> JudgeResult {
+        if let Some(observer) = &self.observer {
+            if let Err(msg) = call_observer(|| observer.on_run_complete(&result)) {
+                result.status = JudgeStatus::SystemError(msg);
+            }
+        }
+        result
+    }
+
+    fn own_answer(&self) -> Result<&AnswerSource, JudgeError> {
+        self.standard_ans_file.as_ref().ok_or(JudgeError::NoAnswer)
+    }
+
+    /*
+     *  Input for the next run. A stream can only be read once, so it goes
+     *  to the first run and later ones fail.
+     */
+    fn next_input(&self) -> io::Result<InputSource> {
+        let mut input = self.input.lock().unwrap_or_else(|e| e.into_inner());
+        match &*input {
+            Some(InputSource::File(path)) => Ok(InputSource::File(path.clone())),
+            Some(InputSource::Bytes(bytes)) => Ok(InputSource::Bytes(bytes.clone())),
+            Some(InputSource::Reader(_)) => Ok(input.take().unwrap()),
+            Some(InputSource::Null) => Ok(InputSource::Null),
+            None => Err(io::Error::other("the streamed input was used up by an earlier run"))
+        }
+    }
+
+    /*
+     *  Limits of a run from the problem's, scaled for the language
+     */
+    fn run_limits(&self, cpu: Duration, wall: Duration, memory: u64, deadline: Option<Instant>) -> RunLimits {
+        let base = Limits { cpu, wall, memory_bytes: memory };
+        RunLimits {
+            cpu: self.multipliers.scale_time(cpu),
+            wall: self.multipliers.scale_time(wall),
+            memory: self.multipliers.scale_memory(base.memory_bytes),
+            base,
+            deadline,
+            cancel: None
+        }
+    }
+
+    /*
+     *  The sandbox policy of one run, with its CPU and output limits applied
+     */
+    fn run_policy(&self, limits: &RunLimits) -> SandboxPolicy {
+        let mut policy = self.policy.clone();
+        if limits.cpu != Duration::MAX {
+            policy.cpu_limit = Some(limits.cpu);
+        }
+        policy.output_limit = self.file_limit();
+        policy
+    }
+
+    /*
+     *  How large a file the program writes may grow, which the I/O limit
+     *  caps as well as the output limit
+     */
+    fn file_limit(&self) -> Option<u64> {
+        match (self.output_limit, self.io_limit) {
+            (Some(output), Some(io)) => Some(output.min(io)),
+            (output, io) => output.or(io)
+        }
+    }
+
+    /*
+     *  Set up what a single run needs, apart from the session
+     */
+    fn prepare_run(&self, exec: &Path, limits: &RunLimits) -> Result<RunState, Box<dyn Error>> {
+        let policy = self.run_policy(limits);
+        // Of the original, which the copy is the same file as
+        let interpreter = self.interpreter_of(exec)?.map(|(interpreter, _)| interpreter);
+        // Everything the run leaves behind goes away with the scratch directory
+        self.check_scratch(&policy, exec)?;
+        let scratch_base = self.scratch_base.clone().unwrap_or_else(env::temp_dir);
+        let scratch = ScratchDir::create_named(&scratch_base, self.scratch_prefix())?;
+        debug!("scratch directory {}", scratch.path().display());
+        let (exec, exec_sha256) = match self.copy_exec {
+            true => {
+                let copy = scratch.copy_executable(exec).map_err(|e| {
+                    io::Error::new(e.kind(), format!("cannot copy {}: {e}", exec.display()))
+                })?;
+                let hash = sha256::file_digest(&copy)?;
+                trace!("copied {} to {}", exec.display(), copy.display());
+                (copy, Some(hash))
+            },
+            false => (exec.to_path_buf(), None)
+        };
+        let prewarmed = self.prewarm && self.prewarm_run(&exec, interpreter.as_ref());
+        Ok(RunState {
+            stdout: scratch.file("stdout"),
+            stderr: scratch.file("stderr"),
+            work_dir: scratch.work_dir(),
+            scratch,
+            exec,
+            exec_sha256,
+            prewarmed,
+            interpreter,
+            policy
+        })
+    }
+
+    fn scratch_prefix(&self) -> &str {
+        self.scratch_prefix.as_deref().unwrap_or(scratch::DEFAULT_PREFIX)
+    }
+
+    /*
+     *  Read what the run starts into the page cache, so that faulting it
+     *  in from a cold disk isn't timed. A copy was just written and is
+     *  cached already, but reading it again costs little. Whether every
+     *  file could be read, which fails for one the judger may only execute.
+     */
+    fn prewarm_run(&self, exec: &Path, interpreter: Option<&Interpreter>) -> bool {
+        let files = std::iter::once(exec).chain(interpreter.map(|i| i.path.as_path()));
+        let mut prewarmed = true;
+        for file in files {
+            if let Err(e) = utils::prewarm(file) {
+                debug!("cannot prewarm {}: {e}", file.display());
+                prewarmed = false;
+            }
+        }
+        prewarmed
+    }
+
+    /*
+     *  Set up a run and start the program in the sandbox
+     */
+    fn launch(
+        &self,
+        exec: &Path,
+        input: InputSource,
+        limits: &RunLimits,
+        args: &[&str]
+    ) -> Result<LaunchedRun, JudgeError> {
+        let run = self.prepare_run(exec, limits).map_err(JudgeError::launch)?;
+        let cgroup = self.create_cgroup(limits.memory);
+        let io = SandboxIo {
+            stdin: input,
+            stdout: &run.stdout,
+            stderr: &run.stderr,
+            scratch_dir: &run.work_dir,
+            named: match &self.io_mode {
+                IoMode::Standard => None,
+                IoMode::NamedFiles { input_name, output_name } => Some(NamedFiles {
+                    input: input_name,
+                    output: output_name
+                })
+            },
+            provided: &self.provide_files
+        };
+        let spec = RunSpec {
+            exec: &run.exec,
+            args,
+            interpreter: run.interpreter.as_ref(),
+            io,
+            policy: &run.policy,
+            cgroup: cgroup.as_ref(),
+            wakeup: self.cancel.as_ref().and_then(|c| c.wakeup_fd())
+        };
+        let child = self.sandbox.spawn(spec).map_err(JudgeError::launch)?;
+        Ok((run, cgroup, child))
+    }
+
+    /*
+     *  Judge the program's output against `answer`, taking the result from
+     *  the cache if the same run was judged before. Runs whose output is
+     *  kept always happen, the cache only has results.
+     */
+    async fn judge_cached<W: Waiter>(
+        &self,
+        waiter: W,
+        exec: &Path,
+        input: InputSource,
+        limits: &RunLimits,
+        args: &[&str],
+        answer: &AnswerSource
+    ) -> Result<JudgeResult, Box<dyn Error + Send + Sync>> {
+        let check = OutputCheck::<io::Sink>::Compare(answer);
+        let cache = match &self.cache {
+            Some(cache) if matches!(self.keep_output, KeepPolicy::Never) => cache,
+            _ => return self.judge_with(waiter, exec, input, limits, args, check).await
+        };
+        if self.cancelled() {
+            return Ok(JudgeResult::unfinished(JudgeStatus::Cancelled));
+        }
+        // Trouble with the cache only costs the run its caching
+        let key = match self.cache_key(exec, &input, limits, args, answer) {
+            Ok(key) => key,
+            Err(e) => {
+                debug!("not caching the run: {e}");
+                None
+            }
+        };
+        let Some(key) = key else {
+            return self.judge_with(waiter, exec, input, limits, args, check).await;
+        };
+        if let Some(result) = cache.get(&key) {
+            debug!("{} from the cache, entry {key}", result.status.abbr());
+            return Ok(result);
+        }
+        let result = self.judge_with(waiter, exec, input, limits, args, check).await?;
+        if let Err(e) = cache.put(&key, &result) {
+            debug!("cannot store the result in the cache: {e}");
+        }
+        Ok(result)
+    }
+
+    /*
+     *  The cache key of a run: the digests of the executable, the
+     *  interpreter of a script, its input, the answer and the validator,
+     *  with everything of the session that can change the verdict. None if
+     *  a file is too large to hash or the input is a stream.
+     */
+    fn cache_key(
+        &self,
+        exec: &Path,
+        input: &InputSource,
+        limits: &RunLimits,
+        args: &[&str],
+        answer: &AnswerSource
+    ) -> io::Result<Option<String>> {
+        let input = match input {
+            InputSource::File(path) => cache::file_digest(path)?,
+            InputSource::Bytes(bytes) => Some(sha256::digest(bytes)),
+            InputSource::Reader(_) => None,
+            // Not the digest of no bytes, a pipe and /dev/null can be told apart
+            InputSource::Null => Some(String::from("null"))
+        };
+        let answer = match answer {
+            AnswerSource::File(path) => cache::file_digest(path)?,
+            AnswerSource::Bytes(bytes) => Some(sha256::digest(bytes)),
+            // What is judged against is the answer as first read, which the
+            // file may no longer be
+            AnswerSource::Cached(answers, path) => match answers.get(path)? {
+                Some(answer) => Some(sha256::digest(&answer)),
+                None => cache::file_digest(path)?
+            }
+        };
+        let validator = match &self.validator {
+            Some(validator) => cache::file_digest(&validator.session.exec)?.map(Some),
+            None => Some(None)
+        };
+        // A script is run by whatever its interpreter is now
+        let interpreter = match self.interpreter_of(exec).map_err(|e| io::Error::other(e.to_string()))? {
+            Some((interpreter, _)) => match cache::file_digest(&interpreter.path)? {
+                Some(digest) => format!("{} {:?} {digest}", interpreter.path.display(), interpreter.arg),
+                None => return Ok(None)
+            },
+            None => String::new()
+        };
+        let mut provided = Vec::with_capacity(self.provide_files.len());
+        for (source, name) in &self.provide_files {
+            let Some(digest) = cache::file_digest(source)? else {
+                return Ok(None);
+            };
+            provided.push(format!("{name:?} {digest}"));
+        }
+        let (Some(exec), Some(input), Some(answer), Some(validator)) =
+            (cache::file_digest(exec)?, input, answer, validator) else {
+            return Ok(None);
+        };
+        let effective = limits.applied().effective;
+        Ok(Some(cache::key(&[
+            format!("judger {}", env!("CARGO_PKG_VERSION")),
+            format!("exec {exec}"),
+            format!("interpreter {interpreter}"),
+            format!("input {input}"),
+            format!("answer {answer}"),
+            format!("validator {}", validator.unwrap_or_default()),
+            format!("args {args:?}"),
+            format!("limits {:?} {:?} {} {:?}", effective.cpu, effective.wall, effective.memory_bytes, self.io_limit),
+            format!("comparison {}", self.comparison),
+            format!("io {:?}", self.io_mode),
+            format!("provided {}", provided.join(", ")),
+            format!("policy {:?}", self.run_policy(limits)),
+            format!("cgroup {} {} {:?}", self.cgroup_root.is_some(), self.max_tasks, self.cpu_quota),
+            format!("termination {:?}", self.termination),
+            format!("copy {}", self.copy_exec),
+            format!("prewarm {}", self.prewarm)
+        ])))
+    }
+
+    /*
+     *  Run the program once and judge it, doing with its output what `check` says
+     */
+    fn judge(
+        &self,
+        exec: &Path,
+        input: InputSource,
+        limits: &RunLimits,
+        args: &[&str],
+        check: OutputCheck<'_, dyn Write + '_>
+    ) -> Result<JudgeResult, Box<dyn Error>> {
+        blocking(self.judge_with(Blocking, exec, input, limits, args, check))
+    }
+
+    /*
+     *  Judge, with the waits on the program of `waiter`
+     */
+    async fn judge_with<W: Waiter, S: Write + ?Sized>(
+        &self,
+        waiter: W,
+        exec: &Path,
+        input: InputSource,
+        limits: &RunLimits,
+        args: &[&str],
+        check: OutputCheck<'_, S>
+    ) -> Result<JudgeResult, Box<dyn Error + Send + Sync>> {
+        let session_start = Instant::now();
+        let _span = waiter.span("run");
+        if self.cancelled() {
+            return Ok(JudgeResult::unfinished(JudgeStatus::Cancelled));
+        }
+        if let (Some(validator), InputSource::File(path)) = (&self.validator, &input) {
+            let invalid = validator.check(waiter, path).await?;
+            self.emit(RunEvent::Validated { input: path, problem: invalid.as_deref() });
+            if self.cancelled() {
+                return Ok(JudgeResult::unfinished(JudgeStatus::Cancelled));
+            }
+            if let Some(message) = invalid {
+                return Ok(JudgeResult::system_error(format!("invalid test data: {message}")));
+            }
+        }
+        self.judge_input(waiter, session_start, exec, input, limits, args, check).await
+    }
+
+    /*
+     *  The run of judge_with once the input was found valid, which the
+     *  validator's own runs start with. Its overhead counts from
+     *  `session_start` on.
+     */
+    #[allow(clippy::too_many_arguments)]
+    async fn judge_input<W: Waiter, S: Write + ?Sized>(
+        &self,
+        waiter: W,
+        session_start: Instant,
+        exec: &Path,
+        input: InputSource,
+        limits: &RunLimits,
+        args: &[&str],
+        mut check: OutputCheck<'_, S>
+    ) -> Result<JudgeResult, Box<dyn Error + Send + Sync>> {
+        // Names the kept output, as a file name whatever the input's path
+        let input_name = match &input {
+            InputSource::File(path) => path
+                .file_stem()
+                .and_then(|stem| utils::sanitize_file_name(&stem.to_string_lossy()))
+                .unwrap_or_else(|| String::from("input")),
+            _ => String::from("stdin")
+        };
+        let mut input = input;
+        let mut retries = 0;
+        debug!(
+            "limits: cpu {:?}, wall {:?}, memory {} bytes, output {}",
+            limits.cpu,
+            limits.wall,
+            limits.memory,
+            self.output_limit.map_or(String::from("unlimited"), |bytes| format!("{bytes} bytes"))
+        );
+        let (mut run, cgroup, mut child) = loop {
+            // A stream can't be replayed, so that run gets only one try
+            let spare = replay_input(&input);
+            match (self.launch(exec, input, limits, args), spare) {
+                (Ok(x), _) => break x,
+                (Err(e), Some(spare)) if e.is_transient() && retries + 1 < self.retry.max_attempts => {
+                    let backoff = self.retry.backoff.saturating_mul(1 << retries.min(16));
+                    debug!("launch failed ({e}), retrying in {backoff:?}");
+                    self.emit(RunEvent::LaunchFailed { error: e.to_string(), retry_in: Some(backoff) });
+                    waiter.sleep(backoff).await;
+                    if self.cancelled() {
+                        return Ok(JudgeResult::unfinished(JudgeStatus::Cancelled));
+                    }
+                    retries += 1;
+                    input = spare;
+                },
+                (Err(e), _) => {
+                    self.emit(RunEvent::LaunchFailed { error: e.to_string(), retry_in: None });
+                    return Err(e.into());
+                }
+            }
+        };
+
+        let begin_instant = child.start_instant();
+        let judge_overhead = begin_instant.saturating_duration_since(session_start);
+        self.emit(RunEvent::Started {
+            pid: child.pid(),
+            exec: &run.exec,
+            exec_sha256: run.exec_sha256.as_deref(),
+            limits: limits.applied(),
+            strength: run.policy.strength,
+            seccomp: child.seccomp()
+        });
+        let exit = self.wait_child(waiter, child.as_mut(), cgroup.as_ref(), limits).await;
+        let stderr = read_stderr(&run.stderr);
+        // The capture file holds no more than the limit
+        let stderr_bytes = child.stderr_written()
+            .unwrap_or_else(|| fs::metadata(&run.stderr).map_or(0, |m| m.len()));
+        let stderr_truncated = stderr_bytes > run.policy.stderr_limit;
+        let exit = match exit {
+            Ok(x) => x,
+            Err(msg) => return Ok(JudgeResult::system_error(msg))
+        };
+        let ChildExit {
+            return_value,
+            res_used,
+            stop_instant,
+            memory_observed,
+            threads_observed,
+            wall_timeout,
+            killed_at,
+            interrupted,
+            wakeup_lag,
+            verdict
+        } = exit;
+        self.emit(RunEvent::Exited {
+            status: return_value,
+            elapsed: stop_instant.saturating_duration_since(begin_instant),
+            usage: res_used
+        });
+        let output_missing = child.named_output_opened() == Some(false);
+        // Killed by SIGXFSZ, or stopped at the limit if it ignored that
+        let output_exceeded = self.file_limit().is_some_and(|limit| {
+            (libc::WIFSIGNALED(return_value) && libc::WTERMSIG(return_value) == libc::SIGXFSZ)
+                || fs::metadata(&run.stdout).is_ok_and(|m| m.len() > limit)
+        });
+
+        // Don't bill the program for the time it took to kill it, the
+        // termination grace period included, nor for the judger waking up
+        // late to its wall limit
+        let mut duration = killed_at.unwrap_or(stop_instant).saturating_duration_since(begin_instant);
+        if wall_timeout {
+            duration = duration.min(limits.wall);
+        }
+        let memory_used_bytes = cgroup.as_ref()
+            .and_then(|c| c.memory_peak())
+            .unwrap_or(res_used.max_rss_bytes)
+            .max(memory_observed);
+        let oom_killed = cgroup.as_ref().is_some_and(|c| c.oom_killed());
+        let tasks_peak = cgroup.as_ref().and_then(|c| c.pids_peak());
+        let task_limit_hits = cgroup.as_ref().map_or(0, |c| c.pids_limit_hits());
+        let cpu_throttled = match self.cpu_quota {
+            Some(_) => cgroup.as_ref().and_then(|c| c.cpu_throttled()),
+            None => None
+        };
+        let max_threads = match self.policy.allows_threads() {
+            true => tasks_peak.map(|peak| peak.min(u64::from(u32::MAX)) as u32).or(threads_observed),
+            false => None
+        };
+        let cpu_time = res_used.cpu_time();
+        let cpu_time_ms = cpu_time.as_millis() as u64;
+        let io_read_bytes = res_used.read_bytes();
+        let io_written_bytes = res_used.written_bytes();
+        // Files are capped at the I/O limit already, which leaves the reads
+        // and writes spread over many files to be caught here
+        let io_used = cgroup.as_ref()
+            .and_then(|c| c.io_bytes())
+            .map_or(0, |(read, written)| read.saturating_add(written))
+            .max(io_read_bytes.saturating_add(io_written_bytes));
+        let io_limit_exceeded = match self.io_limit {
+            Some(limit) => io_used > limit || (output_exceeded && self.output_limit.is_none_or(|output| limit < output)),
+            None => false
+        };
+
+        let status = if let Some(verdict) = verdict {
+            verdict
+        } else if oom_killed || memory_used_bytes > limits.memory {
             JudgeStatus::MemoryLimitExceeded
-        } else if duration > self.max_allowed_time {
+        } else if cpu_time > limits.cpu {
             JudgeStatus::TimeLimitExceeded
+        } else if wall_timeout || duration > limits.wall {
+            // Ran out of wall time: idle if it mostly wasn't running
+            match cpu_time * 2 < duration {
+                true => JudgeStatus::IdlenessLimitExceeded,
+                false => JudgeStatus::TimeLimitExceeded
+            }
+        } else if output_exceeded || io_limit_exceeded {
+            JudgeStatus::OutputLimitExceeded
         } else if return_value != 0 {
             if libc::WIFSIGNALED(return_value) {
                 match libc::WTERMSIG(return_value) {
@@ -199,46 +2270,1165 @@ impl JudgeSession {
             } else {
                 JudgeStatus::ReturnNonZero(return_value)
             }
+        } else if output_missing {
+            JudgeStatus::OutputMissing
         } else {
-            let std_ans = File::open(&self.standard_ans_file)?;
-            let test_ans = File::open(&tmp_out)?;
-            let result = compare_content(std_ans, test_ans)?;
-            fs::remove_file(&tmp_out)?;
-            result
+            match &check {
+                OutputCheck::Compare(answer) => self.compare(answer, &run.stdout)?,
+                OutputCheck::Reuse(verdict) => (*verdict).clone(),
+                OutputCheck::Forward(_) => JudgeStatus::Accepted,
+                // Made by the program, which may have left a symlink there
+                // instead for the judger to move out
+                OutputCheck::Collect { name, .. } => match fs::symlink_metadata(run.work_dir.join(name)) {
+                    Ok(metadata) if metadata.is_file() => JudgeStatus::Accepted,
+                    _ => JudgeStatus::OutputMissing
+                }
+            }
+        };
+        let difference = match (&status, &check) {
+            (JudgeStatus::WrongAnswer, OutputCheck::Compare(answer)) => self.difference(answer, &run.stdout),
+            _ => None
+        };
+        // Out of wall time with CPU time to spare, while not idle as ILE
+        // would have it, and the judger too waited for a CPU long enough
+        // to account for some of the time the program didn't run: it was
+        // runnable but didn't get a CPU
+        let timing_suspect = matches!(status, JudgeStatus::TimeLimitExceeded)
+            && cpu_time.saturating_mul(5) < limits.cpu.saturating_mul(4)
+            && wakeup_lag.saturating_mul(10) >= duration.saturating_sub(cpu_time);
+        if timing_suspect {
+            debug!(
+                "TLE with {cpu_time_ms}ms of CPU time against a limit of {}ms, the host may have held the program back \
+                 (judger woke up to {wakeup_lag:?} late)",
+                limits.cpu.as_millis()
+            );
+        }
+        // Only a run that got as far as its output is compared
+        if let (OutputCheck::Compare(answer), true) = (&check, status.is_comparison()) {
+            self.emit(RunEvent::Compared {
+                answer: answer.path(),
+                output: &run.stdout,
+                comparison: self.comparison,
+                status: &status,
+                difference: difference.as_deref()
+            });
+        }
+        match &mut check {
+            OutputCheck::Forward(sink) => {
+                io::copy(&mut File::open(&run.stdout)?, sink)?;
+            },
+            OutputCheck::Collect { name, dest } if matches!(status, JudgeStatus::Accepted) => {
+                move_file(&run.work_dir.join(*name), dest).map_err(|e| {
+                    io::Error::new(e.kind(), format!("cannot collect {name} as {}: {e}", dest.display()))
+                })?;
+            },
+            _ => {}
+        }
+        debug!(
+            "{} after {:?}, cpu {} ms, memory {} bytes, exit status {return_value}",
+            status.abbr(),
+            duration,
+            cpu_time_ms,
+            memory_used_bytes
+        );
+        let keep = match self.keep_output {
+            KeepPolicy::Never => false,
+            KeepPolicy::OnFailure => !matches!(status, JudgeStatus::Accepted | JudgeStatus::Cancelled),
+            KeepPolicy::Always => !matches!(status, JudgeStatus::Cancelled)
+        };
+        let (kept_dir, output_path) = match (keep, &self.output_dir) {
+            (false, _) => (None, None),
+            (true, Some(dir)) => {
+                let dest = dir.join(format!("{input_name}.{}.out", status.abbr()));
+                move_file(&run.stdout, &dest).map_err(|e| {
+                    io::Error::new(e.kind(), format!("cannot keep output as {}: {e}", dest.display()))
+                })?;
+                (None, Some(dest))
+            },
+            (true, None) => {
+                run.scratch.keep();
+                (Some(run.scratch.path().to_path_buf()), Some(run.stdout.clone()))
+            }
+        };
+
+        Ok(JudgeResult {
+            status,
+            time_used: duration,
+            judge_overhead,
+            cpu_time_ms,
+            memory_used_bytes,
+            tasks_peak,
+            task_limit_hits,
+            cpu_throttled,
+            max_threads,
+            io_read_bytes,
+            io_written_bytes,
+            io_limit_exceeded,
+            interrupted,
+            stderr,
+            stderr_bytes,
+            stderr_truncated,
+            difference,
+            exec_sha256: run.exec_sha256,
+            prewarmed: run.prewarmed,
+            kept_dir,
+            output_path,
+            retries,
+            limits: Some(limits.applied()),
+            sandbox: Some(AppliedSandbox { strength: run.policy.strength, seccomp: child.seccomp() }),
+            cached: false,
+            resumed: false,
+            environment: Some(JudgeEnvironment::current()),
+            cpu: None,
+            timing_suspect,
+            scheduling_latency: wakeup_lag
+        })
+    }
+
+    /*
+     *  Whether the runs were cancelled or stopped, for which no more are
+     *  started
+     */
+    fn cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|c| c.is_cancelled() || c.is_stopping())
+    }
+
+    fn compare(&self, answer: &AnswerSource, output: &Path) -> io::Result<JudgeStatus> {
+        let _span = log::span("compare");
+        debug!("comparing {} with {answer} ({})", output.display(), self.comparison);
+        let status = match (answer, self.kept_answer(answer)) {
+            (AnswerSource::Bytes(bytes), _) => self.comparison.compare_readers(Cursor::new(bytes), File::open(output)?)?,
+            (_, Some(kept)) => self.comparison.compare_readers(Cursor::new(kept), File::open(output)?)?,
+            (AnswerSource::File(path) | AnswerSource::Cached(_, path), None) => {
+                self.comparison.compare(File::open(path)?, File::open(output)?)?
+            }
+        };
+        trace!("comparison gave {}", status.abbr());
+        Ok(status)
+    }
+
+    /*
+     *  Where the output differs from the answer, None if that can't be
+     *  told as the verdict stands either way
+     */
+    fn difference(&self, answer: &AnswerSource, output: &Path) -> Option<String> {
+        let difference = match (answer, self.kept_answer(answer)) {
+            (AnswerSource::Bytes(bytes), _) => {
+                File::open(output).and_then(|output| self.comparison.difference(Cursor::new(bytes), output))
+            },
+            (_, Some(kept)) => File::open(output).and_then(|output| self.comparison.difference(Cursor::new(kept), output)),
+            (AnswerSource::File(path) | AnswerSource::Cached(_, path), None) => {
+                File::open(path).and_then(|answer| self.comparison.difference(answer, File::open(output)?))
+            }
         };
+        match difference {
+            Ok(difference) => difference,
+            Err(e) => {
+                debug!("cannot tell where {} differs from {answer}: {e}", output.display());
+                None
+            }
+        }
+    }
 
-        Ok(JudgeResult { status, time_used: duration, cpu_time_ms, memory_used_bytes })
+    /*
+     *  The answer from its own cache or the session's, None without one,
+     *  for an answer the cache doesn't keep, to be read from its file, or
+     *  for one in memory already. Trouble reading it there is left to
+     *  reading the file.
+     */
+    fn kept_answer(&self, answer: &AnswerSource) -> Option<Arc<[u8]>> {
+        let (answers, path) = match answer {
+            AnswerSource::File(path) => (self.answers.as_ref()?, path),
+            AnswerSource::Cached(answers, path) => (answers, path),
+            AnswerSource::Bytes(_) => return None
+        };
+        match answers.get(path) {
+            Ok(kept) => kept,
+            Err(e) => {
+                debug!("cannot keep the answer {} in memory: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    /*
+     *  Stop a child that ran out of time: send the termination signal,
+     *  give it the grace period to exit on its own, then kill it
+     */
+    async fn terminate<W: Waiter>(&self, waiter: W, child: &mut dyn SandboxChildHandle, reason: KillReason) -> Result<i32, String> {
+        let TerminationPolicy { term_signal, grace_period, kill_signal } = self.termination;
+        if let Some(signal) = term_signal {
+            debug!("sending signal {signal} to {}", child.pid());
+            self.signalled(child, signal, reason);
+            let _ = child.kill(signal);
+            // A stopped child can't act on the signal, so don't wait for it then
+            match waiter.wait(child, Some(grace_period)).await {
+                Ok(WaitOutcome::Exited(status)) => {
+                    METRICS.count_kill();
+                    return Ok(status);
+                },
+                Ok(_) => {},
+                Err(e) => return Err(wait_error(child.pid(), e))
+            }
+        }
+        // Without a termination signal the kill is the first signal it gets
+        let reason = match term_signal {
+            Some(_) => KillReason::GracePeriodOver,
+            None => reason
+        };
+        debug!("{} still running after {grace_period:?}, killing it", child.pid());
+        if kill_signal != libc::SIGKILL {
+            self.signalled(child, kill_signal, reason);
+            let _ = child.kill(kill_signal);
+            if let Ok(WaitOutcome::Exited(status)) = waiter.wait(child, Some(grace_period)).await {
+                METRICS.count_kill();
+                return Ok(status);
+            }
+        }
+        self.kill(waiter, child, reason).await
+    }
+
+    /*
+     *  Kill the child for `reason` and reap it
+     */
+    async fn kill<W: Waiter>(&self, waiter: W, child: &mut dyn SandboxChildHandle, reason: KillReason) -> Result<i32, String> {
+        self.signalled(child, libc::SIGKILL, reason);
+        kill_and_wait(waiter, child).await
+    }
+
+    fn signalled(&self, child: &dyn SandboxChildHandle, signal: i32, reason: KillReason) {
+        self.emit(RunEvent::Signalled { signal, reason, elapsed: child.start_instant().elapsed() });
+    }
+
+    /*
+     *  Tell the observer about a step of the run
+     */
+    fn emit(&self, event: RunEvent) {
+        if let Some(observer) = &self.observer {
+            if let Err(msg) = call_observer(|| observer.on_event(&event)) {
+                warn!("{msg}");
+            }
+        }
+    }
+
+    /*
+     *  Set up the per-run cgroup if configured, falling back to rusage
+     *  based accounting when that isn't possible
+     */
+    fn create_cgroup(&self, memory_max: u64) -> Option<RunCgroup> {
+        let root = self.cgroup_root.as_ref()?;
+        let limits = CgroupLimits {
+            memory_max,
+            pids_max: self.max_tasks,
+            cpu_cores: self.cpu_quota,
+            io: self.io_limit.is_some()
+        };
+        match RunCgroup::create(root, &limits) {
+            Ok(c) => {
+                debug!("run cgroup created under {}", root.display());
+                Some(c)
+            },
+            Err(e) => {
+                warn!("cannot use cgroup under {} ({e}), falling back to rusage memory accounting", root.display());
+                None
+            }
+        }
+    }
+
+    /*
+     *  Wait for the child to exit, killing it once the time limit is reached
+     *  or when it gets stopped, and reap it. The verdict is decided here
+     *  already if the way the child ended demands it.
+     *  Failures are reported as a message for a SystemError verdict.
+     */
+    async fn wait_child<W: Waiter>(
+        &self,
+        waiter: W,
+        child: &mut dyn SandboxChildHandle,
+        cgroup: Option<&RunCgroup>,
+        limits: &RunLimits
+    ) -> Result<ChildExit, String> {
+        // Live memory and CPU time sampling, coarse enough to keep its
+        // overhead negligible
+        const SAMPLE_INTERVAL: Duration = Duration::from_millis(50);
+        // Runs this short can't get far past their limits before being
+        // stopped anyway, exceeding them is caught after the run
+        const SAMPLE_MIN_TIME: Duration = Duration::from_millis(200);
+        // How soon a cancelled run notices
+        const CANCEL_INTERVAL: Duration = Duration::from_millis(20);
+
+        let _span = waiter.span("wait");
+        let pid = child.pid();
+        let begin_instant = child.start_instant();
+        let timeout = match limits.wall {
+            Duration::MAX => None,
+            t => Some(t)
+        };
+        let sample = limits.cpu.min(limits.wall) >= SAMPLE_MIN_TIME;
+        // A cgroup counts them itself, and without threads there is one
+        let count_threads = cgroup.is_none() && self.policy.allows_threads();
+        let mut memory_observed: u64 = 0;
+        let mut threads_observed: Option<u32> = None;
+        let mut wall_timeout = false;
+        // Once stopped, when the program's grace period ends
+        let mut stop_deadline: Option<Instant> = None;
+        let mut verdict = None;
+        let mut last_tick = begin_instant;
+        // Most a wait that timed out overran its timeout by, the judger
+        // having waited that long for a CPU to wake up on
+        let mut wakeup_lag = Duration::ZERO;
+        // When the last wait returned, which is when the judger went to
+        // kill the child if it did
+        let mut woke;
+        let return_value = loop {
+            let remaining = timeout.map(|t| t.saturating_sub(begin_instant.elapsed()));
+            // Wake up for the batch deadline too
+            let remaining = match limits.deadline.map(|d| d.saturating_duration_since(Instant::now())) {
+                Some(left) => Some(remaining.map_or(left, |r| r.min(left))),
+                None => remaining
+            };
+            let interval = [
+                sample.then_some(SAMPLE_INTERVAL),
+                (self.cancel.is_some() || limits.cancel.is_some()).then_some(CANCEL_INTERVAL),
+                self.observer.is_some().then_some(self.tick_interval)
+            ].into_iter().flatten().min();
+            let wait_time = match interval {
+                Some(interval) => Some(remaining.map_or(interval, |r| r.min(interval))),
+                None => remaining
+            };
+            let waited = Instant::now();
+            let outcome = waiter.wait(child, wait_time).await.map_err(|e| wait_error(pid, e))?;
+            woke = Instant::now();
+            if let (WaitOutcome::Timeout, Some(wait_time)) = (&outcome, wait_time) {
+                wakeup_lag = wakeup_lag.max(waited.elapsed().saturating_sub(wait_time));
+            }
+            match outcome {
+                WaitOutcome::Exited(status) => break status,
+                WaitOutcome::Stopped => match self.policy.on_stop {
+                    StopAction::Resume => {
+                        debug!("child {pid} stopped, resuming it");
+                        let _ = child.kill(libc::SIGCONT);
+                    },
+                    StopAction::ReportIdleness => {
+                        debug!("child {pid} stopped, killing it as idle");
+                        verdict = Some(JudgeStatus::IdlenessLimitExceeded);
+                        break self.kill(waiter, child, KillReason::Idle).await?;
+                    },
+                    StopAction::ReportViolation => {
+                        debug!("child {pid} stopped, killing it for a security violation");
+                        verdict = Some(JudgeStatus::SecurityViolation);
+                        break self.kill(waiter, child, KillReason::SecurityViolation).await?;
+                    }
+                },
+                WaitOutcome::Timeout => {
+                    if stop_deadline.is_none() && self.cancel.as_ref().is_some_and(|c| c.is_stopping()) {
+                        let grace = self.termination.grace_period;
+                        debug!("judging stopped, sending SIGTERM to {pid} and giving it {grace:?} to exit");
+                        self.signalled(child, libc::SIGTERM, KillReason::Stopping);
+                        let _ = child.kill(libc::SIGTERM);
+                        stop_deadline = Some(Instant::now() + grace);
+                    }
+                    let cancelled = [self.cancel.as_ref(), limits.cancel.as_ref()].into_iter().flatten().any(|c| c.is_cancelled());
+                    let past_deadline = [limits.deadline, stop_deadline].into_iter().flatten().any(|d| Instant::now() >= d);
+                    if cancelled || past_deadline {
+                        debug!("run cancelled, killing child {pid}");
+                        verdict = Some(JudgeStatus::Cancelled);
+                        break self.kill(waiter, child, KillReason::Cancelled).await?;
+                    }
+                    let mut rss = None;
+                    if sample {
+                        let current = match cgroup {
+                            Some(c) => c.memory_current(),
+                            None => child.resident_memory()
+                        };
+                        rss = current;
+                        memory_observed = memory_observed.max(current.unwrap_or(0));
+                        if count_threads {
+                            // None sorts first, so samples that failed don't count
+                            threads_observed = threads_observed.max(child.thread_count());
+                        }
+                        if memory_observed > limits.memory {
+                            let limit = limits.memory;
+                            debug!("memory {memory_observed} bytes over the limit of {limit}, killing child {pid}");
+                            verdict = Some(JudgeStatus::MemoryLimitExceeded);
+                            break self.kill(waiter, child, KillReason::Memory).await?;
+                        }
+                        let cpu_used = match cgroup {
+                            Some(c) => c.cpu_usage(),
+                            None => child.cpu_time()
+                        };
+                        trace!("sampled memory {current:?} bytes, cpu {cpu_used:?}");
+                        if let Some(used) = cpu_used.filter(|&t| t > limits.cpu) {
+                            debug!("cpu time {used:?} over the limit of {:?}, stopping child {pid}", limits.cpu);
+                            verdict = Some(JudgeStatus::TimeLimitExceeded);
+                            break self.terminate(waiter, child, KillReason::CpuTime).await?;
+                        }
+                        // Only a cgroup counts the I/O of a running program
+                        let io_used = cgroup.and_then(|c| c.io_bytes()).map(|(read, written)| read.saturating_add(written));
+                        if let (Some(used), Some(limit)) = (io_used, self.io_limit) {
+                            if used > limit {
+                                debug!("disk I/O of {used} bytes over the limit of {limit}, killing child {pid}");
+                                verdict = Some(JudgeStatus::OutputLimitExceeded);
+                                break self.kill(waiter, child, KillReason::DiskIo).await?;
+                            }
+                        }
+                    }
+                    if let Some(observer) = self.observer.as_ref().filter(|_| last_tick.elapsed() >= self.tick_interval) {
+                        last_tick = Instant::now();
+                        if let Err(msg) = call_observer(|| observer.on_tick(begin_instant.elapsed(), rss)) {
+                            verdict = Some(JudgeStatus::SystemError(msg));
+                            break self.kill(waiter, child, KillReason::ObserverFailed).await?;
+                        }
+                    }
+                    if timeout.is_some_and(|t| begin_instant.elapsed() >= t) {
+                        // TLE or ILE, depending on the CPU time it used
+                        debug!("wall time limit of {:?} reached, stopping child {pid}", limits.wall);
+                        wall_timeout = true;
+                        break self.terminate(waiter, child, KillReason::WallTime).await?;
+                    }
+                }
+            }
+        };
+        // Record time as soon as the tested program exits
+        // Making result more percise.
+        let stop_instant = Instant::now();
+
+        match child.rusage() {
+            Some(&res_used) => Ok(ChildExit {
+                return_value,
+                res_used,
+                stop_instant,
+                memory_observed,
+                threads_observed,
+                wall_timeout,
+                // Every kill but for the wall time decides the verdict
+                killed_at: (wall_timeout || verdict.is_some()).then_some(woke),
+                interrupted: stop_deadline.is_some(),
+                wakeup_lag,
+                verdict
+            }),
+            None => Err(format!("no resource usage for child {pid} after reaping it"))
+        }
     }
 }
 
+/// Configures a JudgeSession, checking the whole configuration once it is
+/// built. Without an input the program reads nothing, and without an
+/// answer the session can only judge test cases given to run_case.
+pub struct JudgeSessionBuilder {
+    session: JudgeSession,
+    // WALL_LIMIT_FACTOR times the CPU limit if not set
+    wall_limit: Option<Duration>,
+    // Those of the script's language if not set
+    multipliers: Option<LimitMultipliers>,
+    validator: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    // Made the session's one observer when there are several
+    observers: Vec<Arc<dyn JudgeObserver>>
+}
+
+impl JudgeSessionBuilder {
+    /// Where the program reads its input from, /dev/null by default
+    pub fn input(mut self, input: InputSource) -> Self {
+        self.session.input = Mutex::new(Some(input));
+        self
+    }
+
+    /// The answer file the output is judged against
+    pub fn answer(self, answer: PathBuf) -> Self {
+        self.answer_source(AnswerSource::File(answer))
+    }
+
+    /// The answer the output is judged against, which may be given in
+    /// memory rather than as a file
+    pub fn answer_source(mut self, answer: AnswerSource) -> Self {
+        self.session.standard_ans_file = Some(answer);
+        self
+    }
+
+    /// CPU time limit, counting all threads and descendants of the program
+    pub fn time_limit(mut self, limit: Duration) -> Self {
+        self.session.cpu_limit = limit;
+        self
+    }
+
+    /// Wall time limit, for programs that wait instead of running
+    pub fn wall_time_limit(mut self, limit: Duration) -> Self {
+        self.wall_limit = Some(limit);
+        self
+    }
+
+    /// Limit of the program's resident memory
+    pub fn memory_limit(mut self, bytes: u64) -> Self {
+        self.session.max_allowed_memory_bytes = bytes;
+        self
+    }
+
+    /// Largest output the program may write, which goes for any other file
+    /// it writes as well. Going over it is Output Limit Exceeded.
+    pub fn output_limit(mut self, bytes: u64) -> Self {
+        self.session.output_limit = Some(bytes);
+        self
+    }
+
+    /// Most the program may read from and write to disk together, counted
+    /// in the blocks rusage reports, and in the cgroup's io.stat as it
+    /// runs when judged in a cgroup with the io controller. Every file it
+    /// writes is capped at it as by the output limit. Going over it is
+    /// Output Limit Exceeded.
+    pub fn io_limit(mut self, bytes: u64) -> Self {
+        self.session.io_limit = Some(bytes);
+        self
+    }
+
+    /// How the output is checked against the answer, exactly by default
+    pub fn comparison(mut self, comparison: Comparison) -> Self {
+        self.session.comparison = comparison;
+        self
+    }
+
+    /// Replaces the whole policy, so exec_arch and scratch_limit have to
+    /// come after it
+    pub fn policy(mut self, policy: SandboxPolicy) -> Self {
+        self.session.policy = policy;
+        self
+    }
+
+    /// Start the runs, the validator's too, with `sandbox` instead of the
+    /// LinuxSandbox, such as a MockSandbox in tests
+    pub fn sandbox(mut self, sandbox: Arc<dyn Sandbox>) -> Self {
+        self.session.sandbox = sandbox;
+        self
+    }
+
+    /// Account and enforce memory with a per-run cgroup v2 created under `root`
+    pub fn cgroup_root(mut self, root: PathBuf) -> Self {
+        self.session.cgroup_root = Some(root);
+        self
+    }
+
+    /// Cap on the number of tasks alive at once, enforced when judging in a cgroup
+    pub fn max_tasks(mut self, max_tasks: u64) -> Self {
+        self.session.max_tasks = max_tasks;
+        self
+    }
+
+    /// Throttle the program to this many cores' worth of CPU time, e.g.
+    /// 1.0 for one full core. Needs a cgroup root.
+    pub fn cpu_quota(mut self, cores: f64) -> Self {
+        self.session.cpu_quota = Some(cores);
+        self
+    }
+
+    /// Sandbox the program as `arch` instead of going by its ELF header
+    pub fn exec_arch(mut self, arch: ExecArch) -> Self {
+        self.session.policy.exec_arch = Some(arch);
+        self
+    }
+
+    /// Whether runs fail without the seccomp filter, or go on without it
+    pub fn sandbox_strength(mut self, strength: SandboxStrength) -> Self {
+        self.session.policy.strength = strength;
+        self
+    }
+
+    /// Run the program in `namespace` instead of the judger's namespace of
+    /// its kind, see SandboxPolicy::join_namespaces
+    pub fn join_namespace(mut self, namespace: JoinNamespace) -> Self {
+        self.session.policy.join_namespaces.push(namespace);
+        self
+    }
+
+    /// Set the variable `name` to `value` in the program's environment,
+    /// over the judger's and TZ=UTC, LANG=C and LC_ALL=C, see
+    /// SandboxPolicy::env
+    pub fn env(mut self, name: String, value: String) -> Self {
+        self.session.policy.env.push((name, value));
+        self
+    }
+
+    /// Most of what the program writes to stderr that is kept, 1MiB by
+    /// default. The rest is counted and dropped, see
+    /// SandboxPolicy::stderr_limit.
+    pub fn stderr_limit(mut self, bytes: u64) -> Self {
+        self.session.policy.stderr_limit = bytes;
+        self
+    }
+
+    /// Size limit of the private /tmp the program gets when mount
+    /// namespaces are available
+    pub fn scratch_limit(mut self, bytes: u64) -> Self {
+        self.session.policy.scratch_limit = bytes;
+        self
+    }
+
+    /// How the program gets its input and hands in its output
+    pub fn io_mode(mut self, io_mode: IoMode) -> Self {
+        self.session.io_mode = io_mode;
+        self
+    }
+
+    /// Copy the file at `source` into the working directory of every run as
+    /// `name`, read-only, for data the problem gives such as a dictionary.
+    /// The name has to be a plain file name no other file there has.
+    pub fn provide_file(mut self, source: PathBuf, name: String) -> Self {
+        self.session.provide_files.push((source, name));
+        self
+    }
+
+    /// Run a private copy of the executable made in the scratch directory
+    /// (the default), or the original in place
+    pub fn copy_exec(mut self, copy_exec: bool) -> Self {
+        self.session.copy_exec = copy_exec;
+        self
+    }
+
+    /// Read the executable, and the interpreter of a script, into the page
+    /// cache before each run, so that the first run off a cold disk isn't
+    /// timed loading it. Off by default. JudgeResult::prewarmed says
+    /// whether it worked, as it needs the files to be readable.
+    pub fn prewarm(mut self, prewarm: bool) -> Self {
+        self.session.prewarm = prewarm;
+        self
+    }
+
+    /// Put the per-run scratch directories under `dir` instead of the
+    /// system temp dir, e.g. a dedicated volume when /tmp is small
+    pub fn scratch_dir(mut self, dir: PathBuf) -> Self {
+        self.session.scratch_base = Some(dir);
+        self
+    }
+
+    /// Start the names of the runs' scratch directories with `prefix`
+    /// instead of secure-judger, such as the id of the submission judged,
+    /// to tell whose a kept directory is. It has to be a file name.
+    pub fn scratch_prefix(mut self, prefix: String) -> Self {
+        self.session.scratch_prefix = Some(prefix);
+        self
+    }
+
+    /// Keep the program's output of the runs `keep_output` picks. It is
+    /// moved to the output directory if one is set, otherwise the run's
+    /// whole scratch directory is left in place.
+    pub fn keep_output(mut self, keep_output: KeepPolicy) -> Self {
+        self.session.keep_output = keep_output;
+        self
+    }
+
+    /// Move kept output to `dir` as INPUT.VERDICT.out, INPUT being the
+    /// input file name without extension or "stdin" if it isn't a file.
+    /// What is at that name already is replaced, a symlink there included,
+    /// and never written through.
+    pub fn output_dir(mut self, dir: PathBuf) -> Self {
+        self.session.output_dir = Some(dir);
+        self
+    }
+
+    /// Try starting a run again on transient failures as `retry` says
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.session.retry = retry;
+        self
+    }
+
+    /// Scales every limit, including those of test cases. Scripts get
+    /// the multipliers of their interpreter's language by default.
+    pub fn limit_multipliers(mut self, multipliers: LimitMultipliers) -> Self {
+        self.multipliers = Some(multipliers);
+        self
+    }
+
+    /// Check the paths of the program, its interpreter and the validator
+    /// before they run, refusing them where another user could have put
+    /// something else there. Off by default.
+    pub fn strict_paths(mut self, strict: StrictPaths) -> Self {
+        self.session.strict_paths = Some(strict);
+        self
+    }
+
+    /// Run scripts with the interpreter at `path`, whatever their `#!`
+    /// line says, and programs not ELF files even without one. Either way
+    /// they run as `path SCRIPT ARGS...`.
+    pub fn interpreter(mut self, path: PathBuf) -> Self {
+        self.session.interpreter = Some(path);
+        self
+    }
+
+    /// Cancel the session's runs through `handle`
+    pub fn cancel_handle(mut self, handle: JudgeHandle) -> Self {
+        self.session.cancel = Some(handle);
+        self
+    }
+
+    /// Check every input file with the validator at `path` before the
+    /// program runs on it. Invalid test data is a System Error, for which
+    /// the program isn't started.
+    pub fn validator(mut self, path: PathBuf) -> Self {
+        self.validator = Some(path);
+        self
+    }
+
+    /// Keep the results of runs in `dir` and answer the same run from
+    /// there next time: the same executable, input, answer and limits.
+    /// Runs on streamed input or files over cache::MAX_HASHED_SIZE happen
+    /// uncached.
+    pub fn cache_dir(mut self, dir: PathBuf) -> Self {
+        self.cache_dir = Some(dir);
+        self
+    }
+
+    /// Compare with the answers kept in `answers`, shared with the other
+    /// sessions given it, rather than reading each answer from its file
+    /// for every run. Answers over cache::MAX_KEPT_ANSWER are still read
+    /// from their files.
+    pub fn answer_cache(mut self, answers: Arc<AnswerCache>) -> Self {
+        self.session.answers = Some(answers);
+        self
+    }
+
+    /// Tell `observer` about every run as it goes, along with the
+    /// observers given before
+    pub fn observer(mut self, observer: Arc<dyn JudgeObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// How often the observer's on_tick is called
+    pub fn tick_interval(mut self, interval: Duration) -> Self {
+        self.session.tick_interval = interval;
+        self
+    }
+
+    /// How a program out of time is stopped
+    pub fn termination(mut self, termination: TerminationPolicy) -> Self {
+        self.session.termination = termination;
+        self
+    }
+
+    /**
+     *  Check the configuration and make the session. Files have to exist
+     *  now, limits have to be positive.
+     */
+    pub fn build(mut self) -> io::Result<JudgeSession> {
+        let invalid = |what: String| io::Error::new(io::ErrorKind::InvalidInput, what);
+        let session = &mut self.session;
+        if let Some(root) = session.strict_paths.as_ref().and_then(|strict| strict.root.as_ref()) {
+            if !root.is_dir() {
+                return Err(invalid(format!("strict paths root {}: not a directory", root.display())));
+            }
+        }
+        let mut names: Vec<&str> = match &session.io_mode {
+            IoMode::Standard => Vec::new(),
+            IoMode::NamedFiles { input_name, output_name } => vec![input_name, output_name]
+        };
+        if let Some(prefix) = session.scratch_prefix.as_deref().filter(|prefix| !utils::is_file_name(prefix)) {
+            return Err(invalid(format!("scratch prefix {prefix:?}: not a file name")));
+        }
+        for (_, name) in &session.provide_files {
+            if !utils::is_file_name(name) {
+                return Err(invalid(format!("provided file {name:?}: not a file name in the working directory")));
+            }
+            if names.contains(&name.as_str()) {
+                return Err(invalid(format!("provided file {name:?}: the working directory has a file of that name already")));
+            }
+            names.push(name);
+        }
+        session.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let language = session.adopt_language().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        session.multipliers = self.multipliers.or(language.map(|lang| lang.limit_multipliers)).unwrap_or_default();
+        if let Some(calibration) = calibration::installed() {
+            session.multipliers.time *= calibration.multiplier;
+        }
+        session.wall_limit = self.wall_limit.unwrap_or(session.cpu_limit.saturating_mul(WALL_LIMIT_FACTOR));
+        if session.cpu_limit.is_zero() || session.wall_limit.is_zero() {
+            return Err(invalid(String::from("time limits must be positive")));
+        }
+        if !session.multipliers.is_valid() {
+            return Err(invalid(String::from("limit multipliers must be positive")));
+        }
+        if session.retry.max_attempts == 0 {
+            return Err(invalid(String::from("a run needs at least one attempt")));
+        }
+        if session.tick_interval.is_zero() {
+            return Err(invalid(String::from("tick interval must be positive")));
+        }
+        if session.max_allowed_memory_bytes == 0 {
+            return Err(invalid(String::from("memory limit must be positive")));
+        }
+        if session.output_limit == Some(0) {
+            return Err(invalid(String::from("output limit must be positive")));
+        }
+        if session.io_limit == Some(0) {
+            return Err(invalid(String::from("I/O limit must be positive")));
+        }
+        if session.max_tasks == 0 {
+            return Err(invalid(String::from("task limit must be positive")));
+        }
+        if session.policy.scratch_limit == 0 {
+            return Err(invalid(String::from("scratch limit must be positive")));
+        }
+        for (name, value) in &session.policy.env {
+            if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+                return Err(invalid(format!("environment variable {name:?}: not a name and value")));
+            }
+            if name == "TMPDIR" {
+                return Err(invalid(String::from("TMPDIR can't be set, it names the program's /tmp")));
+            }
+        }
+        match session.cpu_quota {
+            Some(cores) if !(cores > 0.0 && cores.is_finite()) => {
+                return Err(invalid(format!("CPU quota {cores} must be a positive number of cores")));
+            },
+            Some(_) if session.cgroup_root.is_none() => {
+                return Err(invalid(String::from("a CPU quota needs a cgroup root to be enforced")));
+            },
+            _ => {}
+        }
+        if let Some(dir) = &session.output_dir {
+            if !dir.is_dir() {
+                return Err(invalid(format!("output directory {}: not a directory", dir.display())));
+            }
+        }
+        if let Some(path) = self.validator {
+            // Sandboxed and limited like the program, but not scaled for its
+            // language nor set up for it
+            let mut validator = JudgeSession::defaults(path);
+            validator.cpu_limit = session.cpu_limit;
+            validator.wall_limit = session.wall_limit;
+            validator.max_allowed_memory_bytes = session.max_allowed_memory_bytes;
+            validator.cgroup_root = session.cgroup_root.clone();
+            validator.max_tasks = session.max_tasks;
+            validator.termination = session.termination;
+            validator.scratch_base = session.scratch_base.clone();
+            validator.retry = session.retry;
+            validator.cancel = session.cancel.clone();
+            validator.sandbox = session.sandbox.clone();
+            validator.strict_paths = session.strict_paths.clone();
+            validator.validate_exec(&validator.exec).map_err(|e| invalid(format!("validator: {e}")))?;
+            validator.check_paths(&validator.exec).map_err(|e| invalid(format!("validator: {e}")))?;
+            validator.adopt_language().map_err(|e| invalid(format!("validator: {e}")))?;
+            session.validator = Some(Box::new(Validator { session: validator, checked: Mutex::new(HashMap::new()) }));
+        }
+        if let Some(dir) = self.cache_dir {
+            session.cache = Some(ResultCache::open(dir)?);
+        }
+        session.observer = match self.observers.len() {
+            0 | 1 => self.observers.pop(),
+            _ => Some(Arc::new(Observers(self.observers)))
+        };
+        Ok(self.session)
+    }
+}
+
+/*
+ *  Check that the interpreter at `path` is an ELF executable that can
+ *  run here, which is then what runs. Another script would run under an
+ *  interpreter of its own.
+ */
+fn check_interpreter(path: &Path, exec_arch: Option<ExecArch>) -> Result<(), JudgeError> {
+    match elf::inspect(path) {
+        Ok(Some(info)) => check_elf(path, &info, exec_arch),
+        Ok(None) => Err(JudgeError::ScriptInterpreter(path.to_path_buf())),
+        Err(error) => Err(JudgeError::Inaccessible { what: "interpreter", path: path.to_path_buf(), error })
+    }
+}
+
+/*
+ *  Check that the ELF file at `path` is an executable for `exec_arch`,
+ *  or without one for the host, where 32-bit x86 runs on x86_64 as well.
+ *  A loader that isn't there only gets a warning, in case it shows up by
+ *  the time the program runs.
+ */
+fn check_elf(path: &Path, info: &ElfInfo, exec_arch: Option<ExecArch>) -> Result<(), JudgeError> {
+    if !info.executable {
+        return Err(JudgeError::NotExecutableFormat(path.to_path_buf()));
+    }
+    // The sandbox refuses to run at all on other hosts
+    let Some(native) = ExecArch::native() else {
+        return Ok(());
+    };
+    let runs = match (info.arch, exec_arch) {
+        (Some(found), Some(expected)) => found == expected,
+        (Some(found), None) => found == native || (native == ExecArch::X86_64 && found == ExecArch::I386),
+        (None, _) => false
+    };
+    if !runs {
+        return Err(JudgeError::ArchMismatch {
+            exec: path.to_path_buf(),
+            expected: exec_arch.unwrap_or(native),
+            found: info.arch_name()
+        });
+    }
+    if let Some(loader) = info.interpreter.as_ref().filter(|loader| !loader.exists()) {
+        warn!("{} is dynamically linked against {}, which doesn't exist here", path.display(), loader.display());
+    }
+    Ok(())
+}
+
+/*
+ *  Another copy of `input` for trying a run again, None for a stream
+ */
+fn replay_input(input: &InputSource) -> Option<InputSource> {
+    match input {
+        InputSource::File(path) => Some(InputSource::File(path.clone())),
+        InputSource::Bytes(bytes) => Some(InputSource::Bytes(bytes.clone())),
+        InputSource::Reader(_) => None,
+        InputSource::Null => Some(InputSource::Null)
+    }
+}
+
+/*
+ *  Check that the `what` file at `path` is a regular file the judger can read
+ */
+fn check_file(what: &'static str, path: &Path) -> Result<(), JudgeError> {
+    let inaccessible = |error| JudgeError::Inaccessible { what, path: path.to_path_buf(), error };
+    if !fs::metadata(path).map_err(inaccessible)?.is_file() {
+        return Err(JudgeError::NotRegularFile { what, path: path.to_path_buf() });
+    }
+    File::open(path).map_err(inaccessible)?;
+    Ok(())
+}
+
+/// What a run does with the program's output once it finished
+enum OutputCheck<'a, S: ?Sized = dyn Write> {
+    // Compare it with the answer
+    Compare(&'a AnswerSource),
+    // It is the same as an earlier run's, which got this verdict
+    Reuse(&'a JudgeStatus),
+    // Pass it on without judging it
+    Forward(&'a mut S),
+    // Move the file `name` the program made in its working directory to
+    // `dest`, without judging the output
+    Collect { name: &'a str, dest: &'a Path }
+}
+
+/// Per-run state of a session: the scratch directory with the paths in it
+/// and the policy with the run's limits applied
+struct RunState {
+    scratch: ScratchDir,
+    stdout: PathBuf,
+    stderr: PathBuf,
+    work_dir: PathBuf,
+    // What gets executed, the private copy unless copying is off
+    exec: PathBuf,
+    exec_sha256: Option<String>,
+    // Whether exec and the interpreter were read into the page cache
+    prewarmed: bool,
+    // What runs it if it is a script, for the judger to start instead
+    interpreter: Option<Interpreter>,
+    policy: SandboxPolicy
+}
+
+// A started run, with the cgroup it runs in and the child running it
+type LaunchedRun = (RunState, Option<RunCgroup>, Box<dyn SandboxChildHandle>);
+
+/// Limits of a single run
+#[derive(Clone)]
+struct RunLimits {
+    cpu: Duration,
+    wall: Duration,
+    memory: u64,
+    // What cpu, wall and memory were scaled from
+    base: Limits,
+    // When the batch the run belongs to has to be done
+    deadline: Option<Instant>,
+    // Cancelled once the batch has no more use for the run
+    cancel: Option<JudgeHandle>
+}
+
+impl RunLimits {
+    fn applied(&self) -> AppliedLimits {
+        AppliedLimits {
+            base: self.base,
+            effective: Limits { cpu: self.cpu, wall: self.wall, memory_bytes: self.memory }
+        }
+    }
+}
+
+struct ChildExit {
+    return_value: i32,
+    res_used: ResourceUsage,
+    stop_instant: Instant,
+    // Highest memory usage seen while sampling the running child
+    memory_observed: u64,
+    // Most threads seen while sampling, when they were counted
+    threads_observed: Option<u32>,
+    // Killed for running out of wall time
+    wall_timeout: bool,
+    // When the judger went to kill the child, if it did
+    killed_at: Option<Instant>,
+    // Sent SIGTERM as judging was stopped
+    interrupted: bool,
+    // Most the wait loop woke up late by
+    wakeup_lag: Duration,
+    // Verdict already decided while waiting, overriding the usual checks
+    verdict: Option<JudgeStatus>
+}
+
+/*
+ *  Collect the captured stderr of the program
+ */
+fn read_stderr(path: &Path) -> Vec<u8> {
+    // Only keep as much as is useful for diagnostics
+    const STDERR_KEEP: u64 = 64 * 1024;
+
+    let mut stderr = Vec::new();
+    if let Ok(f) = File::open(path) {
+        let _ = f.take(STDERR_KEEP).read_to_end(&mut stderr);
+    }
+    stderr
+}
+
+/*
+ *  SIGKILL the child and wait for it to die, giving up if it doesn't
+ */
+async fn kill_and_wait<W: Waiter>(waiter: W, child: &mut dyn SandboxChildHandle) -> Result<i32, String> {
+    // How long a SIGKILLed child may take to actually die before we give up on it
+    const KILL_GRACE: Duration = Duration::from_secs(1);
+
+    let _ = child.kill(libc::SIGKILL);
+    METRICS.count_kill();
+    let begin = Instant::now();
+    loop {
+        let remaining = KILL_GRACE.saturating_sub(begin.elapsed());
+        match waiter.wait(child, Some(remaining)).await {
+            Ok(WaitOutcome::Exited(status)) => return Ok(status),
+            // Still reported as stopped until SIGKILL takes effect
+            Ok(WaitOutcome::Stopped) if !remaining.is_zero() => waiter.pause().await,
+            Ok(_) => return Err(format!(
+                "child {} did not die within {}ms after SIGKILL, \
+                possibly stuck in uninterruptible sleep",
+                child.pid(),
+                KILL_GRACE.as_millis()
+            )),
+            Err(e) => return Err(wait_error(child.pid(), e))
+        }
+    }
+}
 
 /*
- *  Judge output files and give a result among AC, PE and WA
+ *  How a run waits for its program: blocking the thread judging it, or
+ *  as a future that something else wakes
  */
-fn compare_content(mut content1: File, mut content2: File) -> io::Result<JudgeStatus> {
-    content1.seek(SeekFrom::Start(0))?;
-    content2.seek(SeekFrom::Start(0))?;
-    let cf1 = BufReader::new(&content1);
-    let cf2 = BufReader::new(&content2);
-    match cf1.bytes().map(|ch| ch.unwrap_or_default()).eq(cf2.bytes().map(|ch| ch.unwrap_or_default())) {
-        true => Ok(JudgeStatus::Accepted),
-        false => {
-            content1.seek(SeekFrom::Start(0))?;
-            content2.seek(SeekFrom::Start(0))?;
-            let cf1 = BufReader::new(&content1);
-            let cf2 = BufReader::new(&content2);
-            let processed_content1 = cf1.bytes()
-                .map(|ch| ch.unwrap_or_default())
-                .filter(|ch| !ch.is_ascii_whitespace())
-                .map(|ch| ch.to_ascii_uppercase());
-            let processed_content2 = cf2.bytes()
-                .map(|ch| ch.unwrap_or_default())
-                .filter(|ch| !ch.is_ascii_whitespace())
-                .map(|ch| ch.to_ascii_uppercase());
-            Ok(match processed_content1.eq(processed_content2) {
-                true    => JudgeStatus::PresentationError,
-                false   => JudgeStatus::WrongAnswer
-            })
-        }
-    }
-}
\ No newline at end of file
+trait Waiter: Copy {
+    // Holds a log span open until dropped
+    type Span;
+
+    fn span(self, name: &'static str) -> Self::Span;
+
+    fn wait(
+        self,
+        child: &mut dyn SandboxChildHandle,
+        timeout: Option<Duration>
+    ) -> impl Future<Output = io::Result<WaitOutcome>> + Send;
+
+    fn sleep(self, duration: Duration) -> impl Future<Output = ()> + Send;
+
+    // Let others run for a moment before trying again
+    fn pause(self) -> impl Future<Output = ()> + Send;
+}
+
+/// Waits in SandboxChildHandle::wait, so that its futures are done when
+/// first polled
+#[derive(Clone, Copy)]
+struct Blocking;
+
+impl Waiter for Blocking {
+    type Span = log::SpanGuard;
+
+    fn span(self, name: &'static str) -> log::SpanGuard {
+        log::span(name)
+    }
+
+    async fn wait(self, child: &mut dyn SandboxChildHandle, timeout: Option<Duration>) -> io::Result<WaitOutcome> {
+        child.wait(timeout)
+    }
+
+    async fn sleep(self, duration: Duration) {
+        thread::sleep(duration);
+    }
+
+    async fn pause(self) {
+        thread::yield_now();
+    }
+}
+
+/// Waits for the exit descriptor of the child through the reactor. Spans
+/// are per thread, which a future may change between polls, so its runs
+/// log without them.
+#[cfg(feature = "async")]
+#[derive(Clone, Copy)]
+struct Polled;
+
+#[cfg(feature = "async")]
+impl Waiter for Polled {
+    type Span = ();
+
+    fn span(self, _name: &'static str) {}
+
+    async fn wait(self, child: &mut dyn SandboxChildHandle, timeout: Option<Duration>) -> io::Result<WaitOutcome> {
+        // Stops don't turn the descriptor readable, so the state is
+        // checked this often anyway
+        const STATE_CHECK_INTERVAL: Duration = Duration::from_millis(20);
+        // Without a descriptor, how long a wait blocks the thread polling
+        // it before the executor's other futures get to run
+        const BLOCKING_SLICE: Duration = Duration::from_millis(1);
+
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            let slice = match child.exit_fd() {
+                Some(_) => Duration::ZERO,
+                None => left.map_or(BLOCKING_SLICE, |left| left.min(BLOCKING_SLICE))
+            };
+            match child.wait(Some(slice))? {
+                WaitOutcome::Timeout => {},
+                outcome => return Ok(outcome)
+            }
+            let now = Instant::now();
+            if deadline.is_some_and(|d| now >= d) {
+                return Ok(WaitOutcome::Timeout);
+            }
+            let fd = child.exit_fd();
+            match fd {
+                Some(_) => {
+                    let check = now + STATE_CHECK_INTERVAL;
+                    reactor::ready(fd, deadline.map_or(check, |d| d.min(check))).await?;
+                },
+                None => reactor::yield_now().await
+            }
+        }
+    }
+
+    async fn sleep(self, duration: Duration) {
+        if let Err(e) = reactor::ready(None, Instant::now() + duration).await {
+            debug!("{e}, sleeping on the thread instead");
+            thread::sleep(duration);
+        }
+    }
+
+    async fn pause(self) {
+        reactor::yield_now().await;
+    }
+}
+
+/*
+ *  The result of `judging`, a future of Blocking waits, which is done
+ *  when first polled
+ */
+fn blocking<T>(judging: impl Future<Output = Result<T, Box<dyn Error + Send + Sync>>>) -> Result<T, Box<dyn Error>> {
+    match pin!(judging).poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(result) => result.map_err(|e| -> Box<dyn Error> { e }),
+        Poll::Pending => unreachable!("blocking waits are done when first polled")
+    }
+}
+
+/*
+ *  Describe a failure to wait for or reap the child
+ */
+fn wait_error(pid: i32, err: io::Error) -> String {
+    match err.raw_os_error() {
+        Some(libc::ECHILD) => format!("child {pid} was reaped by someone else"),
+        _ => format!("failed to wait for child {pid}: {err}")
+    }
+}
+
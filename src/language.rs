@@ -0,0 +1,117 @@
+use std::path::Path;
+use std::time::Duration;
+
+const MIB: u64 = 1 << 20;
+// Scaled limits are rounded up to multiples of these
+const TIME_STEP_MS: f64 = 100.0;
+const MEMORY_STEP: f64 = MIB as f64;
+// Slack for products like 1000ms * 1.1 that come out a hair too large
+const ROUNDING_SLACK: f64 = 1e-6;
+
+/// Factors a language's limits get over the problem's
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LimitMultipliers {
+    /// Factor of the time limits
+    pub time: f64,
+    /// Factor of the memory limit
+    pub memory: f64
+}
+
+impl Default for LimitMultipliers {
+    fn default() -> Self {
+        LimitMultipliers { time: 1.0, memory: 1.0 }
+    }
+}
+
+impl LimitMultipliers {
+    /// Whether both factors are positive and finite
+    pub fn is_valid(&self) -> bool {
+        [self.time, self.memory].iter().all(|m| m.is_finite() && *m > 0.0)
+    }
+
+    /**
+     *  Scale a time limit, rounding up to the next 100ms as judges
+     *  usually do. A factor of 1 keeps the limit exactly.
+     */
+    pub fn scale_time(&self, base: Duration) -> Duration {
+        if self.time == 1.0 || base == Duration::MAX {
+            return base;
+        }
+        let steps = round_up(base.as_secs_f64() * 1000.0 * self.time / TIME_STEP_MS);
+        Duration::try_from_secs_f64(steps * TIME_STEP_MS / 1000.0).unwrap_or(Duration::MAX)
+    }
+
+    /**
+     *  Scale a memory limit, rounding up to the next MiB. A factor of 1
+     *  keeps the limit exactly.
+     */
+    pub fn scale_memory(&self, base: u64) -> u64 {
+        if self.memory == 1.0 {
+            return base;
+        }
+        let steps = round_up(base as f64 * self.memory / MEMORY_STEP);
+        (steps * MEMORY_STEP) as u64
+    }
+}
+
+fn round_up(steps: f64) -> f64 {
+    (steps - ROUNDING_SLACK).ceil().max(1.0)
+}
+
+/// What the judger knows about the language a program is written in
+pub struct Language {
+    /// Name the language is given by, such as "cpp"
+    pub name: &'static str,
+    /// How much more time and memory its programs get
+    pub limit_multipliers: LimitMultipliers,
+    /// Names of the interpreters its scripts name in their `#!` line,
+    /// without version suffixes. Empty for compiled languages.
+    pub interpreters: &'static [&'static str],
+    /// Syscalls the default rules deny that its interpreter can't start
+    /// without, allowed for its scripts
+    pub allowed_syscalls: &'static [&'static str]
+}
+
+/// The languages the judger knows
+pub static LANGUAGES: [Language; 8] = [
+    Language { name: "c", limit_multipliers: LimitMultipliers { time: 1.0, memory: 1.0 }, interpreters: &[], allowed_syscalls: &[] },
+    Language { name: "cpp", limit_multipliers: LimitMultipliers { time: 1.0, memory: 1.0 }, interpreters: &[], allowed_syscalls: &[] },
+    Language { name: "rust", limit_multipliers: LimitMultipliers { time: 1.0, memory: 1.0 }, interpreters: &[], allowed_syscalls: &[] },
+    Language { name: "go", limit_multipliers: LimitMultipliers { time: 1.0, memory: 1.0 }, interpreters: &[], allowed_syscalls: &[] },
+    // The JVM's startup and heap
+    Language { name: "java", limit_multipliers: LimitMultipliers { time: 2.0, memory: 2.0 }, interpreters: &[], allowed_syscalls: &[] },
+    // CPython marks the files it opens close-on-exec with ioctl(FIOCLEX)
+    Language {
+        name: "python",
+        limit_multipliers: LimitMultipliers { time: 3.0, memory: 1.5 },
+        interpreters: &["python"],
+        allowed_syscalls: &["ioctl"]
+    },
+    Language {
+        name: "pypy",
+        limit_multipliers: LimitMultipliers { time: 2.0, memory: 2.0 },
+        interpreters: &["pypy"],
+        allowed_syscalls: &["ioctl"]
+    },
+    Language {
+        name: "sh",
+        limit_multipliers: LimitMultipliers { time: 1.0, memory: 1.0 },
+        interpreters: &["sh", "bash", "dash"],
+        allowed_syscalls: &[]
+    }
+];
+
+/// The language `name` names, ignoring case
+pub fn find(name: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|lang| lang.name.eq_ignore_ascii_case(name))
+}
+
+/**
+ *  The language whose scripts run under `interpreter`, a path or a name,
+ *  going by its file name without a version: python3.11 is python's
+ */
+pub fn for_interpreter(interpreter: &Path) -> Option<&'static Language> {
+    let name = interpreter.file_name()?.to_str()?;
+    let name = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    LANGUAGES.iter().find(|lang| lang.interpreters.contains(&name))
+}
@@ -0,0 +1,109 @@
+//! Judging of contestant programs in a seccomp sandbox, as the
+//! `secure-judger` binary does it, for programs that judge without
+//! shelling out to it.
+//!
+//! A [`judger::JudgeSession`] runs one executable on one input under time,
+//! memory and output limits and compares what it wrote with an answer:
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//! use std::time::Duration;
+//! use secure_judger::judger::JudgeSession;
+//! use secure_judger::sandbox::InputSource;
+//!
+//! let session = JudgeSession::builder(PathBuf::from("./a.out"))
+//!     .input(InputSource::File(PathBuf::from("1.in")))
+//!     .answer(PathBuf::from("1.ans"))
+//!     .time_limit(Duration::from_secs(1))
+//!     .build()?;
+//! let result = session.run_judge(&[])?;
+//! println!("{}", result.status);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! and a [`problem::ProblemJudge`] runs a session over every test of a
+//! problem.
+//!
+//! Sessions start their programs through a [`sandbox::Sandbox`], the
+//! seccomp one unless told otherwise. The `testing` feature adds
+//! `sandbox::MockSandbox`, which plays scripted runs instead, for testing
+//! code that judges without the privileges the real sandbox needs.
+//!
+//! # Stability
+//!
+//! The modules [`judger`], [`sandbox`], [`problem`], [`compare`],
+//! [`language`], [`output`] and [`log`] are the library's API and follow semver: items
+//! are only removed or changed incompatibly with a new major version, with
+//! two exceptions. New variants may be added to the enums, which are
+//! non-exhaustive in spirit and should be matched with a wildcard arm, and
+//! new fields to the structs built with `..Default::default()` or a builder.
+//!
+//! Everything else is public for the binary's sake and may change in any
+//! release: [`utils`], [`json`], [`config`], [`plan`], [`stress`],
+//! [`cache`], [`metrics`], [`transcript`], [`calibration`] and [`admission`], and `grpc` of the grpc feature. Their formats on disk and on the wire, such as
+//! problem.toml or the JSON of a result, are stable even where their Rust
+//! types aren't; the JSON is versioned by [`output::Schema`].
+#![warn(missing_docs)]
+
+/// Where the judger's log goes, and how detailed it is
+#[macro_use]
+pub mod log;
+/// Judging sessions: running a program under limits and judging its output
+pub mod judger;
+/// The seccomp sandbox programs are judged in: what it lets them do and
+/// where their input comes from
+pub mod sandbox;
+/// Tests of a problem, and judging a program on all of them
+pub mod problem;
+/// Checking an output against the answer
+pub mod compare;
+/// The JSON of results, in versions that only ever gain fields
+pub mod output;
+/// Languages and the limit multipliers their programs get
+pub mod language;
+/// Helpers for formatting and parsing sizes, durations and JSON
+pub mod utils;
+/// A small JSON parser, for requests and cache entries
+pub mod json;
+/// Problem files in TOML
+pub mod config;
+/// What a session would do, worked out without running anything
+pub mod plan;
+/// Stress testing a solution against a brute force
+pub mod stress;
+/// Results of earlier runs, stored on disk
+pub mod cache;
+/// Counters of what the judger did, in the Prometheus text format
+pub mod metrics;
+/// Records of every step of the runs, for handling appeals
+pub mod transcript;
+/// How fast this host is against a baseline, and the factor of the time
+/// limits that follows
+pub mod calibration;
+/// Reserving the memory and cores of jobs, so that those run at once fit
+/// the host
+pub mod admission;
+/// A gRPC service judging requests from other hosts, with its server and
+/// a client
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod secrun;
+mod cgroup;
+mod scratch;
+mod sha256;
+mod toml;
+#[cfg(feature = "testing")]
+mod mock;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "grpc")]
+mod h2;
+#[cfg(feature = "grpc")]
+mod hpack;
+#[cfg(feature = "grpc")]
+mod protobuf;
+#[cfg(feature = "fuzz")]
+#[doc(hidden)]
+pub mod fuzz;
+#[cfg(feature = "async")]
+mod reactor;
@@ -0,0 +1,206 @@
+use std::cell::RefCell;
+use std::fmt::Arguments;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// How much detail a log record is, from the least to the most
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Something failed
+    Error = 1,
+    /// Something looks wrong but judging goes on
+    Warn,
+    /// What the judger is doing
+    Info,
+    /// Details of how it does it
+    Debug,
+    /// Every step, such as each syscall rule or sandbox setup call
+    Trace
+}
+
+impl Level {
+    /// Parse a level such as "debug", in any case
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name.to_ascii_lowercase().as_str() {
+            "error" => Some(Self::Error),
+            "warn" => Some(Self::Warn),
+            "info" => Some(Self::Info),
+            "debug" => Some(Self::Debug),
+            "trace" => Some(Self::Trace),
+            _ => None
+        }
+    }
+
+    /// Name of the level in capitals, as the log shows it
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+            Self::Trace => "TRACE"
+        }
+    }
+}
+
+/// Something the judger logged, with the spans it happened in
+pub struct Record<'a> {
+    /// How detailed the record is
+    pub level: Level,
+    /// The spans entered on this thread, outermost first
+    pub spans: &'a [&'static str],
+    /// What happened
+    pub message: Arguments<'a>
+}
+
+/// Where the judger's log goes. Library users install their own with
+/// set_logger to send the records wherever their log goes.
+pub trait Logger: Send + Sync {
+    /// Take a record of an enabled level
+    fn log(&self, record: &Record);
+}
+
+static LOGGER: OnceLock<Box<dyn Logger>> = OnceLock::new();
+// The most detailed level logged, 0 while nothing is
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+thread_local! {
+    static SPANS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/**
+ *  Install the logger and log up to `level`. There is only one logger,
+ *  false means one was installed already and keeps the records.
+ */
+pub fn set_logger(logger: Box<dyn Logger>, level: Level) -> bool {
+    let installed = LOGGER.set(logger).is_ok();
+    if installed {
+        MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+    }
+    installed
+}
+
+/// Whether records of `level` are logged at all
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/**
+ *  Log a record, as the macros do. Nothing is formatted unless the
+ *  level is enabled.
+ */
+pub fn log(level: Level, message: Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    if let Some(logger) = LOGGER.get() {
+        SPANS.with(|spans| logger.log(&Record { level, spans: &spans.borrow(), message }));
+    }
+}
+
+/// A span entered by span(), left when this is dropped
+pub struct SpanGuard {
+    // Spans are per thread, so the guard has to stay on its thread
+    _thread: PhantomData<*const ()>
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        SPANS.with(|spans| spans.borrow_mut().pop());
+    }
+}
+
+/**
+ *  Enter a span: the records logged on this thread until the guard is
+ *  dropped happened in it
+ */
+pub fn span(name: &'static str) -> SpanGuard {
+    SPANS.with(|spans| spans.borrow_mut().push(name));
+    SpanGuard { _thread: PhantomData }
+}
+
+/// Writes the log to stderr, each record with the time since it was
+/// installed, its level and its spans
+pub struct StderrLogger {
+    start: Instant
+}
+
+impl StderrLogger {
+    /// A logger counting the time from now
+    pub fn new() -> Self {
+        StderrLogger { start: Instant::now() }
+    }
+}
+
+impl Default for StderrLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Logger for StderrLogger {
+    fn log(&self, record: &Record) {
+        let elapsed = self.start.elapsed();
+        eprintln!(
+            "[{:4}.{:03}s {:5} {}] {}",
+            elapsed.as_secs(),
+            elapsed.subsec_millis(),
+            record.level.name(),
+            record.spans.join("/"),
+            record.message
+        );
+    }
+}
+
+/**
+ *  The level RUST_LOG asks for. Of its comma separated directives, a
+ *  bare level and those for secure_judger or its modules count, the
+ *  most detailed of them wins. None if it asks for nothing of ours.
+ */
+pub fn env_level(value: &str) -> Option<Level> {
+    value
+        .split(',')
+        .filter_map(|directive| {
+            let directive = directive.trim();
+            // A target alone logs everything of it
+            let (target, level) = match directive.split_once('=') {
+                Some((target, level)) => (target, level),
+                None if Level::from_name(directive).is_some() => ("", directive),
+                None => (directive, "trace")
+            };
+            let ours = target.is_empty()
+                || target == "secure_judger"
+                || target.starts_with("secure_judger::");
+            match ours {
+                true => Level::from_name(level),
+                false => None
+            }
+        })
+        .max()
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Debug, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Trace, format_args!($($arg)*))
+    };
+}
@@ -0,0 +1,208 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::judger::{JudgeResult, JudgeStatus};
+use crate::utils;
+
+// Abbreviations of the verdicts runs are counted by, SKIP never being
+// the verdict of a run
+const VERDICTS: [&str; 13] = ["AC", "WA", "TLE", "MLE", "OLE", "RE", "ILE", "SV", "PE", "OM", "RNZ", "SE", "CAN"];
+// Upper bounds of the judge latency histogram's buckets, in seconds
+const LATENCY_BUCKETS: [f64; 12] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Counters of what the judger did since it started, cheap enough to
+/// update from every run and read without stopping any
+pub struct Metrics {
+    runs: [AtomicU64; VERDICTS.len()],
+    // Runs that failed before they had a verdict, such as a missing input
+    run_errors: AtomicU64,
+    cache_hits: AtomicU64,
+    // Runs taking at most each bucket's bound, not cumulated
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    latency_count: AtomicU64,
+    latency_sum_us: AtomicU64,
+    // Programs the judger had to stop, for their limits or a cancel
+    kills: AtomicU64,
+    seccomp_violations: AtomicU64,
+    scratch_dirs: AtomicU64,
+    // Jobs of the daemon or the server waiting for a worker, and being judged
+    queued: AtomicU64,
+    running: AtomicU64,
+    // What the jobs running reserved, and the memory they may reserve in
+    // all, 0 for no budget
+    memory_reserved: AtomicU64,
+    memory_budget: AtomicU64,
+    cores_reserved: AtomicU64
+}
+
+/// The judger's metrics, updated by every session in the process
+pub static METRICS: Metrics = Metrics::new();
+
+impl Metrics {
+    const fn new() -> Self {
+        Metrics {
+            runs: [const { AtomicU64::new(0) }; VERDICTS.len()],
+            run_errors: AtomicU64::new(0),
+            cache_hits: AtomicU64::new(0),
+            latency_buckets: [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len()],
+            latency_count: AtomicU64::new(0),
+            latency_sum_us: AtomicU64::new(0),
+            kills: AtomicU64::new(0),
+            seccomp_violations: AtomicU64::new(0),
+            scratch_dirs: AtomicU64::new(0),
+            queued: AtomicU64::new(0),
+            running: AtomicU64::new(0),
+            memory_reserved: AtomicU64::new(0),
+            memory_budget: AtomicU64::new(0),
+            cores_reserved: AtomicU64::new(0)
+        }
+    }
+
+    /**
+     *  Count a judged run by its verdict, with how long judging it took.
+     *  None is a run that failed to be judged.
+     */
+    pub fn record_run(&self, result: Option<&JudgeResult>, elapsed: Duration) {
+        let result = match result {
+            Some(x) => x,
+            None => {
+                self.run_errors.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+        if let Some(index) = VERDICTS.iter().position(|abbr| *abbr == result.status.abbr()) {
+            self.runs[index].fetch_add(1, Ordering::Relaxed);
+        }
+        if matches!(result.status, JudgeStatus::SecurityViolation) {
+            self.seccomp_violations.fetch_add(1, Ordering::Relaxed);
+        }
+        if result.cached {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        }
+        let seconds = elapsed.as_secs_f64();
+        if let Some(index) = LATENCY_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.latency_buckets[index].fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_us.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count_kill(&self) {
+        self.kills.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn scratch_created(&self) {
+        self.scratch_dirs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn scratch_removed(&self) {
+        self.scratch_dirs.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// How many jobs wait in the queue, after it changed
+    pub fn set_queued(&self, queued: usize) {
+        self.queued.store(queued as u64, Ordering::Relaxed);
+    }
+
+    /// A job was taken from the queue
+    pub fn job_started(&self) {
+        self.running.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A job was judged and its reply is on its way
+    pub fn job_finished(&self) {
+        self.running.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// The memory and the dedicated cores the jobs running reserved, after
+    /// a job was admitted or finished
+    pub fn set_reserved(&self, memory_bytes: u64, cores: usize) {
+        self.memory_reserved.store(memory_bytes, Ordering::Relaxed);
+        self.cores_reserved.store(cores as u64, Ordering::Relaxed);
+    }
+
+    /// The memory jobs may reserve in all, None for no budget
+    pub fn set_memory_budget(&self, bytes: Option<u64>) {
+        self.memory_budget.store(bytes.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /**
+     *  What the jobs running reserved, as the JSON object GET /health
+     *  gives them in, the budget null if there is none
+     */
+    pub fn reservations_json(&self) -> String {
+        let budget = self.memory_budget.load(Ordering::Relaxed);
+        utils::json_object(&[
+            ("memory_bytes", self.memory_reserved.load(Ordering::Relaxed).to_string()),
+            ("memory_budget_bytes", match budget {
+                0 => String::from("null"),
+                bytes => bytes.to_string()
+            }),
+            ("cores", self.cores_reserved.load(Ordering::Relaxed).to_string())
+        ])
+    }
+
+    /**
+     *  The metrics in the Prometheus text format, with the free space of
+     *  the filesystem the scratch directories go to under `scratch_base`
+     */
+    pub fn render(&self, scratch_base: &Path) -> String {
+        let mut text = String::new();
+        let mut family = |name: &str, kind: &str, help: &str, samples: Vec<(String, u64)>| {
+            text.push_str(&format!("# HELP {name} {help}\n# TYPE {name} {kind}\n"));
+            for (labels, value) in samples {
+                text.push_str(&format!("{name}{labels} {value}\n"));
+            }
+        };
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let runs = VERDICTS.iter()
+            .zip(&self.runs)
+            .map(|(abbr, count)| (format!("{{verdict=\"{abbr}\"}}"), load(count)))
+            .collect();
+        family("judger_runs_total", "counter", "Runs judged, by verdict", runs);
+        family("judger_run_errors_total", "counter", "Runs that failed before getting a verdict", vec![(String::new(), load(&self.run_errors))]);
+        family("judger_cache_hits_total", "counter", "Runs answered from the result cache", vec![(String::new(), load(&self.cache_hits))]);
+        family("judger_kills_total", "counter", "Programs stopped by the judger", vec![(String::new(), load(&self.kills))]);
+        family(
+            "judger_seccomp_violations_total",
+            "counter",
+            "Runs ended for a security violation",
+            vec![(String::new(), load(&self.seccomp_violations))]
+        );
+        family("judger_scratch_dirs", "gauge", "Scratch directories in use", vec![(String::new(), load(&self.scratch_dirs))]);
+        if let Some(free) = free_bytes(scratch_base) {
+            family("judger_scratch_free_bytes", "gauge", "Free space where scratch directories go", vec![(String::new(), free)]);
+        }
+        family("judger_jobs_queued", "gauge", "Jobs waiting for a worker", vec![(String::new(), load(&self.queued))]);
+        family("judger_jobs_running", "gauge", "Jobs being judged", vec![(String::new(), load(&self.running))]);
+        family("judger_memory_reserved_bytes", "gauge", "Memory limits of the jobs being judged, summed", vec![(String::new(), load(&self.memory_reserved))]);
+        if load(&self.memory_budget) > 0 {
+            family("judger_memory_budget_bytes", "gauge", "Memory the jobs being judged may reserve in all", vec![(String::new(), load(&self.memory_budget))]);
+        }
+        family("judger_cores_reserved", "gauge", "Cores dedicated to the jobs being judged", vec![(String::new(), load(&self.cores_reserved))]);
+
+        let mut buckets = Vec::with_capacity(LATENCY_BUCKETS.len() + 1);
+        let mut cumulated = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.latency_buckets) {
+            cumulated += load(count);
+            buckets.push((format!("_bucket{{le=\"{bound}\"}}"), cumulated));
+        }
+        let count = load(&self.latency_count);
+        buckets.push((String::from("_bucket{le=\"+Inf\"}"), count));
+        buckets.push((String::from("_count"), count));
+        family("judger_judge_seconds", "histogram", "Time taken to judge a run", buckets);
+        // The sum is in seconds, which isn't a whole number
+        text.push_str(&format!("judger_judge_seconds_sum {:.6}\n", load(&self.latency_sum_us) as f64 / 1e6));
+        text
+    }
+}
+
+fn free_bytes(path: &Path) -> Option<u64> {
+    let path = std::ffi::CString::new(path.as_os_str().as_encoded_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } < 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
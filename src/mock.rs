@@ -0,0 +1,331 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::secrun::{ResourceUsage, RunSpec, Sandbox, SandboxChildHandle, WaitOutcome};
+
+// How long a run that never ends naps between looks at whether it was killed
+const HANG_NAP: Duration = Duration::from_millis(10);
+
+/// How a scripted run ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MockExit {
+    /// It exits with this code
+    Code(i32),
+    /// It is killed by this signal, as by a crash
+    Signal(i32),
+    /// It stops as if by SIGSTOP, and exits with 0 once sent SIGCONT
+    Stop,
+    /// It never ends by itself, only when the judger kills it
+    Hang
+}
+
+/// One run of a MockSandbox's script: how the program ends and after
+/// how long, what it writes and the resources it reports. Made with
+/// MockRun::exit and the like, then adjusted with the setters.
+#[derive(Clone, Debug)]
+pub struct MockRun {
+    exit: MockExit,
+    // Real time it runs for before it ends
+    wall_time: Duration,
+    cpu_time: Duration,
+    memory_bytes: u64,
+    // Threads it reports while running
+    threads: u32,
+    // Disk I/O it reports once reaped, as (read, written)
+    io_bytes: (u64, u64),
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    // With named file I/O, whether it opens its output file
+    opens_output: bool,
+    // Fails to start with this OS error instead of running
+    spawn_error: Option<i32>,
+    // How late waits that time out return, as on a host too busy to
+    // wake the judger up on time
+    late_wakeups: Duration
+}
+
+impl MockRun {
+    /// A run exiting with `code` right away, using and writing nothing
+    pub fn exit(code: i32) -> Self {
+        MockRun {
+            exit: MockExit::Code(code),
+            wall_time: Duration::ZERO,
+            cpu_time: Duration::ZERO,
+            memory_bytes: 0,
+            threads: 1,
+            io_bytes: (0, 0),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            opens_output: true,
+            spawn_error: None,
+            late_wakeups: Duration::ZERO
+        }
+    }
+
+    /// A run killed by `signal` right away
+    pub fn signal(signal: i32) -> Self {
+        MockRun { exit: MockExit::Signal(signal), ..MockRun::exit(0) }
+    }
+
+    /// A run stopping itself right away
+    pub fn stop() -> Self {
+        MockRun { exit: MockExit::Stop, ..MockRun::exit(0) }
+    }
+
+    /// A run that never ends by itself
+    pub fn hang() -> Self {
+        MockRun { exit: MockExit::Hang, ..MockRun::exit(0) }
+    }
+
+    /// A run failing to start with the OS error `errno`, as fork does with
+    /// EAGAIN when the host is out of processes
+    pub fn spawn_error(errno: i32) -> Self {
+        MockRun { spawn_error: Some(errno), ..MockRun::exit(0) }
+    }
+
+    /// End only after `duration` of real time
+    pub fn wall_time(mut self, duration: Duration) -> Self {
+        self.wall_time = duration;
+        self
+    }
+
+    /// Report `duration` of CPU time, while running and once reaped
+    pub fn cpu_time(mut self, duration: Duration) -> Self {
+        self.cpu_time = duration;
+        self
+    }
+
+    /// Return from every wait that times out `duration` late, as a busy
+    /// host would wake the judger up
+    pub fn late_wakeups(mut self, duration: Duration) -> Self {
+        self.late_wakeups = duration;
+        self
+    }
+
+    /// Report `bytes` of resident memory while running, and as its peak
+    pub fn memory(mut self, bytes: u64) -> Self {
+        self.memory_bytes = bytes;
+        self
+    }
+
+    /// Report having `count` threads while running
+    pub fn threads(mut self, count: u32) -> Self {
+        self.threads = count;
+        self
+    }
+
+    /// Report reading `read` and writing `written` bytes from and to disk,
+    /// rounded up to whole blocks
+    pub fn disk_io(mut self, read: u64, written: u64) -> Self {
+        self.io_bytes = (read, written);
+        self
+    }
+
+    /// Write `output` to standard output, or to the output file with
+    /// named file I/O
+    pub fn stdout(mut self, output: impl Into<Vec<u8>>) -> Self {
+        self.stdout = output.into();
+        self
+    }
+
+    /// Write `output` to standard error
+    pub fn stderr(mut self, output: impl Into<Vec<u8>>) -> Self {
+        self.stderr = output.into();
+        self
+    }
+
+    /// With named file I/O, never open the output file
+    pub fn no_output_file(mut self) -> Self {
+        self.opens_output = false;
+        self
+    }
+}
+
+/// A Sandbox that starts nothing and plays a script instead: every spawn
+/// takes the next MockRun, writes its output and reports what it says.
+/// Lets tests judge verdicts without the privileges for fork and seccomp.
+pub struct MockSandbox {
+    script: Mutex<VecDeque<MockRun>>,
+    // Played for every spawn once the script ran out
+    repeat: Option<MockRun>,
+    spawned: AtomicUsize
+}
+
+impl MockSandbox {
+    /// Play `runs` in order, failing the spawns after them
+    pub fn new(runs: Vec<MockRun>) -> Self {
+        MockSandbox { script: Mutex::new(runs.into()), repeat: None, spawned: AtomicUsize::new(0) }
+    }
+
+    /// Play `run` for every spawn
+    pub fn repeat(run: MockRun) -> Self {
+        MockSandbox { script: Mutex::new(VecDeque::new()), repeat: Some(run), spawned: AtomicUsize::new(0) }
+    }
+
+    /// How many spawns there were, the failed ones included
+    pub fn spawned(&self) -> usize {
+        self.spawned.load(Ordering::Relaxed)
+    }
+}
+
+impl Sandbox for MockSandbox {
+    fn spawn(&self, spec: RunSpec<'_>) -> Result<Box<dyn SandboxChildHandle>, Box<dyn Error>> {
+        let count = self.spawned.fetch_add(1, Ordering::Relaxed) + 1;
+        let next = self.script.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+        let Some(run) = next.or_else(|| self.repeat.clone()) else {
+            return Err(format!("the mock sandbox's script has no run {count}").into());
+        };
+        if let Some(errno) = run.spawn_error {
+            return Err(Box::new(io::Error::from_raw_os_error(errno)));
+        }
+        let named_output = spec.io.named.as_ref().map(|_| run.opens_output);
+        let stdout: &[u8] = match named_output {
+            Some(false) => &[],
+            _ => &run.stdout
+        };
+        fs::write(spec.io.stdout, stdout)?;
+        // Cut at the stderr limit, as the real capture would be
+        let kept = run.stderr.len().min(usize::try_from(spec.policy.stderr_limit).unwrap_or(usize::MAX));
+        fs::write(spec.io.stderr, &run.stderr[..kept])?;
+        let blocks = |bytes: u64| bytes.div_ceil(ResourceUsage::BLOCK_SIZE);
+        let usage = ResourceUsage {
+            user_time: run.cpu_time,
+            max_rss_bytes: run.memory_bytes,
+            blocks_read: blocks(run.io_bytes.0),
+            blocks_written: blocks(run.io_bytes.1),
+            ..ResourceUsage::default()
+        };
+        Ok(Box::new(MockChild {
+            pid: count as i32,
+            run,
+            start: Instant::now(),
+            named_output,
+            usage,
+            status: None,
+            signalled: Cell::new(None),
+            resumed: Cell::new(false)
+        }))
+    }
+}
+
+/*
+ *  A run of a MockSandbox, going through its MockRun in real time
+ */
+struct MockChild {
+    // The number of the spawn, nothing runs under it
+    pid: i32,
+    run: MockRun,
+    start: Instant,
+    named_output: Option<bool>,
+    usage: ResourceUsage,
+    // Wait status, once reaped
+    status: Option<i32>,
+    // A signal it got that ends it, none of them being handled
+    signalled: Cell<Option<i32>>,
+    resumed: Cell<bool>
+}
+
+impl MockChild {
+    fn reap(&mut self, status: i32) -> WaitOutcome {
+        self.status = Some(status);
+        WaitOutcome::Exited(status)
+    }
+}
+
+impl SandboxChildHandle for MockChild {
+    fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    // Nothing runs, so nothing is filtered
+    fn seccomp(&self) -> bool {
+        false
+    }
+
+    fn start_instant(&self) -> Instant {
+        self.start
+    }
+
+    fn wait(&mut self, timeout: Option<Duration>) -> io::Result<WaitOutcome> {
+        if let Some(status) = self.status {
+            return Ok(WaitOutcome::Exited(status));
+        }
+        let begin = Instant::now();
+        loop {
+            if let Some(signal) = self.signalled.get() {
+                return Ok(self.reap(signal));
+            }
+            let left = self.run.wall_time.saturating_sub(self.start.elapsed());
+            if left.is_zero() {
+                match self.run.exit {
+                    MockExit::Code(code) => return Ok(self.reap((code & 0xff) << 8)),
+                    MockExit::Signal(signal) => return Ok(self.reap(signal)),
+                    MockExit::Stop if !self.resumed.get() => return Ok(WaitOutcome::Stopped),
+                    MockExit::Stop => return Ok(self.reap(0)),
+                    MockExit::Hang => {}
+                }
+            }
+            let remaining = timeout.map(|t| t.saturating_sub(begin.elapsed()));
+            if remaining.is_some_and(|r| r.is_zero()) {
+                thread::sleep(self.run.late_wakeups);
+                return Ok(WaitOutcome::Timeout);
+            }
+            // Signals come from the thread waiting, so only the end of the
+            // run or of the timeout can come first
+            let nap = [(!left.is_zero()).then_some(left), remaining].into_iter().flatten().min();
+            thread::sleep(nap.unwrap_or(HANG_NAP));
+        }
+    }
+
+    fn kill(&self, signal: i32) -> io::Result<()> {
+        if self.status.is_some() {
+            return Ok(());
+        }
+        match signal {
+            0 | libc::SIGSTOP => {},
+            libc::SIGCONT => self.resumed.set(true),
+            signal => self.signalled.set(Some(signal))
+        }
+        Ok(())
+    }
+
+    fn rusage(&self) -> Option<&ResourceUsage> {
+        self.status.map(|_| &self.usage)
+    }
+
+    fn named_output_opened(&self) -> Option<bool> {
+        self.named_output
+    }
+
+    fn resident_memory(&self) -> Option<u64> {
+        match self.status {
+            Some(_) => None,
+            None => Some(self.run.memory_bytes)
+        }
+    }
+
+    fn cpu_time(&self) -> Option<Duration> {
+        match self.status {
+            Some(_) => None,
+            None => Some(self.run.cpu_time)
+        }
+    }
+
+    fn thread_count(&self) -> Option<u32> {
+        match self.status {
+            Some(_) => None,
+            None => Some(self.run.threads)
+        }
+    }
+
+    fn stderr_written(&self) -> Option<u64> {
+        self.status.map(|_| self.run.stderr.len() as u64)
+    }
+}
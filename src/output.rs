@@ -0,0 +1,943 @@
+use std::fmt::Display;
+
+use crate::json::{self, Value};
+use crate::judger::{JudgeEnvironment, JudgeResult, Limits};
+use crate::problem::{SubmissionResult, TestCase};
+use crate::utils;
+
+/// Versions of the JSON the judger writes. A version only ever gains
+/// fields, so that a reader of it keeps working on what later judgers
+/// write; anything else makes a new version, with the old one still
+/// written where it is asked for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Schema {
+    /// secure-judger/1, with JsonResultV1 and JsonSubmissionV1
+    V1
+}
+
+impl Schema {
+    /// The version written unless another one is asked for
+    pub const LATEST: Schema = Schema::V1;
+
+    /// Name of the version, as the "schema" member of a document has it
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::V1 => "secure-judger/1"
+        }
+    }
+
+    /**
+     *  The version a document's "schema" member names
+     */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "secure-judger/1" => Some(Self::V1),
+            _ => None
+        }
+    }
+
+    /**
+     *  The version a --format names, such as json for the latest or
+     *  json-v1 pinned
+     */
+    pub fn from_format(format: &str) -> Option<Self> {
+        match format {
+            "json" => Some(Self::LATEST),
+            "json-v1" => Some(Self::V1),
+            _ => None
+        }
+    }
+}
+
+impl Display for Schema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Why a JSON document can't be read as a version of the judger's
+#[derive(Debug)]
+pub enum ReadError {
+    /// The text isn't JSON
+    Json(json::ParseError),
+    /// The document is of a version this reader doesn't know, such as one
+    /// of a later judger
+    Schema(String),
+    /// A member is missing, or of the wrong kind
+    Field {
+        /// Where the member is, such as `batch.tests[2].result.status`
+        field: String,
+        /// What it should have been
+        expected: &'static str
+    }
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Json(e) => f.write_fmt(format_args!("not JSON {e}")),
+            Self::Schema(name) => f.write_fmt(format_args!("unknown schema {name}, expected {}", Schema::LATEST)),
+            Self::Field { field, expected } => f.write_fmt(format_args!("{field} should be {expected}"))
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+/// Limits of a run as version 1 has them
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonLimitsV1 {
+    /// CPU time, in milliseconds
+    pub cpu_ms: u64,
+    /// Real time, in milliseconds
+    pub wall_ms: u64,
+    /// Memory in bytes
+    pub memory_bytes: u64
+}
+
+/// The host a result was judged on, see JudgeEnvironment
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonEnvironmentV1 {
+    /// Network name of the host
+    pub hostname: Option<String>,
+    /// Release of its kernel
+    pub kernel: Option<String>,
+    /// Model of its CPU
+    pub cpu_model: Option<String>,
+    /// CPUs the judger could run on
+    pub cores: Option<u64>,
+    /// Version of the judger
+    pub version: String,
+    /// Whether the seccomp filter could be installed
+    pub seccomp: bool,
+    /// The calibration its time limits were scaled by
+    pub calibration: Option<JsonCalibrationV1>
+}
+
+/// The calibration of a host, as version 1 has it
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonCalibrationV1 {
+    /// How fast the host ran the reference workload against the baseline
+    pub score: f64,
+    /// Factor of the time limits
+    pub multiplier: f64,
+    /// CPU time the workload took, in milliseconds
+    pub cpu_time_ms: u64,
+    /// When it ran, in seconds since the epoch
+    pub measured_at: u64
+}
+
+/// The result of a run as version 1 has it, which is what the JSON of a
+/// JudgeResult is. Members later judgers added are None, zero or false
+/// when read from what an earlier one wrote.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonResultV1 {
+    /// Abbreviation of the verdict, such as AC
+    pub status: String,
+    /// The verdict in full, with what it has to it
+    pub message: String,
+    /// Where the output first differs from the answer
+    pub difference: Option<String>,
+    /// Real time, in milliseconds
+    pub time_ms: u64,
+    /// CPU time, in milliseconds
+    pub cpu_time_ms: u64,
+    /// Peak memory in bytes
+    pub memory_bytes: u64,
+    /// Time the judger itself took around the run
+    pub judge_overhead_ms: f64,
+    /// Peak of tasks in the run's cgroup
+    pub tasks_peak: Option<u64>,
+    /// Task creations the cgroup refused
+    pub task_limit_hits: u64,
+    /// Time held back by the CPU quota
+    pub cpu_throttled_ms: Option<u64>,
+    /// Most threads the program had at once
+    pub max_threads: Option<u64>,
+    /// Roughly how much the program read from disk
+    pub io_read_bytes: u64,
+    /// Roughly how much it wrote to disk
+    pub io_written_bytes: u64,
+    /// Whether it went over the I/O limit
+    pub io_limit_exceeded: bool,
+    /// How much it wrote to stderr
+    pub stderr_bytes: u64,
+    /// Whether its stderr was cut at the limit
+    pub stderr_truncated: bool,
+    /// SHA-256 of the executable run
+    pub exec_sha256: Option<String>,
+    /// Whether the executable was read into the page cache first
+    pub prewarmed: bool,
+    /// Where the output was kept
+    pub output_path: Option<String>,
+    /// Times starting the run failed before it went through
+    pub retries: u64,
+    /// Whether judging was stopped as the program ran
+    pub interrupted: bool,
+    /// Limits of the run as given and as applied, if it started
+    pub limits: Option<(JsonLimitsV1, JsonLimitsV1)>,
+    /// Strength of the sandbox asked for and whether the filter was
+    /// installed, if the run started
+    pub sandbox: Option<(String, bool)>,
+    /// Taken from the result cache
+    pub cached: bool,
+    /// Taken from the stamp of an earlier run of the batch
+    pub resumed: bool,
+    /// Where it was judged, if there and then
+    pub environment: Option<JsonEnvironmentV1>,
+    /// The CPU a batch pinned it to
+    pub cpu: Option<u64>,
+    /// Whether the TLE may have been the host's fault rather than the
+    /// program's
+    pub timing_suspect: bool,
+    /// Most the judger woke up late while waiting for the program
+    pub scheduling_latency_ms: f64
+}
+
+/// A test of a batch with its result, as version 1 has it
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonTestV1 {
+    /// Name of the test
+    pub name: String,
+    /// Path of its input
+    pub input: String,
+    /// Path of its answer
+    pub answer: String,
+    /// Points it is worth
+    pub weight: f64,
+    /// What the program got on it
+    pub result: JsonResultV1
+}
+
+/// What a batch came to, as version 1 has it
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonAggregateV1 {
+    /// Abbreviation of the verdict
+    pub status: String,
+    /// The verdict in full
+    pub message: String,
+    /// Points earned
+    pub score: f64,
+    /// Points there were
+    pub max_score: f64,
+    /// Longest real time of a test, in milliseconds
+    pub max_time_ms: u64,
+    /// Longest CPU time of a test, in milliseconds
+    pub max_cpu_time_ms: u64,
+    /// Most memory a test took
+    pub max_memory_bytes: u64,
+    /// Mean time the judger itself took around a run
+    pub mean_judge_overhead_ms: f64
+}
+
+/// A batch as version 1 has it, which is what batch --json writes
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonSubmissionV1 {
+    /// Every test, in order
+    pub tests: Vec<JsonTestV1>,
+    /// The batch as a whole
+    pub aggregate: JsonAggregateV1
+}
+
+/// Executables judged on the same tests, as compare-runs --json writes
+/// them in version 1
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonComparisonV1 {
+    /// Each executable as given, with how it did
+    pub solutions: Vec<(String, JsonSubmissionV1)>,
+    /// Names of the tests the executables got verdicts of different
+    /// kinds on
+    pub disagreements: Vec<String>
+}
+
+/// How far timings and memory may move between two judgings of a test
+/// and still count as unchanged
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiffTolerance {
+    /// Most real and CPU time may move by, in percent of what they were
+    pub time_percent: f64,
+    /// Moves of time up to this many milliseconds count as unchanged
+    /// whatever their percent, runs of a few milliseconds jittering by
+    /// more than any percent would allow
+    pub time_ms: u64,
+    /// Most memory may move by, in percent of what it was
+    pub memory_percent: f64
+}
+
+impl Default for DiffTolerance {
+    fn default() -> Self {
+        DiffTolerance { time_percent: 5.0, time_ms: 10, memory_percent: 5.0 }
+    }
+}
+
+/// What changed between two results of a test, the old one judged first:
+/// each value as it was and as it is
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResultDiff {
+    /// Abbreviations of the verdicts
+    pub status: (String, String),
+    /// The verdicts in full, which tell apart verdicts of a kind, such as
+    /// the exit codes of RNZ
+    pub message: (String, String),
+    /// Where the outputs first differed from the answer
+    pub difference: (Option<String>, Option<String>),
+    /// Real times, in milliseconds
+    pub time_ms: (u64, u64),
+    /// CPU times, in milliseconds
+    pub cpu_time_ms: (u64, u64),
+    /// Peaks of memory in bytes
+    pub memory_bytes: (u64, u64)
+}
+
+/// Two batches as diff-results compares them in version 1: the tests of
+/// both paired by name, those that changed beyond the tolerance with how
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonResultsDiffV1 {
+    /// What counted as unchanged
+    pub tolerance: DiffTolerance,
+    /// The tests that changed, in the order of the new batch
+    pub changed: Vec<(String, ResultDiff)>,
+    /// How many tests of both batches didn't
+    pub unchanged: usize,
+    /// Tests only the new batch has
+    pub added: Vec<String>,
+    /// Tests only the old batch has
+    pub removed: Vec<String>
+}
+
+impl JsonLimitsV1 {
+    /**
+     *  The limits as version 1 has them
+     */
+    pub fn of(limits: &Limits) -> Self {
+        JsonLimitsV1 {
+            cpu_ms: limits.cpu.as_millis() as u64,
+            wall_ms: limits.wall.as_millis() as u64,
+            memory_bytes: limits.memory_bytes
+        }
+    }
+
+    /**
+     *  The limits as a JSON object
+     */
+    pub fn to_json(&self) -> String {
+        utils::json_object(&[
+            ("cpu_ms", self.cpu_ms.to_string()),
+            ("wall_ms", self.wall_ms.to_string()),
+            ("memory_bytes", self.memory_bytes.to_string())
+        ])
+    }
+
+    fn read(fields: &Fields) -> Result<Self, ReadError> {
+        Ok(JsonLimitsV1 {
+            cpu_ms: fields.count("cpu_ms")?,
+            wall_ms: fields.count("wall_ms")?,
+            memory_bytes: fields.count("memory_bytes")?
+        })
+    }
+}
+
+impl JsonEnvironmentV1 {
+    /**
+     *  The environment as version 1 has it
+     */
+    pub fn of(environment: &JudgeEnvironment) -> Self {
+        JsonEnvironmentV1 {
+            hostname: environment.hostname.clone(),
+            kernel: environment.kernel.clone(),
+            cpu_model: environment.cpu_model.clone(),
+            cores: environment.cores.map(|n| n as u64),
+            version: environment.version.to_string(),
+            seccomp: environment.seccomp,
+            calibration: environment.calibration.as_ref().map(|calibration| JsonCalibrationV1 {
+                score: calibration.score,
+                multiplier: calibration.multiplier,
+                cpu_time_ms: calibration.cpu_time.as_millis() as u64,
+                measured_at: calibration.measured_at
+            })
+        }
+    }
+
+    /**
+     *  The environment as a JSON object
+     */
+    pub fn to_json(&self) -> String {
+        utils::json_object(&[
+            ("hostname", optional(self.hostname.as_deref().map(utils::json_string))),
+            ("kernel", optional(self.kernel.as_deref().map(utils::json_string))),
+            ("cpu_model", optional(self.cpu_model.as_deref().map(utils::json_string))),
+            ("cores", optional(self.cores.map(|n| n.to_string()))),
+            ("version", utils::json_string(&self.version)),
+            ("seccomp", self.seccomp.to_string()),
+            ("calibration", optional(self.calibration.as_ref().map(|calibration| utils::json_object(&[
+                ("score", calibration.score.to_string()),
+                ("multiplier", calibration.multiplier.to_string()),
+                ("cpu_time_ms", calibration.cpu_time_ms.to_string()),
+                ("measured_at", calibration.measured_at.to_string())
+            ]))))
+        ])
+    }
+
+    fn read(fields: &Fields) -> Result<Self, ReadError> {
+        Ok(JsonEnvironmentV1 {
+            hostname: fields.optional_string("hostname")?,
+            kernel: fields.optional_string("kernel")?,
+            cpu_model: fields.optional_string("cpu_model")?,
+            cores: fields.optional_count("cores")?,
+            version: fields.optional_string("version")?.unwrap_or_default(),
+            seccomp: fields.flag("seccomp")?,
+            calibration: match fields.object("calibration")? {
+                Some(calibration) => Some(JsonCalibrationV1 {
+                    score: calibration.number("score")?,
+                    multiplier: calibration.number("multiplier")?,
+                    cpu_time_ms: calibration.count("cpu_time_ms")?,
+                    measured_at: calibration.count("measured_at")?
+                }),
+                None => None
+            }
+        })
+    }
+}
+
+impl JsonResultV1 {
+    /**
+     *  The result as version 1 has it, stderr left out
+     */
+    pub fn of(result: &JudgeResult) -> Self {
+        JsonResultV1 {
+            status: result.status.abbr().to_string(),
+            message: result.status.to_string(),
+            difference: result.difference.clone(),
+            time_ms: result.time_used.as_millis() as u64,
+            cpu_time_ms: result.cpu_time_ms,
+            memory_bytes: result.memory_used_bytes,
+            judge_overhead_ms: result.judge_overhead.as_secs_f64() * 1000.0,
+            tasks_peak: result.tasks_peak,
+            task_limit_hits: result.task_limit_hits,
+            cpu_throttled_ms: result.cpu_throttled.map(|t| t.as_millis() as u64),
+            max_threads: result.max_threads.map(u64::from),
+            io_read_bytes: result.io_read_bytes,
+            io_written_bytes: result.io_written_bytes,
+            io_limit_exceeded: result.io_limit_exceeded,
+            stderr_bytes: result.stderr_bytes,
+            stderr_truncated: result.stderr_truncated,
+            exec_sha256: result.exec_sha256.clone(),
+            prewarmed: result.prewarmed,
+            output_path: result.output_path.as_ref().map(|p| p.to_string_lossy().into_owned()),
+            retries: u64::from(result.retries),
+            interrupted: result.interrupted,
+            limits: result.limits.map(|l| (JsonLimitsV1::of(&l.base), JsonLimitsV1::of(&l.effective))),
+            sandbox: result.sandbox.map(|s| (s.strength.to_string(), s.seccomp)),
+            cached: result.cached,
+            resumed: result.resumed,
+            environment: result.environment.map(JsonEnvironmentV1::of),
+            cpu: result.cpu.map(|cpu| cpu as u64),
+            timing_suspect: result.timing_suspect,
+            scheduling_latency_ms: result.scheduling_latency.as_secs_f64() * 1000.0
+        }
+    }
+
+    /**
+     *  The result as a document of its own, naming its schema
+     */
+    pub fn to_json(&self) -> String {
+        document(Schema::V1, self.members())
+    }
+
+    /**
+     *  Read a result written in version 1, or before versions were named
+     */
+    pub fn parse(text: &str) -> Result<Self, ReadError> {
+        let value = json::parse(text).map_err(ReadError::Json)?;
+        let fields = Fields::of(String::from("result"), &value)?;
+        fields.schema()?;
+        JsonResultV1::read(&fields)
+    }
+
+    /**
+     *  The result as a member of another document, such as a request's
+     *  reply, which names the schema if anything does
+     */
+    pub fn to_nested_json(&self) -> String {
+        utils::json_object(&self.members())
+    }
+
+    fn members(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("status", utils::json_string(&self.status)),
+            ("message", utils::json_string(&self.message)),
+            ("difference", optional(self.difference.as_deref().map(utils::json_string))),
+            ("time_ms", self.time_ms.to_string()),
+            ("cpu_time_ms", self.cpu_time_ms.to_string()),
+            ("memory_bytes", self.memory_bytes.to_string()),
+            ("judge_overhead_ms", format!("{:.3}", self.judge_overhead_ms)),
+            ("tasks_peak", optional(self.tasks_peak.map(|n| n.to_string()))),
+            ("task_limit_hits", self.task_limit_hits.to_string()),
+            ("cpu_throttled_ms", optional(self.cpu_throttled_ms.map(|t| t.to_string()))),
+            ("max_threads", optional(self.max_threads.map(|n| n.to_string()))),
+            ("io_read_bytes", self.io_read_bytes.to_string()),
+            ("io_written_bytes", self.io_written_bytes.to_string()),
+            ("io_limit_exceeded", self.io_limit_exceeded.to_string()),
+            ("stderr_bytes", self.stderr_bytes.to_string()),
+            ("stderr_truncated", self.stderr_truncated.to_string()),
+            ("exec_sha256", optional(self.exec_sha256.as_deref().map(utils::json_string))),
+            ("prewarmed", self.prewarmed.to_string()),
+            ("output_path", optional(self.output_path.as_deref().map(utils::json_string))),
+            ("retries", self.retries.to_string()),
+            ("interrupted", self.interrupted.to_string()),
+            ("limits", optional(self.limits.as_ref().map(|(base, effective)| utils::json_object(&[
+                ("base", base.to_json()),
+                ("effective", effective.to_json())
+            ])))),
+            ("sandbox", optional(self.sandbox.as_ref().map(|(strength, seccomp)| utils::json_object(&[
+                ("strength", utils::json_string(strength)),
+                ("seccomp", seccomp.to_string())
+            ])))),
+            ("cached", self.cached.to_string()),
+            ("resumed", self.resumed.to_string()),
+            ("environment", optional(self.environment.as_ref().map(JsonEnvironmentV1::to_json))),
+            ("cpu", optional(self.cpu.map(|cpu| cpu.to_string()))),
+            ("timing_suspect", self.timing_suspect.to_string()),
+            ("scheduling_latency_ms", format!("{:.3}", self.scheduling_latency_ms))
+        ]
+    }
+
+    fn read(fields: &Fields) -> Result<Self, ReadError> {
+        let limits = match fields.object("limits")? {
+            Some(limits) => Some((JsonLimitsV1::read(&limits.required("base")?)?, JsonLimitsV1::read(&limits.required("effective")?)?)),
+            None => None
+        };
+        let sandbox = match fields.object("sandbox")? {
+            Some(sandbox) => Some((sandbox.string("strength")?, sandbox.flag("seccomp")?)),
+            None => None
+        };
+        let environment = match fields.object("environment")? {
+            Some(environment) => Some(JsonEnvironmentV1::read(&environment)?),
+            None => None
+        };
+        Ok(JsonResultV1 {
+            status: fields.string("status")?,
+            message: fields.string("message")?,
+            difference: fields.optional_string("difference")?,
+            time_ms: fields.count("time_ms")?,
+            cpu_time_ms: fields.count("cpu_time_ms")?,
+            memory_bytes: fields.count("memory_bytes")?,
+            judge_overhead_ms: fields.number("judge_overhead_ms")?,
+            tasks_peak: fields.optional_count("tasks_peak")?,
+            task_limit_hits: fields.count("task_limit_hits")?,
+            cpu_throttled_ms: fields.optional_count("cpu_throttled_ms")?,
+            max_threads: fields.optional_count("max_threads")?,
+            io_read_bytes: fields.count("io_read_bytes")?,
+            io_written_bytes: fields.count("io_written_bytes")?,
+            io_limit_exceeded: fields.flag("io_limit_exceeded")?,
+            stderr_bytes: fields.count("stderr_bytes")?,
+            stderr_truncated: fields.flag("stderr_truncated")?,
+            exec_sha256: fields.optional_string("exec_sha256")?,
+            prewarmed: fields.flag("prewarmed")?,
+            output_path: fields.optional_string("output_path")?,
+            retries: fields.count("retries")?,
+            interrupted: fields.flag("interrupted")?,
+            limits,
+            sandbox,
+            cached: fields.flag("cached")?,
+            resumed: fields.flag("resumed")?,
+            environment,
+            cpu: fields.optional_count("cpu")?,
+            timing_suspect: fields.flag("timing_suspect")?,
+            scheduling_latency_ms: fields.number("scheduling_latency_ms")?
+        })
+    }
+}
+
+impl JsonSubmissionV1 {
+    /**
+     *  The batch on `cases` as version 1 has it
+     */
+    pub fn of(submission: &SubmissionResult, cases: &[TestCase]) -> Self {
+        let tests = cases.iter().zip(&submission.results).map(|(case, result)| JsonTestV1 {
+            name: case.name(),
+            input: case.input.to_string_lossy().into_owned(),
+            answer: case.answer.to_string_lossy().into_owned(),
+            weight: case.weight,
+            result: JsonResultV1::of(result)
+        }).collect();
+        let aggregate = JsonAggregateV1 {
+            status: submission.status.abbr().to_string(),
+            message: submission.status.to_string(),
+            score: submission.score,
+            max_score: submission.max_score,
+            max_time_ms: submission.max_time.as_millis() as u64,
+            max_cpu_time_ms: submission.max_cpu_time_ms,
+            max_memory_bytes: submission.max_memory_bytes,
+            mean_judge_overhead_ms: submission.mean_judge_overhead.as_secs_f64() * 1000.0
+        };
+        JsonSubmissionV1 { tests, aggregate }
+    }
+
+    /**
+     *  The batch as a document of its own, naming its schema
+     */
+    pub fn to_json(&self) -> String {
+        document(Schema::V1, self.members())
+    }
+
+    /**
+     *  Read a batch written in version 1, or before versions were named
+     */
+    pub fn parse(text: &str) -> Result<Self, ReadError> {
+        let value = json::parse(text).map_err(ReadError::Json)?;
+        let fields = Fields::of(String::from("batch"), &value)?;
+        fields.schema()?;
+        JsonSubmissionV1::read(&fields)
+    }
+
+    fn members(&self) -> Vec<(&'static str, String)> {
+        let tests = self.tests.iter().map(|test| utils::json_object(&[
+            ("name", utils::json_string(&test.name)),
+            ("input", utils::json_string(&test.input)),
+            ("answer", utils::json_string(&test.answer)),
+            ("weight", test.weight.to_string()),
+            ("result", test.result.to_nested_json())
+        ]));
+        let aggregate = &self.aggregate;
+        let aggregate = utils::json_object(&[
+            ("status", utils::json_string(&aggregate.status)),
+            ("message", utils::json_string(&aggregate.message)),
+            ("score", aggregate.score.to_string()),
+            ("max_score", aggregate.max_score.to_string()),
+            ("max_time_ms", aggregate.max_time_ms.to_string()),
+            ("max_cpu_time_ms", aggregate.max_cpu_time_ms.to_string()),
+            ("max_memory_bytes", aggregate.max_memory_bytes.to_string()),
+            ("mean_judge_overhead_ms", format!("{:.3}", aggregate.mean_judge_overhead_ms))
+        ]);
+        vec![("tests", utils::json_list(tests)), ("aggregate", aggregate)]
+    }
+
+    fn read(fields: &Fields) -> Result<Self, ReadError> {
+        let mut tests = Vec::new();
+        for (index, test) in fields.array("tests")?.enumerate() {
+            let test = Fields::of(format!("{}.tests[{index}]", fields.path), test)?;
+            tests.push(JsonTestV1 {
+                name: test.string("name")?,
+                input: test.optional_string("input")?.unwrap_or_default(),
+                answer: test.optional_string("answer")?.unwrap_or_default(),
+                weight: test.number("weight")?,
+                result: JsonResultV1::read(&test.required("result")?)?
+            });
+        }
+        let aggregate = fields.required("aggregate")?;
+        let aggregate = JsonAggregateV1 {
+            status: aggregate.string("status")?,
+            message: aggregate.string("message")?,
+            score: aggregate.number("score")?,
+            max_score: aggregate.number("max_score")?,
+            max_time_ms: aggregate.count("max_time_ms")?,
+            max_cpu_time_ms: aggregate.count("max_cpu_time_ms")?,
+            max_memory_bytes: aggregate.count("max_memory_bytes")?,
+            mean_judge_overhead_ms: aggregate.number("mean_judge_overhead_ms")?
+        };
+        Ok(JsonSubmissionV1 { tests, aggregate })
+    }
+}
+
+impl JsonComparisonV1 {
+    /**
+     *  The comparison as a document of its own, naming its schema
+     */
+    pub fn to_json(&self) -> String {
+        let solutions = self.solutions.iter().map(|(executable, submission)| utils::json_object(&[
+            ("executable", utils::json_string(executable)),
+            ("submission", utils::json_object(&submission.members()))
+        ]));
+        let disagreements = self.disagreements.iter().map(|name| utils::json_string(name));
+        document(Schema::V1, vec![("solutions", utils::json_list(solutions)), ("disagreements", utils::json_list(disagreements))])
+    }
+}
+
+impl ResultDiff {
+    /**
+     *  How `new` differs from `old`
+     */
+    pub fn between(old: &JsonResultV1, new: &JsonResultV1) -> Self {
+        ResultDiff {
+            status: (old.status.clone(), new.status.clone()),
+            message: (old.message.clone(), new.message.clone()),
+            difference: (old.difference.clone(), new.difference.clone()),
+            time_ms: (old.time_ms, new.time_ms),
+            cpu_time_ms: (old.cpu_time_ms, new.cpu_time_ms),
+            memory_bytes: (old.memory_bytes, new.memory_bytes)
+        }
+    }
+
+    /// Whether the verdict is of another kind, such as AC turned WA
+    pub fn verdict_changed(&self) -> bool {
+        self.status.0 != self.status.1
+    }
+
+    /// Whether the verdict is the same kind but says otherwise, or the
+    /// output went wrong somewhere else
+    pub fn detail_changed(&self) -> bool {
+        !self.verdict_changed() && (self.message.0 != self.message.1 || self.difference.0 != self.difference.1)
+    }
+
+    /// How much the real time moved, in milliseconds
+    pub fn time_delta_ms(&self) -> i64 {
+        delta(self.time_ms)
+    }
+
+    /// How much the real time moved, in percent of what it was, None if
+    /// it was 0
+    pub fn time_delta_percent(&self) -> Option<f64> {
+        percent(self.time_ms)
+    }
+
+    /// How much the CPU time moved, in milliseconds
+    pub fn cpu_time_delta_ms(&self) -> i64 {
+        delta(self.cpu_time_ms)
+    }
+
+    /// How much the CPU time moved, in percent of what it was, None if it
+    /// was 0
+    pub fn cpu_time_delta_percent(&self) -> Option<f64> {
+        percent(self.cpu_time_ms)
+    }
+
+    /// How much the memory moved, in bytes
+    pub fn memory_delta_bytes(&self) -> i64 {
+        delta(self.memory_bytes)
+    }
+
+    /// How much the memory moved, in percent of what it was, None if it
+    /// was 0
+    pub fn memory_delta_percent(&self) -> Option<f64> {
+        percent(self.memory_bytes)
+    }
+
+    /**
+     *  Whether the real or the CPU time moved by more than `tolerance`
+     *  allows
+     */
+    pub fn time_changed(&self, tolerance: &DiffTolerance) -> bool {
+        [self.time_ms, self.cpu_time_ms].into_iter().any(|times| !within(times, tolerance.time_percent, tolerance.time_ms))
+    }
+
+    /**
+     *  Whether the memory moved by more than `tolerance` allows
+     */
+    pub fn memory_changed(&self, tolerance: &DiffTolerance) -> bool {
+        !within(self.memory_bytes, tolerance.memory_percent, 0)
+    }
+
+    /**
+     *  Whether anything changed beyond `tolerance`: the verdict, what it
+     *  says, the timings or the memory
+     */
+    pub fn changed(&self, tolerance: &DiffTolerance) -> bool {
+        self.verdict_changed() || self.detail_changed() || self.time_changed(tolerance) || self.memory_changed(tolerance)
+    }
+
+    /**
+     *  The diff as a JSON object, with what changed beyond `tolerance`
+     */
+    pub fn to_json(&self, tolerance: &DiffTolerance) -> String {
+        let side = |status: &str, message: &str, difference: &Option<String>, time_ms: u64, cpu_time_ms: u64, memory_bytes: u64| utils::json_object(&[
+            ("status", utils::json_string(status)),
+            ("message", utils::json_string(message)),
+            ("difference", optional(difference.as_deref().map(utils::json_string))),
+            ("time_ms", time_ms.to_string()),
+            ("cpu_time_ms", cpu_time_ms.to_string()),
+            ("memory_bytes", memory_bytes.to_string())
+        ]);
+        let percent = |value: Option<f64>| optional(value.map(|x| format!("{x:.1}")));
+        utils::json_object(&[
+            ("verdict_changed", self.verdict_changed().to_string()),
+            ("detail_changed", self.detail_changed().to_string()),
+            ("time_changed", self.time_changed(tolerance).to_string()),
+            ("memory_changed", self.memory_changed(tolerance).to_string()),
+            ("old", side(&self.status.0, &self.message.0, &self.difference.0, self.time_ms.0, self.cpu_time_ms.0, self.memory_bytes.0)),
+            ("new", side(&self.status.1, &self.message.1, &self.difference.1, self.time_ms.1, self.cpu_time_ms.1, self.memory_bytes.1)),
+            ("time_delta_ms", self.time_delta_ms().to_string()),
+            ("time_delta_percent", percent(self.time_delta_percent())),
+            ("cpu_time_delta_ms", self.cpu_time_delta_ms().to_string()),
+            ("cpu_time_delta_percent", percent(self.cpu_time_delta_percent())),
+            ("memory_delta_bytes", self.memory_delta_bytes().to_string()),
+            ("memory_delta_percent", percent(self.memory_delta_percent()))
+        ])
+    }
+}
+
+fn delta((old, new): (u64, u64)) -> i64 {
+    new as i64 - old as i64
+}
+
+fn percent((old, new): (u64, u64)) -> Option<f64> {
+    (old > 0).then(|| (new as f64 - old as f64) / old as f64 * 100.0)
+}
+
+/*
+ *  Whether a value moved by `slack` at most, or by `percent` at most of
+ *  what it was
+ */
+fn within((old, new): (u64, u64), percent: f64, slack: u64) -> bool {
+    let moved = old.abs_diff(new);
+    moved <= slack || moved as f64 <= old as f64 * percent / 100.0
+}
+
+impl JsonResultsDiffV1 {
+    /**
+     *  Compare the tests of `old` and `new` by name. A name both have
+     *  twice is paired in order.
+     */
+    pub fn between(old: &JsonSubmissionV1, new: &JsonSubmissionV1, tolerance: DiffTolerance) -> Self {
+        let mut unpaired: Vec<&JsonTestV1> = old.tests.iter().collect();
+        let mut diff = JsonResultsDiffV1 { tolerance, changed: Vec::new(), unchanged: 0, added: Vec::new(), removed: Vec::new() };
+        for test in &new.tests {
+            let Some(index) = unpaired.iter().position(|old| old.name == test.name) else {
+                diff.added.push(test.name.clone());
+                continue;
+            };
+            let result = ResultDiff::between(&unpaired.remove(index).result, &test.result);
+            match result.changed(&tolerance) {
+                true => diff.changed.push((test.name.clone(), result)),
+                false => diff.unchanged += 1
+            }
+        }
+        diff.removed = unpaired.iter().map(|test| test.name.clone()).collect();
+        diff
+    }
+
+    /// Whether any test changed, or is only in one of the batches
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /**
+     *  The diff as a document of its own, naming its schema
+     */
+    pub fn to_json(&self) -> String {
+        let tolerance = utils::json_object(&[
+            ("time_percent", self.tolerance.time_percent.to_string()),
+            ("time_ms", self.tolerance.time_ms.to_string()),
+            ("memory_percent", self.tolerance.memory_percent.to_string())
+        ]);
+        let changed = self.changed.iter().map(|(name, diff)| utils::json_object(&[
+            ("name", utils::json_string(name)),
+            ("diff", diff.to_json(&self.tolerance))
+        ]));
+        document(Schema::V1, vec![
+            ("tolerance", tolerance),
+            ("changed", utils::json_list(changed)),
+            ("unchanged", self.unchanged.to_string()),
+            ("added", utils::json_list(self.added.iter().map(|name| utils::json_string(name)))),
+            ("removed", utils::json_list(self.removed.iter().map(|name| utils::json_string(name))))
+        ])
+    }
+}
+
+/*
+ *  An object with "schema" naming `schema` ahead of `members`
+ */
+fn document(schema: Schema, mut members: Vec<(&'static str, String)>) -> String {
+    members.insert(0, ("schema", utils::json_string(schema.name())));
+    utils::json_object(&members)
+}
+
+fn optional(value: Option<String>) -> String {
+    value.unwrap_or_else(|| String::from("null"))
+}
+
+/*
+ *  The members of an object being read, where `path` is in the document.
+ *  Only what versions always had is required, a member missing or null
+ *  is otherwise taken as its default.
+ */
+struct Fields<'a> {
+    path: String,
+    members: &'a [(String, Value)]
+}
+
+impl<'a> Fields<'a> {
+    fn of(path: String, value: &'a Value) -> Result<Self, ReadError> {
+        match value {
+            Value::Object(members) => Ok(Fields { path, members }),
+            _ => Err(ReadError::Field { field: path, expected: "an object" })
+        }
+    }
+
+    /*
+     *  Check the schema the document names, if it names one: those
+     *  written before there were versions are version 1
+     */
+    fn schema(&self) -> Result<Schema, ReadError> {
+        match self.optional_string("schema")? {
+            None => Ok(Schema::V1),
+            Some(name) => Schema::from_name(&name).ok_or(ReadError::Schema(name))
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&'a Value> {
+        self.members.iter()
+            .find(|(member, _)| member == name)
+            .map(|(_, value)| value)
+            .filter(|value| !matches!(value, Value::Null))
+    }
+
+    fn wrong(&self, name: &str, expected: &'static str) -> ReadError {
+        ReadError::Field { field: format!("{}.{name}", self.path), expected }
+    }
+
+    fn string(&self, name: &str) -> Result<String, ReadError> {
+        self.optional_string(name)?.ok_or_else(|| self.wrong(name, "a string"))
+    }
+
+    fn optional_string(&self, name: &str) -> Result<Option<String>, ReadError> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s.clone())),
+            Some(_) => Err(self.wrong(name, "a string"))
+        }
+    }
+
+    fn number(&self, name: &str) -> Result<f64, ReadError> {
+        match self.get(name) {
+            None => Ok(0.0),
+            Some(Value::Number(x)) => Ok(*x),
+            Some(_) => Err(self.wrong(name, "a number"))
+        }
+    }
+
+    fn count(&self, name: &str) -> Result<u64, ReadError> {
+        Ok(self.optional_count(name)?.unwrap_or(0))
+    }
+
+    fn optional_count(&self, name: &str) -> Result<Option<u64>, ReadError> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(Value::Number(x)) if *x >= 0.0 && x.fract() == 0.0 => Ok(Some(*x as u64)),
+            Some(_) => Err(self.wrong(name, "a whole number"))
+        }
+    }
+
+    fn flag(&self, name: &str) -> Result<bool, ReadError> {
+        match self.get(name) {
+            None => Ok(false),
+            Some(Value::Boolean(b)) => Ok(*b),
+            Some(_) => Err(self.wrong(name, "true or false"))
+        }
+    }
+
+    fn object(&self, name: &str) -> Result<Option<Fields<'a>>, ReadError> {
+        self.get(name).map(|value| Fields::of(format!("{}.{name}", self.path), value)).transpose()
+    }
+
+    fn required(&self, name: &str) -> Result<Fields<'a>, ReadError> {
+        self.object(name)?.ok_or_else(|| self.wrong(name, "an object"))
+    }
+
+    fn array(&self, name: &str) -> Result<impl Iterator<Item = &'a Value>, ReadError> {
+        match self.get(name) {
+            Some(Value::Array(items)) => Ok(items.iter()),
+            _ => Err(self.wrong(name, "an array"))
+        }
+    }
+}
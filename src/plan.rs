@@ -0,0 +1,344 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use crate::compare::Comparison;
+use crate::judger::{AppliedLimits, IoMode, RetryPolicy, TerminationPolicy};
+use crate::secrun::{Deny, LaunchPlan, SyscallRule};
+use crate::utils::{self, json_list, json_string, PathReport};
+
+/// How a plan is printed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlanFormat {
+    /// Lines for people
+    Text,
+    /// A JSON object for tools
+    Json
+}
+
+/// What the output of a session is judged against
+#[derive(Clone, Debug)]
+pub enum PlannedAnswer {
+    /// The answer in this file
+    File(PathBuf),
+    /// This many bytes given in memory
+    Bytes(usize),
+    /// Sessions that only judge test cases
+    PerTest
+}
+
+/// Where the program of a session gets its input from
+#[derive(Clone, Debug)]
+pub enum PlannedInput {
+    /// Read from this file
+    File(PathBuf),
+    /// This many bytes given in memory
+    Bytes(usize),
+    /// Streamed from a reader
+    Stream,
+    /// /dev/null, no input at all
+    Null,
+    /// Sessions that only judge test cases
+    PerTest,
+    /// Already handed to an earlier run
+    None
+}
+
+/// Everything a run of a session would do, as worked out by the code that
+/// sets up real runs but without starting anything
+#[derive(Clone, Debug)]
+pub struct SessionPlan {
+    /// The executable given
+    pub exec: PathBuf,
+    /// What actually gets executed when a private copy is made
+    pub exec_copy: Option<PathBuf>,
+    /// Whether what runs is read into the page cache first
+    pub prewarm: bool,
+    /// Where the input comes from
+    pub input: PlannedInput,
+    /// The answer of the session's own test
+    pub answer: PlannedAnswer,
+    /// Limits of the runs
+    pub limits: AppliedLimits,
+    /// Unlimited if None
+    pub output_limit: Option<u64>,
+    /// Disk reads and writes together, unlimited if None
+    pub io_limit: Option<u64>,
+    /// Most of the program's stderr that is kept
+    pub stderr_limit: u64,
+    /// How the output is checked
+    pub comparison: Comparison,
+    /// Memory, task and CPU limits are enforced in a cgroup under this root
+    pub cgroup_root: Option<PathBuf>,
+    /// Most tasks the program may have in its cgroup
+    pub max_tasks: u64,
+    /// CPU cores the cgroup may use, unlimited if None
+    pub cpu_quota: Option<f64>,
+    /// Where the scratch directories of the runs go
+    pub scratch_dir: PathBuf,
+    /// How the program gets its input and hands in its output
+    pub io_mode: IoMode,
+    /// Files copied into the working directory, as (source, name there)
+    pub provided: Vec<(PathBuf, String)>,
+    /// How a program out of time is stopped
+    pub termination: TerminationPolicy,
+    /// How failing starts are retried
+    pub retry: RetryPolicy,
+    /// The programs that passed the strict path checks, None unless
+    /// they are on
+    pub path_checks: Option<Vec<PathReport>>,
+    pub(crate) launch: LaunchPlan
+}
+
+impl SessionPlan {
+    /**
+     *  The plan as a JSON object, for tools to check before trusting it
+     */
+    pub fn to_json(&self) -> String {
+        let path = |p: &PathBuf| json_string(&p.to_string_lossy());
+        let optional = |value: Option<String>| value.unwrap_or_else(|| String::from("null"));
+        let AppliedLimits { base, effective } = self.limits;
+        let launch = &self.launch;
+        let fields = vec![
+            ("exec", path(&self.exec)),
+            ("exec_copy", optional(self.exec_copy.as_ref().map(path))),
+            ("prewarm", self.prewarm.to_string()),
+            ("input", match &self.input {
+                PlannedInput::File(p) => format!("{{\"file\":{}}}", path(p)),
+                PlannedInput::Bytes(n) => format!("{{\"bytes\":{n}}}"),
+                PlannedInput::Stream => json_string("stream"),
+                PlannedInput::Null => json_string("null device"),
+                PlannedInput::PerTest => json_string("per test"),
+                PlannedInput::None => String::from("null")
+            }),
+            ("answer", match &self.answer {
+                PlannedAnswer::File(p) => path(p),
+                PlannedAnswer::Bytes(n) => format!("{{\"bytes\":{n}}}"),
+                PlannedAnswer::PerTest => String::from("null")
+            }),
+            ("interpreter", optional(launch.interpreted.as_ref().map(|_| path(&launch.path)))),
+            ("path_checks", optional(self.path_checks.as_ref().map(|reports| json_list(reports.iter().map(|report| {
+                format!("{{\"path\":{},\"resolved\":{}}}", path(&report.path), path(&report.resolved))
+            }))))),
+            ("argv", json_list(launch.argv.iter().map(|a| json_string(a)))),
+            // Names only, the values are the judger's own and may be secrets
+            ("env", json_list(launch.env.iter().map(|(key, _)| json_string(&key.to_string_lossy())))),
+            // Those the policy sets, TZ and the locale among them
+            ("set_env", format!("{{{}}}", launch.set_env.iter()
+                .map(|(name, value)| format!("{}:{}", json_string(name), json_string(value)))
+                .collect::<Vec<_>>()
+                .join(","))),
+            ("umask", json_string(&format!("{:03o}", launch.umask))),
+            ("cpu_limit_ms", effective.cpu.as_millis().to_string()),
+            ("wall_limit_ms", effective.wall.as_millis().to_string()),
+            ("memory_limit_bytes", effective.memory_bytes.to_string()),
+            ("base_cpu_limit_ms", base.cpu.as_millis().to_string()),
+            ("base_wall_limit_ms", base.wall.as_millis().to_string()),
+            ("base_memory_limit_bytes", base.memory_bytes.to_string()),
+            ("output_limit_bytes", optional(self.output_limit.map(|bytes| bytes.to_string()))),
+            ("io_limit_bytes", optional(self.io_limit.map(|bytes| bytes.to_string()))),
+            ("stderr_limit_bytes", self.stderr_limit.to_string()),
+            ("comparison", json_string(&self.comparison.to_string())),
+            ("rlimit_cpu", optional(launch.cpu_rlimit.map(|(soft, hard)| format!("{{\"soft\":{soft},\"hard\":{hard}}}")))),
+            ("rlimit_fsize", optional(launch.fsize_rlimit.map(|bytes| bytes.to_string()))),
+            ("cgroup_root", optional(self.cgroup_root.as_ref().map(path))),
+            ("max_tasks", self.max_tasks.to_string()),
+            ("cpu_quota", optional(self.cpu_quota.map(|c| c.to_string()))),
+            ("host_arch", json_string(&launch.host_arch.to_string())),
+            ("exec_arch", json_string(&launch.exec_arch.to_string())),
+            ("script", launch.script.to_string()),
+            ("loader", optional(launch.loader.as_ref().map(path))),
+            ("scratch_dir", path(&self.scratch_dir)),
+            ("work_dir", path(&launch.work_dir)),
+            ("tmpfs_size", launch.tmpfs_size.to_string()),
+            ("mount_namespace", launch.private_tmp.to_string()),
+            ("join_namespaces", json_list(launch.namespaces.iter().map(|ns| format!(
+                "{{\"kind\":{},\"path\":{}}}",
+                json_string(ns.kind.name()),
+                path(&ns.path)
+            )))),
+            ("sandbox_strength", json_string(&launch.strength.to_string())),
+            ("seccomp", launch.seccomp.to_string()),
+            ("supervise_writes", launch.supervise_writes.to_string()),
+            ("io", match &self.io_mode {
+                IoMode::Standard => json_string("standard"),
+                IoMode::NamedFiles { input_name, output_name } => {
+                    format!("{{\"input\":{},\"output\":{}}}", json_string(input_name), json_string(output_name))
+                }
+            }),
+            ("provided", json_list(self.provided.iter().map(|(source, name)| {
+                format!("{{\"source\":{},\"name\":{}}}", path(source), json_string(name))
+            }))),
+            ("termination", format!(
+                "{{\"term_signal\":{},\"grace_period_ms\":{},\"kill_signal\":{}}}",
+                optional(self.termination.term_signal.map(|s| s.to_string())),
+                self.termination.grace_period.as_millis(),
+                self.termination.kill_signal
+            )),
+            ("retry", format!(
+                "{{\"max_attempts\":{},\"backoff_ms\":{}}}",
+                self.retry.max_attempts,
+                self.retry.backoff.as_millis()
+            )),
+            ("steps", json_list(launch.steps.iter().map(|s| json_string(s)))),
+            ("denied_syscalls", json_list(launch.syscalls.iter().map(|rule| format!(
+                "{{\"syscall\":{},\"when\":{}}}",
+                json_string(rule.syscall),
+                json_string(&deny_summary(rule))
+            ))))
+        ];
+        let body: Vec<String> = fields.into_iter().map(|(key, value)| format!("  {}: {value}", json_string(key))).collect();
+        format!("{{\n{}\n}}", body.join(",\n"))
+    }
+}
+
+impl Display for SessionPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let launch = &self.launch;
+        f.write_fmt(format_args!("Executable:\t{}\n", self.exec.display()))?;
+        if let Some(copy) = &self.exec_copy {
+            f.write_fmt(format_args!("Run As Copy:\t{}\n", copy.display()))?;
+        }
+        if self.prewarm {
+            f.write_str("Prewarm:\tread into the page cache before each run\n")?;
+        }
+        if launch.interpreted.is_some() {
+            f.write_fmt(format_args!("Interpreter:\t{}\n", launch.path.display()))?;
+        }
+        for report in self.path_checks.iter().flatten() {
+            f.write_fmt(format_args!("Path Check:\t{report}\n"))?;
+        }
+        f.write_fmt(format_args!("Arguments:\t{:?}\n", launch.argv))?;
+        let set: Vec<String> = launch.set_env.iter().map(|(name, value)| format!("{name}={value}")).collect();
+        f.write_fmt(format_args!("Environment:\t{}, and {} variables of the judger's\n", set.join(" "), launch.env.len()))?;
+        f.write_fmt(format_args!("Umask:  \t{:03o}\n", launch.umask))?;
+        match &self.input {
+            PlannedInput::File(path) => f.write_fmt(format_args!("Input:  \t{}\n", path.display()))?,
+            PlannedInput::Bytes(n) => f.write_fmt(format_args!("Input:  \t{n} bytes in memory\n"))?,
+            PlannedInput::Stream => f.write_str("Input:  \tstreamed\n")?,
+            PlannedInput::Null => f.write_str("Input:  \tnone, /dev/null\n")?,
+            PlannedInput::PerTest => f.write_str("Input:  \tper test case\n")?,
+            PlannedInput::None => f.write_str("Input:  \tnone\n")?
+        }
+        match &self.answer {
+            PlannedAnswer::File(path) => f.write_fmt(format_args!("Answer:  \t{}\n", path.display()))?,
+            PlannedAnswer::Bytes(n) => f.write_fmt(format_args!("Answer:  \t{n} bytes in memory\n"))?,
+            PlannedAnswer::PerTest => f.write_str("Answer:  \tper test case\n")?
+        }
+        let AppliedLimits { base, effective } = self.limits;
+        // The problem's limits, when the language scaled them
+        let scaled_from = |scaled: bool, base: String| match scaled {
+            true => format!(" (scaled from {base})"),
+            false => String::new()
+        };
+        f.write_fmt(format_args!(
+            "CPU Time Limit:\t{}ms{}",
+            effective.cpu.as_millis(),
+            scaled_from(effective.cpu != base.cpu, format!("{}ms", base.cpu.as_millis()))
+        ))?;
+        if let Some((soft, hard)) = launch.cpu_rlimit {
+            f.write_fmt(format_args!(" (RLIMIT_CPU {soft}s soft, {hard}s hard)"))?;
+        }
+        f.write_fmt(format_args!(
+            "\nReal Time Limit:\t{}ms{}\n",
+            effective.wall.as_millis(),
+            scaled_from(effective.wall != base.wall, format!("{}ms", base.wall.as_millis()))
+        ))?;
+        f.write_fmt(format_args!(
+            "Memory Limit:\t{}{}\n",
+            utils::format_memory(effective.memory_bytes),
+            scaled_from(effective.memory_bytes != base.memory_bytes, utils::format_memory(base.memory_bytes))
+        ))?;
+        match (self.output_limit, launch.fsize_rlimit) {
+            (Some(bytes), Some(rlimit)) => {
+                f.write_fmt(format_args!("Output Limit:\t{} (RLIMIT_FSIZE {rlimit})\n", utils::format_memory(bytes)))?
+            },
+            _ => f.write_str("Output Limit:\tnone\n")?
+        }
+        match self.io_limit {
+            Some(bytes) => f.write_fmt(format_args!(
+                "I/O Limit:\t{} of disk reads and writes, files capped at it\n",
+                utils::format_memory(bytes)
+            ))?,
+            None => f.write_str("I/O Limit:\tnone\n")?
+        }
+        f.write_fmt(format_args!("Stderr Limit:\t{} kept, the rest dropped\n", utils::format_memory(self.stderr_limit)))?;
+        f.write_fmt(format_args!("Comparison:\t{}\n", self.comparison))?;
+        match &self.cgroup_root {
+            Some(root) => {
+                f.write_fmt(format_args!("Cgroup:  \tunder {}, at most {} tasks", root.display(), self.max_tasks))?;
+                if let Some(cores) = self.cpu_quota {
+                    f.write_fmt(format_args!(", {cores} cores"))?;
+                }
+                f.write_str("\n")?;
+            },
+            None => f.write_str("Cgroup:  \tnone, memory from rusage and sampling\n")?
+        }
+        f.write_fmt(format_args!("Architecture:\t{} on {}", launch.exec_arch, launch.host_arch))?;
+        match (launch.script, &launch.loader) {
+            (true, _) => f.write_str(", a script")?,
+            (false, Some(loader)) => f.write_fmt(format_args!(", loaded by {}", loader.display()))?,
+            (false, None) => f.write_str(", static")?
+        }
+        f.write_fmt(format_args!("\nScratch Dir:\t{}\n", self.scratch_dir.display()))?;
+        match launch.private_tmp {
+            true => f.write_fmt(format_args!(
+                "Private /tmp:\ttmpfs of {} in a new mount namespace, else {}\n",
+                utils::format_memory(launch.tmpfs_size),
+                launch.work_dir.display()
+            ))?,
+            false => f.write_fmt(format_args!("Private /tmp:\tnone, working in {}\n", launch.work_dir.display()))?
+        }
+        if !launch.namespaces.is_empty() {
+            let joined: Vec<String> = launch.namespaces.iter().map(|ns| format!("{} {}", ns.kind, ns.path.display())).collect();
+            f.write_fmt(format_args!("Namespaces:\tjoins {}\n", joined.join(", ")))?;
+        }
+        match &self.io_mode {
+            IoMode::Standard => f.write_str("I/O:    \tstdin and stdout\n")?,
+            IoMode::NamedFiles { input_name, output_name } => {
+                f.write_fmt(format_args!("I/O:    \tfiles {input_name} and {output_name}, writes supervised\n"))?
+            }
+        }
+        for (source, name) in &self.provided {
+            f.write_fmt(format_args!("Provided:\t{} as {name}, read-only\n", source.display()))?;
+        }
+        let TerminationPolicy { term_signal, grace_period, kill_signal } = self.termination;
+        match term_signal {
+            Some(signal) => f.write_fmt(format_args!(
+                "Termination:\tsignal {signal}, {}ms later signal {kill_signal}\n",
+                grace_period.as_millis()
+            ))?,
+            None => f.write_fmt(format_args!("Termination:\tsignal {kill_signal}\n"))?
+        }
+        f.write_fmt(format_args!(
+            "Retries:  \t{} attempts, waiting {}ms at first\n",
+            self.retry.max_attempts,
+            self.retry.backoff.as_millis()
+        ))?;
+        let names: Vec<String> = launch.env.iter().map(|(key, _)| key.to_string_lossy().into_owned()).collect();
+        f.write_fmt(format_args!("Environment:\tthe judger's, with TMPDIR set to /tmp or the work dir: {}", names.join(" ")))?;
+        f.write_str("\nSetup Steps:")?;
+        for (n, step) in launch.steps.iter().enumerate() {
+            f.write_fmt(format_args!("\n\t{}. {step}", n + 1))?;
+        }
+        if !launch.seccomp {
+            return f.write_fmt(format_args!("\nSandbox:\tNO seccomp filter ({}), nothing is denied", launch.strength));
+        }
+        f.write_fmt(format_args!("\nSandbox:\tseccomp filter ({})", launch.strength))?;
+        f.write_str("\nDenied Syscalls:")?;
+        for rule in &launch.syscalls {
+            f.write_fmt(format_args!("\n\t{:<16}{}", rule.syscall, deny_summary(rule)))?;
+        }
+        Ok(())
+    }
+}
+
+/*
+ *  When a rule denies its syscall, e.g. "always" or "arg 2 has 0x40"
+ */
+fn deny_summary(rule: &SyscallRule) -> String {
+    let conditions: Vec<String> = rule.deny.iter().map(|deny| match deny {
+        Deny::Always => String::from("always"),
+        Deny::FlagsSet { arg, flags } => format!("arg {arg} has {flags:#x}")
+    }).collect();
+    conditions.join(" or ")
+}
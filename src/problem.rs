@@ -0,0 +1,724 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::admission::{Admission, Demand};
+use crate::cache::BatchStamps;
+use crate::judger::{JudgeHandle, JudgeResult, JudgeSession, JudgeStatus};
+use crate::output::JsonSubmissionV1;
+use crate::utils::{self, host};
+
+/// One test of a problem
+#[derive(Clone, Debug)]
+pub struct TestCase {
+    /// The input file
+    pub input: PathBuf,
+    /// The answer the output is judged against
+    pub answer: PathBuf,
+    /// Name given by a manifest, the input's file stem is used otherwise
+    pub label: Option<String>,
+    /// Replaces the session's time limits for this test
+    pub time_limit: Option<Duration>,
+    /// Replaces the session's memory limit for this test
+    pub memory_limit: Option<u64>,
+    /// Share of the score the test is worth
+    pub weight: f64
+}
+
+impl TestCase {
+    /// A test of `input` and `answer` worth 1, with the session's limits
+    pub fn new(input: PathBuf, answer: PathBuf) -> Self {
+        TestCase { input, answer, label: None, time_limit: None, memory_limit: None, weight: 1.0 }
+    }
+
+    /// Name of the test for reports, by default the input file name
+    /// without extension
+    pub fn name(&self) -> String {
+        if let Some(label) = &self.label {
+            return label.clone();
+        }
+        self.input.file_stem().unwrap_or(self.input.as_os_str()).to_string_lossy().into_owned()
+    }
+}
+
+/// Which files of a tests directory make up its tests: every
+/// NAME.<input_ext> with the first of NAME.<answer_exts> that exists
+#[derive(Clone, Debug)]
+pub struct TestLayout {
+    /// Extension of the inputs, such as "in"
+    pub input_ext: String,
+    /// Extensions an answer may have, tried in order
+    pub answer_exts: Vec<String>
+}
+
+impl Default for TestLayout {
+    fn default() -> Self {
+        TestLayout { input_ext: String::from("in"), answer_exts: vec![String::from("ans"), String::from("out")] }
+    }
+}
+
+/**
+ *  Collect the test cases in `dir` as laid out by `layout`, in natural
+ *  order of their names so that 2 comes before 10
+ */
+pub fn test_cases_in(dir: &Path, layout: &TestLayout) -> io::Result<Vec<TestCase>> {
+    let answer_patterns: Vec<String> = layout.answer_exts.iter().map(|ext| format!("*.{ext}")).collect();
+    let cases = inputs_in(dir, layout)?;
+    if let Some(case) = cases.iter().find(|case| !case.answer.is_file()) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no answer ({}) for test input {}", answer_patterns.join(" or "), case.input.display())
+        ));
+    }
+    if cases.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no test cases (*.{} with {}) in {}", layout.input_ext, answer_patterns.join(" or "), dir.display())
+        ));
+    }
+    check_readable(&cases)?;
+    Ok(cases)
+}
+
+/**
+ *  The inputs in `dir` as laid out by `layout`, in natural order, each
+ *  with the first of its answers that exists. An input without any gets
+ *  where its answer would go instead, `NAME.<first answer extension>`.
+ */
+pub fn inputs_in(dir: &Path, layout: &TestLayout) -> io::Result<Vec<TestCase>> {
+    let mut cases = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| annotate(e, &format!("tests directory {}", dir.display())))? {
+        let input = entry?.path();
+        if input.extension().is_none_or(|ext| *ext != *layout.input_ext) {
+            continue;
+        }
+        let answer = layout.answer_exts.iter()
+            .map(|ext| input.with_extension(ext))
+            .find(|answer| answer.is_file())
+            .unwrap_or_else(|| input.with_extension(&layout.answer_exts[0]));
+        cases.push(TestCase::new(input, answer));
+    }
+    cases.sort_by(|a, b| natural_cmp(&a.name(), &b.name()));
+    Ok(cases)
+}
+
+/**
+ *  Run the trusted solution of `session` on the test's input and make
+ *  what it writes the test's answer, if it is accepted. The output goes
+ *  to a file from utils::create_beside first that is renamed into place,
+ *  so that no partial output ever becomes an answer and nothing planted
+ *  beside the answer is written through. An existing answer is replaced.
+ */
+pub fn write_answer(session: &JudgeSession, case: &TestCase, args: &[&str]) -> Result<JudgeResult, Box<dyn Error>> {
+    let (mut file, partial) = utils::create_beside(&case.answer)?;
+    let result = session.run_program_on(&case.input, args, &mut file);
+    match result {
+        Ok(result) if result.accepted() => {
+            fs::rename(&partial, &case.answer)?;
+            Ok(result)
+        },
+        result => {
+            let _ = fs::remove_file(&partial);
+            result
+        }
+    }
+}
+
+/**
+ *  Read the tests listed in a manifest, in the order given. Each line
+ *  names a test, its input and its answer, optionally followed by
+ *  weight=W, time=TIME and memory=SIZE. Blank lines and lines starting
+ *  with # are skipped. Relative paths are taken from `base`, the
+ *  manifest's own directory if None.
+ */
+pub fn test_cases_from_manifest(manifest: &Path, base: Option<&Path>) -> io::Result<Vec<TestCase>> {
+    let text = fs::read_to_string(manifest).map_err(|e| annotate(e, &format!("manifest {}", manifest.display())))?;
+    let base = base.or(manifest.parent()).unwrap_or(Path::new("."));
+    let mut cases: Vec<TestCase> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |what: String| io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}:{}: {what}", manifest.display(), number + 1)
+        );
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(input), Some(answer)) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(invalid(String::from("expected a name, an input and an answer")));
+        };
+        if cases.iter().any(|case| case.name() == name) {
+            return Err(invalid(format!("test {name} is listed twice")));
+        }
+        let mut case = TestCase::new(base.join(input), base.join(answer));
+        case.label = Some(name.to_string());
+        for field in fields {
+            let parsed = match field.split_once('=') {
+                Some(("weight", value)) => value.parse::<f64>().ok()
+                    .filter(|w| w.is_finite() && *w >= 0.0)
+                    .map(|w| case.weight = w),
+                Some(("time", value)) => utils::parse_duration(value).ok()
+                    .filter(|t| !t.is_zero())
+                    .map(|t| case.time_limit = Some(t)),
+                Some(("memory", value)) => utils::parse_size(value).ok()
+                    .filter(|&m| m > 0)
+                    .map(|m| case.memory_limit = Some(m)),
+                _ => return Err(invalid(format!("unknown field {field}, expected weight=, time= or memory=")))
+            };
+            if parsed.is_none() {
+                return Err(invalid(format!("invalid {field}")));
+            }
+        }
+        cases.push(case);
+    }
+    if cases.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no test cases listed in {}", manifest.display())
+        ));
+    }
+    check_readable(&cases)?;
+    Ok(cases)
+}
+
+/**
+ *  Open every input and answer, so that a bad file fails the batch
+ *  before anything is run instead of halfway through it
+ */
+pub fn check_readable(cases: &[TestCase]) -> io::Result<()> {
+    for case in cases {
+        for (what, path) in [("input", &case.input), ("answer", &case.answer)] {
+            let describe = |e| annotate(e, &format!("{what} of test {}, {}", case.name(), path.display()));
+            if !fs::metadata(path).map_err(describe)?.is_file() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{what} of test {}, {}: not a regular file", case.name(), path.display())
+                ));
+            }
+            fs::File::open(path).map_err(describe)?;
+        }
+    }
+    Ok(())
+}
+
+fn annotate(e: io::Error, what: &str) -> io::Error {
+    io::Error::new(e.kind(), format!("{what}: {e}"))
+}
+
+
+/*
+ *  Compare names with runs of digits ordered by their value
+ */
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_len = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let b_len = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (a_num, b_num) = (trim_zeros(&a[..a_len]), trim_zeros(&b[..b_len]));
+                let order = a_num.len().cmp(&b_num.len()).then(a_num.cmp(b_num));
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a = &a[a_len..];
+                b = &b[b_len..];
+            },
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&c| c == b'0').count();
+    &digits[zeros..]
+}
+
+/// Result of judging a program on all tests of a problem
+pub struct SubmissionResult {
+    /// Results of all tests in order, Skipped for those that weren't run
+    pub results: Vec<JudgeResult>,
+    /// The worst verdict among the tests, the earliest one on ties, or a
+    /// SystemError if the overall deadline cut the batch short
+    pub status: JudgeStatus,
+    /// Longest wall time any test took
+    pub max_time: Duration,
+    /// Most CPU time any test took
+    pub max_cpu_time_ms: u64,
+    /// Most memory any test used
+    pub max_memory_bytes: u64,
+    /// Average time it took to get the program of a test started
+    pub mean_judge_overhead: Duration,
+    /// Total weight of the accepted tests
+    pub score: f64,
+    /// Total weight of all tests, including those skipped
+    pub max_score: f64
+}
+
+impl SubmissionResult {
+    /// Whether every test was accepted
+    pub fn accepted(&self) -> bool {
+        matches!(self.status, JudgeStatus::Accepted)
+    }
+
+    /**
+     *  The result as a JSON object with the tests' results under "tests",
+     *  each with the name and weight of its test from `cases`, naming the
+     *  latest schema
+     */
+    pub fn to_json(&self, cases: &[TestCase]) -> String {
+        JsonSubmissionV1::of(self, cases).to_json()
+    }
+}
+
+impl Display for SubmissionResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("Status:  \t{}\n", self.status))?;
+        f.write_fmt(format_args!("Score:  \t{}/{}\n", self.score, self.max_score))?;
+        f.write_fmt(format_args!("Max Real Time:\t{}ms\n", self.max_time.as_millis()))?;
+        f.write_fmt(format_args!("Max CPU Time:\t{}ms\n", self.max_cpu_time_ms))?;
+        f.write_fmt(format_args!("Max Memory:\t{}\n", utils::format_memory(self.max_memory_bytes)))?;
+        f.write_fmt(format_args!("Judge Overhead:\t{:.2}ms avg", self.mean_judge_overhead.as_secs_f64() * 1000.0))?;
+        Ok(())
+    }
+}
+
+/// When a batch gives up on the tests left once some failed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Judge every test, for partial scores as IOI-style contests give
+    #[default]
+    RunAll,
+    /// Skip the tests after the first one that isn't accepted, as
+    /// ACM-style contests do
+    StopOnFirst,
+    /// Skip the tests after this many weren't accepted
+    StopAfter(usize)
+}
+
+impl FailurePolicy {
+    /**
+     *  Parse a policy such as run-all, stop-on-first or stop-after:3
+     */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.split_once(':') {
+            None if name == "run-all" => Some(Self::RunAll),
+            None if name == "stop-on-first" => Some(Self::StopOnFirst),
+            Some(("stop-after", count)) => match count.parse::<usize>() {
+                Ok(n) if n > 0 => Some(Self::StopAfter(n)),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    /// How many tests may fail before the rest are skipped, None for no
+    /// limit
+    pub fn max_failures(self) -> Option<usize> {
+        match self {
+            Self::RunAll => None,
+            Self::StopOnFirst => Some(1),
+            Self::StopAfter(n) => Some(n.max(1))
+        }
+    }
+}
+
+impl Display for FailurePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RunAll => f.write_str("run-all"),
+            Self::StopOnFirst => f.write_str("stop-on-first"),
+            Self::StopAfter(n) => f.write_fmt(format_args!("stop-after:{n}"))
+        }
+    }
+}
+
+/// Judges one program on the test cases of a problem, one after another,
+/// with the limits and sandbox settings of `session`
+pub struct ProblemJudge {
+    session: JudgeSession,
+    cases: Vec<TestCase>,
+    failure_policy: FailurePolicy,
+    // Pin each run of run_parallel to a CPU of its own, taken from cpus
+    // or from the physical cores where None
+    pin_cpus: bool,
+    cpus: Option<Vec<usize>>,
+    // Time the whole batch may take
+    overall_deadline: Option<Duration>,
+    // Memory the runs of run_parallel may reserve at once
+    memory_budget: Option<u64>,
+    stamps: Option<BatchStamps>
+}
+
+impl ProblemJudge {
+    /// Judge with `session` on `cases`, all of them and one at a time by
+    /// default
+    pub fn new(session: JudgeSession, cases: Vec<TestCase>) -> Self {
+        ProblemJudge {
+            session,
+            cases,
+            failure_policy: FailurePolicy::RunAll,
+            pin_cpus: false,
+            cpus: None,
+            overall_deadline: None,
+            memory_budget: None,
+            stamps: None
+        }
+    }
+
+    /// Skip the remaining tests once as many failed as `policy` allows,
+    /// which get Skipped. They are always skipped once the session was
+    /// cancelled.
+    pub fn with_failure_policy(mut self, policy: FailurePolicy) -> Self {
+        self.failure_policy = policy;
+        self
+    }
+
+    /// Skip the remaining tests once one isn't accepted, as
+    /// FailurePolicy::StopOnFirst does
+    pub fn with_stop_on_failure(self, stop_on_failure: bool) -> Self {
+        match stop_on_failure {
+            true => self.with_failure_policy(FailurePolicy::StopOnFirst),
+            false => self.with_failure_policy(FailurePolicy::RunAll)
+        }
+    }
+
+    /// Have run_parallel pin every run to a CPU of its own for steadier
+    /// timings, one CPU of each physical core by default so that no two
+    /// runs share the hyperthreads of a core. CPUs are handed out in turn
+    /// and taken back once their run is over. With fewer CPUs than jobs
+    /// nothing is pinned.
+    pub fn with_pin_cpus(mut self, pin_cpus: bool) -> Self {
+        self.pin_cpus = pin_cpus;
+        self
+    }
+
+    /// Pin the runs of run_parallel to `cpus` rather than to the physical
+    /// cores, as with_pin_cpus does
+    pub fn with_cpus(mut self, cpus: Vec<usize>) -> Self {
+        self.pin_cpus = true;
+        self.cpus = Some(cpus);
+        self
+    }
+
+    /// Give up on the batch once it took `deadline`, however far along it
+    /// is. The programs still running are killed and get Cancelled, the
+    /// tests not started yet are Skipped.
+    pub fn with_overall_deadline(mut self, deadline: Duration) -> Self {
+        self.overall_deadline = Some(deadline);
+        self
+    }
+
+    /// Have run_parallel judge a test only once its memory limit, scaled
+    /// for the language, fits in `bytes` besides those of the tests
+    /// running, so that a batch of large tests runs one after another
+    /// whatever the jobs. A test over `bytes` fails the batch before any
+    /// is judged.
+    pub fn with_memory_budget(mut self, bytes: u64) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    /// Stamp every judged test in `stamps`, and take the results of the
+    /// tests stamped there already for the same run instead of judging
+    /// them again, as for a batch that was interrupted. Results that
+    /// didn't judge the program, such as system errors, aren't stamped.
+    pub fn with_stamps(mut self, stamps: BatchStamps) -> Self {
+        self.stamps = Some(stamps);
+        self
+    }
+
+    /// The tests, in the order they are judged
+    pub fn cases(&self) -> &[TestCase] {
+        &self.cases
+    }
+
+    /// Judge the program on every test in order, passing it `args`
+    pub fn run(&self, args: &[&str]) -> Result<SubmissionResult, Box<dyn Error>> {
+        let deadline = self.overall_deadline.map(|d| Instant::now() + d);
+        let mut results = Vec::with_capacity(self.cases.len());
+        let mut failures = 0;
+        for case in &self.cases {
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                break;
+            }
+            let result = self.judge_case(case, args, deadline, None)
+                .map_err(|e| format!("test {}: {e}", case.name()))?;
+            let stop = match result.status {
+                JudgeStatus::Cancelled => true,
+                _ if result.accepted() => false,
+                _ => {
+                    failures += 1;
+                    self.failure_policy.max_failures().is_some_and(|max| failures >= max)
+                }
+            };
+            results.push(result);
+            if stop {
+                break;
+            }
+        }
+        Ok(self.summarize(results, deadline))
+    }
+
+    /**
+     *  Like run, but judge up to `jobs` tests at once. Each run has its own
+     *  scratch directory and process group already, so they don't get in
+     *  each other's way. Stopping for the failure policy keeps the tests
+     *  up to the failure it stops at in order, and the tests after it that
+     *  are still running are cancelled and dropped. So are those after the
+     *  first one cancelled by the overall deadline.
+     */
+    pub fn run_parallel(&self, jobs: usize, args: &[&str]) -> Result<SubmissionResult, Box<dyn Error>> {
+        let admission = self.memory_budget.map(|budget| Admission::new(Some(budget), Vec::new()));
+        if let Some(admission) = &admission {
+            for case in &self.cases {
+                admission.check(self.demand(case)).map_err(|e| format!("test {}: {e}", case.name()))?;
+            }
+        }
+        if jobs <= 1 || self.cases.len() <= 1 {
+            return self.run(args);
+        }
+        let workers = jobs.min(self.cases.len());
+        // The CPUs no run is pinned to at the moment, the one free longest first
+        let pool = self.cpu_pool(workers).map(Mutex::new);
+        let deadline = self.overall_deadline.map(|d| Instant::now() + d);
+        let next = AtomicUsize::new(0);
+        // Index of the test the batch stops at: the failure the policy stops
+        // at, or the first test that errored
+        let stop_at = AtomicUsize::new(usize::MAX);
+        // The tests that failed so far, in order
+        let failed: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+        // Cancels the test of each index while it runs
+        let running: Mutex<Vec<Option<JudgeHandle>>> = Mutex::new(vec![None; self.cases.len()]);
+        let slots: Mutex<Vec<Option<Result<JudgeResult, String>>>> =
+            Mutex::new((0..self.cases.len()).map(|_| None).collect());
+        let stop = |index: usize| {
+            stop_at.fetch_min(index, AtomicOrdering::SeqCst);
+            let running = running.lock().unwrap();
+            for handle in running[index + 1..].iter().flatten() {
+                handle.cancel();
+            }
+        };
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                let (next, stop_at, failed, running, slots, pool, stop, admission) =
+                    (&next, &stop_at, &failed, &running, &slots, &pool, &stop, &admission);
+                scope.spawn(move || {
+                    loop {
+                        let index = next.fetch_add(1, AtomicOrdering::SeqCst);
+                        if index >= self.cases.len() || index > stop_at.load(AtomicOrdering::SeqCst) {
+                            return;
+                        }
+                        let case = &self.cases[index];
+                        // Waits for the tests running to leave room for this one
+                        let _reservation = admission.as_ref().map(|admission| admission.reserve(self.demand(case)));
+                        if deadline.is_some_and(|d| Instant::now() >= d) {
+                            // Leaves the test unjudged, a gap the results stop at
+                            stop_at.fetch_min(index, AtomicOrdering::SeqCst);
+                            return;
+                        }
+                        let handle = JudgeHandle::new();
+                        running.lock().unwrap()[index] = Some(handle.clone());
+                        // The batch may have stopped before the handle was there to cancel
+                        if index > stop_at.load(AtomicOrdering::SeqCst) {
+                            return;
+                        }
+                        // There are as many CPUs as workers, so one is always free
+                        let cpu = pool.as_ref().and_then(|pool| pool.lock().unwrap().pop_front());
+                        // The thread forks the program, which keeps its affinity
+                        let pinned = cpu.filter(|&cpu| match host::pin_thread(&[cpu]) {
+                            Ok(()) => true,
+                            Err(e) => {
+                                warn!("cannot pin judging thread to CPU {cpu} ({e})");
+                                false
+                            }
+                        });
+                        let mut result = self.judge_case(case, args, deadline, Some(&handle))
+                            .map_err(|e| format!("test {}: {e}", case.name()));
+                        if let (Some(pool), Some(cpu)) = (pool, cpu) {
+                            pool.lock().unwrap().push_back(cpu);
+                        }
+                        // Results that didn't run here weren't pinned
+                        if let Ok(result) = result.as_mut() {
+                            if !result.resumed && !result.cached {
+                                result.cpu = pinned;
+                            }
+                        }
+                        running.lock().unwrap()[index] = None;
+                        let stop_index = match &result {
+                            Ok(r) if matches!(r.status, JudgeStatus::Cancelled) => Some(index),
+                            Ok(r) if r.accepted() => None,
+                            Ok(_) => self.failure_policy.max_failures().and_then(|max| {
+                                let mut failed = failed.lock().unwrap();
+                                let at = failed.partition_point(|&i| i < index);
+                                failed.insert(at, index);
+                                failed.get(max - 1).copied()
+                            }),
+                            Err(_) => Some(index)
+                        };
+                        if let Some(index) = stop_index {
+                            stop(index);
+                        }
+                        slots.lock().unwrap()[index] = Some(result);
+                    }
+                });
+            }
+        });
+
+        let mut results = Vec::with_capacity(self.cases.len());
+        let stop_at = stop_at.into_inner();
+        for (index, slot) in slots.into_inner().unwrap().into_iter().enumerate() {
+            if index > stop_at {
+                break;
+            }
+            match slot {
+                Some(Ok(result)) => results.push(result),
+                Some(Err(msg)) => return Err(msg.into()),
+                None => break
+            }
+        }
+        Ok(self.summarize(results, deadline))
+    }
+
+    /*
+     *  What a run of `case` reserves out of the memory budget
+     */
+    fn demand(&self, case: &TestCase) -> Demand {
+        Demand { memory_bytes: self.session.case_memory_limit(case), dedicated_core: false }
+    }
+
+    /*
+     *  The CPUs to pin the runs of `workers` to, if pinning, and so long as
+     *  there are enough of them to give every run its own
+     */
+    fn cpu_pool(&self, workers: usize) -> Option<VecDeque<usize>> {
+        if !self.pin_cpus {
+            return None;
+        }
+        let allowed = match host::allowed_cpus() {
+            Ok(allowed) => allowed,
+            Err(e) => {
+                warn!("cannot tell the CPUs the judger may run on ({e}), judging without pinning");
+                return None;
+            }
+        };
+        let cpus = match &self.cpus {
+            Some(cpus) => {
+                let (usable, others): (Vec<usize>, Vec<usize>) = cpus.iter().partition(|cpu| allowed.contains(cpu));
+                if !others.is_empty() {
+                    warn!("the judger may not run on CPUs {others:?}, leaving them out");
+                }
+                usable
+            },
+            None => host::physical_cores(&allowed)
+        };
+        if cpus.len() < workers {
+            warn!("{} CPUs to pin {workers} jobs to, judging without pinning", cpus.len());
+            return None;
+        }
+        debug!("pinning runs to CPUs {cpus:?}");
+        Some(cpus.into())
+    }
+
+    /*
+     *  Judge one test, or take its result from its stamp. Trouble with the
+     *  stamps only costs the test its stamp.
+     */
+    fn judge_case(
+        &self,
+        case: &TestCase,
+        args: &[&str],
+        deadline: Option<Instant>,
+        cancel: Option<&JudgeHandle>
+    ) -> Result<JudgeResult, Box<dyn Error>> {
+        let Some(stamps) = &self.stamps else {
+            return self.session.run_batch_case(case, args, deadline, cancel);
+        };
+        let name = case.name();
+        let key = match self.session.case_key(case, args) {
+            Ok(Some(key)) => key,
+            Ok(None) => return self.session.run_batch_case(case, args, deadline, cancel),
+            Err(e) => {
+                debug!("not stamping test {name}: {e}");
+                return self.session.run_batch_case(case, args, deadline, cancel);
+            }
+        };
+        if let Some(result) = stamps.get(&name, &key) {
+            debug!("test {name} resumed as {}", result.status.abbr());
+            return Ok(result);
+        }
+        let result = self.session.run_batch_case(case, args, deadline, cancel)?;
+        if let Err(e) = stamps.put(&name, &key, &result) {
+            debug!("cannot stamp test {name}: {e}");
+        }
+        Ok(result)
+    }
+
+    /*
+     *  Sum up the results of the tests that were run, the first ones in
+     *  order, the rest becoming Skipped. Having stopped early with the
+     *  deadline passed means the deadline cut the batch short.
+     */
+    fn summarize(&self, mut results: Vec<JudgeResult>, deadline: Option<Instant>) -> SubmissionResult {
+        let cut_short = results.len() < self.cases.len()
+            || results.last().is_some_and(|r| matches!(r.status, JudgeStatus::Cancelled));
+        let deadline_hit = cut_short && deadline.is_some_and(|d| Instant::now() >= d);
+        let mean_judge_overhead = match results.len() {
+            0 => Duration::ZERO,
+            n => results.iter().map(|r| r.judge_overhead).sum::<Duration>() / n as u32
+        };
+        while results.len() < self.cases.len() {
+            results.push(JudgeResult::unfinished(JudgeStatus::Skipped));
+        }
+
+        let mut status = JudgeStatus::Accepted;
+        let mut score = 0.0;
+        for (result, case) in results.iter().zip(&self.cases) {
+            if result.status.severity() > status.severity() {
+                status = result.status.clone();
+            }
+            if result.accepted() {
+                score += case.weight;
+            }
+        }
+        if deadline_hit {
+            let judged = results.iter()
+                .filter(|r| !matches!(r.status, JudgeStatus::Cancelled | JudgeStatus::Skipped))
+                .count();
+            status = JudgeStatus::SystemError(format!(
+                "overall deadline of {}ms exceeded, {judged} of {} tests judged",
+                self.overall_deadline.unwrap_or_default().as_millis(),
+                self.cases.len()
+            ));
+        }
+        SubmissionResult {
+            status,
+            max_time: results.iter().map(|r| r.time_used).max().unwrap_or_default(),
+            max_cpu_time_ms: results.iter().map(|r| r.cpu_time_ms).max().unwrap_or_default(),
+            max_memory_bytes: results.iter().map(|r| r.memory_used_bytes).max().unwrap_or_default(),
+            mean_judge_overhead,
+            score,
+            max_score: self.cases.iter().map(|c| c.weight).sum(),
+            results
+        }
+    }
+}
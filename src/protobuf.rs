@@ -0,0 +1,159 @@
+// The protobuf wire format, as much of it as the messages of the gRPC
+// service use: varints and length-delimited fields. Other wire types are
+// read past so that fields added by newer clients are skipped.
+
+/// A field of a message as read, by its wire type
+#[derive(Debug)]
+pub enum FieldValue<'a> {
+    Varint(u64),
+    // No field of the service is fixed-width, so their values are skipped
+    Fixed64,
+    Fixed32,
+    Bytes(&'a [u8])
+}
+
+/// Why a message can't be read
+#[derive(Debug)]
+pub struct DecodeError(pub String);
+
+/// Reads the fields of a message one after another
+pub struct Reader<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.data.split_first().ok_or_else(|| DecodeError(String::from("truncated varint")))?;
+            self.data = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError(String::from("varint longer than 10 bytes")))
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], DecodeError> {
+        if self.data.len() < length {
+            return Err(DecodeError(String::from("truncated field")));
+        }
+        let (taken, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    /*
+     *  The next field's number and value, None at the end of the message
+     */
+    pub fn field(&mut self) -> Result<Option<(u32, FieldValue<'a>)>, DecodeError> {
+        if self.data.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let number = u32::try_from(key >> 3).ok().filter(|&n| n > 0).ok_or_else(|| DecodeError(format!("bad field number {}", key >> 3)))?;
+        let value = match key & 7 {
+            0 => FieldValue::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                FieldValue::Fixed64
+            },
+            2 => {
+                let length = usize::try_from(self.varint()?).map_err(|_| DecodeError(String::from("field too long")))?;
+                FieldValue::Bytes(self.take(length)?)
+            },
+            5 => {
+                self.take(4)?;
+                FieldValue::Fixed32
+            },
+            wire => return Err(DecodeError(format!("unsupported wire type {wire} of field {number}")))
+        };
+        Ok(Some((number, value)))
+    }
+}
+
+impl FieldValue<'_> {
+    pub fn as_u64(&self, field: &str) -> Result<u64, DecodeError> {
+        match self {
+            FieldValue::Varint(value) => Ok(*value),
+            _ => Err(DecodeError(format!("{field} must be a varint")))
+        }
+    }
+
+    pub fn as_bool(&self, field: &str) -> Result<bool, DecodeError> {
+        Ok(self.as_u64(field)? != 0)
+    }
+
+    pub fn as_bytes(&self, field: &str) -> Result<&[u8], DecodeError> {
+        match self {
+            FieldValue::Bytes(bytes) => Ok(bytes),
+            _ => Err(DecodeError(format!("{field} must be length-delimited")))
+        }
+    }
+
+    pub fn as_string(&self, field: &str) -> Result<String, DecodeError> {
+        let bytes = self.as_bytes(field)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError(format!("{field} is not UTF-8")))
+    }
+}
+
+/// Writes the fields of a message. Fields at their default value are
+/// left out, as proto3 does.
+#[derive(Default)]
+pub struct Writer {
+    data: Vec<u8>
+}
+
+impl Writer {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.data.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.data.push(value as u8);
+    }
+
+    fn key(&mut self, number: u32, wire: u64) {
+        self.varint(u64::from(number) << 3 | wire);
+    }
+
+    pub fn u64(&mut self, number: u32, value: u64) {
+        if value != 0 {
+            self.key(number, 0);
+            self.varint(value);
+        }
+    }
+
+    pub fn bool(&mut self, number: u32, value: bool) {
+        self.u64(number, u64::from(value));
+    }
+
+    pub fn bytes(&mut self, number: u32, value: &[u8]) {
+        if !value.is_empty() {
+            self.message(number, value);
+        }
+    }
+
+    pub fn string(&mut self, number: u32, value: &str) {
+        self.bytes(number, value.as_bytes());
+    }
+
+    /*
+     *  A length-delimited field written even when empty, for embedded
+     *  messages, whose presence means something
+     */
+    pub fn message(&mut self, number: u32, encoded: &[u8]) {
+        self.key(number, 2);
+        self.varint(encoded.len() as u64);
+        self.data.extend_from_slice(encoded);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
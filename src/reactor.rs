@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::pin::Pin;
+use std::sync::{Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Instant;
+
+// The token of the eventfd waking the reactor's thread, which no wait gets
+const NOTIFY: u64 = 0;
+
+/// Wakes the futures of the runs judged asynchronously once their child
+/// may have exited or their timeout passed, from a thread of its own
+struct Reactor {
+    epoll: OwnedFd,
+    // Written to make the thread look at the timeouts again
+    notify: OwnedFd,
+    waits: Mutex<Waits>
+}
+
+#[derive(Default)]
+struct Waits {
+    last_token: u64,
+    pending: HashMap<u64, Pending>
+}
+
+struct Pending {
+    waker: Waker,
+    until: Instant,
+    // Whether the descriptor turned readable or the timeout passed
+    ready: bool
+}
+
+impl Reactor {
+    /*
+     *  The reactor of the process, its thread started by the first wait
+     */
+    fn get() -> io::Result<&'static Reactor> {
+        static REACTOR: OnceLock<Result<&'static Reactor, String>> = OnceLock::new();
+        let reactor = REACTOR.get_or_init(|| {
+            let reactor: &'static Reactor = Box::leak(Box::new(Reactor::new().map_err(|e| e.to_string())?));
+            thread::Builder::new()
+                .name(String::from("judger-reactor"))
+                .spawn(|| reactor.run())
+                .map_err(|e| e.to_string())?;
+            Ok(reactor)
+        });
+        reactor.as_ref().copied().map_err(|msg| io::Error::other(format!("cannot start the reactor: {msg}")))
+    }
+
+    fn new() -> io::Result<Reactor> {
+        let epoll = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epoll < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let epoll = unsafe { OwnedFd::from_raw_fd(epoll) };
+        let notify = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if notify < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let notify = unsafe { OwnedFd::from_raw_fd(notify) };
+        let reactor = Reactor { epoll, notify, waits: Mutex::new(Waits::default()) };
+        reactor.control(libc::EPOLL_CTL_ADD, reactor.notify.as_raw_fd(), libc::EPOLLIN as u32, NOTIFY)?;
+        Ok(reactor)
+    }
+
+    fn control(&self, op: i32, fd: i32, events: u32, token: u64) -> io::Result<()> {
+        let mut event = libc::epoll_event { events, u64: token };
+        if unsafe { libc::epoll_ctl(self.epoll.as_raw_fd(), op, fd, &mut event) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /*
+     *  Wake `waker` once `fd` turns readable or at `until`, returning the
+     *  token the wait is known by
+     */
+    fn register(&self, fd: Option<BorrowedFd<'_>>, until: Instant, waker: &Waker) -> io::Result<u64> {
+        let token = {
+            let mut waits = self.waits.lock().unwrap_or_else(|e| e.into_inner());
+            waits.last_token += 1;
+            let token = waits.last_token;
+            waits.pending.insert(token, Pending { waker: waker.clone(), until, ready: false });
+            token
+        };
+        if let Some(fd) = fd {
+            // Once is enough, the wait is over when it turns readable
+            let events = (libc::EPOLLIN | libc::EPOLLONESHOT) as u32;
+            if let Err(e) = self.control(libc::EPOLL_CTL_ADD, fd.as_raw_fd(), events, token) {
+                self.waits.lock().unwrap_or_else(|e| e.into_inner()).pending.remove(&token);
+                return Err(e);
+            }
+        }
+        // The thread may be sleeping past the new timeout
+        let one: u64 = 1;
+        unsafe {
+            libc::write(self.notify.as_raw_fd(), (&one as *const u64).cast(), 8);
+        }
+        Ok(token)
+    }
+
+    fn deregister(&self, token: u64, fd: Option<BorrowedFd<'_>>) {
+        self.waits.lock().unwrap_or_else(|e| e.into_inner()).pending.remove(&token);
+        if let Some(fd) = fd {
+            let _ = self.control(libc::EPOLL_CTL_DEL, fd.as_raw_fd(), 0, token);
+        }
+    }
+
+    fn run(&self) {
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; 64];
+        loop {
+            let next = self.waits.lock().unwrap_or_else(|e| e.into_inner())
+                .pending
+                .values()
+                .filter(|p| !p.ready)
+                .map(|p| p.until)
+                .min();
+            // Rounded up, so that timeouts never end early
+            let timeout = match next {
+                Some(until) => {
+                    let left = until.saturating_duration_since(Instant::now()).as_nanos().div_ceil(1_000_000);
+                    left.min(i32::MAX as u128) as i32
+                },
+                None => -1
+            };
+            let count = unsafe {
+                libc::epoll_wait(self.epoll.as_raw_fd(), events.as_mut_ptr(), events.len() as i32, timeout)
+            };
+            let mut woken = Vec::new();
+            let mut waits = self.waits.lock().unwrap_or_else(|e| e.into_inner());
+            for event in &events[..count.max(0) as usize] {
+                let token = event.u64;
+                if token == NOTIFY {
+                    let mut count = [0u8; 8];
+                    unsafe {
+                        libc::read(self.notify.as_raw_fd(), count.as_mut_ptr().cast(), count.len());
+                    }
+                } else if let Some(pending) = waits.pending.get_mut(&token) {
+                    pending.ready = true;
+                    woken.push(pending.waker.clone());
+                }
+            }
+            let now = Instant::now();
+            for pending in waits.pending.values_mut().filter(|p| !p.ready && p.until <= now) {
+                pending.ready = true;
+                woken.push(pending.waker.clone());
+            }
+            drop(waits);
+            woken.into_iter().for_each(Waker::wake);
+        }
+    }
+}
+
+/// Done once `fd` turns readable or at `until`, whichever comes first,
+/// only at `until` without a descriptor
+pub(crate) struct Ready<'a> {
+    fd: Option<BorrowedFd<'a>>,
+    until: Instant,
+    // Set once the reactor knows of the wait
+    token: Option<u64>
+}
+
+/*
+ *  Wait for `fd` to turn readable until `until` at the latest
+ */
+pub(crate) fn ready(fd: Option<BorrowedFd<'_>>, until: Instant) -> Ready<'_> {
+    Ready { fd, until, token: None }
+}
+
+impl Future for Ready<'_> {
+    type Output = io::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if Instant::now() >= self.until {
+            return Poll::Ready(Ok(()));
+        }
+        let reactor = Reactor::get()?;
+        let Some(token) = self.token else {
+            self.token = Some(reactor.register(self.fd, self.until, cx.waker())?);
+            return Poll::Pending;
+        };
+        let mut waits = reactor.waits.lock().unwrap_or_else(|e| e.into_inner());
+        match waits.pending.get_mut(&token) {
+            Some(pending) if !pending.ready => {
+                pending.waker.clone_from(cx.waker());
+                Poll::Pending
+            },
+            _ => Poll::Ready(Ok(()))
+        }
+    }
+}
+
+impl Drop for Ready<'_> {
+    fn drop(&mut self) {
+        if let (Some(token), Ok(reactor)) = (self.token, Reactor::get()) {
+            reactor.deregister(token, self.fd);
+        }
+    }
+}
+
+/// Done on its second poll, letting the other futures of the executor run
+/// in between
+pub(crate) struct YieldNow {
+    yielded: bool
+}
+
+pub(crate) fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
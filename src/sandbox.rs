@@ -0,0 +1,13 @@
+pub use crate::secrun::{default_syscall_rules, Deny, InputSource, PolicyError, SandboxPolicy, SandboxStrength, StopAction, SyscallRule};
+pub use crate::secrun::PROGRAM_UMASK;
+pub use crate::secrun::{JoinNamespace, Namespace, NamespaceError};
+pub use crate::secrun::{
+    LinuxSandbox, NamedFiles, ResourceUsage, RunSpec, Sandbox, SandboxChildHandle, SandboxIo, WaitOutcome
+};
+pub use crate::utils::elf::ExecArch;
+pub use crate::scratch::ScratchDir;
+#[cfg(feature = "testing")]
+pub use crate::mock::{MockExit, MockRun, MockSandbox};
+
+#[doc(hidden)]
+pub use crate::secrun::{compile_filters, inject_fork_failures};
@@ -0,0 +1,221 @@
+use std::ffi::CString;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::metrics::METRICS;
+use crate::utils;
+
+static RUN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// What the names of scratch directories start with unless a session
+/// says otherwise
+pub const DEFAULT_PREFIX: &str = "secure-judger";
+
+// Name of the private copy of the executable
+const EXEC_COPY_NAME: &str = "program";
+// Name of the program's working directory
+const WORK_DIR_NAME: &str = "work";
+
+/// A private directory for a single run, holding the captured output and
+/// the program's working directory. It is only accessible to the judger's
+/// user and is removed together with everything in it on drop, so every
+/// exit path of a run cleans up, unless it was told to keep it. The files
+/// of the judger's own are created relative to the directory as it was
+/// opened once made, as new files and never through a symlink, so that
+/// nothing planted on the way or at their names redirects them.
+pub struct ScratchDir {
+    path: PathBuf,
+    // The directory itself, opened without following a symlink
+    dir: OwnedFd,
+    keep: bool
+}
+
+impl ScratchDir {
+    /**
+     *  Create a fresh 0700 directory under `base`. The name is made
+     *  unpredictable so other users can't prepare anything in its place.
+     */
+    pub fn create(base: &Path) -> io::Result<Self> {
+        ScratchDir::create_named(base, DEFAULT_PREFIX)
+    }
+
+    /**
+     *  Create a fresh directory under `base` as create does, its name
+     *  starting with `prefix`, a file name
+     */
+    pub fn create_named(base: &Path, prefix: &str) -> io::Result<Self> {
+        const ATTEMPTS: usize = 8;
+
+        let mut last_err = None;
+        for _ in 0..ATTEMPTS {
+            let counter = RUN_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = base.join(dir_name(prefix, counter, &format!("{:016x}", random_u64()?)));
+            // mkdir never follows a symlink planted at the path
+            match DirBuilder::new().mode(0o700).create(&path) {
+                Ok(()) => {},
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    last_err = Some(e);
+                    continue;
+                },
+                Err(e) => return Err(e)
+            }
+            let dir = match open_private_dir(&path) {
+                Ok(dir) => dir,
+                Err(e) => {
+                    let _ = fs::remove_dir(&path);
+                    return Err(e);
+                }
+            };
+            METRICS.scratch_created();
+            let scratch = ScratchDir { path, dir, keep: false };
+            DirBuilder::new().mode(0o700).create(scratch.work_dir())?;
+            return Ok(scratch);
+        }
+        Err(last_err.unwrap_or_else(|| io::Error::other("cannot create scratch directory")))
+    }
+
+    /// The directory itself
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Leave the directory in place on drop, e.g. to inspect a run
+    pub fn keep(&mut self) {
+        self.keep = true;
+    }
+
+    /// Working directory of the program when it can't get a private /tmp
+    pub fn work_dir(&self) -> PathBuf {
+        self.path.join(WORK_DIR_NAME)
+    }
+
+    /// Where to keep a file of the judger's own, out of the program's sight
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /**
+     *  Create the judger's file `name` in the directory with `mode`, failing
+     *  if anything is at the name already. `name` has to be a file name,
+     *  see utils::sanitize_file_name for names users had a say in.
+     */
+    pub fn create_file(&self, name: &str, mode: u32) -> io::Result<File> {
+        create_at(&self.dir, name, mode)
+    }
+
+    /**
+     *  Copy the executable at `exec` into the directory as a read-only file
+     *  owned by the judger, so that it can't be swapped out before the exec
+     *  or be read off a slow filesystem while running. std copies between
+     *  files with copy_file_range where possible.
+     */
+    pub fn copy_executable(&self, exec: &Path) -> io::Result<PathBuf> {
+        let mut source = File::open(exec)?;
+        let mut copy = self.create_file(EXEC_COPY_NAME, 0o555)?;
+        io::copy(&mut source, &mut copy)?;
+        Ok(self.file(EXEC_COPY_NAME))
+    }
+}
+
+/// Paths the next scratch directory under a base would have, with a
+/// placeholder for the random part of its name, for describing a run
+pub(crate) struct PlannedScratch {
+    pub path: PathBuf,
+    pub work_dir: PathBuf,
+    pub exec_copy: PathBuf
+}
+
+impl PlannedScratch {
+    pub fn under(base: &Path, prefix: &str) -> Self {
+        let path = base.join(dir_name(prefix, RUN_COUNTER.load(Ordering::Relaxed), "RANDOM"));
+        PlannedScratch {
+            work_dir: path.join(WORK_DIR_NAME),
+            exec_copy: path.join(EXEC_COPY_NAME),
+            path
+        }
+    }
+}
+
+fn dir_name(prefix: &str, counter: u64, random: &str) -> String {
+    format!("{prefix}-{}-{counter}-{random}", std::process::id())
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        METRICS.scratch_removed();
+        if !self.keep {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+/**
+ *  Move the file at `from` to `to`, replacing what is there, and copying
+ *  it when they are on different filesystems. `from` is moved or read as
+ *  the file it is, never through a symlink, and a symlink or a hard link
+ *  planted at `to` is what gets replaced rather than written through: the
+ *  copy goes to a new file from utils::create_beside that is renamed over
+ *  it. `from`
+ *  stays behind after a copy, for its scratch directory to take.
+ */
+pub(crate) fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {},
+        r => return r
+    }
+    let mut source = OpenOptions::new().read(true).custom_flags(libc::O_NOFOLLOW).open(from)?;
+    let (mut copy, temp) = utils::create_beside(to)?;
+    match io::copy(&mut source, &mut copy).and_then(|_| fs::rename(&temp, to)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+/*
+ *  Open the directory just made at `path` as itself, refusing a symlink
+ *  or anything else swapped in for it since: it has to be a directory of
+ *  the judger's user that no one else has access to
+ */
+fn open_private_dir(path: &Path) -> io::Result<OwnedFd> {
+    let dir = OpenOptions::new().read(true).custom_flags(libc::O_DIRECTORY | libc::O_NOFOLLOW).open(path)?;
+    let metadata = dir.metadata()?;
+    let euid = unsafe { libc::geteuid() };
+    if metadata.uid() != euid || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{} is not private to the judger", path.display())));
+    }
+    Ok(dir.into())
+}
+
+/*
+ *  Create the new file `name` in the directory `dir` with openat, with
+ *  O_EXCL and O_NOFOLLOW so that nothing already at the name is opened
+ */
+pub(crate) fn create_at(dir: &OwnedFd, name: &str, mode: u32) -> io::Result<File> {
+    if !utils::is_file_name(name) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{name:?} is not a file name")));
+    }
+    let c_name = CString::new(Path::new(name).as_os_str().as_bytes())?;
+    let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+    let fd = unsafe { libc::openat(dir.as_raw_fd(), c_name.as_ptr(), flags, mode as libc::c_uint) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+fn random_u64() -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    let n = unsafe { libc::getrandom(bytes.as_mut_ptr().cast(), bytes.len(), 0) };
+    if n != bytes.len() as isize {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(u64::from_ne_bytes(bytes))
+}
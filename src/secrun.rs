@@ -1,144 +1,2941 @@
+#[cfg(feature = "seccomp")]
 use seccompiler::*;
-use libc;
-use std::path::Path;
+#[cfg(feature = "seccomp")]
+use std::collections::BTreeMap;
+#[cfg(not(feature = "seccomp"))]
+use libc::sock_filter;
+use std::path::{Path, PathBuf};
 use std::result::Result;
-use std::ffi::{CString, NulError};
-use std::{io, fs};
+use std::ffi::{CStr, CString, NulError, OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{FileExt, MetadataExt, OpenOptionsExt};
+use std::mem::{size_of, MaybeUninit};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::io::{self, Read, Write};
+use std::{fs, ptr, thread};
 use std::error::Error;
-use std::time::Instant;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
-fn install_seccomp(execve_whitepath: &CString) -> Result<(), seccompiler::Error> {
+use crate::cgroup::RunCgroup;
+use crate::utils::elf::{self, ElfKind, ExecArch};
+use crate::log;
+
+// What seccompiler would compile filters to, for the probe's that is put
+// together by hand
+#[cfg(not(feature = "seccomp"))]
+type BpfProgram = Vec<sock_filter>;
+
+/// What to do when the sandboxed program gets stopped, e.g. by raise(SIGSTOP)
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum StopAction {
+    /// Kill it and report Idleness Limit Exceeded
+    #[default]
+    ReportIdleness,
+    /// Kill it and report a Security Violation
+    ReportViolation,
+    /// Resume it with SIGCONT and keep the clock running
+    Resume
+}
+
+/// How much of the sandbox a run insists on. Without the seccomp filter
+/// the program still gets its limits, process group and private /tmp, but
+/// may make any syscall and exec anything.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SandboxStrength {
+    /// Fail the run if the seccomp filter can't be installed
+    #[default]
+    Require,
+    /// Install the filter where the kernel allows it, and run without it
+    /// otherwise, warning about it once
+    BestEffort,
+    /// Never install the filter, e.g. for timing programs locally
+    Disabled
+}
+
+impl SandboxStrength {
+    /// Parse a strength as the command line gives it: require,
+    /// best-effort or disabled
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "require" => Some(Self::Require),
+            "best-effort" => Some(Self::BestEffort),
+            "disabled" => Some(Self::Disabled),
+            _ => None
+        }
+    }
+}
+
+impl Display for SandboxStrength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match &self {
+            Self::Require       => "require",
+            Self::BestEffort    => "best-effort",
+            Self::Disabled      => "disabled"
+        };
+        f.write_str(str)
+    }
+}
+
+/// Condition under which a syscall is denied
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Deny {
+    /// Every call
+    Always,
+    /// Calls where argument `arg` has all bits of `flags` set
+    FlagsSet {
+        /// Index of the argument, from 0
+        arg: u8,
+        /// Bits that have to be set for the call to be denied
+        flags: u64
+    }
+}
+
+/// Entry of the syscall denial table: `syscall` fails with EPERM when any
+/// of the `deny` conditions holds
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SyscallRule {
+    /// Name of the syscall, as seccompiler knows it
+    pub syscall: &'static str,
+    /// Denied when any of these holds
+    pub deny: Vec<Deny>
+}
+
+impl SyscallRule {
+    /// A rule denying every call of `syscall`
+    pub fn always(syscall: &'static str) -> Self {
+        SyscallRule { syscall, deny: vec![Deny::Always] }
+    }
+
+    /// A rule denying calls of `syscall` where argument `arg` has any of
+    /// `flags` fully set
+    pub fn flags_set(syscall: &'static str, arg: u8, flags: &[i32]) -> Self {
+        SyscallRule {
+            syscall,
+            deny: flags.iter().map(|&f| Deny::FlagsSet { arg, flags: f as u64 }).collect()
+        }
+    }
+}
+
+// Opening for writing, or creating a file even when opened read-only
+const MODIFYING_FLAGS: &[i32] = &[libc::O_RDWR, libc::O_WRONLY, libc::O_CREAT, libc::O_TMPFILE];
+
+/**
+ *  The denial table applied to contestant programs by default
+ */
+pub fn default_syscall_rules() -> Vec<SyscallRule> {
+    vec![
+        SyscallRule::flags_set("open", 1, MODIFYING_FLAGS),
+        SyscallRule::flags_set("openat", 2, MODIFYING_FLAGS),
+        // Its flags live behind a pointer that seccomp can't inspect
+        SyscallRule::always("openat2"),
+        SyscallRule::always("socket"),
+        SyscallRule::always("fork"),
+        SyscallRule::always("vfork"),
+        SyscallRule::always("prctl"),
+        SyscallRule::always("ioctl"),
+        SyscallRule::always("clone"),
+        // Like openat2, its flags can't be inspected
+        SyscallRule::always("clone3"),
+        // The process group is how the judger finds every descendant
+        SyscallRule::always("setsid"),
+        SyscallRule::always("setpgid"),
+        SyscallRule::always("mkdir"),
+        SyscallRule::always("mkdirat"),
+        SyscallRule::always("rmdir"),
+        SyscallRule::flags_set("unlinkat", 2, &[libc::AT_REMOVEDIR]),
+        SyscallRule::always("creat"),
+        SyscallRule::always("chroot"),
+        // Would let the program uncover what the private /tmp hides
+        SyscallRule::always("mount"),
+        SyscallRule::always("umount"),
+        SyscallRule::always("umount2"),
+        SyscallRule::always("unshare"),
+        // File metadata
+        SyscallRule::always("truncate"),
+        SyscallRule::always("chmod"),
+        SyscallRule::always("fchmod"),
+        SyscallRule::always("fchmodat"),
+        SyscallRule::always("chown"),
+        SyscallRule::always("fchown"),
+        SyscallRule::always("lchown"),
+        SyscallRule::always("fchownat"),
+        SyscallRule::always("utime"),
+        SyscallRule::always("utimes"),
+        SyscallRule::always("futimesat"),
+        SyscallRule::always("utimensat"),
+        SyscallRule::always("setxattr"),
+        SyscallRule::always("lsetxattr"),
+        SyscallRule::always("fsetxattr"),
+        SyscallRule::always("removexattr"),
+        SyscallRule::always("lremovexattr"),
+        SyscallRule::always("fremovexattr")
+    ]
+}
+
+// Syscalls that only exist on architectures predating the generic syscall
+// table. Elsewhere their *at replacements are covered by their own rules.
+#[cfg(feature = "seccomp")]
+const LEGACY_SYSCALLS: &[&str] = &[
+    "open", "creat", "fork", "vfork", "mkdir", "rmdir", "chmod", "chown",
+    "lchown", "utime", "utimes", "futimesat", "umount"
+];
+
+/*
+ *  Syscall number of a syscall named in the denial table, on x86_64
+ */
+#[cfg(target_arch = "x86_64")]
+fn syscall_number(name: &str) -> Option<i64> {
+    let nr = match name {
+        "open" => libc::SYS_open,
+        "creat" => libc::SYS_creat,
+        "fork" => libc::SYS_fork,
+        "vfork" => libc::SYS_vfork,
+        "mkdir" => libc::SYS_mkdir,
+        "rmdir" => libc::SYS_rmdir,
+        "chmod" => libc::SYS_chmod,
+        "chown" => libc::SYS_chown,
+        "lchown" => libc::SYS_lchown,
+        "utime" => libc::SYS_utime,
+        "utimes" => libc::SYS_utimes,
+        "futimesat" => libc::SYS_futimesat,
+        "openat" => libc::SYS_openat,
+        "openat2" => libc::SYS_openat2,
+        "socket" => libc::SYS_socket,
+        "prctl" => libc::SYS_prctl,
+        "ioctl" => libc::SYS_ioctl,
+        "clone" => libc::SYS_clone,
+        "clone3" => libc::SYS_clone3,
+        "setsid" => libc::SYS_setsid,
+        "setpgid" => libc::SYS_setpgid,
+        "execve" => libc::SYS_execve,
+        "execveat" => libc::SYS_execveat,
+        "mkdirat" => libc::SYS_mkdirat,
+        "unlinkat" => libc::SYS_unlinkat,
+        "chroot" => libc::SYS_chroot,
+        "mount" => libc::SYS_mount,
+        "umount2" => libc::SYS_umount2,
+        "unshare" => libc::SYS_unshare,
+        "truncate" => libc::SYS_truncate,
+        "fchmod" => libc::SYS_fchmod,
+        "fchmodat" => libc::SYS_fchmodat,
+        "fchown" => libc::SYS_fchown,
+        "fchownat" => libc::SYS_fchownat,
+        "utimensat" => libc::SYS_utimensat,
+        "setxattr" => libc::SYS_setxattr,
+        "lsetxattr" => libc::SYS_lsetxattr,
+        "fsetxattr" => libc::SYS_fsetxattr,
+        "removexattr" => libc::SYS_removexattr,
+        "lremovexattr" => libc::SYS_lremovexattr,
+        "fremovexattr" => libc::SYS_fremovexattr,
+        _ => return None
+    };
+    Some(nr)
+}
+
+/*
+ *  Syscall number of a syscall named in the denial table, on aarch64
+ */
+#[cfg(target_arch = "aarch64")]
+fn syscall_number(name: &str) -> Option<i64> {
+    let nr = match name {
+        "openat" => libc::SYS_openat,
+        "openat2" => libc::SYS_openat2,
+        "socket" => libc::SYS_socket,
+        "prctl" => libc::SYS_prctl,
+        "ioctl" => libc::SYS_ioctl,
+        "clone" => libc::SYS_clone,
+        "clone3" => libc::SYS_clone3,
+        "setsid" => libc::SYS_setsid,
+        "setpgid" => libc::SYS_setpgid,
+        "execve" => libc::SYS_execve,
+        "execveat" => libc::SYS_execveat,
+        "mkdirat" => libc::SYS_mkdirat,
+        "unlinkat" => libc::SYS_unlinkat,
+        "chroot" => libc::SYS_chroot,
+        "mount" => libc::SYS_mount,
+        "umount2" => libc::SYS_umount2,
+        "unshare" => libc::SYS_unshare,
+        "truncate" => libc::SYS_truncate,
+        "fchmod" => libc::SYS_fchmod,
+        "fchmodat" => libc::SYS_fchmodat,
+        "fchown" => libc::SYS_fchown,
+        "fchownat" => libc::SYS_fchownat,
+        "utimensat" => libc::SYS_utimensat,
+        "setxattr" => libc::SYS_setxattr,
+        "lsetxattr" => libc::SYS_lsetxattr,
+        "fsetxattr" => libc::SYS_fsetxattr,
+        "removexattr" => libc::SYS_removexattr,
+        "lremovexattr" => libc::SYS_lremovexattr,
+        "fremovexattr" => libc::SYS_fremovexattr,
+        _ => return None
+    };
+    Some(nr)
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn syscall_number(_name: &str) -> Option<i64> {
+    None
+}
+
+/*
+ *  Syscall numbers of a syscall named in the denial table, for 32-bit x86
+ *  programs running on x86_64. Not in libc for this target, so taken from
+ *  the kernel's syscall_32.tbl. Several syscalls have a 16-bit uid, 64-bit
+ *  offset or 64-bit time variant, and socket calls can also be made
+ *  through the socketcall multiplexer; all of them count.
+ */
+#[cfg(feature = "seccomp")]
+fn i386_syscall_numbers(name: &str) -> &'static [i64] {
+    match name {
+        "open" => &[5],
+        "creat" => &[8],
+        "fork" => &[2],
+        "vfork" => &[190],
+        "execve" => &[11],
+        "execveat" => &[358],
+        "mkdir" => &[39],
+        "rmdir" => &[40],
+        "chmod" => &[15],
+        "chown" => &[182, 212],
+        "lchown" => &[16, 198],
+        "utime" => &[30],
+        "utimes" => &[271],
+        "futimesat" => &[299],
+        "openat" => &[295],
+        "openat2" => &[437],
+        "socket" => &[359, 102],
+        "prctl" => &[172],
+        "ioctl" => &[54],
+        "clone" => &[120],
+        "clone3" => &[435],
+        "setsid" => &[66],
+        "setpgid" => &[57],
+        "mkdirat" => &[296],
+        "unlinkat" => &[301],
+        "chroot" => &[61],
+        "mount" => &[21],
+        "umount" => &[22],
+        "umount2" => &[52],
+        "unshare" => &[310],
+        "truncate" => &[92, 193],
+        "fchmod" => &[94],
+        "fchmodat" => &[306],
+        "fchown" => &[95, 207],
+        "fchownat" => &[298],
+        "utimensat" => &[320, 412],
+        "setxattr" => &[226],
+        "lsetxattr" => &[227],
+        "fsetxattr" => &[228],
+        "removexattr" => &[235],
+        "lremovexattr" => &[236],
+        "fremovexattr" => &[237],
+        _ => &[]
+    }
+}
+
+/*
+ *  The architecture the judger runs as, which programs run as unless
+ *  they are built for another one
+ */
+fn host_arch() -> Result<ExecArch, PolicyError> {
+    ExecArch::native().ok_or_else(|| PolicyError::UnsupportedArch(std::env::consts::ARCH.to_string()))
+}
+
+/*
+ *  Check that filters can be made for the host. Refuses to go on with an
+ *  architecture we have no syscall table for, or when the kernel reports
+ *  a different machine than we were built for, as the filter would then
+ *  match the wrong syscalls.
+ */
+fn check_filter_arch(host: ExecArch) -> Result<(), PolicyError> {
+    if host == ExecArch::I386 {
+        return Err(PolicyError::UnsupportedArch(host.to_string()));
+    }
+    let machine = kernel_machine();
+    if machine != std::env::consts::ARCH {
+        return Err(PolicyError::UnsupportedArch(machine));
+    }
+    Ok(())
+}
+
+/*
+ *  The seccomp target for syscalls of `arch`
+ */
+#[cfg(feature = "seccomp")]
+fn target_arch(arch: ExecArch) -> Result<TargetArch, PolicyError> {
+    match arch {
+        ExecArch::X86_64 => Ok(TargetArch::x86_64),
+        ExecArch::Aarch64 => Ok(TargetArch::aarch64),
+        ExecArch::I386 => Err(PolicyError::UnsupportedArch(arch.to_string()))
+    }
+}
+
+/*
+ *  Whether seccomp filters can be installed here, user notifications
+ *  included, as found out once per process. Kernels built without
+ *  seccomp and container runtimes refusing it fail the probe instead of
+ *  the program's setup halfway through.
+ */
+pub(crate) fn seccomp_support() -> Result<(), String> {
+    static SUPPORT: OnceLock<Result<(), String>> = OnceLock::new();
+    SUPPORT.get_or_init(probe_seccomp).clone()
+}
+
+fn probe_seccomp() -> Result<(), String> {
+    if cfg!(not(feature = "seccomp")) {
+        return Err(String::from("the judger was built without the seccomp feature"));
+    }
+    // Fails with EINVAL on kernels without seccomp
+    if unsafe { libc::prctl(libc::PR_GET_SECCOMP) } < 0 {
+        return Err(format!("prctl(PR_GET_SECCOMP): {}", io::Error::last_os_error()));
+    }
+    // Filters can't be removed again, so the trial install happens in a
+    // child that exits right after, with the errno as its status
+    let allow_all: BpfProgram = vec![sock_filter { code: BPF_RET_K, jt: 0, jf: 0, k: SECCOMP_RET_ALLOW }];
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        return Err(format!("fork: {}", io::Error::last_os_error()));
+    }
+    if pid == 0 {
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                libc::_exit(io::Error::last_os_error().raw_os_error().unwrap_or(255));
+            }
+            match install_program(&allow_all, SECCOMP_FILTER_FLAG_NEW_LISTENER) {
+                Ok(_) => libc::_exit(0),
+                Err(e) => libc::_exit(e.raw_os_error().unwrap_or(255))
+            }
+        }
+    }
+    let mut status = 0;
+    if unsafe { libc::waitpid(pid, &mut status, 0) } < 0 {
+        return Err(format!("waitpid: {}", io::Error::last_os_error()));
+    }
+    match libc::WIFEXITED(status) {
+        true if libc::WEXITSTATUS(status) == 0 => Ok(()),
+        true => Err(format!("installing a filter failed: {}", io::Error::from_raw_os_error(libc::WEXITSTATUS(status)))),
+        false => Err(String::from("the probe for seccomp support crashed"))
+    }
+}
+
+/*
+ *  Whether runs under `policy` get the seccomp filter, failing if they
+ *  have to and can't
+ */
+fn use_seccomp(policy: &SandboxPolicy) -> Result<bool, PolicyError> {
+    static WARNED: OnceLock<()> = OnceLock::new();
+
+    match (policy.strength, seccomp_support()) {
+        (SandboxStrength::Disabled, _) => Ok(false),
+        (_, Ok(())) => Ok(true),
+        (SandboxStrength::Require, Err(reason)) => Err(PolicyError::Unavailable(reason)),
+        (SandboxStrength::BestEffort, Err(reason)) => {
+            WARNED.get_or_init(|| warn!(
+                "seccomp is unavailable ({reason}), programs run WITHOUT the syscall filter"
+            ));
+            Ok(false)
+        }
+    }
+}
+
+/*
+ *  Machine name reported by uname(2)
+ */
+fn kernel_machine() -> String {
+    let mut name: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut name) } < 0 {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(name.machine.as_ptr()) }.to_string_lossy().into_owned()
+}
+
+// Room for the error output and the judger's own files of a run, beyond
+// the program's output
+const SCRATCH_SLACK: u64 = 4 * 1024 * 1024;
+
+/// Knobs controlling how the sandboxed program is treated
+#[derive(Clone, Debug)]
+pub struct SandboxPolicy {
+    /// What to do with a program that stopped itself
+    pub on_stop: StopAction,
+    /// Architecture to sandbox the program as, detected from its ELF header if None
+    pub exec_arch: Option<ExecArch>,
+    /// Size of the private tmpfs mounted on /tmp, when namespaces allow it
+    pub scratch_limit: u64,
+    /// Backstop for the judger's CPU time limit, enforced with RLIMIT_CPU
+    pub cpu_limit: Option<Duration>,
+    /// Largest file the program may write, its output included, enforced
+    /// with RLIMIT_FSIZE
+    pub output_limit: Option<u64>,
+    /// Most of what the program writes to stderr that is captured, the
+    /// rest is counted and dropped. Stderr doesn't count against the
+    /// output limit.
+    pub stderr_limit: u64,
+    /// Let the program exec other programs, which only its first exec may
+    /// otherwise
+    pub allow_exec: bool,
+    /// Mount a private tmpfs on /tmp where namespaces allow it. Without it
+    /// the program works in the run's scratch directory, where the judger
+    /// can pick up what it leaves behind.
+    pub private_tmp: bool,
+    /// Syscalls the program may not make, see default_syscall_rules
+    pub syscalls: Vec<SyscallRule>,
+    /// Whether runs insist on the seccomp filter
+    pub strength: SandboxStrength,
+    /// Namespaces to run the program in rather than the judger's, such as
+    /// those of a container prepared for it. Of several of a kind the last
+    /// counts. The private /tmp is then made inside a joined mount
+    /// namespace, and without the privileges for it the scratch directory
+    /// has to exist at the same path in there.
+    pub join_namespaces: Vec<JoinNamespace>,
+    /// Variables the program gets over the judger's environment, of which
+    /// TZ and the locale variables are left out, so that the host's
+    /// settings don't show in its output. By default TZ=UTC, LANG=C and
+    /// LC_ALL=C; a later one of a name wins.
+    pub env: Vec<(String, String)>
+}
+
+/// The umask programs run with, whatever the judger's is
+pub const PROGRAM_UMASK: u32 = 0o022;
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        SandboxPolicy {
+            on_stop: StopAction::default(),
+            exec_arch: None,
+            scratch_limit: 64 * 1024 * 1024,
+            cpu_limit: None,
+            output_limit: None,
+            stderr_limit: 1024 * 1024,
+            allow_exec: false,
+            private_tmp: true,
+            syscalls: default_syscall_rules(),
+            strength: SandboxStrength::default(),
+            join_namespaces: Vec::new(),
+            env: [("TZ", "UTC"), ("LANG", "C"), ("LC_ALL", "C")]
+                .into_iter()
+                .map(|(name, value)| (String::from(name), String::from(value)))
+                .collect()
+        }
+    }
+}
+
+impl SandboxPolicy {
+    /// The default policy, but allowing threads and child processes for
+    /// runtimes that need them. Their number is only bounded when judging
+    /// in a cgroup.
+    pub fn threads_allowed() -> Self {
+        const TASK_CREATION: [&str; 4] = ["clone", "clone3", "fork", "vfork"];
+
+        let mut policy = Self::default();
+        policy.syscalls.retain(|r| !TASK_CREATION.contains(&r.syscall));
+        policy
+    }
+
+    /// For compilers, which run their passes as child processes, write
+    /// intermediate files and make their output executable. They work in
+    /// the scratch directory so that their output can be collected. What
+    /// they may write outside of it is only up to file permissions.
+    pub fn compiler() -> Self {
+        const WRITING: [&str; 12] = [
+            "open", "openat", "creat", "mkdir", "mkdirat", "rmdir", "unlinkat",
+            "truncate", "chmod", "fchmod", "fchmodat", "utimensat"
+        ];
+
+        let mut policy = Self::threads_allowed();
+        policy.syscalls.retain(|r| !WRITING.contains(&r.syscall));
+        policy.allow_exec = true;
+        policy.private_tmp = false;
+        policy
+    }
+
+    /// Whether the program may start threads, which the default denial
+    /// table keeps it from doing
+    pub fn allows_threads(&self) -> bool {
+        self.strength == SandboxStrength::Disabled
+            || !self.syscalls.iter().any(|rule| rule.syscall == "clone" && rule.deny.contains(&Deny::Always))
+    }
+
+    /// Free space the filesystem of a run's scratch directory is checked
+    /// to have: room for the output up to the output limit, or as much as
+    /// the private /tmp takes without one, for the captured stderr and for
+    /// the judger's own files
+    pub fn scratch_needed(&self) -> u64 {
+        self.output_limit.unwrap_or(self.scratch_limit).saturating_add(self.stderr_limit).saturating_add(SCRATCH_SLACK)
+    }
+}
+
+/// A kind of namespace the program can be run in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Namespace {
+    /// User and group ids and the privileges that go with them
+    User,
+    /// The mounts, the container's root among them
+    Mount,
+    /// Process ids, the program's own included
+    Pid,
+    /// Network devices, addresses and ports
+    Net,
+    /// System V IPC and POSIX message queues
+    Ipc,
+    /// Host and domain name
+    Uts
+}
+
+impl Namespace {
+    /// Every kind, in the order they are joined: the user namespace first,
+    /// which the privileges over the others may come from. Also in
+    /// declaration order, so that a kind's discriminant indexes it.
+    pub const ALL: [Namespace; 6] = [Self::User, Self::Mount, Self::Pid, Self::Net, Self::Ipc, Self::Uts];
+
+    /// The name of its file in /proc/PID/ns
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Mount => "mnt",
+            Self::Pid => "pid",
+            Self::Net => "net",
+            Self::Ipc => "ipc",
+            Self::Uts => "uts"
+        }
+    }
+
+    /// The kind with the file named `name` in /proc/PID/ns
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    fn clone_flag(self) -> libc::c_int {
+        match self {
+            Self::User => libc::CLONE_NEWUSER,
+            Self::Mount => libc::CLONE_NEWNS,
+            Self::Pid => libc::CLONE_NEWPID,
+            Self::Net => libc::CLONE_NEWNET,
+            Self::Ipc => libc::CLONE_NEWIPC,
+            Self::Uts => libc::CLONE_NEWUTS
+        }
+    }
+
+    /*
+     *  The child's setup step of joining it
+     */
+    fn joining(self) -> &'static str {
+        match self {
+            Self::User => "joining the user namespace",
+            Self::Mount => "joining the mnt namespace",
+            Self::Pid => "joining the pid namespace",
+            Self::Net => "joining the net namespace",
+            Self::Ipc => "joining the ipc namespace",
+            Self::Uts => "joining the uts namespace"
+        }
+    }
+}
+
+impl Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A namespace for the program to run in, by a file of it such as
+/// /proc/PID/ns/net or one bind-mounted by `ip netns`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JoinNamespace {
+    /// What kind of namespace the file has to be
+    pub kind: Namespace,
+    /// The file
+    pub path: PathBuf
+}
+
+impl JoinNamespace {
+    /// Every kind of namespace of a process from its /proc/PID/ns, or the
+    /// like of it elsewhere. Those the judger is in already are left alone
+    /// when joining.
+    pub fn all_of(dir: &Path) -> Vec<JoinNamespace> {
+        Namespace::ALL.into_iter().map(|kind| JoinNamespace { kind, path: dir.join(kind.name()) }).collect()
+    }
+}
+
+/// Why a namespace of the policy can't be joined
+#[derive(Debug)]
+pub enum NamespaceError {
+    /// Its file can't be opened
+    Open(JoinNamespace, io::Error),
+    /// The file is a namespace of another kind, or none at all
+    WrongKind(JoinNamespace, Option<Namespace>),
+    /// The judger can't put the program in it
+    Join(JoinNamespace, io::Error)
+}
+
+impl Display for NamespaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Open(ns, e) => f.write_fmt(format_args!("cannot open the {} namespace {}: {e}", ns.kind, ns.path.display())),
+            Self::WrongKind(ns, Some(found)) => f.write_fmt(format_args!(
+                "{} is a {found} namespace, not the {} namespace asked for",
+                ns.path.display(),
+                ns.kind
+            )),
+            Self::WrongKind(ns, None) => f.write_fmt(format_args!(
+                "{} is not a namespace, asked for as the {} namespace",
+                ns.path.display(),
+                ns.kind
+            )),
+            Self::Join(ns, e) => f.write_fmt(format_args!("cannot join the {} namespace {}: {e}", ns.kind, ns.path.display()))
+        }
+    }
+}
+
+impl Error for NamespaceError {}
+
+// ioctl(2) on a namespace file giving its CLONE_NEW* flag, since Linux 4.11
+const NS_GET_NSTYPE: u32 = 0xb703;
+
+/*
+ *  Open the namespaces to join, the last of each kind in the order they
+ *  are joined, checking that each is of its kind. Those the judger is in
+ *  already are left out, as setns refuses the judger's own user namespace.
+ */
+fn open_namespaces(namespaces: &[JoinNamespace]) -> Result<Vec<(JoinNamespace, OwnedFd)>, NamespaceError> {
+    let mut opened = Vec::new();
+    for kind in Namespace::ALL {
+        let Some(ns) = namespaces.iter().rev().find(|ns| ns.kind == kind) else {
+            continue;
+        };
+        let file = fs::File::open(&ns.path).map_err(|e| NamespaceError::Open(ns.clone(), e))?;
+        let flag = unsafe { libc::ioctl(file.as_raw_fd(), NS_GET_NSTYPE as _) };
+        let found = Namespace::ALL.into_iter().find(|other| other.clone_flag() == flag);
+        if found != Some(kind) {
+            return Err(NamespaceError::WrongKind(ns.clone(), found));
+        }
+        let own = fs::metadata(Path::new("/proc/self/ns").join(kind.name()));
+        let same = match (file.metadata(), own) {
+            (Ok(target), Ok(own)) => (target.dev(), target.ino()) == (own.dev(), own.ino()),
+            _ => false
+        };
+        match same {
+            true => debug!("already in the {kind} namespace {}", ns.path.display()),
+            false => opened.push((ns.clone(), OwnedFd::from(file)))
+        }
+    }
+    Ok(opened)
+}
+
+/*
+ *  Fork with the child in the pid namespace `target`. setns only puts the
+ *  children the calling thread makes from then on in there, so the thread
+ *  goes back to its own right after.
+ */
+fn fork_in_pid_namespace(ns: &JoinNamespace, target: &OwnedFd) -> Result<i32, Box<dyn Error>> {
+    let own = fs::File::open("/proc/thread-self/ns/pid_for_children")?;
+    if unsafe { libc::setns(target.as_raw_fd(), libc::CLONE_NEWPID) } < 0 {
+        return Err(Box::new(NamespaceError::Join(ns.clone(), io::Error::last_os_error())));
+    }
+    let pid = fork();
+    if let Ok(0) = pid {
+        // The child, which is in there to stay
+        return Ok(0);
+    }
+    if unsafe { libc::setns(own.as_raw_fd(), libc::CLONE_NEWPID) } < 0 {
+        let e = io::Error::last_os_error();
+        // This thread's next children would end up in there too
+        if let Ok(pid) = pid {
+            unsafe {
+                libc::kill(pid, libc::SIGKILL);
+                libc::waitpid(pid, ptr::null_mut(), 0);
+            }
+        }
+        return Err(format!("cannot return to the judger's pid namespace: {e}").into());
+    }
+    Ok(pid?)
+}
+
+/// Why a policy can't be turned into a seccomp filter
+#[derive(Debug)]
+pub enum PolicyError {
+    /// A rule names a syscall the architecture doesn't have
+    UnknownSyscall(&'static str),
+    /// The executable is for an architecture seccomp filters can't be
+    /// made for
+    UnsupportedArch(String),
+    /// Seccomp filters can't be installed on this system, for this reason,
+    /// and the policy requires them
+    Unavailable(String),
+    /// The named feature only works with the seccomp filter installed
+    NeedsSeccomp(&'static str),
+    /// Compiling the filter failed
+    #[cfg(feature = "seccomp")]
+    Seccomp(seccompiler::Error)
+}
+
+impl Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownSyscall(name) => f.write_fmt(format_args!("unknown syscall {name} in policy")),
+            Self::UnsupportedArch(arch) => f.write_fmt(format_args!("no seccomp support for architecture {arch}")),
+            Self::Unavailable(reason) => f.write_fmt(format_args!(
+                "seccomp is unavailable: {reason} (--sandbox best-effort runs without it)"
+            )),
+            Self::NeedsSeccomp(what) => f.write_fmt(format_args!("{what} needs the seccomp filter")),
+            #[cfg(feature = "seccomp")]
+            Self::Seccomp(e) => f.write_fmt(format_args!("{e}"))
+        }
+    }
+}
+
+impl Error for PolicyError {}
+
+#[cfg(feature = "seccomp")]
+impl From<seccompiler::Error> for PolicyError {
+    fn from(e: seccompiler::Error) -> Self {
+        PolicyError::Seccomp(e)
+    }
+}
+
+#[cfg(feature = "seccomp")]
+impl From<seccompiler::BackendError> for PolicyError {
+    fn from(e: seccompiler::BackendError) -> Self {
+        PolicyError::Seccomp(e.into())
+    }
+}
+
+/*
+ *  Compile denial rules into a filter for `arch`, looking up syscall
+ *  numbers with `numbers`
+ */
+#[cfg(feature = "seccomp")]
+fn compile_rules(
+    syscalls: &[SyscallRule],
+    arch: ExecArch,
+    numbers: fn(&str) -> Vec<i64>,
+    action: SeccompAction
+) -> Result<BpfProgram, PolicyError> {
+    // A syscall mapped to no conditions is denied unconditionally
+    let mut rules: BTreeMap<i64, Option<Vec<SeccompRule>>> = BTreeMap::new();
+    for rule in syscalls {
+        let nrs = numbers(rule.syscall);
+        if nrs.is_empty() && !LEGACY_SYSCALLS.contains(&rule.syscall) {
+            return Err(PolicyError::UnknownSyscall(rule.syscall));
+        }
+        for nr in nrs {
+            let chain = rules.entry(nr).or_insert_with(|| Some(Vec::new()));
+            for deny in &rule.deny {
+                match (*deny, chain.as_mut()) {
+                    (Deny::Always, _) => *chain = None,
+                    (Deny::FlagsSet { arg, flags }, Some(chain)) => chain.push(SeccompRule::new(vec![
+                        SeccompCondition::new(
+                            arg,
+                            SeccompCmpArgLen::Dword,
+                            SeccompCmpOp::MaskedEq(flags),
+                            flags
+                        )?
+                    ])?),
+                    (Deny::FlagsSet { .. }, None) => {}
+                }
+            }
+        }
+    }
+    let rules: BTreeMap<i64, Vec<SeccompRule>> = rules.into_iter()
+        .map(|(nr, chain)| (nr, chain.unwrap_or_default()))
+        .collect();
     let filter = SeccompFilter::new(
-        vec![
-            (libc::SYS_open, vec![
-                SeccompRule::new(vec![
-                    SeccompCondition::new(
-                        1,
-                        SeccompCmpArgLen::Dword,
-                        SeccompCmpOp::MaskedEq(libc::O_RDWR as u64),
-                        libc::O_RDWR as u64
-                    )?
-                ])?,
-                SeccompRule::new(vec![
-                    SeccompCondition::new(
-                        1,
-                        SeccompCmpArgLen::Dword,
-                        SeccompCmpOp::MaskedEq(libc::O_WRONLY as u64),
-                        libc::O_WRONLY as u64
-                    )?
-                ])?
-            ]),
-            (libc::SYS_openat, vec![
-                SeccompRule::new(vec![
-                    SeccompCondition::new(
-                        2,
-                        SeccompCmpArgLen::Dword,
-                        SeccompCmpOp::MaskedEq(libc::O_RDWR as u64),
-                        libc::O_RDWR as u64
-                    )?
-                ])?,
-                SeccompRule::new(vec![
-                    SeccompCondition::new(
-                        2,
-                        SeccompCmpArgLen::Dword,
-                        SeccompCmpOp::MaskedEq(libc::O_WRONLY as u64),
-                        libc::O_WRONLY as u64
-                    )?
-                ])?
-            ]),
-            (libc::SYS_execve, vec![
-                SeccompRule::new(vec![
-                    SeccompCondition::new(
-                        0,
-                        SeccompCmpArgLen::Qword,
-                        SeccompCmpOp::Ne,
-                        execve_whitepath.as_ptr() as u64
-                    )?
-                ])?
-            ]),
-            (libc::SYS_execveat, vec![]),
-            (libc::SYS_socket, vec![]),
-            (libc::SYS_fork, vec![]),
-            (libc::SYS_vfork, vec![]),
-            (libc::SYS_prctl, vec![]),
-            (libc::SYS_ioctl, vec![]),
-            (libc::SYS_clone, vec![]),
-            (libc::SYS_mkdir, vec![]),
-            (libc::SYS_rmdir, vec![]),
-            (libc::SYS_creat, vec![]),
-            (libc::SYS_chroot, vec![])
-        ].into_iter().collect(),
+        rules.into_iter().collect(),
         SeccompAction::Allow,
-        SeccompAction::Errno(libc::EPERM as u32),
-        TargetArch::x86_64
+        action,
+        target_arch(arch)?
     )?;
+    Ok(filter.try_into()?)
+}
+
+/*
+ *  Decide which architecture the executable runs as: the policy override,
+ *  else what its ELF header says. Scripts run as native programs.
+ */
+fn resolve_exec_arch(kind: ElfKind, policy: &SandboxPolicy) -> Result<ExecArch, PolicyError> {
+    let native = ExecArch::native()
+        .ok_or_else(|| PolicyError::UnsupportedArch(std::env::consts::ARCH.to_string()))?;
+    if let Some(arch) = policy.exec_arch {
+        return Ok(arch);
+    }
+    Ok(match kind {
+        ElfKind::NotElf => native,
+        ElfKind::Arch(arch) => arch,
+        ElfKind::Unknown { bits, machine } => return Err(
+            PolicyError::UnsupportedArch(format!("{bits}-bit ELF machine {machine}"))
+        )
+    })
+}
+
+#[cfg(feature = "seccomp")]
+fn native_numbers(name: &str) -> Vec<i64> {
+    syscall_number(name).into_iter().collect()
+}
+
+#[cfg(feature = "seccomp")]
+fn i386_numbers(name: &str) -> Vec<i64> {
+    i386_syscall_numbers(name).to_vec()
+}
+
+fn is_compat(host: ExecArch, exec_arch: ExecArch) -> Result<bool, PolicyError> {
+    if Some(exec_arch) == ExecArch::native() {
+        return Ok(false);
+    }
+    if !(host == ExecArch::X86_64 && exec_arch == ExecArch::I386) {
+        return Err(PolicyError::UnsupportedArch(format!("{exec_arch} on {}", std::env::consts::ARCH)));
+    }
+    Ok(true)
+}
+
+/*
+ *  Build the policy filters for an executable of `exec_arch`.
+ *  Native programs get a single filter that kills the process on syscalls
+ *  of any other architecture. 32-bit x86 programs on x86_64 start out as
+ *  the judger's x86_64 child and continue with i386 syscalls, so they get
+ *  one filter per architecture, each passing the other's syscalls on.
+ *  Their first exec happens as x86_64, so every i386 exec can be denied.
+ *  With `supervise_writes` the write-open denials are left out, as the
+ *  exec gate hands those opens to the judger instead.
+ */
+#[cfg(feature = "seccomp")]
+fn policy_programs(
+    policy: &SandboxPolicy,
+    host: ExecArch,
+    exec_arch: ExecArch,
+    supervise_writes: bool
+) -> Result<Vec<BpfProgram>, PolicyError> {
+    const DENY: SeccompAction = SeccompAction::Errno(libc::EPERM as u32);
+
+    let mut syscalls = policy.syscalls.clone();
+    if supervise_writes {
+        for rule in syscalls.iter_mut().filter(|r| r.syscall == "open" || r.syscall == "openat") {
+            rule.deny.retain(|d| *d == Deny::Always);
+        }
+        syscalls.retain(|r| !r.deny.is_empty());
+    }
+    let mut native = compile_rules(&syscalls, host, native_numbers, DENY)?;
+    if !is_compat(host, exec_arch)? {
+        return Ok(vec![native]);
+    }
+
+    let mut compat_rules = syscalls;
+    compat_rules.push(SyscallRule::always("execve"));
+    compat_rules.push(SyscallRule::always("execveat"));
+    // Compiled as x86_64 with i386 numbers, then pointed at the i386 ABI
+    let mut compat = compile_rules(&compat_rules, ExecArch::X86_64, i386_numbers, DENY)?;
+    retarget(&mut native, None)?;
+    retarget(&mut compat, Some(AUDIT_ARCH_I386))?;
+    Ok(vec![native, compat])
+}
+
+/// Seccomp programs compiled for earlier runs, which later runs with the
+/// same syscall rules sandboxing a program the same way can install as
+/// they are instead of compiling them again
+#[derive(Default)]
+pub(crate) struct FilterCache {
+    entries: Mutex<Vec<CachedFilters>>
+}
+
+struct CachedFilters {
+    syscalls: Vec<SyscallRule>,
+    exec_arch: ExecArch,
+    supervise_writes: bool,
+    programs: Arc<SandboxPrograms>
+}
+
+/// Everything a sandboxed child installs
+struct SandboxPrograms {
+    policy_filters: Vec<BpfProgram>,
+    exec_gate: BpfProgram
+}
+
+#[cfg(feature = "seccomp")]
+fn build_programs(
+    policy: &SandboxPolicy,
+    host: ExecArch,
+    exec_arch: ExecArch,
+    supervise_writes: bool
+) -> Result<SandboxPrograms, PolicyError> {
+    Ok(SandboxPrograms {
+        policy_filters: policy_programs(policy, host, exec_arch, supervise_writes)?,
+        exec_gate: exec_gate_program(host, is_compat(host, exec_arch)?, supervise_writes)?
+    })
+}
+
+/*
+ *  Without seccompiler there are no filters to build, and use_seccomp
+ *  never lets a run ask for them
+ */
+#[cfg(not(feature = "seccomp"))]
+fn build_programs(_: &SandboxPolicy, _: ExecArch, _: ExecArch, _: bool) -> Result<SandboxPrograms, PolicyError> {
+    Err(PolicyError::Unavailable(String::from("the judger was built without the seccomp feature")))
+}
+
+/**
+ *  Compile the filters a native program gets under `policy` without
+ *  caching them, returning how many instructions they came to, for the
+ *  benchmarks to time
+ */
+pub fn compile_filters(policy: &SandboxPolicy) -> Result<usize, PolicyError> {
+    let host = host_arch()?;
+    let programs = build_programs(policy, host, host, false)?;
+    Ok(programs.policy_filters.iter().chain([&programs.exec_gate]).map(Vec::len).sum())
+}
+
+impl FilterCache {
+    fn get(
+        &self,
+        policy: &SandboxPolicy,
+        host: ExecArch,
+        exec_arch: ExecArch,
+        supervise_writes: bool
+    ) -> Result<Arc<SandboxPrograms>, PolicyError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let cached = entries.iter().find(|c| {
+            c.exec_arch == exec_arch && c.supervise_writes == supervise_writes && c.syscalls == policy.syscalls
+        });
+        if let Some(cached) = cached {
+            return Ok(cached.programs.clone());
+        }
+        let programs = Arc::new(build_programs(policy, host, exec_arch, supervise_writes)?);
+        entries.push(CachedFilters {
+            syscalls: policy.syscalls.clone(),
+            exec_arch,
+            supervise_writes,
+            programs: programs.clone()
+        });
+        Ok(programs)
+    }
+}
+
+/*
+ *  Rewrite the architecture check seccompiler puts in front of every
+ *  program: optionally compare against another `audit_arch`, and let
+ *  syscalls of other architectures through to the remaining filters
+ *  instead of killing the process
+ */
+#[cfg(feature = "seccomp")]
+fn retarget(prog: &mut BpfProgram, audit_arch: Option<u32>) -> Result<(), PolicyError> {
+    check_prologue(prog)?;
+    if let Some(arch) = audit_arch {
+        prog[1].k = arch;
+    }
+    prog[2].k = SECCOMP_RET_ALLOW;
+    Ok(())
+}
+
+#[cfg(feature = "seccomp")]
+fn check_prologue(prog: &BpfProgram) -> Result<(), PolicyError> {
+    let prologue_ok = prog.len() > 3
+        && prog[0].code == BPF_LD_W_ABS && prog[0].k == 4
+        && prog[1].code == BPF_JEQ_K
+        && prog[2].code == BPF_RET_K;
+    if !prologue_ok {
+        return Err(PolicyError::UnsupportedArch(String::from("i386 (unexpected filter layout)")));
+    }
+    Ok(())
+}
+
+/*
+ *  Join two seccompiler programs for different architectures into one:
+ *  syscalls failing the architecture check of `first` continue in `second`
+ */
+#[cfg(feature = "seccomp")]
+fn chain_programs(mut first: BpfProgram, second: BpfProgram) -> Result<BpfProgram, PolicyError> {
+    check_prologue(&first)?;
+    // Jumps are relative to the next instruction
+    first[2].code = BPF_JA;
+    first[2].k = (first.len() - 3) as u32;
+    first.extend(second);
+    Ok(first)
+}
+
+/*
+ *  Child side: install a filter. Done with the raw syscall rather than
+ *  through seccompiler, as the filters installed first deny prctl.
+ *  Requires no_new_privs to be set.
+ */
+unsafe fn install_program(prog: &BpfProgram, flags: libc::c_uint) -> io::Result<i64> {
+    let fprog = libc::sock_fprog {
+        len: prog.len() as u16,
+        filter: prog.as_ptr() as *mut libc::sock_filter
+    };
+    let ret = libc::syscall(
+        libc::SYS_seccomp,
+        SECCOMP_SET_MODE_FILTER,
+        flags,
+        &fprog as *const libc::sock_fprog
+    );
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ret)
+}
+
+/*
+ *  Exec gating
+ *
+ *  Exactly one execve may happen in the sandbox: the judger's own exec of
+ *  the program. A second filter makes execve and execveat raise a seccomp
+ *  user notification; the judger lets the first one through and then
+ *  closes the notification listener, after which the kernel fails every
+ *  further exec attempt with ENOSYS. Unlike checking the path argument,
+ *  this doesn't depend on anything the contestant controls. With write
+ *  supervision the listener stays open and later execs get EPERM instead.
+ */
+
+// Seccomp user notification ABI, not covered by the libc crate
+const SECCOMP_SET_MODE_FILTER: libc::c_uint = 1;
+const SECCOMP_FILTER_FLAG_NEW_LISTENER: libc::c_uint = 1 << 3;
+#[cfg(feature = "seccomp")]
+const SECCOMP_RET_TRACE: u32 = 0x7ff0_0000;
+#[cfg(feature = "seccomp")]
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+const SECCOMP_USER_NOTIF_FLAG_CONTINUE: u32 = 1;
+const SECCOMP_IOCTL_NOTIF_RECV: libc::c_ulong = 0xc050_2100;
+const SECCOMP_IOCTL_NOTIF_SEND: libc::c_ulong = 0xc018_2101;
+const SECCOMP_IOCTL_NOTIF_ID_VALID: libc::c_ulong = 0x4008_2102;
+const SECCOMP_IOCTL_NOTIF_ADDFD: libc::c_ulong = 0x4018_2103;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const AUDIT_ARCH_I386: u32 = 0x4000_0003;
+
+// Classic BPF opcodes appearing in seccompiler's output
+#[cfg(feature = "seccomp")]
+const BPF_LD_W_ABS: u16 = 0x20;
+#[cfg(feature = "seccomp")]
+const BPF_JEQ_K: u16 = 0x15;
+const BPF_RET_K: u16 = 0x06;
+#[cfg(feature = "seccomp")]
+const BPF_JA: u16 = 0x05;
+
+#[repr(C)]
+struct SeccompData {
+    nr: i32,
+    arch: u32,
+    instruction_pointer: u64,
+    args: [u64; 6]
+}
+
+#[repr(C)]
+struct SeccompNotif {
+    id: u64,
+    pid: u32,
+    flags: u32,
+    data: SeccompData
+}
+
+#[repr(C)]
+struct SeccompNotifResp {
+    id: u64,
+    val: i64,
+    error: i32,
+    flags: u32
+}
+
+#[repr(C)]
+struct SeccompNotifAddfd {
+    id: u64,
+    flags: u32,
+    srcfd: u32,
+    newfd: u32,
+    newfd_flags: u32
+}
+
+/*
+ *  Filter turning every execve and execveat into a user notification, and
+ *  with `supervise_writes` also every open for writing.
+ *  With `compat` set, i386 syscalls go through an i386 part that only
+ *  covers the opens, as the i386 policy filter denies all their execs.
+ */
+#[cfg(feature = "seccomp")]
+fn exec_gate_program(arch: ExecArch, compat: bool, supervise_writes: bool) -> Result<BpfProgram, PolicyError> {
+    const NOTIFY: SeccompAction = SeccompAction::Trace(0);
+
+    let write_opens = [
+        SyscallRule::flags_set("open", 1, MODIFYING_FLAGS),
+        SyscallRule::flags_set("openat", 2, MODIFYING_FLAGS)
+    ];
+    let mut rules = vec![SyscallRule::always("execve"), SyscallRule::always("execveat")];
+    if supervise_writes {
+        rules.extend_from_slice(&write_opens);
+    }
+    let mut prog = compile_rules(&rules, arch, native_numbers, NOTIFY)?;
+    if compat && supervise_writes {
+        let mut i386 = compile_rules(&write_opens, ExecArch::X86_64, i386_numbers, NOTIFY)?;
+        retarget(&mut i386, Some(AUDIT_ARCH_I386))?;
+        prog = chain_programs(prog, i386)?;
+    } else if compat {
+        retarget(&mut prog, None)?;
+    }
+    // seccompiler has no user notification action, so retarget the trace returns
+    for insn in prog.iter_mut() {
+        if insn.code == BPF_RET_K && insn.k == SECCOMP_RET_TRACE {
+            insn.k = SECCOMP_RET_USER_NOTIF;
+        }
+    }
+    Ok(prog)
+}
+
+/*
+ *  Child side: install the exec gate and hand its listener to the judger
+ *  over `channel`. Requires no_new_privs to be set.
+ */
+unsafe fn install_exec_gate(prog: &BpfProgram, channel: i32) -> io::Result<()> {
+    let listener = install_program(prog, SECCOMP_FILTER_FLAG_NEW_LISTENER)?;
+    let sent = send_fd(channel, listener as i32);
+    libc::close(listener as i32);
+    sent
+}
+
+// Control message carrying a single file descriptor
+#[repr(C)]
+struct FdMessage {
+    header: libc::cmsghdr,
+    fd: i32
+}
+
+unsafe fn send_fd(channel: i32, fd: i32) -> io::Result<()> {
+    let mut byte = 0u8;
+    let mut iov = libc::iovec { iov_base: (&mut byte as *mut u8).cast(), iov_len: 1 };
+    let mut control: FdMessage = std::mem::zeroed();
+    control.header.cmsg_len = libc::CMSG_LEN(size_of::<i32>() as u32) as usize;
+    control.header.cmsg_level = libc::SOL_SOCKET;
+    control.header.cmsg_type = libc::SCM_RIGHTS;
+    control.fd = fd;
+    let mut msg: libc::msghdr = std::mem::zeroed();
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = (&mut control as *mut FdMessage).cast();
+    msg.msg_controllen = libc::CMSG_SPACE(size_of::<i32>() as u32) as usize;
+    if libc::sendmsg(channel, &msg, 0) < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn recv_fd(channel: &OwnedFd) -> io::Result<OwnedFd> {
+    unsafe {
+        let mut byte = 0u8;
+        let mut iov = libc::iovec { iov_base: (&mut byte as *mut u8).cast(), iov_len: 1 };
+        let mut control: FdMessage = std::mem::zeroed();
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = (&mut control as *mut FdMessage).cast();
+        msg.msg_controllen = size_of::<FdMessage>();
+        let n = libc::recvmsg(channel.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC);
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if n == 0 || control.header.cmsg_type != libc::SCM_RIGHTS {
+            return Err(io::Error::other("sandboxed child exited during setup"));
+        }
+        Ok(OwnedFd::from_raw_fd(control.fd))
+    }
+}
+
+/*
+ *  Judger side: wait for the child's exec of the program and allow it.
+ *  Also returns when the exec was let through, the closest we get to the
+ *  program's start. Dropping the returned listener makes any later exec fail.
+ */
+fn release_exec(pid: i32, channel: &OwnedFd) -> io::Result<(OwnedFd, Instant)> {
+    const SETUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+    let listener = recv_fd(channel)?;
+    let mut pfd = libc::pollfd { fd: listener.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+    let ts = to_timespec(SETUP_TIMEOUT);
+    let ready = unsafe { libc::ppoll(&mut pfd, 1, &ts, ptr::null()) };
+    if ready < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if ready == 0 || pfd.revents & libc::POLLIN == 0 {
+        return Err(io::Error::other("sandboxed child never reached exec"));
+    }
+
+    let mut notif: SeccompNotif = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_RECV, &mut notif) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if notif.pid != pid as u32 {
+        return Err(io::Error::other("unexpected exec notification"));
+    }
+    let resp = SeccompNotifResp {
+        id: notif.id,
+        val: 0,
+        error: 0,
+        flags: SECCOMP_USER_NOTIF_FLAG_CONTINUE
+    };
+    let released = Instant::now();
+    if unsafe { libc::ioctl(listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_SEND, &resp) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((listener, released))
+}
+
+/*
+ *  Write supervision
+ *
+ *  With named file I/O the program has to open one output file for
+ *  writing, but seccomp can't look at paths. Instead the exec gate keeps
+ *  running and also reports opens for writing. The judger reads the path
+ *  from the program's memory and answers an open of the output file by
+ *  installing its own descriptor for it into the program, denying every
+ *  other one. As the kernel never resolves the path itself, the program
+ *  changing it after the check gains nothing.
+ */
+
+/// Judger side of the exec gate once the program runs
+struct WriteSupervisor {
+    listener: OwnedFd,
+    // Where writes to the named output go
+    output: OwnedFd,
+    // Spellings of the output path accepted from the program
+    output_paths: Vec<Vec<u8>>,
+    output_opened: bool,
+    // Let every exec through instead of denying them
+    allow_exec: bool
+}
+
+impl WriteSupervisor {
+    /*
+     *  Answer all pending notifications
+     */
+    fn serve(&mut self) {
+        loop {
+            let mut pfd = libc::pollfd { fd: self.listener.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            if unsafe { libc::poll(&mut pfd, 1, 0) } <= 0 || pfd.revents & libc::POLLIN == 0 {
+                return;
+            }
+            let mut notif: SeccompNotif = unsafe { std::mem::zeroed() };
+            if unsafe { libc::ioctl(self.listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_RECV, &mut notif) } < 0 {
+                // The task may have been killed in the meantime
+                return;
+            }
+            let resp = match self.allow_exec && is_exec(&notif) {
+                true => Some(SeccompNotifResp { id: notif.id, val: 0, error: 0, flags: SECCOMP_USER_NOTIF_FLAG_CONTINUE }),
+                false => self.output_open_flags(&notif).and_then(|flags| self.grant(&notif, flags))
+            };
+            let resp = resp.unwrap_or(SeccompNotifResp { id: notif.id, val: 0, error: -libc::EPERM, flags: 0 });
+            unsafe {
+                libc::ioctl(self.listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_SEND, &resp);
+            }
+        }
+    }
+
+    /*
+     *  The open flags if the notification is an open of the output file
+     */
+    fn output_open_flags(&self, notif: &SeccompNotif) -> Option<i32> {
+        let data = &notif.data;
+        let nr = data.nr as i64;
+        let (open, openat) = match data.arch == AUDIT_ARCH_I386 {
+            true => (Some(5), 295),
+            false => (syscall_number("open"), libc::SYS_openat)
+        };
+        let (dirfd, path_ptr, flags) = if Some(nr) == open {
+            (libc::AT_FDCWD, data.args[0], data.args[1])
+        } else if nr == openat {
+            (data.args[0] as i32, data.args[1], data.args[2])
+        } else {
+            return None;
+        };
+        let path = read_remote_path(notif.pid as i32, path_ptr)?;
+        // Check that the memory read really came from the notifying task
+        if unsafe { libc::ioctl(self.listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_ID_VALID, &notif.id) } < 0 {
+            return None;
+        }
+        let relative_ok = path.starts_with(b"/") || dirfd == libc::AT_FDCWD;
+        match relative_ok && self.output_paths.contains(&path) {
+            true => Some(flags as i32),
+            false => None
+        }
+    }
+
+    fn grant(&mut self, notif: &SeccompNotif, flags: i32) -> Option<SeccompNotifResp> {
+        let addfd = SeccompNotifAddfd {
+            id: notif.id,
+            flags: 0,
+            srcfd: self.output.as_raw_fd() as u32,
+            newfd: 0,
+            newfd_flags: (flags & libc::O_CLOEXEC) as u32
+        };
+        let fd = unsafe { libc::ioctl(self.listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_ADDFD, &addfd) };
+        if fd < 0 {
+            return None;
+        }
+        self.output_opened = true;
+        Some(SeccompNotifResp { id: notif.id, val: fd as i64, error: 0, flags: 0 })
+    }
+}
+
+/*
+ *  Whether the notification is of a native execve or execveat
+ */
+fn is_exec(notif: &SeccompNotif) -> bool {
+    let nr = notif.data.nr as i64;
+    notif.data.arch != AUDIT_ARCH_I386 && (nr == libc::SYS_execve || nr == libc::SYS_execveat)
+}
+
+/*
+ *  Read a NUL terminated path from the memory of process `pid`
+ */
+fn read_remote_path(pid: i32, addr: u64) -> Option<Vec<u8>> {
+    let mem = fs::File::open(format!("/proc/{pid}/mem")).ok()?;
+    let mut buf = vec![0u8; libc::PATH_MAX as usize];
+    let mut filled = 0;
+    // Stop at the end of the mapping, the path may sit right before it
+    while filled < buf.len() {
+        match mem.read_at(&mut buf[filled..], addr + filled as u64) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => filled += n
+        }
+        if buf[..filled].contains(&0) {
+            break;
+        }
+    }
+    let len = buf[..filled].iter().position(|&c| c == 0)?;
+    buf.truncate(len);
+    Some(buf)
+}
+
+/*
+ *  Close-on-exec pipe, as (read end, write end)
+ */
+pub(crate) fn cloexec_pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0i32; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe {
+        Ok((OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])))
+    }
+}
+
+/// Steps of the child setup, as reported over the error pipe
+#[derive(Clone, Copy)]
+enum ChildStage {
+    ProcessGroup,
+    Cgroup,
+    CpuLimit,
+    Stdin,
+    Stdout,
+    Stderr,
+    OpenExec,
+    ScriptFd,
+    JoinNamespace,
+    Workdir,
+    NamedInput,
+    ProvidedFiles,
+    OutputLimit,
+    NoNewPrivs,
+    ExecGate,
+    Policy,
+    Exec
+}
 
-    let prog: BpfProgram = filter.try_into()?;
-    seccompiler::apply_filter(&prog)?;
-    Ok(())
+impl ChildStage {
+    // In declaration order, so that a stage's discriminant indexes it
+    const ALL: [ChildStage; 17] = [
+        Self::ProcessGroup, Self::Cgroup, Self::CpuLimit, Self::Stdin, Self::Stdout, Self::Stderr, Self::OpenExec,
+        Self::ScriptFd, Self::JoinNamespace, Self::Workdir, Self::NamedInput, Self::ProvidedFiles, Self::OutputLimit,
+        Self::NoNewPrivs, Self::ExecGate, Self::Policy, Self::Exec
+    ];
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::ProcessGroup  => "creating its process group",
+            Self::Cgroup        => "joining the cgroup",
+            Self::CpuLimit      => "setting the CPU time limit",
+            Self::Stdin         => "redirecting stdin",
+            Self::Stdout        => "redirecting stdout",
+            Self::Stderr        => "redirecting stderr",
+            Self::OpenExec      => "opening the executable",
+            Self::ScriptFd      => "handing the script to its interpreter",
+            Self::JoinNamespace => "joining a namespace",
+            Self::Workdir       => "entering the scratch directory",
+            Self::NamedInput    => "copying the input file",
+            Self::ProvidedFiles => "copying the provided files",
+            Self::OutputLimit   => "setting the output limit",
+            Self::NoNewPrivs    => "setting no_new_privs",
+            Self::ExecGate      => "installing the exec gate",
+            Self::Policy        => "installing the seccomp policy",
+            Self::Exec          => "executing the program"
+        }
+    }
 }
 
-fn perror(err_src: &str) -> Result<(), NulError> {
-    let cstr = CString::new(err_src)?;
-    unsafe {
-        libc::perror(cstr.as_ptr());
+/// Everything the child needs after fork, prepared beforehand so that the
+/// child doesn't allocate or take locks another judger thread might hold
+struct ChildSetup<'a> {
+    // 0 in a joined pid namespace, which the judger is outside of
+    parent_pid: i32,
+    cgroup_procs: Option<i32>,
+    cpu_rlimit: Option<libc::rlimit>,
+    fsize_rlimit: Option<libc::rlimit>,
+    stdio: [i32; 3],
+    // The namespaces to join but the pid namespace, which the child is
+    // forked into
+    namespaces: &'a [(i32, Namespace)],
+    // The exec gate with the socket its listener goes to, and the policy
+    // filters, both left out when running without seccomp
+    exec_gate: Option<(&'a BpfProgram, i32)>,
+    policy_filters: &'a [BpfProgram],
+    path: &'a CStr,
+    // Scripts are run through /proc/self/fd, so their fd must survive exec
+    exec_cloexec: bool,
+    // The script an interpreter is started on, which it opens through
+    // /dev/fd, so that its fd must survive exec as well
+    script_fd: Option<i32>,
+    // Null terminated
+    argv: &'a [*const libc::c_char],
+    scratch_dir: &'a CStr,
+    tmpfs_options: &'a CStr,
+    private_tmp: bool,
+    umask: libc::mode_t,
+    // Environment for a private tmpfs on /tmp and for the scratch
+    // directory fallback, both null terminated
+    env_tmpfs: &'a [*const libc::c_char],
+    env_scratch: &'a [*const libc::c_char],
+    // With named file I/O, the input to copy into the working directory
+    // and the name to give it there
+    named_input: Option<(i32, &'a CStr)>,
+    // The provided files to copy there, likewise
+    provided: &'a [(i32, &'a CStr)],
+    // Write end of the stdin pipe, which the child must not keep open
+    input_feed: Option<i32>,
+    error_fd: i32
+}
+
+/*
+ *  Child side of sandbox_run, from fork up to exec. Only async-signal-safe
+ *  calls are made here; failures are reported over the error pipe.
+ */
+unsafe fn child_exec(setup: &ChildSetup) -> ! {
+    // Die together with the judger (strictly, with the forking thread).
+    // If it already died before this point we have been reparented.
+    libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+    if libc::getppid() != setup.parent_pid {
+        libc::_exit(127);
     }
-    Ok(())
+    // Descendants stay in this group, the policy denies leaving it
+    if libc::setpgid(0, 0) < 0 {
+        child_fail(setup.error_fd, ChildStage::ProcessGroup);
+    }
+    if let Some(fd) = setup.input_feed {
+        // Otherwise copying a named input from the pipe never sees EOF
+        libc::close(fd);
+    }
+    if let Some(procs) = setup.cgroup_procs {
+        // Move ourselves into the run's cgroup
+        if libc::write(procs, b"0".as_ptr().cast(), 1) < 0 {
+            child_fail(setup.error_fd, ChildStage::Cgroup);
+        }
+    }
+    if let Some(limit) = &setup.cpu_rlimit {
+        if libc::setrlimit(libc::RLIMIT_CPU, limit) < 0 {
+            child_fail(setup.error_fd, ChildStage::CpuLimit);
+        }
+    }
+    // The copies lose close-on-exec, the originals go away on exec
+    let stdio_stages = [ChildStage::Stdin, ChildStage::Stdout, ChildStage::Stderr];
+    for (target, &fd) in setup.stdio.iter().enumerate() {
+        if libc::dup2(fd, target as i32) < 0 {
+            child_fail(setup.error_fd, stdio_stages[target]);
+        }
+    }
+
+    // Opened before /tmp gets covered, in case the program lives there
+    let cloexec = if setup.exec_cloexec { libc::O_CLOEXEC } else { 0 };
+    let exec_fd = libc::open(setup.path.as_ptr(), libc::O_PATH | cloexec);
+    if exec_fd < 0 {
+        child_fail(setup.error_fd, ChildStage::OpenExec);
+    }
+    if let Some(fd) = setup.script_fd {
+        if libc::fcntl(fd, libc::F_SETFD, 0) < 0 {
+            child_fail(setup.error_fd, ChildStage::ScriptFd);
+        }
+    }
+    for &(fd, kind) in setup.namespaces {
+        if libc::setns(fd, kind.clone_flag()) < 0 {
+            child_report(setup.error_fd, ChildStage::JoinNamespace, kind as u8);
+        }
+    }
+    // A private mount namespace with a size-limited tmpfs on /tmp hides
+    // everything else in there. Without the privileges for it, fall back
+    // to the per-run directory, which can't be size-limited.
+    let private_tmp = setup.private_tmp
+        && libc::unshare(libc::CLONE_NEWNS) == 0
+        && libc::mount(ptr::null(), c"/".as_ptr(), ptr::null(), libc::MS_REC | libc::MS_PRIVATE, ptr::null()) == 0
+        && libc::mount(
+            c"tmpfs".as_ptr(),
+            c"/tmp".as_ptr(),
+            c"tmpfs".as_ptr(),
+            libc::MS_NOSUID | libc::MS_NODEV,
+            setup.tmpfs_options.as_ptr().cast()
+        ) == 0;
+    let (workdir, envp) = match private_tmp {
+        true => (c"/tmp", setup.env_tmpfs),
+        false => (setup.scratch_dir, setup.env_scratch)
+    };
+    if libc::chdir(workdir.as_ptr()) < 0 {
+        child_fail(setup.error_fd, ChildStage::Workdir);
+    }
+    libc::umask(setup.umask);
+    if let Some((input_fd, name)) = setup.named_input {
+        if copy_named_input(input_fd, name).is_err() {
+            child_fail(setup.error_fd, ChildStage::NamedInput);
+        }
+    }
+    for &(fd, name) in setup.provided {
+        if copy_named_input(fd, name).is_err() {
+            child_fail(setup.error_fd, ChildStage::ProvidedFiles);
+        }
+    }
+    // Only now, so that the input copy isn't cut short by it
+    if let Some(limit) = &setup.fsize_rlimit {
+        if libc::setrlimit(libc::RLIMIT_FSIZE, limit) < 0 {
+            child_fail(setup.error_fd, ChildStage::OutputLimit);
+        }
+    }
+
+    if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+        child_fail(setup.error_fd, ChildStage::NoNewPrivs);
+    }
+    if let Some((gate, channel)) = setup.exec_gate {
+        if install_exec_gate(gate, channel).is_err() {
+            child_fail(setup.error_fd, ChildStage::ExecGate);
+        }
+    }
+    for prog in setup.policy_filters {
+        if install_program(prog, 0).is_err() {
+            child_fail(setup.error_fd, ChildStage::Policy);
+        }
+    }
+    libc::syscall(
+        libc::SYS_execveat,
+        exec_fd,
+        c"".as_ptr(),
+        setup.argv.as_ptr(),
+        envp.as_ptr(),
+        libc::AT_EMPTY_PATH
+    );
+    child_fail(setup.error_fd, ChildStage::Exec);
+}
+
+/*
+ *  Child side: copy the input, or a provided file, into a read-only file
+ *  `name` in the working directory
+ */
+unsafe fn copy_named_input(input_fd: i32, name: &CStr) -> io::Result<()> {
+    let fd = libc::open(name.as_ptr(), libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC, 0o444);
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let result = copy_fd(input_fd, fd);
+    libc::close(fd);
+    result
+}
+
+/*
+ *  Copy everything from `from` to `to`, async-signal-safe
+ */
+unsafe fn copy_fd(from: i32, to: i32) -> io::Result<()> {
+    let mut use_sendfile = true;
+    let mut buf = [0u8; 16 * 1024];
+    loop {
+        let n = match use_sendfile {
+            true => libc::sendfile(to, from, ptr::null_mut(), 1 << 30),
+            false => libc::read(from, buf.as_mut_ptr().cast(), buf.len())
+        };
+        if n == 0 {
+            return Ok(());
+        }
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                // Input from a pipe, which sendfile can't read from
+                Some(libc::EINVAL) if use_sendfile => {
+                    use_sendfile = false;
+                    continue;
+                },
+                _ => return Err(err)
+            }
+        }
+        if !use_sendfile {
+            let mut written = 0;
+            while written < n as usize {
+                let w = libc::write(to, buf[written..].as_ptr().cast(), n as usize - written);
+                if w < 0 {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                    continue;
+                }
+                written += w as usize;
+            }
+        }
+    }
+}
+
+/*
+ *  Report the failed stage and errno to the judger and give up
+ */
+unsafe fn child_fail(error_fd: i32, stage: ChildStage) -> ! {
+    child_report(error_fd, stage, 0);
+}
+
+/*
+ *  child_fail with what the stage failed on, such as the namespace it
+ *  couldn't join
+ */
+unsafe fn child_report(error_fd: i32, stage: ChildStage, detail: u8) -> ! {
+    let errno = io::Error::last_os_error().raw_os_error().unwrap_or(0);
+    let mut report = [0u8; 8];
+    report[0] = stage as u8;
+    report[1] = detail;
+    report[4..].copy_from_slice(&errno.to_ne_bytes());
+    libc::write(error_fd, report.as_ptr().cast(), report.len());
+    libc::_exit(127);
+}
+
+/*
+ *  Parent side: wait for the child to exec or report a failure.
+ *  Returns the error the child ran into, if any.
+ */
+fn read_child_error(error_pipe: &OwnedFd) -> io::Result<Option<io::Error>> {
+    let mut report = [0u8; 8];
+    let n = loop {
+        let n = unsafe { libc::read(error_pipe.as_raw_fd(), report.as_mut_ptr().cast(), report.len()) };
+        if n >= 0 {
+            break n as usize;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    };
+    if n < report.len() {
+        return Ok(None);
+    }
+    let stage = match ChildStage::ALL.get(report[0] as usize) {
+        Some(ChildStage::JoinNamespace) => Namespace::ALL.get(report[1] as usize).map_or("joining a namespace", |ns| ns.joining()),
+        Some(stage) => stage.description(),
+        None => "setting up"
+    };
+    let errno = i32::from_ne_bytes([report[4], report[5], report[6], report[7]]);
+    let cause = io::Error::from_raw_os_error(errno);
+    Ok(Some(io::Error::new(cause.kind(), format!("sandboxed child failed {stage}: {cause}"))))
 }
 
-fn execv(path: &CString, args: &[CString]) -> ! {
-    let mut strs: Vec<*const i8> = args.iter().map(|x| x.as_ptr()).collect();
-    strs.push(0 as *const i8);
+/*
+ *  Connected pair of sockets for passing file descriptors to the judger
+ */
+fn fd_channel() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0i32; 2];
+    let ret = unsafe {
+        libc::socketpair(libc::AF_UNIX, libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC, 0, fds.as_mut_ptr())
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
     unsafe {
-        libc::execv(path.as_ptr(), strs.as_ptr());
+        Ok((OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])))
+    }
+}
+
+/*
+ *  The environment of the plan with TMPDIR pointed at `tmpdir`
+ */
+fn environment(plan: &LaunchPlan, tmpdir: &OsStr) -> Result<Vec<CString>, NulError> {
+    let set = plan.set_env.iter().map(|(key, value)| (OsStr::new(key), OsStr::new(value)));
+    let mut env = Vec::new();
+    for (key, value) in plan.env.iter().map(|(key, value)| (key.as_os_str(), value.as_os_str())).chain(set) {
+        let mut entry = key.as_bytes().to_vec();
+        entry.push(b'=');
+        entry.extend_from_slice(value.as_bytes());
+        env.push(CString::new(entry)?);
+    }
+    let mut entry = b"TMPDIR=".to_vec();
+    entry.extend_from_slice(tmpdir.as_bytes());
+    env.push(CString::new(entry)?);
+    Ok(env)
+}
+
+fn null_terminated(strings: &[CString]) -> Vec<*const libc::c_char> {
+    let mut ptrs: Vec<*const libc::c_char> = strings.iter().map(|x| x.as_ptr()).collect();
+    ptrs.push(ptr::null());
+    ptrs
+}
+
+fn open_redirect(path: &Path, options: &fs::OpenOptions) -> io::Result<OwnedFd> {
+    match options.open(path) {
+        Ok(f) => Ok(f.into()),
+        Err(e) => Err(io::Error::new(e.kind(), format!("cannot open {}: {e}", path.display())))
+    }
+}
+
+/*
+ *  Write everything from `source` into the program's stdin pipe from a
+ *  thread of its own, so that the program can read at its own pace. Stops
+ *  quietly when the program closes its end early, as the write then fails
+ *  with EPIPE (the Rust runtime ignores SIGPIPE).
+ */
+fn feed_input(pipe: OwnedFd, mut source: Box<dyn Read + Send>) {
+    // Not joined: a reader like a terminal may block long after the run
+    thread::spawn(move || {
+        let mut pipe = fs::File::from(pipe);
+        let _ = io::copy(&mut source, &mut pipe);
+    });
+}
+
+/*
+ *  Copy what the program writes to `pipe` into `file`, the first `limit`
+ *  bytes of it, and drop the rest. Sends how much was written in all once
+ *  the pipe is closed, when the program and everything it started ended.
+ */
+fn drain_stderr(pipe: OwnedFd, mut file: fs::File, limit: u64) -> mpsc::Receiver<u64> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut pipe = fs::File::from(pipe);
+        let mut buffer = vec![0; 64 * 1024];
+        let mut written: u64 = 0;
+        // A full disk only costs the capture
+        let mut keeping = true;
+        loop {
+            let n = match pipe.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break
+            };
+            let kept = limit.saturating_sub(written).min(n as u64) as usize;
+            if keeping && kept > 0 && file.write_all(&buffer[..kept]).is_err() {
+                keeping = false;
+            }
+            written += n as u64;
+        }
+        let _ = sender.send(written);
+    });
+    receiver
+}
+
+/*
+ *  The paths under which a program may refer to its output file
+ */
+fn output_spellings(name: &str, scratch_dir: &Path) -> Vec<Vec<u8>> {
+    vec![
+        name.as_bytes().to_vec(),
+        format!("./{name}").into_bytes(),
+        format!("/tmp/{name}").into_bytes(),
+        scratch_dir.join(name).into_os_string().into_vec()
+    ]
+}
+
+/*
+ *  RLIMIT_CPU comfortably above `limit`, which the judger checks more
+ *  precisely itself. It only counts per process and in whole seconds, so
+ *  it just catches what slips through. SIGXCPU comes at the soft limit,
+ *  SIGKILL a second later.
+ */
+fn cpu_rlimit(limit: Duration) -> (u64, u64) {
+    let soft = limit.as_secs().saturating_add(2);
+    (soft, soft.saturating_add(1))
+}
+
+/*
+ *  Whether the judger's variable `key` is one of how its host is set up,
+ *  the time zone and locale, which programs get the policy's instead of
+ */
+fn host_specific(key: &OsStr) -> bool {
+    key == "TZ" || key == "LANG" || key == "LANGUAGE" || key.as_bytes().starts_with(b"LC_")
+}
+
+/*
+ *  The policy's variables, the last of each name
+ */
+fn policy_env(policy: &SandboxPolicy) -> Vec<(String, String)> {
+    let mut env: Vec<(String, String)> = Vec::new();
+    for (name, value) in &policy.env {
+        env.retain(|(earlier, _)| earlier != name);
+        env.push((name.clone(), value.clone()));
+    }
+    env
+}
+
+/*
+ *  Have orphaned descendants of the program reparented to the judger
+ *  instead of init, so that they can still be reaped and accounted for
+ */
+fn become_subreaper() -> io::Result<()> {
+    if unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) } < 0 {
+        return Err(io::Error::last_os_error());
     }
-    perror("execv").unwrap();
-    panic!("Unexpected execution");
+    Ok(())
+}
+
+// Forks still to fail, see inject_fork_failures
+static INJECTED_FORK_FAILURES: AtomicU32 = AtomicU32::new(0);
+
+/**
+ *  Make the next `count` forks of sandbox_run fail with EAGAIN, as they
+ *  do on a host out of processes, for the self test to check retrying
+ */
+pub fn inject_fork_failures(count: u32) {
+    INJECTED_FORK_FAILURES.store(count, Ordering::SeqCst);
 }
 
 fn fork() -> Result<i32, io::Error> {
+    let injected = INJECTED_FORK_FAILURES.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+    if injected.is_ok() {
+        return Err(io::Error::from_raw_os_error(libc::EAGAIN));
+    }
     let pid: i32;
     unsafe {
         pid = libc::fork();
     }
     if pid < 0 {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "Failed to fork"
-        ));
+        return Err(io::Error::last_os_error());
     }
     Ok(pid)
 }
 
-pub fn sandbox_run(filepath: &Path, args: &[&str], stdin_file: &Path, stdout_file: &Path) -> Result<(i32, Instant), Box<dyn Error>> {
-    if !stdout_file.exists() {
-        drop(fs::File::create(stdout_file)?);
+/// Where the input of the sandboxed program comes from
+pub enum InputSource {
+    /// Read from this file
+    File(PathBuf),
+    /// These bytes, fed to the program through a pipe
+    Bytes(Vec<u8>),
+    /// Streamed to the program as it reads, e.g. the judger's own stdin
+    Reader(Box<dyn Read + Send>),
+    /// No input: /dev/null, which reads as an immediate end of file
+    Null
+}
+
+/// Files the sandboxed program works with
+pub struct SandboxIo<'a> {
+    /// What the program reads
+    pub stdin: InputSource,
+    /// Where its standard output goes
+    pub stdout: &'a Path,
+    /// Where its standard error goes
+    pub stderr: &'a Path,
+    /// Private working directory, used when /tmp can't be replaced by a tmpfs
+    pub scratch_dir: &'a Path,
+    /// Read and write named files in the working directory instead of
+    /// stdin and stdout
+    pub named: Option<NamedFiles<'a>>,
+    /// Files copied read-only into the working directory before the
+    /// program starts, as (source, name there), such as a dictionary the
+    /// program opens by name
+    pub provided: &'a [(PathBuf, String)]
+}
+
+/// Names of the files a program does its I/O through, relative to its
+/// working directory. The contents of `SandboxIo::stdin` are provided as
+/// `input`, and what the program writes to `output` ends up in
+/// `SandboxIo::stdout`.
+pub struct NamedFiles<'a> {
+    /// The file the program reads
+    pub input: &'a str,
+    /// The file the program writes
+    pub output: &'a str
+}
+
+/// What sandbox_run sets up for a program, worked out without starting
+/// it. sandbox_run follows the plan itself, so that a described run can't
+/// differ from a real one.
+#[derive(Clone, Debug)]
+pub(crate) struct LaunchPlan {
+    pub path: PathBuf,
+    pub argv: Vec<String>,
+    // The judger's environment without TMPDIR, which is set to where the
+    // program's /tmp ends up, and without the variables host_specific
+    // picks or set_env sets
+    pub env: Vec<(OsString, OsString)>,
+    // The policy's variables, one of each name
+    pub set_env: Vec<(String, String)>,
+    pub umask: u32,
+    // Soft and hard RLIMIT_CPU in seconds
+    pub cpu_rlimit: Option<(u64, u64)>,
+    // RLIMIT_FSIZE in bytes, one past the output limit
+    pub fsize_rlimit: Option<u64>,
+    pub host_arch: ExecArch,
+    pub exec_arch: ExecArch,
+    // Not an ELF file, so started through its interpreter
+    pub script: bool,
+    // The loader of a dynamically linked executable
+    pub loader: Option<PathBuf>,
+    // The script when an Interpreter was given, and the argument of the
+    // interpreter's that names it
+    pub interpreted: Option<(PathBuf, usize)>,
+    pub syscalls: Vec<SyscallRule>,
+    // Strength the policy asks for, and whether the filters get installed
+    pub strength: SandboxStrength,
+    pub seccomp: bool,
+    // Writes go through the judger, which only lets the named output through
+    pub supervise_writes: bool,
+    // Whether /tmp is made private at all
+    pub private_tmp: bool,
+    // Size of the tmpfs on /tmp in a private mount namespace
+    pub tmpfs_size: u64,
+    // Working directory and TMPDIR without the privileges for the tmpfs
+    pub work_dir: PathBuf,
+    // Namespaces the program is put in, those the judger is in already
+    // left out
+    pub namespaces: Vec<JoinNamespace>,
+    // What the child does after fork, in order
+    pub steps: Vec<&'static str>
+}
+
+/*
+ *  Work out how sandbox_run would start the program at `filepath`, or
+ *  its interpreter with it, failing where it would fail before forking.
+ *  The interpreter gets the arguments the kernel would give it.
+ */
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn plan_launch(
+    filepath: &Path,
+    args: &[&str],
+    interpreter: Option<&Interpreter>,
+    policy: &SandboxPolicy,
+    named_files: bool,
+    provided_files: bool,
+    in_cgroup: bool,
+    work_dir: &Path
+) -> Result<LaunchPlan, Box<dyn Error>> {
+    let host_arch = host_arch()?;
+    let (path, argv, interpreted) = match interpreter {
+        Some(interpreter) => {
+            let mut argv = vec![interpreter.path.to_string_lossy().into_owned()];
+            argv.extend(interpreter.arg.clone());
+            let script = (filepath.to_path_buf(), argv.len());
+            argv.push(filepath.to_string_lossy().into_owned());
+            argv.extend(args.iter().skip(1).map(|s| s.to_string()));
+            (interpreter.path.clone(), argv, Some(script))
+        },
+        None => (filepath.to_path_buf(), args.iter().map(|s| s.to_string()).collect(), None)
+    };
+    let elf = elf::inspect(&path)?;
+    let exec_kind = ElfKind::of(elf.as_ref());
+    let exec_arch = resolve_exec_arch(exec_kind, policy)?;
+    let seccomp = use_seccomp(policy)?;
+    match seccomp {
+        true => {
+            check_filter_arch(host_arch)?;
+            // Compiling the filters fails the same way for unsupported pairs
+            is_compat(host_arch, exec_arch)?;
+        },
+        // Only write supervision can hand the program its output file
+        false if named_files => return Err(Box::new(PolicyError::NeedsSeccomp("named file I/O"))),
+        false => {}
     }
+    let namespaces: Vec<JoinNamespace> = open_namespaces(&policy.join_namespaces)?.into_iter().map(|(ns, _)| ns).collect();
+    let set_env = policy_env(policy);
+    let steps = ChildStage::ALL.iter()
+        .flat_map(|stage| match stage {
+            ChildStage::ExecGate | ChildStage::Policy if !seccomp => Vec::new(),
+            ChildStage::Cgroup if !in_cgroup => Vec::new(),
+            ChildStage::CpuLimit if policy.cpu_limit.is_none() => Vec::new(),
+            ChildStage::NamedInput if !named_files => Vec::new(),
+            ChildStage::ProvidedFiles if !provided_files => Vec::new(),
+            ChildStage::OutputLimit if policy.output_limit.is_none() => Vec::new(),
+            ChildStage::ScriptFd if interpreted.is_none() => Vec::new(),
+            // The pid namespace is the one the child is forked into
+            ChildStage::JoinNamespace => namespaces.iter()
+                .filter(|ns| ns.kind != Namespace::Pid)
+                .map(|ns| ns.kind.joining())
+                .collect(),
+            _ => vec![stage.description()]
+        })
+        .collect();
+    Ok(LaunchPlan {
+        path,
+        argv,
+        env: std::env::vars_os()
+            .filter(|(key, _)| key != "TMPDIR" && !host_specific(key) && !set_env.iter().any(|(name, _)| key == name.as_str()))
+            .collect(),
+        set_env,
+        umask: PROGRAM_UMASK,
+        cpu_rlimit: policy.cpu_limit.map(cpu_rlimit),
+        // Output of exactly the limit fits, so that more than it can be told apart
+        fsize_rlimit: policy.output_limit.map(|limit| limit.saturating_add(1)),
+        host_arch,
+        exec_arch,
+        script: exec_kind == ElfKind::NotElf,
+        loader: elf.and_then(|elf| elf.interpreter),
+        interpreted,
+        syscalls: policy.syscalls.clone(),
+        strength: policy.strength,
+        seccomp,
+        supervise_writes: named_files,
+        private_tmp: policy.private_tmp,
+        tmpfs_size: policy.scratch_limit,
+        work_dir: work_dir.to_path_buf(),
+        namespaces,
+        steps
+    })
+}
 
-    let full_name_c = CString::new(filepath.to_string_lossy().as_bytes())?;
+/*
+ *  Start the program at `filepath` in the sandbox, reusing the seccomp
+ *  programs in `filters` where possible
+ */
+pub(crate) fn sandbox_run(
+    filepath: &Path,
+    args: &[&str],
+    interpreter: Option<&Interpreter>,
+    io: SandboxIo,
+    policy: &SandboxPolicy,
+    filters: &FilterCache,
+    cgroup: Option<&RunCgroup>
+) -> Result<SandboxChild, Box<dyn Error>> {
+    let _span = log::span("setup");
+    let mut plan = plan_launch(
+        filepath,
+        args,
+        interpreter,
+        policy,
+        io.named.is_some(),
+        !io.provided.is_empty(),
+        cgroup.is_some(),
+        io.scratch_dir
+    )?;
+    debug!("starting {} as {:?} in {}", plan.path.display(), plan.argv, plan.work_dir.display());
+    trace!(
+        "sandboxing a {} program on {}, {} syscall rules, seccomp: {}, supervised writes: {}",
+        plan.exec_arch,
+        plan.host_arch,
+        plan.syscalls.len(),
+        plan.seccomp,
+        plan.supervise_writes
+    );
+    trace!("cpu rlimit {:?}, fsize rlimit {:?}, tmpfs of {} bytes", plan.cpu_rlimit, plan.fsize_rlimit, plan.tmpfs_size);
+    debug!(
+        "environment {}, {} variables of the judger's, umask {:03o}",
+        plan.set_env.iter().map(|(name, value)| format!("{name}={value}")).collect::<Vec<_>>().join(" "),
+        plan.env.len(),
+        plan.umask
+    );
+    // Opened before /tmp gets covered like the executable, and named by
+    // its fd for the same reason
+    let script = match &plan.interpreted {
+        Some((path, index)) => {
+            let file = fs::File::open(path).map_err(|e| io::Error::new(e.kind(), format!("cannot open script {}: {e}", path.display())))?;
+            plan.argv[*index] = format!("/dev/fd/{}", file.as_raw_fd());
+            Some(file)
+        },
+        None => None
+    };
+    let full_name_c = CString::new(plan.path.to_string_lossy().as_bytes())?;
     let mut conv_args: Vec<CString> = Vec::new();
-    for &s in args {
-        conv_args.push(CString::new(s)?);
+    for s in &plan.argv {
+        conv_args.push(CString::new(s.as_str())?);
     }
+    let argv = null_terminated(&conv_args);
+    let scratch_dir_c = CString::new(plan.work_dir.as_os_str().as_bytes())?;
+    let tmpfs_options = CString::new(format!("size={},mode=0700", plan.tmpfs_size))?;
+    let env_tmpfs = environment(&plan, OsStr::new("/tmp"))?;
+    let env_scratch = environment(&plan, plan.work_dir.as_os_str())?;
+    let env_tmpfs_ptrs = null_terminated(&env_tmpfs);
+    let env_scratch_ptrs = null_terminated(&env_scratch);
 
-    let inf = CString::new(stdin_file.to_string_lossy().as_bytes())?;
-    let outf = CString::new(stdout_file.to_string_lossy().as_bytes())?;
+    // Opened here so that failures surface as errors; std opens close-on-exec
+    let (input_fd, feed) = match io.stdin {
+        InputSource::File(path) => (open_redirect(&path, fs::OpenOptions::new().read(true))?, None),
+        InputSource::Bytes(bytes) => {
+            let (read, write) = cloexec_pipe()?;
+            (read, Some((write, Box::new(io::Cursor::new(bytes)) as Box<dyn Read + Send>)))
+        },
+        InputSource::Reader(reader) => {
+            let (read, write) = cloexec_pipe()?;
+            (read, Some((write, reader)))
+        },
+        InputSource::Null => (open_redirect(Path::new("/dev/null"), fs::OpenOptions::new().read(true))?, None)
+    };
+    // The capture files must be new, never something planted at their path
+    let mut capture = fs::OpenOptions::new();
+    capture.write(true).create_new(true).custom_flags(libc::O_NOFOLLOW);
+    let output_fd = open_redirect(io.stdout, &capture)?;
+    // Stderr goes through a pipe the judger drains, keeping what the
+    // stderr limit allows
+    let stderr_file = open_redirect(io.stderr, &capture)?;
+    let (stderr_read, stderr_fd) = cloexec_pipe()?;
+    let (stdin_fd, stdout_fd, named_input_c) = match &io.named {
+        Some(named) => {
+            let null = Path::new("/dev/null");
+            (
+                open_redirect(null, fs::OpenOptions::new().read(true))?,
+                open_redirect(null, fs::OpenOptions::new().write(true))?,
+                Some(CString::new(named.input)?)
+            )
+        },
+        None => (input_fd.try_clone()?, output_fd.try_clone()?, None)
+    };
+    let mut provided_files = Vec::with_capacity(io.provided.len());
+    for (source, name) in io.provided {
+        let file = fs::File::open(source).map_err(|e| io::Error::new(e.kind(), format!("cannot open provided file {}: {e}", source.display())))?;
+        provided_files.push((file, CString::new(name.as_str())?));
+    }
+    let provided: Vec<(i32, &CStr)> = provided_files.iter().map(|(file, name)| (file.as_raw_fd(), name.as_c_str())).collect();
+    let cgroup_procs = cgroup.map(|c| c.open_procs()).transpose()?;
+    let programs = match plan.seccomp {
+        true => Some(filters.get(policy, plan.host_arch, plan.exec_arch, plan.supervise_writes)?),
+        false => None
+    };
+    let gate = programs.as_ref().map(|_| fd_channel()).transpose()?;
+    let namespace_fds = open_namespaces(&plan.namespaces)?;
+    let pid_namespace = namespace_fds.iter().find(|(ns, _)| ns.kind == Namespace::Pid);
+    let child_namespaces: Vec<(i32, Namespace)> = namespace_fds.iter()
+        .filter(|(ns, _)| ns.kind != Namespace::Pid)
+        .map(|(ns, fd)| (fd.as_raw_fd(), ns.kind))
+        .collect();
+    // The child reports a failed setup step over this pipe. Seeing it
+    // closed without a report means the exec went through.
+    let (error_read, error_write) = cloexec_pipe()?;
+    let setup = ChildSetup {
+        parent_pid: match pid_namespace {
+            Some(_) => 0,
+            None => unsafe { libc::getpid() }
+        },
+        cgroup_procs: cgroup_procs.as_ref().map(|fd| fd.as_raw_fd()),
+        cpu_rlimit: plan.cpu_rlimit.map(|(soft, hard)| libc::rlimit {
+            rlim_cur: soft as libc::rlim_t,
+            rlim_max: hard as libc::rlim_t
+        }),
+        fsize_rlimit: plan.fsize_rlimit.map(|bytes| libc::rlimit {
+            rlim_cur: bytes as libc::rlim_t,
+            rlim_max: bytes as libc::rlim_t
+        }),
+        stdio: [stdin_fd.as_raw_fd(), stdout_fd.as_raw_fd(), stderr_fd.as_raw_fd()],
+        namespaces: &child_namespaces,
+        exec_gate: programs.as_ref().zip(gate.as_ref()).map(|(p, (_, child))| (&p.exec_gate, child.as_raw_fd())),
+        policy_filters: programs.as_ref().map_or(&[], |p| p.policy_filters.as_slice()),
+        path: &full_name_c,
+        exec_cloexec: !plan.script,
+        script_fd: script.as_ref().map(|file| file.as_raw_fd()),
+        argv: &argv,
+        scratch_dir: &scratch_dir_c,
+        tmpfs_options: &tmpfs_options,
+        private_tmp: plan.private_tmp,
+        umask: plan.umask as libc::mode_t,
+        env_tmpfs: &env_tmpfs_ptrs,
+        env_scratch: &env_scratch_ptrs,
+        named_input: named_input_c.as_deref().map(|name| (input_fd.as_raw_fd(), name)),
+        provided: &provided,
+        input_feed: feed.as_ref().map(|(pipe, _)| pipe.as_raw_fd()),
+        error_fd: error_write.as_raw_fd()
+    };
+    become_subreaper()?;
     let inst = Instant::now();
-    let pid = fork()?;
+    let pid = match pid_namespace {
+        Some((ns, fd)) => fork_in_pid_namespace(ns, fd)?,
+        None => fork()?
+    };
     if pid == 0 {
-        // Sub process
+        unsafe { child_exec(&setup) };
+    }
+    // The child can't log itself, as it may not allocate
+    trace!("child {pid} sets up: {}", plan.steps.join(", "));
+    // Also done here so that the group exists before the child gets to it
+    unsafe {
+        libc::setpgid(pid, pid);
+    }
+    let gate_parent = gate.map(|(parent, _)| parent);
+    drop(error_write);
+    drop(stderr_fd);
+    if let Some((pipe, source)) = feed {
+        feed_input(pipe, source);
+    }
+    // Owning the child from here on kills it again should setup fail
+    let mut child = SandboxChild::new(pid, inst, plan.seccomp);
+    child.stderr_drain = Some(drain_stderr(stderr_read, fs::File::from(stderr_file), policy.stderr_limit));
+    let Some(gate_parent) = gate_parent else {
+        // Without the exec gate, the pipe closing on exec is all there is to see
+        if let Some(e) = read_child_error(&error_read)? {
+            debug!("child {pid} failed before exec: {e}");
+            return Err(Box::new(e));
+        }
+        child.start = Instant::now();
+        trace!("child {pid} executed without seccomp");
+        return Ok(child);
+    };
+    let listener = match release_exec(pid, &gate_parent) {
+        Ok((listener, released)) => {
+            child.start = released;
+            trace!("child {pid} released to exec");
+            listener
+        },
+        Err(e) => {
+            // Make sure the pipe gets closed, then prefer the child's own report
+            let _ = child.kill(libc::SIGKILL);
+            let e = read_child_error(&error_read).ok().flatten().unwrap_or(e);
+            debug!("child {pid} failed before exec: {e}");
+            return Err(Box::new(e));
+        }
+    };
+    if let Some(e) = read_child_error(&error_read)? {
+        debug!("child {pid} failed before exec: {e}");
+        return Err(Box::new(e));
+    }
+    if io.named.is_some() || policy.allow_exec {
+        child.supervisor = Some(WriteSupervisor {
+            listener,
+            output: output_fd,
+            output_paths: io.named.as_ref().map_or(Vec::new(), |named| output_spellings(named.output, io.scratch_dir)),
+            output_opened: false,
+            allow_exec: policy.allow_exec
+        });
+    }
+    Ok(child)
+}
+
+/// Starts programs for a JudgeSession, which decides the verdict from
+/// what the started child reports. LinuxSandbox is the real one; others
+/// stand in for it where programs can't be sandboxed, as in tests.
+pub trait Sandbox: Send + Sync {
+    /// Start the program `spec` describes, returning once it runs. The
+    /// stdout and stderr files of `spec.io` are the sandbox's to fill.
+    fn spawn(&self, spec: RunSpec<'_>) -> Result<Box<dyn SandboxChildHandle>, Box<dyn Error>>;
+}
+
+/// The interpreter of a script, started with the script as its argument
+/// the way the kernel starts it for a `#!` line. The run is then the
+/// interpreter's: its architecture, and the syscalls it makes from the
+/// start.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Interpreter {
+    /// Its executable
+    pub path: PathBuf,
+    /// The one argument it gets before the script, if any
+    pub arg: Option<String>
+}
+
+/// One run of a program, as a JudgeSession asks a Sandbox for it
+pub struct RunSpec<'a> {
+    /// The program, already copied to the scratch directory unless the
+    /// session runs it in place
+    pub exec: &'a Path,
+    /// Its argv, starting with the name it runs as. An interpreter gets
+    /// the script in place of that name.
+    pub args: &'a [&'a str],
+    /// What runs `exec` as a script, instead of the kernel going by its
+    /// `#!` line
+    pub interpreter: Option<&'a Interpreter>,
+    /// Its input and where its output goes
+    pub io: SandboxIo<'a>,
+    /// What it may do, and its limits
+    pub policy: &'a SandboxPolicy,
+    // Cgroup to start the program in, only the real sandbox has them
+    pub(crate) cgroup: Option<&'a RunCgroup>,
+    /// Turns readable when a wait on the program should end early, as
+    /// when its run is cancelled. Sandboxes that don't poll it are woken
+    /// by the judger's own timeouts instead.
+    pub wakeup: Option<BorrowedFd<'a>>
+}
+
+impl<'a> RunSpec<'a> {
+    /// A run of `exec` with `args`, as a program outside a session starts
+    /// it, with no interpreter, in no cgroup and with nothing to wake its
+    /// waits early
+    pub fn new(exec: &'a Path, args: &'a [&'a str], io: SandboxIo<'a>, policy: &'a SandboxPolicy) -> Self {
+        RunSpec { exec, args, interpreter: None, io, policy, cgroup: None, wakeup: None }
+    }
+}
+
+/// A program a Sandbox started. Dropping it before it was reaped kills
+/// and reaps the program, so an early return never leaks it. Handles are
+/// Send for the runs judged asynchronously, which may move between threads.
+pub trait SandboxChildHandle: Send {
+    /// Its process id, for the log
+    fn pid(&self) -> i32;
+
+    /// Whether the program runs under the seccomp filter
+    fn seccomp(&self) -> bool;
+
+    /// When the child was let through to exec the program, the reference
+    /// point for its wall time
+    fn start_instant(&self) -> Instant;
+
+    /// Wait until the child exits or stops, or the timeout elapses. An
+    /// exited child is reaped right away. A timeout of None waits forever.
+    fn wait(&mut self, timeout: Option<Duration>) -> io::Result<WaitOutcome>;
+
+    /// A descriptor that turns readable once the child exits, for event
+    /// loops to wait on before calling wait with a zero timeout. None if
+    /// there is none, and then only wait tells when the child is done.
+    fn exit_fd(&self) -> Option<BorrowedFd<'_>> {
+        None
+    }
+
+    /// Send a signal to the child and all its descendants, doing nothing
+    /// once it has been reaped
+    fn kill(&self, signal: i32) -> io::Result<()>;
+
+    /// Resource usage of the child and its descendants, available once it
+    /// has been reaped
+    fn rusage(&self) -> Option<&ResourceUsage>;
+
+    /// With named file I/O, whether the program opened its output file
+    fn named_output_opened(&self) -> Option<bool>;
+
+    /// Memory the running child has resident now, for sampling it
+    fn resident_memory(&self) -> Option<u64>;
+
+    /// CPU time the running child used so far, with the children it waited for
+    fn cpu_time(&self) -> Option<Duration>;
+
+    /// Threads the running child has now, for sampling them
+    fn thread_count(&self) -> Option<u32>;
+
+    /// How much the program wrote to stderr, of which the stderr file of
+    /// the run has what the policy's stderr_limit keeps. Known once the
+    /// child has been reaped, unless something it started escaped with
+    /// its stderr still open.
+    fn stderr_written(&self) -> Option<u64>;
+}
+
+/// The seccomp sandbox of this crate, starting programs with sandbox_run.
+/// Keeps the filters it compiled for later runs of the same policy.
+#[derive(Default)]
+pub struct LinuxSandbox {
+    filters: FilterCache
+}
+
+impl Sandbox for LinuxSandbox {
+    fn spawn(&self, spec: RunSpec<'_>) -> Result<Box<dyn SandboxChildHandle>, Box<dyn Error>> {
+        let mut child = sandbox_run(spec.exec, spec.args, spec.interpreter, spec.io, spec.policy, &self.filters, spec.cgroup)?;
+        child.wakeup = spec.wakeup.map(|fd| fd.try_clone_to_owned()).transpose()?;
+        Ok(Box::new(child))
+    }
+}
+
+/// Outcome of waiting on a sandboxed child
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WaitOutcome {
+    /// The child exited and has been reaped, carrying its raw wait status
+    Exited(i32),
+    /// The child was stopped by a signal
+    Stopped,
+    /// The timeout elapsed first
+    Timeout
+}
+
+/// Resource usage of a reaped process, as reported by wait4
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ResourceUsage {
+    /// CPU time spent in the program
+    pub user_time: Duration,
+    /// CPU time spent in the kernel on its behalf
+    pub system_time: Duration,
+    /// Peak resident memory
+    pub max_rss_bytes: u64,
+    /// Page faults served without I/O
+    pub minor_faults: u64,
+    /// Page faults that had to read from disk
+    pub major_faults: u64,
+    /// Times it gave up the CPU, as when waiting for I/O
+    pub voluntary_switches: u64,
+    /// Times it was preempted
+    pub involuntary_switches: u64,
+    /// Blocks it had the filesystems read from disk, of BLOCK_SIZE bytes
+    pub blocks_read: u64,
+    /// Blocks it had the filesystems write to disk, counted as the pages
+    /// are dirtied
+    pub blocks_written: u64
+}
+
+impl ResourceUsage {
+    /// Size of the blocks rusage counts the I/O in, whatever the block
+    /// size of the filesystem
+    pub const BLOCK_SIZE: u64 = 512;
+
+    fn from_rusage(usage: &libc::rusage) -> Self {
+        let time = |tv: &libc::timeval| {
+            Duration::from_secs(tv.tv_sec.max(0) as u64) + Duration::from_micros(tv.tv_usec.max(0) as u64)
+        };
+        let count = |n: libc::c_long| n.max(0) as u64;
+        ResourceUsage {
+            user_time: time(&usage.ru_utime),
+            system_time: time(&usage.ru_stime),
+            // Linux reports maxrss in kilobytes
+            max_rss_bytes: count(usage.ru_maxrss) * 1024,
+            minor_faults: count(usage.ru_minflt),
+            major_faults: count(usage.ru_majflt),
+            voluntary_switches: count(usage.ru_nvcsw),
+            involuntary_switches: count(usage.ru_nivcsw),
+            blocks_read: count(usage.ru_inblock),
+            blocks_written: count(usage.ru_oublock)
+        }
+    }
+
+    /// User and system CPU time together
+    pub fn cpu_time(&self) -> Duration {
+        self.user_time + self.system_time
+    }
+
+    /// Roughly how many bytes were read from disk, reads served from the
+    /// page cache not counting
+    pub fn read_bytes(&self) -> u64 {
+        self.blocks_read.saturating_mul(Self::BLOCK_SIZE)
+    }
+
+    /// Roughly how many bytes were written to disk, files on tmpfs not
+    /// counting
+    pub fn written_bytes(&self) -> u64 {
+        self.blocks_written.saturating_mul(Self::BLOCK_SIZE)
+    }
+
+    /*
+     *  Account the usage of another process, such as a reaped descendant
+     */
+    fn add(&mut self, other: &ResourceUsage) {
+        self.user_time += other.user_time;
+        self.system_time += other.system_time;
+        self.max_rss_bytes = self.max_rss_bytes.max(other.max_rss_bytes);
+        self.minor_faults += other.minor_faults;
+        self.major_faults += other.major_faults;
+        self.voluntary_switches += other.voluntary_switches;
+        self.involuntary_switches += other.involuntary_switches;
+        self.blocks_read += other.blocks_read;
+        self.blocks_written += other.blocks_written;
+    }
+}
+
+/*
+ *  wait4 returning the pid and wait status together with the resource
+ *  usage, which is only converted once the call has succeeded
+ */
+fn wait4(pid: i32, options: i32) -> io::Result<(i32, i32, ResourceUsage)> {
+    let mut status: i32 = 0;
+    let mut usage = MaybeUninit::<libc::rusage>::zeroed();
+    let ret = unsafe { libc::wait4(pid, &mut status, options, usage.as_mut_ptr()) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // wait4 fills in the usage on success, and it is zeroed otherwise, i.e. WNOHANG with nothing to reap
+    let usage = unsafe { usage.assume_init() };
+    Ok((ret, status, ResourceUsage::from_rusage(&usage)))
+}
+
+/// Handle owning a sandboxed child process.
+/// If dropped before the child has been reaped, the child is killed and
+/// reaped, so an early return or a panic in the judger never leaks it.
+pub(crate) struct SandboxChild {
+    pid: i32,
+    pidfd: Option<OwnedFd>,
+    start: Instant,
+    // Wait status and resource usage, once reaped
+    exit: Option<(i32, ResourceUsage)>,
+    supervisor: Option<WriteSupervisor>,
+    // Polled with the pidfd until it first turns readable, which stays so
+    wakeup: Option<OwnedFd>,
+    // Sends how much went to stderr once all of it is captured
+    stderr_drain: Option<mpsc::Receiver<u64>>,
+    stderr_written: Option<u64>,
+    seccomp: bool
+}
+
+impl SandboxChild {
+    fn new(pid: i32, start: Instant, seccomp: bool) -> Self {
+        SandboxChild {
+            pid,
+            pidfd: pidfd_open(pid),
+            start,
+            exit: None,
+            supervisor: None,
+            wakeup: None,
+            stderr_drain: None,
+            stderr_written: None,
+            seccomp
+        }
+    }
+
+    fn reap(&mut self) -> io::Result<i32> {
+        loop {
+            match wait4(self.pid, 0) {
+                Ok((_, status, mut usage)) => {
+                    self.sweep_descendants(&mut usage);
+                    self.exit = Some((status, usage));
+                    self.await_stderr();
+                    return Ok(status);
+                },
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e)
+            }
+        }
+    }
+
+    /*
+     *  Wait for the rest of stderr to be captured, so that the stderr file
+     *  is complete once the child counts as reaped
+     */
+    fn await_stderr(&mut self) {
+        // Only a descendant that left the process group still writes
+        const DRAIN_GRACE: Duration = Duration::from_millis(500);
+
+        if let Some(drain) = self.stderr_drain.take() {
+            self.stderr_written = drain.recv_timeout(DRAIN_GRACE).ok();
+        }
+    }
+
+    /*
+     *  Kill what is left of the child's process group and reap the members
+     *  that were reparented to us, adding their resource usage to `usage`.
+     *  Gives up on descendants that take too long to die.
+     */
+    fn sweep_descendants(&self, usage: &mut ResourceUsage) {
+        const SWEEP_GRACE: Duration = Duration::from_secs(1);
+
+        unsafe {
+            libc::kill(-self.pid, libc::SIGKILL);
+        }
+        let begin = Instant::now();
+        while begin.elapsed() < SWEEP_GRACE {
+            match wait4(-self.pid, libc::WNOHANG) {
+                Ok((0, _, _)) => thread::sleep(Duration::from_millis(1)),
+                Ok((_, _, orphan)) => usage.add(&orphan),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                // None of the group is our child any more
+                Err(_) => return
+            }
+        }
+    }
+}
+
+impl SandboxChildHandle for SandboxChild {
+    fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    fn seccomp(&self) -> bool {
+        self.seccomp
+    }
+
+    fn start_instant(&self) -> Instant {
+        self.start
+    }
+
+    fn wait(&mut self, timeout: Option<Duration>) -> io::Result<WaitOutcome> {
+        if let Some((status, _)) = self.exit {
+            return Ok(WaitOutcome::Exited(status));
+        }
+        let begin = Instant::now();
+        loop {
+            let remaining = timeout.map(|t| t.saturating_sub(begin.elapsed()));
+            let listener = self.supervisor.as_ref().map(|s| &s.listener);
+            match wait_exit(self.pid, self.pidfd.as_ref(), listener, self.wakeup.as_ref(), remaining)? {
+                WaitEvent::Exited => return self.reap().map(WaitOutcome::Exited),
+                WaitEvent::Stopped => return Ok(WaitOutcome::Stopped),
+                WaitEvent::Timeout => return Ok(WaitOutcome::Timeout),
+                // Ends this wait early only, later waits run their course
+                WaitEvent::Woken => {
+                    self.wakeup = None;
+                    return Ok(WaitOutcome::Timeout);
+                },
+                WaitEvent::Notified => if let Some(supervisor) = &mut self.supervisor {
+                    supervisor.serve();
+                }
+            }
+        }
+    }
+
+    // None without pidfd support, or when write supervision needs wait to run
+    fn exit_fd(&self) -> Option<BorrowedFd<'_>> {
+        match self.supervisor {
+            Some(_) => None,
+            None => self.pidfd.as_ref().map(|fd| fd.as_fd())
+        }
+    }
+
+    // Once reaped the pid may already belong to someone else
+    fn kill(&self, signal: i32) -> io::Result<()> {
+        if self.exit.is_some() {
+            return Ok(());
+        }
+        if unsafe { libc::kill(-self.pid, signal) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn rusage(&self) -> Option<&ResourceUsage> {
+        self.exit.as_ref().map(|(_, usage)| usage)
+    }
+
+    fn named_output_opened(&self) -> Option<bool> {
+        self.supervisor.as_ref().filter(|s| !s.output_paths.is_empty()).map(|s| s.output_opened)
+    }
+
+    fn resident_memory(&self) -> Option<u64> {
+        resident_memory(self.pid)
+    }
+
+    fn cpu_time(&self) -> Option<Duration> {
+        cpu_time(self.pid)
+    }
+
+    fn thread_count(&self) -> Option<u32> {
+        status_number(self.pid, "Threads")?.try_into().ok()
+    }
+
+    fn stderr_written(&self) -> Option<u64> {
+        self.stderr_written
+    }
+}
+
+impl Drop for SandboxChild {
+    fn drop(&mut self) {
+        const REAP_GRACE: Duration = Duration::from_millis(100);
+
+        if self.exit.is_some() {
+            return;
+        }
+        unsafe {
+            libc::kill(-self.pid, libc::SIGKILL);
+        }
+        // Don't block forever on a child that refuses to die
+        if let Ok(WaitEvent::Exited) = wait_exit(self.pid, self.pidfd.as_ref(), None, None, Some(REAP_GRACE)) {
+            unsafe {
+                libc::waitpid(self.pid, ptr::null_mut(), libc::WNOHANG);
+            }
+            self.sweep_descendants(&mut ResourceUsage::default());
+        }
+    }
+}
+
+/*
+ *  Obtain a pidfd referring to the child, None if the kernel doesn't support it
+ */
+fn pidfd_open(pid: i32) -> Option<OwnedFd> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+    if fd < 0 {
+        return None;
+    }
+    Some(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+/*
+ *  Resident memory of a running process in bytes, read from /proc
+ */
+fn resident_memory(pid: i32) -> Option<u64> {
+    Some(status_number(pid, "VmRSS")? * 1024)
+}
+
+/*
+ *  A number in /proc/PID/status of a running process, such as Threads,
+ *  or VmRSS in kB without its unit
+ */
+fn status_number(pid: i32, field: &str) -> Option<u64> {
+    let status = fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    status.lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+/*
+ *  CPU time used so far by all threads of a running process and the
+ *  children it has waited for, read from /proc
+ */
+fn cpu_time(pid: i32) -> Option<Duration> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The command name may contain spaces, the fields start after it
+    let fields: Vec<&str> = stat.get(stat.rfind(')')? + 2..)?.split_whitespace().collect();
+    // utime, stime, cutime and cstime
+    let mut ticks: u64 = 0;
+    for field in fields.get(11..15)? {
+        ticks += field.parse::<u64>().ok()?;
+    }
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_sec <= 0 {
+        return None;
+    }
+    Some(Duration::from_secs_f64(ticks as f64 / ticks_per_sec as f64))
+}
+
+fn to_timespec(duration: Duration) -> libc::timespec {
+    libc::timespec {
+        tv_sec: duration.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+        tv_nsec: duration.subsec_nanos() as libc::c_long
+    }
+}
+
+/// What woke up a wait on the sandboxed child
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum WaitEvent {
+    /// The child exited and is ready to be collected with wait4
+    Exited,
+    /// The child was stopped by a signal
+    Stopped,
+    /// The timeout elapsed first
+    Timeout,
+    /// The exec gate listener has a notification waiting
+    Notified,
+    /// The wakeup descriptor turned readable
+    Woken
+}
+
+/*
+ *  Block until the child exits or stops, the timeout elapses, or the
+ *  optional exec gate listener or wakeup descriptor becomes readable,
+ *  without reaping the child. A timeout of None waits forever.
+ *  Stops are not reported through pidfd or reliably through SIGCHLD, so the
+ *  child state is re-checked at least every STATE_CHECK_INTERVAL. Without a
+ *  pidfd an exit may go unnoticed until the next check, so checks start
+ *  out frequent and back off, keeping short runs from being overbilled.
+ */
+fn wait_exit(
+    pid: i32,
+    pidfd: Option<&OwnedFd>,
+    listener: Option<&OwnedFd>,
+    wakeup: Option<&OwnedFd>,
+    timeout: Option<Duration>
+) -> io::Result<WaitEvent> {
+    const STATE_CHECK_INTERVAL: Duration = Duration::from_millis(20);
+    const FALLBACK_FIRST_INTERVAL: Duration = Duration::from_micros(50);
+    const FALLBACK_MAX_INTERVAL: Duration = Duration::from_millis(1);
+
+    let begin = Instant::now();
+    let mut old_set: Option<libc::sigset_t> = None;
+    let polled = pidfd.is_some() || listener.is_some() || wakeup.is_some();
+    if !polled {
+        // Keep SIGCHLD pending for sigtimedwait instead of it being discarded
+        unsafe {
+            let mut old: libc::sigset_t = std::mem::zeroed();
+            libc::pthread_sigmask(libc::SIG_BLOCK, &sigchld_set(), &mut old);
+            old_set = Some(old);
+        }
+    }
+
+    let mut interval = match pidfd {
+        Some(_) => STATE_CHECK_INTERVAL,
+        None => FALLBACK_FIRST_INTERVAL
+    };
+    let result = loop {
+        match check_child(pid) {
+            Ok(Some(event)) => break Ok(event),
+            Ok(None) => {},
+            Err(e) => break Err(e)
+        }
+        let slice = match timeout {
+            Some(t) => {
+                let remaining = t.saturating_sub(begin.elapsed());
+                if remaining.is_zero() {
+                    break Ok(WaitEvent::Timeout);
+                }
+                remaining.min(interval)
+            },
+            None => interval
+        };
+        if pidfd.is_none() {
+            interval = (interval * 2).min(FALLBACK_MAX_INTERVAL);
+        }
+        let slept = match polled {
+            false => wait_sigchld(slice).map(|_| None),
+            true => poll_wakeup(pidfd, listener, wakeup, slice)
+        };
+        match slept {
+            Ok(Some(event)) => break Ok(event),
+            Ok(None) => {},
+            Err(e) => break Err(e)
+        }
+    };
+
+    if let Some(old) = old_set {
         unsafe {
-            let fd = libc::open(inf.as_ptr(), libc::O_RDONLY);
-            libc::close(0);
-            libc::dup2(fd, 0);
-            libc::close(fd);
-            let fd = libc::open(outf.as_ptr(), libc::O_WRONLY);
-            libc::close(1);
-            libc::dup2(fd, 1);
-            libc::close(fd);
-        }
-        install_seccomp(&full_name_c).unwrap();
-        execv(&full_name_c, &conv_args);
-    }
-    Ok((pid, inst))
+            libc::pthread_sigmask(libc::SIG_SETMASK, &old, ptr::null_mut());
+        }
+    }
+    result
+}
+
+/*
+ *  Peek at the child state without consuming it
+ */
+fn check_child(pid: i32) -> io::Result<Option<WaitEvent>> {
+    let info = unsafe {
+        let mut info: libc::siginfo_t = std::mem::zeroed();
+        let ret = libc::waitid(
+            libc::P_PID,
+            pid as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WSTOPPED | libc::WNOHANG | libc::WNOWAIT
+        );
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        info
+    };
+    if unsafe { info.si_pid() } != pid {
+        return Ok(None);
+    }
+    Ok(match info.si_code {
+        libc::CLD_STOPPED | libc::CLD_TRAPPED => Some(WaitEvent::Stopped),
+        _ => Some(WaitEvent::Exited)
+    })
+}
+
+/*
+ *  Sleep until the pidfd, the listener or the wakeup descriptor is
+ *  readable, telling whether one of the last two is. Without a pidfd,
+ *  exits are only noticed at the timeout.
+ */
+fn poll_wakeup(
+    pidfd: Option<&OwnedFd>,
+    listener: Option<&OwnedFd>,
+    wakeup: Option<&OwnedFd>,
+    timeout: Duration
+) -> io::Result<Option<WaitEvent>> {
+    // A negative fd is ignored by poll
+    let pollfd = |fd: Option<&OwnedFd>| libc::pollfd {
+        fd: fd.map_or(-1, |f| f.as_raw_fd()),
+        events: libc::POLLIN,
+        revents: 0
+    };
+    let mut pfds = [pollfd(pidfd), pollfd(listener), pollfd(wakeup)];
+    let ts = to_timespec(timeout);
+    let ret = unsafe { libc::ppoll(pfds.as_mut_ptr(), pfds.len() as libc::nfds_t, &ts, ptr::null()) };
+    if ret < 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+        return Ok(None);
+    }
+    // The pidfd only wakes the poll, the child's state is checked after it
+    match (pfds[1].revents & libc::POLLIN != 0, pfds[2].revents & libc::POLLIN != 0) {
+        (true, _) => Ok(Some(WaitEvent::Notified)),
+        (false, true) => Ok(Some(WaitEvent::Woken)),
+        (false, false) => Ok(None)
+    }
+}
+
+fn sigchld_set() -> libc::sigset_t {
+    unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGCHLD);
+        set
+    }
+}
+
+/*
+ *  Fallback for kernels without pidfd: wait for SIGCHLD with sigtimedwait.
+ *  The caller keeps SIGCHLD blocked so it stays pending for us; if another
+ *  thread of the process receives it instead we just wake up at the timeout.
+ */
+fn wait_sigchld(timeout: Duration) -> io::Result<()> {
+    let ts = to_timespec(timeout);
+    unsafe {
+        libc::sigtimedwait(&sigchld_set(), ptr::null_mut(), &ts);
+    }
+    Ok(())
 }
@@ -0,0 +1,202 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::compare::Comparison;
+use crate::judger::{JudgeResult, JudgeSession, JudgeSessionBuilder, JudgeStatus};
+use crate::scratch::ScratchDir;
+use crate::secrun::InputSource;
+
+/// One of the programs of a stress test, with its session and how it is
+/// named to it as argv[0]
+struct Program {
+    session: JudgeSession,
+    name: String
+}
+
+impl Program {
+    fn build(what: &str, (builder, name): (JudgeSessionBuilder, String), input: Option<&Path>) -> io::Result<Self> {
+        let builder = match input {
+            Some(path) => builder.input(InputSource::File(path.to_path_buf())),
+            None => builder
+        };
+        match builder.build() {
+            Ok(session) => Ok(Program { session, name }),
+            Err(e) => Err(io::Error::new(e.kind(), format!("{what}: {e}")))
+        }
+    }
+
+    /*
+     *  Run the program with `args` after its name, its output going to `output`
+     */
+    fn run(&self, args: &[&str], output: &Path) -> Result<JudgeResult, Box<dyn Error>> {
+        let argv: Vec<&str> = [self.name.as_str()].into_iter().chain(args.iter().copied()).collect();
+        self.session.run_program(&argv, &mut File::create(output)?)
+    }
+}
+
+/// How a stress test ended
+pub enum StressOutcome {
+    /// The candidate agreed with the brute force on every iteration
+    Agreed {
+        /// Iterations run
+        iterations: u64
+    },
+    /// The candidate's output differed from the brute force's, or the
+    /// candidate failed where the brute force didn't, by `status`
+    Failed {
+        /// Index of the failing iteration, from 0
+        iteration: u64,
+        /// Seed the generator got on it
+        seed: u64,
+        /// Verdict of the candidate against the brute force
+        status: JudgeStatus
+    },
+    /// The generator or the brute force failed, so the iteration can't
+    /// tell anything about the candidate
+    Aborted {
+        /// Which of the two failed
+        program: &'static str,
+        /// Index of the iteration, from 0
+        iteration: u64,
+        /// Seed the generator got on it
+        seed: u64,
+        /// How the program failed
+        result: Box<JudgeResult>
+    },
+    /// Cancelled before finishing, after `iterations` that agreed
+    Cancelled {
+        /// Iterations that agreed
+        iterations: u64
+    }
+}
+
+/// Looks for an input a candidate solution gets wrong. Each iteration
+/// runs the generator with a seed as its argument, and the brute force
+/// and the candidate on what it wrote, comparing their outputs. The first
+/// iteration where they differ ends the test, its files are saved to the
+/// save directory.
+pub struct StressTest {
+    generator: Program,
+    brute: Program,
+    candidate: Program,
+    comparison: Comparison,
+    // Holds the input and the outputs of the iteration in progress
+    scratch: ScratchDir,
+    save_dir: PathBuf,
+    // Report progress every this many iterations
+    progress_every: Option<u64>
+}
+
+impl StressTest {
+    /**
+     *  A stress test of the programs of the sessions to be built, whose
+     *  limits and sandbox settings apply. Each executable is given with
+     *  the name it gets as `argv[0]`. The brute force and the candidate get
+     *  the generator's output as their input.
+     */
+    pub fn new(
+        generator: (JudgeSessionBuilder, String),
+        brute: (JudgeSessionBuilder, String),
+        candidate: (JudgeSessionBuilder, String),
+        scratch_base: &Path
+    ) -> Result<Self, Box<dyn Error>> {
+        let scratch = ScratchDir::create(scratch_base)?;
+        scratch.create_file("input", 0o600)?;
+        let input = scratch.file("input");
+        Ok(StressTest {
+            generator: Program::build("generator", generator, None)?,
+            brute: Program::build("brute force", brute, Some(&input))?,
+            candidate: Program::build("candidate", candidate, Some(&input))?,
+            comparison: Comparison::default(),
+            scratch,
+            save_dir: PathBuf::from("stress-failure"),
+            progress_every: None
+        })
+    }
+
+    /// How the candidate's output is checked against the brute force's
+    pub fn with_comparison(mut self, comparison: Comparison) -> Self {
+        self.comparison = comparison;
+        self
+    }
+
+    /// Where the files of the failing iteration go, created if missing
+    pub fn with_save_dir(mut self, dir: PathBuf) -> Self {
+        self.save_dir = dir;
+        self
+    }
+
+    /// Call the progress callback of run every `every` iterations
+    pub fn with_progress(mut self, every: u64) -> Self {
+        self.progress_every = Some(every);
+        self
+    }
+
+    /// Where the files of the failing iteration go
+    pub fn save_dir(&self) -> &Path {
+        &self.save_dir
+    }
+
+    /**
+     *  Run up to `iterations` iterations, the first with `seed` as the
+     *  generator's argument and each one after with the next seed.
+     *  `progress` gets the iterations done so far.
+     */
+    pub fn run(&self, seed: u64, iterations: u64, mut progress: impl FnMut(u64)) -> Result<StressOutcome, Box<dyn Error>> {
+        let input = self.scratch.file("input");
+        let brute_output = self.scratch.file("brute.out");
+        let candidate_output = self.scratch.file("candidate.out");
+        for iteration in 0..iterations {
+            let seed = seed.wrapping_add(iteration);
+            let seed_arg = seed.to_string();
+            let generated = self.generator.run(&[&seed_arg], &input)?;
+            if !generated.accepted() {
+                return Ok(self.stopped("generator", iteration, seed, generated));
+            }
+            let brute = self.brute.run(&[], &brute_output)?;
+            if !brute.accepted() {
+                return Ok(self.stopped("brute force", iteration, seed, brute));
+            }
+            let candidate = self.candidate.run(&[], &candidate_output)?;
+            let status = match candidate.status {
+                JudgeStatus::Accepted => self.comparison.compare(File::open(&brute_output)?, File::open(&candidate_output)?)?,
+                JudgeStatus::Cancelled => return Ok(StressOutcome::Cancelled { iterations: iteration }),
+                status => status
+            };
+            if !matches!(status, JudgeStatus::Accepted) {
+                self.save(seed, &status)?;
+                return Ok(StressOutcome::Failed { iteration, seed, status });
+            }
+            let done = iteration + 1;
+            if self.progress_every.is_some_and(|every| done % every == 0 && done < iterations) {
+                progress(done);
+            }
+        }
+        Ok(StressOutcome::Agreed { iterations })
+    }
+
+    fn stopped(&self, program: &'static str, iteration: u64, seed: u64, result: JudgeResult) -> StressOutcome {
+        match result.status {
+            JudgeStatus::Cancelled => StressOutcome::Cancelled { iterations: iteration },
+            _ => StressOutcome::Aborted { program, iteration, seed, result: Box::new(result) }
+        }
+    }
+
+    /*
+     *  Save the files of the failing iteration: its input, both outputs,
+     *  and the seed with the candidate's verdict
+     */
+    fn save(&self, seed: u64, status: &JudgeStatus) -> io::Result<()> {
+        let dir = &self.save_dir;
+        fs::create_dir_all(dir)?;
+        for name in ["input", "brute.out", "candidate.out"] {
+            fs::copy(self.scratch.file(name), dir.join(name)).map_err(|e| {
+                io::Error::new(e.kind(), format!("cannot save {name} to {}: {e}", dir.display()))
+            })?;
+        }
+        fs::write(dir.join("seed"), format!("{seed}\n"))?;
+        fs::write(dir.join("verdict"), format!("{status}\n"))
+    }
+}
@@ -0,0 +1,357 @@
+use std::fmt::Display;
+
+/// A value of a TOML file, of the kinds problem files need
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>)
+}
+
+impl Value {
+    /// What kind of value it is, for error messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::String(_) => "a string",
+            Self::Integer(_) => "an integer",
+            Self::Float(_) => "a float",
+            Self::Boolean(_) => "a boolean",
+            Self::Array(_) => "an array"
+        }
+    }
+}
+
+/// A key of a table with its value and where it is
+pub(crate) struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize
+}
+
+/// A [table], or one [[table]] of an array of them. The keys before the
+/// first header make up the root table, whose name is empty.
+pub(crate) struct Table {
+    pub name: String,
+    pub array: bool,
+    // Line of the header
+    pub line: usize,
+    pub entries: Vec<Entry>
+}
+
+#[derive(Debug)]
+pub(crate) struct ParseError {
+    pub line: usize,
+    pub message: String
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("line {}: {}", self.line, self.message))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/*
+ *  Parse the tables of a TOML file, in the order they are given. This is
+ *  the part of TOML a problem file needs: tables, arrays of tables, and
+ *  keys that are strings, integers, floats, booleans or arrays of them.
+ *  Inline tables, dotted keys, multi-line strings and dates are refused.
+ */
+pub(crate) fn parse(text: &str) -> Result<Vec<Table>, ParseError> {
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0, line: 1 };
+    let mut tables = vec![Table { name: String::new(), array: false, line: 1, entries: Vec::new() }];
+    loop {
+        parser.skip_blank_lines();
+        let Some(ch) = parser.peek() else {
+            return Ok(tables);
+        };
+        let line = parser.line;
+        if ch == '[' {
+            let table = parser.header()?;
+            let earlier = tables.iter().find(|earlier| earlier.name == table.name);
+            match earlier {
+                Some(earlier) if !earlier.array || !table.array => {
+                    let message = match earlier.array == table.array {
+                        true => format!("table [{}] is given twice, first on line {}", table.name, earlier.line),
+                        false => format!("[{0}] and [[{0}]] can't both be given", table.name)
+                    };
+                    return Err(ParseError { line, message });
+                },
+                _ => tables.push(table)
+            }
+            continue;
+        }
+        let key = parser.key()?;
+        parser.skip_spaces();
+        if parser.peek() == Some('.') {
+            return Err(parser.error(format!("dotted keys are not supported, put {key} in a [table]")));
+        }
+        parser.expect('=', || format!("expected = after key {key}"))?;
+        parser.skip_spaces();
+        let value = parser.value()?;
+        parser.end_of_line()?;
+        let table = tables.last_mut().expect("the root table is always there");
+        if let Some(earlier) = table.entries.iter().find(|entry| entry.key == key) {
+            return Err(ParseError { line, message: format!("key {key} is given twice, first on line {}", earlier.line) });
+        }
+        table.entries.push(Entry { key, value, line });
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += 1;
+        if ch == '\n' {
+            self.line += 1;
+        }
+        Some(ch)
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError { line: self.line, message }
+    }
+
+    fn expect(&mut self, wanted: char, message: impl FnOnce() -> String) -> Result<(), ParseError> {
+        match self.peek() == Some(wanted) {
+            true => {
+                self.bump();
+                Ok(())
+            },
+            false => Err(self.error(message()))
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while self.peek().is_some_and(|ch| ch != '\n') {
+                self.bump();
+            }
+        }
+    }
+
+    /*
+     *  Skip whitespace, comments and line breaks, as between tables and
+     *  keys or inside arrays
+     */
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') => {
+                    self.bump();
+                },
+                Some('\r') if self.chars.get(self.pos + 1) == Some(&'\n') => {
+                    self.bump();
+                },
+                _ => return
+            }
+        }
+    }
+
+    /*
+     *  Nothing but a comment may follow a key's value or a header
+     */
+    fn end_of_line(&mut self) -> Result<(), ParseError> {
+        self.skip_spaces();
+        self.skip_comment();
+        if self.peek() == Some('\r') {
+            self.bump();
+        }
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            },
+            Some(ch) => Err(self.error(format!("unexpected {ch:?}, expected the end of the line")))
+        }
+    }
+
+    fn header(&mut self) -> Result<Table, ParseError> {
+        let line = self.line;
+        self.bump();
+        let array = self.peek() == Some('[');
+        if array {
+            self.bump();
+        }
+        self.skip_spaces();
+        let mut name = self.key()?;
+        // Nested tables go by their full name, a.b
+        loop {
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                break;
+            }
+            self.bump();
+            self.skip_spaces();
+            name = format!("{name}.{}", self.key()?);
+        }
+        let closing = match array {
+            true => "]]",
+            false => "]"
+        };
+        for ch in closing.chars() {
+            self.expect(ch, || format!("expected {closing} to close the table header"))?;
+        }
+        self.end_of_line()?;
+        Ok(Table { name, array, line, entries: Vec::new() })
+    }
+
+    fn key(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-') {
+                    self.bump();
+                }
+                match self.pos > start {
+                    true => Ok(self.chars[start..self.pos].iter().collect()),
+                    false => Err(self.error(String::from("expected a key")))
+                }
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => Err(self.error(String::from("inline tables are not supported, use a [table]"))),
+            _ => self.scalar()
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, ParseError> {
+        if self.chars[self.pos..].starts_with(&['"', '"', '"']) {
+            return Err(self.error(String::from("multi-line strings are not supported")));
+        }
+        // Strings end on the line they start on
+        let line = self.line;
+        self.bump();
+        let mut string = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(ParseError { line, message: String::from("unterminated string") }),
+                Some('"') => return Ok(string),
+                Some('\\') => string.push(self.escape()?),
+                Some(ch) => string.push(ch)
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let ch = match self.bump() {
+            Some('b') => '\u{8}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{c}',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some(kind @ ('u' | 'U')) => {
+                let digits = if kind == 'u' { 4 } else { 8 };
+                let hex: String = (0..digits).filter_map(|_| self.bump()).collect();
+                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    Some(ch) if hex.len() == digits => ch,
+                    _ => return Err(self.error(format!("invalid escape \\{kind}{hex}")))
+                }
+            },
+            Some(other) => return Err(self.error(format!("invalid escape \\{other}"))),
+            None => return Err(self.error(String::from("unterminated string")))
+        };
+        Ok(ch)
+    }
+
+    fn literal_string(&mut self) -> Result<String, ParseError> {
+        if self.chars[self.pos..].starts_with(&['\'', '\'', '\'']) {
+            return Err(self.error(String::from("multi-line strings are not supported")));
+        }
+        let line = self.line;
+        self.bump();
+        let mut string = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(ParseError { line, message: String::from("unterminated string") }),
+                Some('\'') => return Ok(string),
+                Some(ch) => string.push(ch)
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        self.bump();
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank_lines();
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_blank_lines();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                },
+                Some(']') => {},
+                _ => return Err(self.error(String::from("expected , or ] in the array")))
+            }
+        }
+    }
+
+    /*
+     *  A boolean or a number, up to whatever ends a value
+     */
+    fn scalar(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|ch| !ch.is_whitespace() && !matches!(ch, ',' | ']' | '#')) {
+            self.bump();
+        }
+        let token: String = self.chars[start..self.pos].iter().collect();
+        let digits = token.replace('_', "");
+        let value = match token.as_str() {
+            "" => return Err(self.error(String::from("expected a value"))),
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            "inf" | "+inf" => Value::Float(f64::INFINITY),
+            "-inf" => Value::Float(f64::NEG_INFINITY),
+            "nan" | "+nan" | "-nan" => Value::Float(f64::NAN),
+            _ if !token.starts_with(|ch: char| ch.is_ascii_digit() || ch == '+' || ch == '-') => {
+                return Err(self.error(format!("invalid value {token}, strings have to be quoted")));
+            },
+            _ if !digits.contains(['.', 'e', 'E']) => match digits.parse::<i64>() {
+                Ok(n) => Value::Integer(n),
+                Err(_) => return Err(self.error(format!("invalid value {token}, strings have to be quoted")))
+            },
+            _ => match digits.parse::<f64>() {
+                Ok(x) => Value::Float(x),
+                Err(_) => return Err(self.error(format!("invalid value {token}, strings have to be quoted")))
+            }
+        };
+        Ok(value)
+    }
+}
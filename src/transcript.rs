@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::cache;
+use crate::judger::{JudgeEnvironment, JudgeObserver, JudgeResult, RunEvent};
+use crate::output::JsonLimitsV1;
+use crate::problem::TestCase;
+use crate::utils;
+
+/// Most of an answer or an output a transcript includes, the rest being
+/// cut off
+pub const MAX_EXCERPT: u64 = 64 << 10;
+
+/// A record of every step of a session's runs, for handling appeals: the
+/// setup of each run, the signals the judger sent and why, what the
+/// program used, and how its output compared. Written to a file as JSON
+/// lines, each line written as its step happens, so that a judger that
+/// crashed leaves the steps up to the crash. As the session's observer
+/// it is told about every run.
+///
+/// Every line has "event" naming the step, "at_ms" the time it happened
+/// in milliseconds since the epoch, and "test" the test it was on once a
+/// run started. The contents of answers can't be shared with contestants
+/// and are left out unless asked for, and so are those of outputs, which
+/// give the answer away whenever they are accepted.
+pub struct Transcript {
+    file: Mutex<File>,
+    include_answers: bool,
+    // The test each thread runs, as the steps don't say which it is on
+    tests: Mutex<HashMap<ThreadId, String>>
+}
+
+impl Transcript {
+    /**
+     *  Start a transcript at `path`, replacing what was there, with the
+     *  contents of answers and outputs if `include_answers`
+     */
+    pub fn create(path: &Path, include_answers: bool) -> io::Result<Self> {
+        let file = File::create(path)?;
+        let transcript = Transcript { file: Mutex::new(file), include_answers, tests: Mutex::new(HashMap::new()) };
+        transcript.write("begin", vec![
+            ("environment", JudgeEnvironment::current().to_json()),
+            ("answers_included", include_answers.to_string())
+        ])?;
+        Ok(transcript)
+    }
+
+    /*
+     *  Write a line for `event` with `fields`, in a single write so that
+     *  runs on other threads don't split it
+     */
+    fn write(&self, event: &str, fields: Vec<(&str, String)>) -> io::Result<()> {
+        let at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let test = self.tests.lock().unwrap_or_else(|e| e.into_inner()).get(&thread::current().id()).cloned();
+        let mut members = vec![
+            ("event", utils::json_string(event)),
+            ("at_ms", at.as_millis().to_string()),
+            ("test", optional(test.as_deref().map(utils::json_string)))
+        ];
+        members.extend(fields);
+        let mut line = utils::json_object(&members);
+        line.push('\n');
+        self.file.lock().unwrap_or_else(|e| e.into_inner()).write_all(line.as_bytes())
+    }
+
+    /*
+     *  Write a line, warning of a write that failed rather than failing
+     *  the run over it
+     */
+    fn record(&self, event: &str, fields: Vec<(&str, String)>) {
+        if let Err(e) = self.write(event, fields) {
+            warn!("cannot write the transcript: {e}");
+        }
+    }
+
+    /*
+     *  The contents of `path` as `name`, with `truncated` saying
+     *  whether they were cut off, if the transcript includes them
+     */
+    fn excerpt(&self, fields: &mut Vec<(&str, String)>, name: &'static str, truncated: &'static str, path: &Path) {
+        if !self.include_answers {
+            return;
+        }
+        match read_excerpt(path) {
+            Ok((text, cut)) => {
+                fields.push((name, utils::json_string(&text)));
+                fields.push((truncated, cut.to_string()));
+            },
+            Err(e) => fields.push((name, utils::json_object(&[("error", utils::json_string(&e.to_string()))])))
+        }
+    }
+}
+
+impl JudgeObserver for Transcript {
+    fn on_run_start(&self, test: &TestCase) {
+        self.tests.lock().unwrap_or_else(|e| e.into_inner()).insert(thread::current().id(), test.name());
+        let mut fields = vec![
+            ("input", path_json(&test.input)),
+            ("input_sha256", digest_json(&test.input)),
+            ("answer", path_json(&test.answer)),
+            ("answer_sha256", digest_json(&test.answer))
+        ];
+        self.excerpt(&mut fields, "answer_contents", "answer_truncated", &test.answer);
+        self.record("run_start", fields);
+    }
+
+    fn on_event(&self, event: &RunEvent) {
+        match event {
+            RunEvent::Validated { input, problem } => self.record("validated", vec![
+                ("input", path_json(input)),
+                ("problem", optional(problem.map(utils::json_string)))
+            ]),
+            RunEvent::LaunchFailed { error, retry_in } => self.record("launch_failed", vec![
+                ("error", utils::json_string(error)),
+                ("retry_in_ms", optional(retry_in.map(|t| t.as_millis().to_string())))
+            ]),
+            RunEvent::Started { pid, exec, exec_sha256, limits, strength, seccomp } => self.record("started", vec![
+                ("pid", pid.to_string()),
+                ("exec", path_json(exec)),
+                ("exec_sha256", optional(exec_sha256.map(utils::json_string))),
+                ("limits", utils::json_object(&[
+                    ("base", JsonLimitsV1::of(&limits.base).to_json()),
+                    ("effective", JsonLimitsV1::of(&limits.effective).to_json())
+                ])),
+                ("sandbox", utils::json_object(&[
+                    ("strength", utils::json_string(&strength.to_string())),
+                    ("seccomp", seccomp.to_string())
+                ]))
+            ]),
+            RunEvent::Signalled { signal, reason, elapsed } => self.record("signalled", vec![
+                ("signal", signal.to_string()),
+                ("reason", utils::json_string(reason.name())),
+                ("run_ms", millis(*elapsed))
+            ]),
+            RunEvent::Exited { status, elapsed, usage } => {
+                let (code, signal) = match libc::WIFSIGNALED(*status) {
+                    true => (None, Some(libc::WTERMSIG(*status))),
+                    false => (Some(libc::WEXITSTATUS(*status)), None)
+                };
+                self.record("exited", vec![
+                    ("exit_code", optional(code.map(|c| c.to_string()))),
+                    ("signal", optional(signal.map(|s| s.to_string()))),
+                    ("run_ms", millis(*elapsed)),
+                    ("rusage", utils::json_object(&[
+                        ("user_ms", millis(usage.user_time)),
+                        ("system_ms", millis(usage.system_time)),
+                        ("max_rss_bytes", usage.max_rss_bytes.to_string()),
+                        ("minor_faults", usage.minor_faults.to_string()),
+                        ("major_faults", usage.major_faults.to_string()),
+                        ("voluntary_switches", usage.voluntary_switches.to_string()),
+                        ("involuntary_switches", usage.involuntary_switches.to_string()),
+                        ("read_bytes", usage.read_bytes().to_string()),
+                        ("written_bytes", usage.written_bytes().to_string())
+                    ]))
+                ]);
+            },
+            RunEvent::Compared { answer, output, comparison, status, difference } => {
+                let mut fields = vec![
+                    ("comparison", utils::json_string(&comparison.to_string())),
+                    ("answer", optional(answer.map(path_json))),
+                    ("output_sha256", digest_json(output)),
+                    ("status", utils::json_string(status.abbr())),
+                    ("difference", optional(difference.map(utils::json_string)))
+                ];
+                self.excerpt(&mut fields, "output_contents", "output_truncated", output);
+                self.record("compared", fields);
+            }
+        }
+    }
+
+    fn on_run_complete(&self, result: &JudgeResult) {
+        self.record("result", vec![("result", result.to_json())]);
+        self.tests.lock().unwrap_or_else(|e| e.into_inner()).remove(&thread::current().id());
+    }
+}
+
+fn optional(value: Option<String>) -> String {
+    value.unwrap_or_else(|| String::from("null"))
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+fn path_json(path: &Path) -> String {
+    utils::json_string(&path.to_string_lossy())
+}
+
+/*
+ *  SHA-256 of the file, null for one too large to hash or that can't be
+ *  read
+ */
+fn digest_json(path: &Path) -> String {
+    optional(cache::file_digest(path).ok().flatten().map(|digest| utils::json_string(&digest)))
+}
+
+/*
+ *  The first MAX_EXCERPT bytes of the file, and whether there was more
+ */
+fn read_excerpt(path: &Path) -> io::Result<(String, bool)> {
+    let mut bytes = Vec::new();
+    File::open(path)?.take(MAX_EXCERPT).read_to_end(&mut bytes)?;
+    let cut = fs::metadata(path)?.len() > MAX_EXCERPT;
+    Ok((String::from_utf8_lossy(&bytes).into_owned(), cut))
+}
@@ -1,22 +1,606 @@
-use std::path::PathBuf;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::fd::OwnedFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
-pub fn find_path(filename: &str) -> PathBuf {
+use crate::scratch;
+
+/// Reading the ELF headers of executables: what they run on and how they
+/// are linked
+pub mod elf;
+/// What the host is: its name, kernel and CPU
+pub mod host;
+
+/// Why find_path has no program for a name
+#[derive(Debug)]
+pub enum PathLookupError {
+    /// The name has a slash, so it is a path, and nothing is there
+    Missing(PathBuf),
+    /// None of the directories searched has an executable file of the name
+    NotFound {
+        /// The name looked up
+        name: String,
+        /// The directories of PATH, in order
+        searched: Vec<PathBuf>
+    }
+}
+
+impl Display for PathLookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(path) => f.write_fmt(format_args!("executable '{}' does not exist", path.display())),
+            Self::NotFound { name, searched } if searched.is_empty() => {
+                f.write_fmt(format_args!("executable '{name}' not found, PATH is empty"))
+            },
+            Self::NotFound { name, searched } => {
+                let dirs: Vec<String> = searched.iter().map(|dir| dir.display().to_string()).collect();
+                f.write_fmt(format_args!("executable '{name}' not found in PATH, searched {}", dirs.join(", ")))
+            }
+        }
+    }
+}
+
+impl Error for PathLookupError {}
+
+/// Where the program `filename` is, looked up in PATH unless it has a
+/// slash, in which case it only has to exist
+pub fn find_path(filename: &str) -> Result<PathBuf, PathLookupError> {
+    find_in(filename, &std::env::var_os("PATH").unwrap_or_default())
+}
+
+/**
+ *  find_path with `paths` for PATH: the first of its directories with an
+ *  executable regular file of the name, so that a directory or a file
+ *  that can't be run doesn't shadow the program. An empty entry is the
+ *  current directory, as in POSIX, and directories that can't be looked
+ *  in are passed over.
+ */
+pub fn find_in(filename: &str, paths: &OsStr) -> Result<PathBuf, PathLookupError> {
     if filename.contains('/') {
-        return PathBuf::from(filename);
+        let path = PathBuf::from(filename);
+        return match path.exists() {
+            true => Ok(path),
+            false => Err(PathLookupError::Missing(path))
+        };
+    }
+    let mut searched = Vec::new();
+    if !paths.is_empty() {
+        for dir in std::env::split_paths(paths) {
+            let dir = match dir.as_os_str().is_empty() {
+                true => PathBuf::from("."),
+                false => dir
+            };
+            let path = dir.join(filename);
+            if is_executable(&path) {
+                return Ok(path);
+            }
+            searched.push(dir);
+        }
     }
-    let paths = match std::env::var("PATH") {
-        Ok(x) => x,
-        Err(_) => {
-            return PathBuf::from(filename);
+    Err(PathLookupError::NotFound { name: filename.to_string(), searched })
+}
+
+/*
+ *  Whether `path` is a regular file, symlinks followed, with an execute
+ *  bit set
+ */
+fn is_executable(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(meta) => meta.is_file() && meta.permissions().mode() & 0o111 != 0,
+        Err(_) => false
+    }
+}
+
+/// How the paths of programs are checked before they run, on hosts
+/// where another user could swap a program for one of their own
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StrictPaths {
+    /// Programs have to be under this directory, symlinks followed
+    pub root: Option<PathBuf>,
+    /// Who may own a program and the directories above it, root and the
+    /// judger's own user by default
+    pub trusted_uids: Vec<u32>
+}
+
+impl Default for StrictPaths {
+    fn default() -> Self {
+        let euid = unsafe { libc::geteuid() };
+        let mut trusted_uids = vec![0];
+        if euid != 0 {
+            trusted_uids.push(euid);
         }
+        StrictPaths { root: None, trusted_uids }
+    }
+}
+
+/// Why a path fails the strict check
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathProblem {
+    /// Everyone may write to the file or to a directory on the way to
+    /// it, a directory without the sticky bit
+    WorldWritable(PathBuf),
+    /// The file or a directory on the way is owned by an untrusted user
+    Owner {
+        /// The file or directory
+        path: PathBuf,
+        /// Its owner
+        uid: u32
+    },
+    /// The path leads out of the root, which it has to stay under
+    OutsideRoot(PathBuf)
+}
+
+impl Display for PathProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WorldWritable(path) => f.write_fmt(format_args!("{} is world-writable", path.display())),
+            Self::Owner { path, uid } => f.write_fmt(format_args!("{} is owned by uid {uid}", path.display())),
+            Self::OutsideRoot(root) => f.write_fmt(format_args!("it is outside of {}", root.display()))
+        }
+    }
+}
+
+/// What the strict check of a path found, see check_path
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathReport {
+    /// The path as given
+    pub path: PathBuf,
+    /// Where it leads, with symlinks resolved
+    pub resolved: PathBuf,
+    /// What is wrong with it, in order from the file up
+    pub problems: Vec<PathProblem>
+}
+
+impl PathReport {
+    /// Whether the path passed the check
+    pub fn is_safe(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl Display for PathReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}", self.path.display()))?;
+        if self.resolved != self.path {
+            f.write_fmt(format_args!(" -> {}", self.resolved.display()))?;
+        }
+        match self.is_safe() {
+            true => f.write_str(", safe"),
+            false => {
+                let problems: Vec<String> = self.problems.iter().map(PathProblem::to_string).collect();
+                f.write_fmt(format_args!(", {}", problems.join(", ")))
+            }
+        }
+    }
+}
+
+/**
+ *  Check the path of a program the way `strict` says: resolved, the file
+ *  and every directory above it have to be owned by a trusted user, and
+ *  none may be writable by everyone unless it is a sticky directory such
+ *  as /tmp. Fails only if the path can't be resolved.
+ */
+pub fn check_path(path: &Path, strict: &StrictPaths) -> io::Result<PathReport> {
+    let resolved = fs::canonicalize(path)?;
+    let mut problems = Vec::new();
+    for component in resolved.ancestors() {
+        let meta = fs::symlink_metadata(component)?;
+        let mode = meta.mode();
+        if mode & 0o002 != 0 && !(meta.is_dir() && mode & 0o1000 != 0) {
+            problems.push(PathProblem::WorldWritable(component.to_path_buf()));
+        }
+        if !strict.trusted_uids.contains(&meta.uid()) {
+            problems.push(PathProblem::Owner { path: component.to_path_buf(), uid: meta.uid() });
+        }
+    }
+    if let Some(root) = &strict.root {
+        let root = fs::canonicalize(root)?;
+        if !resolved.starts_with(&root) {
+            problems.push(PathProblem::OutsideRoot(root));
+        }
+    }
+    Ok(PathReport { path: path.to_path_buf(), resolved, problems })
+}
+
+/**
+ *  Check that `dir` can take the scratch directories of runs: a directory
+ *  the judger can make files in, on a filesystem mounted writable with at
+ *  least `required_bytes` free, and not mounted noexec when `exec`, as
+ *  when copies of executables are run from it. The error names the
+ *  directory and what is wrong with it.
+ */
+pub fn check_scratch(dir: &Path, required_bytes: u64, exec: bool) -> io::Result<()> {
+    let failed = |kind: io::ErrorKind, what: String| io::Error::new(kind, format!("scratch directory {}: {what}", dir.display()));
+    let os_error = |e: io::Error| failed(e.kind(), e.to_string());
+    if !fs::metadata(dir).map_err(os_error)?.is_dir() {
+        return Err(failed(io::ErrorKind::NotADirectory, String::from("not a directory")));
+    }
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).map_err(|e| failed(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } < 0 {
+        return Err(os_error(io::Error::last_os_error()));
+    }
+    if stat.f_flag & libc::ST_RDONLY != 0 {
+        return Err(failed(io::ErrorKind::ReadOnlyFilesystem, String::from("on a read-only filesystem")));
+    }
+    if exec && stat.f_flag & libc::ST_NOEXEC != 0 {
+        return Err(failed(io::ErrorKind::PermissionDenied, String::from("on a filesystem mounted noexec, the copies of executables can't run from it")));
+    }
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) } < 0 {
+        return Err(os_error(io::Error::last_os_error()));
+    }
+    let free = stat.f_bavail as u64 * stat.f_frsize as u64;
+    if free < required_bytes {
+        return Err(failed(io::ErrorKind::StorageFull, format!(
+            "only {} free, {} short of the {} a run needs",
+            format_memory(free),
+            format_memory(required_bytes - free),
+            format_memory(required_bytes)
+        )));
+    }
+    Ok(())
+}
+
+// Numbers the temporary files of the judger, so that no two of its
+// threads write the same one
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/**
+ *  Create a new file beside `path`, to be renamed over it once written,
+ *  returning it with its path. Its name is `.NAME.PID-N.tmp`, N being a
+ *  number no other file the judger made this way had, so that writers on
+ *  other threads get files of their own as well as those of other
+ *  processes do. It is created as ScratchDir::create_file creates files,
+ *  never through anything already at the name.
+ */
+pub fn create_beside(path: &Path) -> io::Result<(fs::File, PathBuf)> {
+    const ATTEMPTS: usize = 8;
+
+    let Some(name) = path.file_name() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a file", path.display())));
     };
-    let paths_split = paths.split(':');
-    for p in paths_split {
-        let mut path = PathBuf::from(p);
-        path.push(filename);
-        if path.exists() {
-            return path;
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let dir_fd: OwnedFd = fs::OpenOptions::new().read(true).custom_flags(libc::O_DIRECTORY).open(dir)?.into();
+    let mut last_err = None;
+    for _ in 0..ATTEMPTS {
+        let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temp = format!(".{}.{}-{counter}.tmp", name.to_string_lossy(), std::process::id());
+        // Left over by an earlier judger of the same pid, or planted there
+        match scratch::create_at(&dir_fd, &temp, 0o644) {
+            Ok(file) => return Ok((file, dir.join(temp))),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => last_err = Some(e),
+            Err(e) => return Err(e)
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::other("cannot create a temporary file")))
+}
+
+/**
+ *  Replace the file at `path` with `contents` as a whole: they go to a
+ *  file from create_beside and are synced before it is renamed over
+ *  `path`, so that no reader ever sees half of them and a crash leaves
+ *  either the old file or the new one.
+ */
+pub fn replace_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let (mut file, temp) = create_beside(path)?;
+    match file.write_all(contents).and_then(|_| file.sync_all()).and_then(|_| fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+/**
+ *  Bring the file at `path` into the page cache: ask for it with
+ *  posix_fadvise, then read it through, as the advice is only a hint.
+ *  Fails on a file the judger can't read, such as one that is only
+ *  executable.
+ */
+pub fn prewarm(path: &Path) -> io::Result<()> {
+    let mut file = fs::File::open(path)?;
+    let advice = unsafe { libc::posix_fadvise(std::os::fd::AsRawFd::as_raw_fd(&file), 0, 0, libc::POSIX_FADV_WILLNEED) };
+    if advice != 0 {
+        debug!("posix_fadvise on {}: {}", path.display(), io::Error::from_raw_os_error(advice));
+    }
+    let mut buffer = vec![0; 1 << 16];
+    while file.read(&mut buffer)? > 0 {}
+    Ok(())
+}
+
+// Linux reads no more of a script than this for its #! line
+const SHEBANG_LINE_MAX: usize = 256;
+
+/// The `#!` line of a script: the interpreter the kernel would run it
+/// with, and the one argument it would pass before the script's path
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shebang {
+    /// The interpreter as written, a path or a name for env to look up
+    pub interpreter: PathBuf,
+    /// Everything after the interpreter, as a single argument
+    pub arg: Option<String>
+}
+
+/**
+ *  The `#!` line at the start of `header`, read the way Linux reads it:
+ *  blanks around the interpreter are skipped, and the rest of the line is
+ *  one argument, blanks and all. None without a `#!` or an interpreter.
+ */
+pub fn parse_shebang(header: &[u8]) -> Option<Shebang> {
+    let line = header.strip_prefix(b"#!")?;
+    let line = &line[..line.len().min(SHEBANG_LINE_MAX - 2)];
+    let line = match line.iter().position(|&b| b == b'\n') {
+        Some(end) => &line[..end],
+        None => line
+    };
+    let blank = |b: &u8| *b == b' ' || *b == b'\t';
+    let start = line.iter().position(|b| !blank(b))?;
+    let line = &line[start..];
+    let end = line.iter().position(blank).unwrap_or(line.len());
+    let rest = std::str::from_utf8(&line[end..]).ok()?.trim_matches([' ', '\t']);
+    Some(Shebang {
+        interpreter: PathBuf::from(OsStr::from_bytes(&line[..end])),
+        arg: (!rest.is_empty()).then(|| rest.to_string())
+    })
+}
+
+/**
+ *  The `#!` line of the file at `path`, None if it doesn't start with one
+ */
+pub fn read_shebang(path: &Path) -> io::Result<Option<Shebang>> {
+    let mut header = Vec::with_capacity(SHEBANG_LINE_MAX);
+    fs::File::open(path)?.take(SHEBANG_LINE_MAX as u64).read_to_end(&mut header)?;
+    Ok(parse_shebang(&header))
+}
+
+/**
+ *  Format a byte count with a binary unit, e.g. 1.50MiB
+ */
+pub fn format_memory(bytes: u64) -> String {
+    const MEM_UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut mem_display = bytes as f64;
+    let mut display_level: usize = 0;
+    while mem_display > 1024.0 && display_level < MEM_UNITS.len() - 1 {
+        mem_display /= 1024.0;
+        display_level += 1;
+    }
+    format!("{:.2}{}", mem_display, MEM_UNITS[display_level])
+}
+
+/**
+ *  Quote a string as a JSON string literal
+ */
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Why a size or a time given as text can't be read, naming the text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// There is nothing but whitespace
+    Empty,
+    /// What comes before the unit isn't a number
+    NotANumber(String),
+    /// The unit isn't one of those listed
+    UnknownUnit {
+        /// The whole text
+        value: String,
+        /// The unit as given
+        unit: String,
+        /// The units there are
+        expected: &'static str
+    },
+    /// The number has a minus sign
+    Negative(String),
+    /// It is more than fits
+    TooLarge(String)
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => f.write_str("no value given"),
+            Self::NotANumber(value) => f.write_fmt(format_args!("'{value}' is not a number")),
+            Self::UnknownUnit { value, unit, expected } => {
+                f.write_fmt(format_args!("'{value}' has an unknown unit '{unit}', expected {expected}"))
+            },
+            Self::Negative(value) => f.write_fmt(format_args!("'{value}' is negative")),
+            Self::TooLarge(value) => f.write_fmt(format_args!("'{value}' is too large"))
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/*
+ *  Split text such as 1.5g or 500 ms into the number and its unit, the
+ *  unit lower-cased and possibly empty, checking the number is one
+ */
+fn number_and_unit(value: &str) -> Result<(&str, String), ParseError> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(ParseError::Empty);
+    }
+    let unit_start = trimmed.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(unit_start);
+    let number = number.trim_end();
+    let readable = !number.is_empty() && number.chars().all(|c| c.is_ascii_digit() || "+-.".contains(c));
+    match number.parse::<f64>() {
+        Ok(x) if readable && x.is_sign_negative() => Err(ParseError::Negative(trimmed.to_string())),
+        Ok(_) if readable => Ok((number, unit.to_ascii_lowercase())),
+        _ => Err(ParseError::NotANumber(trimmed.to_string()))
+    }
+}
+
+/**
+ *  Parse a byte count with an optional k, m or g suffix, e.g. 64m, which
+ *  may be spelt kb or kib too, in any case, and are all powers of 1024 as
+ *  with judges. A fraction such as 1.5g is rounded down to whole bytes.
+ */
+pub fn parse_size(value: &str) -> Result<u64, ParseError> {
+    let (number, unit) = number_and_unit(value)?;
+    let multiplier: u64 = match unit.as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => return Err(ParseError::UnknownUnit {
+            value: value.trim().to_string(),
+            unit,
+            expected: "b, k, m or g"
+        })
+    };
+    let too_large = || ParseError::TooLarge(value.trim().to_string());
+    if let Ok(count) = number.parse::<u64>() {
+        return count.checked_mul(multiplier).ok_or_else(too_large);
+    }
+    // Checked as a number already, so either a fraction or too many digits
+    let bytes = number.parse::<f64>().map_err(|_| ParseError::NotANumber(value.trim().to_string()))? * multiplier as f64;
+    match bytes < u64::MAX as f64 {
+        true => Ok(bytes as u64),
+        false => Err(too_large())
+    }
+}
+
+/**
+ *  Parse a time such as 1.5s, 500ms or 2m, any case, plain numbers being
+ *  seconds. Zero is a time too.
+ */
+pub fn parse_duration(value: &str) -> Result<Duration, ParseError> {
+    let (number, unit) = number_and_unit(value)?;
+    let scale = match unit.as_str() {
+        "ms" => 1e-3,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        _ => return Err(ParseError::UnknownUnit {
+            value: value.trim().to_string(),
+            unit,
+            expected: "ms, s or m"
+        })
+    };
+    let secs = number.parse::<f64>().map_err(|_| ParseError::NotANumber(value.trim().to_string()))? * scale;
+    Duration::try_from_secs_f64(secs).map_err(|_| ParseError::TooLarge(value.trim().to_string()))
+}
+
+/**
+ *  Join JSON values into an array
+ */
+pub fn json_list(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(","))
+}
+
+/**
+ *  Put JSON values under their keys into an object on one line
+ */
+pub fn json_object(fields: &[(&str, String)]) -> String {
+    let members: Vec<String> = fields.iter().map(|(key, value)| format!("{}:{value}", json_string(key))).collect();
+    format!("{{{}}}", members.join(","))
+}
+
+/**
+ *  Whether `name` names a file right in a directory, as the files a
+ *  program reads and writes in its working directory have to
+ */
+pub fn is_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/')
+}
+
+/**
+ *  `name`, which users had a say in, made a file name for the judger to
+ *  create: the slashes and NULs that would take it elsewhere become
+ *  underscores. None for a name that would still be the directory or its
+ *  parent.
+ */
+pub fn sanitize_file_name(name: &str) -> Option<String> {
+    let name: String = name.chars().map(|c| if c == '/' || c == '\0' { '_' } else { c }).collect();
+    is_file_name(&name).then_some(name)
+}
+
+/**
+ *  The candidate closest to a misspelt name, if any is close enough,
+ *  leading dashes aside
+ */
+pub fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let wanted = name.trim_start_matches('-');
+    candidates
+        .map(|candidate| {
+            let known = candidate.trim_start_matches('-');
+            // Abbreviations count as close
+            let distance = match known.starts_with(wanted) && wanted.len() >= 3 {
+                true => 0,
+                false => edit_distance(wanted, known)
+            };
+            (distance, candidate)
+        })
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/**
+ *  Decode standard base64, with or without its padding. Whitespace is
+ *  skipped, as line-wrapped encoders put it in. None if it isn't base64.
+ */
+pub fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let sextet = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None
+    };
+    let digits: Vec<u8> = text.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+    let unpadded = digits.strip_suffix(b"==").or(digits.strip_suffix(b"=")).unwrap_or(&digits);
+    if (digits.len() != unpadded.len() && !digits.len().is_multiple_of(4)) || unpadded.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(unpadded.len() * 3 / 4);
+    for chunk in unpadded.chunks(4) {
+        let mut group: u32 = 0;
+        for &c in chunk {
+            group = group << 6 | u32::from(sextet(c)?);
         }
+        group <<= 6 * (4 - chunk.len() as u32);
+        let group = group.to_be_bytes();
+        bytes.extend_from_slice(&group[1..chunk.len()]);
     }
-    return PathBuf::from(filename);
-}
\ No newline at end of file
+    Some(bytes)
+}
@@ -0,0 +1,266 @@
+use std::fmt::Display;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const PT_INTERP: u64 = 3;
+// Sizes of a program header by class
+const PHDR32_SIZE: usize = 32;
+const PHDR64_SIZE: usize = 56;
+// Loader paths are short, a longer one is a broken header
+const MAX_INTERP_SIZE: u64 = 4096;
+const EM_386: u16 = 3;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+/// Instruction set an executable is built for
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExecArch {
+    /// 64-bit x86
+    X86_64,
+    /// 32-bit x86
+    I386,
+    /// 64-bit ARM
+    Aarch64
+}
+
+impl ExecArch {
+    /// The architecture the judger itself was built for
+    pub fn native() -> Option<Self> {
+        match std::env::consts::ARCH {
+            "x86_64" => Some(Self::X86_64),
+            "x86" => Some(Self::I386),
+            "aarch64" => Some(Self::Aarch64),
+            _ => None
+        }
+    }
+
+    /// Parse an architecture name such as x86_64, amd64, i386 or arm64
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "x86_64" | "amd64" => Some(Self::X86_64),
+            "i386" | "i686" | "x86" => Some(Self::I386),
+            "aarch64" | "arm64" => Some(Self::Aarch64),
+            _ => None
+        }
+    }
+}
+
+impl Display for ExecArch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match &self {
+            Self::X86_64    => "x86_64",
+            Self::I386      => "i386",
+            Self::Aarch64   => "aarch64"
+        };
+        f.write_str(str)
+    }
+}
+
+/// What the headers of an ELF file say about running it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElfInfo {
+    /// 32 or 64, from its class
+    pub bits: u8,
+    /// Its e_machine
+    pub machine: u16,
+    /// The architecture of the machine, if the judger knows it
+    pub arch: Option<ExecArch>,
+    /// Whether it is an executable or a position-independent one, rather
+    /// than an object file or a core dump
+    pub executable: bool,
+    /// The loader its PT_INTERP header asks for, None if it is static
+    pub interpreter: Option<PathBuf>
+}
+
+impl ElfInfo {
+    /// Whether it needs a loader to start, which then loads its libraries
+    pub fn is_dynamic(&self) -> bool {
+        self.interpreter.is_some()
+    }
+
+    /// Its architecture, or its class and machine number for one the
+    /// judger doesn't know
+    pub fn arch_name(&self) -> String {
+        match self.arch {
+            Some(arch) => arch.to_string(),
+            None => format!("{}-bit ELF machine {}", self.bits, self.machine)
+        }
+    }
+}
+
+/**
+ *  The headers of the ELF file at `path`, None if it isn't one
+ */
+pub fn inspect(path: &Path) -> io::Result<Option<ElfInfo>> {
+    parse(&mut File::open(path)?)
+}
+
+/**
+ *  The headers of the ELF file `reader` reads, from its start. None if it
+ *  doesn't start with the ELF magic, an InvalidData error if it does but
+ *  its headers are cut short or make no sense.
+ */
+pub fn parse<R: Read + Seek>(reader: &mut R) -> io::Result<Option<ElfInfo>> {
+    let mut header = [0u8; 64];
+    let len = read_up_to(reader, &mut header)?;
+    if len < 4 || header[..4] != ELF_MAGIC {
+        return Ok(None);
+    }
+    let bits = match header[4] {
+        ELFCLASS32 => 32,
+        ELFCLASS64 => 64,
+        _ => return Err(invalid("unknown ELF class"))
+    };
+    let int = Endian::of(header[5])?;
+    // e_phoff, e_phentsize and e_phnum, and the size of the header
+    let (header_size, phoff, phentsize, phnum) = match bits {
+        32 => (52, int.u32(&header[28..]), int.u16(&header[42..]), int.u16(&header[44..])),
+        _ => (64, int.u64(&header[32..]), int.u16(&header[54..]), int.u16(&header[56..]))
+    };
+    if len < header_size {
+        return Err(invalid("ELF header cut short"));
+    }
+    let kind = int.u16(&header[16..]);
+    let machine = int.u16(&header[18..]);
+    let arch = match (bits, machine) {
+        (64, EM_X86_64) => Some(ExecArch::X86_64),
+        (32, EM_386) => Some(ExecArch::I386),
+        (64, EM_AARCH64) => Some(ExecArch::Aarch64),
+        _ => None
+    };
+    let entry_size = match bits {
+        32 => PHDR32_SIZE,
+        _ => PHDR64_SIZE
+    };
+    let mut interpreter = None;
+    if phnum > 0 && usize::from(phentsize) < entry_size {
+        return Err(invalid("ELF program headers too small"));
+    }
+    for i in 0..u64::from(phnum) {
+        let mut phdr = [0u8; PHDR64_SIZE];
+        reader.seek(SeekFrom::Start(phoff.saturating_add(i * u64::from(phentsize))))?;
+        if read_up_to(reader, &mut phdr[..entry_size])? < entry_size {
+            return Err(invalid("ELF program headers cut short"));
+        }
+        if int.u32(&phdr) != PT_INTERP {
+            continue;
+        }
+        let (offset, size) = match bits {
+            32 => (int.u32(&phdr[4..]), int.u32(&phdr[16..])),
+            _ => (int.u64(&phdr[8..]), int.u64(&phdr[32..]))
+        };
+        if size == 0 || size > MAX_INTERP_SIZE {
+            return Err(invalid("ELF interpreter of an unlikely size"));
+        }
+        let mut path = vec![0u8; size as usize];
+        reader.seek(SeekFrom::Start(offset))?;
+        if read_up_to(reader, &mut path)? < path.len() {
+            return Err(invalid("ELF interpreter cut short"));
+        }
+        // Null terminated, as the kernel expects
+        let end = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+        path.truncate(end);
+        interpreter = Some(PathBuf::from(OsString::from_vec(path)));
+    }
+    Ok(Some(ElfInfo { bits, machine, arch, executable: kind == ET_EXEC || kind == ET_DYN, interpreter }))
+}
+
+// Byte order of the fields after e_ident
+#[derive(Clone, Copy)]
+enum Endian {
+    Little,
+    Big
+}
+
+impl Endian {
+    fn of(data: u8) -> io::Result<Self> {
+        match data {
+            ELFDATA2LSB => Ok(Self::Little),
+            ELFDATA2MSB => Ok(Self::Big),
+            _ => Err(invalid("unknown ELF byte order"))
+        }
+    }
+
+    fn u16(self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        match self {
+            Self::Little => u16::from_le_bytes(bytes),
+            Self::Big => u16::from_be_bytes(bytes)
+        }
+    }
+
+    fn u32(self, bytes: &[u8]) -> u64 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        u64::from(match self {
+            Self::Little => u32::from_le_bytes(bytes),
+            Self::Big => u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn u64(self, bytes: &[u8]) -> u64 {
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&bytes[..8]);
+        match self {
+            Self::Little => u64::from_le_bytes(word),
+            Self::Big => u64::from_be_bytes(word)
+        }
+    }
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+/*
+ *  Fill as much of `buf` as `reader` has left, returning how much that is
+ */
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e)
+        }
+    }
+    Ok(len)
+}
+
+/// What the ELF header of an executable says about its architecture
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ElfKind {
+    /// Not an ELF file, e.g. a script with a shebang line
+    NotElf,
+    Arch(ExecArch),
+    /// An ELF file for an architecture we don't know
+    Unknown { bits: u8, machine: u16 }
+}
+
+impl ElfKind {
+    /// The kind of a file with the headers `info`, None if not ELF
+    pub(crate) fn of(info: Option<&ElfInfo>) -> Self {
+        match info {
+            None => ElfKind::NotElf,
+            Some(ElfInfo { arch: Some(arch), .. }) => ElfKind::Arch(*arch),
+            Some(info) => ElfKind::Unknown { bits: info.bits, machine: info.machine }
+        }
+    }
+}
+
+/*
+ *  Read the ELF header of the file at `path` to find out its architecture
+ */
+pub(crate) fn detect(path: &Path) -> io::Result<ElfKind> {
+    Ok(ElfKind::of(inspect(path)?.as_ref()))
+}
@@ -0,0 +1,134 @@
+use std::ffi::CStr;
+use std::fs;
+use std::io;
+use std::mem::size_of;
+use std::path::Path;
+
+// Keys /proc/cpuinfo names the CPU model under, x86's first, then those
+// of ARM and other architectures
+const CPU_MODEL_KEYS: [&str; 4] = ["model name", "Processor", "cpu model", "Hardware"];
+
+/// What uname(2) names the host and its kernel
+pub struct Uname {
+    /// Network name of the host
+    pub nodename: String,
+    /// Release of the running kernel, such as 6.1.0-18-amd64
+    pub release: String
+}
+
+/**
+ *  What uname(2) says about the host, None if it fails
+ */
+pub fn uname() -> Option<Uname> {
+    let mut name: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut name) } < 0 {
+        return None;
+    }
+    let field = |chars: &[libc::c_char]| unsafe { CStr::from_ptr(chars.as_ptr()) }.to_string_lossy().into_owned();
+    Some(Uname { nodename: field(&name.nodename), release: field(&name.release) })
+}
+
+/**
+ *  Model of the host's CPU as /proc/cpuinfo names it, None where the file
+ *  can't be read or names none
+ */
+pub fn cpu_model() -> Option<String> {
+    cpu_model_in(&fs::read_to_string("/proc/cpuinfo").ok()?)
+}
+
+/**
+ *  The CPU model in the text of a /proc/cpuinfo: the model name x86 gives
+ *  every core, or what other architectures list it as
+ */
+pub fn cpu_model_in(cpuinfo: &str) -> Option<String> {
+    CPU_MODEL_KEYS.iter().find_map(|key| {
+        cpuinfo.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            let value = value.trim();
+            (name.trim() == *key && !value.is_empty()).then(|| value.to_string())
+        })
+    })
+}
+
+/**
+ *  CPUs in the kernel's list format, such as 0-3,8, None if it isn't one
+ */
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in list.trim().split(',') {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.parse().ok()?, last.parse().ok()?),
+            None => {
+                let cpu = part.parse().ok()?;
+                (cpu, cpu)
+            }
+        };
+        if first > last {
+            return None;
+        }
+        for cpu in first..=last {
+            if !cpus.contains(&cpu) {
+                cpus.push(cpu);
+            }
+        }
+    }
+    Some(cpus)
+}
+
+/**
+ *  One CPU of each physical core among `cpus`, as the topology under
+ *  /sys/devices/system/cpu has them: the hyperthreads of a core picked
+ *  already are left out
+ */
+pub fn physical_cores(cpus: &[usize]) -> Vec<usize> {
+    physical_cores_in(Path::new("/sys/devices/system/cpu"), cpus)
+}
+
+/**
+ *  Like physical_cores, with the topology under `root`. A CPU whose
+ *  siblings can't be read is taken as a core of its own.
+ */
+pub fn physical_cores_in(root: &Path, cpus: &[usize]) -> Vec<usize> {
+    let mut picked = Vec::new();
+    let mut siblings_of_picked = Vec::new();
+    for &cpu in cpus {
+        if siblings_of_picked.contains(&cpu) {
+            continue;
+        }
+        let siblings = fs::read_to_string(root.join(format!("cpu{cpu}/topology/thread_siblings_list")))
+            .ok()
+            .and_then(|list| parse_cpu_list(&list));
+        siblings_of_picked.extend(siblings.unwrap_or_default());
+        picked.push(cpu);
+    }
+    picked
+}
+
+/**
+ *  CPUs the calling thread may run on
+ */
+pub fn allowed_cpus() -> io::Result<Vec<usize>> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    if unsafe { libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut set) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((0..libc::CPU_SETSIZE as usize).filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) }).collect())
+}
+
+/**
+ *  Restrict the calling thread to `cpus`. Children forked by the thread
+ *  inherit its affinity.
+ */
+pub fn pin_thread(cpus: &[usize]) -> io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &cpu in cpus.iter().filter(|&&cpu| cpu < libc::CPU_SETSIZE as usize) {
+        unsafe {
+            libc::CPU_SET(cpu, &mut set);
+        }
+    }
+    // On Linux pid 0 means the calling thread, not the whole process
+    if unsafe { libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &set) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+// Admission of jobs within a memory budget and a pool of cores: a burst
+// of jobs whose memory limits don't fit together runs one after another,
+// those that fit start at once, and a job that can never fit is told so
+// rather than left waiting. So do the tests of a batch with a budget,
+// judged on the mock sandbox.
+
+mod support;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use secure_judger::admission::{Admission, Demand};
+use secure_judger::judger::{JudgeObserver, JudgeResult, JudgeSession};
+use secure_judger::problem::{ProblemJudge, TestCase};
+use secure_judger::sandbox::{MockRun, MockSandbox};
+
+const MIB: u64 = 1 << 20;
+const GIB: u64 = 1 << 30;
+
+fn memory(bytes: u64) -> Demand {
+    Demand { memory_bytes: bytes, dedicated_core: false }
+}
+
+/// Counts the runs going on at once, keeping the most there were
+#[derive(Default)]
+struct Overlap {
+    running: AtomicUsize,
+    most: AtomicUsize
+}
+
+impl JudgeObserver for Overlap {
+    fn on_run_start(&self, _test: &TestCase) {
+        let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.most.fetch_max(now, Ordering::SeqCst);
+    }
+
+    fn on_run_complete(&self, _result: &JudgeResult) {
+        self.running.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/*
+ *  A batch of four tests of 64MiB each within `budget`, whose runs take
+ *  100ms and are counted by `overlap`
+ */
+fn batch(name: &str, budget: u64, overlap: Arc<Overlap>) -> ProblemJudge {
+    let dir = support::dir(name);
+    let cases: Vec<TestCase> = support::cases(&dir, 4).into_iter().map(|case| TestCase { memory_limit: Some(64 * MIB), ..case }).collect();
+    let session = JudgeSession::builder(support::mock_program(&dir.join("program")))
+        .answer(cases[0].answer.clone())
+        .time_limit(Duration::from_secs(30))
+        .copy_exec(false)
+        .sandbox(Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("3\n").wall_time(Duration::from_millis(100)))))
+        .observer(overlap)
+        .build()
+        .unwrap();
+    ProblemJudge::new(session, cases).with_memory_budget(budget)
+}
+
+#[test]
+fn a_burst_of_large_jobs_runs_one_at_a_time() {
+    let admission = Admission::new(Some(8 * GIB), Vec::new());
+    let running = AtomicUsize::new(0);
+    let most_running = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                let _reservation = admission.reserve(memory(6 * GIB));
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most_running.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(50));
+                running.fetch_sub(1, Ordering::SeqCst);
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+    });
+    assert_eq!(done.load(Ordering::SeqCst), 4);
+    assert_eq!(most_running.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn jobs_that_fit_start_together() {
+    let admission = Admission::new(Some(8 * GIB), Vec::new());
+    let reservations: Vec<_> = (0..4).map(|_| admission.try_reserve(memory(2 * GIB)).expect("room for four")).collect();
+    assert!(admission.try_reserve(memory(1)).is_none());
+    drop(reservations);
+    assert!(admission.try_reserve(memory(8 * GIB)).is_some());
+}
+
+#[test]
+fn dedicated_cores_are_not_shared() {
+    let admission = Admission::new(None, vec![2, 5]);
+    let dedicated = Demand { memory_bytes: GIB, dedicated_core: true };
+    let first = admission.try_reserve(dedicated).expect("a core");
+    let second = admission.try_reserve(dedicated).expect("another core");
+    let mut cores = vec![first.core().unwrap(), second.core().unwrap()];
+    cores.sort();
+    assert_eq!(cores, [2, 5]);
+    assert!(admission.try_reserve(dedicated).is_none());
+    // Jobs without a core of their own still start
+    assert_eq!(admission.try_reserve(memory(GIB)).map(|reservation| reservation.core()), Some(None));
+
+    let freed = first.core();
+    drop(first);
+    assert_eq!(admission.try_reserve(dedicated).and_then(|reservation| reservation.core()), freed);
+}
+
+#[test]
+fn jobs_that_never_fit_are_refused() {
+    let admission = Admission::new(Some(8 * GIB), Vec::new());
+    assert!(admission.check(memory(8 * GIB)).is_ok());
+    assert!(admission.check(memory(8 * GIB + 1)).unwrap_err().contains("over the memory budget"));
+    let error = admission.check(Demand { memory_bytes: GIB, dedicated_core: true }).unwrap_err();
+    assert!(error.contains("no core"), "{error}");
+    assert!(Admission::new(None, Vec::new()).check(memory(u64::MAX)).is_ok());
+}
+
+#[test]
+fn large_tests_of_a_batch_run_one_after_another() {
+    let overlap = Arc::new(Overlap::default());
+    let submission = batch("large", 100 * MIB, overlap.clone()).run_parallel(4, &[]).unwrap();
+    assert_eq!(submission.results.len(), 4);
+    assert!(submission.results.iter().all(JudgeResult::accepted));
+    assert_eq!(overlap.most.load(Ordering::SeqCst), 1);
+
+    // With room for all of them they run together
+    let overlap = Arc::new(Overlap::default());
+    batch("small", 256 * MIB, overlap.clone()).run_parallel(4, &[]).unwrap();
+    assert!(overlap.most.load(Ordering::SeqCst) > 1);
+
+    // One over the budget fails the batch before any test runs
+    let overlap = Arc::new(Overlap::default());
+    let Err(error) = batch("over-budget", 32 * MIB, overlap.clone()).run_parallel(4, &[]) else {
+        panic!("a test over the budget was judged");
+    };
+    assert!(error.to_string().contains("over the memory budget"), "{error}");
+    assert_eq!(overlap.most.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,122 @@
+// Answers given to a session in memory or through a shared cache instead
+// of as a file, judged against the fixtures in the real sandbox without
+// anything written for them, cached results keyed by the answer as it
+// was judged, and scratch directories named for whoever embeds the
+// judger. Skipped where the fixtures can't be built.
+#![cfg(feature = "seccomp")]
+
+mod support;
+
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+
+use secure_judger::cache::AnswerCache;
+use secure_judger::judger::{AnswerSource, JudgeResult, JudgeSession, JudgeSessionBuilder, JudgeStatus, KeepPolicy};
+use secure_judger::plan::PlannedAnswer;
+
+/*
+ *  Judge the fixture `name` against `answer`, after `configure` had its
+ *  say. None if skipped.
+ */
+fn judge(name: &str, answer: AnswerSource, configure: impl FnOnce(JudgeSessionBuilder) -> JudgeSessionBuilder) -> Option<JudgeResult> {
+    let exec = support::fixture(name)?;
+    let builder = JudgeSession::builder(exec).answer_source(answer).time_limit(Duration::from_millis(500));
+    Some(configure(builder).build().unwrap().run_judge(&[]).unwrap())
+}
+
+#[test]
+fn answers_in_memory_are_judged() {
+    let Some(result) = judge("hello", AnswerSource::Bytes(b"hello\n".to_vec()), |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+
+    let Some(result) = judge("hello", AnswerSource::Bytes(b"hello\nworld\n".to_vec()), |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::WrongAnswer), "{}", result.status);
+    assert!(result.difference.is_some());
+}
+
+#[test]
+fn answers_in_memory_are_not_written_out() {
+    let scratch = support::write_file("memory-scratch.keep", b"").with_file_name("memory-scratch");
+    let _ = fs::remove_dir_all(&scratch);
+    fs::create_dir_all(&scratch).unwrap();
+    let Some(result) = judge("hello", AnswerSource::Bytes(b"hello\n".to_vec()), |b| {
+        b.scratch_dir(scratch.clone()).keep_output(KeepPolicy::Always)
+    }) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    let kept = result.kept_dir.unwrap();
+    let mut names: Vec<String> = fs::read_dir(&kept).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    names.sort();
+    assert_eq!(names, ["program", "stderr", "stdout", "work"]);
+    assert_eq!(fs::read_dir(&scratch).unwrap().count(), 1);
+}
+
+#[test]
+fn cached_answers_are_read_once() {
+    let Some(exec) = support::fixture("hello") else { return };
+    let path = support::write_file("cached.ans", b"hello\n");
+    let answers = Arc::new(AnswerCache::new());
+    let build = || {
+        JudgeSession::builder(exec.clone())
+            .answer_source(AnswerSource::Cached(answers.clone(), path.clone()))
+            .build()
+            .unwrap()
+    };
+    let first = build().run_judge(&[]).unwrap();
+    // Kept as it was when first read
+    fs::write(&path, "bye\n").unwrap();
+    let second = build().run_judge(&[]).unwrap();
+    assert!(matches!(first.status, JudgeStatus::Accepted), "{}", first.status);
+    assert!(matches!(second.status, JudgeStatus::Accepted), "{}", second.status);
+    assert_eq!(answers.reads(), 1);
+}
+
+#[test]
+fn results_are_cached_under_the_answer_judged() {
+    let Some(exec) = support::fixture("hello") else { return };
+    let path = support::write_file("changed.ans", b"hello\n");
+    let results = support::dir("answer-results");
+    let answers = Arc::new(AnswerCache::new());
+    let judge = |answer: AnswerSource| {
+        let builder = JudgeSession::builder(exec.clone()).answer_source(answer).cache_dir(results.clone());
+        builder.build().unwrap().run_judge(&[]).unwrap()
+    };
+    let first = judge(AnswerSource::Cached(answers.clone(), path.clone()));
+    fs::write(&path, "bye\n").unwrap();
+    // Still the answer as first read, and the result of that
+    let second = judge(AnswerSource::Cached(answers.clone(), path.clone()));
+    assert!(matches!(first.status, JudgeStatus::Accepted), "{}", first.status);
+    assert!(matches!(second.status, JudgeStatus::Accepted) && second.cached, "{}", second.status);
+    // Not a result judged against the old answer
+    let third = judge(AnswerSource::File(path));
+    assert!(matches!(third.status, JudgeStatus::WrongAnswer), "{}", third.status);
+    assert!(!third.cached);
+}
+
+#[test]
+fn answers_in_memory_are_planned() {
+    let Some(exec) = support::fixture("hello") else { return };
+    let session = JudgeSession::builder(exec).answer_source(AnswerSource::Bytes(b"hello\n".to_vec())).build().unwrap();
+    let plan = session.describe(&[]).unwrap();
+    assert!(matches!(plan.answer, PlannedAnswer::Bytes(6)));
+    assert!(plan.to_string().contains("6 bytes in memory"));
+    assert!(plan.to_json().contains("\"answer\": {\"bytes\":6}"), "{}", plan.to_json());
+}
+
+#[test]
+fn scratch_directories_take_the_prefix() {
+    let scratch = support::write_file("prefix-scratch.keep", b"").with_file_name("prefix-scratch");
+    let _ = fs::remove_dir_all(&scratch);
+    fs::create_dir_all(&scratch).unwrap();
+    let Some(result) = judge("hello", AnswerSource::Bytes(b"hello\n".to_vec()), |b| {
+        b.scratch_dir(scratch.clone()).scratch_prefix(String::from("submission-42")).keep_output(KeepPolicy::Always)
+    }) else { return };
+    let kept = result.kept_dir.unwrap();
+    assert!(kept.file_name().unwrap().to_string_lossy().starts_with("submission-42-"), "{}", kept.display());
+
+    let Some(exec) = support::fixture("hello") else { return };
+    for prefix in ["", "..", "../escape"] {
+        let error = JudgeSession::builder(exec.clone()).scratch_prefix(String::from(prefix)).build().err().expect(prefix);
+        assert!(error.to_string().contains("scratch prefix"), "{error}");
+    }
+}
@@ -0,0 +1,160 @@
+// Runs judged as futures: many of them waiting on one thread, each still
+// held to its wall limit, and killed when cancelled through the
+// session's handle or dropped.
+// Runs the fixtures in the real sandbox, skipped where they can't be
+// built, and the mock sandbox for a child without a pidfd to wait on.
+#![cfg(all(feature = "seccomp", feature = "async"))]
+
+mod support;
+
+use std::future::Future;
+use std::io::{self, Read};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use secure_judger::judger::{JudgeHandle, JudgeObserver, JudgeSession, JudgeSessionBuilder, JudgeStatus, RunEvent};
+use secure_judger::sandbox::{InputSource, MockRun, MockSandbox};
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// An input that gives nothing for a long while, so that a program
+/// reading it waits idle until it is stopped
+struct Stalled;
+
+impl Read for Stalled {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        thread::sleep(Duration::from_secs(10));
+        Ok(0)
+    }
+}
+
+/// Keeps the process IDs of the programs started
+#[derive(Default)]
+struct Pids(Mutex<Vec<i32>>);
+
+impl JudgeObserver for Pids {
+    fn on_event(&self, event: &RunEvent) {
+        if let RunEvent::Started { pid, .. } = event {
+            self.0.lock().unwrap().push(*pid);
+        }
+    }
+}
+
+/*
+ *  Poll `futures` on this thread until all of them are done, parking it
+ *  while none can go on
+ */
+fn join_all<T>(futures: Vec<Pin<Box<dyn Future<Output = T> + '_>>>) -> Vec<T> {
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut futures: Vec<_> = futures.into_iter().map(Some).collect();
+    let mut done: Vec<Option<T>> = futures.iter().map(|_| None).collect();
+    while done.iter().any(Option::is_none) {
+        for (future, output) in futures.iter_mut().zip(&mut done) {
+            if let Some(Poll::Ready(result)) = future.as_mut().map(|f| f.as_mut().poll(&mut cx)) {
+                *output = Some(result);
+                *future = None;
+            }
+        }
+        if done.iter().any(Option::is_none) {
+            thread::park();
+        }
+    }
+    done.into_iter().map(Option::unwrap).collect()
+}
+
+/*
+ *  A session judging `exec` against the answer "hello"
+ */
+fn session(exec: &Path) -> JudgeSessionBuilder {
+    let answer = support::write_file("async.ans", b"hello\n");
+    JudgeSession::builder(exec.to_path_buf()).answer(answer)
+}
+
+/*
+ *  A session of `read_stdin` waiting on input that doesn't come
+ */
+fn stalled(read_stdin: &Path) -> JudgeSessionBuilder {
+    session(read_stdin).input(InputSource::Reader(Box::new(Stalled)))
+}
+
+#[test]
+fn runs_wait_together_on_one_thread() {
+    let (Some(hello), Some(read_stdin)) = (support::fixture("hello"), support::fixture("read_stdin")) else {
+        return;
+    };
+    let hanging: Vec<_> = (0..4)
+        .map(|_| stalled(&read_stdin).time_limit(Duration::from_millis(200)).build().unwrap())
+        .collect();
+    let accepted = session(&hello).build().unwrap();
+    let start = Instant::now();
+    let mut futures: Vec<Pin<Box<dyn Future<Output = _>>>> = hanging
+        .iter()
+        .map(|session| Box::pin(session.run_judge_async(&[])) as Pin<Box<dyn Future<Output = _>>>)
+        .collect();
+    futures.push(Box::pin(accepted.run_judge_async(&[])));
+    let results: Vec<_> = join_all(futures).into_iter().map(Result::unwrap).collect();
+    // Each waits past its 600ms wall limit, one after the other they would
+    // take 2.4s
+    assert!(start.elapsed() < Duration::from_secs(2), "the runs took {:?}", start.elapsed());
+    for result in &results[..4] {
+        assert!(matches!(result.status, JudgeStatus::IdlenessLimitExceeded), "{}", result.status);
+    }
+    assert!(matches!(results[4].status, JudgeStatus::Accepted), "{}", results[4].status);
+    assert!(results[4].time_used < Duration::from_millis(300), "{:?}", results[4].time_used);
+}
+
+#[test]
+fn cancelling_the_handle_cancels_the_run() {
+    let Some(read_stdin) = support::fixture("read_stdin") else {
+        return;
+    };
+    let handle = JudgeHandle::new();
+    let session = stalled(&read_stdin).time_limit(Duration::from_secs(5)).cancel_handle(handle.clone()).build().unwrap();
+    let start = Instant::now();
+    let cancelling = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        handle.cancel();
+    });
+    let result = join_all(vec![Box::pin(session.run_judge_async(&[]))]).pop().unwrap().unwrap();
+    cancelling.join().unwrap();
+    assert!(matches!(result.status, JudgeStatus::Cancelled), "{}", result.status);
+    assert!(start.elapsed() < Duration::from_secs(2), "cancelling took {:?}", start.elapsed());
+}
+
+#[test]
+fn dropping_the_future_kills_the_program() {
+    let Some(read_stdin) = support::fixture("read_stdin") else {
+        return;
+    };
+    let pids = Arc::new(Pids::default());
+    let session = stalled(&read_stdin).time_limit(Duration::from_secs(5)).observer(pids.clone()).build().unwrap();
+    let mut future = Box::pin(session.run_judge_async(&[]));
+    assert!(future.as_mut().poll(&mut Context::from_waker(Waker::noop())).is_pending());
+    let pid = *pids.0.lock().unwrap().last().expect("the program started");
+    let proc = format!("/proc/{pid}");
+    assert!(Path::new(&proc).exists());
+    drop(future);
+    assert!(!Path::new(&proc).exists(), "{proc} is still there");
+}
+
+#[test]
+fn futures_move_between_threads() {
+    let sandbox = Arc::new(MockSandbox::new(vec![MockRun::exit(0).stdout("hello\n").wall_time(Duration::from_millis(50))]));
+    let exec = std::env::current_exe().unwrap();
+    let session = session(&exec).copy_exec(false).sandbox(sandbox).build().unwrap();
+    // Mock children have no pidfd, so this waits a slice at a time
+    let future = session.run_judge_async(&[]);
+    let result = thread::scope(|scope| scope.spawn(|| join_all(vec![Box::pin(future)]).pop().unwrap()).join().unwrap());
+    assert!(matches!(result.unwrap().status, JudgeStatus::Accepted));
+}
@@ -0,0 +1,74 @@
+// Calibrating by the reference workload: the score and multiplier a
+// workload time comes to, storing them and reading them back, and the
+// sessions of a process with a calibration installed, whose time limits
+// are scaled and whose results say so. Judged on the mock sandbox.
+
+mod support;
+
+use std::fs;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use secure_judger::calibration::{self, Calibration};
+use secure_judger::judger::{JudgeSession, JudgeStatus};
+use secure_judger::sandbox::{InputSource, MockRun, MockSandbox};
+
+#[test]
+fn workload_is_the_same_everywhere() {
+    assert_eq!(calibration::workload(), calibration::WORKLOAD_CHECKSUM);
+}
+
+#[test]
+fn scores_against_the_baseline() {
+    let slower = Calibration::of(calibration::BASELINE * 2);
+    assert_eq!((slower.score, slower.multiplier), (0.5, 2.0));
+    let faster = Calibration::of(Duration::from_millis(1500));
+    assert_eq!((faster.score, faster.multiplier), (1.33, 0.75));
+    assert!(faster.measured_at > 0);
+}
+
+#[test]
+fn stored_and_read_back() {
+    let dir = support::dir("stored");
+    let path = dir.join("state/calibration.json");
+    assert!(Calibration::load(&path).unwrap().is_none());
+    let calibration = Calibration::of(Duration::from_millis(2500));
+    calibration.store(&path).unwrap();
+    assert_eq!(Calibration::load(&path).unwrap(), Some(calibration));
+    // Nothing is left beside it
+    assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+    for text in ["{\"score\":1}", "{\"score\":1,\"multiplier\":0,\"cpu_time_ms\":2000,\"measured_at\":0}", "[1]", "multiplier = 1"] {
+        fs::write(&path, text).unwrap();
+        let error = Calibration::load(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{text}: {error}");
+    }
+}
+
+#[test]
+fn installed_calibration_scales_sessions() {
+    let dir = support::dir("installed");
+    let case = support::cases(&dir, 1).remove(0);
+    let exec = support::mock_program(&dir.join("program"));
+    let mut slower = Calibration::of(Duration::from_secs(3));
+    assert!(calibration::install(slower.clone()));
+    slower.multiplier = 9.0;
+    assert!(!calibration::install(slower));
+
+    let session = JudgeSession::builder(exec)
+        .input(InputSource::File(case.input))
+        .answer(case.answer)
+        .time_limit(Duration::from_secs(1))
+        .copy_exec(false)
+        .sandbox(Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("3\n"))))
+        .build()
+        .unwrap();
+    let result = session.run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    let limits = result.limits.unwrap();
+    assert_eq!((limits.base.cpu, limits.effective.cpu), (Duration::from_secs(1), Duration::from_millis(1500)));
+    let environment = result.environment.unwrap();
+    assert_eq!(environment.calibration.as_ref().map(|c| c.multiplier), Some(1.5));
+    assert!(environment.to_json().contains(",\"calibration\":{\"score\":0.67,\"multiplier\":1.5,"), "{}", environment.to_json());
+}
@@ -0,0 +1,98 @@
+// The compare-runs subcommand: several executables judged on the same
+// tests, their verdicts side by side with the tests they disagree on
+// marked, as a table and as JSON. Runs the binary on the fixtures in the
+// real sandbox, skipped where they can't be built.
+#![cfg(feature = "seccomp")]
+
+mod support;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/*
+ *  Tests in a directory of their own that hello and copy_input both pass
+ *  but for "bye", which only copy_input does
+ */
+fn tests(name: &str) -> PathBuf {
+    let dir = support::write_file(&format!("{name}.keep"), b"").with_file_name(format!("{name}-tests"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (test, text) in [("1", "hello\n"), ("bye", "bye\n"), ("3", "hello\n")] {
+        fs::write(dir.join(format!("{test}.in")), text).unwrap();
+        fs::write(dir.join(format!("{test}.ans")), text).unwrap();
+    }
+    dir
+}
+
+/*
+ *  The exit code and stdout of compare-runs on `execs`
+ */
+fn compare_runs(dir: &Path, options: &[&str], execs: &[&Path]) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_secure-judger"))
+        .arg("compare-runs")
+        .arg("--quiet")
+        .args(options)
+        .arg("--tests")
+        .arg(dir)
+        .args(execs)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    (output.status.code(), String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[test]
+fn disagreements_are_marked() {
+    let (Some(hello), Some(copy)) = (support::fixture("hello"), support::fixture("copy_input")) else { return };
+    let dir = tests("compare-runs");
+    let (code, report) = compare_runs(&dir, &[], &[&hello, &copy]);
+    assert_eq!(code, Some(1), "{report}");
+    let lines: Vec<&str> = report.lines().collect();
+    assert!(lines[0].starts_with("TEST"), "{report}");
+    assert!(lines[0].contains(&*hello.to_string_lossy()) && lines[0].contains(&*copy.to_string_lossy()), "{report}");
+    let row = |test: &str| *lines.iter().find(|line| line.starts_with(&format!("{test} "))).unwrap_or_else(|| panic!("{report}"));
+    assert!(row("bye").contains("WA") && row("bye").ends_with("differs"), "{report}");
+    assert!(row("1").matches("AC").count() == 2 && !row("1").contains("differs"), "{report}");
+    assert!(row("total").contains("WA 2/3") && row("total").contains("AC 3/3"), "{report}");
+    assert!(report.contains("They disagree on 1 of 3 tests: bye"), "{report}");
+}
+
+#[test]
+fn agreement_exits_with_zero() {
+    let Some(hello) = support::fixture("hello") else { return };
+    let dir = tests("compare-runs-agree");
+    let (code, report) = compare_runs(&dir, &[], &[&hello, &hello]);
+    assert_eq!(code, Some(0), "{report}");
+    assert!(report.contains("The 2 executables agree on every test"), "{report}");
+}
+
+#[test]
+fn json_lists_disagreements() {
+    let (Some(hello), Some(copy)) = (support::fixture("hello"), support::fixture("copy_input")) else { return };
+    let dir = tests("compare-runs-json");
+    let (code, report) = compare_runs(&dir, &["--json"], &[&copy, &hello]);
+    assert_eq!(code, Some(1), "{report}");
+    assert!(report.starts_with("{\"schema\":\"secure-judger/1\",\"solutions\":[{\"executable\":"), "{report}");
+    assert!(report.contains("\"disagreements\":[\"bye\"]"), "{report}");
+    assert_eq!(report.matches("\"aggregate\":").count(), 2, "{report}");
+}
+
+#[test]
+fn schema_can_be_pinned() {
+    let (Some(hello), Some(copy)) = (support::fixture("hello"), support::fixture("copy_input")) else { return };
+    let dir = tests("compare-runs-pinned");
+    let (code, pinned) = compare_runs(&dir, &["--format", "json-v1"], &[&copy, &hello]);
+    assert_eq!(code, Some(1), "{pinned}");
+    assert!(pinned.starts_with("{\"schema\":\"secure-judger/1\","), "{pinned}");
+    let (code, report) = compare_runs(&dir, &["--format", "json-v9"], &[&copy, &hello]);
+    assert_eq!(code, Some(2), "{report}");
+}
+
+#[test]
+fn needs_two_executables() {
+    let Some(hello) = support::fixture("hello") else { return };
+    let dir = tests("compare-runs-one");
+    let (code, report) = compare_runs(&dir, &[], &[&hello]);
+    assert_eq!(code, Some(2), "{report}");
+}
@@ -0,0 +1,179 @@
+// The daemon keeping its jobs in a state directory: killed with requests
+// still queued and started again on the directory, it judges every job
+// once and keeps the results for the jobs command. Given a memory budget,
+// it judges in turn the requests that don't fit in it together. Runs the
+// binary on the fixtures in the real sandbox, skipped where they can't be
+// built.
+#![cfg(feature = "seccomp")]
+
+mod support;
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use secure_judger::json::{self, Value};
+
+const JOBS: usize = 5;
+
+/// A daemon of the binary, killed if the test fails with it running
+struct Daemon(Child);
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/*
+ *  A daemon on `socket` keeping its jobs in `state`, judging one at a
+ *  time unless `options` say otherwise, once it listens
+ */
+fn start(socket: &Path, state: &Path, options: &[&str]) -> (Daemon, UnixStream) {
+    let daemon = Daemon(Command::new(env!("CARGO_BIN_EXE_secure-judger"))
+        .arg("daemon")
+        .arg("--socket")
+        .arg(socket)
+        .arg("--state-dir")
+        .arg(state)
+        .arg("--time-limit")
+        .arg("200ms")
+        .args(options)
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap());
+    let start = Instant::now();
+    loop {
+        if let Ok(stream) = UnixStream::connect(socket) {
+            stream.set_read_timeout(Some(Duration::from_secs(30))).unwrap();
+            return (daemon, stream);
+        }
+        assert!(start.elapsed() < Duration::from_secs(10), "the daemon didn't start listening");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn member<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(members) => members.iter().find(|(member, _)| member == key).map(|(_, value)| value),
+        _ => None
+    }
+}
+
+fn read_reply(reader: &mut impl BufRead) -> Value {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    json::parse(&line).unwrap_or_else(|e| panic!("reply {line:?}: {e}"))
+}
+
+/*
+ *  The stored jobs, once none is queued or running any more
+ */
+fn finished_jobs(stream: &mut UnixStream) -> Vec<Value> {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let start = Instant::now();
+    loop {
+        stream.write_all(b"{\"cmd\": \"jobs\"}\n").unwrap();
+        let Some(Value::Array(jobs)) = member(&read_reply(&mut reader), "jobs").cloned() else {
+            panic!("no jobs in the reply");
+        };
+        let pending = jobs.iter().any(|job| !matches!(member(job, "status"), Some(Value::String(status)) if status == "done"));
+        if !pending {
+            return jobs;
+        }
+        assert!(start.elapsed() < Duration::from_secs(30), "jobs still pending");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn jobs_survive_a_restart() {
+    let Some(exec) = support::fixture("infinite_loop") else { return };
+    let input = support::write_file("daemon.in", b"");
+    let answer = support::write_file("daemon.ans", b"");
+    let dir = input.parent().unwrap().join("daemon-state");
+    let _ = std::fs::remove_dir_all(&dir);
+    let socket: PathBuf = input.parent().unwrap().join("daemon.sock");
+    let request = |id: usize| {
+        format!("{{\"id\": {id}, \"exec\": {:?}, \"input\": {:?}, \"answer\": {:?}}}\n", exec.display(), input.display(), answer.display())
+    };
+
+    let (daemon, mut stream) = start(&socket, &dir, &[]);
+    for id in 1..=JOBS {
+        stream.write_all(request(id).as_bytes()).unwrap();
+    }
+    // Killed once the first result is in, the second job running and the
+    // others queued
+    let first = read_reply(&mut BufReader::new(stream.try_clone().unwrap()));
+    drop(daemon);
+    assert_eq!(member(&first, "job"), Some(&Value::Number(1.0)));
+    let first_result = member(&first, "result").expect("a result").to_json();
+
+    let (mut daemon, mut stream) = start(&socket, &dir, &[]);
+    let jobs = finished_jobs(&mut stream);
+    let numbers: Vec<Option<&Value>> = jobs.iter().map(|job| member(job, "job")).collect();
+    let expected: Vec<Value> = (1..=JOBS).map(|n| Value::Number(n as f64)).collect();
+    assert_eq!(numbers, expected.iter().map(Some).collect::<Vec<_>>());
+    for (job, id) in jobs.iter().zip(1..) {
+        assert_eq!(member(job, "id"), Some(&Value::Number(f64::from(id))));
+        let result = member(job, "result").expect("a result");
+        assert_eq!(member(result, "status"), Some(&Value::String(String::from("TLE"))), "{}", result.to_json());
+    }
+    // The job done before the kill wasn't judged again
+    assert_eq!(member(&jobs[0], "result").unwrap().to_json(), first_result);
+
+    // New jobs are numbered after those before the restart
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    stream.write_all(request(JOBS + 1).as_bytes()).unwrap();
+    assert_eq!(member(&read_reply(&mut reader), "job"), Some(&Value::Number((JOBS + 1) as f64)));
+    stream.write_all(b"{\"cmd\": \"shutdown\"}\n").unwrap();
+    read_reply(&mut reader);
+    assert!(daemon.0.wait().unwrap().success());
+}
+
+#[test]
+fn requests_over_the_memory_budget_are_judged_in_turn() {
+    let Some(exec) = support::fixture("wind_down") else { return };
+    let input = support::write_file("budget.in", b"");
+    let answer = support::write_file("budget.ans", b"");
+    let dir = input.parent().unwrap().join("budget-state");
+    let _ = std::fs::remove_dir_all(&dir);
+    let socket: PathBuf = input.parent().unwrap().join("budget.sock");
+    let request = |id: usize, memory: u64| {
+        format!(
+            "{{\"id\": {id}, \"exec\": {:?}, \"input\": {:?}, \"answer\": {:?}, \"memory_limit_bytes\": {memory}}}\n",
+            exec.display(),
+            input.display(),
+            answer.display()
+        )
+    };
+
+    let (mut daemon, mut stream) = start(&socket, &dir, &["--jobs", "2", "--memory-budget", "96m"]);
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    // Never fits, and is refused rather than left waiting
+    stream.write_all(request(1, 128 << 20).as_bytes()).unwrap();
+    let refused = read_reply(&mut reader);
+    assert_eq!(member(&refused, "id"), Some(&Value::Number(1.0)));
+    let Some(Value::String(error)) = member(&refused, "error") else {
+        panic!("no error in {}", refused.to_json());
+    };
+    assert!(error.contains("over the memory budget"), "{error}");
+
+    // Two workers, but room for one of them at a time: the second run
+    // starts once the first, waiting out its wall time limit of 600ms,
+    // gave its memory back
+    let start = Instant::now();
+    stream.write_all((request(2, 64 << 20) + &request(3, 64 << 20)).as_bytes()).unwrap();
+    for _ in 0..2 {
+        let reply = read_reply(&mut reader);
+        assert!(member(&reply, "result").is_some(), "no result in {}", reply.to_json());
+    }
+    assert!(start.elapsed() >= Duration::from_millis(1200), "judged together in {:?}", start.elapsed());
+    stream.write_all(b"{\"cmd\": \"shutdown\"}\n").unwrap();
+    read_reply(&mut reader);
+    assert!(daemon.0.wait().unwrap().success());
+}
@@ -0,0 +1,101 @@
+// The diff-results subcommand: two batch reports compared test by test,
+// the tests whose verdicts or timings changed beyond the tolerance shown
+// as a table and as JSON, and the exit code telling whether any did.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/*
+ *  A batch report at `name` in its own directory, of a test "1" and a
+ *  test `second` judged to `statuses` in `time_ms`
+ */
+fn report(name: &str, second: &str, statuses: [&str; 2], time_ms: [u64; 2]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("diff-results");
+    fs::create_dir_all(&dir).unwrap();
+    let test = |name: &str, status: &str, time_ms: u64| {
+        format!(
+            "{{\"name\":\"{name}\",\"input\":\"tests/{name}.in\",\"answer\":\"tests/{name}.ans\",\"weight\":1,\
+             \"result\":{{\"status\":\"{status}\",\"message\":\"{status}\",\"time_ms\":{time_ms},\"cpu_time_ms\":{time_ms},\
+             \"memory_bytes\":1048576,\"judge_overhead_ms\":0.300}}}}"
+        )
+    };
+    let text = format!(
+        "{{\"schema\":\"secure-judger/1\",\"tests\":[{},{}],\"aggregate\":{{\"status\":\"AC\",\"message\":\"Accepted\",\
+         \"score\":2,\"max_score\":2,\"max_time_ms\":0,\"max_cpu_time_ms\":0,\"max_memory_bytes\":0,\"mean_judge_overhead_ms\":0.300}}}}",
+        test("1", statuses[0], time_ms[0]),
+        test(second, statuses[1], time_ms[1])
+    );
+    let path = dir.join(name);
+    fs::write(&path, text).unwrap();
+    path
+}
+
+/*
+ *  The exit code and stdout of diff-results on `old` and `new`
+ */
+fn diff_results(options: &[&str], old: &Path, new: &Path) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_secure-judger"))
+        .arg("diff-results")
+        .args(options)
+        .arg(old)
+        .arg(new)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    (output.status.code(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn unchanged_reports_exit_zero() {
+    let old = report("same-old.json", "2", ["AC", "AC"], [100, 4]);
+    let new = report("same-new.json", "2", ["AC", "AC"], [104, 13]);
+    let (code, stdout) = diff_results(&[], &old, &new);
+    assert_eq!(code, Some(0), "{stdout}");
+    assert!(stdout.starts_with("None of the 2 tests changed"), "{stdout}");
+}
+
+#[test]
+fn changes_are_shown_and_exit_one() {
+    let old = report("changed-old.json", "2", ["AC", "AC"], [100, 100]);
+    let new = report("changed-new.json", "3", ["WA", "AC"], [100, 100]);
+    let (code, stdout) = diff_results(&[], &old, &new);
+    assert_eq!(code, Some(1), "{stdout}");
+    let row = stdout.lines().find(|line| line.starts_with("1 ")).expect(&stdout);
+    assert!(row.contains("AC -> WA"), "{row}");
+    assert!(stdout.contains(&format!("Only in {}: 2", old.display())), "{stdout}");
+    assert!(stdout.contains(&format!("Only in {}: 3", new.display())), "{stdout}");
+    assert!(stdout.contains("1 of 1 tests changed, 1 of them in verdict"), "{stdout}");
+
+    // A slower run only changed past the tolerance given
+    let slower = report("slower.json", "2", ["AC", "AC"], [100, 130]);
+    let (code, stdout) = diff_results(&[], &old, &slower);
+    assert_eq!(code, Some(1));
+    assert!(stdout.contains("100ms -> 130ms (+30.0%)"), "{stdout}");
+    assert_eq!(diff_results(&["--time-tolerance", "30%"], &old, &slower).0, Some(0));
+    assert_eq!(diff_results(&["--min-time-change", "30ms", "--time-tolerance", "0"], &old, &slower).0, Some(0));
+}
+
+#[test]
+fn json_lists_the_changes() {
+    let old = report("json-old.json", "2", ["AC", "TLE"], [100, 1000]);
+    let new = report("json-new.json", "2", ["AC", "AC"], [100, 400]);
+    let (code, stdout) = diff_results(&["--json"], &old, &new);
+    assert_eq!(code, Some(1));
+    assert!(stdout.starts_with("{\"schema\":\"secure-judger/1\",\"tolerance\":{\"time_percent\":5,\"time_ms\":10,\"memory_percent\":5}"), "{stdout}");
+    assert!(stdout.contains("\"changed\":[{\"name\":\"2\",\"diff\":{\"verdict_changed\":true"), "{stdout}");
+    assert!(stdout.contains("\"time_delta_ms\":-600,\"time_delta_percent\":-60.0"), "{stdout}");
+    assert!(stdout.contains("\"unchanged\":1,\"added\":[],\"removed\":[]"), "{stdout}");
+}
+
+#[test]
+fn unreadable_reports_are_setup_errors() {
+    let old = report("readable.json", "2", ["AC", "AC"], [1, 1]);
+    let missing = old.with_file_name("missing.json");
+    let (code, stdout) = diff_results(&[], &old, &missing);
+    assert_eq!(code, Some(2));
+    assert!(stdout.contains("missing.json"), "{stdout}");
+    let broken = old.with_file_name("broken.json");
+    fs::write(&broken, "{\"tests\":").unwrap();
+    assert_eq!(diff_results(&[], &broken, &old).0, Some(2));
+}
@@ -0,0 +1,213 @@
+// The C programs of tests/fixtures judged in the real sandbox, checking
+// the verdict, roughly how long the run took and that what the sandbox
+// should block was blocked. Skipped where the fixtures can't be built.
+// Without the seccomp feature there is nothing to test them in.
+#![cfg(feature = "seccomp")]
+
+mod support;
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use secure_judger::judger::{IoMode, JudgeResult, JudgeSession, JudgeSessionBuilder, JudgeStatus, KeepPolicy, RuntimeErrorKind, TerminationPolicy};
+use secure_judger::sandbox::{InputSource, SandboxPolicy};
+
+const MIB: u64 = 1 << 20;
+
+/*
+ *  Judge the fixture `name` against `answer` with a 500ms CPU limit and
+ *  64MiB of memory, after `configure` had its say. None if skipped.
+ */
+fn judge(name: &str, answer: &str, configure: impl FnOnce(JudgeSessionBuilder) -> JudgeSessionBuilder) -> Option<JudgeResult> {
+    let exec = support::fixture(name)?;
+    let answer = support::write_file(&format!("{name}.ans"), answer.as_bytes());
+    let builder = JudgeSession::builder(exec)
+        .answer(answer)
+        .time_limit(Duration::from_millis(500))
+        .memory_limit(64 * MIB);
+    let session = configure(builder).build().unwrap();
+    Some(session.run_judge(&[]).unwrap())
+}
+
+#[test]
+fn hello_is_accepted() {
+    let Some(result) = judge("hello", "hello\n", |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert!(result.time_used < Duration::from_millis(500), "took {:?}", result.time_used);
+    assert!(result.sandbox.unwrap().seccomp);
+}
+
+#[test]
+fn executable_is_prewarmed() {
+    let Some(result) = judge("hello", "hello\n", |b| b.copy_exec(false).prewarm(true)) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert!(result.prewarmed);
+    let Some(result) = judge("hello", "hello\n", |b| b) else { return };
+    assert!(!result.prewarmed);
+}
+
+#[test]
+fn wrong_output() {
+    let Some(result) = judge("hello", "goodbye\n", |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::WrongAnswer), "{}", result.status);
+}
+
+#[test]
+fn infinite_loop_exceeds_time() {
+    let Some(result) = judge("infinite_loop", "", |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::TimeLimitExceeded), "{}", result.status);
+    // Stopped soon after the limit, and well before the wall limit of 1.5s
+    assert!(result.cpu_time_ms >= 500, "used {}ms", result.cpu_time_ms);
+    assert!(result.time_used < Duration::from_millis(1200), "took {:?}", result.time_used);
+}
+
+#[test]
+fn grace_period_is_not_billed() {
+    // The signal asking it to exit is one it ignores, so it spins on
+    // through the grace period until it is killed
+    let termination = TerminationPolicy { term_signal: Some(libc::SIGURG), grace_period: Duration::from_secs(1), ..TerminationPolicy::default() };
+    let Some(result) = judge("infinite_loop", "", |b| b.wall_time_limit(Duration::from_secs(5)).termination(termination)) else { return };
+    assert!(matches!(result.status, JudgeStatus::TimeLimitExceeded), "{}", result.status);
+    assert!(result.cpu_time_ms >= 1200, "used {}ms", result.cpu_time_ms);
+    assert!(result.time_used < Duration::from_millis(1000), "took {:?}", result.time_used);
+}
+
+#[test]
+fn big_alloc_exceeds_memory() {
+    let Some(result) = judge("big_alloc", "1\n", |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::MemoryLimitExceeded), "{}", result.status);
+}
+
+#[test]
+fn fork_is_blocked() {
+    let Some(result) = judge("fork_attempt", "blocked\n", |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "fork was not blocked: {}", result.status);
+}
+
+#[test]
+fn write_to_etc_is_blocked() {
+    let Some(result) = judge("write_etc", "blocked\n", |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "the write was not blocked: {}", result.status);
+    assert!(!Path::new("/etc/secure-judger-e2e").exists());
+}
+
+#[test]
+fn segfault_is_a_runtime_error() {
+    let Some(result) = judge("segfault", "", |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::RuntimeError(RuntimeErrorKind::SegmentationFault)), "{}", result.status);
+}
+
+#[test]
+fn divide_by_zero_is_a_runtime_error() {
+    let Some(result) = judge("divide_by_zero", "", |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::RuntimeError(RuntimeErrorKind::FloatingPointError)), "{}", result.status);
+}
+
+#[test]
+fn huge_output_exceeds_output_limit() {
+    let Some(result) = judge("huge_output", "", |b| b.output_limit(MIB)) else { return };
+    assert!(matches!(result.status, JudgeStatus::OutputLimitExceeded), "{}", result.status);
+}
+
+#[test]
+fn gigabyte_output_exceeds_io_limit() {
+    let Some(result) = judge("gigabyte_output", "", |b| b.io_limit(10 * MIB)) else { return };
+    assert!(matches!(result.status, JudgeStatus::OutputLimitExceeded), "{}", result.status);
+    assert!(result.io_limit_exceeded);
+    // Stopped at the limit, long before writing it all
+    assert!(result.io_written_bytes <= 11 * MIB, "{} bytes written", result.io_written_bytes);
+}
+
+#[test]
+fn copying_is_counted_as_disk_io() {
+    let input = vec![b'1'; 4 * MIB as usize];
+    let answer = String::from_utf8(input.clone()).unwrap();
+    let Some(result) = judge("copy_input", &answer, |b| b.input(InputSource::Bytes(input))) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert!(result.io_written_bytes > 0);
+    assert!(!result.io_limit_exceeded);
+}
+
+#[test]
+fn stderr_flood_is_cut_at_the_stderr_limit() {
+    let Some(result) = judge("stderr_flood", "", |b| {
+        b.time_limit(Duration::from_secs(2)).output_limit(MIB).keep_output(KeepPolicy::Always)
+    }) else { return };
+    // Neither the stderr limit nor the output limit changes the verdict
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert_eq!(result.stderr_bytes, 100 * MIB);
+    assert!(result.stderr_truncated);
+    let captured = fs::metadata(result.kept_dir.unwrap().join("stderr")).unwrap().len();
+    assert_eq!(captured, MIB);
+    assert_eq!(result.stderr.len(), 64 * 1024);
+}
+
+#[test]
+fn huge_output_without_limit_is_judged() {
+    let Some(result) = judge("huge_output", "", |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::WrongAnswer), "{}", result.status);
+}
+
+#[test]
+fn whole_input_is_read() {
+    let input = vec![b'1'; 3 * MIB as usize];
+    let answer = format!("{}\n", input.len());
+    let Some(result) = judge("read_stdin", &answer, |b| b.input(InputSource::Bytes(input))) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+}
+
+#[test]
+fn no_input_reads_as_end_of_file() {
+    let Some(result) = judge("read_stdin", "0\n", |b| b) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    let Some(result) = judge("read_stdin", "0\n", |b| b.input(InputSource::Null)) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+}
+
+#[test]
+fn threads_are_counted() {
+    let Some(result) = judge("spawn_threads", "", |b| b.policy(SandboxPolicy::threads_allowed())) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert!(result.max_threads >= Some(8), "{:?}", result.max_threads);
+    // Not counted where the program can't start any
+    let Some(result) = judge("hello", "hello\n", |b| b) else { return };
+    assert_eq!(result.max_threads, None);
+}
+
+#[test]
+fn provided_files_are_read_only() {
+    let dict = support::write_file("dict.txt", b"apple\nbanana\n");
+    let answer = "apple\nbanana\nwrite blocked\nchmod blocked\ncreate blocked\n";
+    let Some(result) = judge("read_provided", answer, |b| b.provide_file(dict, String::from("dict.txt"))) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    // And the same without a private /tmp
+    let dict = support::write_file("dict.txt", b"apple\nbanana\n");
+    let Some(result) = judge("read_provided", answer, |b| {
+        b.provide_file(dict, String::from("dict.txt")).policy(SandboxPolicy { private_tmp: false, ..SandboxPolicy::default() })
+    }) else { return };
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+}
+
+#[test]
+fn provided_file_names() {
+    let Some(exec) = support::fixture("read_provided") else { return };
+    let dict = support::write_file("names-dict.txt", b"");
+    let error = |name: &str, io_mode: IoMode| {
+        let builder = JudgeSession::builder(exec.clone())
+            .io_mode(io_mode)
+            .provide_file(dict.clone(), String::from("dict.txt"))
+            .provide_file(dict.clone(), name.to_string());
+        builder.build().err().map(|e| e.to_string())
+    };
+    let named = IoMode::NamedFiles { input_name: String::from("in.txt"), output_name: String::from("out.txt") };
+    let taken = |name: &str| format!("provided file {name:?}: the working directory has a file of that name already");
+    assert_eq!(error("dict.txt", IoMode::Standard), Some(taken("dict.txt")));
+    assert_eq!(error("out.txt", named.clone()), Some(taken("out.txt")));
+    for name in ["../dict.txt", "/etc/passwd", "..", ""] {
+        assert_eq!(error(name, IoMode::Standard), Some(format!("provided file {name:?}: not a file name in the working directory")));
+    }
+    assert_eq!(error("words.txt", named), None);
+    let missing = JudgeSession::builder(exec).provide_file(dict.with_extension("missing"), String::from("dict.txt")).build();
+    assert!(missing.err().unwrap().to_string().starts_with("provided file "));
+}
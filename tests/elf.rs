@@ -0,0 +1,189 @@
+// Reading ELF headers made up byte by byte, and sessions refusing what
+// they say can't run here before starting anything.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use secure_judger::judger::JudgeSession;
+use secure_judger::sandbox::{ExecArch, SandboxPolicy};
+use secure_judger::utils::elf::{self, ElfInfo};
+
+const EM_386: u16 = 3;
+const EM_ARM: u16 = 40;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+const ET_REL: u16 = 1;
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+
+/// The headers of a made up ELF file
+struct Header {
+    bits: u8,
+    big_endian: bool,
+    kind: u16,
+    machine: u16,
+    // PT_INTERP, after a PT_LOAD header
+    interpreter: Option<&'static str>
+}
+
+impl Header {
+    fn new(bits: u8, machine: u16) -> Self {
+        Header { bits, big_endian: false, kind: ET_EXEC, machine, interpreter: None }
+    }
+
+    fn put(&self, bytes: &mut [u8], at: usize, value: u64, size: usize) {
+        let value = match self.big_endian {
+            true => value.to_be_bytes()[8 - size..].to_vec(),
+            false => value.to_le_bytes()[..size].to_vec()
+        };
+        bytes[at..at + size].copy_from_slice(&value);
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let (header_size, phdr_size) = match self.bits {
+            32 => (52, 32),
+            _ => (64, 56)
+        };
+        let phnum = 1 + usize::from(self.interpreter.is_some());
+        let mut bytes = vec![0u8; header_size + phnum * phdr_size];
+        bytes[..4].copy_from_slice(b"\x7fELF");
+        bytes[4] = self.bits / 32;
+        bytes[5] = if self.big_endian { 2 } else { 1 };
+        bytes[6] = 1;
+        self.put(&mut bytes, 16, u64::from(self.kind), 2);
+        self.put(&mut bytes, 18, u64::from(self.machine), 2);
+        let (phoff, phentsize, phnum_at) = match self.bits {
+            32 => (28, 42, 44),
+            _ => (32, 54, 56)
+        };
+        let word = usize::from(self.bits / 8);
+        self.put(&mut bytes, phoff, header_size as u64, word);
+        self.put(&mut bytes, phentsize, phdr_size as u64, 2);
+        self.put(&mut bytes, phnum_at, phnum as u64, 2);
+        // PT_LOAD
+        self.put(&mut bytes, header_size, 1, 4);
+        if let Some(interpreter) = self.interpreter {
+            let phdr = header_size + phdr_size;
+            let (offset_at, size_at) = match self.bits {
+                32 => (4, 16),
+                _ => (8, 32)
+            };
+            self.put(&mut bytes, phdr, 3, 4);
+            let offset = bytes.len() as u64;
+            self.put(&mut bytes, phdr + offset_at, offset, word);
+            self.put(&mut bytes, phdr + size_at, interpreter.len() as u64 + 1, word);
+            bytes.extend(interpreter.as_bytes());
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    fn parse(&self) -> ElfInfo {
+        elf::parse(&mut Cursor::new(self.bytes())).unwrap().expect("an ELF file")
+    }
+}
+
+#[test]
+fn static_executables() {
+    let info = Header::new(64, EM_X86_64).parse();
+    assert_eq!(info, ElfInfo { bits: 64, machine: EM_X86_64, arch: Some(ExecArch::X86_64), executable: true, interpreter: None });
+    assert!(!info.is_dynamic());
+    assert_eq!(Header::new(32, EM_386).parse().arch, Some(ExecArch::I386));
+    assert_eq!(Header::new(64, EM_AARCH64).parse().arch, Some(ExecArch::Aarch64));
+}
+
+#[test]
+fn dynamic_executables() {
+    let header = Header { kind: ET_DYN, interpreter: Some("/lib64/ld-linux-x86-64.so.2"), ..Header::new(64, EM_X86_64) };
+    let info = header.parse();
+    assert!(info.executable && info.is_dynamic());
+    assert_eq!(info.interpreter, Some(PathBuf::from("/lib64/ld-linux-x86-64.so.2")));
+    let header = Header { interpreter: Some("/lib/ld-linux.so.2"), ..Header::new(32, EM_386) };
+    assert_eq!(header.parse().interpreter, Some(PathBuf::from("/lib/ld-linux.so.2")));
+    // Byte order is the file's own
+    let header = Header { big_endian: true, interpreter: Some("/lib/ld.so.1"), ..Header::new(32, 8) };
+    let info = header.parse();
+    assert_eq!((info.arch, info.machine, info.interpreter), (None, 8, Some(PathBuf::from("/lib/ld.so.1"))));
+}
+
+#[test]
+fn unknown_machines() {
+    let info = Header::new(32, EM_ARM).parse();
+    assert_eq!(info.arch, None);
+    assert_eq!(info.arch_name(), "32-bit ELF machine 40");
+    // x32 is a 32-bit class on the x86_64 machine
+    assert_eq!(Header::new(32, EM_X86_64).parse().arch, None);
+    assert!(!Header { kind: ET_REL, ..Header::new(64, EM_X86_64) }.parse().executable);
+}
+
+#[test]
+fn not_elf() {
+    for bytes in [&b""[..], b"\x7fEL", b"#!/bin/sh\n", b"MZ\x90\x00\x03\x00\x00\x00"] {
+        assert_eq!(elf::parse(&mut Cursor::new(bytes)).unwrap(), None, "{bytes:?}");
+    }
+}
+
+#[test]
+fn broken_headers() {
+    let error = |bytes: Vec<u8>| elf::parse(&mut Cursor::new(bytes)).unwrap_err().to_string();
+    let whole = Header { interpreter: Some("/lib/ld.so"), ..Header::new(64, EM_X86_64) }.bytes();
+    assert_eq!(error(whole[..40].to_vec()), "ELF header cut short");
+    assert_eq!(error(whole[..100].to_vec()), "ELF program headers cut short");
+    assert_eq!(error(whole[..whole.len() - 4].to_vec()), "ELF interpreter cut short");
+    let mut bytes = whole.clone();
+    bytes[4] = 3;
+    assert_eq!(error(bytes), "unknown ELF class");
+    let mut bytes = whole.clone();
+    bytes[5] = 0;
+    assert_eq!(error(bytes), "unknown ELF byte order");
+    let mut bytes = whole;
+    bytes[54] = 8;
+    assert_eq!(error(bytes), "ELF program headers too small");
+}
+
+fn write(name: &str, bytes: &[u8]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("elf-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(name), bytes).unwrap();
+    dir.join(name)
+}
+
+fn build_error(exec: PathBuf) -> String {
+    match JudgeSession::builder(exec).build() {
+        Ok(_) => panic!("the session was built"),
+        Err(e) => e.to_string()
+    }
+}
+
+#[test]
+fn foreign_executables_are_refused() {
+    let Some(native) = ExecArch::native() else { return };
+    let (foreign, name) = match native {
+        ExecArch::Aarch64 => (EM_X86_64, "x86_64"),
+        _ => (EM_AARCH64, "aarch64")
+    };
+    let exec = write("foreign", &Header::new(64, foreign).bytes());
+    assert_eq!(build_error(exec.clone()), format!("executable {}: built for {name}, not {native}", exec.display()));
+    let exec = write("arm32", &Header::new(32, EM_ARM).bytes());
+    assert_eq!(build_error(exec.clone()), format!("executable {}: built for 32-bit ELF machine 40, not {native}", exec.display()));
+    // What the policy says the program is wins
+    let exec = write("native", &Header::new(64, EM_X86_64).bytes());
+    let policy = SandboxPolicy { exec_arch: Some(ExecArch::I386), ..SandboxPolicy::default() };
+    let error = match JudgeSession::builder(exec.clone()).policy(policy).build() {
+        Ok(_) => panic!("the session was built"),
+        Err(e) => e.to_string()
+    };
+    assert_eq!(error, format!("executable {}: built for x86_64, not i386", exec.display()));
+}
+
+#[test]
+fn other_formats_are_refused() {
+    let object = write("object.o", &Header { kind: ET_REL, ..Header::new(64, EM_X86_64) }.bytes());
+    let windows = write("program.exe", b"MZ\x90\x00\x03\x00\x00\x00\x04\x00\x00\x00\xff\xff\x00\x00");
+    for exec in [object, windows] {
+        assert_eq!(build_error(exec.clone()), format!("executable {}: neither an ELF executable nor a script", exec.display()));
+    }
+    let cut = write("cut", &Header::new(64, EM_X86_64).bytes()[..30]);
+    assert_eq!(build_error(cut.clone()), format!("executable {}: ELF header cut short", cut.display()));
+}
@@ -0,0 +1,65 @@
+// The environment programs run in: TZ=UTC, LANG=C and LC_ALL=C whatever
+// the judger's are, a umask of 022 whatever the judger's is, and the
+// variables a session sets over them. Runs the fixture in the real
+// sandbox, skipped where it can't be built.
+#![cfg(feature = "seccomp")]
+
+mod support;
+
+use std::process::Command;
+use std::time::Duration;
+
+use secure_judger::judger::{JudgeSession, JudgeStatus};
+
+const IN_UTC: &[u8] = b"2001-09-09 01:46:40 UTC\n1234.50\numask 022\n";
+
+/*
+ *  The judger's report on judging local_time against `answer`, run with
+ *  the host's time zone, locale and umask as given
+ */
+fn judge_on_host(tz: &str, locale: &str, umask: &str, answer: &[u8]) -> Option<String> {
+    let exec = support::fixture("local_time")?;
+    let answer = support::write_file("local_time.ans", answer);
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!("umask {umask} && exec \"$0\" \"$@\""))
+        .arg(env!("CARGO_BIN_EXE_secure-judger"))
+        .args(["judge", "/dev/null"])
+        .arg(answer)
+        .arg(exec)
+        .env("TZ", tz)
+        .env("LANG", locale)
+        .env("LC_ALL", locale)
+        .output()
+        .unwrap();
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[test]
+fn host_settings_dont_show() {
+    let Some(first) = judge_on_host("EST5", "de_DE.UTF-8", "077", IN_UTC) else { return };
+    let Some(second) = judge_on_host("JST-9", "fr_FR.UTF-8", "002", IN_UTC) else { return };
+    assert!(first.contains("Accepted"), "{first}");
+    assert!(second.contains("Accepted"), "{second}");
+}
+
+#[test]
+fn set_variables_win() {
+    let Some(exec) = support::fixture("local_time") else { return };
+    let answer = support::write_file("local_time_jst.ans", b"2001-09-09 10:46:40 JST\n1234.50\numask 022\n");
+    let session = JudgeSession::builder(exec)
+        .answer(answer)
+        .time_limit(Duration::from_millis(500))
+        .env(String::from("TZ"), String::from("EST5"))
+        .env(String::from("TZ"), String::from("JST-9"))
+        .build()
+        .unwrap();
+    let result = session.run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+}
+
+#[test]
+fn tmpdir_is_not_set() {
+    let error = JudgeSession::builder("/bin/true".into()).env(String::from("TMPDIR"), String::from("/var/tmp")).build().err().unwrap();
+    assert!(error.to_string().contains("TMPDIR can't be set"), "{error}");
+}
@@ -0,0 +1,82 @@
+// The failure policy of a batch: how many tests may fail before the rest
+// are skipped, one after another and in parallel, where the tests still
+// running once the batch stops are cancelled. Judged on the mock sandbox.
+
+mod support;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use secure_judger::judger::{JudgeSession, JudgeStatus};
+use secure_judger::problem::{FailurePolicy, ProblemJudge, SubmissionResult, TestCase};
+use secure_judger::sandbox::{MockRun, MockSandbox};
+
+fn judge(cases: &[TestCase], sandbox: Arc<MockSandbox>, policy: FailurePolicy) -> ProblemJudge {
+    let exec = support::mock_program(&cases[0].input.with_file_name("program"));
+    let session = JudgeSession::builder(exec)
+        .answer(cases[0].answer.clone())
+        .time_limit(Duration::from_secs(30))
+        .copy_exec(false)
+        .sandbox(sandbox)
+        .build()
+        .unwrap();
+    ProblemJudge::new(session, cases.to_vec()).with_failure_policy(policy)
+}
+
+fn statuses(submission: &SubmissionResult) -> Vec<&'static str> {
+    submission.results.iter().map(|result| result.status.abbr()).collect()
+}
+
+#[test]
+fn policy_names() {
+    assert_eq!(FailurePolicy::from_name("run-all"), Some(FailurePolicy::RunAll));
+    assert_eq!(FailurePolicy::from_name("stop-on-first"), Some(FailurePolicy::StopOnFirst));
+    assert_eq!(FailurePolicy::from_name("stop-after:3"), Some(FailurePolicy::StopAfter(3)));
+    assert_eq!(FailurePolicy::from_name("stop-after:0"), None);
+    assert_eq!(FailurePolicy::from_name("stop-after"), None);
+    assert_eq!(FailurePolicy::StopAfter(3).to_string(), "stop-after:3");
+}
+
+#[test]
+fn stops_after_the_failures_allowed() {
+    let dir = support::dir("sequential");
+    let cases = support::cases(&dir, 4);
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("4\n")));
+    let submission = judge(&cases, sandbox.clone(), FailurePolicy::StopAfter(2)).run(&[]).unwrap();
+    assert_eq!(sandbox.spawned(), 2);
+    // Padded with the skipped tests, as long as there are tests
+    assert_eq!(statuses(&submission), ["WA", "WA", "SKIP", "SKIP"]);
+
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("4\n")));
+    let submission = judge(&cases, sandbox.clone(), FailurePolicy::RunAll).run(&[]).unwrap();
+    assert_eq!(sandbox.spawned(), 4);
+    assert_eq!(statuses(&submission), ["WA"; 4]);
+}
+
+#[test]
+fn running_tests_are_cancelled() {
+    let dir = support::dir("parallel");
+    let mut cases = support::cases(&dir, 4);
+    // Only the first test is given up on soon, the others would run for long
+    cases[0].time_limit = Some(Duration::from_millis(100));
+    let start = Instant::now();
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::hang()));
+    let submission = judge(&cases, sandbox.clone(), FailurePolicy::StopOnFirst).run_parallel(2, &[]).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(10), "took {:?}", start.elapsed());
+    assert_eq!(sandbox.spawned(), 2);
+    assert_eq!(statuses(&submission), ["ILE", "SKIP", "SKIP", "SKIP"]);
+    assert!(matches!(submission.status, JudgeStatus::IdlenessLimitExceeded), "{}", submission.status);
+}
+
+#[test]
+fn stops_after_the_failures_allowed_in_parallel() {
+    let dir = support::dir("parallel-after");
+    let mut cases = support::cases(&dir, 4);
+    cases[0].time_limit = Some(Duration::from_millis(100));
+    cases[1].time_limit = Some(Duration::from_millis(100));
+    let start = Instant::now();
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::hang()));
+    let submission = judge(&cases, sandbox, FailurePolicy::StopAfter(2)).run_parallel(3, &[]).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(10), "took {:?}", start.elapsed());
+    assert_eq!(statuses(&submission), ["ILE", "ILE", "SKIP", "SKIP"]);
+}
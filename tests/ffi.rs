@@ -0,0 +1,98 @@
+// The C API: tests/ffi/smoke.c built against include/secure_judger.h and
+// the cdylib, and run. Skipped without a C compiler.
+//
+//     cargo test --features ffi --test ffi
+#![cfg(feature = "ffi")]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/*
+ *  The cdylib built with the ffi feature, in a target directory of its
+ *  own. The one next to the test binary can't be used: cdylibs have no
+ *  hash in their name, so every build of the library with other features,
+ *  such as the binary's, overwrites it.
+ */
+fn library_dir() -> PathBuf {
+    let target = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi-target");
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let built = Command::new(cargo)
+        .args(["build", "--offline", "--lib", "--no-default-features", "--features", "ffi,seccomp", "--target-dir"])
+        .arg(&target)
+        .current_dir(manifest_dir())
+        .output()
+        .unwrap();
+    assert!(built.status.success(), "building the cdylib failed:\n{}", String::from_utf8_lossy(&built.stderr));
+    target.join("debug")
+}
+
+#[test]
+fn c_smoke_test() {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("ffi-{}", std::process::id()));
+    fs::create_dir_all(&scratch).unwrap();
+    let exec = scratch.join("smoke");
+    let lib = library_dir();
+    let cc = env::var_os("CC").unwrap_or_else(|| "cc".into());
+    let compiled = Command::new(&cc)
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir().join("include"))
+        .arg("-o")
+        .arg(&exec)
+        .arg(manifest_dir().join("tests/ffi/smoke.c"))
+        .arg("-L")
+        .arg(&lib)
+        .arg(format!("-Wl,-rpath,{}", lib.display()))
+        .arg("-lsecure_judger")
+        .output();
+    let compiled = match compiled {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("skipping: no C compiler ({} failed: {e}), set CC to use another", cc.to_string_lossy());
+            return;
+        }
+    };
+    assert!(compiled.status.success(), "compiling smoke.c failed:\n{}", String::from_utf8_lossy(&compiled.stderr));
+
+    let empty = scratch.join("empty.ans");
+    let other = scratch.join("other.ans");
+    fs::write(&empty, "").unwrap();
+    fs::write(&other, "42\n").unwrap();
+    // cargo points LD_LIBRARY_PATH at its deps directory, which would win
+    // over the rpath
+    let run = Command::new(&exec).arg(&empty).arg(&other).env_remove("LD_LIBRARY_PATH").output().unwrap();
+    assert!(
+        run.status.success(),
+        "smoke.c failed:\n{}{}",
+        String::from_utf8_lossy(&run.stdout),
+        String::from_utf8_lossy(&run.stderr)
+    );
+}
+
+#[test]
+fn header_declares_every_function() {
+    let source = fs::read_to_string(manifest_dir().join("src/ffi.rs")).unwrap();
+    let header = fs::read_to_string(manifest_dir().join("include/secure_judger.h")).unwrap();
+    let exported = source
+        .lines()
+        .filter_map(|line| line.strip_prefix("pub unsafe extern \"C\" fn "))
+        .map(|rest| &rest[..rest.find('(').unwrap()]);
+    let mut count = 0;
+    for name in exported {
+        let declared = [" ", "*"].iter().any(|before| header.contains(&format!("{before}{name}(")));
+        assert!(declared, "{name} isn't declared in the header, rerun cbindgen");
+        count += 1;
+    }
+    assert!(count > 0, "no exported functions found in src/ffi.rs");
+    let constants = source.lines().filter_map(|line| line.strip_prefix("pub const ")).map(|rest| &rest[..rest.find(':').unwrap()]);
+    for name in constants {
+        assert!(header.contains(&format!("#define {name} ")), "{name} isn't defined in the header, rerun cbindgen");
+    }
+}
@@ -0,0 +1,80 @@
+/*
+ *  Judges /bin/true through the C API against the answers given as its
+ *  arguments, an empty one and one it doesn't write, and checks the
+ *  errors of bad calls. Prints what failed and exits non-zero.
+ */
+#include <stdio.h>
+#include <string.h>
+
+#include "secure_judger.h"
+
+static int failures = 0;
+
+#define CHECK(cond) do { \
+    if (!(cond)) { \
+        size_t len = 0; \
+        const char *error = sj_last_error(&len); \
+        fprintf(stderr, "line %d: %s failed, last error: %.*s\n", __LINE__, #cond, (int)len, error); \
+        failures++; \
+    } \
+} while (0)
+
+#define STR(s) s, strlen(s)
+
+static sj_result *judge(sj_session *session, const char *answer) {
+    sj_result *result = NULL;
+    CHECK(sj_session_set_answer(session, STR(answer)) == SJ_OK);
+    CHECK(sj_run(session, &result) == SJ_OK);
+    return result;
+}
+
+int main(int argc, char **argv) {
+    if (argc != 3) {
+        fprintf(stderr, "usage: %s EMPTY_ANSWER OTHER_ANSWER\n", argv[0]);
+        return 2;
+    }
+    sj_session *session = NULL;
+    CHECK(sj_session_new(STR("/bin/true"), &session) == SJ_OK);
+    CHECK(sj_session_set_time_limit_ms(session, 1000) == SJ_OK);
+    CHECK(sj_session_set_memory_limit(session, 64 << 20) == SJ_OK);
+    CHECK(sj_session_set_mode(session, STR("tokens")) == SJ_OK);
+    /* Wherever the tests run, seccomp or not */
+    CHECK(sj_session_set_sandbox(session, STR("best-effort")) == SJ_OK);
+
+    sj_result *accepted = judge(session, argv[1]);
+    CHECK(sj_result_verdict(accepted) == SJ_VERDICT_AC);
+    size_t len = 0;
+    const char *detail = sj_result_detail(accepted, &len);
+    CHECK(detail != NULL && len == strlen("[AC] Accepted") && memcmp(detail, "[AC] Accepted", len) == 0);
+    CHECK(sj_result_time_ms(accepted) < 1000);
+    sj_result_free(accepted);
+
+    sj_result *wrong = judge(session, argv[2]);
+    CHECK(sj_result_verdict(wrong) == SJ_VERDICT_WA);
+    sj_result_free(wrong);
+
+    /* Bad calls are refused with the code saying why */
+    CHECK(sj_session_set_mode(session, STR("fuzzy")) == SJ_ERR_INVALID);
+    CHECK(strstr(sj_last_error(NULL), "fuzzy") != NULL);
+    CHECK(sj_session_set_sandbox(session, STR("strict")) == SJ_ERR_INVALID);
+    CHECK(sj_session_set_answer(session, "\xff\xfe", 2) == SJ_ERR_UTF8);
+    CHECK(sj_session_set_time_limit_ms(NULL, 1000) == SJ_ERR_NULL);
+    CHECK(sj_session_new(NULL, 0, &session) == SJ_ERR_NULL);
+    CHECK(sj_run(session, NULL) == SJ_ERR_NULL);
+    CHECK(sj_result_verdict(NULL) == SJ_ERR_NULL);
+    CHECK(sj_result_detail(NULL, &len) == NULL);
+
+    /* A session that doesn't check out is refused when run */
+    sj_result *result = NULL;
+    CHECK(sj_session_set_time_limit_ms(session, 0) == SJ_OK);
+    CHECK(sj_run(session, &result) == SJ_ERR_INVALID);
+    CHECK(result == NULL);
+
+    sj_session_free(session);
+    sj_session_free(NULL);
+    sj_result_free(NULL);
+    if (failures == 0) {
+        puts("ok");
+    }
+    return failures == 0 ? 0 : 1;
+}
@@ -0,0 +1,18 @@
+/* Allocates and touches 1GiB, far over any limit the tests set */
+#include <stdio.h>
+#include <stdlib.h>
+
+int main(void) {
+    size_t size = (size_t)1 << 30;
+    /* volatile so that the compiler can't leave out the writes */
+    volatile char *block = malloc(size);
+    if (block == NULL) {
+        puts("no memory");
+        return 1;
+    }
+    for (size_t i = 0; i < size; i += 4096) {
+        block[i] = 1;
+    }
+    printf("%d\n", block[size - 4096]);
+    return 0;
+}
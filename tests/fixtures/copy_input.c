@@ -0,0 +1,13 @@
+/* Copies its input to its output, which is a file on disk */
+#include <unistd.h>
+
+int main(void) {
+    static char buffer[65536];
+    ssize_t n;
+    while ((n = read(0, buffer, sizeof buffer)) > 0) {
+        if (write(1, buffer, n) != n) {
+            return 1;
+        }
+    }
+    return n < 0;
+}
@@ -0,0 +1,12 @@
+/* Divides by zero. Integer division doesn't trap on every architecture,
+ * so the signal is raised by hand where it didn't. */
+#include <signal.h>
+#include <stdio.h>
+
+int main(void) {
+    volatile int zero = 0;
+    volatile int quotient = 1 / zero;
+    raise(SIGFPE);
+    printf("%d\n", quotient);
+    return 0;
+}
@@ -0,0 +1,6 @@
+#!/usr/bin/python3
+# Prints every number of its input doubled, one a line
+import sys
+
+for line in sys.stdin:
+    print(int(line) * 2)
@@ -0,0 +1,6 @@
+#!/bin/sh
+# Prints every number of its input doubled, one a line, with builtins
+# only as the sandbox lets nothing fork
+while read -r n; do
+    echo $((n * 2))
+done
@@ -0,0 +1,12 @@
+/* Tries to start a second process, printing whether it could */
+#include <stdio.h>
+#include <unistd.h>
+
+int main(void) {
+    pid_t pid = fork();
+    if (pid == 0) {
+        _exit(0);
+    }
+    puts(pid < 0 ? "blocked" : "allowed");
+    return 0;
+}
@@ -0,0 +1,7 @@
+/* Prints a greeting and exits, the run everything else is measured against */
+#include <stdio.h>
+
+int main(void) {
+    puts("hello");
+    return 0;
+}
@@ -0,0 +1,15 @@
+/* Writes 64MiB of output */
+#include <string.h>
+#include <unistd.h>
+
+int main(void) {
+    static char line[4096];
+    memset(line, 'x', sizeof line - 1);
+    line[sizeof line - 1] = '\n';
+    for (int i = 0; i < 16384; i++) {
+        if (write(1, line, sizeof line) < 0) {
+            return 1;
+        }
+    }
+    return 0;
+}
@@ -0,0 +1,7 @@
+/* Spins on the CPU until it is killed */
+int main(void) {
+    volatile unsigned long n = 0;
+    for (;;) {
+        n++;
+    }
+}
@@ -0,0 +1,17 @@
+/* Prints a fixed moment in local time, a number formatted for the locale
+ * of the environment, and the umask it runs with */
+#include <locale.h>
+#include <stdio.h>
+#include <sys/stat.h>
+#include <time.h>
+
+int main(void) {
+    setlocale(LC_ALL, "");
+    time_t moment = 1000000000;
+    char text[64];
+    strftime(text, sizeof text, "%Y-%m-%d %H:%M:%S %Z", localtime(&moment));
+    puts(text);
+    printf("%.2f\n", 1234.5);
+    printf("umask %03o\n", (unsigned)umask(0));
+    return 0;
+}
@@ -0,0 +1,11 @@
+/* Leaves a symlink to its argument where a compiler leaves its artifact */
+#include <stdio.h>
+#include <unistd.h>
+
+int main(int argc, char **argv) {
+    if (argc < 2 || symlink(argv[1], "a.out") != 0) {
+        perror("symlink");
+        return 1;
+    }
+    return 0;
+}
@@ -0,0 +1,21 @@
+/* Prints the provided file dict.txt, then whether it could change it or
+ * make a file beside it */
+#include <fcntl.h>
+#include <stdio.h>
+#include <sys/stat.h>
+
+int main(void) {
+    FILE *dict = fopen("dict.txt", "r");
+    if (!dict) {
+        puts("missing");
+        return 1;
+    }
+    int c;
+    while ((c = getc(dict)) != EOF) {
+        putchar(c);
+    }
+    puts(open("dict.txt", O_WRONLY) < 0 ? "write blocked" : "write allowed");
+    puts(chmod("dict.txt", 0644) < 0 ? "chmod blocked" : "chmod allowed");
+    puts(open("new.txt", O_WRONLY | O_CREAT, 0644) < 0 ? "create blocked" : "create allowed");
+    return 0;
+}
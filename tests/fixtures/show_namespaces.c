@@ -0,0 +1,22 @@
+/* Prints the net and uts namespaces it runs in, and whether its parent is
+ * outside of its pid namespace, which makes getppid() 0 */
+#include <stdio.h>
+#include <unistd.h>
+
+static void show(const char *path) {
+    char link[64];
+    ssize_t n = readlink(path, link, sizeof link - 1);
+    if (n < 0) {
+        perror(path);
+        return;
+    }
+    link[n] = '\0';
+    puts(link);
+}
+
+int main(void) {
+    show("/proc/self/ns/net");
+    show("/proc/self/ns/uts");
+    printf("ppid %d\n", (int)getppid());
+    return 0;
+}
@@ -0,0 +1,24 @@
+/* Starts 8 threads that sleep for a while, then waits for them */
+#include <pthread.h>
+#include <time.h>
+
+#define THREADS 8
+
+static void *nap(void *arg) {
+    struct timespec time = { 0, 300 * 1000 * 1000 };
+    nanosleep(&time, NULL);
+    return arg;
+}
+
+int main(void) {
+    pthread_t threads[THREADS];
+    for (int i = 0; i < THREADS; i++) {
+        if (pthread_create(&threads[i], NULL, nap, NULL) != 0) {
+            return 1;
+        }
+    }
+    for (int i = 0; i < THREADS; i++) {
+        pthread_join(threads[i], NULL);
+    }
+    return 0;
+}
@@ -0,0 +1,18 @@
+/* Writes 100MB to stderr and nothing to stdout */
+#include <string.h>
+#include <unistd.h>
+
+int main(void) {
+    static char block[1 << 20];
+    memset(block, 'e', sizeof block);
+    for (int i = 0; i < 100; i++) {
+        size_t done = 0;
+        while (done < sizeof block) {
+            ssize_t n = write(2, block + done, sizeof block - done);
+            if (n < 0)
+                return 1;
+            done += n;
+        }
+    }
+    return 0;
+}
@@ -0,0 +1,19 @@
+/* Waits for SIGTERM, then says so and exits. Given an argument it
+ * ignores SIGTERM and waits on, until it is killed. */
+#include <signal.h>
+#include <unistd.h>
+
+static void on_term(int signal) {
+    static const char message[] = "stopped\n";
+    (void)signal;
+    write(1, message, sizeof message - 1);
+    _exit(0);
+}
+
+int main(int argc, char **argv) {
+    (void)argv;
+    signal(SIGTERM, argc > 1 ? SIG_IGN : on_term);
+    for (;;) {
+        pause();
+    }
+}
@@ -0,0 +1,9 @@
+/* Tries to create a file under /etc, printing whether it could */
+#include <fcntl.h>
+#include <stdio.h>
+
+int main(void) {
+    int fd = open("/etc/secure-judger-e2e", O_WRONLY | O_CREAT, 0644);
+    puts(fd < 0 ? "blocked" : "allowed");
+    return 0;
+}
@@ -0,0 +1,118 @@
+// A short run of the fuzz targets' invariants on generated inputs, for
+// running them without cargo-fuzz and nightly:
+//
+//     cargo test --features fuzz --test fuzz
+//
+// FUZZ_ITERATIONS sets how many inputs each test tries, FUZZ_SEED where
+// the generator starts.
+#![cfg(feature = "fuzz")]
+
+use secure_judger::fuzz;
+
+// Pieces outputs are made of: numbers written several ways, words in
+// either case and every kind of whitespace
+const TOKENS: [&[u8]; 24] = [
+    b"1", b"1.0", b"-0", b"0", b"1e-7", b"1.0000001", b"-1e308", b"nan", b"inf", b"-inf", b"abc", b"ABC",
+    b"aBc", b"\xc3\xa9", b"\x00", b" ", b"  ", b"\n", b"\r\n", b"\t", b"\x0b", b"\x0c", b"", b"12345678901234567890"
+];
+
+// Pieces problem files and JSON texts are made of
+const CONFIG_PIECES: [&str; 40] = [
+    "[limits]", "[compare]", "[io]", "[[test]]", "[tests]", "[", "]", "[[", "]]", "time = \"1s\"", "memory = \"256m\"",
+    "time = ", "mode = \"float\"", "tolerance = 1e-9", "input = \"1.in\"", "answer = \"1.ans\"", "dir = \"tests\"",
+    "=", "\"", "'", "#", " ", "\n", "\r\n", "\t", "é", "\\u00e9", "\\", "{", "}", ":", ",", "\"a\"", "1e400", "-",
+    "\\u0000", "\\ud800", "true", "null", "0.5"
+];
+
+/*
+ *  xorshift64*, enough to spread the inputs around
+ */
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn setting(name: &str, default: u64) -> u64 {
+    std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+fn rng() -> Rng {
+    // Zero would keep xorshift at zero
+    Rng(setting("FUZZ_SEED", 0x5eed).max(1))
+}
+
+/*
+ *  An output of up to 16 pieces, or random bytes now and then
+ */
+fn output(rng: &mut Rng) -> Vec<u8> {
+    let pieces = rng.below(17);
+    (0..pieces)
+        .flat_map(|_| match rng.below(8) {
+            0 => vec![rng.next() as u8],
+            _ => TOKENS[rng.below(TOKENS.len())].to_vec()
+        })
+        .collect()
+}
+
+/*
+ *  `answer` with a few bytes changed, removed, duplicated or changed in
+ *  case, so that the output is mostly close to it
+ */
+fn mutate(rng: &mut Rng, answer: &[u8]) -> Vec<u8> {
+    let mut output = answer.to_vec();
+    for _ in 0..rng.below(4) {
+        let at = rng.below(output.len() + 1);
+        match rng.below(5) {
+            0 => output.insert(at, b" \n\t"[rng.below(3)]),
+            1 if at < output.len() => {
+                output.remove(at);
+            },
+            2 if at < output.len() => output[at] ^= 0x20,
+            3 => output.splice(at..at, TOKENS[rng.below(TOKENS.len())].iter().copied()).for_each(drop),
+            _ => {}
+        }
+    }
+    output
+}
+
+#[test]
+fn comparisons() {
+    let mut rng = rng();
+    for _ in 0..setting("FUZZ_ITERATIONS", 20_000) {
+        let answer = output(&mut rng);
+        let output = match rng.below(3) {
+            0 => output(&mut rng),
+            _ => mutate(&mut rng, &answer)
+        };
+        fuzz::comparisons(&answer, &output);
+    }
+}
+
+#[test]
+fn comparisons_of_split_input() {
+    let mut rng = rng();
+    for _ in 0..setting("FUZZ_ITERATIONS", 20_000) / 4 {
+        let data: Vec<u8> = (0..rng.below(64)).map(|_| rng.next() as u8).collect();
+        let (answer, output) = fuzz::split(&data);
+        fuzz::comparisons(answer, output);
+    }
+}
+
+#[test]
+fn config() {
+    let mut rng = rng();
+    for _ in 0..setting("FUZZ_ITERATIONS", 20_000) {
+        let text: String = (0..rng.below(24)).map(|_| CONFIG_PIECES[rng.below(CONFIG_PIECES.len())]).collect();
+        fuzz::config(text.as_bytes());
+    }
+}
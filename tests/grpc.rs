@@ -0,0 +1,270 @@
+// The gRPC service over loopback: a server judging on the mock sandbox
+// and the client of the grpc module calling it, for both methods, the
+// cancelling of calls and what requests are refused. Clients that would
+// tie up the server are written frame by frame.
+#![cfg(feature = "grpc")]
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use secure_judger::grpc::{Client, Code, JudgeEvent, JudgeRequest, Limits, Server, ServerConfig, Source};
+use secure_judger::judger::JudgeSession;
+use secure_judger::sandbox::{MockRun, MockSandbox};
+
+/// A server on a free port, stopped when dropped
+struct Running {
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.server.stop();
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
+
+impl Running {
+    fn client(&self) -> Client {
+        Client::connect(self.server.local_addr().unwrap()).unwrap()
+    }
+}
+
+/*
+ *  Serve with one worker on the runs of `sandbox`, with `config` otherwise
+ */
+fn serve(sandbox: Arc<MockSandbox>, config: ServerConfig) -> Running {
+    let server = Arc::new(Server::bind("127.0.0.1:0", ServerConfig { scratch_base: scratch(), ..config }).unwrap());
+    let serving = Arc::clone(&server);
+    let thread = thread::spawn(move || {
+        serving
+            .serve(|exec, policy| {
+                let builder = JudgeSession::builder(exec).policy(policy).time_limit(Duration::from_secs(1)).copy_exec(false);
+                builder.sandbox(Arc::clone(&sandbox) as _)
+            })
+            .unwrap();
+    });
+    Running { server, thread: Some(thread) }
+}
+
+fn scratch() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("grpc-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/*
+ *  A request with everything inline, judged against the answer "3"
+ */
+fn inline_request() -> JudgeRequest {
+    JudgeRequest {
+        exec: Some(Source::Inline(b"#!/bin/sh\n".to_vec())),
+        input: Some(Source::Inline(b"1 2\n".to_vec())),
+        answer: Some(Source::Inline(b"3\n".to_vec())),
+        ..Default::default()
+    }
+}
+
+/*
+ *  The bytes of an HTTP/2 frame
+ */
+fn frame(kind: u8, flags: u8, stream: u32, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+    bytes.extend_from_slice(&[kind, flags]);
+    bytes.extend_from_slice(&stream.to_be_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+/*
+ *  Read a frame off `stream`: its type, stream and payload
+ */
+fn read_frame(stream: &mut TcpStream) -> (u8, u32, Vec<u8>) {
+    let mut header = [0u8; 9];
+    stream.read_exact(&mut header).unwrap();
+    let mut payload = vec![0u8; u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize];
+    stream.read_exact(&mut payload).unwrap();
+    (header[3], u32::from_be_bytes(header[5..].try_into().unwrap()) & !(1 << 31), payload)
+}
+
+/*
+ *  A connection to `running` that sent the preface and its settings
+ */
+fn raw_connection(running: &Running) -> TcpStream {
+    let mut stream = TcpStream::connect(running.server.local_addr().unwrap()).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n").unwrap();
+    stream.write_all(&frame(4, 0, 0, &[])).unwrap();
+    stream
+}
+
+#[test]
+fn unary_verdicts() {
+    let sandbox = Arc::new(MockSandbox::new(vec![
+        MockRun::exit(0).stdout("3\n").cpu_time(Duration::from_millis(20)),
+        MockRun::exit(0).stdout("4\n")
+    ]));
+    let running = serve(sandbox, ServerConfig::default());
+    let mut client = running.client();
+    let accepted = client.judge(&inline_request()).unwrap();
+    assert_eq!(accepted.status, "AC", "{}", accepted.detail);
+    assert_eq!(accepted.cpu_time_ms, 20);
+    assert!(accepted.result_json.contains("\"cpu_time_ms\""), "{}", accepted.result_json);
+    // The same connection takes the next call
+    let wrong = client.judge(&inline_request()).unwrap();
+    assert_eq!(wrong.status, "WA", "{}", wrong.detail);
+}
+
+#[test]
+fn events_end_with_the_result() {
+    let sandbox = Arc::new(MockSandbox::new(vec![MockRun::exit(0).stdout("3\n").wall_time(Duration::from_millis(350))]));
+    let running = serve(sandbox, ServerConfig::default());
+    let mut client = running.client();
+    let events: Vec<JudgeEvent> = client.judge_events(&inline_request()).unwrap().map(Result::unwrap).collect();
+    assert_eq!(events.first(), Some(&JudgeEvent::Started));
+    assert!(events.iter().any(|event| matches!(event, JudgeEvent::Tick { .. })), "{events:?}");
+    match events.last() {
+        Some(JudgeEvent::Finished(response)) => assert_eq!(response.status, "AC", "{}", response.detail),
+        last => panic!("ended with {last:?}")
+    }
+}
+
+#[test]
+fn cancelling_frees_the_worker() {
+    let sandbox = Arc::new(MockSandbox::new(vec![MockRun::hang(), MockRun::exit(0).stdout("3\n")]));
+    let running = serve(Arc::clone(&sandbox), ServerConfig::default());
+    let mut client = running.client();
+    let request = JudgeRequest { limits: Limits { wall_time_limit_ms: 60_000, ..Default::default() }, ..inline_request() };
+    let mut events = client.judge_events(&request).unwrap();
+    assert_eq!(events.next().unwrap().unwrap(), JudgeEvent::Started);
+    // Once it ticks the program was started, cancelled before that the
+    // hanging run would be left for the next request
+    assert!(matches!(events.next().unwrap().unwrap(), JudgeEvent::Tick { .. }));
+    events.cancel();
+    // With the only worker stuck on the hanging run, this would wait out
+    // its minute
+    let start = Instant::now();
+    let response = client.judge(&inline_request()).unwrap();
+    assert_eq!(response.status, "AC", "{}", response.detail);
+    assert!(start.elapsed() < Duration::from_secs(10), "{:?}", start.elapsed());
+    assert_eq!(sandbox.spawned(), 2);
+}
+
+#[test]
+fn paths_need_a_root() {
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("3\n")));
+    let running = serve(sandbox, ServerConfig::default());
+    let request = JudgeRequest { answer: Some(Source::Path(PathBuf::from("1.ans"))), ..inline_request() };
+    let status = running.client().judge(&request).unwrap_err();
+    assert_eq!(status.code, Code::PermissionDenied, "{status}");
+}
+
+#[test]
+fn paths_under_the_root() {
+    let root = scratch().join("root");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("1.ans"), "3\n").unwrap();
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("3\n")));
+    let running = serve(sandbox, ServerConfig { root: Some(root), ..Default::default() });
+    let mut client = running.client();
+    let request = JudgeRequest { answer: Some(Source::Path(PathBuf::from("1.ans"))), ..inline_request() };
+    let response = client.judge(&request).unwrap();
+    assert_eq!(response.status, "AC", "{}", response.detail);
+    let outside = JudgeRequest { answer: Some(Source::Path(PathBuf::from("../../../etc/passwd"))), ..inline_request() };
+    assert_eq!(client.judge(&outside).unwrap_err().code, Code::PermissionDenied);
+}
+
+#[test]
+fn inline_files_are_capped() {
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("3\n")));
+    let running = serve(sandbox, ServerConfig { inline_limit: 1024, ..Default::default() });
+    let mut client = running.client();
+    let request = JudgeRequest { input: Some(Source::Inline(vec![b'1'; 2048])), ..inline_request() };
+    assert_eq!(client.judge(&request).unwrap_err().code, Code::ResourceExhausted);
+    // Past what any request can be, the body isn't even read in full
+    let request = JudgeRequest { input: Some(Source::Inline(vec![b'1'; 1 << 20])), ..inline_request() };
+    assert_eq!(client.judge(&request).unwrap_err().code, Code::ResourceExhausted);
+    assert_eq!(client.judge(&inline_request()).unwrap().status, "AC");
+}
+
+#[test]
+fn invalid_requests() {
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("3\n")));
+    let running = serve(sandbox, ServerConfig::default());
+    let mut client = running.client();
+    let missing = JudgeRequest { answer: None, ..inline_request() };
+    assert_eq!(client.judge(&missing).unwrap_err().code, Code::InvalidArgument);
+    let compare = JudgeRequest { compare: String::from("fuzzy"), ..inline_request() };
+    assert_eq!(client.judge(&compare).unwrap_err().code, Code::InvalidArgument);
+}
+
+#[test]
+fn requests_over_the_memory_budget_are_refused() {
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("3\n")));
+    let running = serve(sandbox, ServerConfig { memory_budget: Some(64 << 20), ..Default::default() });
+    let mut client = running.client();
+    let large = JudgeRequest { limits: Limits { memory_limit_bytes: 128 << 20, ..Default::default() }, ..inline_request() };
+    let status = client.judge(&large).unwrap_err();
+    assert_eq!(status.code, Code::InvalidArgument);
+    assert!(status.message.contains("over the memory budget"), "{}", status.message);
+    let small = JudgeRequest { limits: Limits { memory_limit_bytes: 32 << 20, ..Default::default() }, ..inline_request() };
+    assert_eq!(client.judge(&small).unwrap().status, "AC");
+}
+
+#[test]
+fn endless_header_blocks_are_refused() {
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::exit(0)));
+    let running = serve(sandbox, ServerConfig::default());
+    let mut stream = raw_connection(&running);
+    let chunk = vec![0u8; 16 << 10];
+    let _ = stream.write_all(&frame(1, 0, 1, &chunk));
+    // The server goes away part of the way through
+    for _ in 0..8 {
+        let _ = stream.write_all(&frame(9, 0, 1, &chunk));
+    }
+    let goaway = loop {
+        match read_frame(&mut stream) {
+            (7, _, payload) => break payload,
+            _ => continue
+        }
+    };
+    // ENHANCE_YOUR_CALM
+    assert_eq!(goaway[4..8], [0, 0, 0, 0xb]);
+}
+
+#[test]
+fn streams_past_the_limit_are_refused() {
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::exit(0)));
+    let running = serve(sandbox, ServerConfig::default());
+    let mut stream = raw_connection(&running);
+    let mut block = Vec::new();
+    for (name, value) in [(":method", "POST"), (":path", "/secure_judger.Judger/Judge"), ("content-type", "application/grpc")] {
+        // Literals without indexing, as the server's encoder writes them
+        block.extend_from_slice(&[0, name.len() as u8]);
+        block.extend_from_slice(name.as_bytes());
+        block.push(value.len() as u8);
+        block.extend_from_slice(value.as_bytes());
+    }
+    // None of the requests ends, so all of them stay open
+    for id in 0..101 {
+        stream.write_all(&frame(1, 0x4, 2 * id + 1, &block)).unwrap();
+    }
+    let (kind, _, settings) = read_frame(&mut stream);
+    assert_eq!(kind, 4);
+    assert!(settings.chunks(6).any(|setting| setting == [0, 3, 0, 0, 0, 100]), "{settings:?}");
+    let reset = loop {
+        match read_frame(&mut stream) {
+            (3, id, payload) => break (id, payload),
+            _ => continue
+        }
+    };
+    // The 101st, with REFUSED_STREAM
+    assert_eq!(reset, (201, vec![0, 0, 0, 7]));
+}
@@ -0,0 +1,108 @@
+// The judger sent SIGTERM as the program runs: the program gets SIGTERM
+// too and the grace period to exit, is judged as it ended and the result
+// says it was interrupted, and cancelling cuts the grace period short.
+// Runs the fixtures in the real sandbox, skipped where they can't be built.
+#![cfg(feature = "seccomp")]
+
+mod support;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use secure_judger::judger::{JudgeHandle, JudgeSession, JudgeStatus, TerminationPolicy};
+
+/*
+ *  The judger judging `exec` with `args` against an answer of "stopped",
+ *  and the pid of the program once it has been running for a while
+ */
+fn start(exec: &Path, args: &[&str]) -> (Child, i32) {
+    let answer = support::write_file("interrupt.ans", b"stopped\n");
+    let judger = Command::new(env!("CARGO_BIN_EXE_secure-judger"))
+        .args(["judge", "--time-limit", "5s", "/dev/null"])
+        .arg(answer)
+        .arg(exec)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let children = format!("/proc/{0}/task/{0}/children", judger.id());
+    let start = Instant::now();
+    let program = loop {
+        let listed = fs::read_to_string(&children).unwrap_or_default();
+        if let Some(pid) = listed.split_whitespace().next().and_then(|pid| pid.parse().ok()) {
+            break pid;
+        }
+        assert!(start.elapsed() < Duration::from_secs(10), "the program didn't start");
+        thread::sleep(Duration::from_millis(10));
+    };
+    // Time to exec and set up its handler
+    thread::sleep(Duration::from_millis(300));
+    (judger, program)
+}
+
+fn sigterm(child: &Child) {
+    assert_eq!(unsafe { libc::kill(child.id() as i32, libc::SIGTERM) }, 0);
+}
+
+fn finish(judger: Child, program: i32) -> Output {
+    let output = judger.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(128 + libc::SIGTERM));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Judging interrupted by SIGTERM"), "{stderr}");
+    assert!(!Path::new(&format!("/proc/{program}")).exists(), "the program was left running");
+    output
+}
+
+#[test]
+fn program_winds_down() {
+    let Some(exec) = support::fixture("wind_down") else { return };
+    let (judger, program) = start(&exec, &[]);
+    let stopped = Instant::now();
+    sigterm(&judger);
+    let output = finish(judger, program);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[AC] Accepted"), "{stdout}");
+    assert!(stdout.contains("Interrupted:"), "{stdout}");
+    assert!(stopped.elapsed() < Duration::from_secs(2), "took {:?}", stopped.elapsed());
+}
+
+#[test]
+fn program_ignoring_it_is_killed() {
+    let Some(exec) = support::fixture("wind_down") else { return };
+    let (judger, program) = start(&exec, &["ignore"]);
+    sigterm(&judger);
+    let output = finish(judger, program);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[CAN] Cancelled"), "{stdout}");
+    assert!(stdout.contains("Interrupted:"), "{stdout}");
+}
+
+#[test]
+fn cancelling_ends_the_grace_period() {
+    let Some(exec) = support::fixture("wind_down") else { return };
+    let answer = support::write_file("grace.ans", b"stopped\n");
+    let handle = JudgeHandle::new();
+    let session = JudgeSession::builder(exec.clone())
+        .answer(answer)
+        .termination(TerminationPolicy { grace_period: Duration::from_secs(30), ..TerminationPolicy::default() })
+        .cancel_handle(handle.clone())
+        .build()
+        .unwrap();
+    let name = exec.to_string_lossy().into_owned();
+    let stopper = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        handle.stop();
+        thread::sleep(Duration::from_millis(100));
+        handle.cancel();
+    });
+    let start = Instant::now();
+    let result = session.run_judge(&[&name, "ignore"]).unwrap();
+    stopper.join().unwrap();
+    assert!(matches!(result.status, JudgeStatus::Cancelled), "{}", result.status);
+    assert!(result.interrupted);
+    assert!(start.elapsed() < Duration::from_secs(5), "took {:?}", start.elapsed());
+}
@@ -0,0 +1,126 @@
+// Running the program in namespaces made by another tool, here by
+// unshare(1), and the errors for namespaces that can't be joined. Joining
+// needs root, so those tests are skipped without unshare working.
+#![cfg(feature = "seccomp")]
+
+mod support;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use secure_judger::judger::{JudgeSession, JudgeStatus};
+use secure_judger::sandbox::{JoinNamespace, Namespace};
+
+/// A process in namespaces of its own, killed with them when dropped
+struct Unshared(Child);
+
+impl Drop for Unshared {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+impl Unshared {
+    fn ns(&self) -> PathBuf {
+        PathBuf::from(format!("/proc/{}/ns", self.0.id()))
+    }
+}
+
+/*
+ *  A process in new net, uts and pid namespaces, or None after saying
+ *  why the test is skipped. The pid namespace is the one of its children,
+ *  which can only be joined once its init is forked.
+ */
+fn unshare() -> Option<Unshared> {
+    let child = Command::new("unshare")
+        .args(["--net", "--uts", "--pid", "--fork", "--kill-child", "sleep", "60"])
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(child) = child else {
+        eprintln!("skipping: no unshare");
+        return None;
+    };
+    let unshared = Unshared(child);
+    let own = fs::read_link("/proc/self/ns/net").unwrap();
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        match fs::read_link(unshared.ns().join("net")) {
+            Ok(net) if net != own && unshared.ns().join("pid_for_children").exists() => return Some(unshared),
+            Ok(_) => thread::sleep(Duration::from_millis(10)),
+            Err(_) => break
+        }
+    }
+    eprintln!("skipping: unshare can't make namespaces here");
+    None
+}
+
+fn error_of(namespace: JoinNamespace) -> Option<String> {
+    let exec = support::fixture("show_namespaces")?;
+    let answer = support::write_file("namespaces.ans", b"");
+    let session = JudgeSession::builder(exec).answer(answer).join_namespace(namespace).build().unwrap();
+    match session.run_judge(&[]) {
+        Ok(result) => panic!("judged as {}", result.status),
+        Err(e) => Some(e.to_string())
+    }
+}
+
+#[test]
+fn runs_in_joined_namespaces() {
+    let Some(exec) = support::fixture("show_namespaces") else { return };
+    let Some(unshared) = unshare() else { return };
+    let ns = unshared.ns();
+    let link = |kind: &str| fs::read_link(ns.join(kind)).unwrap().to_string_lossy().into_owned();
+    let answer = format!("{}\n{}\nppid 0\n", link("net"), link("uts"));
+    let answer = support::write_file("namespaces.ans", answer.as_bytes());
+    let session = JudgeSession::builder(exec)
+        .answer(answer)
+        .time_limit(Duration::from_millis(500))
+        .join_namespace(JoinNamespace { kind: Namespace::Net, path: ns.join("net") })
+        .join_namespace(JoinNamespace { kind: Namespace::Uts, path: ns.join("uts") })
+        .join_namespace(JoinNamespace { kind: Namespace::Pid, path: ns.join("pid_for_children") })
+        .build()
+        .unwrap();
+    let result = session.run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert!(result.sandbox.unwrap().seccomp);
+}
+
+#[test]
+fn own_namespaces_are_left_alone() {
+    let Some(exec) = support::fixture("show_namespaces") else { return };
+    let link = |kind: &str| fs::read_link(Path::new("/proc/self/ns").join(kind)).unwrap().to_string_lossy().into_owned();
+    let answer = format!("{}\n{}\nppid {}\n", link("net"), link("uts"), std::process::id());
+    let answer = support::write_file("own-namespaces.ans", answer.as_bytes());
+    let mut builder = JudgeSession::builder(exec).answer(answer).time_limit(Duration::from_millis(500));
+    // The user namespace among them, which setns would refuse
+    for namespace in JoinNamespace::all_of(Path::new("/proc/self/ns")) {
+        builder = builder.join_namespace(namespace);
+    }
+    let result = builder.build().unwrap().run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+}
+
+#[test]
+fn missing_namespace() {
+    let path = PathBuf::from("/proc/self/ns/no-such-namespace");
+    let Some(error) = error_of(JoinNamespace { kind: Namespace::Ipc, path }) else { return };
+    assert!(error.contains("cannot open the ipc namespace /proc/self/ns/no-such-namespace"), "{error}");
+}
+
+#[test]
+fn namespace_of_another_kind() {
+    let path = PathBuf::from("/proc/self/ns/uts");
+    let Some(error) = error_of(JoinNamespace { kind: Namespace::Net, path }) else { return };
+    assert!(error.contains("/proc/self/ns/uts is a uts namespace, not the net namespace"), "{error}");
+}
+
+#[test]
+fn not_a_namespace() {
+    let path = PathBuf::from("/proc/self/status");
+    let Some(error) = error_of(JoinNamespace { kind: Namespace::Mount, path }) else { return };
+    assert!(error.contains("/proc/self/status is not a namespace"), "{error}");
+}
@@ -0,0 +1,155 @@
+// The versioned JSON of results: documents written before versions were
+// named, and those of version 1, read back and written again unchanged,
+// so that a version only ever gains fields, and what changed between two
+// results or two batches of them.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use secure_judger::judger::{JudgeResult, JudgeStatus};
+use secure_judger::output::{DiffTolerance, JsonResultV1, JsonResultsDiffV1, JsonSubmissionV1, ReadError, Schema};
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/json").join(name)).unwrap()
+}
+
+#[test]
+fn schema_names() {
+    assert_eq!(Schema::LATEST.name(), "secure-judger/1");
+    assert_eq!(Schema::from_name("secure-judger/1"), Some(Schema::V1));
+    assert_eq!(Schema::from_format("json-v1"), Some(Schema::V1));
+    assert_eq!(Schema::from_format("json"), Some(Schema::LATEST));
+    assert_eq!(Schema::from_format("json-v2"), None);
+}
+
+#[test]
+fn unversioned_result_is_read_as_v1() {
+    let result = JsonResultV1::parse(&fixture("result-unversioned.json")).unwrap();
+    assert_eq!(result.status, "WA");
+    assert_eq!(result.time_ms, 12);
+    assert_eq!(result.judge_overhead_ms, 1.25);
+    // Added since, so taken as their defaults
+    assert_eq!(result.difference, None);
+    assert_eq!(result.environment, None);
+    assert!(!result.resumed);
+    assert_eq!(result.sandbox, Some((String::from("require"), true)));
+
+    let written = result.to_json();
+    assert!(written.starts_with("{\"schema\":\"secure-judger/1\",\"status\":\"WA\""), "{written}");
+    assert_eq!(JsonResultV1::parse(&written).unwrap(), result);
+}
+
+#[test]
+fn v1_result_round_trips() {
+    let text = fixture("result-v1.json");
+    let result = JsonResultV1::parse(&text).unwrap();
+    assert_eq!(result.environment.as_ref().and_then(|e| e.cores), Some(4));
+    assert_eq!(result.environment.as_ref().and_then(|e| e.calibration.as_ref()).map(|c| c.multiplier), Some(1.25));
+    // Written byte for byte as it was, so nothing was lost or reordered
+    assert_eq!(result.to_json(), text.trim_end());
+}
+
+#[test]
+fn unversioned_batch_round_trips() {
+    let batch = JsonSubmissionV1::parse(&fixture("batch-unversioned.json")).unwrap();
+    assert_eq!(batch.tests.len(), 2);
+    assert_eq!(batch.tests[1].weight, 2.0);
+    assert!(batch.tests[1].result.cached);
+    assert_eq!(batch.aggregate.max_score, 3.0);
+    assert_eq!(JsonSubmissionV1::parse(&batch.to_json()).unwrap(), batch);
+}
+
+#[test]
+fn judged_result_round_trips() {
+    let mut result = JudgeResult::unfinished(JudgeStatus::WrongAnswer);
+    result.difference = Some(String::from("line 2: expected 3, found 4"));
+    result.cpu_time_ms = 17;
+    let written = JsonResultV1::of(&result);
+    assert_eq!(JsonResultV1::parse(&written.to_json()).unwrap(), written);
+    // Nested in other documents, without a schema of its own
+    assert_eq!(result.to_json(), written.to_nested_json());
+    assert!(!result.to_json().contains("\"schema\""));
+}
+
+#[test]
+fn unknown_documents_are_refused() {
+    let later = fixture("result-v1.json").replace("secure-judger/1", "secure-judger/2");
+    assert!(matches!(JsonResultV1::parse(&later), Err(ReadError::Schema(name)) if name == "secure-judger/2"));
+
+    let error = JsonResultV1::parse("{\"message\":\"Accepted\"}").unwrap_err();
+    assert_eq!(error.to_string(), "result.status should be a string");
+    let error = JsonSubmissionV1::parse(&fixture("batch-unversioned.json").replacen("\"weight\":1", "\"weight\":\"1\"", 1)).unwrap_err();
+    assert_eq!(error.to_string(), "batch.tests[0].weight should be a number");
+    assert!(matches!(JsonResultV1::parse("{"), Err(ReadError::Json(_))));
+}
+
+fn timed(status: JudgeStatus, time_ms: u64, memory_bytes: u64) -> JudgeResult {
+    let mut result = JudgeResult::unfinished(status);
+    result.time_used = Duration::from_millis(time_ms);
+    result.cpu_time_ms = time_ms;
+    result.memory_used_bytes = memory_bytes;
+    result
+}
+
+#[test]
+fn results_differ_beyond_the_tolerance() {
+    let tolerance = DiffTolerance::default();
+    let old = timed(JudgeStatus::Accepted, 1000, 1 << 20);
+    assert!(!old.diff(&old).changed(&tolerance));
+
+    // 5% of 1000ms either way is within it, a millisecond more isn't
+    let diff = old.diff(&timed(JudgeStatus::Accepted, 1050, 1 << 20));
+    assert!(!diff.time_changed(&tolerance));
+    assert_eq!(diff.time_delta_ms(), 50);
+    let diff = old.diff(&timed(JudgeStatus::Accepted, 1051, 1 << 20));
+    assert!(diff.time_changed(&tolerance) && !diff.verdict_changed());
+    assert!(old.diff(&timed(JudgeStatus::Accepted, 900, 1 << 20)).time_changed(&tolerance));
+
+    // Short runs move by the slack whatever their percent
+    let short = timed(JudgeStatus::Accepted, 2, 1 << 20);
+    let diff = short.diff(&timed(JudgeStatus::Accepted, 12, 1 << 20));
+    assert_eq!(diff.time_delta_percent(), Some(500.0));
+    assert!(!diff.changed(&tolerance));
+    assert!(short.diff(&timed(JudgeStatus::Accepted, 13, 1 << 20)).time_changed(&tolerance));
+    assert_eq!(timed(JudgeStatus::Accepted, 0, 0).diff(&short).time_delta_percent(), None);
+
+    let diff = old.diff(&timed(JudgeStatus::Accepted, 1000, 2 << 20));
+    assert!(diff.memory_changed(&tolerance) && !diff.time_changed(&tolerance));
+    assert_eq!(diff.memory_delta_percent(), Some(100.0));
+    assert!(!diff.memory_changed(&DiffTolerance { memory_percent: 100.0, ..tolerance }));
+
+    let diff = old.diff(&timed(JudgeStatus::WrongAnswer, 1000, 1 << 20));
+    assert!(diff.verdict_changed() && diff.changed(&tolerance));
+    assert_eq!(diff.status, (String::from("AC"), String::from("WA")));
+}
+
+#[test]
+fn verdicts_of_a_kind_differ_in_detail() {
+    let mut old = timed(JudgeStatus::WrongAnswer, 10, 1 << 20);
+    old.difference = Some(String::from("line 2: expected 3, found 4"));
+    let mut new = timed(JudgeStatus::WrongAnswer, 10, 1 << 20);
+    new.difference = Some(String::from("line 5: expected 1, found 2"));
+    let diff = old.diff(&new);
+    assert!(!diff.verdict_changed() && diff.detail_changed());
+    assert!(diff.changed(&DiffTolerance::default()));
+    assert!(!old.diff(&old).detail_changed());
+}
+
+#[test]
+fn batches_are_diffed_by_test_name() {
+    let old = JsonSubmissionV1::parse(&fixture("batch-unversioned.json")).unwrap();
+    let mut new = old.clone();
+    new.tests.swap(0, 1);
+    assert!(JsonResultsDiffV1::between(&old, &new, DiffTolerance::default()).is_empty());
+
+    new.tests[0].result.status = String::from("AC");
+    new.tests[1].name = String::from("4");
+    let diff = JsonResultsDiffV1::between(&old, &new, DiffTolerance::default());
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].0, "2");
+    assert_eq!(diff.changed[0].1.status, (String::from("TLE"), String::from("AC")));
+    assert_eq!(diff.unchanged, 0);
+    assert_eq!((diff.added.as_slice(), diff.removed.as_slice()), (&[String::from("4")][..], &[String::from("1")][..]));
+    assert!(diff.to_json().starts_with("{\"schema\":\"secure-judger/1\""));
+}
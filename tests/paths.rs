@@ -0,0 +1,143 @@
+// The strict check of the paths programs are run from: directories and
+// files others can write to or own, and symlinks out of the root
+// programs have to stay under. Owners other than root are only tried
+// when running as root.
+
+mod support;
+
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use secure_judger::judger::JudgeSession;
+use secure_judger::utils::{self, PathProblem, StrictPaths};
+
+fn set_mode(path: &Path, mode: u32) {
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+}
+
+/*
+ *  A copy of the test binary at `path`, an ELF executable that runs here
+ */
+fn program(path: &Path) -> PathBuf {
+    fs::copy(std::env::current_exe().unwrap(), path).unwrap();
+    path.to_path_buf()
+}
+
+fn problems(path: &Path, strict: &StrictPaths) -> Vec<PathProblem> {
+    let report = utils::check_path(path, strict).unwrap();
+    // Unless the test directory itself is in an unsafe place
+    report.problems.into_iter().filter(|problem| match problem {
+        PathProblem::WorldWritable(at) | PathProblem::Owner { path: at, .. } => at.starts_with(env!("CARGO_TARGET_TMPDIR")),
+        PathProblem::OutsideRoot(_) => true
+    }).collect()
+}
+
+#[test]
+fn private_directories_are_safe() {
+    let dir = support::dir("private");
+    let prog = program(&dir.join("prog"));
+    let report = utils::check_path(&prog, &StrictPaths::default()).unwrap();
+    assert_eq!(report.resolved, fs::canonicalize(&prog).unwrap());
+    assert_eq!(problems(&prog, &StrictPaths::default()), vec![]);
+}
+
+#[test]
+fn world_writable_places() {
+    let open = support::dir("open");
+    let prog = program(&open.join("prog"));
+    set_mode(&open, 0o777);
+    let found = problems(&prog, &StrictPaths::default());
+    set_mode(&open, 0o755);
+    assert_eq!(found, vec![PathProblem::WorldWritable(fs::canonicalize(&open).unwrap())]);
+    // Sticky directories such as /tmp are fine, their files aren't
+    let sticky = support::dir("sticky");
+    let prog = program(&sticky.join("prog"));
+    set_mode(&sticky, 0o1777);
+    set_mode(&prog, 0o777);
+    let found = problems(&prog, &StrictPaths::default());
+    set_mode(&sticky, 0o755);
+    assert_eq!(found, vec![PathProblem::WorldWritable(fs::canonicalize(&prog).unwrap())]);
+}
+
+#[test]
+fn untrusted_owners() {
+    let dir = support::dir("owned");
+    let prog = program(&dir.join("prog"));
+    if std::os::unix::fs::chown(&prog, Some(12345), None).is_err() {
+        eprintln!("skipping: can't give files away without root");
+        return;
+    }
+    let prog = fs::canonicalize(&prog).unwrap();
+    assert_eq!(problems(&prog, &StrictPaths::default()), vec![PathProblem::Owner { path: prog.clone(), uid: 12345 }]);
+    // Unless trusted
+    let strict = StrictPaths { trusted_uids: vec![0, 12345], ..StrictPaths::default() };
+    assert_eq!(problems(&prog, &strict), vec![]);
+}
+
+#[test]
+fn symlinks_out_of_the_root() {
+    let (root, outside) = (support::dir("root"), support::dir("outside"));
+    let inside = program(&root.join("prog"));
+    let elsewhere = program(&outside.join("prog"));
+    symlink(&elsewhere, root.join("link")).unwrap();
+    symlink(&inside, outside.join("link-in")).unwrap();
+    let strict = StrictPaths { root: Some(root.clone()), ..StrictPaths::default() };
+    assert_eq!(problems(&inside, &strict), vec![]);
+    assert_eq!(problems(&outside.join("link-in"), &strict), vec![]);
+    let outside_root = vec![PathProblem::OutsideRoot(fs::canonicalize(&root).unwrap())];
+    assert_eq!(problems(&root.join("link"), &strict), outside_root);
+    assert_eq!(problems(&elsewhere, &strict), outside_root);
+    assert!(utils::check_path(&root.join("missing"), &strict).is_err());
+}
+
+fn build_error(exec: PathBuf, interpreter: Option<PathBuf>) -> String {
+    let mut builder = JudgeSession::builder(exec).strict_paths(StrictPaths::default());
+    if let Some(interpreter) = interpreter {
+        builder = builder.interpreter(interpreter);
+    }
+    match builder.build() {
+        Ok(_) => panic!("the session was built"),
+        Err(e) => e.to_string()
+    }
+}
+
+#[test]
+fn sessions_refuse_unsafe_programs() {
+    let open = support::dir("session-open");
+    let prog = program(&open.join("prog"));
+    let interpreter = program(&open.join("python3"));
+    let script = support::dir("session-script").join("solution.py");
+    fs::write(&script, "print(1)\n").unwrap();
+    set_mode(&open, 0o777);
+    let exec_error = build_error(prog.clone(), None);
+    let interpreter_error = build_error(script.clone(), Some(interpreter.clone()));
+    let unchecked = JudgeSession::builder(prog.clone()).build().map(|_| ());
+    set_mode(&open, 0o755);
+    let open = fs::canonicalize(&open).unwrap();
+    assert_eq!(exec_error, format!("executable {}: unsafe path, {} is world-writable", prog.display(), open.display()));
+    assert_eq!(interpreter_error, format!("interpreter {}: unsafe path, {} is world-writable", interpreter.display(), open.display()));
+    // Only with strict paths
+    assert!(unchecked.is_ok());
+    let error = match JudgeSession::builder(prog).strict_paths(StrictPaths { root: Some(open.join("none")), ..StrictPaths::default() }).build() {
+        Ok(_) => panic!("the session was built"),
+        Err(e) => e.to_string()
+    };
+    assert_eq!(error, format!("strict paths root {}: not a directory", open.join("none").display()));
+}
+
+#[test]
+#[cfg(feature = "seccomp")]
+fn plans_show_the_checks() {
+    let dir = support::dir("plan");
+    let prog = program(&dir.join("prog"));
+    symlink(&prog, dir.join("link")).unwrap();
+    let session = JudgeSession::builder(dir.join("link")).strict_paths(StrictPaths::default()).build().unwrap();
+    let plan = session.describe(&[]).unwrap();
+    let resolved = fs::canonicalize(&prog).unwrap();
+    assert!(plan.to_string().contains(&format!("Path Check:\t{} -> {}, safe\n", dir.join("link").display(), resolved.display())), "{plan}");
+    assert!(plan.to_json().contains(&format!("\"path_checks\": [{{\"path\":{:?},\"resolved\":{:?}}}]", dir.join("link"), resolved)), "{}", plan.to_json());
+    let unchecked = JudgeSession::builder(prog).build().unwrap().describe(&[]).unwrap();
+    assert!(!unchecked.to_string().contains("Path Check"));
+    assert!(unchecked.to_json().contains("\"path_checks\": null"));
+}
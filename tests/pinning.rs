@@ -0,0 +1,67 @@
+// Pinning the tests of a parallel batch to CPUs: each running test has a
+// CPU of its own, recorded in its result, and with fewer CPUs than jobs
+// nothing is pinned. Judged on the mock sandbox.
+
+mod support;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use secure_judger::judger::JudgeSession;
+use secure_judger::problem::ProblemJudge;
+use secure_judger::sandbox::{MockRun, MockSandbox};
+
+/*
+ *  A judge of six tests answered by "3", run by a program printing "3"
+ */
+fn judge(name: &str) -> ProblemJudge {
+    let dir = support::dir(name);
+    let cases = support::cases(&dir, 6);
+    let exec = support::mock_program(&dir.join("program"));
+    let session = JudgeSession::builder(exec)
+        .answer(cases[0].answer.clone())
+        .time_limit(Duration::from_secs(30))
+        .copy_exec(false)
+        .sandbox(Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("3\n").wall_time(Duration::from_millis(20)))))
+        .build()
+        .unwrap();
+    ProblemJudge::new(session, cases)
+}
+
+/*
+ *  The CPUs the tests may run on
+ */
+fn allowed_cpus() -> Vec<usize> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    assert_eq!(unsafe { libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut set) }, 0);
+    (0..libc::CPU_SETSIZE as usize).filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) }).collect()
+}
+
+#[test]
+fn runs_are_pinned_to_the_cpus_given() {
+    let allowed = allowed_cpus();
+    if allowed.len() < 2 {
+        eprintln!("skipping: the tests may only run on {allowed:?}");
+        return;
+    }
+    let cpus = allowed[..2].to_vec();
+    let submission = judge("given").with_cpus(cpus.clone()).run_parallel(2, &[]).unwrap();
+    for result in &submission.results {
+        assert!(result.cpu.is_some_and(|cpu| cpus.contains(&cpu)), "pinned to {:?}", result.cpu);
+    }
+    let result = &submission.results[0];
+    assert!(result.to_json().ends_with(&format!(",\"cpu\":{}}}", result.cpu.unwrap())), "{}", result.to_json());
+}
+
+#[test]
+fn too_few_cpus_pin_nothing() {
+    let allowed = allowed_cpus();
+    let submission = judge("too-few").with_cpus(allowed[..1].to_vec()).run_parallel(2, &[]).unwrap();
+    assert!(submission.results.iter().all(|result| result.cpu.is_none()));
+    // Nor do CPUs the judger may not run on count
+    let submission = judge("not-allowed").with_cpus(vec![libc::CPU_SETSIZE as usize - 1; 2]).run_parallel(2, &[]).unwrap();
+    assert!(submission.results.iter().all(|result| result.cpu.is_none()));
+    // Sequential runs are never pinned
+    let submission = judge("sequential").with_pin_cpus(true).run(&[]).unwrap();
+    assert!(submission.results.iter().all(|result| result.cpu.is_none()));
+}
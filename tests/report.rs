@@ -0,0 +1,101 @@
+// The report of the batch subcommand: a row per test with long names cut
+// short, the totals under them, and where the output went wrong when a
+// single test failed, with the progress on stderr apart from it. Runs the
+// binary on the fixtures in the real sandbox, skipped where they can't be
+// built.
+#![cfg(feature = "seccomp")]
+
+mod support;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const LONG_NAME: &str = "a_test_with_a_name_too_long_for_its_column";
+
+/*
+ *  Tests in `dir` the fixture hello passes but for those with answers in
+ *  `wrong`
+ */
+fn tests(dir: &Path, wrong: &[&str]) {
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    for name in ["1", "2", LONG_NAME] {
+        let answer = match wrong.contains(&name) {
+            true => "bye\n",
+            false => "hello\n"
+        };
+        fs::write(dir.join(format!("{name}.in")), "").unwrap();
+        fs::write(dir.join(format!("{name}.ans")), answer).unwrap();
+    }
+}
+
+/*
+ *  What the batch writes to stdout and to stderr, neither of them a
+ *  terminal
+ */
+fn run_batch(exec: &Path, dir: &Path, options: &[&str]) -> (String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_secure-judger"))
+        .arg("batch")
+        .args(options)
+        .arg("--tests")
+        .arg(dir)
+        .arg(exec)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    (String::from_utf8_lossy(&output.stdout).into_owned(), String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+fn report(exec: &Path, dir: &Path) -> String {
+    run_batch(exec, dir, &[]).0
+}
+
+#[test]
+fn single_failure_shows_its_difference() {
+    let Some(exec) = support::fixture("hello") else { return };
+    let dir = support::write_file("report.keep", b"").with_file_name("report-tests");
+    tests(&dir, &["2"]);
+    let report = report(&exec, &dir);
+    let lines: Vec<&str> = report.lines().collect();
+    assert!(lines[0].starts_with("TEST") && lines[0].contains("VERDICT") && lines[0].contains("SCORE"), "{report}");
+    // Cut short, but lined up with the others
+    let cut = format!("{}…", &LONG_NAME[..31]);
+    let row = lines.iter().find(|line| line.starts_with(&cut)).unwrap_or_else(|| panic!("{report}"));
+    let column = |line: &str, text: &str| line.find(text).map(|at| line[..at].chars().count());
+    assert_eq!(column(row, "AC"), column(lines[0], "VERDICT"), "{report}");
+    assert!(report.contains("\ntotal"), "{report}");
+    assert!(report.contains("2/3 points"), "{report}");
+    assert!(report.contains("Test 2 differs at line 1: expected \"bye\", got \"hello\""), "{report}");
+    assert!(!report.contains('\x1b'), "{report}");
+}
+
+#[test]
+fn several_failures_show_none() {
+    let Some(exec) = support::fixture("hello") else { return };
+    let dir = support::write_file("report-several.keep", b"").with_file_name("report-several-tests");
+    tests(&dir, &["1", "2"]);
+    let report = report(&exec, &dir);
+    assert!(report.contains("1/3 points"), "{report}");
+    assert!(!report.contains("differs at"), "{report}");
+}
+
+#[test]
+fn progress_is_plain_lines_on_stderr() {
+    let Some(exec) = support::fixture("hello") else { return };
+    let dir = support::write_file("report-progress.keep", b"").with_file_name("report-progress-tests");
+    tests(&dir, &["2"]);
+    let (report, progress) = run_batch(&exec, &dir, &[]);
+    // The first test as it starts, then how the batch ended up
+    let lines: Vec<&str> = progress.lines().collect();
+    assert_eq!(lines.first(), Some(&"test 1/3 (1), 0s"), "{progress}");
+    let last = lines.last().unwrap();
+    assert!(last.starts_with(&format!("test 3/3 ({LONG_NAME}), ")), "{progress}");
+    assert!(last.ends_with(", 2 AC 1 WA"), "{progress}");
+    assert!(!progress.contains('\r') && !progress.contains('\x1b'), "{progress}");
+    assert!(!report.contains("test 1/3"), "{report}");
+
+    let (report, progress) = run_batch(&exec, &dir, &["--quiet"]);
+    assert_eq!(progress, "");
+    assert!(report.contains("2/3 points"), "{report}");
+}
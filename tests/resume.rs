@@ -0,0 +1,122 @@
+// A batch with stamps, cut short and run again: the tests it judged are
+// taken from their stamps and only the rest are run. Judged on the mock
+// sandbox, which counts the runs it was asked for.
+
+mod support;
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use secure_judger::cache::BatchStamps;
+use secure_judger::judger::{JudgeObserver, JudgeResult, JudgeSession, JudgeSessionBuilder, JudgeStatus};
+use secure_judger::problem::{ProblemJudge, TestCase};
+use secure_judger::sandbox::{MockRun, MockSandbox};
+
+/*
+ *  A session on the runs of `sandbox`
+ */
+fn session(cases: &[TestCase], sandbox: Arc<MockSandbox>) -> JudgeSessionBuilder {
+    let exec = support::mock_program(&cases[0].input.with_file_name("program"));
+    JudgeSession::builder(exec)
+        .answer(cases[0].answer.clone())
+        .time_limit(Duration::from_secs(1))
+        .copy_exec(false)
+        .sandbox(sandbox)
+}
+
+fn judge(cases: &[TestCase], sandbox: Arc<MockSandbox>, stamps: &Path, reuse: bool) -> ProblemJudge {
+    stamped(session(cases, sandbox), cases, stamps, reuse)
+}
+
+fn stamped(session: JudgeSessionBuilder, cases: &[TestCase], stamps: &Path, reuse: bool) -> ProblemJudge {
+    let stamps = BatchStamps::open(stamps.to_path_buf(), reuse).unwrap();
+    ProblemJudge::new(session.build().unwrap(), cases.to_vec()).with_stamps(stamps)
+}
+
+/// Fails every run it sees finish, making it a SystemError
+struct Failing;
+
+impl JudgeObserver for Failing {
+    fn on_run_complete(&self, _result: &JudgeResult) {
+        panic!("failing on purpose");
+    }
+}
+
+fn accepted() -> MockRun {
+    MockRun::exit(0).stdout("3\n")
+}
+
+#[test]
+fn interrupted_batch_resumes() {
+    let dir = support::dir("interrupted");
+    let cases = support::cases(&dir, 3);
+    let stamps = dir.join("stamps");
+    // The script runs out at the third test, failing the batch there
+    let sandbox = Arc::new(MockSandbox::new(vec![accepted(), MockRun::exit(0).stdout("4\n")]));
+    assert!(judge(&cases, sandbox, &stamps, true).run(&[]).is_err());
+
+    let sandbox = Arc::new(MockSandbox::repeat(accepted()));
+    let submission = judge(&cases, sandbox.clone(), &stamps, true).run(&[]).unwrap();
+    assert_eq!(sandbox.spawned(), 1);
+    let resumed: Vec<bool> = submission.results.iter().map(|result| result.resumed).collect();
+    assert_eq!(resumed, [true, true, false]);
+    assert!(matches!(submission.results[1].status, JudgeStatus::WrongAnswer), "{}", submission.results[1].status);
+    // Summed up over the resumed tests as over the judged one
+    assert!(matches!(submission.status, JudgeStatus::WrongAnswer), "{}", submission.status);
+    assert_eq!(submission.score, 2.0);
+
+    let sandbox = Arc::new(MockSandbox::repeat(accepted()));
+    let submission = judge(&cases, sandbox.clone(), &stamps, true).run_parallel(2, &[]).unwrap();
+    assert_eq!(sandbox.spawned(), 0);
+    assert!(submission.results.iter().all(|result| result.resumed));
+}
+
+#[test]
+fn without_reuse_every_test_is_judged() {
+    let dir = support::dir("no-reuse");
+    let cases = support::cases(&dir, 2);
+    let stamps = dir.join("stamps");
+    judge(&cases, Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("4\n"))), &stamps, true).run(&[]).unwrap();
+
+    let sandbox = Arc::new(MockSandbox::repeat(accepted()));
+    let submission = judge(&cases, sandbox.clone(), &stamps, false).run(&[]).unwrap();
+    assert_eq!(sandbox.spawned(), 2);
+    assert!(submission.accepted());
+    // And stamped anew
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("4\n")));
+    assert!(judge(&cases, sandbox.clone(), &stamps, true).run(&[]).unwrap().accepted());
+    assert_eq!(sandbox.spawned(), 0);
+}
+
+#[test]
+fn changed_test_is_judged_again() {
+    let dir = support::dir("changed");
+    let mut cases = support::cases(&dir, 2);
+    let stamps = dir.join("stamps");
+    judge(&cases, Arc::new(MockSandbox::repeat(accepted())), &stamps, true).run(&[]).unwrap();
+
+    fs::write(&cases[0].answer, "4\n").unwrap();
+    cases[1].time_limit = Some(Duration::from_millis(500));
+    let sandbox = Arc::new(MockSandbox::repeat(accepted()));
+    let submission = judge(&cases, sandbox.clone(), &stamps, true).run(&[]).unwrap();
+    assert_eq!(sandbox.spawned(), 2);
+    assert!(!submission.results[0].resumed && !submission.results[1].resumed);
+    assert!(matches!(submission.results[0].status, JudgeStatus::WrongAnswer), "{}", submission.results[0].status);
+}
+
+#[test]
+fn system_errors_are_not_stamped() {
+    let dir = support::dir("system-error");
+    let cases = support::cases(&dir, 1);
+    let stamps = dir.join("stamps");
+    let session = session(&cases, Arc::new(MockSandbox::repeat(accepted()))).observer(Arc::new(Failing));
+    let submission = stamped(session, &cases, &stamps, true).run(&[]).unwrap();
+    assert!(matches!(submission.status, JudgeStatus::SystemError(_)), "{}", submission.status);
+
+    let sandbox = Arc::new(MockSandbox::repeat(accepted()));
+    let submission = judge(&cases, sandbox.clone(), &stamps, true).run(&[]).unwrap();
+    assert_eq!(sandbox.spawned(), 1);
+    assert!(submission.accepted());
+}
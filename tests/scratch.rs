@@ -0,0 +1,73 @@
+// The paths the judger writes to on its own: kept output moved over a
+// symlink planted at its name, answers made beside symlinks planted at
+// and beside theirs, and an artifact a compiler left as a symlink, each
+// checked to leave what the symlink points to untouched.
+// Runs the fixtures in the real sandbox, skipped where they can't be built.
+#![cfg(feature = "seccomp")]
+
+mod support;
+
+use std::fs;
+use std::os::unix::fs::symlink;
+
+use secure_judger::judger::{JudgeSession, JudgeStatus, KeepPolicy};
+use secure_judger::problem::{self, TestCase};
+use secure_judger::sandbox::{InputSource, SandboxPolicy};
+
+#[test]
+fn kept_output_replaces_a_planted_symlink() {
+    let Some(exec) = support::fixture("hello") else { return };
+    let victim = support::write_file("kept-victim", b"precious\n");
+    let output_dir = support::dir("kept-output");
+    symlink(&victim, output_dir.join("1.AC.out")).unwrap();
+    let input = support::write_file("1.in", b"");
+    let answer = support::write_file("kept.ans", b"hello\n");
+    let session = JudgeSession::builder(exec)
+        .input(InputSource::File(input))
+        .answer(answer)
+        .keep_output(KeepPolicy::Always)
+        .output_dir(output_dir.clone())
+        .build()
+        .unwrap();
+    let result = session.run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    let kept = output_dir.join("1.AC.out");
+    assert_eq!(result.output_path.as_ref(), Some(&kept));
+    assert!(fs::symlink_metadata(&kept).unwrap().is_file());
+    assert_eq!(fs::read_to_string(&kept).unwrap(), "hello\n");
+    assert_eq!(fs::read_to_string(&victim).unwrap(), "precious\n");
+    assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 1);
+}
+
+#[test]
+fn answers_replace_planted_symlinks() {
+    let Some(exec) = support::fixture("hello") else { return };
+    let victim = support::write_file("answer-victim", b"precious\n");
+    let dir = support::dir("answers");
+    let answer = dir.join("1.ans");
+    symlink(&victim, &answer).unwrap();
+    // Where the partial answer once went
+    symlink(&victim, dir.join(".1.ans.partial")).unwrap();
+    let input = dir.join("1.in");
+    fs::write(&input, "").unwrap();
+    let session = JudgeSession::builder(exec).build().unwrap();
+    let result = problem::write_answer(&session, &TestCase::new(input, answer.clone()), &[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert!(fs::symlink_metadata(&answer).unwrap().is_file());
+    assert_eq!(fs::read_to_string(&answer).unwrap(), "hello\n");
+    assert_eq!(fs::read_to_string(&victim).unwrap(), "precious\n");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+}
+
+#[test]
+fn artifacts_left_as_symlinks_are_not_collected() {
+    let Some(exec) = support::fixture("plant_symlink") else { return };
+    let victim = support::write_file("artifact-victim", b"precious\n");
+    let out = support::dir("artifact-out");
+    let dest = out.join("program");
+    let session = JudgeSession::builder(exec).policy(SandboxPolicy::compiler()).build().unwrap();
+    let result = session.compile(&["plant_symlink", victim.to_str().unwrap()], "a.out", &dest).unwrap();
+    assert!(matches!(result.status, JudgeStatus::OutputMissing), "{}", result.status);
+    assert!(fs::symlink_metadata(&dest).is_err());
+    assert_eq!(fs::read_to_string(&victim).unwrap(), "precious\n");
+}
@@ -0,0 +1,154 @@
+// Scripts judged in the real sandbox under the interpreter their #! line
+// names, or the one the session is given, with the limits and syscalls
+// of the interpreter's language, and cached as runs of their interpreter.
+// Skipped where the interpreter is missing.
+#![cfg(feature = "seccomp")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use secure_judger::judger::{JudgeResult, JudgeSession, JudgeSessionBuilder, JudgeStatus};
+use secure_judger::language;
+use secure_judger::sandbox::InputSource;
+
+/*
+ *  The script `name` of tests/fixtures, or None after saying why the test
+ *  is skipped if `interpreter` isn't there to run it
+ */
+fn fixture(name: &str, interpreter: &str) -> Option<PathBuf> {
+    if !Path::new(interpreter).exists() {
+        eprintln!("skipping: no {interpreter}");
+        return None;
+    }
+    Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name))
+}
+
+/*
+ *  Judge `exec` doubling 1, 2 and 3 with a 1s CPU limit, after
+ *  `configure` had its say
+ */
+fn write_file(name: &str, contents: &[u8]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("scripts-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(name), contents).unwrap();
+    dir.join(name)
+}
+
+fn judge(exec: PathBuf, configure: impl FnOnce(JudgeSessionBuilder) -> JudgeSessionBuilder) -> JudgeResult {
+    let answer = write_file("double.ans", b"2\n4\n6\n");
+    let builder = JudgeSession::builder(exec)
+        .input(InputSource::Bytes(b"1\n2\n3\n".to_vec()))
+        .answer(answer)
+        .time_limit(Duration::from_secs(1));
+    configure(builder).build().unwrap().run_judge(&[]).unwrap()
+}
+
+fn build_error(exec: PathBuf) -> String {
+    match JudgeSession::builder(exec).build() {
+        Ok(_) => panic!("the session was built"),
+        Err(e) => e.to_string()
+    }
+}
+
+#[test]
+fn python_script_is_accepted() {
+    let Some(exec) = fixture("double.py", "/usr/bin/python3") else { return };
+    let result = judge(exec.clone(), |b| b);
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}: {}", result.status, String::from_utf8_lossy(&result.stderr));
+    assert!(result.sandbox.unwrap().seccomp);
+    // Python's limits
+    assert_eq!(result.limits.unwrap().effective.cpu, Duration::from_secs(3));
+    let plan = JudgeSession::builder(exec).build().unwrap().describe(&[]).unwrap().to_string();
+    assert!(plan.contains("Interpreter:\t/usr/bin/python3\n"), "{plan}");
+}
+
+#[test]
+fn shell_script_is_accepted() {
+    let Some(exec) = fixture("double.sh", "/bin/sh") else { return };
+    let result = judge(exec, |b| b);
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}: {}", result.status, String::from_utf8_lossy(&result.stderr));
+    assert_eq!(result.limits.unwrap().effective.cpu, Duration::from_secs(1));
+}
+
+#[test]
+fn explicit_multipliers_win() {
+    let Some(exec) = fixture("double.py", "/usr/bin/python3") else { return };
+    let result = judge(exec, |b| b.limit_multipliers(Default::default()));
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert_eq!(result.limits.unwrap().effective.cpu, Duration::from_secs(1));
+}
+
+#[test]
+fn env_looks_up_the_interpreter() {
+    if fixture("double.sh", "/usr/bin/env").is_none() {
+        return;
+    }
+    let script = write_file("env-double.sh", b"#!/usr/bin/env sh\nwhile read -r n; do echo $((n * 2)); done\n");
+    let result = judge(script, |b| b);
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}: {}", result.status, String::from_utf8_lossy(&result.stderr));
+}
+
+#[test]
+fn interpreter_overrides_the_line() {
+    let Some(script_interpreter) = fixture("double.sh", "/bin/sh") else { return };
+    let script = write_file("wrong-line.sh", b"#!/no/such/interpreter\nwhile read -r n; do echo $((n * 2)); done\n");
+    let result = judge(script, |b| b.interpreter(PathBuf::from("/bin/sh")));
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    // Without a #! line at all
+    let script = write_file("no-line.py", b"import sys\nfor n in sys.stdin: print(int(n) * 2)\n");
+    if Path::new("/usr/bin/python3").exists() {
+        let result = judge(script, |b| b.interpreter(PathBuf::from("/usr/bin/python3")));
+        assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    }
+    let error = build_error(write_file("no-line.sh", b"echo\n"));
+    assert!(error.contains("neither an ELF executable nor a script"), "{error}");
+    // What would run under yet another interpreter
+    let result = JudgeSession::builder(write_file("no-line.sh", b"echo\n")).interpreter(script_interpreter.clone()).build();
+    let error = result.err().expect("a script as the interpreter").to_string();
+    assert_eq!(error, format!("interpreter {}: a script itself, not an ELF file", script_interpreter.display()));
+}
+
+#[test]
+fn the_cache_tells_interpreters_apart() {
+    let Some(exec) = fixture("double.sh", "/bin/sh") else { return };
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("scripts-cache-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    // A shell of its own, which can change under the script
+    let shell = dir.join("sh");
+    fs::copy("/bin/sh", &shell).unwrap();
+    let cached = |exec: &Path| judge(exec.to_path_buf(), |b| b.interpreter(shell.clone()).cache_dir(dir.join("cache"))).cached;
+    assert!(!cached(&exec));
+    assert!(cached(&exec));
+    // What follows the ELF file's contents doesn't change how it runs
+    let mut changed = fs::read(&shell).unwrap();
+    changed.push(0);
+    fs::write(&shell, changed).unwrap();
+    assert!(!cached(&exec));
+}
+
+#[test]
+fn unknown_interpreters_are_refused() {
+    let script = write_file("double.pl", b"#!/usr/bin/perl -w\nprint 2 * $_ for <>;\n");
+    let error = build_error(script.clone());
+    assert_eq!(error, format!("executable {}: no language is configured for its interpreter /usr/bin/perl", script.display()));
+    let script = write_file("missing.py", b"#!/no/such/python3\n");
+    assert_eq!(build_error(script), "interpreter /no/such/python3: No such file or directory (os error 2)");
+    let script = write_file("missing-env.py", b"#!/usr/bin/env no-such-python3\n");
+    let error = build_error(script);
+    assert!(error.starts_with("interpreter no-such-python3: executable 'no-such-python3' not found"), "{error}");
+}
+
+#[test]
+fn languages_of_interpreters() {
+    let name = |path: &str| language::for_interpreter(Path::new(path)).map(|lang| lang.name);
+    assert_eq!(name("/usr/bin/python3"), Some("python"));
+    assert_eq!(name("/usr/local/bin/python3.11"), Some("python"));
+    assert_eq!(name("python"), Some("python"));
+    assert_eq!(name("/usr/bin/pypy3"), Some("pypy"));
+    assert_eq!(name("/bin/sh"), Some("sh"));
+    assert_eq!(name("/bin/bash"), Some("sh"));
+    assert_eq!(name("/usr/bin/perl"), None);
+    assert_eq!(name("/usr/bin/pythonista"), None);
+}
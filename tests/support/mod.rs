@@ -0,0 +1,112 @@
+// Compiling the C programs of tests/fixtures for the end-to-end tests,
+// once per test binary, with $CC or else cc, and the files and tests the
+// tests judge on, in a scratch directory of each test binary's own.
+// Not every test binary uses every helper.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use secure_judger::problem::TestCase;
+
+// Fixtures compiled so far, None for the ones that can't be
+static COMPILED: Mutex<Option<HashMap<String, Option<PathBuf>>>> = Mutex::new(None);
+
+/*
+ *  The fixture `name`.c compiled, or None after saying why the test is
+ *  skipped: the sandbox only exists on Linux, and the fixtures need a C
+ *  compiler. A fixture that doesn't compile fails the test instead.
+ */
+pub fn fixture(name: &str) -> Option<PathBuf> {
+    if !cfg!(target_os = "linux") {
+        eprintln!("skipping: the sandbox needs Linux");
+        return None;
+    }
+    let mut compiled = COMPILED.lock().unwrap_or_else(|e| e.into_inner());
+    let compiled = compiled.get_or_insert_with(HashMap::new);
+    if let Some(exec) = compiled.get(name) {
+        if exec.is_none() {
+            eprintln!("skipping: no C compiler");
+        }
+        return exec.clone();
+    }
+    let exec = compile(name);
+    compiled.insert(name.to_string(), exec.clone());
+    exec
+}
+
+/*
+ *  A file in the test binary's scratch directory with `contents`, such
+ *  as an answer
+ */
+pub fn write_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = scratch().join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/*
+ *  An empty directory `name` in the test binary's scratch directory, not
+ *  writable by others, emptied if it was there already
+ */
+pub fn dir(name: &str) -> PathBuf {
+    let dir = scratch().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+    dir
+}
+
+/*
+ *  Tests 1 to `count` in `dir`, each with an empty input answered by "3"
+ */
+pub fn cases(dir: &Path, count: usize) -> Vec<TestCase> {
+    (1..=count).map(|n| {
+        let input = dir.join(format!("{n}.in"));
+        let answer = dir.join(format!("{n}.ans"));
+        fs::write(&input, "").unwrap();
+        fs::write(&answer, "3\n").unwrap();
+        TestCase::new(input, answer)
+    }).collect()
+}
+
+/*
+ *  A program at `path` for the mock sandbox, which never runs it: a
+ *  script, small so as to be hashed quickly for every test's key
+ */
+pub fn mock_program(path: &Path) -> PathBuf {
+    fs::write(path, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_path_buf()
+}
+
+fn scratch() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("e2e-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn compile(name: &str) -> Option<PathBuf> {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(format!("{name}.c"));
+    let exec = scratch().join(name);
+    let cc = env::var_os("CC").unwrap_or_else(|| "cc".into());
+    let output = match Command::new(&cc).arg("-O2").arg("-o").arg(&exec).arg(&source).output() {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("skipping: no C compiler ({} failed: {e}), set CC to use another", cc.to_string_lossy());
+            return None;
+        }
+    };
+    assert!(
+        output.status.success(),
+        "compiling {} failed:\n{}",
+        source.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Some(exec)
+}
@@ -0,0 +1,99 @@
+// Transcripts of runs for appeals: the steps of a run out of time, with
+// the signal that stopped it and when, and the answers left out unless
+// they are asked for. Judged on the mock sandbox.
+
+mod support;
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use secure_judger::json::{self, Value};
+use secure_judger::judger::{JudgeSession, JudgeStatus};
+use secure_judger::sandbox::{MockRun, MockSandbox};
+use secure_judger::transcript::Transcript;
+
+/*
+ *  Judge one run of `run` on an input answered by "3", with a transcript
+ *  in `dir`, and read the transcript's lines back
+ */
+fn transcribe(dir: &Path, run: MockRun, include_answers: bool) -> (JudgeStatus, Vec<Value>) {
+    let exec = support::mock_program(&dir.join("program"));
+    let case = support::cases(dir, 1).remove(0);
+    let path = dir.join("transcript.jsonl");
+    let transcript = Transcript::create(&path, include_answers).unwrap();
+    let session = JudgeSession::builder(exec)
+        .input(secure_judger::sandbox::InputSource::File(case.input))
+        .answer(case.answer)
+        .time_limit(Duration::from_millis(300))
+        .copy_exec(false)
+        .sandbox(Arc::new(MockSandbox::new(vec![run])))
+        .observer(Arc::new(transcript))
+        .build()
+        .unwrap();
+    let result = session.run_judge(&[]).unwrap();
+    let lines = fs::read_to_string(&path).unwrap().lines().map(|line| json::parse(line).unwrap()).collect();
+    (result.status, lines)
+}
+
+fn member<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(members) => members.iter().find(|(key, _)| key == name).map(|(_, value)| value),
+        _ => None
+    }
+}
+
+fn events(lines: &[Value]) -> Vec<&str> {
+    lines.iter().filter_map(|line| match member(line, "event") {
+        Some(Value::String(event)) => Some(event.as_str()),
+        _ => None
+    }).collect()
+}
+
+fn find<'a>(lines: &'a [Value], event: &str) -> &'a Value {
+    lines.iter().find(|line| member(line, "event") == Some(&Value::String(event.to_string()))).unwrap()
+}
+
+#[test]
+fn time_limit_kill_is_recorded() {
+    let dir = support::dir("tle");
+    let (status, lines) = transcribe(&dir, MockRun::hang().cpu_time(Duration::from_secs(2)), false);
+    assert!(matches!(status, JudgeStatus::TimeLimitExceeded), "{status}");
+    assert_eq!(events(&lines), ["begin", "run_start", "started", "signalled", "exited", "result"]);
+
+    let signalled = find(&lines, "signalled");
+    assert_eq!(member(signalled, "reason"), Some(&Value::String(String::from("cpu-time"))));
+    // Asked to exit first, as the termination policy has it
+    assert_eq!(member(signalled, "signal"), Some(&Value::Number(f64::from(libc::SIGTERM))));
+    assert_eq!(member(signalled, "test"), Some(&Value::String(String::from("1"))));
+    // When the judger decided on it, as a time and into the run
+    let (Some(Value::Number(at)), Some(Value::Number(started_at))) = (member(signalled, "at_ms"), member(find(&lines, "started"), "at_ms")) else {
+        panic!("no times");
+    };
+    assert!(at >= started_at);
+    assert!(matches!(member(signalled, "run_ms"), Some(Value::Number(ms)) if *ms > 0.0));
+
+    let exited = find(&lines, "exited");
+    assert_eq!(member(exited, "signal"), Some(&Value::Number(f64::from(libc::SIGTERM))));
+    assert!(member(exited, "rusage").and_then(|rusage| member(rusage, "user_ms")).is_some());
+    let result = member(find(&lines, "result"), "result").unwrap();
+    assert_eq!(member(result, "status"), Some(&Value::String(String::from("TLE"))));
+}
+
+#[test]
+fn answers_are_left_out_unless_asked_for() {
+    let dir = support::dir("answers");
+    let (status, lines) = transcribe(&dir, MockRun::exit(0).stdout("4\n"), false);
+    assert!(matches!(status, JudgeStatus::WrongAnswer), "{status}");
+    assert_eq!(events(&lines), ["begin", "run_start", "started", "exited", "compared", "result"]);
+    assert!(member(find(&lines, "run_start"), "answer_sha256").is_some_and(|digest| matches!(digest, Value::String(_))));
+    assert_eq!(member(find(&lines, "run_start"), "answer_contents"), None);
+    assert_eq!(member(find(&lines, "compared"), "output_contents"), None);
+    assert_eq!(member(find(&lines, "compared"), "status"), Some(&Value::String(String::from("WA"))));
+
+    let (_, lines) = transcribe(&dir, MockRun::exit(0).stdout("4\n"), true);
+    assert_eq!(member(find(&lines, "begin"), "answers_included"), Some(&Value::Boolean(true)));
+    assert_eq!(member(find(&lines, "run_start"), "answer_contents"), Some(&Value::String(String::from("3\n"))));
+    assert_eq!(member(find(&lines, "compared"), "output_contents"), Some(&Value::String(String::from("4\n"))));
+}
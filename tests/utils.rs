@@ -0,0 +1,418 @@
+// Looking up programs in PATH, with directories of decoys named like the
+// program ahead of the real one, reading the sizes and times limits are
+// given in, reading the #! lines of scripts, and checking the space
+// runs get their scratch directories in, and the CPUs of the host,
+// making file names of names users had a say in, and replacing files from
+// several threads at once.
+
+mod support;
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use secure_judger::judger::JudgeSession;
+use secure_judger::utils::{self, host, ParseError, PathLookupError, Shebang};
+
+fn file(path: &Path, mode: u32) {
+    fs::write(path, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+}
+
+fn search(dirs: &[&Path]) -> OsString {
+    env::join_paths(dirs).unwrap()
+}
+
+#[test]
+fn decoys_are_passed_over() {
+    let (subdir, plain, real) = (support::dir("subdir"), support::dir("plain"), support::dir("real"));
+    fs::create_dir(subdir.join("prog")).unwrap();
+    file(&plain.join("prog"), 0o644);
+    file(&real.join("prog"), 0o755);
+    let found = utils::find_in("prog", &search(&[&subdir, &plain, &real])).unwrap();
+    assert_eq!(found, real.join("prog"));
+}
+
+#[test]
+fn symlinks_to_programs_count() {
+    let (links, real) = (support::dir("links"), support::dir("link-target"));
+    file(&real.join("prog"), 0o700);
+    std::os::unix::fs::symlink(real.join("prog"), links.join("prog")).unwrap();
+    std::os::unix::fs::symlink(real.join("missing"), links.join("dangling")).unwrap();
+    assert_eq!(utils::find_in("prog", &search(&[&links])).unwrap(), links.join("prog"));
+    assert!(utils::find_in("dangling", &search(&[&links])).is_err());
+}
+
+#[test]
+fn missing_and_locked_directories() {
+    let (locked, real) = (support::dir("locked"), support::dir("after-locked"));
+    file(&locked.join("prog"), 0o755);
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    file(&real.join("prog"), 0o755);
+    let missing = real.join("no-such-dir");
+    let found = utils::find_in("prog", &search(&[&missing, &locked, &real])).unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    // Root looks in the locked directory regardless
+    assert!(found == real.join("prog") || found == locked.join("prog"), "{}", found.display());
+}
+
+#[test]
+fn missing_programs() {
+    let (empty, plain) = (support::dir("empty"), support::dir("only-plain"));
+    file(&plain.join("prog"), 0o644);
+    match utils::find_in("prog", &search(&[&empty, &plain])) {
+        Err(PathLookupError::NotFound { name, searched }) => {
+            assert_eq!(name, "prog");
+            assert_eq!(searched, vec![empty.clone(), plain.clone()]);
+        },
+        other => panic!("{other:?}")
+    }
+    let error = utils::find_in("prog", &search(&[&empty])).unwrap_err().to_string();
+    assert_eq!(error, format!("executable 'prog' not found in PATH, searched {}", empty.display()));
+    let error = utils::find_in("prog", &OsString::new()).unwrap_err().to_string();
+    assert_eq!(error, "executable 'prog' not found, PATH is empty");
+}
+
+#[test]
+fn paths_are_not_looked_up() {
+    let (dir, elsewhere) = (support::dir("paths"), support::dir("paths-elsewhere"));
+    file(&dir.join("prog"), 0o755);
+    file(&elsewhere.join("prog"), 0o755);
+    // Absolute, whatever PATH has
+    let absolute = dir.join("prog");
+    assert_eq!(utils::find_in(&absolute.to_string_lossy(), &search(&[&elsewhere])).unwrap(), absolute);
+    match utils::find_in("./no-such-prog", &search(&[&elsewhere])) {
+        Err(PathLookupError::Missing(path)) => assert_eq!(path, PathBuf::from("./no-such-prog")),
+        other => panic!("{other:?}")
+    }
+    let missing = dir.join("missing");
+    assert_eq!(
+        utils::find_in(&missing.to_string_lossy(), &search(&[&dir])).unwrap_err().to_string(),
+        format!("executable '{}' does not exist", missing.display())
+    );
+}
+
+#[test]
+fn relative_to_the_current_directory() {
+    let (cwd, real) = (support::dir("cwd"), support::dir("after-cwd"));
+    file(&cwd.join("prog"), 0o755);
+    fs::create_dir(cwd.join("sub")).unwrap();
+    file(&cwd.join("sub/tool"), 0o755);
+    file(&real.join("prog"), 0o755);
+    let paths = OsString::from(format!(":{}", real.display()));
+    // Only this test depends on the working directory, and no other
+    // changes it
+    env::set_current_dir(&cwd).unwrap();
+    assert_eq!(utils::find_in("prog", &paths).unwrap(), Path::new(".").join("prog"));
+    // A relative path with a slash is taken as it is
+    assert_eq!(utils::find_in("sub/tool", &paths).unwrap(), PathBuf::from("sub/tool"));
+    assert!(matches!(utils::find_in("sub/prog", &paths), Err(PathLookupError::Missing(_))));
+    let paths = OsString::from(format!("{}::", real.display()));
+    assert_eq!(utils::find_in("prog", &paths).unwrap(), real.join("prog"));
+    fs::remove_file(cwd.join("prog")).unwrap();
+    assert_eq!(utils::find_in("prog", &OsString::from(format!(":{}", real.display()))).unwrap(), real.join("prog"));
+}
+
+const KIB: u64 = 1 << 10;
+const MIB: u64 = 1 << 20;
+const GIB: u64 = 1 << 30;
+
+#[test]
+fn sizes() {
+    let cases = [
+        ("0", 0),
+        ("1", 1),
+        ("104857600", 100 * MIB),
+        ("18446744073709551615", u64::MAX),
+        ("1b", 1),
+        ("1B", 1),
+        ("1k", KIB),
+        ("1kb", KIB),
+        ("1KiB", KIB),
+        ("256m", 256 * MIB),
+        ("256M", 256 * MIB),
+        ("64MiB", 64 * MIB),
+        ("64mb", 64 * MIB),
+        ("1g", GIB),
+        ("1GB", GIB),
+        ("1gib", GIB),
+        ("1.5g", GIB + GIB / 2),
+        ("0.5k", 512),
+        ("1.9b", 1),
+        (".5m", MIB / 2),
+        ("64 m", 64 * MIB),
+        (" 64m ", 64 * MIB),
+        ("+2k", 2 * KIB),
+        ("17179869183g", 17179869183 * GIB)
+    ];
+    for (text, bytes) in cases {
+        assert_eq!(utils::parse_size(text), Ok(bytes), "{text}");
+    }
+}
+
+#[test]
+fn malformed_sizes() {
+    let not_a_number = |text: &str| ParseError::NotANumber(text.to_string());
+    let unknown = |text: &str, unit: &str| ParseError::UnknownUnit {
+        value: text.to_string(),
+        unit: unit.to_string(),
+        expected: "b, k, m or g"
+    };
+    let cases = [
+        ("", ParseError::Empty),
+        ("   ", ParseError::Empty),
+        ("m", not_a_number("m")),
+        ("abc", not_a_number("abc")),
+        ("1.2.3m", not_a_number("1.2.3m")),
+        (".", not_a_number(".")),
+        ("--5", not_a_number("--5")),
+        ("inf", not_a_number("inf")),
+        ("nan", not_a_number("nan")),
+        ("5t", unknown("5t", "t")),
+        ("5kk", unknown("5kk", "kk")),
+        ("1e3", unknown("1e3", "e3")),
+        ("5mi", unknown("5mi", "mi")),
+        ("-1", ParseError::Negative(String::from("-1"))),
+        ("-5k", ParseError::Negative(String::from("-5k"))),
+        ("-0", ParseError::Negative(String::from("-0"))),
+        ("18446744073709551616", ParseError::TooLarge(String::from("18446744073709551616"))),
+        ("17179869184g", ParseError::TooLarge(String::from("17179869184g"))),
+        ("99999999999999999999999k", ParseError::TooLarge(String::from("99999999999999999999999k")))
+    ];
+    for (text, error) in cases {
+        assert_eq!(utils::parse_size(text), Err(error), "{text:?}");
+    }
+}
+
+#[test]
+fn durations() {
+    let cases = [
+        ("0", Duration::ZERO),
+        ("2", Duration::from_secs(2)),
+        ("2s", Duration::from_secs(2)),
+        ("2S", Duration::from_secs(2)),
+        ("1.5s", Duration::from_millis(1500)),
+        ("500ms", Duration::from_millis(500)),
+        ("500MS", Duration::from_millis(500)),
+        ("0.5ms", Duration::from_micros(500)),
+        ("2m", Duration::from_secs(120)),
+        ("1.5m", Duration::from_secs(90)),
+        ("250 ms", Duration::from_millis(250)),
+        (" 3s ", Duration::from_secs(3)),
+        (".25s", Duration::from_millis(250))
+    ];
+    for (text, time) in cases {
+        assert_eq!(utils::parse_duration(text), Ok(time), "{text}");
+    }
+}
+
+#[test]
+fn malformed_durations() {
+    let unknown = |text: &str, unit: &str| ParseError::UnknownUnit {
+        value: text.to_string(),
+        unit: unit.to_string(),
+        expected: "ms, s or m"
+    };
+    let cases = [
+        ("", ParseError::Empty),
+        ("s", ParseError::NotANumber(String::from("s"))),
+        ("fast", ParseError::NotANumber(String::from("fast"))),
+        ("1..5s", ParseError::NotANumber(String::from("1..5s"))),
+        ("5h", unknown("5h", "h")),
+        ("5us", unknown("5us", "us")),
+        ("5sec", unknown("5sec", "sec")),
+        ("5msec", unknown("5msec", "msec")),
+        ("-1s", ParseError::Negative(String::from("-1s"))),
+        ("-500ms", ParseError::Negative(String::from("-500ms"))),
+        ("1e30", unknown("1e30", "e30")),
+        ("99999999999999999999999", ParseError::TooLarge(String::from("99999999999999999999999"))),
+        ("999999999999999999999m", ParseError::TooLarge(String::from("999999999999999999999m")))
+    ];
+    for (text, error) in cases {
+        assert_eq!(utils::parse_duration(text), Err(error), "{text:?}");
+    }
+}
+
+#[test]
+fn parse_errors_name_the_value() {
+    assert_eq!(utils::parse_size("5t").unwrap_err().to_string(), "'5t' has an unknown unit 't', expected b, k, m or g");
+    assert_eq!(utils::parse_size("-5k").unwrap_err().to_string(), "'-5k' is negative");
+    assert_eq!(utils::parse_size("lots").unwrap_err().to_string(), "'lots' is not a number");
+    assert_eq!(utils::parse_duration("1e99").unwrap_err().to_string(), "'1e99' has an unknown unit 'e99', expected ms, s or m");
+    assert_eq!(utils::parse_duration("99999999999999999999999").unwrap_err().to_string(), "'99999999999999999999999' is too large");
+}
+
+#[test]
+fn shebang_lines() {
+    let shebang = |interpreter: &str, arg: Option<&str>| Some(Shebang { interpreter: PathBuf::from(interpreter), arg: arg.map(String::from) });
+    let cases: [(&[u8], Option<Shebang>); 11] = [
+        (b"#!/bin/sh\necho", shebang("/bin/sh", None)),
+        (b"#!/bin/sh", shebang("/bin/sh", None)),
+        (b"#! /usr/bin/python3 \n", shebang("/usr/bin/python3", None)),
+        (b"#!/usr/bin/env python3\n", shebang("/usr/bin/env", Some("python3"))),
+        // The rest of the line is one argument
+        (b"#!/usr/bin/env  python3 -u\t\n", shebang("/usr/bin/env", Some("python3 -u"))),
+        (b"#!\t/bin/bash\t-e\n", shebang("/bin/bash", Some("-e"))),
+        (b"#!/bin/sh\r\n", shebang("/bin/sh\r", None)),
+        (b"#!\n/bin/sh\n", None),
+        (b"#!   \n", None),
+        (b"\x7fELF\x02\x01\x01", None),
+        (b"", None)
+    ];
+    for (header, expected) in cases {
+        assert_eq!(utils::parse_shebang(header), expected, "{:?}", String::from_utf8_lossy(header));
+    }
+    // Only so much of the line is read
+    let mut long = b"#!/bin/".to_vec();
+    long.extend([b's'; 300]);
+    let interpreter = utils::parse_shebang(&long).unwrap().interpreter;
+    assert_eq!(interpreter.as_os_str().len(), 254);
+}
+
+#[test]
+fn shebang_of_files() {
+    let dir = support::dir("shebang");
+    fs::write(dir.join("script"), "#!/bin/sh -e\nexit 0\n").unwrap();
+    fs::write(dir.join("plain"), "exit 0\n").unwrap();
+    assert_eq!(utils::read_shebang(&dir.join("script")).unwrap(), Some(Shebang { interpreter: PathBuf::from("/bin/sh"), arg: Some(String::from("-e")) }));
+    assert_eq!(utils::read_shebang(&dir.join("plain")).unwrap(), None);
+    assert!(utils::read_shebang(&dir.join("missing")).is_err());
+}
+
+#[test]
+fn scratch_space() {
+    let dir = support::dir("scratch");
+    utils::check_scratch(&dir, 1, true).unwrap();
+    fs::write(dir.join("file"), "").unwrap();
+    let error = utils::check_scratch(&dir.join("file"), 1, true).unwrap_err();
+    assert_eq!(error.to_string(), format!("scratch directory {}: not a directory", dir.join("file").display()));
+    assert!(utils::check_scratch(&dir.join("missing"), 1, true).is_err());
+    let error = utils::check_scratch(&dir, u64::MAX, true).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::StorageFull);
+    let message = error.to_string();
+    assert!(message.starts_with(&format!("scratch directory {}: only ", dir.display())), "{message}");
+    assert!(message.ends_with(&format!(" short of the {} a run needs", utils::format_memory(u64::MAX))), "{message}");
+}
+
+/*
+ *  A mount point of the system with `option`, such as ro
+ */
+fn mounted_with(option: &str) -> Option<PathBuf> {
+    let mounts = fs::read_to_string("/proc/self/mounts").ok()?;
+    mounts.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(' ').collect();
+        let options = fields.get(3)?;
+        match options.split(',').any(|o| o == option) && Path::new(fields[1]).is_dir() {
+            true => Some(PathBuf::from(fields[1])),
+            false => None
+        }
+    })
+}
+
+#[test]
+fn scratch_mount_options() {
+    match mounted_with("ro") {
+        Some(dir) => {
+            let error = utils::check_scratch(&dir, 0, false).unwrap_err();
+            assert_eq!(error.to_string(), format!("scratch directory {}: on a read-only filesystem", dir.display()));
+        },
+        None => eprintln!("skipping read-only: no such mount")
+    }
+    match mounted_with("noexec").filter(|dir| mounted_with("ro").as_ref() != Some(dir)) {
+        Some(dir) => {
+            let error = utils::check_scratch(&dir, 0, true).unwrap_err();
+            assert!(error.to_string().ends_with("on a filesystem mounted noexec, the copies of executables can't run from it"), "{error}");
+        },
+        None => eprintln!("skipping noexec: no such mount")
+    }
+}
+
+#[test]
+fn sessions_check_their_scratch_space() {
+    let dir = support::dir("session-scratch");
+    fs::write(dir.join("file"), "").unwrap();
+    let exec = env::current_exe().unwrap();
+    let build = |builder: secure_judger::judger::JudgeSessionBuilder| match builder.build() {
+        Ok(_) => panic!("the session was built"),
+        Err(e) => e.to_string()
+    };
+    let error = build(JudgeSession::builder(exec.clone()).scratch_dir(dir.join("file")));
+    assert_eq!(error, format!("scratch directory {}: not a directory", dir.join("file").display()));
+    // Room for the output, up to its limit
+    let error = build(JudgeSession::builder(exec.clone()).scratch_dir(dir.clone()).output_limit(u64::MAX / 2));
+    assert!(error.starts_with(&format!("scratch directory {}: only ", dir.display())), "{error}");
+    assert!(JudgeSession::builder(exec).scratch_dir(dir).output_limit(1 << 20).build().is_ok());
+}
+
+#[test]
+fn cpu_model_of_cpuinfo() {
+    let x86 = "processor\t: 0\nvendor_id\t: GenuineIntel\nmodel name\t: Intel(R) Core(TM) i7-8650U CPU @ 1.90GHz\nflags\t\t: fpu\n";
+    assert_eq!(host::cpu_model_in(x86).as_deref(), Some("Intel(R) Core(TM) i7-8650U CPU @ 1.90GHz"));
+    // ARM lists the cores by number, and the model only at the end if at all
+    let arm = "processor\t: 0\nBogoMIPS\t: 108.00\nCPU part\t: 0xd08\n\nHardware\t: BCM2835\n";
+    assert_eq!(host::cpu_model_in(arm).as_deref(), Some("BCM2835"));
+    assert_eq!(host::cpu_model_in("processor\t: 0\nmodel name\t:\n"), None);
+    assert_eq!(host::cpu_model_in(""), None);
+}
+
+#[test]
+fn cpu_lists() {
+    assert_eq!(host::parse_cpu_list("0-3,8\n"), Some(vec![0, 1, 2, 3, 8]));
+    assert_eq!(host::parse_cpu_list("5"), Some(vec![5]));
+    // Overlapping ranges give each CPU once
+    assert_eq!(host::parse_cpu_list("2-4,3,1-2"), Some(vec![2, 3, 4, 1]));
+    for list in ["", "3-1", "0,", "a", "1-", "-2"] {
+        assert_eq!(host::parse_cpu_list(list), None, "{list:?}");
+    }
+}
+
+#[test]
+fn hyperthreads_are_left_out() {
+    // Four cores of two hyperthreads each, as cpu N and N + 4
+    let root = support::dir("topology");
+    for cpu in 0..8 {
+        let topology = root.join(format!("cpu{cpu}/topology"));
+        fs::create_dir_all(&topology).unwrap();
+        fs::write(topology.join("thread_siblings_list"), format!("{},{}\n", cpu % 4, cpu % 4 + 4)).unwrap();
+    }
+    assert_eq!(host::physical_cores_in(&root, &(0..8).collect::<Vec<_>>()), [0, 1, 2, 3]);
+    assert_eq!(host::physical_cores_in(&root, &[5, 1, 6, 2, 3]), [5, 6, 3]);
+    // CPUs missing from the topology are cores of their own
+    assert_eq!(host::physical_cores_in(&root, &[0, 4, 9, 10]), [0, 9, 10]);
+}
+
+#[test]
+fn hostile_names_are_made_file_names() {
+    assert_eq!(utils::sanitize_file_name("1.in").as_deref(), Some("1.in"));
+    assert_eq!(utils::sanitize_file_name("../../etc/cron.d/x.out").as_deref(), Some(".._.._etc_cron.d_x.out"));
+    assert_eq!(utils::sanitize_file_name("/etc/passwd").as_deref(), Some("_etc_passwd"));
+    assert_eq!(utils::sanitize_file_name("a\0b").as_deref(), Some("a_b"));
+    for name in ["", ".", ".."] {
+        assert_eq!(utils::sanitize_file_name(name), None, "{name:?}");
+    }
+}
+
+#[test]
+fn files_are_replaced_from_threads_at_once() {
+    let dir = support::dir("replaced");
+    let path = dir.join("entry.json");
+    thread::scope(|scope| {
+        for writer in 0..8 {
+            let path = &path;
+            scope.spawn(move || {
+                for _ in 0..50 {
+                    utils::replace_file(path, format!("{writer}\n").repeat(1000).as_bytes()).unwrap();
+                }
+            });
+        }
+    });
+    // Whole, and nothing left beside it
+    let contents = fs::read_to_string(&path).unwrap();
+    assert_eq!(contents, contents.lines().next().map(|line| format!("{line}\n")).unwrap().repeat(1000));
+    let names: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(names, ["entry.json"]);
+}
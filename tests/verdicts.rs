@@ -0,0 +1,296 @@
+// How a session turns what a run did into a verdict, judged on the mock
+// sandbox so that nothing gets started: the order the limits are checked
+// in, how crashes are told apart and how the resource usage is reported.
+
+mod support;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use secure_judger::cache::AnswerCache;
+use secure_judger::compare::Comparison;
+use secure_judger::judger::{IoMode, JudgeResult, JudgeSession, JudgeSessionBuilder, JudgeStatus, RetryPolicy, RuntimeErrorKind};
+use secure_judger::sandbox::{MockRun, MockSandbox, SandboxPolicy};
+
+const MIB: u64 = 1 << 20;
+
+/*
+ *  A session judging against the answer "3", with a 1s CPU limit and
+ *  64MiB of memory, on the runs of `sandbox`. The program is the test
+ *  binary itself, which only has to pass for an executable, run in place
+ *  so as not to copy and hash it for every run.
+ */
+fn session(name: &str, sandbox: Arc<MockSandbox>) -> JudgeSessionBuilder {
+    let answer = support::write_file(&format!("{name}.ans"), b"3\n");
+    JudgeSession::builder(std::env::current_exe().unwrap())
+        .answer(answer)
+        .time_limit(Duration::from_secs(1))
+        .memory_limit(64 * MIB)
+        .copy_exec(false)
+        .sandbox(sandbox)
+}
+
+fn judge(name: &str, run: MockRun) -> JudgeResult {
+    session(name, Arc::new(MockSandbox::new(vec![run]))).build().unwrap().run_judge(&[]).unwrap()
+}
+
+#[test]
+fn accepted_reports_usage() {
+    let result = judge("accepted", MockRun::exit(0).stdout("3\n").cpu_time(Duration::from_millis(250)).memory(5 * MIB));
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert_eq!(result.cpu_time_ms, 250);
+    assert_eq!(result.memory_used_bytes, 5 * MIB);
+    assert!(!result.sandbox.unwrap().seccomp);
+}
+
+#[test]
+fn wrong_answer() {
+    let result = judge("wrong-answer", MockRun::exit(0).stdout("4\n"));
+    assert!(matches!(result.status, JudgeStatus::WrongAnswer), "{}", result.status);
+}
+
+#[test]
+fn stderr_is_kept() {
+    let result = judge("stderr", MockRun::exit(0).stdout("3\n").stderr("debug output"));
+    assert_eq!(result.stderr, b"debug output");
+}
+
+#[test]
+fn nonzero_exit() {
+    let result = judge("nonzero", MockRun::exit(3).stdout("3\n"));
+    match result.status {
+        JudgeStatus::ReturnNonZero(status) => assert_eq!(libc::WEXITSTATUS(status), 3),
+        status => panic!("{status}")
+    }
+}
+
+#[test]
+fn crashes_by_signal() {
+    let result = judge("segv", MockRun::signal(libc::SIGSEGV));
+    assert!(matches!(result.status, JudgeStatus::RuntimeError(RuntimeErrorKind::SegmentationFault)), "{}", result.status);
+    let result = judge("fpe", MockRun::signal(libc::SIGFPE));
+    assert!(matches!(result.status, JudgeStatus::RuntimeError(RuntimeErrorKind::FloatingPointError)), "{}", result.status);
+    // Other signals aren't told apart
+    let result = judge("abort", MockRun::signal(libc::SIGABRT));
+    assert!(matches!(result.status, JudgeStatus::ReturnNonZero(libc::SIGABRT)), "{}", result.status);
+}
+
+#[test]
+fn memory_limit_comes_before_time_limit() {
+    let run = MockRun::signal(libc::SIGSEGV).cpu_time(Duration::from_secs(2)).memory(100 * MIB);
+    let result = judge("mle-tle", run);
+    assert!(matches!(result.status, JudgeStatus::MemoryLimitExceeded), "{}", result.status);
+}
+
+#[test]
+fn time_limit_comes_before_crash() {
+    let result = judge("tle-re", MockRun::signal(libc::SIGSEGV).cpu_time(Duration::from_secs(2)));
+    assert!(matches!(result.status, JudgeStatus::TimeLimitExceeded), "{}", result.status);
+}
+
+#[test]
+fn crash_comes_before_answer() {
+    let result = judge("re-ac", MockRun::signal(libc::SIGSEGV).stdout("3\n"));
+    assert!(matches!(result.status, JudgeStatus::RuntimeError(_)), "{}", result.status);
+}
+
+#[test]
+fn output_limit() {
+    let sandbox = Arc::new(MockSandbox::new(vec![MockRun::exit(0).stdout("3\n".repeat(10))]));
+    let result = session("output-limit", sandbox).output_limit(8).build().unwrap().run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::OutputLimitExceeded), "{}", result.status);
+}
+
+#[test]
+fn io_limit_counts_reads_and_writes() {
+    let run = MockRun::exit(0).stdout("3\n").disk_io(3 * MIB, 2 * MIB);
+    let result = session("io-limit", Arc::new(MockSandbox::new(vec![run.clone()]))).io_limit(4 * MIB).build().unwrap().run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::OutputLimitExceeded), "{}", result.status);
+    assert!(result.io_limit_exceeded);
+    assert_eq!((result.io_read_bytes, result.io_written_bytes), (3 * MIB, 2 * MIB));
+    let result = session("io-within", Arc::new(MockSandbox::new(vec![run]))).io_limit(8 * MIB).build().unwrap().run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert!(!result.io_limit_exceeded);
+}
+
+#[test]
+fn stderr_past_its_limit_is_counted() {
+    let run = MockRun::exit(0).stdout("3\n").stderr(vec![b'e'; 5000]);
+    let result = session("stderr-limit", Arc::new(MockSandbox::new(vec![run]))).stderr_limit(4096).build().unwrap().run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert_eq!(result.stderr_bytes, 5000);
+    assert!(result.stderr_truncated);
+    assert_eq!(result.stderr.len(), 4096);
+}
+
+#[test]
+fn wall_limit_while_idle() {
+    // Too short a limit for sampling, the wall limit ends the run
+    let sandbox = Arc::new(MockSandbox::new(vec![MockRun::hang()]));
+    let session = session("idle", sandbox)
+        .time_limit(Duration::from_millis(100))
+        .wall_time_limit(Duration::from_millis(150))
+        .build()
+        .unwrap();
+    let result = session.run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::IdlenessLimitExceeded), "{}", result.status);
+    assert_eq!(result.time_used, Duration::from_millis(150));
+}
+
+#[test]
+fn wall_limit_while_busy() {
+    // Under the CPU limit, but running most of the wall time
+    let sandbox = Arc::new(MockSandbox::new(vec![MockRun::hang().cpu_time(Duration::from_millis(90))]));
+    let session = session("busy", sandbox)
+        .time_limit(Duration::from_millis(100))
+        .wall_time_limit(Duration::from_millis(150))
+        .build()
+        .unwrap();
+    let result = session.run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::TimeLimitExceeded), "{}", result.status);
+    // Using most of its CPU time, so it was the program's doing
+    assert!(!result.timing_suspect);
+}
+
+#[test]
+fn wall_limit_with_the_judger_on_time() {
+    // Short of CPU time, but with nothing to say the host held it back
+    let run = MockRun::hang().cpu_time(Duration::from_millis(200));
+    let session = session("on-time", Arc::new(MockSandbox::new(vec![run])))
+        .time_limit(Duration::from_secs(1))
+        .wall_time_limit(Duration::from_millis(300))
+        .build()
+        .unwrap();
+    let result = session.run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::TimeLimitExceeded), "{}", result.status);
+    assert!(!result.timing_suspect, "the judger woke up {:?} late", result.scheduling_latency);
+}
+
+#[test]
+fn wall_limit_on_a_busy_host() {
+    // Runnable all along, but only getting a CPU for 200ms of 300ms
+    let run = MockRun::hang().cpu_time(Duration::from_millis(200)).late_wakeups(Duration::from_millis(15));
+    let session = session("descheduled", Arc::new(MockSandbox::new(vec![run])))
+        .time_limit(Duration::from_secs(1))
+        .wall_time_limit(Duration::from_millis(300))
+        .build()
+        .unwrap();
+    let result = session.run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::TimeLimitExceeded), "{}", result.status);
+    assert!(result.timing_suspect);
+    assert!(result.scheduling_latency >= Duration::from_millis(15), "{:?}", result.scheduling_latency);
+    assert!(result.to_json().contains(",\"timing_suspect\":true,\"scheduling_latency_ms\":"));
+}
+
+#[test]
+fn memory_sampled_while_running() {
+    let result = judge("sampled-memory", MockRun::hang().memory(100 * MIB));
+    assert!(matches!(result.status, JudgeStatus::MemoryLimitExceeded), "{}", result.status);
+    assert!(result.time_used < Duration::from_secs(1));
+}
+
+#[test]
+fn threads_sampled_while_running() {
+    let run = MockRun::exit(0).stdout("3\n").wall_time(Duration::from_millis(200)).threads(8);
+    let sandbox = Arc::new(MockSandbox::repeat(run));
+    let session = session("threads", sandbox).policy(SandboxPolicy::threads_allowed()).build().unwrap();
+    assert_eq!(session.run_judge(&[]).unwrap().max_threads, Some(8));
+    let run = MockRun::exit(0).stdout("3\n").wall_time(Duration::from_millis(200)).threads(8);
+    assert_eq!(judge("no-threads", run).max_threads, None);
+}
+
+#[test]
+fn cpu_sampled_while_running() {
+    let result = judge("sampled-cpu", MockRun::hang().cpu_time(Duration::from_secs(2)));
+    assert!(matches!(result.status, JudgeStatus::TimeLimitExceeded), "{}", result.status);
+    assert!(result.time_used < Duration::from_secs(1));
+}
+
+#[test]
+fn wrong_answer_says_where() {
+    let result = judge("where", MockRun::exit(0).stdout("4\n"));
+    assert!(matches!(result.status, JudgeStatus::WrongAnswer), "{}", result.status);
+    assert_eq!(result.difference.as_deref(), Some("line 1: expected \"3\", got \"4\""));
+    let result = judge("short", MockRun::exit(0).stdout(""));
+    assert_eq!(result.difference.as_deref(), Some("line 1: expected \"3\", got the end of the output"));
+    let result = judge("long-line", MockRun::exit(0).stdout("7".repeat(100)));
+    assert_eq!(result.difference.as_deref(), Some(format!("line 1: expected \"3\", got \"{}…\"", "7".repeat(39)).as_str()));
+    assert!(judge("right", MockRun::exit(0).stdout("3\n")).difference.is_none());
+}
+
+#[test]
+fn tokens_differ_by_token() {
+    let sandbox = Arc::new(MockSandbox::new(vec![MockRun::exit(0).stdout("  3 4\n")]));
+    let result = session("tokens", sandbox).comparison(Comparison::Tokens).build().unwrap().run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::WrongAnswer), "{}", result.status);
+    assert_eq!(result.difference.as_deref(), Some("token 2: expected the end of the output, got \"4\""));
+}
+
+#[test]
+fn stopped_is_idle() {
+    let result = judge("stopped", MockRun::stop());
+    assert!(matches!(result.status, JudgeStatus::IdlenessLimitExceeded), "{}", result.status);
+}
+
+#[test]
+fn output_file_missing() {
+    let sandbox = Arc::new(MockSandbox::new(vec![MockRun::exit(0).stdout("3\n").no_output_file()]));
+    let io_mode = IoMode::NamedFiles { input_name: String::from("a.in"), output_name: String::from("a.out") };
+    let result = session("no-output-file", sandbox).io_mode(io_mode).build().unwrap().run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::OutputMissing), "{}", result.status);
+}
+
+#[test]
+fn transient_launch_failure_is_retried() {
+    let sandbox = Arc::new(MockSandbox::new(vec![MockRun::spawn_error(libc::EAGAIN), MockRun::exit(0).stdout("3\n")]));
+    let retry = RetryPolicy { max_attempts: 2, backoff: Duration::from_millis(1) };
+    let result = session("retried", sandbox.clone()).retry(retry).build().unwrap().run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::Accepted), "{}", result.status);
+    assert_eq!(result.retries, 1);
+    assert_eq!(sandbox.spawned(), 2);
+}
+
+#[test]
+fn lasting_launch_failure_is_an_error() {
+    let sandbox = Arc::new(MockSandbox::new(vec![MockRun::spawn_error(libc::EACCES)]));
+    let session = session("launch-error", sandbox.clone()).build().unwrap();
+    assert!(session.run_judge(&[]).is_err());
+    assert_eq!(sandbox.spawned(), 1);
+}
+
+#[test]
+fn same_run_each_time() {
+    let sandbox = Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("3\n")));
+    let session = session("repeated", sandbox.clone()).build().unwrap();
+    for _ in 0..3 {
+        assert!(session.run_judge(&[]).unwrap().accepted());
+    }
+    assert_eq!(sandbox.spawned(), 3);
+}
+
+#[test]
+fn shared_answers_are_read_once() {
+    let answers = Arc::new(AnswerCache::new());
+    let first = session("shared", Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("3\n"))))
+        .answer_cache(answers.clone())
+        .build()
+        .unwrap();
+    assert!(matches!(first.run_judge(&[]).unwrap().status, JudgeStatus::Accepted));
+    let second = session("shared", Arc::new(MockSandbox::repeat(MockRun::exit(0).stdout("4\n"))))
+        .answer_cache(answers.clone())
+        .build()
+        .unwrap();
+    let result = second.run_judge(&[]).unwrap();
+    assert!(matches!(result.status, JudgeStatus::WrongAnswer), "{}", result.status);
+    assert_eq!(result.difference.as_deref(), Some("line 1: expected \"3\", got \"4\""));
+    assert_eq!(answers.reads(), 1);
+}
+
+#[test]
+fn environment_is_reported() {
+    let result = judge("environment", MockRun::exit(0).stdout("3\n"));
+    let environment = result.environment.unwrap();
+    assert_eq!(environment.version, env!("CARGO_PKG_VERSION"));
+    assert!(result.to_json().contains(&format!("\"environment\":{}", environment.to_json())));
+    assert!(JudgeResult::unfinished(JudgeStatus::Skipped).to_json().contains("\"environment\":null,"));
+}